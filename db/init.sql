@@ -1,34 +1,139 @@
 create
     extension if not exists "uuid-ossp";
+--订单时段排斥约束需要uuid的gist索引
+create
+    extension if not exists "btree_gist";
+--运动类型
+create type sport_type as enum ('badminton', 'basketball', 'tennis', 'table_tennis', 'football', 'volleyball', 'other');
+--性别
+create type gender as enum ('unknown', 'male', 'female');
+--会员等级: 普通/VIP
+create type user_tier as enum ('normal', 'vip');
+--员工的授权范围: 查看订单/前台签到
+create type staff_scope as enum ('orders', 'checkin');
 create table if not exists "users"
 (
     user_id   uuid        not null default uuid_generate_v4() primary key,
     user_name varchar(30) not null unique,
     user_pwd  varchar     not null,
-    phone     varchar(20) not null unique,
+    --密码散列算法版本: 1为PBKDF2, 2为Argon2id, 旧版本在登录成功后重新散列
+    hash_version int2     not null default 1,
+    --微信登录创建的用户未绑定手机号
+    phone     varchar(20) unique,
     --是否为球场管理员
-    is_admin  bool        not null
+    is_admin  bool        not null,
+    --是否为超级管理员, 只能在数据库中设置
+    is_super  bool        not null default false,
+    --员工账号所属的场馆管理员, 不为空时为员工
+    staff_of  uuid references users (user_id),
+    staff_scopes staff_scope[] not null default '{}',
+    --累计未签到次数, 达到上限后清零并限制预约
+    no_show_count integer not null default 0,
+    --限制预约截止时间
+    banned_until  timestamp without time zone,
+    --是否接收预约开始前的提醒
+    notify_reminder bool not null default true,
+    --绑定的微信openid, 用于微信登录与微信支付
+    openid    varchar(64) unique,
+    --微信开放平台unionid, 小程序绑定开放平台后返回
+    unionid   varchar(64) unique,
+    --个人资料
+    nickname  varchar(20),
+    avatar_url varchar(255),
+    gender    gender      not null default 'unknown',
+    --常玩的运动, 用于推荐球场
+    preferred_sports sport_type[] not null default '{}',
+    --会员等级与到期时间, 到期时间为空表示长期有效, 到期后按普通用户处理
+    tier      user_tier   not null default 'normal',
+    tier_until timestamp without time zone,
+    --注销时间, 注销后个人信息已匿名化, 不能再登录
+    deleted_time timestamp without time zone,
+    --封禁时间, 由超级管理员设置, 封禁后不能登录, 已签发的token立即失效
+    disabled_time timestamp without time zone,
+    --密码登录连续失败次数, 达到上限后锁定至locked_until
+    failed_logins int4        not null default 0,
+    locked_until  timestamp without time zone,
+    --管理员二次验证(TOTP), 开启前totp_secret为待确认的密钥
+    totp_secret       varchar(32),
+    totp_enabled      bool        not null default false,
+    --最近一次通过验证的时间步, 防止验证码重放
+    totp_last_step    int8        not null default 0,
+    --备用验证码的SHA-256哈希, 使用后移除
+    totp_backup_codes text[]      not null default '{}'
 );
 
 -----------------------------------------------
+create table if not exists "venues"
+(
+    venue_id     uuid         not null default uuid_generate_v4() primary key,
+    admin_id     uuid         not null references users (user_id),
+    venue_name   varchar(100) not null,
+    address      varchar(300) not null,
+    --场馆公告
+    announcement text         not null default '',
+    --场馆图片url
+    photos       varchar[]    not null default '{}',
+    unique (admin_id, venue_name)
+);
+-----------------------------------------------
+--球场状态: 开放/维护中/关闭
+create type court_status as enum ('open', 'maintenance', 'closed');
 create table if not exists "courts"
 (
     court_id       uuid         not null default uuid_generate_v4() primary key,
     admin_id       uuid         not null references users (user_id),
+    --所属场馆
+    venue_id       uuid references venues (venue_id) on delete set null,
     --球场名称
     court_name     varchar(100) not null,
     --球场标签
     label          varchar(100) not null,
     --球场位置
     location       varchar(300) not null,
-    price_per_hour float8       not null check ( price_per_hour > 0 ),
+    price_per_hour numeric(12, 2) not null check ( price_per_hour > 0 ),
     open_time      time         not null,
     close_time     time         not null,
+    --纬度/经度
+    latitude       float8 check ( latitude between -90 and 90 ),
+    longitude      float8 check ( longitude between -180 and 180 ),
+    status         court_status not null default 'open',
+    sport_type     sport_type   not null default 'other',
+    --可容纳人数
+    capacity       int4 check ( capacity > 0 ),
+    --押金, 订单结束后签到过的退还, 未签到的扣除
+    deposit        numeric(12, 2) not null default 0 check ( deposit >= 0 ),
+    --评价平均分与评价数, 不含已隐藏的评价
+    rating         float8       not null default 0,
+    rating_count   int4         not null default 0,
+    --最后修改时间, 由触发器维护, 用于列表缓存校验
+    update_time    timestamp without time zone not null default now(),
     check (open_time < close_time),
+    check ((latitude is null) = (longitude is null)),
     unique (admin_id, court_name)
 );
 create index on courts (admin_id, court_id);
 -----------------------------------------------
+--每周重复预约, 展开为多个订单
+create table if not exists "order_series"
+(
+    series_id   uuid primary key                  not null default uuid_generate_v4(),
+    user_id     uuid references users (user_id)   not null,
+    court_id    uuid references courts (court_id) not null,
+    --第一次预约的日期, 之后每周同一天
+    first_date  date                              not null,
+    start_time  time                              not null,
+    end_time    time                              not null,
+    weeks       int4                              not null check ( weeks > 0 ),
+    create_time timestamp without time zone       not null default now(),
+    check ( start_time < end_time )
+);
+-----------------------------------------------
+--押金状态: 无押金/冻结中/已退还/已扣除
+create type deposit_status as enum ('none', 'held', 'released', 'forfeited');
+--订单状态: 待支付 -> 已支付 -> 已确认 -> 已完成 / 已取消 / 已退款 / 未到场
+create type order_state as enum ('pending_payment', 'paid', 'confirmed', 'completed', 'cancelled', 'refunded', 'no_show');
+--支付方式: 微信支付/钱包余额/线下收款/次卡全额抵扣
+create type pay_method as enum ('wechat', 'balance', 'offline', 'package');
 create table if not exists "orders"
 (
     order_id    uuid primary key                  not null default uuid_generate_v4(),
@@ -40,9 +145,991 @@ create table if not exists "orders"
     apt_start   timestamp without time zone       not null,
     --订单结束时间
     apt_end     timestamp without time zone       not null,
-    cost        float8                            not null check ( cost > 0 ),
+    --次卡全额抵扣时为0
+    cost        numeric(12, 2)                    not null check ( cost >= 0 ),
+    --扫码签到时间, 未签到为空
+    check_in_time timestamp without time zone,
+    --押金, 不计入cost
+    deposit     numeric(12, 2)                    not null default 0 check ( deposit >= 0 ),
+    deposit_status deposit_status                 not null default 'none',
+    status      order_state                       not null default 'pending_payment',
+    --取消原因与取消费用
+    cancel_reason varchar(200),
+    cancel_fee  numeric(12, 2)                    not null default 0,
+    --所属的每周重复预约
+    series_id   uuid references order_series (series_id) on delete set null,
+    --拼单的付款截止时间, 非拼单为空
+    pay_deadline timestamp without time zone,
+    --管理员代客预约时的顾客信息, user_id为代订的管理员
+    customer_name  varchar(30),
+    customer_phone varchar(20),
+    --现场联系人, 下单时从常用联系人复制
+    contact_name   varchar(30),
+    contact_phone  varchar(20),
+    --是否已发送开始前提醒
+    reminded    bool                              not null default false,
+    --客户端提交的幂等键, 重复提交时返回原订单
+    idempotency_key varchar(64),
+    --用户下单时的备注, 管理员可见
+    remark      varchar(200),
+    --管理员内部备注, 用户不可见
+    internal_note varchar(500),
+    --微信支付订单号与实付金额, 支付成功通知时写入, 原路退款时使用
+    transaction_id varchar(32),
+    pay_amount  numeric(12, 2),
+    --已发起微信支付的金额, 发起后不能再修改订单金额
+    prepay_amount numeric(12, 2),
+    --支付方式, 退款时按原支付方式退回, 拼单为空
+    pay_method  pay_method,
+    --支付成功时间, 对账时按该时间匹配当日账单
+    paid_time   timestamp without time zone,
+    --优惠券抵扣金额, cost为扣除后的应付金额
+    discount    numeric(12, 2)                    not null default 0 check ( discount >= 0 ),
+    --次卡抵扣的时数与对应的场地费, cost为扣除后的应付金额
+    package_hours  float8                         not null default 0 check ( package_hours >= 0 ),
+    package_amount numeric(12, 2)                 not null default 0 check ( package_amount >= 0 ),
+    --积分抵扣的积分数与金额, cost为扣除后的应付金额
+    points_used    int4                           not null default 0 check ( points_used >= 0 ),
+    points_amount  numeric(12, 2)                 not null default 0 check ( points_amount >= 0 ),
+    --促销活动优惠金额, cost为扣除后的应付金额
+    promotion_amount numeric(12, 2)               not null default 0 check ( promotion_amount >= 0 ),
+    --会员折扣金额, cost为扣除后的应付金额
+    member_amount  numeric(12, 2)                 not null default 0 check ( member_amount >= 0 ),
     check ( create_time < apt_start ),
-    check ( apt_start < apt_end )
+    check ( apt_start < apt_end ),
+    --同一球场未取消的订单时段不能重叠, 并发下单时的最后一道保障
+    constraint orders_no_overlap exclude using gist (
+        court_id with =,
+        tsrange(apt_start, apt_end) with &&
+        ) where ( status not in ('cancelled', 'refunded') )
 );
 create index on orders (user_id);
-create index on orders (court_id);
\ No newline at end of file
+create index on orders (court_id);
+create index on orders (series_id);
+create unique index on orders (user_id, idempotency_key);
+-----------------------------------------------
+create table if not exists "court_images"
+(
+    image_id    uuid primary key                                    not null default uuid_generate_v4(),
+    court_id    uuid references courts (court_id) on delete cascade not null,
+    --访问地址
+    url         varchar                                             not null,
+    --存储后端中的对象名
+    storage_key varchar                                             not null,
+    --展示顺序, 升序
+    sort_order  int4                                                not null default 0,
+    create_time timestamp without time zone                         not null default now()
+);
+create index on court_images (court_id, sort_order);
+-----------------------------------------------
+create table if not exists "court_open_hours"
+(
+    court_id   uuid references courts (court_id) on delete cascade not null,
+    --星期, 1(周一)~7(周日)
+    weekday    int2                                                not null check ( weekday between 1 and 7 ),
+    open_time  time                                                not null,
+    close_time time                                                not null,
+    --当天是否闭馆
+    is_closed  bool                                                not null default false,
+    check (open_time < close_time),
+    primary key (court_id, weekday)
+);
+-----------------------------------------------
+create table if not exists "court_price_rules"
+(
+    rule_id        uuid primary key                                    not null default uuid_generate_v4(),
+    court_id       uuid references courts (court_id) on delete cascade not null,
+    --星期, 1(周一)~7(周日), 为空时每天生效
+    weekday        int2 check ( weekday between 1 and 7 ),
+    start_time     time                                                not null,
+    end_time       time                                                not null,
+    price_per_hour numeric(12, 2)                                      not null check ( price_per_hour > 0 ),
+    check (start_time < end_time)
+);
+create index on court_price_rules (court_id);
+-----------------------------------------------
+create table if not exists "court_tags"
+(
+    tag_id   uuid primary key                not null default uuid_generate_v4(),
+    admin_id uuid references users (user_id) not null,
+    tag_name varchar(50)                     not null,
+    unique (admin_id, tag_name)
+);
+create table if not exists "court_tag_links"
+(
+    court_id uuid references courts (court_id) on delete cascade   not null,
+    tag_id   uuid references court_tags (tag_id) on delete cascade not null,
+    primary key (court_id, tag_id)
+);
+create index on court_tag_links (tag_id);
+-----------------------------------------------
+--管理员屏蔽的时间段(包场/维修等)
+create table if not exists "court_blocks"
+(
+    block_id    uuid primary key                                    not null default uuid_generate_v4(),
+    court_id    uuid references courts (court_id) on delete cascade not null,
+    block_start timestamp without time zone                         not null,
+    block_end   timestamp without time zone                         not null,
+    reason      varchar(200)                                        not null default '',
+    create_time timestamp without time zone                         not null default now(),
+    check ( block_start < block_end )
+);
+create index on court_blocks (court_id, block_start);
+-----------------------------------------------
+--特殊日期(节假日)价格, 优先于价格时段
+create table if not exists "court_price_overrides"
+(
+    override_id    uuid primary key                                    not null default uuid_generate_v4(),
+    court_id       uuid references courts (court_id) on delete cascade not null,
+    override_date  date                                                not null,
+    price_per_hour numeric(12, 2)                                      not null check ( price_per_hour > 0 ),
+    note           varchar(100)                                        not null default '',
+    unique (court_id, override_date)
+);
+--按当天预订率调整价格, 预订率不低于min_utilization时按adjust_percent调整
+--取满足条件的最高一档, 没有设置时不调整
+create table if not exists "court_demand_tiers"
+(
+    court_id        uuid references courts (court_id) on delete cascade not null,
+    --当天营业时间内已预订时长的百分比
+    min_utilization int2                                                not null check ( min_utilization between 0 and 100 ),
+    --调整的百分比, 如20表示上调20%, -10表示下调10%
+    adjust_percent  numeric(5, 2)                                       not null check ( adjust_percent between -90 and 200 ),
+    primary key (court_id, min_utilization)
+);
+-----------------------------------------------
+--球场预约规则, 字段为空表示不限制
+create table if not exists "court_booking_rules"
+(
+    court_id          uuid primary key references courts (court_id) on delete cascade not null,
+    --单次预约最短/最长时长, 分钟
+    min_minutes       int4 check ( min_minutes > 0 ),
+    max_minutes       int4 check ( max_minutes > 0 ),
+    --最多可提前预约的天数
+    max_advance_days  int4 check ( max_advance_days >= 0 ),
+    --每个用户在该球场未完成订单数上限
+    max_active_orders int4 check ( max_active_orders > 0 ),
+    --开始前多少分钟之前可免费取消, 为空表示随时免费取消
+    free_cancel_minutes int4 check ( free_cancel_minutes >= 0 ),
+    --超过免费取消时间后收取的费用比例
+    cancel_fee_rate   numeric(4, 3) not null default 0 check ( cancel_fee_rate between 0 and 1 ),
+    check ( min_minutes <= max_minutes )
+);
+-----------------------------------------------
+--球场修改记录
+create table if not exists "court_audit"
+(
+    audit_id    uuid primary key                                    not null default uuid_generate_v4(),
+    --球场删除后仍保留记录, 不设外键
+    court_id    uuid                                                not null,
+    --操作人
+    operator_id uuid references users (user_id) on delete cascade  not null,
+    --create / update / delete / status / image
+    action      varchar(10)                                         not null,
+    --变更字段 {"字段":{"old":旧值,"new":新值}}
+    diff        jsonb                                               not null,
+    create_time timestamp without time zone                         not null default now()
+);
+create index on court_audit (court_id, create_time);
+-----------------------------------------------
+--球场附加项目(球拍, 用球, 灯光费等)
+create table if not exists "court_addons"
+(
+    addon_id   uuid primary key                                    not null default uuid_generate_v4(),
+    court_id   uuid references courts (court_id) on delete cascade not null,
+    addon_name varchar(50)                                         not null,
+    price      numeric(12, 2)                                      not null check ( price >= 0 ),
+    --true: 按预约小时计费, false: 按件计费
+    per_hour   bool                                                not null default false,
+    --下架后不能再选择, 历史订单仍然保留
+    is_active  bool                                                not null default true,
+    unique (court_id, addon_name)
+);
+-----------------------------------------------
+--订单选择的附加项目, 价格为下单时的快照
+create table if not exists "order_addons"
+(
+    order_id uuid references orders (order_id) on delete cascade not null,
+    addon_id uuid references court_addons (addon_id)             not null,
+    quantity int4                                                not null check ( quantity > 0 ),
+    price    numeric(12, 2)                                      not null,
+    per_hour bool                                                not null,
+    primary key (order_id, addon_id)
+);
+-----------------------------------------------
+--球场及其图片/标签变化时刷新courts.update_time
+create or replace function touch_court() returns trigger as
+$$
+begin
+    if tg_table_name = 'courts' then
+        new.update_time = now();
+        return new;
+    end if;
+    if tg_table_name = 'court_tags' then
+        update courts
+        set update_time = now()
+        where court_id in (select court_id from court_tag_links where tag_id = new.tag_id);
+        return null;
+    end if;
+    if tg_op = 'DELETE' then
+        update courts set update_time = now() where court_id = old.court_id;
+    else
+        update courts set update_time = now() where court_id = new.court_id;
+    end if;
+    return null;
+end;
+$$ language plpgsql;
+create or replace trigger courts_touch
+    before update
+    on courts
+    for each row
+execute function touch_court();
+create or replace trigger court_images_touch
+    after insert or update or delete
+    on court_images
+    for each row
+execute function touch_court();
+create or replace trigger court_tag_links_touch
+    after insert or update or delete
+    on court_tag_links
+    for each row
+execute function touch_court();
+create or replace trigger court_tags_touch
+    after update
+    on court_tags
+    for each row
+execute function touch_court();
+-----------------------------------------------
+--支付前临时锁定的时段, 过期后自动失效
+create table if not exists "slot_holds"
+(
+    hold_id     uuid primary key                                    not null default uuid_generate_v4(),
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    court_id    uuid references courts (court_id) on delete cascade not null,
+    hold_start  timestamp without time zone                         not null,
+    hold_end    timestamp without time zone                         not null,
+    expire_time timestamp without time zone                         not null,
+    create_time timestamp without time zone                         not null default now(),
+    check ( hold_start < hold_end )
+);
+create index on slot_holds (court_id, hold_start);
+-----------------------------------------------
+--订单时间线, 记录状态变化与改期
+create table if not exists "order_timeline"
+(
+    event_id    uuid primary key                                    not null default uuid_generate_v4(),
+    order_id    uuid references orders (order_id) on delete cascade not null,
+    --status / reschedule
+    action      varchar(20)                                         not null,
+    detail      jsonb                                               not null,
+    create_time timestamp without time zone                         not null default now()
+);
+create index on order_timeline (order_id, create_time);
+-----------------------------------------------
+--订单价格明细: 基础价/时段加价/附加项目/优惠/押金
+--押金以外的明细合计等于订单cost
+create type order_item_kind as enum ('base', 'peak', 'addon', 'discount', 'package', 'member', 'points', 'promotion', 'deposit');
+create table if not exists "order_items"
+(
+    item_id  uuid primary key                                    not null default uuid_generate_v4(),
+    order_id uuid references orders (order_id) on delete cascade not null,
+    kind     order_item_kind                                     not null,
+    name     varchar(50)                                         not null,
+    quantity integer                                             not null default 1,
+    amount   numeric(12, 2)                                      not null
+);
+create index on order_items (order_id);
+-----------------------------------------------
+--退款申请: 待处理/已通过/已拒绝
+create type refund_status as enum ('pending', 'approved', 'rejected');
+create type refund_channel_status as enum ('processing', 'success', 'closed', 'abnormal');
+--退款原因: 用户取消/改期/天气/场地问题/服务问题/支付问题/其他/押金退还
+create type refund_reason as enum ('user_cancel', 'schedule_change', 'weather', 'venue_issue', 'service_issue', 'payment_issue', 'other', 'deposit');
+create table if not exists "refunds"
+(
+    refund_id   uuid primary key                                    not null default uuid_generate_v4(),
+    order_id    uuid references orders (order_id) on delete cascade not null,
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    amount      numeric(12, 2)                                      not null check ( amount > 0 ),
+    reason      varchar(200)                                        not null,
+    reason_code refund_reason                                       not null default 'other',
+    status      refund_status                                       not null default 'pending',
+    --处理的管理员与回复
+    admin_id    uuid references users (user_id) on delete set null,
+    reply       varchar(200),
+    create_time timestamp without time zone                         not null default now(),
+    handle_time timestamp without time zone,
+    --支付渠道的退款单号与退款结果, 线下退款为空, 商户退款单号即refund_id
+    channel_refund_id varchar(64),
+    channel_status refund_channel_status,
+    success_time timestamp without time zone
+);
+create index on refunds (order_id);
+--部分退款对应的订单明细, 明细随改期重新生成时保留名称与类型
+create table if not exists "refund_items"
+(
+    refund_item_id uuid primary key                                        not null default uuid_generate_v4(),
+    refund_id      uuid references refunds (refund_id) on delete cascade   not null,
+    item_id        uuid references order_items (item_id) on delete set null,
+    kind           order_item_kind                                         not null,
+    name           varchar(50)                                             not null,
+    amount         numeric(12, 2)                                          not null check ( amount > 0 )
+);
+create index on refund_items (refund_id);
+-----------------------------------------------
+--拼单分摊: 待支付/已支付/已退款/已过期
+create type share_status as enum ('pending', 'paid', 'refunded', 'expired');
+create table if not exists "order_participants"
+(
+    participant_id uuid primary key                                    not null default uuid_generate_v4(),
+    order_id       uuid references orders (order_id) on delete cascade not null,
+    --领取分摊的用户, 发起人以外的分摊在支付时领取
+    user_id        uuid references users (user_id) on delete cascade,
+    share          numeric(12, 2)                                      not null check ( share > 0 ),
+    status         share_status                                        not null default 'pending',
+    --每份单独支付, 退款时以participant_id作为退款记录号
+    pay_method     pay_method,
+    transaction_id varchar(32),
+    paid_time      timestamp without time zone,
+    create_time    timestamp without time zone                         not null default now(),
+    unique (order_id, user_id)
+);
+create index on order_participants (order_id);
+-----------------------------------------------
+--电子发票: 已申请/已开具/已驳回
+create type invoice_status as enum ('requested', 'issued', 'rejected');
+create table if not exists "invoices"
+(
+    invoice_id  uuid primary key                                    not null default uuid_generate_v4(),
+    --每个订单只开一张发票, 驳回后可修改信息重新申请
+    order_id    uuid references orders (order_id) on delete cascade not null unique,
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    --发票抬头与税号, 个人抬头税号为空
+    title       varchar(100)                                        not null,
+    tax_number  varchar(20),
+    --接收发票的邮箱
+    email       varchar(100)                                        not null,
+    amount      numeric(12, 2)                                      not null check ( amount > 0 ),
+    status      invoice_status                                      not null default 'requested',
+    --开具后的发票文件地址
+    url         varchar(500),
+    admin_id    uuid references users (user_id) on delete set null,
+    reply       varchar(200),
+    create_time timestamp without time zone                         not null default now(),
+    handle_time timestamp without time zone
+);
+-----------------------------------------------
+--订单完成后的评价, 每个订单一条
+create table if not exists "court_reviews"
+(
+    review_id     uuid primary key                                    not null default uuid_generate_v4(),
+    order_id      uuid references orders (order_id) on delete cascade not null unique,
+    court_id      uuid references courts (court_id) on delete cascade not null,
+    user_id       uuid references users (user_id) on delete cascade   not null,
+    rating        int4                                                not null check ( rating between 1 and 5 ),
+    comment       varchar(500)                                        not null default '',
+    --管理员隐藏的评价不公开展示, 不计入评分
+    hidden        bool                                                not null default false,
+    --管理员举报的不当评价, 由平台处理
+    reported      bool                                                not null default false,
+    report_reason varchar(200),
+    create_time   timestamp without time zone                         not null default now()
+);
+create index on court_reviews (court_id, create_time);
+-----------------------------------------------
+--钱包余额, 首次充值或调整时创建
+create table if not exists "wallets"
+(
+    user_id     uuid primary key references users (user_id) on delete cascade not null,
+    balance     numeric(12, 2)                                                not null default 0 check ( balance >= 0 ),
+    update_time timestamp without time zone                                   not null default now()
+);
+--钱包充值单: 待支付/已支付, 微信支付以recharge_id作为商户订单号
+create type recharge_status as enum ('pending', 'paid');
+create table if not exists "wallet_recharges"
+(
+    recharge_id    uuid primary key                                  not null default uuid_generate_v4(),
+    user_id        uuid references users (user_id) on delete cascade not null,
+    amount         numeric(12, 2)                                    not null check ( amount > 0 ),
+    status         recharge_status                                   not null default 'pending',
+    transaction_id varchar(32),
+    create_time    timestamp without time zone                       not null default now(),
+    paid_time      timestamp without time zone
+);
+--钱包流水: 充值/订单支付/订单退款/管理员调整/礼品卡兑换
+create type wallet_txn_kind as enum ('recharge', 'payment', 'refund', 'adjust', 'gift_card');
+create table if not exists "wallet_transactions"
+(
+    txn_id      uuid primary key                                  not null default uuid_generate_v4(),
+    user_id     uuid references users (user_id) on delete cascade not null,
+    kind        wallet_txn_kind                                   not null,
+    --变动金额, 支出为负
+    amount      numeric(12, 2)                                    not null check ( amount <> 0 ),
+    --变动后的余额
+    balance     numeric(12, 2)                                    not null,
+    order_id    uuid references orders (order_id) on delete set null,
+    recharge_id uuid references wallet_recharges (recharge_id) on delete set null,
+    --调整余额的管理员, 与remark一起作为审计记录
+    admin_id    uuid references users (user_id) on delete set null,
+    remark      varchar(200)                                      not null default '',
+    create_time timestamp without time zone                       not null default now()
+);
+create index on wallet_transactions (user_id, create_time);
+-----------------------------------------------
+--优惠券模板: 固定金额/百分比折扣
+create type coupon_kind as enum ('fixed', 'percent');
+create table if not exists "coupon_templates"
+(
+    template_id  uuid primary key                                  not null default uuid_generate_v4(),
+    --创建的管理员, 只能用于该管理员名下的球场
+    admin_id     uuid references users (user_id) on delete cascade not null,
+    name         varchar(50)                                       not null,
+    kind         coupon_kind                                       not null,
+    --固定金额为抵扣的元数, 百分比为减免的比例, 如20表示减免20%
+    value        numeric(12, 2)                                    not null check ( value > 0 ),
+    --使用门槛, 订单金额不低于该值
+    min_amount   numeric(12, 2)                                    not null default 0 check ( min_amount >= 0 ),
+    --百分比券的抵扣上限, 为空时不限
+    max_discount numeric(12, 2) check ( max_discount > 0 ),
+    valid_from   timestamp without time zone                       not null,
+    valid_to     timestamp without time zone                       not null,
+    --停用后已发放的券不能再使用
+    enabled      bool                                              not null default true,
+    create_time  timestamp without time zone                       not null default now(),
+    check ( valid_from < valid_to ),
+    check ( kind <> 'percent' or value < 100 )
+);
+--优惠券限定的球场, 没有记录时可用于管理员名下全部球场
+create table if not exists "coupon_courts"
+(
+    template_id uuid references coupon_templates (template_id) on delete cascade not null,
+    court_id    uuid references courts (court_id) on delete cascade              not null,
+    primary key (template_id, court_id)
+);
+--用户持有的优惠券: 未使用/已使用
+create type coupon_status as enum ('unused', 'used');
+create table if not exists "user_coupons"
+(
+    coupon_id   uuid primary key                                                not null default uuid_generate_v4(),
+    template_id uuid references coupon_templates (template_id) on delete cascade not null,
+    user_id     uuid references users (user_id) on delete cascade                not null,
+    status      coupon_status                                                   not null default 'unused',
+    --批量发放的批次
+    batch_id    uuid                                                            not null,
+    --使用的订单与抵扣金额
+    order_id    uuid references orders (order_id) on delete set null,
+    discount    numeric(12, 2),
+    create_time timestamp without time zone                                     not null default now(),
+    used_time   timestamp without time zone
+);
+create index on user_coupons (user_id, status);
+create index on user_coupons (order_id);
+-----------------------------------------------
+--次卡套餐, 如"10小时卡", 可用于管理员名下全部球场
+create table if not exists "packages"
+(
+    package_id  uuid primary key                                  not null default uuid_generate_v4(),
+    admin_id    uuid references users (user_id) on delete cascade not null,
+    name        varchar(50)                                       not null,
+    hours       float8                                            not null check ( hours > 0 ),
+    price       numeric(12, 2)                                    not null check ( price > 0 ),
+    --购买后的有效天数
+    valid_days  int4                                              not null check ( valid_days > 0 ),
+    --停用后不能再购买, 已购买的次卡不受影响
+    enabled     bool                                              not null default true,
+    create_time timestamp without time zone                       not null default now()
+);
+--次卡购买单: 待支付/已支付, 微信支付以purchase_id作为商户订单号
+create table if not exists "package_purchases"
+(
+    purchase_id    uuid primary key                                          not null default uuid_generate_v4(),
+    package_id     uuid references packages (package_id) on delete cascade not null,
+    user_id        uuid references users (user_id) on delete cascade         not null,
+    amount         numeric(12, 2)                                            not null check ( amount > 0 ),
+    status         recharge_status                                           not null default 'pending',
+    transaction_id varchar(32),
+    create_time    timestamp without time zone                               not null default now(),
+    paid_time      timestamp without time zone
+);
+--用户持有的次卡, 下单时先到期的先使用, 过期后剩余时数作废
+create table if not exists "user_packages"
+(
+    user_package_id uuid primary key                                                   not null default uuid_generate_v4(),
+    user_id         uuid references users (user_id) on delete cascade                  not null,
+    package_id      uuid references packages (package_id) on delete cascade            not null,
+    --冗余套餐的管理员, 下单时按球场所属管理员查找
+    admin_id        uuid references users (user_id) on delete cascade                  not null,
+    purchase_id     uuid unique references package_purchases (purchase_id) on delete set null,
+    hours           float8                                                             not null check ( hours > 0 ),
+    hours_left      float8                                                             not null check ( hours_left >= 0 ),
+    expire_time     timestamp without time zone                                        not null,
+    create_time     timestamp without time zone                                        not null default now()
+);
+create index on user_packages (user_id, admin_id, expire_time);
+--次卡使用记录, 订单取消或退款后退回时数
+create table if not exists "package_usages"
+(
+    usage_id        uuid primary key                                                    not null default uuid_generate_v4(),
+    user_package_id uuid references user_packages (user_package_id) on delete cascade not null,
+    order_id        uuid references orders (order_id) on delete cascade                not null,
+    hours           float8                                                              not null check ( hours > 0 ),
+    restored        bool                                                                not null default false,
+    create_time     timestamp without time zone                                         not null default now()
+);
+create index on package_usages (order_id);
+-----------------------------------------------
+--每日对账: 下载微信支付交易账单与本地支付记录比对, 每个账单日一条
+create table if not exists "reconcile_runs"
+(
+    bill_date    date primary key                            not null,
+    --账单中支付成功的笔数与金额
+    trade_count  int4                                        not null,
+    trade_amount numeric(12, 2)                              not null,
+    issue_count  int4                                        not null,
+    --对账失败的原因, 失败的账单日由定时任务继续重试
+    error        varchar(500),
+    run_time     timestamp without time zone                 not null default now()
+);
+--对账差异: 本地缺失(支付通知丢失)/账单缺失/金额不一致/重复支付
+create type reconcile_issue_kind as enum ('missing_local', 'missing_remote', 'amount_mismatch', 'duplicate');
+create table if not exists "reconcile_issues"
+(
+    issue_id       uuid primary key                                                not null default uuid_generate_v4(),
+    bill_date      date references reconcile_runs (bill_date) on delete cascade    not null,
+    kind           reconcile_issue_kind                                            not null,
+    --相关场馆的管理员, 钱包充值或无法对应本地记录时为空, 仅超级管理员可见
+    admin_id       uuid references users (user_id) on delete set null,
+    out_trade_no   varchar(32)                                                     not null,
+    transaction_id varchar(32),
+    local_amount   numeric(12, 2),
+    remote_amount  numeric(12, 2),
+    --核实处理后标记, 备注处理方式
+    resolved       bool                                                            not null default false,
+    resolved_by    uuid references users (user_id) on delete set null,
+    remark         varchar(200),
+    create_time    timestamp without time zone                                     not null default now()
+);
+create index on reconcile_issues (bill_date);
+-----------------------------------------------
+--结算流水: 订单支付与退款、次卡购买发生时写入, 用于管理员结算报表
+create type ledger_kind as enum ('payment', 'refund');
+create table if not exists "settlement_ledger"
+(
+    entry_id    uuid primary key                                                 not null default uuid_generate_v4(),
+    admin_id    uuid references users (user_id) on delete cascade                not null,
+    --订单流水对应球场与订单, 次卡购买流水对应购买单
+    court_id    uuid references courts (court_id) on delete cascade,
+    order_id    uuid references orders (order_id) on delete cascade,
+    purchase_id uuid references package_purchases (purchase_id) on delete cascade,
+    kind        ledger_kind                                                      not null,
+    pay_method  pay_method,
+    --支付为正, 退款为负
+    amount      numeric(12, 2)                                                   not null,
+    --平台服务费, 按写入时的费率计算, 退款时按比例退还, 押金部分不收取
+    fee         numeric(12, 2)                                                   not null default 0,
+    create_time timestamp without time zone                                      not null default now(),
+    check ( (order_id is null) != (purchase_id is null) )
+);
+create index on settlement_ledger (admin_id, create_time);
+-----------------------------------------------
+--积分设置, 仅一行, 未设置时使用默认值
+create table if not exists "points_config"
+(
+    config_id          int2 primary key            not null default 1 check ( config_id = 1 ),
+    --每消费1元获得的积分
+    earn_rate          numeric(8, 4)               not null check ( earn_rate >= 0 ),
+    --每积分抵扣的金额, 元
+    redeem_rate        numeric(8, 4)               not null check ( redeem_rate >= 0 ),
+    --单笔订单最多抵扣应付金额的百分比
+    max_redeem_percent int2                        not null check ( max_redeem_percent between 0 and 100 ),
+    update_time        timestamp without time zone not null default now()
+);
+create table if not exists "user_points"
+(
+    user_id     uuid primary key references users (user_id) on delete cascade not null,
+    points      int4                                                          not null default 0 check ( points >= 0 ),
+    update_time timestamp without time zone                                   not null default now()
+);
+--积分流水: 订单完成获得/下单抵扣/取消退回
+create type points_txn_kind as enum ('earn', 'redeem', 'restore');
+create table if not exists "points_transactions"
+(
+    txn_id      uuid primary key                                  not null default uuid_generate_v4(),
+    user_id     uuid references users (user_id) on delete cascade not null,
+    kind        points_txn_kind                                   not null,
+    --变动积分, 支出为负
+    points      int4                                              not null check ( points <> 0 ),
+    --变动后的积分
+    balance     int4                                              not null,
+    order_id    uuid references orders (order_id) on delete set null,
+    create_time timestamp without time zone                       not null default now()
+);
+create index on points_transactions (user_id, create_time);
+--同一订单只获得与退回一次
+create unique index on points_transactions (order_id, kind) where kind in ('earn', 'restore');
+-----------------------------------------------
+--促销活动: 满时长赠送时长/按比例折扣, 报价与下单时自动计算
+create type promotion_kind as enum ('free_hours', 'percent_off');
+create table if not exists "promotions"
+(
+    promotion_id   uuid primary key                                  not null default uuid_generate_v4(),
+    --创建的管理员, 只对该管理员名下的球场生效
+    admin_id       uuid references users (user_id) on delete cascade not null,
+    name           varchar(50)                                       not null,
+    kind           promotion_kind                                    not null,
+    --赠送的时数, 或减免的百分比, 如20表示减免20%
+    value          numeric(5, 2)                                     not null check ( value > 0 ),
+    --预约时长不低于该时数时可参加, 如订3小时送1小时为3
+    min_hours      numeric(5, 2)                                     not null default 0 check ( min_hours >= 0 ),
+    --限定星期, 1(周一)~7(周日), 为空时每天
+    weekdays       int2[]                                            not null default '{}',
+    --限定时段, 订单须在时段内, 为空时不限
+    start_time     time,
+    end_time       time,
+    --可叠加的活动可同时参加并可与优惠券同时使用, 不可叠加的只能单独参加
+    stackable      bool                                              not null default false,
+    --总参加次数与每人参加次数上限, 为空时不限
+    usage_limit    int4 check ( usage_limit > 0 ),
+    per_user_limit int4 check ( per_user_limit > 0 ),
+    valid_from     timestamp without time zone                       not null,
+    valid_to       timestamp without time zone                       not null,
+    enabled        bool                                              not null default true,
+    create_time    timestamp without time zone                       not null default now(),
+    check ( valid_from < valid_to ),
+    check ( kind <> 'percent_off' or value < 100 ),
+    check ( (start_time is null) = (end_time is null) and start_time < end_time )
+);
+create index on promotions (admin_id);
+--活动限定的球场, 没有记录时对管理员名下全部球场生效
+create table if not exists "promotion_courts"
+(
+    promotion_id uuid references promotions (promotion_id) on delete cascade not null,
+    court_id     uuid references courts (court_id) on delete cascade         not null,
+    primary key (promotion_id, court_id)
+);
+--活动参加记录, 订单取消或退款后删除, 不再计入参加次数
+create table if not exists "promotion_usages"
+(
+    promotion_id uuid references promotions (promotion_id) on delete cascade not null,
+    order_id     uuid references orders (order_id) on delete cascade         not null,
+    user_id      uuid references users (user_id) on delete cascade           not null,
+    amount       numeric(12, 2)                                              not null,
+    create_time  timestamp without time zone                                 not null default now(),
+    primary key (promotion_id, order_id)
+);
+create index on promotion_usages (promotion_id, user_id);
+-----------------------------------------------
+--礼品卡: 待支付/可兑换/已兑换, 购买后可将兑换码赠送他人, 兑换为钱包余额
+create type gift_card_status as enum ('pending', 'active', 'redeemed');
+create table if not exists "gift_cards"
+(
+    card_id        uuid primary key                                  not null default uuid_generate_v4(),
+    --兑换码, 16位大写字母与数字
+    code           varchar(16) unique                                not null,
+    amount         numeric(12, 2)                                    not null check ( amount > 0 ),
+    --购买的用户与赠言
+    buyer_id       uuid references users (user_id) on delete cascade not null,
+    message        varchar(100)                                      not null default '',
+    status         gift_card_status                                  not null default 'pending',
+    transaction_id varchar(32),
+    --兑换的用户, 兑换后直接支付的订单
+    redeemed_by    uuid references users (user_id) on delete set null,
+    order_id       uuid references orders (order_id) on delete set null,
+    create_time    timestamp without time zone                       not null default now(),
+    paid_time      timestamp without time zone,
+    redeemed_time  timestamp without time zone
+);
+create index on gift_cards (buyer_id);
+create index on gift_cards (redeemed_by);
+-----------------------------------------------
+--已处理的微信支付通知, 按微信支付订单号去重, 重复或并发的通知不会重复入账
+create table if not exists "payment_notifications"
+(
+    transaction_id varchar(32) primary key     not null,
+    out_trade_no   varchar(64)                 not null,
+    --业务类型: 订单/充值/次卡/礼品卡
+    attach         varchar(20)                 not null default '',
+    amount         numeric(12, 2)              not null,
+    create_time    timestamp without time zone not null default now()
+);
+-----------------------------------------------
+--用户收藏的球场
+create table if not exists "court_favorites"
+(
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    court_id    uuid references courts (court_id) on delete cascade not null,
+    create_time timestamp without time zone                         not null default now(),
+    primary key (user_id, court_id)
+);
+-----------------------------------------------
+--站内通知类型: 订单状态/退款/场馆公告/反馈处理/找球友
+create type notification_kind as enum ('order', 'refund', 'announcement', 'feedback', 'partner');
+create table if not exists "user_notifications"
+(
+    notification_id uuid primary key                                   not null default uuid_generate_v4(),
+    user_id         uuid references users (user_id) on delete cascade  not null,
+    kind            notification_kind                                  not null,
+    title           varchar(50)                                        not null,
+    content         varchar(500)                                       not null,
+    --关联的订单, 场馆公告为空
+    order_id        uuid references orders (order_id) on delete set null,
+    is_read         bool                                               not null default false,
+    create_time     timestamp without time zone                        not null default now()
+);
+create index if not exists "user_notifications_user_idx" on user_notifications (user_id, create_time);
+-----------------------------------------------
+--场馆黑名单, 名单内的用户不能预约该场馆的球场, 到期后自动失效
+create table if not exists "venue_blacklist"
+(
+    venue_id    uuid references venues (venue_id) on delete cascade not null,
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    --操作的管理员
+    admin_id    uuid references users (user_id)                     not null,
+    reason      varchar(200)                                        not null,
+    --为空时永久有效
+    expire_time timestamp without time zone,
+    create_time timestamp without time zone                         not null default now(),
+    primary key (venue_id, user_id)
+);
+-----------------------------------------------
+--邀请码, 首次查看时生成
+create table if not exists "referral_codes"
+(
+    user_id     uuid primary key references users (user_id) on delete cascade not null,
+    code        varchar(8) unique                                             not null,
+    create_time timestamp without time zone                                   not null default now()
+);
+--邀请关系, 被邀请人注册时绑定, 首笔订单完成后双方获得优惠券
+create table if not exists "referrals"
+(
+    invitee_id    uuid primary key references users (user_id) on delete cascade not null,
+    inviter_id    uuid references users (user_id) on delete cascade             not null,
+    --触发奖励的首笔订单与奖励时间, 未奖励时为空
+    order_id      uuid references orders (order_id) on delete set null,
+    rewarded_time timestamp without time zone,
+    create_time   timestamp without time zone                                   not null default now()
+);
+create index on referrals (inviter_id, create_time);
+--邀请奖励设置, 仅一行, 未设置时不发放奖励
+create table if not exists "referral_config"
+(
+    config_id           int2 primary key            not null default 1 check ( config_id = 1 ),
+    --邀请人与被邀请人获得的优惠券模板, 为空时不发放
+    inviter_template_id uuid references coupon_templates (template_id) on delete set null,
+    invitee_template_id uuid references coupon_templates (template_id) on delete set null,
+    update_time         timestamp without time zone not null default now()
+);
+-----------------------------------------------
+--登录会话, 每次登录签发一个token, 撤销或过期后token失效
+create table if not exists "user_sessions"
+(
+    --token中的token_uuid
+    session_id       uuid primary key                                  not null,
+    user_id          uuid references users (user_id) on delete cascade not null,
+    --登录设备(User-Agent)与ip
+    device           varchar(255)                                      not null default '',
+    ip               varchar(45)                                       not null default '',
+    create_time      timestamp without time zone                       not null default now(),
+    last_active_time timestamp without time zone                       not null default now(),
+    expire_time      timestamp without time zone                       not null,
+    revoked_time     timestamp without time zone,
+    --登录时已通过二次验证, 开启二次验证前签发的会话为false
+    mfa_verified     bool                                              not null default false
+);
+create index on user_sessions (user_id, create_time);
+create index on user_sessions (expire_time);
+-----------------------------------------------
+--球场分享海报, 封面图变化后重新生成
+create table if not exists "court_posters"
+(
+    court_id    uuid primary key references courts (court_id) on delete cascade not null,
+    url         varchar(255)                                                   not null,
+    storage_key varchar(255)                                                   not null,
+    --生成时使用的封面图, 没有图片时为空
+    cover_key   varchar(255)                                                   not null default '',
+    create_time timestamp without time zone                                    not null default now()
+);
+-----------------------------------------------
+--用户反馈: 设施问题/费用争议/建议
+create type feedback_category as enum ('facility', 'billing', 'suggestion');
+--处理状态: 新提交 → 处理中 → 已解决
+create type feedback_status as enum ('new', 'in_progress', 'resolved');
+create table if not exists "feedback"
+(
+    feedback_id uuid primary key                                  not null default uuid_generate_v4(),
+    user_id     uuid references users (user_id) on delete cascade not null,
+    category    feedback_category                                 not null,
+    content     varchar(1000)                                     not null,
+    --相关的球场与订单, 费用争议须关联订单
+    court_id    uuid references courts (court_id) on delete set null,
+    order_id    uuid references orders (order_id) on delete set null,
+    --图片附件地址
+    images      varchar(255)[]                                    not null default '{}',
+    --负责处理的管理员, 即球场所属管理员, 为空时由超级管理员处理
+    admin_id    uuid references users (user_id) on delete set null,
+    status      feedback_status                                   not null default 'new',
+    --处理回复
+    reply       varchar(500),
+    create_time timestamp without time zone                       not null default now(),
+    update_time timestamp without time zone                       not null default now()
+);
+create index on feedback (user_id, create_time);
+create index on feedback (admin_id, status, create_time);
+-----------------------------------------------
+--找球友: 招募中/已满员/已关闭
+create type partner_post_status as enum ('open', 'full', 'closed');
+create table if not exists "partner_posts"
+(
+    post_id     uuid primary key                                    not null default uuid_generate_v4(),
+    --发起招募的预约, 每个预约只能发起一次
+    order_id    uuid references orders (order_id) on delete cascade not null unique,
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    --招募人数与已加入人数, 不包括发起人
+    slots       int2                                                not null check ( slots between 1 and 20 ),
+    joined      int2                                                not null default 0 check ( joined between 0 and slots ),
+    note        varchar(200)                                        not null default '',
+    status      partner_post_status                                 not null default 'open',
+    create_time timestamp without time zone                         not null default now(),
+    update_time timestamp without time zone                         not null default now()
+);
+create index on partner_posts (status, create_time);
+--加入申请: 待审核/已通过/已拒绝/已撤回
+create type partner_request_status as enum ('pending', 'approved', 'rejected', 'withdrawn');
+create table if not exists "partner_requests"
+(
+    request_id  uuid primary key                                             not null default uuid_generate_v4(),
+    post_id     uuid references partner_posts (post_id) on delete cascade    not null,
+    user_id     uuid references users (user_id) on delete cascade            not null,
+    message     varchar(200)                                                 not null default '',
+    status      partner_request_status                                       not null default 'pending',
+    create_time timestamp without time zone                                  not null default now(),
+    handle_time timestamp without time zone,
+    unique (post_id, user_id)
+);
+create index on partner_requests (user_id, status);
+-----------------------------------------------
+--会员等级权益, 由超级管理员设置, 未设置的等级没有权益
+create table if not exists "tier_benefits"
+(
+    tier               user_tier primary key       not null,
+    --在球场预约规则的最多提前天数基础上增加的天数
+    extra_advance_days int4                        not null default 0 check ( extra_advance_days between 0 and 365 ),
+    --场地费折扣百分比, 如10表示优惠10%
+    discount_percent   numeric(5, 2)               not null default 0 check ( discount_percent between 0 and 50 ),
+    update_time        timestamp without time zone not null default now()
+);
+-----------------------------------------------
+--订阅消息授权结果: 允许/拒绝/被封禁/被过滤(模板标题与后台不一致)
+create type subscribe_status as enum ('accept', 'reject', 'ban', 'filter');
+--用户对订阅消息模板的授权, 每次允许可下发一条, 下发后扣减
+create table if not exists "subscribe_consents"
+(
+    user_id     uuid references users (user_id) on delete cascade not null,
+    template_id varchar(64)                                       not null,
+    status      subscribe_status                                  not null,
+    --剩余可下发次数
+    quota       int4                                              not null default 0 check ( quota >= 0 ),
+    --最近一次允许的时间
+    accept_time timestamp without time zone,
+    update_time timestamp without time zone                       not null default now(),
+    primary key (user_id, template_id)
+);
+-----------------------------------------------
+--关注的场馆, 场馆发布公告时通知关注的用户
+create table if not exists "venue_follows"
+(
+    user_id     uuid references users (user_id) on delete cascade   not null,
+    venue_id    uuid references venues (venue_id) on delete cascade not null,
+    --是否同时通过通知渠道推送公告
+    push        boolean                                             not null default false,
+    create_time timestamp without time zone                         not null default now(),
+    primary key (user_id, venue_id)
+);
+create index on venue_follows (venue_id);
+-----------------------------------------------
+--球场搜索记录, 同一用户的相同关键词只保留一条, 用于最近搜索与热门搜索
+create table if not exists "search_history"
+(
+    user_id     uuid references users (user_id) on delete cascade not null,
+    keyword     varchar(50)                                       not null,
+    times       int4                                              not null default 1,
+    search_time timestamp without time zone                       not null default now(),
+    primary key (user_id, keyword)
+);
+create index on search_history (search_time);
+-----------------------------------------------
+--常用联系人, 下单时可选为现场联系人, 最近使用的在前
+create table if not exists "user_contacts"
+(
+    contact_id     uuid primary key                                  not null default uuid_generate_v4(),
+    user_id        uuid references users (user_id) on delete cascade not null,
+    contact_name   varchar(30)                                       not null,
+    phone          varchar(20)                                       not null,
+    create_time    timestamp without time zone                       not null default now(),
+    last_used_time timestamp without time zone,
+    unique (user_id, phone)
+);
+-----------------------------------------------
+--刷新令牌, 只保存哈希, 每次使用后轮换; 已使用的令牌再次出现时撤销整个会话
+create table if not exists "refresh_tokens"
+(
+    token_hash  char(64) primary key                                             not null,
+    session_id  uuid references user_sessions (session_id) on delete cascade not null,
+    create_time timestamp without time zone                                     not null default now(),
+    used_time   timestamp without time zone
+);
+create index on refresh_tokens (session_id);
+-----------------------------------------------
+--找回密码的验证码, 每个用户只保留最近一次, 只保存哈希
+create table if not exists "password_resets"
+(
+    user_id     uuid primary key references users (user_id) on delete cascade not null,
+    code_hash   char(64)                                                     not null,
+    --验证失败次数, 达到上限后验证码作废
+    attempts    int4                                                         not null default 0,
+    send_time   timestamp without time zone                                  not null default now(),
+    expire_time timestamp without time zone                                  not null
+);
+-----------------------------------------------
+--第三方对接的API密钥, 归属于场馆管理员, 只保存哈希, 明文只在创建时返回一次
+create table if not exists "api_keys"
+(
+    key_id         uuid primary key                                  not null default uuid_generate_v4(),
+    admin_id       uuid references users (user_id) on delete cascade not null,
+    key_name       varchar(50)                                       not null,
+    --明文的前几位, 便于管理员辨认
+    key_prefix     varchar(12)                                       not null,
+    key_hash       char(64) unique                                   not null,
+    create_time    timestamp without time zone                       not null default now(),
+    last_used_time timestamp without time zone,
+    revoked_time   timestamp without time zone
+);
+create index on api_keys (admin_id);
+--API密钥的调用记录
+create table if not exists "api_key_logs"
+(
+    log_id      uuid primary key                                     not null default uuid_generate_v4(),
+    key_id      uuid references api_keys (key_id) on delete cascade not null,
+    method      varchar(10)                                          not null,
+    path        varchar(200)                                         not null,
+    status      int2                                                 not null,
+    ip          varchar(64)                                          not null,
+    create_time timestamp without time zone                          not null default now()
+);
+create index on api_key_logs (key_id, create_time);
+-----------------------------------------------
+--管理端的写操作审计, 包括被拒绝的请求; 请求体只保存SHA-256摘要
+create table if not exists "audit_log"
+(
+    log_id         uuid primary key                not null default uuid_generate_v4(),
+    actor_id       uuid references users (user_id) not null,
+    ip             varchar(64)                     not null,
+    method         varchar(10)                     not null,
+    route          varchar(200)                    not null,
+    status         int2                            not null,
+    --上传文件等multipart请求不计算摘要
+    payload_digest char(64),
+    create_time    timestamp without time zone     not null default now()
+);
+create index on audit_log (create_time);
+create index on audit_log (actor_id, create_time);
@@ -0,0 +1,79 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::api_key::{ApiKeyCreate, ApiKeyOp, ApiKeyRevoke, ApiKeyUsageQuery},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/apikey/* 挂载中");
+    Router::new()
+        .route("/create", post(create))
+        .route("/list", get(list))
+        .route("/revoke", post(revoke))
+        .route("/usage", get(usage))
+}
+
+//明文密钥只在创建时返回
+async fn create(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ApiKeyCreate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (key, secret) = ApiKeyOp::create(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"创建成功, 请妥善保存密钥, 关闭后无法再次查看",
+        "data":{
+            "key":key,
+            "api_key":secret
+        }
+    })))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let keys = ApiKeyOp::list(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":keys
+    })))
+}
+
+async fn revoke(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ApiKeyRevoke>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    ApiKeyOp::revoke(auth.user.user_id, schema.key_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已吊销",
+        "data":null
+    })))
+}
+
+//密钥的调用记录
+async fn usage(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ApiKeyUsageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let logs = ApiKeyOp::usage(auth.user.user_id, query.key_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":logs
+    })))
+}
@@ -0,0 +1,39 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::audit::{AuditOp, AuditQuery},
+};
+use axum::{
+    extract::{Query, State},
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/audit/* 挂载中");
+    Router::new()
+        .route("/list", get(list))
+        .layer(middleware::from_fn(crate::utils::auth::super_auth))
+}
+
+//按操作人、方法、路径前缀、状态码与时间筛选, 分页
+async fn list(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let page = query.page;
+    let (total, logs) = AuditOp::list(query, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "total":total,
+            "page":page,
+            "logs":logs
+        }
+    })))
+}
@@ -0,0 +1,77 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::blacklist::{BlacklistAdd, BlacklistDel, BlacklistOp, BlacklistQuery},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/blacklist/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .route("/add", post(add))
+        .route("/del", delete(del))
+}
+
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BlacklistQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let list = BlacklistOp::list::<String>(auth.user.user_id, &query, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":list
+    })))
+}
+
+//加入场馆黑名单, 已在名单中时更新原因与期限
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<BlacklistAdd>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let entry = BlacklistOp::add::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})将用户({})加入场馆({})黑名单",
+        auth.user.user_name, entry.user_id, entry.venue_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已加入黑名单",
+        "data":{
+            "venue_id":entry.venue_id,
+            "user_id":entry.user_id,
+            "reason":entry.reason,
+            "expire_time":entry.expire_time,
+            "create_time":entry.create_time
+        }
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<BlacklistDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (venue_id, user_id) = (schema.venue_id, schema.user_id);
+    BlacklistOp::remove::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})将用户({})移出场馆({})黑名单",
+        auth.user.user_name, user_id, venue_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已移出黑名单",
+        "data":null
+    })))
+}
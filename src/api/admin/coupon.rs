@@ -0,0 +1,111 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        coupon::{CouponIssue, CouponOp, CouponTemplateDel, CouponTemplateSave},
+        db::sea_orm_active_enums::CouponKind,
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::prelude::Decimal;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/coupon/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .route("/save", post(save))
+        .route("/del", delete(del))
+        .route("/issue", post(issue))
+}
+
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let templates = CouponOp::templates::<String>(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":templates
+    })))
+}
+
+//新建或修改优惠券模板, 满减按金额抵扣, 折扣按百分比抵扣
+async fn save(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CouponTemplateSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.name.trim().is_empty() {
+        return Err(HandleErr::BadRequest(-1, "名称不能为空".to_string()));
+    }
+    if schema.value <= Decimal::ZERO
+        || (schema.kind == CouponKind::Percent && schema.value >= Decimal::ONE_HUNDRED)
+    {
+        return Err(HandleErr::BadRequest(-1, "优惠额度无效".to_string()));
+    }
+    if schema.min_amount < Decimal::ZERO || schema.max_discount.is_some_and(|e| e <= Decimal::ZERO)
+    {
+        return Err(HandleErr::BadRequest(-1, "使用门槛无效".to_string()));
+    }
+    if schema.valid_from >= schema.valid_to {
+        return Err(HandleErr::BadRequest(-1, "有效期无效".to_string()));
+    }
+    let template = CouponOp::save::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})保存优惠券模板({})",
+        auth.user.user_name, template.template.template_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"保存成功",
+        "data":template
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CouponTemplateDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CouponOp::delete::<String>(schema.template_id, auth.user.user_id, &state).await?;
+    info!(
+        "admin({})删除优惠券模板({})",
+        auth.user.user_name, schema.template_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"删除成功",
+        "data":null
+    })))
+}
+
+//向指定用户批量发放, 同一次发放的优惠券批次号相同
+async fn issue(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CouponIssue>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let template_id = schema.template_id;
+    let (batch_id, issued) = CouponOp::issue::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})发放优惠券({})共{}张, 批次{}",
+        auth.user.user_name, template_id, issued, batch_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"发放成功",
+        "data":{
+            "batch_id":batch_id,
+            "issued":issued
+        }
+    })))
+}
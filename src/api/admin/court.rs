@@ -1,25 +1,40 @@
 use crate::{
     appstate::AppState,
     error::HandleErr,
-    module::court::{CourtAdd, CourtDel, CourtOp, CourtSave},
+    module::court::{
+        CourtAdd, CourtBatchDel, CourtBatchResult, CourtBatchUpdate, CourtClone, CourtClose,
+        CourtCloseAffected, CourtDel, CourtImportErr, CourtImportRow, CourtOp, CourtSave,
+        CourtSearch, CourtStatusSet, CourtTransfer,
+    },
+    module::order::{group::GroupOp, refund::RefundOp, state, OrderOp},
+    module::venue::VenueOp,
     module::{
         court::{CourtAdminSchema, CourtUpdate},
         db,
-        db::prelude::{self, Courts},
+        db::prelude::{self, CourtOpenHours, CourtPriceRules, CourtTagLinks, Courts, Users},
+        db::sea_orm_active_enums::{CourtStatus, RefundReason},
     },
-    utils::auth::JWTAuthMiddleware,
+    utils::{auth::JWTAuthMiddleware, qrcode},
 };
 use axum::{
-    extract::State,
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    middleware,
     response::IntoResponse,
     routing::{delete, get, post},
     Extension, Json, Router,
 };
 use prelude::Orders;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::{Expr, LikeExpr};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, Condition, EntityTrait, IntoActiveModel,
+    QueryFilter, Set, TransactionTrait,
+};
 use serde_json::json;
+use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 pub fn router() -> Router<Arc<AppState>> {
     info!("/court/* 挂载中");
@@ -28,6 +43,19 @@ pub fn router() -> Router<Arc<AppState>> {
         .route("/del", delete(del))
         .route("/all", get(all))
         .route("/update", post(update))
+        .route("/search", get(search))
+        .route("/import", post(import))
+        .route("/status", post(status))
+        .route("/close_and_cancel", post(close_and_cancel))
+        .route("/clone", post(clone))
+        .route("/batch_update", post(batch_update))
+        .route("/batch_del", delete(batch_del))
+        .route("/history/:court_id", get(history))
+        .route("/qrcode/:court_id", get(qrcode))
+        .route(
+            "/transfer",
+            post(transfer).layer(middleware::from_fn(crate::utils::auth::super_auth)),
+        )
 }
 
 async fn add(
@@ -52,25 +80,22 @@ async fn add(
             Err(HandleErr::BadRequest(-1, "球场名重复".to_string()))
         })?;
 
+    if let Some(venue_id) = schema.venue_id {
+        VenueOp::owned::<String>(venue_id, auth.user.user_id, &state).await?;
+    }
+    let court_name = schema.court_name.clone();
     let court = CourtOp::save(
         CourtSave {
             court_id: None,
             admin_id: Some(auth.user.user_id),
-            court_name: schema.court_name.clone(),
-            location: schema.location,
-            label: schema.label,
-            price_per_hour: schema.price_per_hour,
-            open_time: schema.open_time,
-            close_time: schema.close_time,
+            ..schema
         },
+        auth.user.user_id,
         &state,
     )
     .await?;
 
-    info!(
-        "admin({})添加球场({})",
-        auth.user.user_name, schema.court_name
-    );
+    info!("admin({})添加球场({})", auth.user.user_name, court_name);
 
     debug!("pass court add");
 
@@ -91,7 +116,8 @@ async fn del(
         .filter(
             db::orders::Column::CourtId
                 .eq(schema.court_id)
-                .and(db::orders::Column::AptEnd.gte(now)),
+                .and(db::orders::Column::AptEnd.gte(now))
+                .and(db::orders::Column::Status.is_not_in(state::RELEASED)),
         )
         .one(&state.db)
         .await
@@ -104,36 +130,59 @@ async fn del(
             Err(HandleErr::BadRequest(-1, "球场仍有未完成的订单").into())
         })?;
 
-    let rows_affected = Courts::delete(db::courts::ActiveModel {
-        admin_id: Set(auth.user.user_id),
-        court_id: Set(schema.court_id),
-        ..Default::default()
-    })
-    .exec(&state.db)
-    .await
-    .map_err(|err| {
+    let txn = state.db.begin().await.map_err(|err| {
         let id = Uuid::new_v4();
         error!("{} >>>> {}", id, err.to_string());
-        HandleErr::ServerInnerErr::<String>(id)
-    })?
-    .rows_affected;
-
-    if rows_affected == 0 {
-        Err(HandleErr::BadRequest(-1, "没有球场被删除").into())
-    } else {
-        info!(
-            "admin({})删除球场({})",
-            auth.user.user_name, schema.court_id
-        );
-        Ok(Json(json!({"code":0,"msg":"球场删除成功"})))
-    }
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let court = Courts::find()
+        .filter(
+            db::courts::Column::CourtId
+                .eq(schema.court_id)
+                .and(db::courts::Column::AdminId.eq(auth.user.user_id)),
+        )
+        .one(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "没有球场被删除".to_string()))?;
+    Courts::delete_by_id(court.court_id)
+        .exec(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    CourtOp::audit(
+        court.court_id,
+        auth.user.user_id,
+        "delete",
+        &json!(court),
+        &serde_json::Value::Null,
+        &txn,
+    )
+    .await?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})删除球场({})",
+        auth.user.user_name, schema.court_id
+    );
+    Ok(Json(json!({"code":0,"msg":"球场删除成功"})))
 }
 
-async fn all(
+pub(super) async fn all(
     Extension(auth): Extension<JWTAuthMiddleware>,
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, HandleErr<String>> {
-    let courts: Vec<_> = Courts::find()
+    let mut courts: Vec<_> = Courts::find()
         .filter(db::courts::Column::AdminId.eq(auth.user.user_id))
         .all(&state.db)
         .await
@@ -143,17 +192,9 @@ async fn all(
             HandleErr::ServerInnerErr::<String>(id)
         })?
         .into_iter()
-        .map(|e| CourtAdminSchema {
-            court_id: Some(e.court_id),
-            admin_id: Some(auth.user.user_id),
-            court_name: e.court_name,
-            location: e.location,
-            label: e.label,
-            price_per_hour: e.price_per_hour,
-            open_time: e.open_time,
-            close_time: e.close_time,
-        })
+        .map(CourtAdminSchema::from)
         .collect();
+    CourtOp::fill(&mut courts, &state).await?;
     debug!("pass court all");
     Ok(Json(json!({
         "code":0,
@@ -181,17 +222,15 @@ async fn update(
             HandleErr::ServerInnerErr::<String>(id)
         })?
         .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string()))?;
+    if let Some(venue_id) = schema.venue_id {
+        VenueOp::owned::<String>(venue_id, auth.user.user_id, &state).await?;
+    }
     let court = CourtOp::save::<String>(
         CourtSave {
-            court_id: schema.court_id,
             admin_id: Some(auth.user.user_id),
-            court_name: schema.court_name,
-            location: schema.location,
-            label: schema.label,
-            price_per_hour: schema.price_per_hour,
-            open_time: schema.open_time,
-            close_time: schema.close_time,
+            ..schema
         },
+        auth.user.user_id,
         &state,
     )
     .await?;
@@ -204,3 +243,813 @@ async fn update(
         "data":court
     })))
 }
+
+//模糊匹配的LIKE模式, 转义输入中的通配符, 按字面匹配
+fn contains(term: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+async fn search(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<CourtSearch>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let mut cond = Condition::all().add(db::courts::Column::AdminId.eq(auth.user.user_id));
+    if let Some(name) = schema.name.filter(|e| !e.is_empty()) {
+        cond = cond.add(
+            Expr::col((db::courts::Entity, db::courts::Column::CourtName))
+                .like(LikeExpr::new(contains(&name)).escape('\\')),
+        );
+    }
+    if let Some(label) = schema.label.filter(|e| !e.is_empty()) {
+        cond = cond.add(db::courts::Column::Label.eq(label));
+    }
+    if let Some(min_price) = schema.min_price {
+        cond = cond.add(db::courts::Column::PricePerHour.gte(min_price));
+    }
+    if let Some(max_price) = schema.max_price {
+        cond = cond.add(db::courts::Column::PricePerHour.lte(max_price));
+    }
+    if let Some(sport_type) = schema.sport_type {
+        cond = cond.add(db::courts::Column::SportType.eq(sport_type));
+    }
+
+    let mut courts: Vec<_> = Courts::find()
+        .filter(cond)
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .into_iter()
+        .map(CourtAdminSchema::from)
+        .collect();
+    CourtOp::fill(&mut courts, &state).await?;
+    debug!("pass court search");
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":courts
+    })))
+}
+
+//CSV导入, 任意一行校验失败则整体不导入
+async fn import(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let mut data = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?
+    {
+        if field.name() == Some("file") {
+            data = Some(
+                field
+                    .bytes()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?,
+            );
+        }
+    }
+    let data = data.ok_or(HandleErr::BadRequest(-1, "未上传文件".to_string()))?;
+
+    //已有球场名
+    let mut names: HashSet<String> = Courts::find()
+        .filter(db::courts::Column::AdminId.eq(auth.user.user_id))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .into_iter()
+        .map(|e| e.court_name)
+        .collect();
+
+    let mut models = vec![];
+    let mut errs = vec![];
+    let mut reader = csv::Reader::from_reader(data.as_ref());
+    for (i, record) in reader.deserialize::<CourtImportRow>().enumerate() {
+        let row = i + 1;
+        let court = record
+            .map_err(|err| format!("格式错误: {}", err))
+            .and_then(CourtImportRow::validate)
+            .and_then(|e| {
+                if names.insert(e.court_name.clone()) {
+                    Ok(e)
+                } else {
+                    Err(format!("球场名重复: {}", e.court_name))
+                }
+            });
+        match court {
+            Ok(e) => models.push(db::courts::ActiveModel {
+                admin_id: Set(auth.user.user_id),
+                court_name: Set(e.court_name),
+                location: Set(e.location),
+                label: Set(e.label),
+                price_per_hour: Set(e.price_per_hour),
+                open_time: Set(e.open_time),
+                close_time: Set(e.close_time),
+                ..Default::default()
+            }),
+            Err(msg) => errs.push(CourtImportErr { row, msg }),
+        }
+    }
+
+    if !errs.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code":-1,
+                "msg":"导入失败",
+                "data":errs
+            })),
+        ));
+    }
+    if models.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "文件中没有数据".to_string()));
+    }
+
+    let count = models.len();
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    Courts::insert_many(models)
+        .exec(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+
+    info!("admin({})导入{}个球场", auth.user.user_name, count);
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "code":0,
+            "msg":"导入成功",
+            "data":{"count":count}
+        })),
+    ))
+}
+
+async fn status(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtStatusSet>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let court = CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    db::courts::ActiveModel {
+        court_id: Set(schema.court_id),
+        status: Set(schema.status.clone()),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    CourtOp::audit(
+        court.court_id,
+        auth.user.user_id,
+        "status",
+        &json!({"status":court.status}),
+        &json!({"status":schema.status}),
+        &txn,
+    )
+    .await?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})将球场({})状态设为{:?}",
+        auth.user.user_name, schema.court_id, schema.status
+    );
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+//停用球场, 同一事务内取消未开始的订单并退款, 提交后向受影响的用户发送致歉通知
+async fn close_and_cancel(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtClose>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let court = CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let reason = schema
+        .reason
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .unwrap_or("球场停用".to_string());
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    db::courts::ActiveModel {
+        court_id: Set(court.court_id),
+        status: Set(CourtStatus::Closed),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    CourtOp::audit(
+        court.court_id,
+        auth.user.user_id,
+        "status",
+        &json!({"status":court.status}),
+        &json!({"status":CourtStatus::Closed}),
+        &txn,
+    )
+    .await?;
+    let cancelled = OrderOp::cancel_by_court(court.court_id, reason.clone(), &state, &txn).await?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})停用球场({}), 取消{}个订单",
+        auth.user.user_name,
+        court.court_name,
+        cancelled.len()
+    );
+    for (order, refund) in &cancelled {
+        if *refund <= Decimal::ZERO {
+            continue;
+        }
+        if order.pay_amount.is_some() {
+            RefundOp::dispatch_order::<String>(order.order_id, &state).await?;
+        } else {
+            GroupOp::refund_paid(order, &reason, RefundReason::VenueIssue, &state).await;
+        }
+    }
+    let users = Users::find()
+        .filter(db::users::Column::UserId.is_in(cancelled.iter().map(|(e, _)| e.user_id)))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    let mut affected = vec![];
+    for (order, refund) in cancelled {
+        let user = users.iter().find(|e| e.user_id == order.user_id);
+        //代客预约的订单通知顾客本人
+        let (user_name, phone) = match (&order.customer_name, user) {
+            (Some(name), _) => (
+                name.clone(),
+                order.customer_phone.clone().unwrap_or_default(),
+            ),
+            (None, Some(user)) => (
+                user.user_name.clone(),
+                user.phone.clone().unwrap_or_default(),
+            ),
+            (None, None) => (String::new(), String::new()),
+        };
+        let mut content = format!(
+            "很抱歉, 您预约的{}({})因{}无法使用, 订单已取消",
+            court.court_name,
+            order.apt_start.format("%m-%d %H:%M"),
+            reason
+        );
+        if refund > Decimal::ZERO {
+            content.push_str(&format!(", {:.2}元将原路退回", refund));
+        }
+        let notified = !phone.is_empty()
+            && state
+                .notifier
+                .send(&phone, "订单取消通知", &content)
+                .await
+                .inspect_err(|err| warn!("订单({})取消通知发送失败: {}", order.order_id, err))
+                .is_ok();
+        affected.push(CourtCloseAffected {
+            order_id: order.order_id,
+            user_id: order.user_id,
+            user_name,
+            phone,
+            apt_start: order.apt_start,
+            refund,
+            notified,
+        });
+    }
+    let refunded: Decimal = affected.iter().map(|e| e.refund).sum();
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":{
+            "cancelled":affected.len(),
+            "refunded":refunded,
+            "affected":affected
+        }
+    })))
+}
+
+//复制球场及其价格时段/营业时间/标签
+async fn clone(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtClone>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let court_name = schema.court_name.trim().to_string();
+    if court_name.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "球场名不能为空".to_string()));
+    }
+    let src = CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    Courts::find()
+        .filter(
+            db::courts::Column::CourtName
+                .eq(&court_name)
+                .and(db::courts::Column::AdminId.eq(auth.user.user_id)),
+        )
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .map_or(Ok(()), |_| {
+            Err(HandleErr::BadRequest(-1, "球场名重复".to_string()))
+        })?;
+
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let court = db::courts::ActiveModel {
+        court_id: NotSet,
+        court_name: Set(court_name),
+        rating: Set(0.0),
+        rating_count: Set(0),
+        update_time: NotSet,
+        ..src.clone().into_active_model()
+    }
+    .insert(&txn)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+
+    let rules: Vec<_> = CourtPriceRules::find()
+        .filter(db::court_price_rules::Column::CourtId.eq(src.court_id))
+        .all(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .into_iter()
+        .map(|e| db::court_price_rules::ActiveModel {
+            rule_id: NotSet,
+            court_id: Set(court.court_id),
+            ..e.into_active_model()
+        })
+        .collect();
+    let hours: Vec<_> = CourtOpenHours::find()
+        .filter(db::court_open_hours::Column::CourtId.eq(src.court_id))
+        .all(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .into_iter()
+        .map(|e| db::court_open_hours::ActiveModel {
+            court_id: Set(court.court_id),
+            ..e.into_active_model()
+        })
+        .collect();
+    let links: Vec<_> = CourtTagLinks::find()
+        .filter(db::court_tag_links::Column::CourtId.eq(src.court_id))
+        .all(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .into_iter()
+        .map(|e| db::court_tag_links::ActiveModel {
+            court_id: Set(court.court_id),
+            tag_id: Set(e.tag_id),
+        })
+        .collect();
+
+    if !rules.is_empty() {
+        CourtPriceRules::insert_many(rules)
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+    }
+    if !hours.is_empty() {
+        CourtOpenHours::insert_many(hours)
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+    }
+    if !links.is_empty() {
+        CourtTagLinks::insert_many(links)
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+    }
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+
+    info!(
+        "admin({})复制球场({})为({})",
+        auth.user.user_name, src.court_name, court.court_name
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"球场复制成功",
+        "data":court
+    })))
+}
+
+//批量修改, 每项使用独立的保存点, 失败项回滚不影响其他项
+async fn batch_update(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtBatchUpdate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let mut results = vec![];
+    for item in schema.items {
+        let court_id = item.court_id;
+        //每项在各自的保存点中执行, 失败时只回滚该项, 不影响事务中的其他项
+        let result: Result<(), String> = async {
+            let savepoint = txn.begin().await.map_err(|err| err.to_string())?;
+            let court = Courts::find()
+                .filter(
+                    db::courts::Column::CourtId
+                        .eq(court_id)
+                        .and(db::courts::Column::AdminId.eq(auth.user.user_id)),
+                )
+                .one(&savepoint)
+                .await
+                .map_err(|err| err.to_string())?
+                .ok_or("球场不存在".to_string())?;
+            if item.price_per_hour.is_some_and(|e| e <= Decimal::ZERO) {
+                return Err("价格须大于0".to_string());
+            }
+            if item.open_time.unwrap_or(court.open_time)
+                >= item.close_time.unwrap_or(court.close_time)
+            {
+                return Err("开放时间须早于关闭时间".to_string());
+            }
+            let old = json!(court);
+            let mut model = court.into_active_model();
+            if let Some(label) = item.label {
+                model.label = Set(label);
+            }
+            if let Some(price) = item.price_per_hour {
+                model.price_per_hour = Set(price);
+            }
+            if let Some(open_time) = item.open_time {
+                model.open_time = Set(open_time);
+            }
+            if let Some(close_time) = item.close_time {
+                model.close_time = Set(close_time);
+            }
+            if let Some(status) = item.status {
+                model.status = Set(status);
+            }
+            let court = model
+                .update(&savepoint)
+                .await
+                .map_err(|err| err.to_string())?;
+            CourtOp::audit(
+                court_id,
+                auth.user.user_id,
+                "update",
+                &old,
+                &json!(court),
+                &savepoint,
+            )
+            .await
+            .map_err(|_: HandleErr<String>| "记录修改失败".to_string())?;
+            savepoint.commit().await.map_err(|err| err.to_string())
+        }
+        .await;
+        results.push(match result {
+            Ok(()) => CourtBatchResult {
+                court_id,
+                success: true,
+                msg: "修改成功".to_string(),
+            },
+            Err(msg) => {
+                warn!("批量修改球场({})失败: {}", court_id, msg);
+                CourtBatchResult {
+                    court_id,
+                    success: false,
+                    msg,
+                }
+            }
+        });
+    }
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})批量修改{}个球场",
+        auth.user.user_name,
+        results.iter().filter(|e| e.success).count()
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作完成",
+        "data":results
+    })))
+}
+
+async fn batch_del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtBatchDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let now = chrono::Utc::now().naive_utc();
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let mut results = vec![];
+    for court_id in schema.court_ids {
+        //每项在各自的保存点中执行, 失败时只回滚该项, 不影响事务中的其他项
+        let result: Result<(), String> = async {
+            let savepoint = txn.begin().await.map_err(|err| err.to_string())?;
+            let pending = Orders::find()
+                .filter(
+                    db::orders::Column::CourtId
+                        .eq(court_id)
+                        .and(db::orders::Column::AptEnd.gte(now))
+                        .and(db::orders::Column::Status.is_not_in(state::RELEASED)),
+                )
+                .one(&savepoint)
+                .await
+                .map_err(|err| err.to_string())?;
+            if pending.is_some() {
+                return Err("球场仍有未完成的订单".to_string());
+            }
+            let court = Courts::find()
+                .filter(
+                    db::courts::Column::CourtId
+                        .eq(court_id)
+                        .and(db::courts::Column::AdminId.eq(auth.user.user_id)),
+                )
+                .one(&savepoint)
+                .await
+                .map_err(|err| err.to_string())?
+                .ok_or("球场不存在".to_string())?;
+            Courts::delete_by_id(court_id)
+                .exec(&savepoint)
+                .await
+                .map_err(|err| err.to_string())?;
+            CourtOp::audit(
+                court_id,
+                auth.user.user_id,
+                "delete",
+                &json!(court),
+                &serde_json::Value::Null,
+                &savepoint,
+            )
+            .await
+            .map_err(|_: HandleErr<String>| "记录删除失败".to_string())?;
+            savepoint.commit().await.map_err(|err| err.to_string())
+        }
+        .await;
+        results.push(match result {
+            Ok(()) => CourtBatchResult {
+                court_id,
+                success: true,
+                msg: "删除成功".to_string(),
+            },
+            Err(msg) => {
+                warn!("批量删除球场({})失败: {}", court_id, msg);
+                CourtBatchResult {
+                    court_id,
+                    success: false,
+                    msg,
+                }
+            }
+        });
+    }
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})批量删除{}个球场",
+        auth.user.user_name,
+        results.iter().filter(|e| e.success).count()
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作完成",
+        "data":results
+    })))
+}
+
+//转移球场归属(超级管理员)
+//订单通过court_id关联球场, 随球场一并转移; 场馆与标签属于原管理员, 转移时解除关联
+async fn transfer(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtTransfer>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let court = Courts::find_by_id(schema.court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string()))?;
+    let admin = Users::find_by_id(schema.admin_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .filter(|e| e.is_admin)
+        .ok_or(HandleErr::BadRequest(-1, "目标管理员不存在".to_string()))?;
+    if court.admin_id == admin.user_id {
+        return Err(HandleErr::BadRequest(-1, "球场已属于该管理员".to_string()));
+    }
+    Courts::find()
+        .filter(
+            db::courts::Column::CourtName
+                .eq(&court.court_name)
+                .and(db::courts::Column::AdminId.eq(admin.user_id)),
+        )
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .map_or(Ok(()), |_| {
+            Err(HandleErr::BadRequest(
+                -1,
+                "目标管理员已有同名球场".to_string(),
+            ))
+        })?;
+
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    CourtTagLinks::delete_many()
+        .filter(db::court_tag_links::Column::CourtId.eq(court.court_id))
+        .exec(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    let court = db::courts::ActiveModel {
+        court_id: Set(court.court_id),
+        admin_id: Set(admin.user_id),
+        venue_id: Set(None),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+
+    info!(
+        "super({})将球场({})转移给admin({})",
+        auth.user.user_name, court.court_id, admin.user_name
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"球场转移成功",
+        "data":court
+    })))
+}
+
+async fn history(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let history = CourtOp::history::<String>(court_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":history
+    })))
+}
+
+//球场签到二维码, 有效期一年, 当天生成的缓存在内存中
+async fn qrcode(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let today = chrono::Utc::now().date_naive();
+    let png = match state.qrcodes.get(court_id, today) {
+        Some(png) => png,
+        None => {
+            let png = qrcode::sign(court_id, &state.cfg.tokencfg.access_prikey)
+                .map_err(anyhow::Error::from)
+                .and_then(|token| qrcode::png(&token))
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            state.qrcodes.put(court_id, today, png.clone());
+            png
+        }
+    };
+    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+}
+
+#[test]
+fn contains_test() {
+    assert_eq!(contains("a_b%c\\d"), "%a\\_b\\%c\\\\d%");
+    assert_eq!(contains("球场"), "%球场%");
+}
@@ -0,0 +1,154 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{
+            addon::{AddonDel, AddonOp, AddonSave},
+            CourtOp,
+        },
+        db::{self, prelude::*},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, Set};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/addon/* 挂载中");
+    Router::new()
+        .route("/:court_id", get(list))
+        .route("/add", post(add))
+        .route("/update", post(update))
+        .route("/del", delete(del))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let addons = AddonOp::list::<String>(court_id, false, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":addons
+    })))
+}
+
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<AddonSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let addon = AddonOp::save::<String>(
+        AddonSave {
+            addon_id: None,
+            ..schema
+        },
+        &state,
+    )
+    .await?;
+    info!(
+        "admin({})为球场({})添加附加项目({})",
+        auth.user.user_name, addon.court_id, addon.addon_name
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"添加成功",
+        "data":addon
+    })))
+}
+
+async fn update(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<AddonSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let addon_id = schema
+        .addon_id
+        .ok_or(HandleErr::BadRequest(-1, "缺少addon_id".to_string()))?;
+    let addon = CourtAddons::find_by_id(addon_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "附加项目不存在".to_string()))?;
+    if addon.court_id != schema.court_id {
+        return Err(HandleErr::BadRequest(-1, "court_id无效".to_string()));
+    }
+    CourtOp::owned::<String>(addon.court_id, auth.user.user_id, &state).await?;
+    let addon = AddonOp::save::<String>(schema, &state).await?;
+    debug!("pass court addon update");
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":addon
+    })))
+}
+
+//已被订单使用的项目只下架, 保留历史订单记录
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<AddonDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let addon = CourtAddons::find_by_id(schema.addon_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "附加项目不存在".to_string()))?;
+    CourtOp::owned::<String>(addon.court_id, auth.user.user_id, &state).await?;
+    let used = OrderAddons::find()
+        .filter(db::order_addons::Column::AddonId.eq(addon.addon_id))
+        .count(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    if used > 0 {
+        CourtAddons::update(db::court_addons::ActiveModel {
+            addon_id: Set(addon.addon_id),
+            is_active: Set(false),
+            ..Default::default()
+        })
+        .exec(&state.db)
+        .await
+    } else {
+        CourtAddons::delete_by_id(addon.addon_id)
+            .exec(&state.db)
+            .await
+            .map(|_| addon.clone())
+    }
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})删除附加项目({})",
+        auth.user.user_name, addon.addon_name
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":if used > 0 { "已被订单使用, 已下架" } else { "删除成功" }
+    })))
+}
@@ -0,0 +1,150 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{
+            calendar::{BlockAdd, BlockDel, CalendarOp, CalendarQuery},
+            CourtOp,
+        },
+        db::{self, prelude::*},
+        order::state,
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/calendar/* 挂载中");
+    Router::new()
+        .route("/:court_id", get(calendar))
+        .route("/block", post(block))
+        .route("/unblock", delete(unblock))
+}
+
+//时间段内的屏蔽记录与订单
+async fn calendar(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+    Query(schema): Query<CalendarQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let blocks = CalendarOp::blocks::<String>(court_id, schema.from, schema.to, &state).await?;
+    let orders = Orders::find()
+        .filter(
+            db::orders::Column::CourtId
+                .eq(court_id)
+                .and(db::orders::Column::AptStart.lt(schema.to))
+                .and(db::orders::Column::AptEnd.gt(schema.from))
+                .and(db::orders::Column::Status.is_not_in(state::RELEASED)),
+        )
+        .order_by_asc(db::orders::Column::AptStart)
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "blocks":blocks,
+            "orders":orders
+        }
+    })))
+}
+
+async fn block(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<BlockAdd>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.block_start >= schema.block_end {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "开始时间须早于结束时间".to_string(),
+        ));
+    }
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    //已有订单需先处理
+    Orders::find()
+        .filter(
+            db::orders::Column::CourtId
+                .eq(schema.court_id)
+                .and(db::orders::Column::AptStart.lt(schema.block_end))
+                .and(db::orders::Column::AptEnd.gt(schema.block_start))
+                .and(db::orders::Column::Status.is_not_in(state::RELEASED)),
+        )
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .map_or(Ok(()), |_| {
+            Err(HandleErr::BadRequest(-1, "与已有订单冲突".to_string()))
+        })?;
+
+    let block = db::court_blocks::ActiveModel {
+        court_id: Set(schema.court_id),
+        block_start: Set(schema.block_start),
+        block_end: Set(schema.block_end),
+        reason: Set(schema.reason),
+        ..Default::default()
+    }
+    .insert(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})屏蔽球场({})时段 {} ~ {}",
+        auth.user.user_name, block.court_id, block.block_start, block.block_end
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":block
+    })))
+}
+
+async fn unblock(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<BlockDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let block = CourtBlocks::find_by_id(schema.block_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "屏蔽记录不存在".to_string()))?;
+    CourtOp::owned::<String>(block.court_id, auth.user.user_id, &state).await?;
+    CourtBlocks::delete_by_id(block.block_id)
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    debug!("pass court unblock");
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
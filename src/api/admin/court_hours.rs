@@ -0,0 +1,126 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{
+            open_hours::{OpenHoursDel, OpenHoursSet},
+            CourtOp,
+        },
+        db::{self, prelude::*},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::{sea_query::OnConflict, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/hours/* 挂载中");
+    Router::new()
+        .route("/:court_id", get(list))
+        .route("/set", post(set))
+        .route("/del", delete(del))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let hours = CourtOpenHours::find()
+        .filter(db::court_open_hours::Column::CourtId.eq(court_id))
+        .order_by_asc(db::court_open_hours::Column::Weekday)
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":hours
+    })))
+}
+
+async fn set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<OpenHoursSet>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !(1..=7).contains(&schema.weekday) {
+        return Err(HandleErr::BadRequest(-1, "weekday应在1~7之间".to_string()));
+    }
+    if schema.open_time >= schema.close_time {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "开放时间须早于关闭时间".to_string(),
+        ));
+    }
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+
+    CourtOpenHours::insert(db::court_open_hours::ActiveModel {
+        court_id: Set(schema.court_id),
+        weekday: Set(schema.weekday),
+        open_time: Set(schema.open_time),
+        close_time: Set(schema.close_time),
+        is_closed: Set(schema.is_closed),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            db::court_open_hours::Column::CourtId,
+            db::court_open_hours::Column::Weekday,
+        ])
+        .update_columns([
+            db::court_open_hours::Column::OpenTime,
+            db::court_open_hours::Column::CloseTime,
+            db::court_open_hours::Column::IsClosed,
+        ])
+        .to_owned(),
+    )
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+
+    info!(
+        "admin({})设置球场({})星期{}营业时间",
+        auth.user.user_name, schema.court_id, schema.weekday
+    );
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<OpenHoursDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let rows_affected = CourtOpenHours::delete_by_id((schema.court_id, schema.weekday))
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .rows_affected;
+    if rows_affected == 0 {
+        Err(HandleErr::BadRequest(-1, "没有数据行被删除".to_string()))
+    } else {
+        debug!("pass court hours del");
+        Ok(Json(json!({"code":0,"msg":"操作成功"})))
+    }
+}
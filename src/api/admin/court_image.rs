@@ -0,0 +1,265 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{CourtImageDel, CourtImageSort, CourtOp},
+        db::{self, prelude::*},
+        upload::{UploadOp, UploadPurpose},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, State},
+    response::IntoResponse,
+    routing::{delete, post},
+    Extension, Json, Router,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+    TransactionTrait,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+//单次上传大小上限
+const MAX_UPLOAD_SIZE: usize = 10 * 1024 * 1024;
+
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/image/* 挂载中");
+    Router::new()
+        .route("/upload", post(upload))
+        .route("/del", delete(del))
+        .route("/sort", post(sort))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+}
+
+//multipart字段: court_id, file(可多个)
+async fn upload(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let mut court_id = None;
+    let mut files = vec![];
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?
+    {
+        match field.name() {
+            Some("court_id") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                court_id = Some(
+                    Uuid::parse_str(&text)
+                        .map_err(|_| HandleErr::BadRequest(-1, "court_id无效".to_string()))?,
+                );
+            }
+            Some("file") => {
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                //按文件内容识别格式, 不信任客户端声明的Content-Type
+                files.push(UploadOp::validate(data.to_vec(), UploadPurpose::Court).await?);
+            }
+            _ => {}
+        }
+    }
+    let court_id = court_id.ok_or(HandleErr::BadRequest(-1, "缺少court_id".to_string()))?;
+    if files.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "未上传图片".to_string()));
+    }
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+
+    //新图片排在已有图片之后
+    let mut sort_order = CourtImages::find()
+        .filter(db::court_images::Column::CourtId.eq(court_id))
+        .order_by_desc(db::court_images::Column::SortOrder)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .map_or(0, |e| e.sort_order + 1);
+
+    let mut images = vec![];
+    for (data, content_type, ext) in files {
+        let key = format!("court/{}/{}.{}", court_id, Uuid::new_v4(), ext);
+        let url = state
+            .storage
+            .put(&key, content_type, data)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let image = db::court_images::ActiveModel {
+            court_id: Set(court_id),
+            url: Set(url),
+            storage_key: Set(key),
+            sort_order: Set(sort_order),
+            ..Default::default()
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        sort_order += 1;
+        images.push(image);
+    }
+    CourtOp::audit(
+        court_id,
+        auth.user.user_id,
+        "image",
+        &json!({"images":null}),
+        &json!({"images":images.iter().map(|e| &e.url).collect::<Vec<_>>()}),
+        &state.db,
+    )
+    .await?;
+
+    info!(
+        "admin({})为球场({})上传{}张图片",
+        auth.user.user_name,
+        court_id,
+        images.len()
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"上传成功",
+        "data":images
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtImageDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let image = CourtImages::find_by_id(schema.image_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "图片不存在".to_string()))?;
+    CourtOp::owned::<String>(image.court_id, auth.user.user_id, &state).await?;
+
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    CourtImages::delete_by_id(image.image_id)
+        .exec(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    CourtOp::audit(
+        image.court_id,
+        auth.user.user_id,
+        "image",
+        &json!({"images":[&image.url]}),
+        &json!({"images":null}),
+        &txn,
+    )
+    .await?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    //对象删除失败不影响结果
+    if let Err(err) = state.storage.delete(&image.storage_key).await {
+        error!(
+            "删除图片({})失败 >>>> {}",
+            image.storage_key,
+            err.to_string()
+        );
+    }
+    debug!("pass court image del");
+    Ok(Json(json!({"code":0,"msg":"图片删除成功"})))
+}
+
+async fn sort(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CourtImageSort>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let old: Vec<Uuid> = CourtImages::find()
+        .select_only()
+        .column(db::court_images::Column::ImageId)
+        .filter(db::court_images::Column::CourtId.eq(schema.court_id))
+        .order_by_asc(db::court_images::Column::SortOrder)
+        .into_tuple()
+        .all(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    for (i, image_id) in schema.image_ids.iter().enumerate() {
+        let rows_affected = CourtImages::update_many()
+            .col_expr(
+                db::court_images::Column::SortOrder,
+                sea_orm::sea_query::Expr::value(i as i32),
+            )
+            .filter(
+                db::court_images::Column::ImageId
+                    .eq(*image_id)
+                    .and(db::court_images::Column::CourtId.eq(schema.court_id)),
+            )
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("图片({})不存在", image_id),
+            ));
+        }
+    }
+    CourtOp::audit(
+        schema.court_id,
+        auth.user.user_id,
+        "image",
+        &json!({"sort":old}),
+        &json!({"sort":schema.image_ids}),
+        &txn,
+    )
+    .await?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    debug!("pass court image sort");
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
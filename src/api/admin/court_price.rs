@@ -0,0 +1,259 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::CourtOp,
+        db::prelude::*,
+        db::{self, court_price_overrides},
+        money,
+        pricing::{
+            demand::{DemandOp, DemandTiersSet},
+            PriceOverrideDel, PriceOverrideSet, PriceRuleDel, PriceRuleSave, PricingOp,
+        },
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::prelude::Decimal;
+use sea_orm::{sea_query::OnConflict, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/price/* 挂载中");
+    Router::new()
+        .route("/:court_id", get(list))
+        .route("/add", post(add))
+        .route("/update", post(update))
+        .route("/del", delete(del))
+        .route("/override/:court_id", get(override_list))
+        .route("/override/set", post(override_set))
+        .route("/override/del", delete(override_del))
+        .route("/demand/:court_id", get(demand_list))
+        .route("/demand/set", post(demand_set))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let rules = PricingOp::rules::<String>(court_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":rules
+    })))
+}
+
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PriceRuleSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let rule = PricingOp::save::<String>(
+        PriceRuleSave {
+            rule_id: None,
+            ..schema
+        },
+        &state,
+    )
+    .await?;
+    info!(
+        "admin({})为球场({})添加价格时段",
+        auth.user.user_name, rule.court_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"添加成功",
+        "data":rule
+    })))
+}
+
+async fn update(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PriceRuleSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rule_id = schema
+        .rule_id
+        .ok_or(HandleErr::BadRequest(-1, "缺少rule_id".to_string()))?;
+    let rule = CourtPriceRules::find_by_id(rule_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "价格时段不存在".to_string()))?;
+    //不允许把时段移到其他球场
+    if rule.court_id != schema.court_id {
+        return Err(HandleErr::BadRequest(-1, "court_id无效".to_string()));
+    }
+    CourtOp::owned::<String>(rule.court_id, auth.user.user_id, &state).await?;
+    let rule = PricingOp::save::<String>(schema, &state).await?;
+    debug!("pass court price update");
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":rule
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PriceRuleDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rule = CourtPriceRules::find_by_id(schema.rule_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "价格时段不存在".to_string()))?;
+    CourtOp::owned::<String>(rule.court_id, auth.user.user_id, &state).await?;
+    CourtPriceRules::delete_by_id(rule.rule_id)
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    debug!("pass court price del");
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+async fn override_list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let overrides = CourtPriceOverrides::find()
+        .filter(court_price_overrides::Column::CourtId.eq(court_id))
+        .order_by_asc(court_price_overrides::Column::OverrideDate)
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":overrides
+    })))
+}
+
+async fn override_set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PriceOverrideSet>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let price_per_hour = money::round(schema.price_per_hour);
+    if price_per_hour <= Decimal::ZERO {
+        return Err(HandleErr::BadRequest(-1, "价格须大于0".to_string()));
+    }
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    CourtPriceOverrides::insert(db::court_price_overrides::ActiveModel {
+        court_id: Set(schema.court_id),
+        override_date: Set(schema.override_date),
+        price_per_hour: Set(price_per_hour),
+        note: Set(schema.note),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([
+            court_price_overrides::Column::CourtId,
+            court_price_overrides::Column::OverrideDate,
+        ])
+        .update_columns([
+            court_price_overrides::Column::PricePerHour,
+            court_price_overrides::Column::Note,
+        ])
+        .to_owned(),
+    )
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})设置球场({}){}的特殊价格",
+        auth.user.user_name, schema.court_id, schema.override_date
+    );
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+async fn override_del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PriceOverrideDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let e = CourtPriceOverrides::find_by_id(schema.override_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "特殊价格不存在".to_string()))?;
+    CourtOp::owned::<String>(e.court_id, auth.user.user_id, &state).await?;
+    CourtPriceOverrides::delete_by_id(e.override_id)
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    debug!("pass court price override del");
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+async fn demand_list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let tiers = DemandOp::tiers::<String>(court_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":tiers
+    })))
+}
+
+async fn demand_set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<DemandTiersSet>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    let court_id = schema.court_id;
+    let tiers = DemandOp::set::<String>(schema, &state).await?;
+    info!("球场 {} 预订率调价设置 {} 档", court_id, tiers.len());
+    Ok(Json(json!({
+        "code":0,
+        "msg":"设置成功",
+        "data":tiers
+    })))
+}
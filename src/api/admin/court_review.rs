@@ -0,0 +1,83 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::court::{
+        review::{ReviewHide, ReviewOp, ReviewReport},
+        CourtOp,
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/review/* 挂载中");
+    Router::new()
+        .route("/:court_id", get(list))
+        .route("/hide", post(hide))
+        .route("/report", post(report))
+}
+
+//球场全部评价, 包括已隐藏的
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let reviews = ReviewOp::of_court::<String>(court_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":reviews
+    })))
+}
+
+//隐藏或恢复评价, 隐藏的评价不计入评分
+async fn hide(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ReviewHide>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let review = ReviewOp::hide::<String>(schema, auth.user.user_id, &state).await?;
+    info!(
+        "admin({}){}评价({})",
+        auth.user.user_name,
+        if review.hidden { "隐藏" } else { "恢复" },
+        review.review_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":review
+    })))
+}
+
+//举报不当评价
+async fn report(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ReviewReport>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let reason = schema.reason.trim();
+    if reason.is_empty() || reason.chars().count() > 200 {
+        return Err(HandleErr::BadRequest(-1, "举报原因无效".to_string()));
+    }
+    let review = ReviewOp::report::<String>(schema, auth.user.user_id, &state).await?;
+    info!(
+        "admin({})举报评价({})",
+        auth.user.user_name, review.review_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"举报成功",
+        "data":review
+    })))
+}
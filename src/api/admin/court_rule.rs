@@ -0,0 +1,107 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{
+            booking_rule::{BookingRuleOp, BookingRuleSet},
+            CourtOp,
+        },
+        db::{self, prelude::*},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use sea_orm::prelude::Decimal;
+use sea_orm::{sea_query::OnConflict, EntityTrait, Set};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/rule/* 挂载中");
+    Router::new()
+        .route("/:court_id", get(get_rule))
+        .route("/set", post(set))
+}
+
+async fn get_rule(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(court_id, auth.user.user_id, &state).await?;
+    let rule = BookingRuleOp::rule::<String>(court_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":rule
+    })))
+}
+
+async fn set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<BookingRuleSet>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if [
+        schema.min_minutes,
+        schema.max_minutes,
+        schema.max_active_orders,
+    ]
+    .iter()
+    .flatten()
+    .any(|e| *e <= 0)
+        || schema.max_advance_days.is_some_and(|e| e < 0)
+        || schema.free_cancel_minutes.is_some_and(|e| e < 0)
+        || !(Decimal::ZERO..=Decimal::ONE).contains(&schema.cancel_fee_rate)
+    {
+        return Err(HandleErr::BadRequest(-1, "规则数值无效".to_string()));
+    }
+    if let (Some(min), Some(max)) = (schema.min_minutes, schema.max_minutes) {
+        if min > max {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "最短时长不能大于最长时长".to_string(),
+            ));
+        }
+    }
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    CourtBookingRules::insert(db::court_booking_rules::ActiveModel {
+        court_id: Set(schema.court_id),
+        min_minutes: Set(schema.min_minutes),
+        max_minutes: Set(schema.max_minutes),
+        max_advance_days: Set(schema.max_advance_days),
+        max_active_orders: Set(schema.max_active_orders),
+        free_cancel_minutes: Set(schema.free_cancel_minutes),
+        cancel_fee_rate: Set(schema.cancel_fee_rate),
+    })
+    .on_conflict(
+        OnConflict::column(db::court_booking_rules::Column::CourtId)
+            .update_columns([
+                db::court_booking_rules::Column::MinMinutes,
+                db::court_booking_rules::Column::MaxMinutes,
+                db::court_booking_rules::Column::MaxAdvanceDays,
+                db::court_booking_rules::Column::MaxActiveOrders,
+                db::court_booking_rules::Column::FreeCancelMinutes,
+                db::court_booking_rules::Column::CancelFeeRate,
+            ])
+            .to_owned(),
+    )
+    .exec(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})设置球场({})预约规则",
+        auth.user.user_name, schema.court_id
+    );
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
@@ -0,0 +1,251 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{
+            tag::{TagBind, TagDel, TagMerge, TagOp, TagSave},
+            CourtOp,
+        },
+        db::{self, prelude::*},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/court/tag/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .route("/add", post(add))
+        .route("/rename", post(rename))
+        .route("/merge", post(merge))
+        .route("/del", delete(del))
+        .route("/bind", post(bind))
+}
+
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let tags = CourtTags::find()
+        .filter(db::court_tags::Column::AdminId.eq(auth.user.user_id))
+        .order_by_asc(db::court_tags::Column::TagName)
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":tags
+    })))
+}
+
+//标签名在管理员范围内唯一
+async fn name_unused(
+    tag_name: &str,
+    admin_id: Uuid,
+    state: &AppState,
+) -> Result<(), HandleErr<String>> {
+    CourtTags::find()
+        .filter(
+            db::court_tags::Column::TagName
+                .eq(tag_name)
+                .and(db::court_tags::Column::AdminId.eq(admin_id)),
+        )
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .map_or(Ok(()), |_| {
+            Err(HandleErr::BadRequest(-1, "标签名重复".to_string()))
+        })
+}
+
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TagSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let tag_name = schema.tag_name.trim().to_string();
+    if tag_name.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "标签名不能为空".to_string()));
+    }
+    name_unused(&tag_name, auth.user.user_id, &state).await?;
+    let tag = db::court_tags::ActiveModel {
+        admin_id: Set(auth.user.user_id),
+        tag_name: Set(tag_name),
+        ..Default::default()
+    }
+    .insert(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!("admin({})添加标签({})", auth.user.user_name, tag.tag_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"添加成功",
+        "data":tag
+    })))
+}
+
+async fn rename(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TagSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let tag_id = schema
+        .tag_id
+        .ok_or(HandleErr::BadRequest(-1, "缺少tag_id".to_string()))?;
+    let tag_name = schema.tag_name.trim().to_string();
+    if tag_name.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "标签名不能为空".to_string()));
+    }
+    TagOp::owned::<String>(vec![tag_id], auth.user.user_id, &state).await?;
+    name_unused(&tag_name, auth.user.user_id, &state).await?;
+    let tag = db::court_tags::ActiveModel {
+        tag_id: Set(tag_id),
+        tag_name: Set(tag_name),
+        ..Default::default()
+    }
+    .update(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    debug!("pass court tag rename");
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":tag
+    })))
+}
+
+async fn merge(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TagMerge>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let from: Vec<_> = schema
+        .from
+        .into_iter()
+        .filter(|e| *e != schema.into)
+        .collect();
+    if from.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "没有需要合并的标签".to_string()));
+    }
+    let mut ids = from.clone();
+    ids.push(schema.into);
+    TagOp::owned::<String>(ids, auth.user.user_id, &state).await?;
+
+    let court_ids: Vec<_> = CourtTagLinks::find()
+        .filter(db::court_tag_links::Column::TagId.is_in(from.clone()))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .into_iter()
+        .map(|e| e.court_id)
+        .collect();
+
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    TagOp::link(court_ids, schema.into, &txn).await?;
+    //关联随标签级联删除
+    CourtTags::delete_many()
+        .filter(db::court_tags::Column::TagId.is_in(from))
+        .exec(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!("admin({})合并标签到({})", auth.user.user_name, schema.into);
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TagDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    TagOp::owned::<String>(vec![schema.tag_id], auth.user.user_id, &state).await?;
+    CourtTags::delete_by_id(schema.tag_id)
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    debug!("pass court tag del");
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
+
+async fn bind(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TagBind>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    TagOp::owned::<String>(schema.tag_ids.clone(), auth.user.user_id, &state).await?;
+
+    let txn = state.db.begin().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    CourtTagLinks::delete_many()
+        .filter(db::court_tag_links::Column::CourtId.eq(schema.court_id))
+        .exec(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    for tag_id in schema.tag_ids {
+        TagOp::link(vec![schema.court_id], tag_id, &txn).await?;
+    }
+    txn.commit().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    debug!("pass court tag bind");
+    Ok(Json(json!({"code":0,"msg":"操作成功"})))
+}
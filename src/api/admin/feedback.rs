@@ -0,0 +1,61 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::feedback::{FeedbackOp, FeedbackQuery, FeedbackUpdate},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/feedback/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .route("/status", post(status))
+}
+
+//反馈处理队列, 可按状态与类型筛选, 超级管理员可查看全部反馈
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<FeedbackQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (feedback, total) =
+        FeedbackOp::queue(auth.user.user_id, auth.user.is_super, &schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "feedback":feedback,
+            "total":total
+        }
+    })))
+}
+
+//推进反馈的处理状态, 可附回复, 用户会收到站内通知
+async fn status(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<FeedbackUpdate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let feedback =
+        FeedbackOp::update(auth.user.user_id, auth.user.is_super, schema, &state).await?;
+    info!(
+        "admin({})处理反馈({}): {:?}",
+        auth.user.user_name, feedback.feedback_id, feedback.status
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":feedback
+    })))
+}
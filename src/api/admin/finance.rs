@@ -0,0 +1,56 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::finance::{self, LedgerOp, SettlementQuery},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use sea_orm::prelude::Decimal;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, info};
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/finance/* 挂载中");
+    Router::new().route("/settlement", get(settlement))
+}
+
+//账期内各场馆、球场的收入、退款、平台服务费与应结算金额
+async fn settlement(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<SettlementQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (from, to) = finance::period(&schema.period).ok_or(HandleErr::BadRequest(
+        -1,
+        "账期格式应为2024-05或2024-05-01".to_string(),
+    ))?;
+    debug!(
+        "admin({}) 查询结算报表({})",
+        auth.user.user_name, schema.period
+    );
+    let courts = LedgerOp::settlement::<String>(auth.user.user_id, from, to, &state).await?;
+    let venues = finance::by_venue(&courts);
+    let sum = |f: fn(&finance::VenueSettlement) -> Decimal| venues.iter().map(f).sum::<Decimal>();
+    let total = json!({
+        "gross":sum(|e| e.gross),
+        "refunds":sum(|e| e.refunds),
+        "fees":sum(|e| e.fees),
+        "net":sum(|e| e.net)
+    });
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "period":schema.period,
+            "fee_percent":state.cfg.financecfg.fee_percent,
+            "total":total,
+            "venues":venues,
+            "courts":courts
+        }
+    })))
+}
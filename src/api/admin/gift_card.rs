@@ -0,0 +1,42 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::gift_card::{GiftCardOp, GiftCardQuery},
+};
+use axum::{
+    extract::{Query, State},
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/giftcard/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .layer(middleware::from_fn(crate::utils::auth::super_auth))
+}
+
+//已售出与已兑换的礼品卡, 附各状态的张数与金额
+async fn all(
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<GiftCardQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (cards, total) = GiftCardOp::list::<String>(&schema, &state).await?;
+    let stats = GiftCardOp::stats::<String>(&state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "cards":cards,
+            "total":total,
+            "stats":stats
+        }
+    })))
+}
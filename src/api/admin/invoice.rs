@@ -0,0 +1,62 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::order::invoice::{InvoiceHandle, InvoiceOp},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/invoice/* 挂载中");
+    Router::new()
+        .route("/pending", get(pending))
+        .route("/issue", post(issue))
+}
+
+//待开具的发票申请
+async fn pending(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let invoices = InvoiceOp::pending(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":invoices
+    })))
+}
+
+//标记发票已开具并附上发票文件地址, 或驳回申请
+async fn issue(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<InvoiceHandle>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema
+        .reply
+        .as_ref()
+        .is_some_and(|e| e.chars().count() > 200)
+    {
+        return Err(HandleErr::BadRequest(-1, "回复过长".to_string()));
+    }
+    if schema.url.as_ref().is_some_and(|e| e.len() > 500) {
+        return Err(HandleErr::BadRequest(-1, "发票地址过长".to_string()));
+    }
+    let invoice = InvoiceOp::handle::<String>(schema, auth.user.user_id, &state).await?;
+    info!(
+        "admin({})处理发票申请({}): {:?}",
+        auth.user.user_name, invoice.invoice_id, invoice.status
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":invoice
+    })))
+}
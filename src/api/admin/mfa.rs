@@ -0,0 +1,70 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::mfa::{MfaCode, MfaOp},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{extract::State, response::IntoResponse, routing::post, Extension, Json, Router};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/mfa/* 挂载中");
+    Router::new()
+        .route("/setup", post(setup))
+        .route("/enable", post(enable))
+        .route("/disable", post(disable))
+        .route("/backup", post(backup))
+}
+
+//生成密钥与otpauth链接, 前端据此展示二维码
+async fn setup(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let setup = MfaOp::setup(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"请使用验证器App扫码后输入验证码",
+        "data":setup
+    })))
+}
+
+async fn enable(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<MfaCode>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let codes = MfaOp::enable(auth.user.user_id, auth.session_id, &schema.code, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已开启二次验证, 请妥善保存备用验证码",
+        "data":{"backup_codes":codes}
+    })))
+}
+
+async fn disable(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<MfaCode>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    MfaOp::disable(auth.user.user_id, &schema.code, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已关闭二次验证",
+        "data":null
+    })))
+}
+
+async fn backup(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<MfaCode>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let codes = MfaOp::backup(auth.user.user_id, &schema.code, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已重新生成备用验证码",
+        "data":{"backup_codes":codes}
+    })))
+}
@@ -1,14 +1,95 @@
-use crate::appstate::AppState;
-use axum::{middleware, Router};
+use crate::{
+    appstate::AppState,
+    module::user::Role,
+    utils::auth::{mfa_auth, role_auth},
+};
+use axum::{middleware, routing::get, Router};
 use std::sync::Arc;
 use tracing::info;
+mod api_key;
+mod audit;
+mod blacklist;
+mod coupon;
 mod court;
+mod court_addon;
+mod court_calendar;
+mod court_hours;
+mod court_image;
+mod court_price;
+mod court_review;
+mod court_rule;
+mod court_tag;
+mod feedback;
+mod finance;
+mod gift_card;
+mod invoice;
+mod mfa;
 mod order;
+mod package;
+mod points;
+mod promotion;
+mod reconcile;
+mod referral;
+mod refund;
+mod staff;
+mod tier;
+mod user;
+mod venue;
+mod wallet;
 pub fn router() -> Router<Arc<AppState>> {
     info!("/admin/* 挂载中");
 
     Router::new()
         .nest("/court", court::router())
+        .nest("/court/image", court_image::router())
+        .nest("/court/hours", court_hours::router())
+        .nest("/court/calendar", court_calendar::router())
+        .nest("/court/price", court_price::router())
+        .nest("/court/review", court_review::router())
+        .nest("/court/tag", court_tag::router())
+        .nest("/court/rule", court_rule::router())
+        .nest("/court/addon", court_addon::router())
+        .nest("/blacklist", blacklist::router())
+        .nest("/coupon", coupon::router())
+        .nest("/feedback", feedback::router())
+        .nest("/finance", finance::router())
+        .nest("/giftcard", gift_card::router())
+        .nest("/invoice", invoice::router())
+        .nest("/package", package::router())
+        .nest("/points", points::router())
+        .nest("/promotion", promotion::router())
+        .nest("/reconcile", reconcile::router())
+        .nest("/referral", referral::router())
+        .nest("/refund", refund::router())
+        .nest("/mfa", mfa::router())
+        .nest("/audit", audit::router())
+        .nest(
+            "/apikey",
+            api_key::router().layer(middleware::from_fn(mfa_auth)),
+        )
+        .nest("/tier", tier::router())
+        //敏感操作要求已开启二次验证
+        .nest("/user", user::router().layer(middleware::from_fn(mfa_auth)))
+        .nest("/venue", venue::router())
+        .nest(
+            "/wallet",
+            wallet::router().layer(middleware::from_fn(mfa_auth)),
+        )
+        .nest(
+            "/staff",
+            staff::router().layer(middleware::from_fn(mfa_auth)),
+        )
+        .layer(middleware::from_fn_with_state(Role::Admin, role_auth))
+        //员工可访问的接口, 在各路由上按授权范围校验
         .nest("/order", order::router())
         .layer(middleware::from_fn(crate::utils::auth::admin_auth))
 }
+
+//第三方对接的只读接口, 以API密钥认证, 在 /api/integration 下挂载
+pub fn integration() -> Router<Arc<AppState>> {
+    info!("/integration/* 挂载中");
+    Router::new()
+        .route("/courts", get(court::all))
+        .route("/courts/:id/orders", get(order::ordersOfcourt))
+        .route("/orders", get(order::list))
+}
@@ -2,31 +2,462 @@ use crate::{
     appstate::AppState,
     error::HandleErr,
     module::{
+        court::CourtOp,
+        db::sea_orm_active_enums::{OrderState, StaffScope},
         db::{courts, orders, prelude::*, users},
-        order::OrderAdminSchema,
+        order::{
+            self, state, CheckinListQuery, DepositSettle, DeskCheckIn, ExportQuery, InternalNote,
+            ManualOrder, OfflinePay, OrderAdminSchema, OrderListQuery, OrderOp, OrderSearch,
+            SaveOrder, StatsQuery,
+        },
+        payment::provider::{Charge, OfflineProvider, Payer, PaymentProvider},
+        pricing::PricingOp,
+        user::Role,
+        venue::VenueOp,
+    },
+    utils::{
+        auth::{role_auth, scope_auth, JWTAuthMiddleware},
+        cursor::{self, Cursor},
     },
 };
 use axum::{
-    extract::{Path, State},
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    middleware,
     response::IntoResponse,
-    routing::get,
-    Json, Router,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use futures_util::StreamExt;
+use sea_orm::prelude::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, JoinType, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, Set,
 };
-use sea_orm::{ColumnTrait, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait};
 use serde_json::json;
 use std::sync::Arc;
 use tracing::{debug, error, info};
 use uuid::Uuid;
 pub fn router() -> Router<Arc<AppState>> {
     info!("/order/* 挂载中");
-    Router::new().route("/:id", get(ordersOfcourt))
+    let admin = || middleware::from_fn_with_state(Role::Admin, role_auth);
+    let orders = || middleware::from_fn_with_state(StaffScope::Orders, scope_auth);
+    let checkin = || middleware::from_fn_with_state(StaffScope::Checkin, scope_auth);
+    Router::new()
+        .route("/list", get(list).layer(orders()))
+        .route("/create", post(create).layer(admin()))
+        .route("/export", get(export).layer(admin()))
+        .route("/checkins", get(checkins).layer(checkin()))
+        .route("/checkin", post(checkin_order).layer(checkin()))
+        .route("/stats", get(stats).layer(admin()))
+        .route("/search", get(search).layer(orders()))
+        .route("/:id", get(ordersOfcourt).layer(orders()))
+        .route("/deposit", post(deposit).layer(admin()))
+        .route("/offline_pay", post(offline_pay).layer(admin()))
+        .route("/note", post(note).layer(admin()))
+}
+
+//代客预约, 跳过预约规则与线上支付, 冲突检测与用户下单相同
+async fn create(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ManualOrder>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (customer_name, customer_phone) = schema
+        .customer()
+        .map_err(|msg| HandleErr::BadRequest(-1, msg.to_string()))?;
+    let court = CourtOp::owned::<String>(schema.court_id, auth.user.user_id, &state).await?;
+    if OrderOp::hasClash(
+        schema.apt_start,
+        schema.apt_end,
+        None,
+        schema.court_id,
+        auth.user.user_id,
+        &state,
+    )
+    .await
+    .map_err(|err| err.into())?
+    {
+        return Err(HandleErr::BadRequest(-1, "时间冲突".to_string()));
+    }
+    let cost = PricingOp::cost(&court, schema.apt_start, schema.apt_end, &state).await?;
+    let order = OrderOp::create_manual(
+        auth.user.user_id,
+        SaveOrder {
+            order_id: None,
+            court_id: Some(schema.court_id),
+            apt_start: schema.apt_start,
+            apt_end: schema.apt_end,
+            cost,
+            deposit: Some(Decimal::ZERO),
+            idempotency_key: None,
+            remark: None,
+            coupon_id: None,
+            discount: None,
+            package: None,
+            points: None,
+            promotions: vec![],
+            member: None,
+            contact: None,
+        },
+        customer_name,
+        customer_phone,
+        &state,
+    )
+    .await?;
+    info!(
+        "admin({})为{}代订订单({})",
+        auth.user.user_name,
+        order.customer_name.as_deref().unwrap_or_default(),
+        order.order_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"预定成功",
+        "data":order
+    })))
+}
+
+//订单的查询范围, 指定场馆时按场馆可见性校验, 否则为名下全部球场
+async fn scope(
+    venue_id: Option<Uuid>,
+    auth: &JWTAuthMiddleware,
+    state: &AppState,
+) -> Result<Condition, HandleErr<String>> {
+    let cond = match venue_id {
+        Some(venue_id) => {
+            let venue = VenueOp::viewable::<String>(venue_id, &auth.user, state).await?;
+            courts::Column::VenueId.eq(venue.venue_id)
+        }
+        None => courts::Column::AdminId.eq(auth.admin_id()),
+    };
+    Ok(Condition::all().add(cond))
+}
+
+//管理员名下球场的订单, 分页
+pub(super) async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<OrderListQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let mut cond = scope(schema.venue_id, &auth, &state).await?;
+    if let Some(court_id) = schema.court_id {
+        cond = cond.add(orders::Column::CourtId.eq(court_id));
+    }
+    if let Some(status) = schema.status {
+        cond = cond.add(orders::Column::Status.eq(status));
+    }
+    if let Some(from) = schema.from {
+        cond = cond.add(orders::Column::AptStart.gte(from));
+    }
+    if let Some(to) = schema.to {
+        cond = cond.add(orders::Column::AptStart.lt(to));
+    }
+    let select = Orders::find()
+        .join(JoinType::InnerJoin, orders::Relation::Users.def())
+        .column_as(users::Column::UserName, "user_name")
+        .column_as(users::Column::Phone, "user_phone")
+        .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+        .column_as(courts::Column::CourtName, "court_name")
+        .order_by_desc(orders::Column::AptStart)
+        .order_by_desc(orders::Column::OrderId);
+    //游标翻页, 数据量大时不受偏移量影响
+    if let Some(token) = schema.cursor {
+        if !token.is_empty() {
+            cond = cond.add(order::after(&token)?);
+        }
+        let rows = select
+            .filter(cond)
+            .limit(schema.page_size + 1)
+            .into_model::<OrderAdminSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let (orders, next) = cursor::page(rows, schema.page_size, |e| Cursor {
+            apt_start: e.apt_start,
+            order_id: e.order_id,
+        });
+        return Ok(Json(json!({
+            "code":0,
+            "msg":"查询成功",
+            "data":{
+                "next_cursor":next,
+                "orders":orders
+            }
+        })));
+    }
+    let paginator = select
+        .filter(cond)
+        .into_model::<OrderAdminSchema>()
+        .paginate(&state.db, schema.page_size);
+    let total = paginator.num_items().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let orders = paginator.fetch_page(schema.page - 1).await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "total":total,
+            "page":schema.page,
+            "orders":orders
+        }
+    })))
+}
+
+//前台按手机号或昵称查找订单, 分页
+async fn search(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<OrderSearch>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let q = schema.q.trim();
+    if q.is_empty() {
+        return Err(HandleErr::BadRequest(-1, "搜索内容不能为空".to_string()));
+    }
+    let cond = scope(schema.venue_id, &auth, &state).await?.add(
+        Condition::any()
+            .add(users::Column::Phone.contains(q))
+            .add(users::Column::UserName.contains(q))
+            .add(orders::Column::CustomerPhone.contains(q))
+            .add(orders::Column::CustomerName.contains(q)),
+    );
+    let paginator = Orders::find()
+        .join(JoinType::InnerJoin, orders::Relation::Users.def())
+        .column_as(users::Column::UserName, "user_name")
+        .column_as(users::Column::Phone, "user_phone")
+        .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+        .column_as(courts::Column::CourtName, "court_name")
+        .filter(cond)
+        .order_by_desc(orders::Column::AptStart)
+        .into_model::<OrderAdminSchema>()
+        .paginate(&state.db, schema.page_size);
+    let total = paginator.num_items().await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let orders = paginator.fetch_page(schema.page - 1).await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "total":total,
+            "page":schema.page,
+            "orders":orders
+        }
+    })))
+}
+
+//某天的签到名单, 未签到的订单check_in_time为空
+async fn checkins(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<CheckinListQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let start = schema.date.and_hms_opt(0, 0, 0).unwrap();
+    let mut cond = Condition::all()
+        .add(courts::Column::AdminId.eq(auth.admin_id()))
+        .add(orders::Column::AptStart.gte(start))
+        .add(orders::Column::AptStart.lt(start + chrono::Duration::days(1)))
+        .add(orders::Column::Status.is_not_in(state::RELEASED));
+    if let Some(court_id) = schema.court_id {
+        cond = cond.add(orders::Column::CourtId.eq(court_id));
+    }
+    let orders = Orders::find()
+        .join(JoinType::InnerJoin, orders::Relation::Users.def())
+        .column_as(users::Column::UserName, "user_name")
+        .column_as(users::Column::Phone, "user_phone")
+        .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+        .column_as(courts::Column::CourtName, "court_name")
+        .filter(cond)
+        .order_by_asc(orders::Column::AptStart)
+        .into_model::<OrderAdminSchema>()
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    let checked = orders.iter().filter(|e| e.check_in_time.is_some()).count();
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "total":orders.len(),
+            "checked":checked,
+            "orders":orders
+        }
+    })))
 }
 
-async fn ordersOfcourt(
+//前台为到场的顾客签到
+async fn checkin_order(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<DeskCheckIn>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = OrderOp::desk_check_in::<String>(
+        schema.order_id,
+        auth.admin_id(),
+        auth.user.user_id,
+        &state,
+    )
+    .await?;
+    info!("{} 为订单({})签到", auth.user.user_name, order.order_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"签到成功",
+        "data":order
+    })))
+}
+
+//按日/周/月统计预约数、收入与取消数
+async fn stats(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<StatsQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    debug!(
+        "admin({}) 查询订单统计({})",
+        auth.user.user_name,
+        schema.granularity.as_str()
+    );
+    let stats = OrderOp::stats(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":stats
+    })))
+}
+
+//导出时每次查询的行数
+const EXPORT_BATCH: u64 = 500;
+
+//导出管理员名下球场的订单为CSV, 分批查询边查边写, 不在内存中拼接整个文件
+async fn export(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<ExportQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.from >= schema.to {
+        return Err(HandleErr::BadRequest(-1, "时间范围无效".to_string()));
+    }
+    let cond = Condition::all()
+        .add(courts::Column::AdminId.eq(auth.user.user_id))
+        .add(orders::Column::AptStart.gte(schema.from))
+        .add(orders::Column::AptStart.lt(schema.to));
+    info!(
+        "admin({})导出订单 {} ~ {}",
+        auth.user.user_name, schema.from, schema.to
+    );
+
+    //带BOM, 方便Excel识别中文
+    let head = csv_row(&[
+        "订单号",
+        "球场",
+        "用户",
+        "手机号",
+        "开始时间",
+        "结束时间",
+        "金额",
+        "押金",
+        "状态",
+    ]);
+    let head = [&b"\xEF\xBB\xBF"[..], &head].concat();
+    let rows = futures_util::stream::try_unfold(Some(0), move |page| {
+        let state = state.clone();
+        let cond = cond.clone();
+        async move {
+            let Some(page) = page else {
+                return Ok(None);
+            };
+            let orders = Orders::find()
+                .join(JoinType::InnerJoin, orders::Relation::Users.def())
+                .column_as(users::Column::UserName, "user_name")
+                .column_as(users::Column::Phone, "user_phone")
+                .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+                .column_as(courts::Column::CourtName, "court_name")
+                .filter(cond)
+                .order_by_asc(orders::Column::AptStart)
+                .order_by_asc(orders::Column::OrderId)
+                .into_model::<OrderAdminSchema>()
+                .paginate(&state.db, EXPORT_BATCH)
+                .fetch_page(page)
+                .await
+                .inspect_err(|err| error!("{} >>>> {}", Uuid::new_v4(), err.to_string()))?;
+            if orders.is_empty() {
+                return Ok(None);
+            }
+            let next = (orders.len() as u64 == EXPORT_BATCH).then_some(page + 1);
+            let chunk = orders
+                .iter()
+                .flat_map(|e| {
+                    csv_row(&[
+                        &e.order_id.to_string(),
+                        &e.court_name,
+                        &e.user_name,
+                        e.user_phone.as_deref().unwrap_or_default(),
+                        &e.apt_start.to_string(),
+                        &e.apt_end.to_string(),
+                        &e.cost.to_string(),
+                        &e.deposit.to_string(),
+                        &format!("{:?}", e.status),
+                    ])
+                })
+                .collect::<Vec<u8>>();
+            Ok::<_, sea_orm::DbErr>(Some((chunk, next)))
+        }
+    });
+    let body = futures_util::stream::once(async { Ok(head) }).chain(rows);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"orders.csv\"",
+            ),
+        ],
+        Body::from_stream(body),
+    ))
+}
+
+fn csv_row(fields: &[&str]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    //写入内存不会失败
+    writer.write_record(fields).unwrap();
+    writer.into_inner().unwrap()
+}
+
+pub(super) async fn ordersOfcourt(
+    Extension(auth): Extension<JWTAuthMiddleware>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, HandleErr<String>> {
     debug!("into admin order");
+    CourtOp::owned::<String>(id, auth.admin_id(), &state).await?;
     let orders = Orders::find()
         .filter(orders::Column::CourtId.eq(id))
         .join(JoinType::InnerJoin, orders::Relation::Users.def())
@@ -49,3 +480,128 @@ async fn ordersOfcourt(
         "data":orders
     })))
 }
+
+//待支付订单在前台以现金等方式线下收款
+async fn offline_pay(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<OfflinePay>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = Orders::find_by_id(schema.order_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "order_id无效".to_string()))?;
+    CourtOp::owned::<String>(order.court_id, auth.user.user_id, &state).await?;
+    if order.status != OrderState::PendingPayment || order.pay_deadline.is_some() {
+        return Err(HandleErr::BadRequest(-1, "订单不是待支付状态".to_string()));
+    }
+    let payer = Payer {
+        openid: None,
+        description: "线下收款",
+    };
+    let Charge::Paid(order) = OfflineProvider.charge(&order, &payer, &state).await? else {
+        return Err(HandleErr::BadRequest(-1, "线下收款失败".to_string()));
+    };
+    info!(
+        "admin({})确认订单({})线下收款",
+        auth.user.user_name, order.order_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"收款成功",
+        "data":order
+    })))
+}
+
+//手动退还/扣除冻结中的押金
+async fn deposit(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<DepositSettle>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = Orders::find_by_id(schema.order_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "order_id无效".to_string()))?;
+    CourtOp::owned::<String>(order.court_id, auth.user.user_id, &state).await?;
+    if !OrderOp::deposit::<String>(&order, schema.release, &state).await? {
+        return Err(HandleErr::BadRequest(-1, "押金不在冻结中".to_string()));
+    }
+    let order = Orders::find_by_id(order.order_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "order_id无效".to_string()))?;
+    info!(
+        "admin({})将订单({})押金设为{:?}",
+        auth.user.user_name, order.order_id, order.deposit_status
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":order
+    })))
+}
+
+//修改订单的内部备注, 仅管理员可见
+async fn note(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<InternalNote>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let note = schema
+        .internal_note
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty());
+    if note.as_ref().is_some_and(|e| e.chars().count() > 500) {
+        return Err(HandleErr::BadRequest(-1, "备注过长".to_string()));
+    }
+    let order = Orders::find_by_id(schema.order_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "order_id无效".to_string()))?;
+    CourtOp::owned::<String>(order.court_id, auth.user.user_id, &state).await?;
+    let order = orders::ActiveModel {
+        order_id: Set(order.order_id),
+        internal_note: Set(note),
+        ..Default::default()
+    }
+    .update(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    info!(
+        "admin({})修改订单({})内部备注",
+        auth.user.user_name, order.order_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":{
+            "order_id":order.order_id,
+            "internal_note":order.internal_note
+        }
+    })))
+}
@@ -0,0 +1,79 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::package::{PackageDel, PackageOp, PackageSave},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::prelude::Decimal;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/package/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .route("/save", post(save))
+        .route("/del", delete(del))
+}
+
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let packages = PackageOp::packages::<String, _>(auth.user.user_id, false, &state.db).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":packages
+    })))
+}
+
+//新建或修改次卡套餐, 修改不影响已购买的次卡
+async fn save(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PackageSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.name.trim().is_empty() {
+        return Err(HandleErr::BadRequest(-1, "名称不能为空".to_string()));
+    }
+    if schema.hours <= 0.0 || schema.price <= Decimal::ZERO || schema.valid_days <= 0 {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "时数、价格与有效天数须大于0".to_string(),
+        ));
+    }
+    let package = PackageOp::save::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})保存次卡套餐({})",
+        auth.user.user_name, package.package_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"保存成功",
+        "data":package
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PackageDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    PackageOp::delete::<String>(schema.package_id, auth.user.user_id, &state).await?;
+    info!(
+        "admin({})删除次卡套餐({})",
+        auth.user.user_name, schema.package_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"删除成功",
+        "data":null
+    })))
+}
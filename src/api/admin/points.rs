@@ -0,0 +1,49 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::points::{PointsOp, PointsRule},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/points/* 挂载中");
+    Router::new().route("/config", get(config)).route(
+        "/config/set",
+        post(config_set).layer(middleware::from_fn(crate::utils::auth::super_auth)),
+    )
+}
+
+async fn config(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rule = PointsOp::rule::<String, _>(&state.db).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":rule
+    })))
+}
+
+//设置积分获得比例与抵扣规则, 仅超级管理员
+async fn config_set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PointsRule>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rule = PointsOp::set_rule::<String>(schema, &state).await?;
+    info!("admin({}) 修改积分规则: {:?}", auth.user.user_name, rule);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"设置成功",
+        "data":rule
+    })))
+}
@@ -0,0 +1,102 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::sea_orm_active_enums::PromotionKind,
+        promotion::{PromotionDel, PromotionOp, PromotionSave},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::prelude::Decimal;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/promotion/* 挂载中");
+    Router::new()
+        .route("/all", get(all))
+        .route("/save", post(save))
+        .route("/del", delete(del))
+}
+
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let promotions = PromotionOp::all::<String>(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":promotions
+    })))
+}
+
+//新建或修改促销活动, 赠送时长按时数优惠, 折扣按百分比优惠
+async fn save(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PromotionSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.name.trim().is_empty() {
+        return Err(HandleErr::BadRequest(-1, "名称不能为空".to_string()));
+    }
+    if schema.value <= Decimal::ZERO
+        || (schema.kind == PromotionKind::PercentOff && schema.value >= Decimal::ONE_HUNDRED)
+        || (schema.kind == PromotionKind::FreeHours && schema.value >= schema.min_hours)
+    {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "优惠额度无效, 赠送时数须小于参加时长".to_string(),
+        ));
+    }
+    if schema.min_hours < Decimal::ZERO || schema.min_hours >= Decimal::ONE_HUNDRED {
+        return Err(HandleErr::BadRequest(-1, "参加时长无效".to_string()));
+    }
+    if schema.weekdays.iter().any(|e| !(1..=7).contains(e)) {
+        return Err(HandleErr::BadRequest(-1, "weekday应在1~7之间".to_string()));
+    }
+    match (schema.start_time, schema.end_time) {
+        (None, None) => {}
+        (Some(start), Some(end)) if start < end => {}
+        _ => return Err(HandleErr::BadRequest(-1, "限定时段无效".to_string())),
+    }
+    if schema.usage_limit.is_some_and(|e| e <= 0) || schema.per_user_limit.is_some_and(|e| e <= 0) {
+        return Err(HandleErr::BadRequest(-1, "参加次数上限须大于0".to_string()));
+    }
+    if schema.valid_from >= schema.valid_to {
+        return Err(HandleErr::BadRequest(-1, "有效期无效".to_string()));
+    }
+    let promotion = PromotionOp::save::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})保存促销活动({})",
+        auth.user.user_name, promotion.promotion.promotion_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"保存成功",
+        "data":promotion
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PromotionDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    PromotionOp::delete::<String>(schema.promotion_id, auth.user.user_id, &state).await?;
+    info!(
+        "admin({})删除促销活动({})",
+        auth.user.user_name, schema.promotion_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"删除成功",
+        "data":null
+    })))
+}
@@ -0,0 +1,102 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        order::PageQuery,
+        payment::reconcile::{IssueQuery, IssueResolve, ReconcileOp, ReconcileRun},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/reconcile/* 挂载中");
+    Router::new()
+        .route("/issues", get(issues))
+        .route("/resolve", post(resolve))
+        .route(
+            "/runs",
+            get(runs).layer(middleware::from_fn(crate::utils::auth::super_auth)),
+        )
+        .route(
+            "/run",
+            post(run).layer(middleware::from_fn(crate::utils::auth::super_auth)),
+        )
+}
+
+//对账差异, 管理员只能看到自己场馆的, 超级管理员可看到全部
+async fn issues(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<IssueQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let admin_id = (!auth.user.is_super).then_some(auth.user.user_id);
+    let (issues, total) = ReconcileOp::issues::<String>(admin_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "issues":issues,
+            "total":total
+        }
+    })))
+}
+
+//核实后标记差异已处理
+async fn resolve(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<IssueResolve>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let issue =
+        ReconcileOp::resolve::<String>(schema, auth.user.user_id, auth.user.is_super, &state)
+            .await?;
+    info!(
+        "admin({})处理对账差异({})",
+        auth.user.user_name, issue.issue_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"处理成功",
+        "data":issue
+    })))
+}
+
+//对账记录, 含失败的账单日与原因, 仅超级管理员可查看
+async fn runs(
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (runs, total) = ReconcileOp::runs::<String>(schema.page, schema.page_size, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "runs":runs,
+            "total":total
+        }
+    })))
+}
+
+//重新对账某一账单日, 覆盖之前的结果
+async fn run(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ReconcileRun>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let run = ReconcileOp::run::<String>(schema.bill_date, &state).await?;
+    info!("admin({})重新对账{}", auth.user.user_name, schema.bill_date);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"对账完成",
+        "data":run
+    })))
+}
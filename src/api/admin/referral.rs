@@ -0,0 +1,75 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::referral::{ReferralChainQuery, ReferralOp, ReferralRule},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+//邀请统计与奖励设置, 仅超级管理员
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/referral/* 挂载中");
+    Router::new()
+        .route("/stats", get(stats))
+        .route("/chain", get(chain))
+        .route("/config", get(config))
+        .route("/config/set", post(config_set))
+        .layer(middleware::from_fn(crate::utils::auth::super_auth))
+}
+
+//邀请总数/已奖励数与邀请最多的用户
+async fn stats(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, HandleErr<String>> {
+    let stats = ReferralOp::stats(&state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":stats
+    })))
+}
+
+//用户的上级邀请人与直接邀请的用户
+async fn chain(
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<ReferralChainQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let chain = ReferralOp::chain(schema.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":chain
+    })))
+}
+
+async fn config(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rule = ReferralOp::rule::<String, _>(&state.db).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":rule
+    })))
+}
+
+//设置邀请人与被邀请人获得的优惠券
+async fn config_set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ReferralRule>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rule = ReferralOp::set_rule::<String>(schema, &state).await?;
+    info!("admin({}) 修改邀请奖励: {:?}", auth.user.user_name, rule);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"设置成功",
+        "data":rule
+    })))
+}
@@ -0,0 +1,64 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::order::refund::{RefundApprove, RefundOp},
+    utils::auth::{mfa_auth, JWTAuthMiddleware},
+};
+use axum::{
+    extract::State,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/refund/* 挂载中");
+    Router::new()
+        .route("/pending", get(pending))
+        //审批退款须已开启二次验证
+        .route(
+            "/approve",
+            post(approve).layer(middleware::from_fn(mfa_auth)),
+        )
+}
+
+//待处理的退款申请
+async fn pending(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let refunds = RefundOp::pending(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":refunds
+    })))
+}
+
+//通过或拒绝退款申请
+async fn approve(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<RefundApprove>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema
+        .reply
+        .as_ref()
+        .is_some_and(|e| e.chars().count() > 200)
+    {
+        return Err(HandleErr::BadRequest(-1, "回复过长".to_string()));
+    }
+    let refund = RefundOp::handle::<String>(schema, auth.user.user_id, &state).await?;
+    info!(
+        "admin({})处理退款申请({}): {:?}",
+        auth.user.user_name, refund.refund.refund_id, refund.refund.status
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":refund
+    })))
+}
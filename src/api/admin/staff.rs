@@ -0,0 +1,79 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::staff::{StaffAdd, StaffDel, StaffOp, StaffUpdate},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/staff/* 挂载中");
+    Router::new()
+        .route("/add", post(add))
+        .route("/update", post(update))
+        .route("/del", post(del))
+        .route("/list", get(list))
+}
+
+//创建员工账号, 可授予查看订单与前台签到的范围
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<StaffAdd>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let staff = StaffOp::add(auth.user.user_id, schema, &state).await?;
+    info!(
+        "admin({})创建员工账号({})",
+        auth.user.user_name, staff.user_name
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"创建成功",
+        "data":staff
+    })))
+}
+
+async fn update(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<StaffUpdate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let staff = StaffOp::update(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":staff
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<StaffDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    StaffOp::del(auth.user.user_id, schema.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已停用",
+        "data":null
+    })))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let staff = StaffOp::list(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":staff
+    })))
+}
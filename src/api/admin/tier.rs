@@ -0,0 +1,69 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::tier::{TierAssign, TierBenefitSet, TierOp},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+//会员等级与权益设置, 仅超级管理员
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/tier/* 挂载中");
+    Router::new()
+        .route("/assign", post(assign))
+        .route("/benefits", get(benefits))
+        .route("/benefits/set", post(benefits_set))
+        .layer(middleware::from_fn(crate::utils::auth::super_auth))
+}
+
+//设置用户的会员等级与到期时间
+async fn assign(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TierAssign>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let tier = TierOp::assign::<String>(schema, &state).await?;
+    info!(
+        "super({})设置用户({})会员等级: {:?}",
+        auth.user.user_name, tier.user_id, tier.tier
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"设置成功",
+        "data":tier
+    })))
+}
+
+//各等级的提前预约天数与场地费折扣
+async fn benefits(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let benefits = TierOp::benefits(&state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":benefits
+    })))
+}
+
+async fn benefits_set(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<TierBenefitSet>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let benefit = TierOp::set_benefit::<String>(schema, &state).await?;
+    info!("super({})设置会员权益: {:?}", auth.user.user_name, benefit);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"设置成功",
+        "data":benefit
+    })))
+}
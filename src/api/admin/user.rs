@@ -0,0 +1,87 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::user::{UserDisable, UserGrant, UserOP, UserUnlock},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State, middleware, response::IntoResponse, routing::post, Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/user/* 挂载中");
+    Router::new()
+        .route("/disable", post(disable))
+        .route("/enable", post(enable))
+        .route("/unlock", post(unlock))
+        .route("/grant", post(grant))
+        .layer(middleware::from_fn(crate::utils::auth::super_auth))
+}
+
+//封禁账号, 该用户的全部登录立即失效
+async fn disable(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<UserDisable>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    UserOP::disable(schema.user_id, true, &state).await?;
+    info!("admin({})封禁用户({})", auth.user.user_name, schema.user_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已封禁",
+        "data":null
+    })))
+}
+
+async fn enable(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<UserDisable>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    UserOP::disable(schema.user_id, false, &state).await?;
+    info!("admin({})解封用户({})", auth.user.user_name, schema.user_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已解封",
+        "data":null
+    })))
+}
+
+//授予或撤销管理员身份, 注册接口不再接受客户端指定
+async fn grant(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<UserGrant>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (user_id, is_admin) = (schema.user_id, schema.is_admin);
+    UserOP::grant::<String>(schema, &state).await?;
+    info!(
+        "admin({})将用户({})的管理员身份设为{}",
+        auth.user.user_name, user_id, is_admin
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"设置成功",
+        "data":null
+    })))
+}
+
+//解除密码登录失败锁定, 不必等待锁定到期
+async fn unlock(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<UserUnlock>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    UserOP::unlock(schema.user_id, &state).await?;
+    info!(
+        "admin({})解除用户({})的登录锁定",
+        auth.user.user_name, schema.user_id
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已解除锁定",
+        "data":null
+    })))
+}
@@ -0,0 +1,288 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{CourtAdminSchema, CourtOp},
+        db::{self, prelude::*},
+        notify::inbox::{AnnounceTarget, InboxOp},
+        upload::{UploadOp, UploadPurpose},
+        user::Role,
+        venue::{VenueAnnounce, VenueDel, VenueOp, VenueSave},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Multipart, Path, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/venue/* 挂载中");
+    Router::new()
+        .route("/add", post(add))
+        .route("/update", post(update))
+        .route("/announce", post(announce))
+        .route("/del", delete(del))
+        .route("/all", get(all))
+        .route("/:venue_id/courts", get(courts))
+        .route("/photo", post(photo))
+}
+
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VenueSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    Venues::find()
+        .filter(
+            db::venues::Column::VenueName
+                .eq(&schema.venue_name)
+                .and(db::venues::Column::AdminId.eq(auth.user.user_id)),
+        )
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr::<String>(id)
+        })?
+        .map_or(Ok(()), |_| {
+            Err(HandleErr::BadRequest(-1, "场馆名重复".to_string()))
+        })?;
+    let venue = VenueOp::save(
+        auth.user.user_id,
+        VenueSave {
+            venue_id: None,
+            ..schema
+        },
+        &state,
+    )
+    .await?;
+    info!(
+        "admin({})添加场馆({})",
+        auth.user.user_name, venue.venue_name
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"场馆添加成功",
+        "data":venue
+    })))
+}
+
+async fn update(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VenueSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let venue_id = schema
+        .venue_id
+        .ok_or(HandleErr::BadRequest(-1, "缺少venue_id".to_string()))?;
+    let old = VenueOp::owned::<String>(venue_id, auth.user.user_id, &state).await?;
+    let venue = VenueOp::save(auth.user.user_id, schema, &state).await?;
+    //公告变化时通知有未开始预约的用户与关注者
+    if !venue.announcement.trim().is_empty() && venue.announcement != old.announcement {
+        if let Err(err) = InboxOp::announce::<String>(&venue, AnnounceTarget::All, &state).await {
+            warn!("场馆({})公告通知失败: {:?}", venue.venue_id, err);
+        }
+    }
+    debug!("pass venue update");
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":venue
+    })))
+}
+
+//只更新公告, 通知指定的对象
+async fn announce(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VenueAnnounce>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.announcement.trim().is_empty() {
+        return Err(HandleErr::BadRequest(-1, "公告不能为空".to_string()));
+    }
+    VenueOp::owned::<String>(schema.venue_id, auth.user.user_id, &state).await?;
+    let venue = db::venues::ActiveModel {
+        venue_id: Set(schema.venue_id),
+        announcement: Set(schema.announcement),
+        ..Default::default()
+    }
+    .update(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    let notified = InboxOp::announce(&venue, schema.target, &state).await?;
+    info!(
+        "admin({})发布场馆({})公告, 通知{}位用户",
+        auth.user.user_name, venue.venue_id, notified
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"公告已发布",
+        "data":{
+            "venue":venue,
+            "notified":notified
+        }
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VenueDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let rows_affected = Venues::delete_many()
+        .filter(
+            db::venues::Column::VenueId
+                .eq(schema.venue_id)
+                .and(db::venues::Column::AdminId.eq(auth.user.user_id)),
+        )
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .rows_affected;
+    if rows_affected == 0 {
+        Err(HandleErr::BadRequest(-1, "没有场馆被删除".to_string()))
+    } else {
+        info!(
+            "admin({})删除场馆({})",
+            auth.user.user_name, schema.venue_id
+        );
+        Ok(Json(json!({"code":0,"msg":"场馆删除成功"})))
+    }
+}
+
+//超级管理员查看全部场馆
+async fn all(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let mut select = Venues::find();
+    if auth.user.role() != Role::Super {
+        select = select.filter(db::venues::Column::AdminId.eq(auth.user.user_id));
+    }
+    let venues = select.all(&state.db).await.map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":venues
+    })))
+}
+
+async fn courts(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(venue_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let venue = VenueOp::viewable::<String>(venue_id, &auth.user, &state).await?;
+    let mut courts: Vec<_> = Courts::find()
+        .filter(db::courts::Column::VenueId.eq(venue_id))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .into_iter()
+        .map(CourtAdminSchema::from)
+        .collect();
+    CourtOp::fill(&mut courts, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "venue":venue,
+            "courts":courts
+        }
+    })))
+}
+
+//multipart字段: venue_id, file
+async fn photo(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let mut venue_id = None;
+    let mut file = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?
+    {
+        match field.name() {
+            Some("venue_id") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                venue_id = Some(
+                    Uuid::parse_str(&text)
+                        .map_err(|_| HandleErr::BadRequest(-1, "venue_id无效".to_string()))?,
+                );
+            }
+            Some("file") => {
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                file = Some(data.to_vec());
+            }
+            _ => {}
+        }
+    }
+    let venue_id = venue_id.ok_or(HandleErr::BadRequest(-1, "缺少venue_id".to_string()))?;
+    let data = file.ok_or(HandleErr::BadRequest(-1, "未上传图片".to_string()))?;
+    let venue = VenueOp::owned::<String>(venue_id, auth.user.user_id, &state).await?;
+
+    //按文件内容识别格式, 不信任客户端声明的Content-Type
+    let (data, content_type, ext) = UploadOp::validate(data, UploadPurpose::Court).await?;
+    let key = format!("venue/{}/{}.{}", venue_id, Uuid::new_v4(), ext);
+    let url = state
+        .storage
+        .put(&key, content_type, data)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+    let mut photos = venue.photos;
+    photos.push(url);
+    let venue = db::venues::ActiveModel {
+        venue_id: Set(venue_id),
+        photos: Set(photos),
+        ..Default::default()
+    }
+    .update(&state.db)
+    .await
+    .map_err(|err| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr(id)
+    })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"上传成功",
+        "data":venue
+    })))
+}
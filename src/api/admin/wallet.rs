@@ -0,0 +1,84 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        audit::{AuditEntry, AuditOp},
+        order::PageQuery,
+        session::client_ip,
+        wallet::{WalletAdjust, WalletOp},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::{net::SocketAddr, sync::Arc};
+use tracing::info;
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/wallet/* 挂载中");
+    Router::new()
+        .route(
+            "/adjust",
+            post(adjust).layer(middleware::from_fn(crate::utils::auth::super_auth)),
+        )
+        .route("/:user_id", get(detail))
+}
+
+//调整用户余额, 仅超级管理员可操作, 流水中记录操作的管理员与原因
+//另写一条以用户与流水为路由的审计记录, 便于按用户查询调整历史
+async fn adjust(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(schema): Json<WalletAdjust>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let record = WalletOp::adjust::<String>(schema, auth.user.user_id, &state).await?;
+    AuditOp::record(
+        AuditEntry {
+            actor_id: auth.user.user_id,
+            ip: client_ip(&headers, addr, &state.cfg.servercfg.trusted_proxies).to_string(),
+            method: "ADJUST".to_string(),
+            route: format!("/wallet/{}/{}", record.user_id, record.txn_id),
+            status: 200,
+            payload_digest: None,
+        },
+        &state,
+    )
+    .await;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"调整成功",
+        "data":record
+    })))
+}
+
+//用户余额与流水
+async fn detail(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<Uuid>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    WalletOp::visible::<String>(user_id, &auth.user, auth.admin_id(), &state).await?;
+    let balance = WalletOp::balance::<String, _>(user_id, &state.db).await?;
+    let (transactions, total) =
+        WalletOp::transactions::<String, _>(user_id, schema.page, schema.page_size, &state.db)
+            .await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "balance":balance,
+            "transactions":transactions,
+            "total":total
+        }
+    })))
+}
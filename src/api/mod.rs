@@ -1,20 +1,62 @@
-use crate::appstate::AppState;
+use crate::{
+    appstate::AppState,
+    utils::ratelimit::{ratelimit, RateLimiter},
+};
 use axum::Router;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tracing::info;
 pub mod admin;
 pub mod open;
+pub mod payment;
+pub mod public;
 pub mod test;
 pub mod user;
 
 pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
     info!("/api/* 挂载中");
+    //第三方对接, 每个密钥单独限流, 配置了对接方时还须校验请求签名
+    let mut integration = admin::integration()
+        .layer(axum::middleware::from_fn_with_state(
+            RateLimiter::new(60, Duration::from_secs(60)),
+            ratelimit,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::utils::auth::api_key_auth,
+        ));
+    if !state.cfg.signcfg.clients.is_empty() {
+        info!("/integration/* 启用请求签名");
+        integration = integration.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::utils::auth::sign_auth,
+        ));
+    }
     Router::new()
         .nest("/user", user::router())
-        .nest("/admin", admin::router())
+        //管理端的写操作全部记入审计
+        .nest(
+            "/admin",
+            admin::router().layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                crate::utils::audit::audit,
+            )),
+        )
+        //按用户限流, 须在身份验证之后
+        .layer(axum::middleware::from_fn_with_state(
+            RateLimiter::new(300, Duration::from_secs(60)),
+            ratelimit,
+        ))
         .layer(axum::middleware::from_fn_with_state(
             state.clone(),
             crate::utils::auth::auth,
         ))
         .merge(open::router())
+        .nest("/user/auth", user::auth::router())
+        .nest("/public", public::router(state.clone()))
+        .nest("/payment", payment::router())
+        .nest("/integration", integration)
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::utils::ratelimit::client_addr,
+        ))
 }
@@ -1,31 +1,59 @@
 use crate::error::HandleErr;
 use crate::module::db;
-use crate::module::user::{UserLoginSchema, UserOP, UserRegisterSchema};
-use crate::utils::{passwd, token};
+use crate::module::mfa::MfaOp;
+use crate::module::password::{ForgotSchema, PasswordResetOp, ResetSchema, VerifySchema};
+use crate::module::referral::ReferralOp;
+use crate::module::session::{client_ip, SessionOp};
+use crate::module::user::{Role, UserLoginSchema, UserOP, UserRegisterSchema};
+use crate::utils::passwd;
+use crate::utils::ratelimit::{ratelimit, RateLimiter};
 use crate::{appstate::AppState, module::db::prelude::Users};
-use axum::extract::State;
+use axum::extract::{ConnectInfo, State};
 use axum::http::{header, HeaderMap};
 use axum::response::{IntoResponse, Response};
-use axum::{routing::post, Json, Router};
+use axum::{middleware, routing::post, Json, Router};
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde_json::json;
-use std::sync::Arc;
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tracing::{debug, warn};
 use tracing::{error, info};
 use uuid::Uuid;
 pub fn router() -> Router<Arc<AppState>> {
     info!("/login 挂载中");
     info!("/register 挂载中");
+    info!("/password 挂载中");
+    //登录、注册与找回密码按ip严格限流
     Router::new()
         .route("/login", post(login))
         .route("/register", post(register))
+        .route("/password/forgot", post(forgot))
+        .route("/password/verify", post(verify))
+        .route("/password/reset", post(reset))
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(10, Duration::from_secs(60)),
+            ratelimit,
+        ))
 }
 
 async fn login(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req_headers: HeaderMap,
     Json(schema): Json<UserLoginSchema>,
 ) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let ip = client_ip(&req_headers, addr, &state.cfg.servercfg.trusted_proxies).to_string();
+    if state
+        .login_failures
+        .blocked(&ip, &state.cfg.logincfg, Instant::now())
+    {
+        warn!("来自{}的登录失败次数过多, 拒绝登录", ip);
+        return Err(HandleErr::BadRequest(-1, "登录失败次数过多, 请稍后再试"));
+    }
     //先根据用户名查询用户信息
     debug!("查询用户信息");
     let user_schema = Users::find()
@@ -37,24 +65,76 @@ async fn login(
             error!("{} >>>> {}", id, err.to_string());
             HandleErr::ServerInnerErr(id)
         })?
-        .ok_or(HandleErr::BadRequest(-1, "用户不存在"))?;
+        .ok_or_else(|| {
+            state
+                .login_failures
+                .record(&ip, &state.cfg.logincfg, Instant::now());
+            HandleErr::BadRequest(-1, "用户不存在")
+        })?;
+    if user_schema
+        .locked_until
+        .is_some_and(|e| e > chrono::Utc::now().naive_utc())
+    {
+        warn!("用户({})已锁定, 拒绝来自{}的登录", schema.name, ip);
+        return Err(HandleErr::BadRequest(
+            -1,
+            "密码错误次数过多, 账号已临时锁定",
+        ));
+    }
     debug!("校验密码");
-    passwd::verify_password(&schema.pwd, &user_schema.user_pwd)?;
+    if let Err(err) = passwd::verify_password(&schema.pwd, &user_schema.user_pwd) {
+        if let HandleErr::BadRequest(..) = err {
+            if UserOP::login_failed(&user_schema, &ip, &state)
+                .await?
+                .is_some()
+            {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "密码错误次数过多, 账号已临时锁定",
+                ));
+            }
+        }
+        return Err(err);
+    }
+    //已开启二次验证时, 未填写验证码以-2提示前端输入, 填错按密码错误计入失败次数
+    if user_schema.totp_enabled {
+        let Some(otp) = schema.otp.as_deref() else {
+            return Err(HandleErr::BadRequest(-2, "请输入二次验证码"));
+        };
+        if !MfaOp::verify(&user_schema, otp, &state).await? {
+            if UserOP::login_failed(&user_schema, &ip, &state)
+                .await?
+                .is_some()
+            {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "密码错误次数过多, 账号已临时锁定",
+                ));
+            }
+            return Err(HandleErr::BadRequest(-1, "二次验证码错误"));
+        }
+    }
+    UserOP::login_succeeded(&user_schema, &state).await?;
+    if passwd::needs_rehash(user_schema.hash_version) {
+        UserOP::rehash(user_schema.user_id, &schema.pwd, &state).await?;
+    }
+    if user_schema.disabled_time.is_some() {
+        warn!("用户({})已被封禁, 拒绝登录", schema.name);
+        return Err(HandleErr::BadRequest(-1, "账号已被封禁"));
+    }
     //生成access_token
     debug!("生成token");
-    let access_token = token::create(
+    let tokens = SessionOp::issue(
         user_schema.user_id,
-        state.cfg.tokencfg.access_token_ttl,
-        &state.cfg.tokencfg.access_prikey,
+        user_schema.totp_enabled,
+        &req_headers,
+        addr,
+        &state,
     )
-    .map_err(|err| {
-        let id = Uuid::new_v4();
-        error!("{} >>>> {}", id, err.to_string());
-        HandleErr::ServerInnerErr(id)
-    })?;
+    .await?;
 
     //设置cookie
-    let access_cookie = Cookie::build(("access_token", &access_token))
+    let access_cookie = Cookie::build(("access_token", &tokens.access_token))
         .path("/")
         .max_age(time::Duration::minutes(
             state.cfg.tokencfg.access_token_ttl * 60,
@@ -67,8 +147,10 @@ async fn login(
         "code": 0,
         "msg":"登录成功",
         "data":{
-            "access_token":&access_token,
-            "is_admin":user_schema.is_admin
+            "access_token":&tokens.access_token,
+            "refresh_token":&tokens.refresh_token,
+            "is_admin":user_schema.is_admin,
+            "role":Role::of(&user_schema)
         }})
         .to_string(),
     );
@@ -110,8 +192,42 @@ async fn register(
         return Err(HandleErr::BadRequest(-1, "用户名已存在, 或者号码已存在"));
     }
 
+    //填写的邀请码无效时拒绝注册
+    let inviter = match schema.referral_code.as_deref().map(str::trim) {
+        Some(code) if !code.is_empty() => Some(ReferralOp::inviter(code, &state.db).await?),
+        _ => None,
+    };
+
     debug!("注册中");
-    let name = UserOP::register_new_user(schema, &state).await?;
+    let name = UserOP::register_new_user(schema, inviter, &state).await?;
     info!("用户({})注册成功", name);
     Ok(Json(json!({"code":0,"msg":"注册成功"})))
 }
+
+async fn forgot(
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ForgotSchema>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    PasswordResetOp::forgot(schema, &state).await?;
+    Ok(Json(
+        json!({"code":0,"msg":"若该手机号已绑定账号, 验证码将发送至该手机"}),
+    ))
+}
+
+async fn verify(
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VerifySchema>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    PasswordResetOp::verify(schema, &state).await?;
+    Ok(Json(json!({"code":0,"msg":"验证码正确"})))
+}
+
+async fn reset(
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ResetSchema>,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let revoked = PasswordResetOp::reset(schema, &state).await?;
+    Ok(Json(
+        json!({"code":0,"msg":"密码已重置, 请重新登录","data":{"revoked":revoked}}),
+    ))
+}
@@ -0,0 +1,114 @@
+use crate::appstate::AppState;
+use crate::module::{
+    gift_card::GiftCardOp,
+    order::{group::GroupOp, pay::PayOp, refund::RefundOp},
+    package::PackageOp,
+    payment::{
+        wechat::{Transaction, WechatRefund},
+        ATTACH_GIFT_CARD, ATTACH_GROUP_SHARE, ATTACH_PACKAGE, ATTACH_RECHARGE,
+    },
+    wallet::WalletOp,
+};
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{routing::post, Json, Router};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/payment/wechat/notify 挂载中");
+    info!("/payment/wechat/refund_notify 挂载中");
+    Router::new()
+        .route("/wechat/notify", post(wechat_notify))
+        .route("/wechat/refund_notify", post(wechat_refund_notify))
+}
+
+//应答成功后微信支付不再重复通知
+fn success() -> Response {
+    (
+        StatusCode::OK,
+        Json(json!({"code":"SUCCESS", "message":"成功"})),
+    )
+        .into_response()
+}
+
+//应答失败时微信支付会按间隔重试通知
+fn fail() -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"code":"FAIL", "message":"失败"})),
+    )
+        .into_response()
+}
+
+//微信支付结果通知, 无需登录, 通过签名验证来源
+async fn wechat_notify(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let (event_type, transaction) = match state.payment.notify::<Transaction>(&headers, &body) {
+        Ok(e) => e,
+        Err(err) => {
+            warn!("微信支付通知无效: {}", err);
+            return fail();
+        }
+    };
+    if event_type == "TRANSACTION.SUCCESS" && transaction.trade_state == "SUCCESS" {
+        //同一交易的通知正在处理时返回失败, 由微信稍后重试
+        let Some(_guard) = state.notifying.enter(&transaction.transaction_id) else {
+            info!("微信支付订单({})的通知正在处理", transaction.transaction_id);
+            return fail();
+        };
+        let result = match transaction.attach.as_deref() {
+            Some(ATTACH_RECHARGE) => WalletOp::recharged::<String>(&transaction, &state)
+                .await
+                .map(|_| ()),
+            Some(ATTACH_PACKAGE) => PackageOp::purchased::<String>(&transaction, &state)
+                .await
+                .map(|_| ()),
+            Some(ATTACH_GIFT_CARD) => GiftCardOp::purchased::<String>(&transaction, &state)
+                .await
+                .map(|_| ()),
+            Some(ATTACH_GROUP_SHARE) => GroupOp::paid::<String>(&transaction, &state)
+                .await
+                .map(|_| ()),
+            _ => PayOp::paid::<String>(&transaction, &state)
+                .await
+                .map(|_| ()),
+        };
+        if let Err(err) = result {
+            warn!("微信支付通知处理失败: {:?}", err);
+            return fail();
+        }
+    } else {
+        info!(
+            "订单({})支付通知: {} {}",
+            transaction.out_trade_no, event_type, transaction.trade_state
+        );
+    }
+    success()
+}
+
+//微信退款结果通知, 更新对应退款记录的退款状态
+async fn wechat_refund_notify(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let (event_type, refund) = match state.payment.notify::<WechatRefund>(&headers, &body) {
+        Ok(e) => e,
+        Err(err) => {
+            warn!("微信退款通知无效: {}", err);
+            return fail();
+        }
+    };
+    info!("退款({})通知: {}", refund.out_refund_no, event_type);
+    if let Err(err) = RefundOp::notified::<String>(&refund, &state).await {
+        warn!("微信退款通知处理失败: {:?}", err);
+        return fail();
+    }
+    success()
+}
@@ -0,0 +1,27 @@
+use crate::{
+    api::user::court,
+    appstate::AppState,
+    utils::ratelimit::{ratelimit, RateLimiter},
+};
+use axum::{middleware, routing::get, Router};
+use std::{sync::Arc, time::Duration};
+use tracing::info;
+
+//无需登录的只读接口, 供小程序首页在登录前展示球场, 已登录时附带收藏状态
+pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    info!("/public/* 挂载中");
+    Router::new()
+        .route("/court/all", get(court::all))
+        .route("/court/detail/:court_id", get(court::detail))
+        .route("/court/:court_id/availability", get(court::availability))
+        .route("/court/:court_id/reviews", get(court::reviews))
+        .route("/court/:court_id/poster", get(court::poster))
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(60, Duration::from_secs(60)),
+            ratelimit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state,
+            crate::utils::auth::optional_auth,
+        ))
+}
@@ -0,0 +1,71 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::account::{AccountDelete, AccountOp, PasswordChange},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/account/* 挂载中");
+    Router::new()
+        .route("/export", get(export))
+        .route("/delete", post(delete))
+        .route("/password", post(password))
+}
+
+//导出个人数据
+async fn export(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let data = AccountOp::export(auth.user.user_id, &state).await?;
+    info!("{} 导出个人数据", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":data
+    })))
+}
+
+//注销账号, 个人信息匿名化后不可恢复
+async fn delete(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<AccountDelete>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !schema.confirm {
+        return Err(HandleErr::BadRequest(-1, "请确认注销账号".to_string()));
+    }
+    AccountOp::delete(auth.user.user_id, &state).await?;
+    info!("{} 注销账号", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"账号已注销",
+        "data":null
+    })))
+}
+
+//修改密码, 其他设备需重新登录
+async fn password(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PasswordChange>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let revoked = AccountOp::password(auth.user.user_id, auth.session_id, schema, &state).await?;
+    info!("{} 修改密码", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"密码已修改",
+        "data":{
+            "revoked":revoked
+        }
+    })))
+}
@@ -0,0 +1,107 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        referral::ReferralOp,
+        session::{RefreshSchema, SessionOp},
+        user::{Role, UserOP, WechatLogin},
+    },
+    utils::ratelimit::{ratelimit, RateLimiter},
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+    middleware,
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use serde_json::json;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+//无需登录即可访问
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/user/auth 挂载中");
+    Router::new()
+        .route("/login", post(login))
+        .route("/refresh", post(refresh))
+        //按ip严格限流
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(10, Duration::from_secs(60)),
+            ratelimit,
+        ))
+}
+
+//小程序登录, 用wx.login得到的code换取openid, 首次登录时创建用户
+async fn login(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(schema): Json<WechatLogin>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let Some(wechat) = &state.wechat else {
+        return Err(HandleErr::BadRequest(-1, "未配置微信小程序".to_string()));
+    };
+    let session = wechat
+        .code2session(schema.code.trim())
+        .await
+        .map_err(|err| {
+            warn!("微信登录失败: {}", err);
+            HandleErr::BadRequest(-1, "微信授权失败".to_string())
+        })?;
+    //邀请码只在首次登录时绑定, 无效时忽略, 不影响登录
+    let inviter = match schema.referral_code.as_deref().map(str::trim) {
+        Some(code) if !code.is_empty() => ReferralOp::inviter::<String, _>(code, &state.db)
+            .await
+            .inspect_err(|_| warn!("微信登录的邀请码({})无效", code))
+            .ok(),
+        _ => None,
+    };
+    let (user, created, referred) = UserOP::wechat_login(&session, inviter, &state).await?;
+    if user.disabled_time.is_some() {
+        warn!("微信用户({})已被封禁, 拒绝登录", user.user_name);
+        return Err(HandleErr::BadRequest(-1, "账号已被封禁".to_string()));
+    }
+    //微信登录无法校验二次验证码
+    if user.totp_enabled {
+        warn!("用户({})已开启二次验证, 拒绝微信登录", user.user_name);
+        return Err(HandleErr::BadRequest(
+            -1,
+            "该账号已开启二次验证, 请使用密码登录".to_string(),
+        ));
+    }
+    //开启二次验证的账号不能微信登录, 会话均未经二次验证
+    let tokens = SessionOp::issue(user.user_id, false, &headers, addr, &state).await?;
+    if created {
+        info!("微信用户({})首次登录, 已创建", user.user_name);
+    } else {
+        info!("微信用户({})登录成功", user.user_name);
+    }
+    Ok(Json(json!({
+        "code":0,
+        "msg":"登录成功",
+        "data":{
+            "access_token":tokens.access_token,
+            "refresh_token":tokens.refresh_token,
+            "is_admin":user.is_admin,
+            "role":Role::of(&user),
+            "is_new":created,
+            "referred":referred,
+            "bound_phone":user.phone.is_some()
+        }
+    })))
+}
+
+//access_token过期后用刷新令牌换取新的一对令牌, 旧的刷新令牌不能再次使用
+async fn refresh(
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<RefreshSchema>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let tokens = SessionOp::refresh(&schema.refresh_token, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"刷新成功",
+        "data":tokens
+    })))
+}
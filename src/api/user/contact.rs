@@ -0,0 +1,63 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::contact::{ContactDel, ContactOp, ContactSave},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/contact/* 挂载中");
+    Router::new()
+        .route("/list", get(list))
+        .route("/save", post(save))
+        .route("/del", post(del))
+}
+
+//常用联系人, 最近使用的在前
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let contacts = ContactOp::list(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":contacts
+    })))
+}
+
+//contact_id为空时新增, 否则修改
+async fn save(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ContactSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let contact = ContactOp::save(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"保存成功",
+        "data":contact
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ContactDel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    ContactOp::del(auth.user.user_id, schema.contact_id, &state).await?;
+    info!("{} 删除联系人({})", auth.user.user_name, schema.contact_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"删除成功",
+        "data":null
+    })))
+}
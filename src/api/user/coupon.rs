@@ -0,0 +1,24 @@
+use crate::{
+    appstate::AppState, error::HandleErr, module::coupon::CouponOp, utils::auth::JWTAuthMiddleware,
+};
+use axum::{extract::State, response::IntoResponse, routing::get, Extension, Json, Router};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/coupon/* 挂载中");
+    Router::new().route("/mine", get(mine))
+}
+
+//我的优惠券, 未使用的在前
+async fn mine(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let coupons = CouponOp::mine::<String>(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":coupons
+    })))
+}
@@ -1,23 +1,116 @@
 use crate::{
     appstate::AppState,
     error::HandleErr,
-    module::{court::CourtUserSchema, db::prelude::Courts},
+    module::{
+        court::{
+            addon::AddonOp,
+            availability::{AvailabilityOp, AvailabilityQuery},
+            favorite::FavoriteOp,
+            poster::PosterOp,
+            review::ReviewOp,
+            CourtDistance, CourtFilter, CourtNearby, CourtNearbySchema, CourtOp, CourtUserSchema,
+            CourtVersion,
+        },
+        db::{
+            self,
+            prelude::{CourtTagLinks, Courts},
+            sea_orm_active_enums::CourtStatus,
+        },
+        order::PageQuery,
+    },
+    utils::{auth::JWTAuthMiddleware, etag},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, EntityTrait, JoinType, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, RelationTrait,
 };
-use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
-use sea_orm::EntityTrait;
 use serde_json::json;
 use std::sync::Arc;
 use tracing::error;
 use uuid::Uuid;
 pub fn router() -> Router<Arc<AppState>> {
-    Router::new().route("/all", get(all))
+    Router::new()
+        .route("/all", get(all))
+        .route("/nearby", get(nearby))
+        .route("/detail/:court_id", get(detail))
+        .route("/:court_id/availability", get(availability))
+        .route("/:court_id/reviews", get(reviews))
+        .route("/:court_id/poster", get(poster))
 }
 
-async fn all(
-    // Extension(auth): Extension<JWTAuthMiddleware>,
+//支持If-None-Match, 列表未变化时返回304; 已登录时标记收藏的球场
+pub(crate) async fn all(
+    auth: Option<Extension<JWTAuthMiddleware>>,
     State(state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, HandleErr<String>> {
-    let courts: Vec<_> = Courts::find()
+    headers: HeaderMap,
+    Query(schema): Query<CourtFilter>,
+) -> Result<Response, HandleErr<String>> {
+    let (sport_type, tag) = (
+        format!("{:?}", schema.sport_type),
+        format!("{:?}", schema.tag),
+    );
+    //关闭的球场不对用户展示, 维护中的球场通过status标记
+    let mut cond = Condition::all().add(db::courts::Column::Status.ne(CourtStatus::Closed));
+    if let Some(sport_type) = schema.sport_type {
+        cond = cond.add(db::courts::Column::SportType.eq(sport_type));
+    }
+    if let Some(tag) = schema.tag.filter(|e| !e.is_empty()) {
+        cond = cond.add(
+            db::courts::Column::CourtId.in_subquery(
+                CourtTagLinks::find()
+                    .select_only()
+                    .column(db::court_tag_links::Column::CourtId)
+                    .join(
+                        JoinType::InnerJoin,
+                        db::court_tag_links::Relation::CourtTags.def(),
+                    )
+                    .filter(db::court_tags::Column::TagName.eq(tag))
+                    .into_query(),
+            ),
+        );
+    }
+    let version = Courts::find()
+        .select_only()
+        .column_as(db::courts::Column::CourtId.count(), "count")
+        .column_as(db::courts::Column::UpdateTime.max(), "update_time")
+        .filter(cond.clone())
+        .into_model::<CourtVersion>()
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "查询失败".to_string()))?;
+    let favorites = match &auth {
+        Some(auth) => Some(FavoriteOp::ids(auth.user.user_id, &state).await?),
+        None => None,
+    };
+    //收藏变化时列表也需更新
+    let mut favorite_ids: Vec<_> = favorites.iter().flatten().map(Uuid::to_string).collect();
+    favorite_ids.sort();
+    let etag = etag::etag(&[
+        &version.count.to_string(),
+        &format!("{:?}", version.update_time),
+        &sport_type,
+        &tag,
+        &format!("{:?}", auth.as_ref().map(|e| e.user.user_id)),
+        &favorite_ids.join(","),
+    ]);
+    if etag::fresh(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let mut courts: Vec<_> = Courts::find()
+        .filter(cond)
         .all(&state.db)
         .await
         .map_err(|err| {
@@ -27,16 +120,84 @@ async fn all(
         })?
         .into_iter()
         .map(|e| CourtUserSchema {
-            court_id: Some(e.court_id),
             admin_id: None,
-            court_name: e.court_name,
-            location: e.location,
-            label: e.label,
-            price_per_hour: e.price_per_hour,
-            open_time: e.open_time,
-            close_time: e.close_time,
+            ..e.into()
         })
         .collect();
+    CourtOp::fill(&mut courts, &state).await?;
+    if let Some(favorites) = &favorites {
+        FavoriteOp::mark(&mut courts, favorites);
+    }
+
+    Ok((
+        [(header::ETAG, etag)],
+        Json(json!({
+            "code":0,
+            "msg":"OK",
+            "data":courts
+        })),
+    )
+        .into_response())
+}
+
+//附近搜索的默认与最大半径, 单位km
+const DEFAULT_RADIUS: f64 = 10.0;
+const MAX_RADIUS: f64 = 50.0;
+
+//Haversine公式, 单位km
+const HAVERSINE: &str = "6371 * 2 * asin(sqrt(power(sin(radians(latitude - $1) / 2), 2) \
+    + cos(radians($2)) * cos(radians(latitude)) * power(sin(radians(longitude - $3) / 2), 2)))";
+
+async fn nearby(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<CourtNearby>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !(-90.0..=90.0).contains(&schema.lat) || !(-180.0..=180.0).contains(&schema.lng) {
+        return Err(HandleErr::BadRequest(-1, "经纬度无效".to_string()));
+    }
+    //非法半径按默认值处理, 过大的半径限制为最大值
+    let radius = schema
+        .radius
+        .filter(|e| e.is_finite() && *e > 0.0)
+        .unwrap_or(DEFAULT_RADIUS)
+        .min(MAX_RADIUS);
+    let distance = || Expr::cust_with_values(HAVERSINE, [schema.lat, schema.lat, schema.lng]);
+    let courts = Courts::find()
+        .column_as(distance(), "distance")
+        .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+        .filter(db::courts::Column::Latitude.is_not_null())
+        .filter(Expr::expr(distance()).lte(radius))
+        .order_by_asc(Expr::cust("distance"))
+        .into_model::<CourtDistance>()
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+
+    let (mut list, distances): (Vec<_>, Vec<_>) = courts
+        .into_iter()
+        .map(|e| {
+            (
+                CourtUserSchema {
+                    admin_id: None,
+                    ..e.court.into()
+                },
+                e.distance,
+            )
+        })
+        .unzip();
+    CourtOp::fill(&mut list, &state).await?;
+    let favorites = FavoriteOp::ids(auth.user.user_id, &state).await?;
+    FavoriteOp::mark(&mut list, &favorites);
+    let courts: Vec<_> = list
+        .into_iter()
+        .zip(distances)
+        .map(|(court, distance)| CourtNearbySchema { court, distance })
+        .collect();
 
     Ok(Json(json!({
         "code":0,
@@ -44,3 +205,117 @@ async fn all(
         "data":courts
     })))
 }
+
+//球场详情附带的最新评价数
+const DETAIL_REVIEWS: u64 = 5;
+
+pub(crate) async fn detail(
+    auth: Option<Extension<JWTAuthMiddleware>>,
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let court = Courts::find_by_id(court_id)
+        .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string()))?;
+    let stats = CourtOp::stats(court_id, &state).await?;
+    let addons = AddonOp::list(court_id, true, &state).await?;
+    let open_hours = CourtOp::open_hours_30d(&court, &state).await?;
+    let (_, reviews) = ReviewOp::list(court_id, 1, DETAIL_REVIEWS, &state).await?;
+    let utilization = if open_hours > 0.0 {
+        (stats.booked_hours / open_hours).min(1.0)
+    } else {
+        0.0
+    };
+
+    let mut courts = vec![CourtUserSchema {
+        admin_id: None,
+        ..court.into()
+    }];
+    CourtOp::fill(&mut courts, &state).await?;
+    if let Some(auth) = auth {
+        let favorites = FavoriteOp::ids(auth.user.user_id, &state).await?;
+        FavoriteOp::mark(&mut courts, &favorites);
+    }
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "court":courts.pop(),
+            "upcoming_orders":stats.upcoming_orders,
+            "booked_hours_30d":stats.booked_hours,
+            "utilization_30d":utilization,
+            "addons":addons,
+            "reviews":reviews
+        }
+    })))
+}
+
+//球场评价, 分页
+pub(crate) async fn reviews(
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (total, reviews) = ReviewOp::list(court_id, schema.page, schema.page_size, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "total":total,
+            "page":schema.page,
+            "reviews":reviews
+        }
+    })))
+}
+
+//球场分享海报, 封面图与小程序码合成, 按球场缓存
+pub(crate) async fn poster(
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let url = PosterOp::court(court_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "url":url
+        }
+    })))
+}
+
+//某天的可预约时段
+pub(crate) async fn availability(
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+    Query(schema): Query<AvailabilityQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !(15..=240).contains(&schema.step) {
+        return Err(HandleErr::BadRequest(-1, "step应在15~240之间".to_string()));
+    }
+    let court = Courts::find_by_id(court_id)
+        .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string()))?;
+    let slots = AvailabilityOp::of(&court, schema.date, schema.step, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":slots
+    })))
+}
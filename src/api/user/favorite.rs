@@ -0,0 +1,62 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::court::favorite::{FavoriteOp, FavoriteSave},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/favorite/* 挂载中");
+    Router::new()
+        .route("/add", post(add))
+        .route("/del", post(del))
+        .route("/list", get(list))
+}
+
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<FavoriteSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    FavoriteOp::add(auth.user.user_id, schema.court_id, &state).await?;
+    info!("{} 收藏球场({})", auth.user.user_name, schema.court_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"收藏成功",
+        "data":null
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<FavoriteSave>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    FavoriteOp::del(auth.user.user_id, schema.court_id, &state).await?;
+    info!("{} 取消收藏球场({})", auth.user.user_name, schema.court_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已取消收藏",
+        "data":null
+    })))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let courts = FavoriteOp::list(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":courts
+    })))
+}
@@ -0,0 +1,129 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::sea_orm_active_enums::FeedbackCategory,
+        feedback::{FeedbackOp, FeedbackSubmit, MAX_IMAGES},
+        order::PageQuery,
+        upload::{UploadOp, UploadPurpose},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Query, State},
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+//单次上传大小上限
+const MAX_UPLOAD_SIZE: usize = 10 * 1024 * 1024;
+
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/feedback/* 挂载中");
+    Router::new()
+        .route("/", get(mine).post(submit))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+}
+
+//我提交的反馈与处理进度
+async fn mine(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (feedback, total) =
+        FeedbackOp::mine(auth.user.user_id, schema.page, schema.page_size, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "feedback":feedback,
+            "total":total
+        }
+    })))
+}
+
+//multipart字段: category(facility/billing/suggestion), content, court_id, order_id, file(最多3张)
+async fn submit(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (mut category, mut content, mut court_id, mut order_id) = (None, None, None, None);
+    let mut files = vec![];
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?
+    {
+        match field.name() {
+            Some("file") => {
+                if files.len() == MAX_IMAGES {
+                    return Err(HandleErr::BadRequest(
+                        -1,
+                        format!("最多上传{}张图片", MAX_IMAGES),
+                    ));
+                }
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                //按文件内容识别格式, 不信任客户端声明的Content-Type
+                files.push(UploadOp::validate(data.to_vec(), UploadPurpose::Feedback).await?);
+            }
+            Some(name) => {
+                let name = name.to_string();
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                let text = text.trim();
+                match name.as_str() {
+                    "category" => {
+                        category = Some(
+                            serde_json::from_value::<FeedbackCategory>(json!(text)).map_err(
+                                |_| HandleErr::BadRequest(-1, "反馈类型无效".to_string()),
+                            )?,
+                        );
+                    }
+                    "content" => content = Some(text.to_string()),
+                    "court_id" if !text.is_empty() => {
+                        court_id =
+                            Some(Uuid::parse_str(text).map_err(|_| {
+                                HandleErr::BadRequest(-1, "court_id无效".to_string())
+                            })?);
+                    }
+                    "order_id" if !text.is_empty() => {
+                        order_id =
+                            Some(Uuid::parse_str(text).map_err(|_| {
+                                HandleErr::BadRequest(-1, "order_id无效".to_string())
+                            })?);
+                    }
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+    }
+    let schema = FeedbackSubmit {
+        category: category.ok_or(HandleErr::BadRequest(-1, "缺少反馈类型".to_string()))?,
+        content: content.unwrap_or_default(),
+        court_id,
+        order_id,
+    };
+    let feedback = FeedbackOp::submit(auth.user.user_id, schema, files, &state).await?;
+    info!("{} 提交反馈({})", auth.user.user_name, feedback.feedback_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"提交成功",
+        "data":feedback
+    })))
+}
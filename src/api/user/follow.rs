@@ -0,0 +1,61 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::follow::{FollowOp, VenueFollow, VenueUnfollow},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/follow/* 挂载中");
+    Router::new()
+        .route("/add", post(add))
+        .route("/del", post(del))
+        .route("/list", get(list))
+}
+
+async fn add(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VenueFollow>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    FollowOp::add(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"关注成功",
+        "data":null
+    })))
+}
+
+async fn del(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<VenueUnfollow>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    FollowOp::del(auth.user.user_id, schema.venue_id, &state).await?;
+    info!("{} 取消关注场馆({})", auth.user.user_name, schema.venue_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已取消关注",
+        "data":null
+    })))
+}
+
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let venues = FollowOp::list(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":venues
+    })))
+}
@@ -0,0 +1,107 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::sea_orm_active_enums::PayMethod,
+        gift_card::{GiftCardBuy, GiftCardOp, GiftCardRedeem},
+        payment::{Payment, ATTACH_GIFT_CARD},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{info, warn};
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/giftcard/* 挂载中");
+    Router::new()
+        .route("/mine", get(mine))
+        .route("/buy", post(buy))
+        .route("/redeem", post(redeem))
+}
+
+//我购买与兑换的礼品卡, 支付成功后可将兑换码赠送他人
+async fn mine(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let cards = GiftCardOp::mine::<String>(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":cards
+    })))
+}
+
+//微信支付购买礼品卡, 支付成功通知后兑换码生效
+async fn buy(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<GiftCardBuy>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if matches!(state.payment, Payment::Offline) {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "未开通线上支付, 请联系管理员".to_string(),
+        ));
+    }
+    let card = GiftCardOp::purchase::<String>(auth.user.user_id, schema, &state).await?;
+    let payment = state
+        .payment
+        .prepay(
+            card.card_id,
+            ATTACH_GIFT_CARD,
+            "礼品卡",
+            card.amount,
+            auth.user.openid.as_deref(),
+        )
+        .await
+        .map_err(|err| {
+            warn!("礼品卡({})发起支付失败: {}", card.card_id, err);
+            HandleErr::BadRequest(-1, "发起支付失败, 请稍后重试".to_string())
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "card_id":card.card_id,
+            "amount":card.amount,
+            "payment":payment
+        }
+    })))
+}
+
+//兑换到钱包余额, 指定订单时随后用余额支付该订单
+async fn redeem(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<GiftCardRedeem>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (card, record) =
+        GiftCardOp::redeem::<String>(auth.user.user_id, &schema.code, &state).await?;
+    let Some(order_id) = schema.order_id else {
+        return Ok(Json(json!({
+            "code":0,
+            "msg":"兑换成功",
+            "data":{
+                "amount":card.amount,
+                "balance":record.balance
+            }
+        })));
+    };
+    let paid = super::order::pay_with(auth, state.clone(), order_id, PayMethod::Balance)
+        .await
+        .map_err(|err| match err {
+            HandleErr::BadRequest(code, msg) => {
+                HandleErr::BadRequest(code, format!("礼品卡已兑换至余额, 订单支付失败: {}", msg))
+            }
+            err => err,
+        })?;
+    GiftCardOp::applied::<String>(card.card_id, order_id, &state).await?;
+    Ok(paid)
+}
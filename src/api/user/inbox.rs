@@ -0,0 +1,80 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        notify::inbox::{InboxOp, InboxRead},
+        order::PageQuery,
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/notifications/* 挂载中");
+    Router::new()
+        .route("/", get(list))
+        .route("/unread", get(unread))
+        .route("/read", post(read))
+}
+
+//站内通知列表与未读数
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (notifications, total) =
+        InboxOp::list(auth.user.user_id, schema.page, schema.page_size, &state).await?;
+    let unread = InboxOp::unread(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "notifications":notifications,
+            "total":total,
+            "unread":unread
+        }
+    })))
+}
+
+async fn unread(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let unread = InboxOp::unread(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "unread":unread
+        }
+    })))
+}
+
+//标记已读, 未指定通知时全部已读
+async fn read(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<InboxRead>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let count = InboxOp::read(auth.user.user_id, schema.notification_ids, &state).await?;
+    let unread = InboxOp::unread(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "read":count,
+            "unread":unread
+        }
+    })))
+}
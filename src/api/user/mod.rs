@@ -1,21 +1,70 @@
 use crate::{
     appstate::AppState,
-    error::HandleErr,
-    module::{db::prelude::Users, user::UserSchema},
+    error::{unique_err, HandleErr},
+    module::{
+        db::{self, prelude::Users},
+        user::{NotifyPreference, PhoneBind, ProfileUpdate, UserOP, UserSchema, WechatBind},
+    },
     utils::auth::JWTAuthMiddleware,
 };
-use axum::{extract::State, response::IntoResponse, routing::get, Extension, Json, Router};
-use sea_orm::EntityTrait;
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
 use serde_json::json;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+pub mod account;
+pub mod auth;
+pub mod contact;
+pub mod coupon;
 pub mod court;
+pub mod favorite;
+pub mod feedback;
+pub mod follow;
+pub mod gift_card;
+pub mod inbox;
 pub mod order;
+pub mod package;
+pub mod partner;
+pub mod points;
+pub mod referral;
+pub mod search;
+pub mod session;
+pub mod stats;
+pub mod subscribe;
+pub mod upload;
+pub mod wallet;
 pub fn router() -> Router<Arc<AppState>> {
     info!("/user 挂载中");
     Router::new()
         .nest("/order", order::router())
+        .nest("/wallet", wallet::router())
+        .nest("/coupon", coupon::router())
+        .nest("/package", package::router())
+        .nest("/partner", partner::router())
+        .nest("/giftcard", gift_card::router())
+        .nest("/points", points::router())
+        .nest("/favorite", favorite::router())
+        .nest("/contact", contact::router())
+        .nest("/follow", follow::router())
+        .nest("/feedback", feedback::router())
+        .nest("/notifications", inbox::router())
+        .nest("/account", account::router())
+        .nest("/referral", referral::router())
+        .nest("/search", search::router())
+        .nest("/sessions", session::router())
+        .nest("/stats", stats::router())
+        .nest("/subscribe", subscribe::router())
+        .nest("/upload", upload::router())
         .route("/info", get(user_info))
+        .route("/profile", get(profile).post(update_profile))
+        .route("/notify", post(notify_preference))
+        .route("/wechat/bind", post(wechat_bind))
+        .route("/wechat/phone", post(phone_bind))
         .nest("/court", court::router())
 }
 
@@ -42,3 +91,121 @@ async fn user_info(
         }
     })))
 }
+
+async fn profile(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let profile = UserOP::profile(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "profile":profile
+        }
+    })))
+}
+
+//修改个人资料, 只更新提供的字段
+async fn update_profile(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ProfileUpdate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    debug!("{} 修改个人资料: {:?}", auth.user.user_id, schema);
+    let profile = UserOP::update_profile(auth.user.user_id, schema, &state).await?;
+    info!("{} 修改个人资料", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"修改成功",
+        "data":{
+            "profile":profile
+        }
+    })))
+}
+
+//设置是否接收预约提醒
+async fn notify_preference(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<NotifyPreference>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    debug!("{} 设置预约提醒: {}", auth.user.user_id, schema.reminder);
+    db::users::ActiveModel {
+        user_id: Set(auth.user.user_id),
+        notify_reminder: Set(schema.reminder),
+        ..Default::default()
+    }
+    .update(&state.db)
+    .await
+    .map_err(|err| {
+        let id = uuid::Uuid::new_v4();
+        error!("{} >>>> {}", id, err.to_string());
+        HandleErr::ServerInnerErr::<String>(id)
+    })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":null
+    })))
+}
+
+//绑定微信openid, 微信支付下单时使用
+async fn wechat_bind(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<WechatBind>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let Some(wechat) = &state.wechat else {
+        return Err(HandleErr::BadRequest(-1, "未配置微信小程序".to_string()));
+    };
+    let session = wechat
+        .code2session(schema.code.trim())
+        .await
+        .map_err(|err| {
+            warn!("{} 绑定微信失败: {}", auth.user.user_id, err);
+            HandleErr::BadRequest(-1, "微信授权失败".to_string())
+        })?;
+    db::users::ActiveModel {
+        user_id: Set(auth.user.user_id),
+        openid: Set(Some(session.openid)),
+        ..Default::default()
+    }
+    .update(&state.db)
+    .await
+    //openid唯一, 该微信已绑定其他账号时提示
+    .map_err(|err| unique_err(err, "该微信已绑定其他账号"))?;
+    info!("{} 绑定微信", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"绑定成功",
+        "data":null
+    })))
+}
+
+//绑定微信手机号, 小程序getPhoneNumber得到的code换取手机号, 管理员处理预约时可联系用户
+async fn phone_bind(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PhoneBind>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let Some(wechat) = &state.wechat else {
+        return Err(HandleErr::BadRequest(-1, "未配置微信小程序".to_string()));
+    };
+    let phone = wechat
+        .phone_number(schema.code.trim())
+        .await
+        .map_err(|err| {
+            warn!("{} 获取微信手机号失败: {}", auth.user.user_id, err);
+            HandleErr::BadRequest(-1, "获取手机号失败".to_string())
+        })?;
+    UserOP::bind_phone(auth.user.user_id, phone.clone(), &state).await?;
+    info!("{} 绑定手机号", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"绑定成功",
+        "data":{
+            "phone":phone
+        }
+    })))
+}
@@ -1,102 +1,653 @@
 use crate::{
     appstate::AppState,
     error::HandleErr,
-    module::db::{self, prelude::*},
+    module::contact::ContactOp,
+    module::coupon::CouponOp,
+    module::court::{
+        addon::AddonOp,
+        booking_rule::{self, BookingRuleOp},
+        review::{ReviewCreate, ReviewOp},
+    },
+    module::db::{
+        self,
+        prelude::*,
+        sea_orm_active_enums::{CourtStatus, OrderState, PayMethod, ShareStatus},
+    },
+    module::money,
+    module::order::{
+        self as order,
+        group::{GroupCreate, GroupOp},
+        hold::{HoldCreate, HoldOp, HoldRelease},
+        invoice::{InvoiceOp, InvoiceRequest},
+        item::{self, ItemOp},
+        refund::{RefundOp, RefundRequest},
+        series::{SeriesCancel, SeriesConflict, SeriesCreate, SeriesOp},
+        state,
+    },
     module::order::{
-        DelOrder, OrderOp, OrderStatus, OrderUserSchema, SaveOrder, SubmitOrder, UpdateOrder,
+        CancelOrder, CheckIn, CursorQuery, OrderOp, OrderUserSchema, PageQuery, PayQuery,
+        SaveOrder, SubmitOrder, UpdateOrder,
+    },
+    module::package::PackageOp,
+    module::partner::PartnerOp,
+    module::payment::{
+        provider::{Charge, Payer, PaymentProvider, Provider},
+        wechat::RequestPayment,
+    },
+    module::points::{self, PointsOp},
+    module::pricing::{self, PricingOp},
+    module::promotion::{self, Applied, PromotionOp},
+    module::tier::TierOp,
+    utils::{
+        auth::JWTAuthMiddleware,
+        cursor::{self, Cursor},
+        qrcode,
+        ratelimit::{ratelimit, RateLimiter},
     },
-    utils::auth::JWTAuthMiddleware,
 };
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
     response::IntoResponse,
     routing::{delete, get, post},
     Extension, Json, Router,
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect, RelationTrait};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::{
+    ColumnTrait, Condition, EntityTrait, Order, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, RelationTrait,
+};
 use serde_json::json;
-use std::sync::Arc;
-use tracing::{error, info};
+use std::{sync::Arc, time::Duration};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 pub fn router() -> Router<Arc<AppState>> {
     info!("/order/* 挂载中");
+    //下单按用户严格限流, 新旧接口共用
+    let limiter = RateLimiter::new(10, Duration::from_secs(60));
+    let limit = || middleware::from_fn_with_state(limiter.clone(), ratelimit);
     Router::new()
-        .route("/submit", post(submit))
+        .route("/quote", post(quote))
+        .route("/create", post(create).layer(limit()))
+        .route("/pay/:order_id", post(pay))
+        .route("/pay_balance/:order_id", post(pay_balance))
+        //兼容旧版小程序
+        .route("/submit", post(create).layer(limit()))
         .route("/all", get(all))
-        .route("/del", delete(del))
+        .route("/mine", get(mine))
+        .route("/detail/:order_id", get(detail))
+        .route("/cancel", post(cancel))
+        //兼容旧版小程序
+        .route("/del", delete(cancel))
         .route("/update", post(update))
+        .route("/refund", post(refund))
+        .route("/refunds", get(refunds))
+        .route("/review", post(review))
+        .route("/invoice", post(invoice))
+        .route("/invoice/:order_id", get(invoice_of))
+        .route("/reschedule", post(reschedule))
+        .route("/timeline/:order_id", get(timeline))
+        .route("/checkin", post(checkin))
+        .route("/ticket/:order_id", get(ticket))
+        .route("/hold", post(hold))
+        .route("/hold/release", post(hold_release))
+        .route("/group/create", post(group_create))
+        .route("/group/pay/:participant_id", post(group_pay))
+        .route("/group/:participant_id", get(group_share))
+        .route("/series/create", post(series_create))
+        .route("/series/cancel", post(series_cancel))
+        .route("/series/:series_id", get(series_orders))
+}
+
+//重复提交时携带相同值的请求头, 返回首次提交生成的订单
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, HandleErr<String>> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .map(|e| e.trim().to_string())
+        .unwrap_or_default();
+    if key.is_empty() || key.len() > 64 {
+        return Err(HandleErr::BadRequest(-1, "Idempotency-Key无效".to_string()));
+    }
+    Ok(Some(key))
+}
+
+//去掉首尾空白, 空备注视为未填写
+fn remark(remark: Option<String>) -> Result<Option<String>, HandleErr<String>> {
+    let remark = remark
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty());
+    if remark.as_ref().is_some_and(|e| e.chars().count() > 200) {
+        return Err(HandleErr::BadRequest(-1, "备注过长".to_string()));
+    }
+    Ok(remark)
+}
+
+//待支付订单按选择的方式发起支付, 用户可选择微信支付或余额支付
+async fn charge(
+    order: &db::orders::Model,
+    court: &db::courts::Model,
+    method: &PayMethod,
+    openid: Option<&str>,
+    state: &AppState,
+) -> Result<Charge, HandleErr<String>> {
+    if !matches!(method, PayMethod::Wechat | PayMethod::Balance) {
+        return Err(HandleErr::BadRequest(-1, "不支持的支付方式".to_string()));
+    }
+    if order.status != OrderState::PendingPayment {
+        return Ok(Charge::Prepay(None));
+    }
+    let description = format!(
+        "{} {}",
+        court.court_name,
+        order.apt_start.format("%m-%d %H:%M")
+    );
+    let payer = Payer {
+        openid,
+        description: &description,
+    };
+    let provider = Provider::of(method);
+    info!(
+        "订单({})发起支付, 支付方式: {:?}",
+        order.order_id,
+        provider.method()
+    );
+    provider.charge(order, &payer, state).await
+}
+
+//下单后发起支付, 失败时记录到订单时间线并返回失败原因
+//订单保持待支付, 可通过 /order/pay 重试
+async fn settle(
+    order: db::orders::Model,
+    court: &db::courts::Model,
+    method: &PayMethod,
+    openid: Option<&str>,
+    state: &AppState,
+) -> (db::orders::Model, Option<RequestPayment>, Option<String>) {
+    let err = match charge(&order, court, method, openid, state).await {
+        Ok(Charge::Prepay(payment)) => return (order, payment, None),
+        Ok(Charge::Paid(paid)) => return (*paid, None, None),
+        Err(err) => err,
+    };
+    let msg = match err {
+        HandleErr::BadRequest(_, msg) => msg,
+        HandleErr::ServerInnerErr(id) => format!("服务器内部错误, 错误代码{}", id),
+        HandleErr::UnAuthorized => "未授权".to_string(),
+    };
+    warn!("订单({})发起{:?}支付失败: {}", order.order_id, method, msg);
+    if let Err(err) = OrderOp::record::<String, _>(
+        order.order_id,
+        "pay_failed",
+        json!({"method":method, "error":msg}),
+        &state.db,
+    )
+    .await
+    {
+        warn!("订单({})记录支付失败: {:?}", order.order_id, err);
+    }
+    (order, None, Some(msg))
+}
+
+//下单成功的返回数据, 应付总额包含押金
+//发起支付失败或已支付时payment为空, 失败时pay_error为失败原因, 可通过 /order/pay 重新获取
+fn created(
+    order: db::orders::Model,
+    court: db::courts::Model,
+    payment: Option<RequestPayment>,
+    pay_error: Option<String>,
+) -> serde_json::Value {
+    let order = OrderUserSchema {
+        order_id: order.order_id,
+        user_id: order.user_id,
+        court_id: order.court_id,
+        court_name: court.court_name,
+        court_location: court.location,
+        create_time: order.create_time,
+        apt_start: order.apt_start,
+        apt_end: order.apt_end,
+        cost: order.cost,
+        deposit: order.deposit,
+        deposit_status: order.deposit_status,
+        status: order.status,
+        remark: order.remark,
+        contact_name: order.contact_name,
+        contact_phone: order.contact_phone,
+    };
+    let mut data = json!(order);
+    data["total"] = json!(order.cost + order.deposit);
+    data["payment"] = json!(payment);
+    data["pay_error"] = json!(pay_error);
+    json!({
+        "code":0,
+        "msg":"预定成功",
+        "data":data
+    })
 }
 
-async fn submit(
+//下单与报价的抵扣金额、参加的活动与应付金额
+//次卡优先抵扣场地费, 会员折扣与促销活动优惠剩余的场地费, 优惠券抵扣剩余部分, 最后按积分抵扣
+async fn deductions(
+    schema: &SubmitOrder,
+    user_id: Uuid,
+    court: &db::courts::Model,
+    addons: &[(db::court_addons::Model, i32)],
+    state: &AppState,
+) -> Result<(item::Deductions, Vec<Applied>, Decimal), HandleErr<String>> {
+    let gross =
+        PricingOp::order_cost(court, schema.apt_start, schema.apt_end, addons, state).await?;
+    let court_cost = gross
+        - pricing::addons_cost(
+            addons
+                .iter()
+                .map(|(addon, quantity)| (addon.price, addon.per_hour, *quantity)),
+            schema.apt_start,
+            schema.apt_end,
+        );
+    let hours = (schema.apt_end - schema.apt_start).num_minutes() as f64 / 60.0;
+    let available = PackageOp::available(user_id, court.admin_id, &state.db).await?;
+    let (package_hours, package_amount) = pricing::package(court_cost, hours, available);
+    let member_amount = match TierOp::benefit_of(user_id, &state.db).await? {
+        Some(benefit) => pricing::member(court_cost - package_amount, benefit.discount_percent),
+        None => Decimal::ZERO,
+    };
+    let candidates = PromotionOp::available(court, user_id, &state.db).await?;
+    let promotions = promotion::best(
+        &candidates,
+        court_cost,
+        court_cost - package_amount - member_amount,
+        schema.apt_start,
+        schema.apt_end,
+        schema.coupon_id.is_some(),
+    );
+    let promotion_amount = promotion::total(&promotions);
+    let cash = pricing::net(
+        gross - package_amount - member_amount - promotion_amount,
+        Decimal::ZERO,
+    );
+    let discount = match schema.coupon_id {
+        Some(_) if cash <= Decimal::ZERO => {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "订单已由次卡全额抵扣, 无需使用优惠券".to_string(),
+            ))
+        }
+        Some(coupon_id) => {
+            let coupon = CouponOp::usable(coupon_id, user_id, court, cash, &state.db).await?;
+            pricing::discount(&coupon, cash)
+        }
+        None => Decimal::ZERO,
+    };
+    let cash = pricing::net(cash, discount);
+    let (points_used, points_amount) = match schema.points {
+        Some(requested) if requested > 0 => {
+            let rule = PointsOp::rule(&state.db).await?;
+            let balance = PointsOp::balance(user_id, &state.db).await?;
+            points::redeem(cash, requested, balance, &rule)
+        }
+        _ => (0, Decimal::ZERO),
+    };
+    Ok((
+        item::Deductions {
+            package_hours,
+            package_amount,
+            discount,
+            points_used,
+            points_amount,
+            promotion_amount,
+            member_amount,
+        },
+        promotions,
+        money::round(cash - points_amount),
+    ))
+}
+
+//下单前报价, 与下单使用相同的计价方式
+async fn quote(
     Extension(auth): Extension<JWTAuthMiddleware>,
     State(state): State<Arc<AppState>>,
     Json(schema): Json<SubmitOrder>,
 ) -> Result<impl IntoResponse, HandleErr<String>> {
-    if !OrderOp::hasClash(
+    if schema.apt_start >= schema.apt_end {
+        return Err(HandleErr::BadRequest(-1, "时间范围无效".to_string()));
+    }
+    let court = Courts::find_by_id(schema.court_id)
+        .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+    let addons = AddonOp::resolve(schema.court_id, &schema.addons, &state).await?;
+    let (deductions, promotions, cost) =
+        deductions(&schema, auth.user.user_id, &court, &addons, &state).await?;
+    let items = item::breakdown(
+        court.price_per_hour,
+        cost,
+        &item::lines(&addons),
+        deductions,
+        court.deposit,
+        schema.apt_start,
+        schema.apt_end,
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "cost":cost,
+            "discount":deductions.discount,
+            "package_hours":deductions.package_hours,
+            "package_amount":deductions.package_amount,
+            "points_used":deductions.points_used,
+            "points_amount":deductions.points_amount,
+            "promotion_amount":deductions.promotion_amount,
+            "member_amount":deductions.member_amount,
+            "promotions":promotions,
+            "deposit":court.deposit,
+            "total":cost + court.deposit,
+            "items":items
+        }
+    })))
+}
+
+//下单, 冲突检测在事务中加锁完成
+async fn create(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(schema): Json<SubmitOrder>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let key = idempotency_key(&headers)?;
+    let remark = remark(schema.remark.clone())?;
+    let method = schema.pay_method.clone().unwrap_or(PayMethod::Wechat);
+    if !matches!(method, PayMethod::Wechat | PayMethod::Balance) {
+        return Err(HandleErr::BadRequest(-1, "不支持的支付方式".to_string()));
+    }
+    let court = Courts::find_by_id(schema.court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+    if let Some(key) = &key {
+        if let Some(order) =
+            OrderOp::by_idempotency_key::<String, _>(auth.user.user_id, key, &state.db).await?
+        {
+            if order.court_id != court.court_id {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "Idempotency-Key已被其他订单使用".to_string(),
+                ));
+            }
+            info!("{} 重复提交订单({})", auth.user.user_name, order.order_id);
+            let (order, payment, pay_error) =
+                settle(order, &court, &method, auth.user.openid.as_deref(), &state).await;
+            return Ok(Json(created(order, court, payment, pay_error)));
+        }
+    }
+    if schema.apt_start >= schema.apt_end {
+        return Err(HandleErr::BadRequest(-1, "时间范围无效".to_string()));
+    }
+    if schema.apt_start <= chrono::Utc::now().naive_utc() {
+        return Err(HandleErr::BadRequest(-1, "预约开始时间已过".to_string()));
+    }
+    BookingRuleOp::check(
+        schema.court_id,
+        auth.user.user_id,
+        schema.apt_start,
+        schema.apt_end,
+        None,
+        &state,
+    )
+    .await?;
+    if OrderOp::hasClash(
         schema.apt_start,
         schema.apt_end,
         None,
         schema.court_id,
+        auth.user.user_id,
         &state,
     )
     .await
     .map_err(|err| err.into())?
     {
-        let court = Courts::find_by_id(schema.court_id)
-            .one(&state.db)
-            .await
-            .map_err(|err| {
-                let id = Uuid::new_v4();
-                error!("{} >>>> {}", id, err.to_string());
-                HandleErr::ServerInnerErr(id)
-            })?
-            .unwrap();
-
-        let cost =
-            (schema.apt_end - schema.apt_start).num_minutes() as f64 / 60.0 * court.price_per_hour;
-        let order = OrderOp::save(
-            auth.user.user_id,
-            SaveOrder {
-                order_id: None,
-                court_id: Some(schema.court_id),
-                apt_start: schema.apt_start,
-                apt_end: schema.apt_end,
-                cost,
-            },
-            &state,
-        )
-        .await?;
-        let order = OrderUserSchema {
-            order_id: order.order_id,
-            court_id: order.court_id,
-            court_name: court.court_name,
-            create_time: order.create_time,
-            apt_start: order.apt_start,
-            apt_end: order.apt_end,
+        return Err(HandleErr::BadRequest(-1, "时间冲突".to_string()));
+    }
+    let addons = AddonOp::resolve(schema.court_id, &schema.addons, &state).await?;
+    let (deductions, promotions, cost) =
+        deductions(&schema, auth.user.user_id, &court, &addons, &state).await?;
+    let contact = match schema.contact_id {
+        Some(contact_id) => {
+            Some(ContactOp::take::<String, _>(auth.user.user_id, contact_id, &state.db).await?)
+        }
+        None => None,
+    };
+    let order = OrderOp::create(
+        auth.user.user_id,
+        SaveOrder {
+            order_id: None,
+            court_id: Some(schema.court_id),
+            apt_start: schema.apt_start,
+            apt_end: schema.apt_end,
             cost,
-        };
+            deposit: Some(court.deposit),
+            idempotency_key: key,
+            remark,
+            coupon_id: schema.coupon_id,
+            discount: Some(deductions.discount),
+            package: Some((deductions.package_hours, deductions.package_amount)),
+            points: Some((deductions.points_used, deductions.points_amount)),
+            promotions,
+            member: Some(deductions.member_amount),
+            contact,
+        },
+        &addons,
+        &state,
+    )
+    .await?;
+    let (order, payment, pay_error) =
+        settle(order, &court, &method, auth.user.openid.as_deref(), &state).await;
+    Ok(Json(created(order, court, payment, pay_error)))
+}
 
-        Ok(Json(json!({
+//重新获取待支付订单的支付参数, 拼单按分摊支付
+async fn pay(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Query(schema): Query<PayQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let method = schema.method.unwrap_or(PayMethod::Wechat);
+    pay_with(auth, state, order_id, method).await
+}
+
+//余额支付, 同 /order/pay?method=balance
+async fn pay_balance(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    pay_with(auth, state, order_id, PayMethod::Balance).await
+}
+
+pub(crate) async fn pay_with(
+    auth: JWTAuthMiddleware,
+    state: Arc<AppState>,
+    order_id: Uuid,
+    method: PayMethod,
+) -> Result<Json<serde_json::Value>, HandleErr<String>> {
+    let order = OrderOp::owned::<String>(order_id, auth.user.user_id, &state).await?;
+    if order.status != OrderState::PendingPayment || order.pay_deadline.is_some() {
+        return Err(HandleErr::BadRequest(-1, "订单不是待支付状态".to_string()));
+    }
+    let court = Courts::find_by_id(order.court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+    match charge(&order, &court, &method, auth.user.openid.as_deref(), &state).await? {
+        Charge::Prepay(Some(payment)) => Ok(Json(json!({
             "code":0,
-            "msg":"预定成功",
+            "msg":"OK",
+            "data":payment
+        }))),
+        Charge::Prepay(None) => Err(HandleErr::BadRequest(-1, "订单无需线上支付".to_string())),
+        Charge::Paid(order) => Ok(Json(json!({
+            "code":0,
+            "msg":"支付成功",
             "data":order
-        })))
-    } else {
-        Err(HandleErr::BadRequest(-1, "时间冲突".to_string()))
+        }))),
     }
 }
 
+//我的预约, 未结束与历史订单分别分页
+async fn mine(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let now = chrono::Utc::now().naive_utc();
+    let upcoming = Condition::all()
+        .add(db::orders::Column::AptEnd.gt(now))
+        .add(db::orders::Column::Status.is_not_in(state::RELEASED));
+    let mut res = vec![];
+    for (cond, order) in [
+        (upcoming.clone(), Order::Asc),
+        (upcoming.not(), Order::Desc),
+    ] {
+        //包括作为球友加入的预约
+        let paginator = Orders::find()
+            .filter(
+                Condition::any()
+                    .add(db::orders::Column::UserId.eq(auth.user.user_id))
+                    .add(
+                        db::orders::Column::OrderId
+                            .in_subquery(PartnerOp::joined_orders(auth.user.user_id)),
+                    ),
+            )
+            .filter(cond)
+            .join(
+                sea_orm::JoinType::InnerJoin,
+                db::orders::Relation::Courts.def(),
+            )
+            .column_as(db::courts::Column::CourtName, "court_name")
+            .column_as(db::courts::Column::Location, "court_location")
+            .order_by(db::orders::Column::AptStart, order)
+            .into_model::<OrderUserSchema>()
+            .paginate(&state.db, schema.page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let orders = paginator.fetch_page(schema.page - 1).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let orders: Vec<_> = orders.iter().map(|e| e.view(auth.user.user_id)).collect();
+        res.push(json!({"total":total,"orders":orders}));
+    }
+    let past = res.pop();
+    let upcoming = res.pop();
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "page":schema.page,
+            "upcoming":upcoming,
+            "past":past
+        }
+    })))
+}
+
+//订单详情, 含价格明细、附加项目与事件记录
+async fn detail(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = OrderOp::owned::<String>(order_id, auth.user.user_id, &state).await?;
+    let items = ItemOp::of_order(order.order_id, &state.db).await?;
+    let timeline = OrderOp::timeline(order.order_id, &state).await?;
+    let addons = ItemOp::addons_of(order.order_id, &state.db)
+        .await?
+        .into_iter()
+        .map(|(name, price, per_hour, quantity)| {
+            json!({"name":name, "price":price, "per_hour":per_hour, "quantity":quantity})
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "order":order,
+            "items":items,
+            "addons":addons,
+            "timeline":timeline
+        }
+    })))
+}
+
 async fn all(
     Extension(auth): Extension<JWTAuthMiddleware>,
     State(state): State<Arc<AppState>>,
+    Query(schema): Query<CursorQuery>,
 ) -> Result<impl IntoResponse, HandleErr<String>> {
-    let orders = Orders::find()
+    let select = Orders::find()
         .filter(db::orders::Column::UserId.eq(auth.user.user_id))
         .join(
             sea_orm::JoinType::InnerJoin,
             db::orders::Relation::Courts.def(),
         )
         .column_as(db::courts::Column::CourtName, "court_name")
+        .column_as(db::courts::Column::Location, "court_location");
+    //传入cursor时按(apt_start, order_id)倒序分页, 首页传空值
+    let Some(token) = schema.cursor else {
+        let orders = select
+            .into_model::<OrderUserSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        return Ok(Json(json!({
+            "code":0,
+            "msg":"查询成功",
+            "data":orders
+        })));
+    };
+    if !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let mut cond = Condition::all();
+    if !token.is_empty() {
+        cond = cond.add(order::after(&token)?);
+    }
+    let rows = select
+        .filter(cond)
+        .order_by_desc(db::orders::Column::AptStart)
+        .order_by_desc(db::orders::Column::OrderId)
+        .limit(schema.page_size + 1)
         .into_model::<OrderUserSchema>()
         .all(&state.db)
         .await
@@ -105,39 +656,170 @@ async fn all(
             error!("{} >>>> {}", id, err.to_string());
             HandleErr::ServerInnerErr(id)
         })?;
+    let (orders, next) = cursor::page(rows, schema.page_size, |e| Cursor {
+        apt_start: e.apt_start,
+        order_id: e.order_id,
+    });
     Ok(Json(json!({
         "code":0,
         "msg":"查询成功",
-        "data":orders
+        "data":{
+            "next_cursor":next,
+            "orders":orders
+        }
     })))
 }
 
-async fn del(
+//取消订单, 已开始的订单不能取消
+//超过球场免费取消时间的已支付订单收取取消费用, 其余部分退款
+async fn cancel(
     Extension(auth): Extension<JWTAuthMiddleware>,
     State(state): State<Arc<AppState>>,
-    Json(schema): Json<DelOrder>,
-) -> Result<impl IntoResponse, HandleErr<&'static str>> {
-    if OrderStatus::Done == OrderOp::orderStatus(schema.order_id, auth.user.user_id, &state).await?
-    {
-        return Err(HandleErr::BadRequest(-1, "当前订单已完成"));
+    Json(schema): Json<CancelOrder>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.reason.chars().count() > 200 {
+        return Err(HandleErr::BadRequest(-1, "取消原因过长".to_string()));
     }
-    let rows_affected = Orders::delete_by_id(schema.order_id)
-        .exec(&state.db)
-        .await
-        .map_err(|err| {
-            let id = Uuid::new_v4();
-            error!("{} >>>> {}", id, err.to_string());
-            HandleErr::ServerInnerErr(id)
-        })?
-        .rows_affected;
-    if rows_affected == 0 {
-        Err(HandleErr::BadRequest(-1, "没有数据行被删除"))
+    let order = OrderOp::owned::<String>(schema.order_id, auth.user.user_id, &state).await?;
+    let now = chrono::Utc::now().naive_utc();
+    if order.apt_start <= now {
+        return Err(HandleErr::BadRequest(-1, "订单已开始".to_string()));
+    }
+    //未支付的订单不收取费用
+    let fee = if order.status == OrderState::PendingPayment {
+        Decimal::ZERO
     } else {
-        Ok(Json(json!({
-            "code":0,
-            "msg":"操作成功"
-        })))
+        let rule = BookingRuleOp::rule::<String>(order.court_id, &state).await?;
+        booking_rule::cancel_fee(rule.as_ref(), order.cost, order.apt_start, now)
+    };
+    let order = OrderOp::cancel::<String>(&order, schema.reason, fee, &state).await?;
+    info!(
+        "{} 取消订单({}), 取消费用{:.2}",
+        auth.user.user_name, order.order_id, fee
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":order
+    })))
+}
+
+//评价已完成的订单
+async fn review(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<ReviewCreate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !(1..=5).contains(&schema.rating) {
+        return Err(HandleErr::BadRequest(-1, "评分应在1~5之间".to_string()));
+    }
+    let comment = schema.comment.trim().to_string();
+    if comment.chars().count() > 500 {
+        return Err(HandleErr::BadRequest(-1, "评价内容过长".to_string()));
+    }
+    let order = OrderOp::owned::<String>(schema.order_id, auth.user.user_id, &state).await?;
+    let review = ReviewOp::create::<String>(&order, schema.rating, comment, &state).await?;
+    info!(
+        "{} 评价订单({}): {}分",
+        auth.user.user_name, order.order_id, review.rating
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"评价成功",
+        "data":review
+    })))
+}
+
+//申请开具电子发票, 由球场管理员开具
+async fn invoice(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(mut schema): Json<InvoiceRequest>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    schema.title = schema.title.trim().to_string();
+    schema.email = schema.email.trim().to_string();
+    schema.tax_number = schema
+        .tax_number
+        .map(|e| e.trim().to_uppercase())
+        .filter(|e| !e.is_empty());
+    if schema.title.is_empty() || schema.title.chars().count() > 100 {
+        return Err(HandleErr::BadRequest(-1, "发票抬头无效".to_string()));
+    }
+    //统一社会信用代码为18位, 旧税号为15~20位
+    if schema.tax_number.as_ref().is_some_and(|e| {
+        !(15..=20).contains(&e.len()) || !e.chars().all(|c| c.is_ascii_alphanumeric())
+    }) {
+        return Err(HandleErr::BadRequest(-1, "税号无效".to_string()));
+    }
+    if !schema.email.contains('@') || schema.email.len() > 100 {
+        return Err(HandleErr::BadRequest(-1, "邮箱无效".to_string()));
+    }
+    let order = OrderOp::owned::<String>(schema.order_id, auth.user.user_id, &state).await?;
+    let invoice = InvoiceOp::request::<String>(&order, schema, &state).await?;
+    info!(
+        "{} 申请订单({})发票{:.2}元",
+        auth.user.user_name, order.order_id, invoice.amount
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已提交开票申请",
+        "data":invoice
+    })))
+}
+
+//订单的发票申请及开具状态, 未申请时为空
+async fn invoice_of(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = OrderOp::owned::<String>(order_id, auth.user.user_id, &state).await?;
+    let invoice = InvoiceOp::of_order::<String, _>(order.order_id, &state.db).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":invoice
+    })))
+}
+
+//申请退款, 由球场管理员审核
+async fn refund(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<RefundRequest>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.reason.chars().count() > 200 {
+        return Err(HandleErr::BadRequest(-1, "退款原因过长".to_string()));
     }
+    let order = OrderOp::owned::<String>(schema.order_id, auth.user.user_id, &state).await?;
+    let refund = RefundOp::request::<String>(&order, schema, &state).await?;
+    info!(
+        "{} 申请订单({})退款{:.2}元",
+        auth.user.user_name, order.order_id, refund.refund.amount
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已提交退款申请",
+        "data":refund
+    })))
+}
+
+//退款记录, 含部分退款对应的明细
+async fn refunds(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (refunds, total) =
+        RefundOp::mine::<String>(auth.user.user_id, schema.page, schema.page_size, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "refunds":refunds,
+            "total":total
+        }
+    })))
 }
 
 async fn update(
@@ -145,37 +827,40 @@ async fn update(
     State(state): State<Arc<AppState>>,
     Json(schema): Json<UpdateOrder>,
 ) -> Result<impl IntoResponse, HandleErr<String>> {
-    let court_id = Orders::find_by_id(schema.order_id)
-        .one(&state.db)
-        .await
-        .map_err(|err| {
-            let id = Uuid::new_v4();
-            error!("{} >>>> {}", id, err.to_string());
-            HandleErr::ServerInnerErr(id)
-        })?
-        .ok_or(HandleErr::BadRequest(-1, "order_id无效").into())?
-        .court_id;
+    let order = OrderOp::owned::<String>(schema.order_id, auth.user.user_id, &state).await?;
+    let court_id = order.court_id;
+    //已支付的订单通过改期结算差价, 拼单按分摊支付, 均不能直接修改
+    if order.status != OrderState::PendingPayment || order.pay_deadline.is_some() {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "仅待支付的订单可以修改".to_string(),
+        ));
+    }
 
-    if OrderOp::orderStatus(schema.order_id, auth.user.user_id, &state)
-        .await
-        .map_err(|err| err.into())?
-        == OrderStatus::Waiting
+    BookingRuleOp::check(
+        court_id,
+        auth.user.user_id,
+        schema.apt_start,
+        schema.apt_end,
+        Some(schema.order_id),
+        &state,
+    )
+    .await?;
+    //未开始且未结束的订单可以修改
+    if order.apt_start > chrono::Utc::now().naive_utc()
+        && order.status.is_open()
         && !OrderOp::hasClash(
             schema.apt_start,
             schema.apt_end,
             Some(schema.order_id),
             court_id,
+            auth.user.user_id,
             &state,
         )
         .await
         .map_err(|err| err.into())?
     {
-        let price = Courts::find()
-            .join_rev(
-                sea_orm::JoinType::InnerJoin,
-                db::orders::Relation::Courts.def(),
-            )
-            .filter(db::orders::Column::OrderId.eq(schema.order_id))
+        let court = Courts::find_by_id(court_id)
             .one(&state.db)
             .await
             .map_err(|err| {
@@ -183,27 +868,595 @@ async fn update(
                 error!("{} >>>> {}", id, err.to_string());
                 HandleErr::ServerInnerErr(id)
             })?
-            .unwrap()
-            .price_per_hour;
+            .unwrap();
+        let (package, promotions, cost) =
+            reprice(&order, &court, schema.apt_start, schema.apt_end, &state).await?;
+        //已发起支付的订单金额须与支付单一致
+        if order
+            .prepay_amount
+            .is_some_and(|e| e != cost + order.deposit)
+        {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "订单已发起支付, 不能修改金额, 请取消后重新预订".to_string(),
+            ));
+        }
 
-        let order = OrderOp::save(
+        let updated = OrderOp::save(
             auth.user.user_id,
+            court.admin_id,
             SaveOrder {
                 order_id: Some(schema.order_id),
                 court_id: None,
                 apt_start: schema.apt_start,
                 apt_end: schema.apt_end,
-                cost: (schema.apt_end - schema.apt_start).num_minutes() as f64 / 60.0 * price,
+                cost,
+                deposit: None,
+                idempotency_key: None,
+                remark: None,
+                coupon_id: None,
+                discount: None,
+                package: (order.package_hours > 0.0).then_some(package),
+                points: None,
+                promotions,
+                member: None,
+                contact: None,
             },
             &state,
         )
         .await?;
+        OrderOp::record::<String, _>(
+            order.order_id,
+            "update",
+            json!({
+                "old":{"apt_start":order.apt_start, "apt_end":order.apt_end, "cost":order.cost},
+                "new":{"apt_start":updated.apt_start, "apt_end":updated.apt_end, "cost":updated.cost}
+            }),
+            &state.db,
+        )
+        .await?;
+        let lines = ItemOp::addons_of(updated.order_id, &state.db).await?;
+        ItemOp::write(&updated, &court, &lines, &state.db).await?;
         Ok(Json(json!({
             "code":0,
             "msg":"订单已修改",
-            "data":order
+            "data":updated
         })))
     } else {
         Err(HandleErr::BadRequest(-1, "无法修改").into())
     }
 }
+
+//修改时段后按新时段重新计价, 返回次卡抵扣(时数, 金额)、活动优惠与应付金额
+//附加项目沿用下单时的价格, 按新的时长重新计算
+//下单时使用了次卡的按新时长重新抵扣, 可用时数包含本单已扣减且仍有效的部分
+//下单时参加的活动按新时段重新判断条件并计算优惠, 不参加新的活动
+//会员折扣、优惠券与积分沿用下单时的抵扣金额
+async fn reprice(
+    order: &db::orders::Model,
+    court: &db::courts::Model,
+    apt_start: DateTime,
+    apt_end: DateTime,
+    state: &AppState,
+) -> Result<((f64, Decimal), Vec<Applied>, Decimal), HandleErr<String>> {
+    let addons = AddonOp::of_order(order.order_id, state).await?;
+    let court_cost = PricingOp::cost(court, apt_start, apt_end, state).await?;
+    let gross = court_cost
+        + pricing::addons_cost(
+            addons.iter().map(|e| (e.price, e.per_hour, e.quantity)),
+            apt_start,
+            apt_end,
+        );
+    let package = if order.package_hours > 0.0 {
+        let hours = (apt_end - apt_start).num_minutes() as f64 / 60.0;
+        let available = PackageOp::available(order.user_id, court.admin_id, &state.db).await?
+            + PackageOp::restorable(order.order_id, &state.db).await?;
+        pricing::package(court_cost, hours, available)
+    } else {
+        (0.0, Decimal::ZERO)
+    };
+    let promotions = promotion::best(
+        &PromotionOp::of_order(order.order_id, &state.db).await?,
+        court_cost,
+        court_cost - package.1 - order.member_amount,
+        apt_start,
+        apt_end,
+        order.discount > Decimal::ZERO,
+    );
+    let cost = pricing::net(
+        gross
+            - package.1
+            - order.member_amount
+            - promotion::total(&promotions)
+            - order.points_amount,
+        order.discount,
+    );
+    Ok((package, promotions, cost))
+}
+
+//已支付订单改期, 保留支付, 按新时段重新计价, 价格变化时不允许改期
+async fn reschedule(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<UpdateOrder>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = OrderOp::owned::<String>(schema.order_id, auth.user.user_id, &state).await?;
+    if !matches!(order.status, OrderState::Paid | OrderState::Confirmed) {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "仅已支付的订单可以改期".to_string(),
+        ));
+    }
+    let now = chrono::Utc::now().naive_utc();
+    if order.apt_start <= now || schema.apt_start <= now {
+        return Err(HandleErr::BadRequest(-1, "无法改期".to_string()));
+    }
+    BookingRuleOp::check(
+        order.court_id,
+        auth.user.user_id,
+        schema.apt_start,
+        schema.apt_end,
+        Some(order.order_id),
+        &state,
+    )
+    .await?;
+    if OrderOp::hasClash(
+        schema.apt_start,
+        schema.apt_end,
+        Some(order.order_id),
+        order.court_id,
+        auth.user.user_id,
+        &state,
+    )
+    .await
+    .map_err(|err| err.into())?
+    {
+        return Err(HandleErr::BadRequest(-1, "时间冲突".to_string()));
+    }
+    let court = Courts::find_by_id(order.court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+    let (package, promotions, cost) =
+        reprice(&order, &court, schema.apt_start, schema.apt_end, &state).await?;
+    let order = OrderOp::reschedule::<String>(
+        &order,
+        schema.apt_start,
+        schema.apt_end,
+        cost,
+        package,
+        &promotions,
+        &state,
+    )
+    .await?;
+    info!(
+        "{} 订单({})改期至{}",
+        auth.user.user_name, order.order_id, order.apt_start
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"改期成功",
+        "data":order
+    })))
+}
+
+async fn timeline(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = OrderOp::owned::<String>(order_id, auth.user.user_id, &state).await?;
+    let events = OrderOp::timeline(order.order_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":events
+    })))
+}
+
+//订单电子票, 前端据此生成二维码
+async fn ticket(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let order = OrderOp::owned::<String>(order_id, auth.user.user_id, &state).await?;
+    if !order.status.is_open() {
+        return Err(HandleErr::BadRequest(-1, "订单已结束".to_string()));
+    }
+    let token = qrcode::sign_ticket(
+        order.order_id,
+        order.apt_end,
+        &state.cfg.tokencfg.access_prikey,
+    )
+    .map_err(|_| {
+        let id = Uuid::new_v4();
+        error!("{} >>>> 电子票生成失败", id);
+        HandleErr::ServerInnerErr(id)
+    })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":token
+    })))
+}
+
+//扫描球场二维码或订单电子票签到
+async fn checkin(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<CheckIn>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let scanned = qrcode::verify(&schema.token, &state.cfg.tokencfg.access_pubkey)
+        .map_err(|_| HandleErr::BadRequest(-1, "无效的签到码".to_string()))?;
+    let order = OrderOp::check_in::<String>(auth.user.user_id, scanned, &state).await?;
+    info!("{} 签到订单({})", auth.user.user_name, order.order_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"签到成功",
+        "data":order
+    })))
+}
+
+//支付前锁定时段, 过期前其他用户无法预约
+async fn hold(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<HoldCreate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    BookingRuleOp::check(
+        schema.court_id,
+        auth.user.user_id,
+        schema.apt_start,
+        schema.apt_end,
+        None,
+        &state,
+    )
+    .await?;
+    if OrderOp::hasClash(
+        schema.apt_start,
+        schema.apt_end,
+        None,
+        schema.court_id,
+        auth.user.user_id,
+        &state,
+    )
+    .await
+    .map_err(|err| err.into())?
+    {
+        return Err(HandleErr::BadRequest(-1, "时间冲突".to_string()));
+    }
+    let hold = HoldOp::create::<String>(auth.user.user_id, schema, &state).await?;
+    info!(
+        "{} 锁定时段({}), 至{}",
+        auth.user.user_name, hold.hold_id, hold.expire_time
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"锁定成功",
+        "data":hold
+    })))
+}
+
+async fn hold_release(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<HoldRelease>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let hold = HoldOp::owned::<String>(schema.hold_id, auth.user.user_id, &state).await?;
+    HoldOp::release::<String, _>(
+        auth.user.user_id,
+        hold.court_id,
+        Some(hold.hold_id),
+        &state.db,
+    )
+    .await?;
+    info!("{} 释放时段锁定({})", auth.user.user_name, hold.hold_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"释放成功",
+        "data":null
+    })))
+}
+
+//发起拼单, 费用按人数均摊, 全部支付后订单生效
+async fn group_create(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<GroupCreate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !(2..=20).contains(&schema.size) {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "拼单人数应在2~20之间".to_string(),
+        ));
+    }
+    let submit = schema.order;
+    let remark = remark(submit.remark.clone())?;
+    BookingRuleOp::check(
+        submit.court_id,
+        auth.user.user_id,
+        submit.apt_start,
+        submit.apt_end,
+        None,
+        &state,
+    )
+    .await?;
+    if OrderOp::hasClash(
+        submit.apt_start,
+        submit.apt_end,
+        None,
+        submit.court_id,
+        auth.user.user_id,
+        &state,
+    )
+    .await
+    .map_err(|err| err.into())?
+    {
+        return Err(HandleErr::BadRequest(-1, "时间冲突".to_string()));
+    }
+    let court = Courts::find_by_id(submit.court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+    let addons = AddonOp::resolve(submit.court_id, &submit.addons, &state).await?;
+    let cost =
+        PricingOp::order_cost(&court, submit.apt_start, submit.apt_end, &addons, &state).await?;
+    let order = OrderOp::create(
+        auth.user.user_id,
+        SaveOrder {
+            order_id: None,
+            court_id: Some(submit.court_id),
+            apt_start: submit.apt_start,
+            apt_end: submit.apt_end,
+            cost,
+            deposit: Some(court.deposit),
+            idempotency_key: None,
+            remark,
+            coupon_id: None,
+            discount: None,
+            package: None,
+            points: None,
+            promotions: vec![],
+            member: None,
+            contact: None,
+        },
+        &addons,
+        &state,
+    )
+    .await?;
+    let group = GroupOp::open(&order, schema.size, &state).await?;
+    info!(
+        "{} 发起拼单({}), {}人",
+        auth.user.user_name, order.order_id, schema.size
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"拼单已发起",
+        "data":group
+    })))
+}
+
+//通过分摊链接查看拼单
+async fn group_share(
+    State(state): State<Arc<AppState>>,
+    Path(participant_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let group = GroupOp::of_share::<String>(participant_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":group
+    })))
+}
+
+//支付一份分摊, 默认微信支付, 返回调起支付的参数, 支付通知后分摊变为已支付
+async fn group_pay(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(participant_id): Path<Uuid>,
+    Query(schema): Query<PayQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let method = schema.method.unwrap_or(PayMethod::Wechat);
+    let (share, payment) = GroupOp::pay::<String>(
+        participant_id,
+        auth.user.user_id,
+        method,
+        auth.user.openid.as_deref(),
+        &state,
+    )
+    .await?;
+    let msg = if share.status == ShareStatus::Paid {
+        info!(
+            "{} 支付拼单({})分摊{:.2}元",
+            auth.user.user_name, share.order_id, share.share
+        );
+        "支付成功"
+    } else {
+        "OK"
+    };
+    Ok(Json(json!({
+        "code":0,
+        "msg":msg,
+        "data":{
+            "share":share,
+            "payment":payment
+        }
+    })))
+}
+
+//每周重复预约, 全部时段可预约时才创建, 否则返回每一次的冲突原因
+async fn series_create(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<SeriesCreate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if !(1..=52).contains(&schema.weeks) {
+        return Err(HandleErr::BadRequest(-1, "周数应在1~52之间".to_string()));
+    }
+    if schema.start_time >= schema.end_time {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "开始时间须早于结束时间".to_string(),
+        ));
+    }
+    let court = Courts::find_by_id(schema.court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+
+    let mut conflicts = vec![];
+    let mut orders = vec![];
+    for (start, end) in schema.occurrences() {
+        let checked = match BookingRuleOp::check(
+            schema.court_id,
+            auth.user.user_id,
+            start,
+            end,
+            None,
+            &state,
+        )
+        .await
+        {
+            Ok(()) => {
+                OrderOp::hasClash(start, end, None, schema.court_id, auth.user.user_id, &state)
+                    .await
+                    .map_err(|err| err.into())
+                    .and_then(|clash| {
+                        if clash {
+                            Err(HandleErr::BadRequest(-1, "时间冲突".to_string()))
+                        } else {
+                            Ok(())
+                        }
+                    })
+            }
+            Err(err) => Err(err),
+        };
+        match checked {
+            Ok(()) => {
+                let cost = PricingOp::cost(&court, start, end, &state).await?;
+                orders.push(SaveOrder {
+                    order_id: None,
+                    court_id: Some(schema.court_id),
+                    apt_start: start,
+                    apt_end: end,
+                    cost,
+                    deposit: Some(court.deposit),
+                    idempotency_key: None,
+                    remark: None,
+                    coupon_id: None,
+                    discount: None,
+                    package: None,
+                    points: None,
+                    promotions: vec![],
+                    member: None,
+                    contact: None,
+                });
+            }
+            Err(HandleErr::BadRequest(_, msg)) => conflicts.push(SeriesConflict {
+                date: start.date(),
+                msg,
+            }),
+            Err(err) => return Err(err),
+        }
+    }
+    if !conflicts.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code":-1,
+                "msg":"部分时段无法预约",
+                "data":conflicts
+            })),
+        ));
+    }
+
+    let (series, orders) =
+        SeriesOp::create::<String>(auth.user.user_id, &schema, orders, &state).await?;
+    info!(
+        "{} 创建重复预约({}), 共{}次",
+        auth.user.user_name,
+        series.series_id,
+        orders.len()
+    );
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "code":0,
+            "msg":"预定成功",
+            "data":{
+                "series":series,
+                "orders":orders
+            }
+        })),
+    ))
+}
+
+async fn series_orders(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Path(series_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let orders = SeriesOp::orders::<String>(series_id, auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":orders
+    })))
+}
+
+//取消重复预约中所有未开始的订单, 单次取消使用 /cancel
+async fn series_cancel(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<SeriesCancel>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.reason.chars().count() > 200 {
+        return Err(HandleErr::BadRequest(-1, "取消原因过长".to_string()));
+    }
+    let orders = SeriesOp::orders::<String>(schema.series_id, auth.user.user_id, &state).await?;
+    let now = chrono::Utc::now().naive_utc();
+    let rule = match orders.first() {
+        Some(e) => BookingRuleOp::rule::<String>(e.court_id, &state).await?,
+        None => None,
+    };
+    let pending = orders
+        .into_iter()
+        .filter(|e| e.status.is_open() && e.apt_start > now)
+        .map(|order| {
+            let fee = if order.status == OrderState::PendingPayment {
+                Decimal::ZERO
+            } else {
+                booking_rule::cancel_fee(rule.as_ref(), order.cost, order.apt_start, now)
+            };
+            (order, fee)
+        })
+        .collect::<Vec<_>>();
+    let cancelled = SeriesOp::cancel::<String>(&pending, schema.reason, &state).await?;
+    info!(
+        "{} 取消重复预约({}), 共{}次",
+        auth.user.user_name, schema.series_id, cancelled
+    );
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":{
+            "cancelled":cancelled
+        }
+    })))
+}
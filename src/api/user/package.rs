@@ -0,0 +1,101 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::prelude::Courts,
+        package::{PackageBuy, PackageOp},
+        payment::{Payment, ATTACH_PACKAGE},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use sea_orm::EntityTrait;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/package/* 挂载中");
+    Router::new()
+        .route("/court/:court_id", get(of_court))
+        .route("/mine", get(mine))
+        .route("/buy", post(buy))
+}
+
+//球场所属场馆在售的次卡套餐
+async fn of_court(
+    State(state): State<Arc<AppState>>,
+    Path(court_id): Path<Uuid>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let court = Courts::find_by_id(court_id)
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string()))?;
+    let packages = PackageOp::packages::<String, _>(court.admin_id, true, &state.db).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":packages
+    })))
+}
+
+//我的次卡, 下单时自动优先抵扣
+async fn mine(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let cards = PackageOp::mine::<String>(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":cards
+    })))
+}
+
+//微信支付购买次卡, 支付成功通知后发放
+async fn buy(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PackageBuy>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if matches!(state.payment, Payment::Offline) {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "未开通线上支付, 请联系管理员".to_string(),
+        ));
+    }
+    let (purchase, package) =
+        PackageOp::purchase::<String>(auth.user.user_id, schema.package_id, &state).await?;
+    let payment = state
+        .payment
+        .prepay(
+            purchase.purchase_id,
+            ATTACH_PACKAGE,
+            &package.name,
+            purchase.amount,
+            auth.user.openid.as_deref(),
+        )
+        .await
+        .map_err(|err| {
+            warn!("次卡购买单({})发起支付失败: {}", purchase.purchase_id, err);
+            HandleErr::BadRequest(-1, "发起支付失败, 请稍后重试".to_string())
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "purchase":purchase,
+            "payment":payment
+        }
+    })))
+}
@@ -0,0 +1,165 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        order::PageQuery,
+        partner::{
+            PartnerBoardQuery, PartnerHandle, PartnerJoin, PartnerOp, PartnerOpen, PartnerPostId,
+            PartnerRequestId,
+        },
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/partner/* 挂载中");
+    Router::new()
+        .route("/", get(board))
+        .route("/open", post(open))
+        .route("/close", post(close))
+        .route("/join", post(join))
+        .route("/withdraw", post(withdraw))
+        .route("/handle", post(handle))
+        .route("/mine", get(mine))
+        .route("/applied", get(applied))
+}
+
+//招募中的球友信息, 可按运动类型筛选
+async fn board(
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PartnerBoardQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (posts, total) = PartnerOp::board(&schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "posts":posts,
+            "total":total
+        }
+    })))
+}
+
+//为自己的预约招募球友
+async fn open(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PartnerOpen>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let post = PartnerOp::open(auth.user.user_id, schema, &state).await?;
+    info!("{} 发起招募({})", auth.user.user_name, post.post_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"发布成功",
+        "data":post
+    })))
+}
+
+async fn close(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PartnerPostId>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    PartnerOp::close(auth.user.user_id, schema.post_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已关闭",
+        "data":null
+    })))
+}
+
+//申请加入, 发起人审核通过后预约出现在订单列表中
+async fn join(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PartnerJoin>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let request = PartnerOp::join(auth.user.user_id, &auth.user.user_name, schema, &state).await?;
+    info!("{} 申请加入招募({})", auth.user.user_name, request.post_id);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"申请成功",
+        "data":request
+    })))
+}
+
+//撤回申请或退出已加入的预约
+async fn withdraw(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PartnerRequestId>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    PartnerOp::withdraw(auth.user.user_id, schema.request_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已撤回",
+        "data":null
+    })))
+}
+
+//发起人审核申请
+async fn handle(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<PartnerHandle>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let request = PartnerOp::handle(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"操作成功",
+        "data":request
+    })))
+}
+
+//我发起的招募与收到的申请
+async fn mine(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (posts, total) =
+        PartnerOp::mine(auth.user.user_id, schema.page, schema.page_size, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "posts":posts,
+            "total":total
+        }
+    })))
+}
+
+//我提交的申请
+async fn applied(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if schema.page == 0 || !(1..=100).contains(&schema.page_size) {
+        return Err(HandleErr::BadRequest(-1, "分页参数无效".to_string()));
+    }
+    let (requests, total) =
+        PartnerOp::applied(auth.user.user_id, schema.page, schema.page_size, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"查询成功",
+        "data":{
+            "requests":requests,
+            "total":total
+        }
+    })))
+}
@@ -0,0 +1,42 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{order::PageQuery, points::PointsOp},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/points/* 挂载中");
+    Router::new().route("/", get(points))
+}
+
+//积分余额、抵扣规则与流水
+async fn points(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let balance = PointsOp::balance::<String, _>(auth.user.user_id, &state.db).await?;
+    let rule = PointsOp::rule::<String, _>(&state.db).await?;
+    let (transactions, total) =
+        PointsOp::transactions::<String>(auth.user.user_id, schema.page, schema.page_size, &state)
+            .await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "points":balance,
+            "rule":rule,
+            "transactions":transactions,
+            "total":total
+        }
+    })))
+}
@@ -0,0 +1,25 @@
+use crate::{
+    appstate::AppState, error::HandleErr, module::referral::ReferralOp,
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{extract::State, response::IntoResponse, routing::get, Extension, Json, Router};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/referral/* 挂载中");
+    Router::new().route("/", get(summary))
+}
+
+//我的邀请码与邀请记录
+async fn summary(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let summary = ReferralOp::summary(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":summary
+    })))
+}
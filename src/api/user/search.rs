@@ -0,0 +1,72 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::court::search::{SearchOp, SearchQuery},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/search/* 挂载中");
+    Router::new()
+        .route("/courts", get(courts))
+        .route("/suggest", get(suggest))
+        .route("/recent", get(recent).delete(clear))
+}
+
+async fn courts(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<SearchQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let courts = SearchOp::courts(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":courts
+    })))
+}
+
+//搜索框联想, 输入为空时返回空列表
+async fn suggest(
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<SearchQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let suggestions = SearchOp::suggest(&schema.q, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":suggestions
+    })))
+}
+
+async fn recent(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let keywords = SearchOp::recent(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":keywords
+    })))
+}
+
+async fn clear(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    SearchOp::clear(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"搜索记录已清空",
+        "data":null
+    })))
+}
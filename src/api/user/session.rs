@@ -0,0 +1,81 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::session::{SessionOp, SessionRevoke},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/sessions/* 挂载中");
+    Router::new()
+        .route("/", get(list))
+        .route("/revoke", post(revoke))
+        .route("/revoke/others", post(revoke_others))
+        .route("/logout", post(logout))
+}
+
+//已登录的设备
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let sessions = SessionOp::list(auth.user.user_id, auth.session_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "sessions":sessions
+        }
+    })))
+}
+
+//下线指定设备, 撤销当前会话即退出登录
+async fn revoke(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<SessionRevoke>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    SessionOp::revoke(auth.user.user_id, schema.session_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已下线",
+        "data":null
+    })))
+}
+
+//下线除当前设备外的全部设备
+async fn revoke_others(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let revoked = SessionOp::revoke_others(auth.user.user_id, auth.session_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已下线",
+        "data":{
+            "revoked":revoked
+        }
+    })))
+}
+
+//退出登录, 当前会话的token与刷新令牌立即失效
+async fn logout(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    SessionOp::revoke(auth.user.user_id, auth.session_id, &state).await?;
+    info!("{} 退出登录", auth.user.user_name);
+    Ok(Json(json!({
+        "code":0,
+        "msg":"已退出登录",
+        "data":null
+    })))
+}
@@ -0,0 +1,33 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::order::summary::{SummaryOp, SummaryQuery},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/stats/* 挂载中");
+    Router::new().route("/", get(summary))
+}
+
+//年度运动报告: 总时长, 最常去的球场, 最常打球的星期与每月花费
+async fn summary(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<SummaryQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let summary = SummaryOp::get(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":summary
+    })))
+}
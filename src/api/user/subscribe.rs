@@ -0,0 +1,43 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::notify::subscribe::{SubscribeOp, SubscribeRecord},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{extract::State, response::IntoResponse, routing::get, Extension, Json, Router};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/subscribe/* 挂载中");
+    Router::new().route("/", get(list).post(record))
+}
+
+//需要申请授权的模板与已有的授权记录
+async fn list(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let consents = SubscribeOp::list(auth.user.user_id, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "templates":state.cfg.subscribecfg.templates(),
+            "consents":consents
+        }
+    })))
+}
+
+async fn record(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<SubscribeRecord>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let consents = SubscribeOp::record(auth.user.user_id, schema, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":consents
+    })))
+}
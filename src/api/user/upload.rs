@@ -0,0 +1,76 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::upload::{UploadOp, UploadPurpose},
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, State},
+    response::IntoResponse,
+    routing::post,
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+
+//单次上传大小上限, 各用途的图片大小另有限制
+const MAX_UPLOAD_SIZE: usize = 10 * 1024 * 1024 + 64 * 1024;
+
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/upload/* 挂载中");
+    Router::new()
+        .route("/image", post(image))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+}
+
+//multipart字段: purpose(avatar/court/feedback), file
+async fn image(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let (mut purpose, mut file) = (None, None);
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?
+    {
+        match field.name() {
+            Some("purpose") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                purpose = Some(
+                    serde_json::from_value::<UploadPurpose>(json!(text))
+                        .map_err(|_| HandleErr::BadRequest(-1, "purpose无效".to_string()))?,
+                );
+            }
+            Some("file") => {
+                field
+                    .content_type()
+                    .filter(|e| e.starts_with("image/"))
+                    .ok_or(HandleErr::BadRequest(-1, "仅支持图片文件".to_string()))?;
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|err| HandleErr::BadRequest(-1, err.body_text()))?;
+                file = Some(data.to_vec());
+            }
+            _ => {}
+        }
+    }
+    let purpose = purpose.ok_or(HandleErr::BadRequest(-1, "缺少purpose".to_string()))?;
+    let file = file.ok_or(HandleErr::BadRequest(-1, "未上传图片".to_string()))?;
+    //球场图片只能由管理员上传
+    if purpose == UploadPurpose::Court && !auth.user.is_admin {
+        return Err(HandleErr::BadRequest(-1, "无权上传球场图片".to_string()));
+    }
+    let image = UploadOp::image(auth.user.user_id, purpose, file, &state).await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"上传成功",
+        "data":image
+    })))
+}
@@ -0,0 +1,87 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        order::PageQuery,
+        payment::{Payment, ATTACH_RECHARGE},
+        wallet::{RechargeCreate, WalletOp},
+    },
+    utils::auth::JWTAuthMiddleware,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{info, warn};
+pub fn router() -> Router<Arc<AppState>> {
+    info!("/wallet/* 挂载中");
+    Router::new()
+        .route("/", get(wallet))
+        .route("/recharge", post(recharge))
+}
+
+//余额与流水
+async fn wallet(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Query(schema): Query<PageQuery>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    let balance = WalletOp::balance::<String, _>(auth.user.user_id, &state.db).await?;
+    let (transactions, total) = WalletOp::transactions::<String, _>(
+        auth.user.user_id,
+        schema.page,
+        schema.page_size,
+        &state.db,
+    )
+    .await?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "balance":balance,
+            "transactions":transactions,
+            "total":total
+        }
+    })))
+}
+
+//微信支付充值, 支付成功通知后入账
+async fn recharge(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    State(state): State<Arc<AppState>>,
+    Json(schema): Json<RechargeCreate>,
+) -> Result<impl IntoResponse, HandleErr<String>> {
+    if matches!(state.payment, Payment::Offline) {
+        return Err(HandleErr::BadRequest(
+            -1,
+            "未开通线上充值, 请联系管理员".to_string(),
+        ));
+    }
+    let recharge = WalletOp::recharge::<String>(auth.user.user_id, schema.amount, &state).await?;
+    let payment = state
+        .payment
+        .prepay(
+            recharge.recharge_id,
+            ATTACH_RECHARGE,
+            "钱包充值",
+            recharge.amount,
+            auth.user.openid.as_deref(),
+        )
+        .await
+        .map_err(|err| {
+            warn!("充值单({})发起支付失败: {}", recharge.recharge_id, err);
+            HandleErr::BadRequest(-1, "发起支付失败, 请稍后重试".to_string())
+        })?;
+    Ok(Json(json!({
+        "code":0,
+        "msg":"OK",
+        "data":{
+            "recharge":recharge,
+            "payment":payment
+        }
+    })))
+}
@@ -1,23 +1,55 @@
-use crate::{cfg::Cfg, utils::ws::Msg};
-use std::sync::Arc;
-#[derive(Clone, Debug)]
+use crate::{
+    cfg::Cfg,
+    module::{
+        notify::Notifier,
+        order::summary::SummaryCache,
+        payment::{notification::InFlight, Payment},
+        storage::Storage,
+        user::LoginFailures,
+        wechat::Wechat,
+    },
+    utils::{auth::NonceCache, qrcode::QrCache},
+};
+#[derive(Debug)]
 pub struct AppState {
     pub db: sea_orm::DatabaseConnection,
     pub cfg: Cfg,
-    pub sender: Arc<tokio::sync::broadcast::Sender<Msg>>,
+    pub storage: Storage,
+    pub payment: Payment,
+    pub notifier: Notifier,
+    //小程序登录, 未配置appid时为None
+    pub wechat: Option<Wechat>,
+    //球场签到码PNG缓存
+    pub qrcodes: QrCache,
+    //正在处理的微信支付通知
+    pub notifying: InFlight,
+    //正在生成分享海报的球场
+    pub posters: InFlight,
+    //用户年度运动报告, 按天缓存
+    pub summaries: SummaryCache,
+    //签名请求已使用的随机串, 防止重放
+    pub nonces: NonceCache,
+    //按ip统计的密码登录失败次数
+    pub login_failures: LoginFailures,
 }
 
 impl AppState {
-    pub async fn new(
-        cfg: Cfg,
-        sender: Arc<tokio::sync::broadcast::Sender<Msg>>,
-    ) -> crate::App::Result<Self> {
+    pub async fn new(cfg: Cfg) -> crate::App::Result<Self> {
         Ok(Self {
             db: sea_orm::Database::connect(&cfg.servercfg.db_url)
                 .await
                 .unwrap(),
+            storage: Storage::new(&cfg.storagecfg),
+            payment: Payment::new(&cfg.paymentcfg)?,
+            notifier: Notifier::new(&cfg.notifycfg),
+            wechat: Wechat::new(&cfg),
+            qrcodes: Default::default(),
+            notifying: Default::default(),
+            posters: Default::default(),
+            summaries: Default::default(),
+            nonces: Default::default(),
+            login_failures: Default::default(),
             cfg,
-            sender,
         })
     }
 }
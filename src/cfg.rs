@@ -5,6 +5,25 @@ use tracing::info;
 pub struct Cfg {
     pub servercfg: ServerCfg,
     pub tokencfg: TokenCfg,
+    pub storagecfg: StorageCfg,
+    #[serde(default)]
+    pub noshowcfg: NoShowCfg,
+    #[serde(default)]
+    pub notifycfg: NotifyCfg,
+    #[serde(default)]
+    pub paymentcfg: PaymentCfg,
+    #[serde(default)]
+    pub wechatcfg: Option<WechatCfg>,
+    #[serde(default)]
+    pub ordercfg: OrderCfg,
+    #[serde(default)]
+    pub financecfg: FinanceCfg,
+    #[serde(default)]
+    pub subscribecfg: SubscribeCfg,
+    #[serde(default)]
+    pub logincfg: LoginCfg,
+    #[serde(default)]
+    pub signcfg: SignCfg,
 }
 
 pub async fn parse() -> crate::App::Result<Cfg> {
@@ -23,6 +42,9 @@ pub struct ServerCfg {
     pub ip: String,
     pub port: u16,
     pub db_url: String,
+    //反向代理的地址, 只有来自这些地址的请求才采用X-Forwarded-For中的客户端ip
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -30,4 +52,223 @@ pub struct TokenCfg {
     pub access_token_ttl: i64,
     pub access_prikey: String,
     pub access_pubkey: String,
+    //刷新令牌有效天数, 每次刷新后重新计算
+    #[serde(default = "default_refresh_token_ttl")]
+    pub refresh_token_ttl: i64,
+}
+
+fn default_refresh_token_ttl() -> i64 {
+    30
+}
+
+//文件存储, 本地目录或者阿里云OSS
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum StorageCfg {
+    Local {
+        root: String,
+        base_url: String,
+    },
+    Oss {
+        endpoint: String,
+        bucket: String,
+        access_key_id: String,
+        access_key_secret: String,
+        //CDN域名, 未设置时使用bucket域名
+        base_url: Option<String>,
+    },
+}
+
+//未到场处罚, limit为0时只记录次数不限制预约
+#[derive(Debug, Deserialize, Clone)]
+pub struct NoShowCfg {
+    //累计未到场次数上限
+    pub limit: i32,
+    //达到上限后限制预约的天数
+    pub ban_days: i64,
+}
+
+impl Default for NoShowCfg {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            ban_days: 7,
+        }
+    }
+}
+
+//密码登录失败锁定, max_failures为0时不锁定
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoginCfg {
+    //连续失败次数上限
+    pub max_failures: i32,
+    //达到上限后锁定的分钟数, 同时也是按ip统计失败次数的窗口
+    pub lock_minutes: i64,
+    //同一ip在窗口内的失败次数上限, 不区分账号, 0为不限制
+    #[serde(default = "default_ip_max_failures")]
+    pub ip_max_failures: i32,
+}
+
+fn default_ip_max_failures() -> i32 {
+    20
+}
+
+impl Default for LoginCfg {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            lock_minutes: 15,
+            ip_max_failures: default_ip_max_failures(),
+        }
+    }
+}
+
+//服务端对接的请求签名, 未配置对接方时不校验签名
+#[derive(Debug, Deserialize, Clone)]
+pub struct SignCfg {
+    #[serde(default)]
+    pub clients: Vec<SignClient>,
+    //请求时间与服务器时间允许的偏差(秒), 同时也是随机串的保留时长
+    #[serde(default = "default_max_skew")]
+    pub max_skew: i64,
+}
+
+impl Default for SignCfg {
+    fn default() -> Self {
+        Self {
+            clients: vec![],
+            max_skew: default_max_skew(),
+        }
+    }
+}
+
+fn default_max_skew() -> i64 {
+    300
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SignClient {
+    pub app_id: String,
+    pub secret: String,
+}
+
+impl SignCfg {
+    pub fn secret(&self, app_id: &str) -> Option<&str> {
+        self.clients
+            .iter()
+            .find(|e| e.app_id == app_id)
+            .map(|e| e.secret.as_str())
+    }
+}
+
+//订单设置
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderCfg {
+    //待支付订单超时取消的分钟数, 0为不自动取消
+    pub pay_timeout_minutes: i64,
+}
+
+impl Default for OrderCfg {
+    fn default() -> Self {
+        Self {
+            pay_timeout_minutes: 15,
+        }
+    }
+}
+
+//结算设置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct FinanceCfg {
+    //平台服务费率(%), 按支付金额收取
+    pub fee_percent: rust_decimal::Decimal,
+}
+
+//小程序订阅消息模板, 未配置的模板不通过订阅消息下发
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SubscribeCfg {
+    //预约开始前的提醒
+    pub reminder: Option<ReminderTemplate>,
+}
+
+impl SubscribeCfg {
+    //已配置的模板id, 由小程序端申请授权
+    pub fn templates(&self) -> Vec<&str> {
+        self.reminder
+            .iter()
+            .map(|e| e.template_id.as_str())
+            .collect()
+    }
+}
+
+//预约提醒模板, 字段名以公众平台中选用的模板为准
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReminderTemplate {
+    pub template_id: String,
+    //球场名称, 开始时间与备注对应的字段
+    #[serde(default = "default_court_field")]
+    pub court_field: String,
+    #[serde(default = "default_time_field")]
+    pub time_field: String,
+    #[serde(default = "default_remark_field")]
+    pub remark_field: String,
+}
+
+fn default_court_field() -> String {
+    "thing1".into()
+}
+
+fn default_time_field() -> String {
+    "time2".into()
+}
+
+fn default_remark_field() -> String {
+    "thing3".into()
+}
+
+//通知渠道, 未配置时只写日志
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum NotifyCfg {
+    #[default]
+    Log,
+    Webhook {
+        url: String,
+    },
+}
+
+//支付渠道, 未配置时线下收款
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PaymentCfg {
+    #[default]
+    Offline,
+    Wechat(Box<WechatPayCfg>),
+}
+
+//小程序登录, 未配置时使用微信支付配置中的appid与secret
+#[derive(Debug, Deserialize, Clone)]
+pub struct WechatCfg {
+    pub appid: String,
+    pub secret: String,
+}
+
+//微信支付(JSAPI), 使用APIv3
+#[derive(Debug, Deserialize, Clone)]
+pub struct WechatPayCfg {
+    //小程序appid与secret, 未配置wechatcfg时也用于小程序登录
+    pub appid: String,
+    pub secret: String,
+    //商户号与商户API证书序列号
+    pub mchid: String,
+    pub serial_no: String,
+    //商户API证书私钥, PKCS#8 PEM
+    pub private_key: String,
+    //支付结果与退款结果通知地址
+    pub notify_url: String,
+    pub refund_notify_url: String,
+    //APIv3密钥, 用于解密回调通知
+    pub api_v3_key: String,
+    //微信支付公钥ID与公钥(PEM), 用于验证回调签名
+    pub public_key_id: String,
+    pub public_key: String,
 }
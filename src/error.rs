@@ -1,6 +1,7 @@
 use axum::{http::StatusCode, response::IntoResponse, Json};
+use sea_orm::{DbErr, SqlErr};
 use serde_json::json;
-use tracing::warn;
+use tracing::{error, warn};
 use uuid::Uuid;
 
 #[derive(Debug)]
@@ -37,8 +38,19 @@ impl<T: serde::Serialize + ToString> IntoResponse for HandleErr<T> {
     }
 }
 
+//唯一约束冲突时返回给定提示, 其余按服务器错误处理
+pub fn unique_err<T: From<&'static str>>(err: DbErr, msg: &'static str) -> HandleErr<T> {
+    if let Some(SqlErr::UniqueConstraintViolation(_)) = err.sql_err() {
+        return HandleErr::BadRequest(-1, msg.into());
+    }
+    let id = Uuid::new_v4();
+    error!("{} >>>> {}", id, err.to_string());
+    HandleErr::ServerInnerErr(id)
+}
+
 macro_rules! impl_into {
     ($T:ty, $U:ty) => {
+        #[allow(clippy::from_over_into)]
         impl Into<HandleErr<$T>> for HandleErr<$U> {
             fn into(self) -> HandleErr<$T> {
                 match self {
@@ -1,13 +1,13 @@
 #![allow(non_snake_case)]
 use axum::{http::StatusCode, Router};
 use std::{future::IntoFuture, sync::Arc};
-use tokio::sync;
 use tracing::{info, warn};
 mod api;
 mod appstate;
 mod cfg;
 mod error;
 mod module;
+mod tasks;
 mod utils;
 mod App {
     pub type Result<T> = anyhow::Result<T>;
@@ -26,14 +26,16 @@ async fn main() {
         .init();
     //配置
 
-    let (sender, _) = sync::broadcast::channel(100);
     let cfg = cfg::parse().await.unwrap();
     let addrstr = format!("{}:{}", cfg.servercfg.ip, cfg.servercfg.port);
-    let state = Arc::new(
-        appstate::AppState::new(cfg, Arc::new(sender))
-            .await
-            .unwrap(),
-    );
+    //本地存储时由服务器提供静态文件访问
+    let static_root = match &cfg.storagecfg {
+        cfg::StorageCfg::Local { root, .. } => Some(root.clone()),
+        _ => None,
+    };
+    let state = Arc::new(appstate::AppState::new(cfg).await.unwrap());
+    //定时任务
+    tasks::spawn(state.clone());
     //挂载路由
     let approuter = api::router(state.clone())
         .with_state(state.clone())
@@ -42,10 +44,14 @@ async fn main() {
     info!("路由挂载完成");
     //启动服务器
     info!("🚀👏 {}", addrstr);
+    let mut router = Router::new().nest("/api", approuter);
+    if let Some(root) = static_root {
+        router = router.nest_service("/static", tower_http::services::ServeDir::new(root));
+    }
     let serve_handler = tokio::spawn(
         axum::serve::serve(
             tokio::net::TcpListener::bind(addrstr).await.unwrap(),
-            Router::new().nest("/api", approuter).into_make_service(),
+            router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
         )
         .into_future(),
     );
@@ -0,0 +1,468 @@
+use super::db::{
+    court_favorites, court_reviews, gift_cards, invoices, orders, points_transactions,
+    prelude::{
+        CourtFavorites, CourtReviews, GiftCards, Invoices, Orders, PointsTransactions,
+        ReferralCodes, Refunds, SearchHistory, SubscribeConsents, UserContacts, UserCoupons,
+        UserNotifications, UserPackages, UserSessions, Users, VenueFollows, WalletTransactions,
+    },
+    refunds,
+    sea_orm_active_enums::{Gender, GiftCardStatus, OrderState},
+    search_history, subscribe_consents, user_contacts, user_coupons, user_notifications,
+    user_packages, user_sessions, users, venue_follows, wallet_transactions,
+};
+use super::{points::PointsOp, session::SessionOp, wallet::WalletOp};
+use crate::{appstate::AppState, error::HandleErr, utils::passwd};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//注销账号须确认
+#[derive(Debug, Deserialize)]
+pub struct AccountDelete {
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+//修改密码, 微信登录创建的用户没有可用的原密码
+#[derive(Debug, Deserialize)]
+pub struct PasswordChange {
+    pub old_pwd: String,
+    pub new_pwd: String,
+}
+
+pub struct AccountOp;
+impl AccountOp {
+    //修改密码后撤销其他设备上的会话, 当前会话保留, 返回撤销数
+    pub async fn password(
+        user_id: Uuid,
+        session_id: Uuid,
+        schema: PasswordChange,
+        state: &AppState,
+    ) -> Result<u64, HandleErr<String>> {
+        if !(6..=64).contains(&schema.new_pwd.chars().count()) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "新密码长度须为6-64位".to_string(),
+            ));
+        }
+        let user = Users::find_by_id(user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string()))?;
+        passwd::verify_password(&schema.old_pwd, &user.user_pwd).map_err(Into::into)?;
+        let password_hash = passwd::hash_password(&schema.new_pwd)?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        users::ActiveModel {
+            user_id: Set(user_id),
+            user_pwd: Set(password_hash),
+            hash_version: Set(passwd::HASH_VERSION),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let revoked = SessionOp::revoke_all(user_id, Some(session_id), &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})修改密码, 撤销{}个会话", user_id, revoked);
+        Ok(revoked)
+    }
+
+    //导出用户的全部个人数据
+    pub async fn export<T: From<String>>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Value, HandleErr<T>> {
+        let user = Users::find_by_id(user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string().into()))?;
+        let profile = json!({
+            "user_id":user.user_id,
+            "user_name":user.user_name,
+            "phone":user.phone,
+            "nickname":user.nickname,
+            "avatar_url":user.avatar_url,
+            "gender":user.gender,
+            "preferred_sports":user.preferred_sports,
+            "notify_reminder":user.notify_reminder,
+            "no_show_count":user.no_show_count,
+            "banned_until":user.banned_until,
+            "wechat_bound":user.openid.is_some(),
+        });
+        let orders = Orders::find()
+            .filter(orders::Column::UserId.eq(user_id))
+            .order_by_desc(orders::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let refunds = Refunds::find()
+            .filter(refunds::Column::UserId.eq(user_id))
+            .order_by_desc(refunds::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let invoices = Invoices::find()
+            .filter(invoices::Column::UserId.eq(user_id))
+            .order_by_desc(invoices::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let reviews = CourtReviews::find()
+            .filter(court_reviews::Column::UserId.eq(user_id))
+            .order_by_desc(court_reviews::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let favorites = CourtFavorites::find()
+            .filter(court_favorites::Column::UserId.eq(user_id))
+            .order_by_desc(court_favorites::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let wallet_transactions = WalletTransactions::find()
+            .filter(wallet_transactions::Column::UserId.eq(user_id))
+            .order_by_desc(wallet_transactions::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let points_transactions = PointsTransactions::find()
+            .filter(points_transactions::Column::UserId.eq(user_id))
+            .order_by_desc(points_transactions::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let coupons = UserCoupons::find()
+            .filter(user_coupons::Column::UserId.eq(user_id))
+            .all(&state.db)
+            .await;
+        let packages = UserPackages::find()
+            .filter(user_packages::Column::UserId.eq(user_id))
+            .all(&state.db)
+            .await;
+        let gift_cards = GiftCards::find()
+            .filter(
+                gift_cards::Column::BuyerId
+                    .eq(user_id)
+                    .or(gift_cards::Column::RedeemedBy.eq(user_id)),
+            )
+            .order_by_desc(gift_cards::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let notifications = UserNotifications::find()
+            .filter(user_notifications::Column::UserId.eq(user_id))
+            .order_by_desc(user_notifications::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let contacts = UserContacts::find()
+            .filter(user_contacts::Column::UserId.eq(user_id))
+            .order_by_desc(user_contacts::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let search_history = SearchHistory::find()
+            .filter(search_history::Column::UserId.eq(user_id))
+            .order_by_desc(search_history::Column::SearchTime)
+            .all(&state.db)
+            .await;
+        let follows = VenueFollows::find()
+            .filter(venue_follows::Column::UserId.eq(user_id))
+            .order_by_desc(venue_follows::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let consents = SubscribeConsents::find()
+            .filter(subscribe_consents::Column::UserId.eq(user_id))
+            .all(&state.db)
+            .await;
+        let sessions = UserSessions::find()
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .order_by_desc(user_sessions::Column::CreateTime)
+            .all(&state.db)
+            .await;
+        let map_err = |err: sea_orm::DbErr| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        };
+        Ok(json!({
+            "profile":profile,
+            "wallet_balance":WalletOp::balance::<T, _>(user_id, &state.db).await?,
+            "points":PointsOp::balance::<T, _>(user_id, &state.db).await?,
+            "orders":orders.map_err(map_err)?,
+            "refunds":refunds.map_err(map_err)?,
+            "invoices":invoices.map_err(map_err)?,
+            "reviews":reviews.map_err(map_err)?,
+            "favorites":favorites.map_err(map_err)?,
+            "wallet_transactions":wallet_transactions.map_err(map_err)?,
+            "points_transactions":points_transactions.map_err(map_err)?,
+            "coupons":coupons.map_err(map_err)?,
+            "packages":packages.map_err(map_err)?,
+            "gift_cards":gift_cards.map_err(map_err)?,
+            "notifications":notifications.map_err(map_err)?,
+            "contacts":contacts.map_err(map_err)?,
+            "search_history":search_history.map_err(map_err)?,
+            "follows":follows.map_err(map_err)?,
+            "consents":consents.map_err(map_err)?,
+            "sessions":sessions.map_err(map_err)?,
+            "export_time":chrono::Utc::now().naive_utc(),
+        }))
+    }
+
+    //注销账号: 匿名化个人信息、订单联系人与发票抬头, 删除收藏/站内通知/邀请码, 订单与资金流水保留用于对账
+    //有未完成的预约、钱包余额、未用完的次卡或未兑换的礼品卡时不能注销
+    pub async fn delete<T: From<String>>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let user = Users::find_by_id(user_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string().into()))?;
+        if user.is_admin {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "管理员账号不能自助注销".to_string().into(),
+            ));
+        }
+        if Self::active_orders(user_id, &txn).await? > 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "有未完成的预约, 请完成或取消后再注销".to_string().into(),
+            ));
+        }
+        if WalletOp::balance::<T, _>(user_id, &txn).await? > sea_orm::prelude::Decimal::ZERO {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "钱包仍有余额, 请使用完毕后再注销".to_string().into(),
+            ));
+        }
+        if Self::unused_assets(user_id, &txn).await? {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "有未用完的次卡或未兑换的礼品卡, 请使用完毕后再注销"
+                    .to_string()
+                    .into(),
+            ));
+        }
+        //随机密码, 注销后不能再用密码登录
+        let password_hash = passwd::hash_password(&Uuid::new_v4().to_string())?;
+        users::ActiveModel {
+            user_id: Set(user_id),
+            user_name: Set(deleted_user_name(user_id)),
+            user_pwd: Set(password_hash),
+            hash_version: Set(passwd::HASH_VERSION),
+            phone: Set(None),
+            openid: Set(None),
+            unionid: Set(None),
+            nickname: Set(None),
+            avatar_url: Set(None),
+            gender: Set(Gender::Unknown),
+            preferred_sports: Set(vec![]),
+            notify_reminder: Set(false),
+            deleted_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Orders::update_many()
+            .col_expr(
+                orders::Column::ContactName,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(
+                orders::Column::ContactPhone,
+                Expr::value(Option::<String>::None),
+            )
+            .filter(orders::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Invoices::update_many()
+            .col_expr(invoices::Column::Title, Expr::value("已注销"))
+            .col_expr(
+                invoices::Column::TaxNumber,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(invoices::Column::Email, Expr::value(""))
+            .filter(invoices::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        CourtFavorites::delete_many()
+            .filter(court_favorites::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        ReferralCodes::delete_by_id(user_id)
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        UserNotifications::delete_many()
+            .filter(user_notifications::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        SearchHistory::delete_many()
+            .filter(search_history::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        VenueFollows::delete_many()
+            .filter(venue_follows::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        SubscribeConsents::delete_many()
+            .filter(subscribe_consents::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        UserContacts::delete_many()
+            .filter(user_contacts::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        SessionOp::revoke_all(user_id, None, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})已注销", user_id);
+        Ok(())
+    }
+
+    //未过期且有剩余时数的次卡, 或购买后未兑换的礼品卡
+    async fn unused_assets<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        let packages = UserPackages::find()
+            .filter(
+                user_packages::Column::UserId
+                    .eq(user_id)
+                    .and(user_packages::Column::HoursLeft.gt(0.0))
+                    .and(user_packages::Column::ExpireTime.gt(chrono::Utc::now().naive_utc())),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let gift_cards = GiftCards::find()
+            .filter(
+                gift_cards::Column::BuyerId
+                    .eq(user_id)
+                    .and(gift_cards::Column::Status.eq(GiftCardStatus::Active)),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(packages > 0 || gift_cards > 0)
+    }
+
+    //待支付或未结束的已支付订单数
+    async fn active_orders<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        Orders::find()
+            .filter(orders::Column::UserId.eq(user_id))
+            .filter(
+                orders::Column::Status
+                    .eq(OrderState::PendingPayment)
+                    .or(orders::Column::Status
+                        .is_in([OrderState::Paid, OrderState::Confirmed])
+                        .and(orders::Column::AptEnd.gt(chrono::Utc::now().naive_utc()))),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+}
+
+//注销用户的用户名, 保持唯一
+fn deleted_user_name(user_id: Uuid) -> String {
+    format!("已注销_{}", &user_id.simple().to_string()[..12])
+}
@@ -0,0 +1,263 @@
+use super::db::{api_key_logs, api_keys, prelude::ApiKeyLogs, prelude::ApiKeys, prelude::Users};
+use super::user::Role;
+use crate::{appstate::AppState, error::HandleErr};
+use rand_core::RngCore;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//明文密钥的前缀, 便于在代码仓库等处识别泄露的密钥
+const KEY_PREFIX: &str = "mpk_";
+//每个管理员可同时持有的有效密钥数
+const MAX_KEYS: u64 = 10;
+const NAME_LEN: usize = 50;
+//调用记录最多返回的条数
+const MAX_LOGS: u64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyCreate {
+    pub key_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyRevoke {
+    pub key_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyUsageQuery {
+    pub key_id: Uuid,
+}
+
+pub struct ApiKeyOp;
+impl ApiKeyOp {
+    //返回密钥记录与明文, 明文不落库, 只在此时返回一次
+    pub async fn create<T: From<String>>(
+        admin_id: Uuid,
+        schema: ApiKeyCreate,
+        state: &AppState,
+    ) -> Result<(api_keys::Model, String), HandleErr<T>> {
+        let key_name = schema.key_name.trim();
+        if key_name.is_empty() || key_name.chars().count() > NAME_LEN {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "密钥名称不能为空且不超过50字".to_string().into(),
+            ));
+        }
+        let count = ApiKeys::find()
+            .filter(api_keys::Column::AdminId.eq(admin_id))
+            .filter(api_keys::Column::RevokedTime.is_null())
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if count >= MAX_KEYS {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("最多同时持有{}个密钥", MAX_KEYS).into(),
+            ));
+        }
+        let key = generate();
+        let key = api_keys::ActiveModel {
+            key_id: NotSet,
+            admin_id: Set(admin_id),
+            key_name: Set(key_name.to_string()),
+            key_prefix: Set(key[..KEY_PREFIX.len() + 6].to_string()),
+            key_hash: Set(digest(&key)),
+            create_time: NotSet,
+            last_used_time: NotSet,
+            revoked_time: NotSet,
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+        .map(|e| (e, key))?;
+        info!("管理员({})创建API密钥({})", admin_id, key.0.key_id);
+        Ok(key)
+    }
+
+    pub async fn list<T>(
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<api_keys::Model>, HandleErr<T>> {
+        ApiKeys::find()
+            .filter(api_keys::Column::AdminId.eq(admin_id))
+            .order_by_desc(api_keys::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //吊销后立即失效, 记录保留以便查看调用记录
+    pub async fn revoke<T: From<String>>(
+        admin_id: Uuid,
+        key_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let rows_affected = ApiKeys::update_many()
+            .col_expr(
+                api_keys::Column::RevokedTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(api_keys::Column::KeyId.eq(key_id))
+            .filter(api_keys::Column::AdminId.eq(admin_id))
+            .filter(api_keys::Column::RevokedTime.is_null())
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "密钥不存在或已吊销".to_string().into(),
+            ));
+        }
+        info!("管理员({})吊销API密钥({})", admin_id, key_id);
+        Ok(())
+    }
+
+    //最近的调用记录, 最新的在前
+    pub async fn usage<T: From<String>>(
+        admin_id: Uuid,
+        key_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<api_key_logs::Model>, HandleErr<T>> {
+        ApiKeys::find_by_id(key_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.admin_id == admin_id)
+            .ok_or(HandleErr::BadRequest(-1, "密钥不存在".to_string().into()))?;
+        ApiKeyLogs::find()
+            .filter(api_key_logs::Column::KeyId.eq(key_id))
+            .order_by_desc(api_key_logs::Column::CreateTime)
+            .limit(MAX_LOGS)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //校验明文密钥, 已吊销、格式不符或所属管理员已失去权限时返回None
+    pub async fn authenticate<T>(
+        key: &str,
+        state: &AppState,
+    ) -> Result<Option<(api_keys::Model, super::db::users::Model)>, HandleErr<T>> {
+        if !key.starts_with(KEY_PREFIX) {
+            return Ok(None);
+        }
+        let Some((key, Some(admin))) = ApiKeys::find()
+            .filter(api_keys::Column::KeyHash.eq(digest(key)))
+            .filter(api_keys::Column::RevokedTime.is_null())
+            .find_also_related(Users)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+        else {
+            return Ok(None);
+        };
+        if admin.deleted_time.is_some()
+            || admin.disabled_time.is_some()
+            || Role::of(&admin) < Role::Admin
+        {
+            warn!(
+                "API密钥({})所属的管理员({})已失去权限",
+                key.key_id, admin.user_id
+            );
+            return Ok(None);
+        }
+        Ok(Some((key, admin)))
+    }
+
+    //记录一次调用并更新最近使用时间, 失败只记日志, 不影响响应
+    pub async fn record(
+        key_id: Uuid,
+        method: &str,
+        path: &str,
+        status: u16,
+        ip: &str,
+        state: &AppState,
+    ) {
+        let now = chrono::Utc::now().naive_utc();
+        let log = api_key_logs::ActiveModel {
+            log_id: NotSet,
+            key_id: Set(key_id),
+            method: Set(method.to_string()),
+            path: Set(path.chars().take(200).collect()),
+            status: Set(status as i16),
+            ip: Set(ip.chars().take(64).collect()),
+            create_time: Set(now),
+        }
+        .insert(&state.db)
+        .await;
+        let touch = api_keys::ActiveModel {
+            key_id: Set(key_id),
+            last_used_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await;
+        if let Err(err) = log.map(|_| ()).and(touch.map(|_| ())) {
+            warn!("API密钥({})调用记录写入失败: {}", key_id, err);
+        }
+    }
+}
+
+//mpk_加40位十六进制
+fn generate() -> String {
+    let mut bytes = [0u8; 20];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|e| format!("{:02x}", e)).collect();
+    format!("{}{}", KEY_PREFIX, hex)
+}
+
+//密钥本身有足够的熵, 不加盐
+fn digest(key: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect()
+}
+
+#[test]
+fn test_api_key() {
+    let key = generate();
+    assert!(key.starts_with(KEY_PREFIX));
+    assert_eq!(key.len(), KEY_PREFIX.len() + 40);
+    assert_ne!(key, generate());
+    assert_eq!(digest(&key), digest(&key));
+    assert_eq!(digest(&key).len(), 64);
+    assert_ne!(digest(&key), digest(&generate()));
+}
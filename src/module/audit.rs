@@ -0,0 +1,122 @@
+use super::court::search::escape;
+use super::db::{audit_log, prelude::AuditLog, users};
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, Condition, EntityTrait, FromQueryResult,
+    JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+//审计记录查询, 条件均可选
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuditQuery {
+    pub actor_id: Option<Uuid>,
+    pub method: Option<String>,
+    //路径前缀, 如 /api/admin/court
+    pub route: Option<String>,
+    pub status: Option<i16>,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+    #[serde(default = "super::order::default_page")]
+    pub page: u64,
+    #[serde(default = "super::order::default_page_size")]
+    pub page_size: u64,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct AuditSchema {
+    pub log_id: Uuid,
+    pub actor_id: Uuid,
+    pub actor_name: String,
+    pub ip: String,
+    pub method: String,
+    pub route: String,
+    pub status: i16,
+    pub payload_digest: Option<String>,
+    pub create_time: DateTime,
+}
+
+//一次写操作
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub actor_id: Uuid,
+    pub ip: String,
+    pub method: String,
+    pub route: String,
+    pub status: u16,
+    pub payload_digest: Option<String>,
+}
+
+pub struct AuditOp;
+impl AuditOp {
+    //写入失败只记日志, 不影响已完成的操作
+    pub async fn record(entry: AuditEntry, state: &AppState) {
+        let result = audit_log::ActiveModel {
+            log_id: NotSet,
+            actor_id: Set(entry.actor_id),
+            ip: Set(entry.ip.chars().take(64).collect()),
+            method: Set(entry.method.clone()),
+            route: Set(entry.route.chars().take(200).collect()),
+            status: Set(entry.status as i16),
+            payload_digest: Set(entry.payload_digest.clone()),
+            create_time: NotSet,
+        }
+        .insert(&state.db)
+        .await;
+        if let Err(err) = result {
+            warn!("审计记录写入失败({:?}): {}", entry, err);
+        }
+    }
+
+    //最新的在前
+    pub async fn list<T: From<&'static str>>(
+        query: AuditQuery,
+        state: &AppState,
+    ) -> Result<(u64, Vec<AuditSchema>), HandleErr<T>> {
+        if query.page == 0 || !(1..=100).contains(&query.page_size) {
+            return Err(HandleErr::BadRequest(-1, "分页参数无效".into()));
+        }
+        let mut cond = Condition::all();
+        if let Some(actor_id) = query.actor_id {
+            cond = cond.add(audit_log::Column::ActorId.eq(actor_id));
+        }
+        if let Some(method) = query.method {
+            cond = cond.add(audit_log::Column::Method.eq(method.to_uppercase()));
+        }
+        if let Some(route) = query.route.filter(|e| !e.is_empty()) {
+            cond =
+                cond.add(Expr::col(audit_log::Column::Route).like(format!("{}%", escape(&route))));
+        }
+        if let Some(status) = query.status {
+            cond = cond.add(audit_log::Column::Status.eq(status));
+        }
+        if let Some(from) = query.from {
+            cond = cond.add(audit_log::Column::CreateTime.gte(from));
+        }
+        if let Some(to) = query.to {
+            cond = cond.add(audit_log::Column::CreateTime.lt(to));
+        }
+        let paginator = AuditLog::find()
+            .join(JoinType::InnerJoin, audit_log::Relation::Users.def())
+            .column_as(users::Column::UserName, "actor_name")
+            .filter(cond)
+            .order_by_desc(audit_log::Column::CreateTime)
+            .into_model::<AuditSchema>()
+            .paginate(&state.db, query.page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let logs = paginator.fetch_page(query.page - 1).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok((total, logs))
+    }
+}
@@ -0,0 +1,203 @@
+use super::db::{
+    courts,
+    prelude::{Users, VenueBlacklist},
+    users, venue_blacklist, venues,
+};
+use super::venue::VenueOp;
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveValue::NotSet, ColumnTrait, Condition, EntityTrait, FromQueryResult, JoinType,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlacklistAdd {
+    pub venue_id: Uuid,
+    pub user_id: Uuid,
+    pub reason: String,
+    //为空时永久有效
+    pub expire_time: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlacklistDel {
+    pub venue_id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlacklistQuery {
+    //为空时查询名下全部场馆
+    pub venue_id: Option<Uuid>,
+    //是否包含已过期的记录
+    #[serde(default)]
+    pub expired: bool,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct BlacklistSchema {
+    pub venue_id: Uuid,
+    pub venue_name: String,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub phone: Option<String>,
+    pub reason: String,
+    pub expire_time: Option<DateTime>,
+    pub create_time: DateTime,
+}
+
+pub struct BlacklistOp;
+impl BlacklistOp {
+    //加入黑名单, 已在名单中时更新原因与期限
+    pub async fn add<T: From<&'static str>>(
+        admin_id: Uuid,
+        schema: BlacklistAdd,
+        state: &AppState,
+    ) -> Result<venue_blacklist::Model, HandleErr<T>> {
+        let reason = schema.reason.trim();
+        if reason.is_empty() || reason.chars().count() > 200 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "原因不能为空且不超过200字".into(),
+            ));
+        }
+        let now = chrono::Utc::now().naive_utc();
+        if schema.expire_time.is_some_and(|e| e <= now) {
+            return Err(HandleErr::BadRequest(-1, "到期时间应晚于当前时间".into()));
+        }
+        if schema.user_id == admin_id {
+            return Err(HandleErr::BadRequest(-1, "不能将自己加入黑名单".into()));
+        }
+        VenueOp::owned::<T>(schema.venue_id, admin_id, state).await?;
+        Users::find_by_id(schema.user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "用户不存在".into()))?;
+        VenueBlacklist::insert(venue_blacklist::ActiveModel {
+            venue_id: Set(schema.venue_id),
+            user_id: Set(schema.user_id),
+            admin_id: Set(admin_id),
+            reason: Set(reason.to_string()),
+            expire_time: Set(schema.expire_time),
+            create_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::columns([
+                venue_blacklist::Column::VenueId,
+                venue_blacklist::Column::UserId,
+            ])
+            .update_columns([
+                venue_blacklist::Column::AdminId,
+                venue_blacklist::Column::Reason,
+                venue_blacklist::Column::ExpireTime,
+            ])
+            .to_owned(),
+        )
+        .exec_with_returning(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //移出黑名单, 只能操作名下场馆
+    pub async fn remove<T: From<&'static str>>(
+        admin_id: Uuid,
+        schema: BlacklistDel,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        VenueOp::owned::<T>(schema.venue_id, admin_id, state).await?;
+        let rows_affected = VenueBlacklist::delete_by_id((schema.venue_id, schema.user_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "用户不在黑名单中".into()));
+        }
+        Ok(())
+    }
+
+    //管理员名下场馆的黑名单, 默认只返回有效的记录
+    pub async fn list<T>(
+        admin_id: Uuid,
+        query: &BlacklistQuery,
+        state: &AppState,
+    ) -> Result<Vec<BlacklistSchema>, HandleErr<T>> {
+        let mut cond = Condition::all().add(venues::Column::AdminId.eq(admin_id));
+        if let Some(venue_id) = query.venue_id {
+            cond = cond.add(venue_blacklist::Column::VenueId.eq(venue_id));
+        }
+        if !query.expired {
+            cond = cond.add(active(chrono::Utc::now().naive_utc()));
+        }
+        VenueBlacklist::find()
+            .select_only()
+            .columns([
+                venue_blacklist::Column::VenueId,
+                venue_blacklist::Column::UserId,
+                venue_blacklist::Column::Reason,
+                venue_blacklist::Column::ExpireTime,
+                venue_blacklist::Column::CreateTime,
+            ])
+            .column(venues::Column::VenueName)
+            .column(users::Column::UserName)
+            .column(users::Column::Phone)
+            .join(JoinType::InnerJoin, venue_blacklist::Relation::Venues.def())
+            .join(JoinType::InnerJoin, venue_blacklist::Relation::Users1.def())
+            .filter(cond)
+            .order_by_desc(venue_blacklist::Column::CreateTime)
+            .into_model::<BlacklistSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //用户在球场所属场馆的有效黑名单记录
+    pub async fn of<T>(
+        court_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Option<venue_blacklist::Model>, HandleErr<T>> {
+        VenueBlacklist::find()
+            .join(JoinType::InnerJoin, venue_blacklist::Relation::Venues.def())
+            .join(JoinType::InnerJoin, venues::Relation::Courts.def())
+            .filter(courts::Column::CourtId.eq(court_id))
+            .filter(venue_blacklist::Column::UserId.eq(user_id))
+            .filter(active(chrono::Utc::now().naive_utc()))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+}
+
+//未到期的记录
+fn active(now: DateTime) -> Condition {
+    Condition::any()
+        .add(venue_blacklist::Column::ExpireTime.is_null())
+        .add(venue_blacklist::Column::ExpireTime.gt(now))
+}
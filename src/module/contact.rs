@@ -0,0 +1,208 @@
+use super::db::{prelude::UserContacts, user_contacts};
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+use uuid::Uuid;
+
+//每个用户最多保存的联系人数
+const MAX_CONTACTS: u64 = 20;
+pub const NAME_LEN: usize = 30;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct ContactSave {
+    pub contact_id: Option<Uuid>,
+    pub contact_name: String,
+    pub phone: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ContactDel {
+    pub contact_id: Uuid,
+}
+
+pub struct ContactOp;
+impl ContactOp {
+    //最近使用的在前, 未使用过的按添加时间倒序
+    pub async fn list<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<user_contacts::Model>, HandleErr<T>> {
+        UserContacts::find()
+            .filter(user_contacts::Column::UserId.eq(user_id))
+            .order_by_asc(user_contacts::Column::LastUsedTime.is_null())
+            .order_by_desc(user_contacts::Column::LastUsedTime)
+            .order_by_desc(user_contacts::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    pub async fn save<T: From<String>>(
+        user_id: Uuid,
+        schema: ContactSave,
+        state: &AppState,
+    ) -> Result<user_contacts::Model, HandleErr<T>> {
+        let (name, phone) = validate(&schema.contact_name, &schema.phone)
+            .map_err(|e| HandleErr::BadRequest(-1, e.to_string().into()))?;
+        let duplicate = UserContacts::find()
+            .filter(user_contacts::Column::UserId.eq(user_id))
+            .filter(user_contacts::Column::Phone.eq(&phone))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .is_some_and(|e| Some(e.contact_id) != schema.contact_id);
+        if duplicate {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "该手机号的联系人已存在".to_string().into(),
+            ));
+        }
+        let contact = match schema.contact_id {
+            Some(contact_id) => {
+                Self::owned(user_id, contact_id, &state.db).await?;
+                user_contacts::ActiveModel {
+                    contact_id: Set(contact_id),
+                    contact_name: Set(name),
+                    phone: Set(phone),
+                    ..Default::default()
+                }
+                .update(&state.db)
+                .await
+            }
+            None => {
+                let count = UserContacts::find()
+                    .filter(user_contacts::Column::UserId.eq(user_id))
+                    .count(&state.db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?;
+                if count >= MAX_CONTACTS {
+                    return Err(HandleErr::BadRequest(
+                        -1,
+                        format!("最多保存{}个联系人", MAX_CONTACTS).into(),
+                    ));
+                }
+                user_contacts::ActiveModel {
+                    contact_id: NotSet,
+                    user_id: Set(user_id),
+                    contact_name: Set(name),
+                    phone: Set(phone),
+                    create_time: NotSet,
+                    last_used_time: NotSet,
+                }
+                .insert(&state.db)
+                .await
+            }
+        }
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})保存联系人({})", user_id, contact.contact_id);
+        Ok(contact)
+    }
+
+    pub async fn del<T: From<String>>(
+        user_id: Uuid,
+        contact_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let rows_affected = UserContacts::delete_many()
+            .filter(user_contacts::Column::ContactId.eq(contact_id))
+            .filter(user_contacts::Column::UserId.eq(user_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "联系人不存在".to_string().into()));
+        }
+        Ok(())
+    }
+
+    //下单时选用联系人, 记录使用时间, 返回姓名与手机号
+    pub async fn take<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        contact_id: Uuid,
+        db: &C,
+    ) -> Result<(String, String), HandleErr<T>> {
+        let contact = Self::owned(user_id, contact_id, db).await?;
+        user_contacts::ActiveModel {
+            contact_id: Set(contact.contact_id),
+            last_used_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok((contact.contact_name, contact.phone))
+    }
+
+    async fn owned<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        contact_id: Uuid,
+        db: &C,
+    ) -> Result<user_contacts::Model, HandleErr<T>> {
+        UserContacts::find_by_id(contact_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.user_id == user_id)
+            .ok_or(HandleErr::BadRequest(-1, "联系人不存在".to_string().into()))
+    }
+}
+
+//姓名不能为空, 手机号为数字, 境外号码可带+区号
+pub fn validate(name: &str, phone: &str) -> Result<(String, String), &'static str> {
+    let (name, phone) = (name.trim(), phone.trim());
+    if name.is_empty() || name.chars().count() > NAME_LEN || name.chars().any(char::is_control) {
+        return Err("联系人姓名不能为空且不超过30字");
+    }
+    let digits = phone.strip_prefix('+').unwrap_or(phone);
+    if !(5..=19).contains(&digits.len()) || !digits.chars().all(|e| e.is_ascii_digit()) {
+        return Err("手机号格式不正确");
+    }
+    Ok((name.to_string(), phone.to_string()))
+}
+
+#[test]
+fn test_validate() {
+    assert_eq!(
+        validate(" 张三 ", "13800138000"),
+        Ok(("张三".to_string(), "13800138000".to_string()))
+    );
+    assert!(validate("Tom", "+85291234567").is_ok());
+    assert!(validate("", "13800138000").is_err());
+    assert!(validate("张三", "1380013800a").is_err());
+    assert!(validate("张三", "+").is_err());
+    assert!(validate(&"张".repeat(31), "13800138000").is_err());
+}
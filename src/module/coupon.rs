@@ -0,0 +1,544 @@
+use super::db::{
+    coupon_courts, coupon_templates, courts,
+    prelude::{CouponCourts, CouponTemplates, Courts, UserCoupons, Users},
+    sea_orm_active_enums::{CouponKind, CouponStatus},
+    user_coupons, users,
+};
+use super::money;
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+//单次批量发放的上限, 用户数乘以每人张数
+pub const MAX_ISSUE: usize = 5000;
+//批量写入时每条insert的行数, 避免超出postgres单条语句65535个参数的限制
+const INSERT_CHUNK: usize = 1000;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct CouponTemplateSave {
+    pub template_id: Option<Uuid>,
+    pub name: String,
+    pub kind: CouponKind,
+    pub value: Decimal,
+    #[serde(default)]
+    pub min_amount: Decimal,
+    pub max_discount: Option<Decimal>,
+    pub valid_from: DateTime,
+    pub valid_to: DateTime,
+    //限定的球场, 为空时可用于名下全部球场
+    #[serde(default)]
+    pub court_ids: Vec<Uuid>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CouponTemplateDel {
+    pub template_id: Uuid,
+}
+
+//按用户批量发放, quantity为每人张数
+#[derive(Debug, Deserialize, Clone)]
+pub struct CouponIssue {
+    pub template_id: Uuid,
+    pub user_ids: Vec<Uuid>,
+    #[serde(default = "default_quantity")]
+    pub quantity: usize,
+}
+
+fn default_quantity() -> usize {
+    1
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CouponTemplateSchema {
+    #[serde(flatten)]
+    pub template: coupon_templates::Model,
+    pub court_ids: Vec<Uuid>,
+    //已发放与已使用张数
+    pub issued: u64,
+    pub used: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct UserCouponSchema {
+    #[serde(flatten)]
+    pub coupon: user_coupons::Model,
+    pub template: Option<coupon_templates::Model>,
+}
+
+pub struct CouponOp;
+impl CouponOp {
+    //管理员创建的优惠券模板, 附带限定球场与发放使用情况
+    pub async fn templates<T>(
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<CouponTemplateSchema>, HandleErr<T>> {
+        let templates = CouponTemplates::find()
+            .filter(coupon_templates::Column::AdminId.eq(admin_id))
+            .order_by_desc(coupon_templates::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut schemas = vec![];
+        for template in templates {
+            let court_ids = Self::courts_of(template.template_id, &state.db).await?;
+            let issued = Self::count(template.template_id, None, &state.db).await?;
+            let used =
+                Self::count(template.template_id, Some(CouponStatus::Used), &state.db).await?;
+            schemas.push(CouponTemplateSchema {
+                template,
+                court_ids,
+                issued,
+                used,
+            });
+        }
+        Ok(schemas)
+    }
+
+    async fn courts_of<T, C: ConnectionTrait>(
+        template_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<Uuid>, HandleErr<T>> {
+        Ok(CouponCourts::find()
+            .filter(coupon_courts::Column::TemplateId.eq(template_id))
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| e.court_id)
+            .collect())
+    }
+
+    async fn count<T, C: ConnectionTrait>(
+        template_id: Uuid,
+        status: Option<CouponStatus>,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        let mut query =
+            UserCoupons::find().filter(user_coupons::Column::TemplateId.eq(template_id));
+        if let Some(status) = status {
+            query = query.filter(user_coupons::Column::Status.eq(status));
+        }
+        query.count(db).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //管理员名下的模板
+    pub async fn owned<T: From<String>, C: ConnectionTrait>(
+        template_id: Uuid,
+        admin_id: Uuid,
+        db: &C,
+    ) -> Result<coupon_templates::Model, HandleErr<T>> {
+        CouponTemplates::find_by_id(template_id)
+            .filter(coupon_templates::Column::AdminId.eq(admin_id))
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "优惠券模板不存在".to_string().into(),
+            ))
+    }
+
+    //新建或修改模板, 同时替换限定球场, 限定的球场须属于该管理员
+    pub async fn save<T: From<String>>(
+        admin_id: Uuid,
+        schema: CouponTemplateSave,
+        state: &AppState,
+    ) -> Result<CouponTemplateSchema, HandleErr<T>> {
+        if !schema.court_ids.is_empty() {
+            let owned = Courts::find()
+                .filter(
+                    courts::Column::CourtId
+                        .is_in(schema.court_ids.clone())
+                        .and(courts::Column::AdminId.eq(admin_id)),
+                )
+                .count(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            if owned as usize != schema.court_ids.len() {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "court_ids无效".to_string().into(),
+                ));
+            }
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if let Some(template_id) = schema.template_id {
+            Self::owned::<T, _>(template_id, admin_id, &txn).await?;
+        }
+        let template = coupon_templates::ActiveModel {
+            template_id: schema.template_id.map(Set).unwrap_or(NotSet),
+            admin_id: Set(admin_id),
+            name: Set(schema.name),
+            kind: Set(schema.kind),
+            value: Set(money::round(schema.value)),
+            min_amount: Set(money::round(schema.min_amount)),
+            max_discount: Set(schema.max_discount.map(money::round)),
+            valid_from: Set(schema.valid_from),
+            valid_to: Set(schema.valid_to),
+            enabled: Set(schema.enabled),
+            create_time: NotSet,
+        }
+        .save(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        CouponCourts::delete_many()
+            .filter(coupon_courts::Column::TemplateId.eq(template.template_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if !schema.court_ids.is_empty() {
+            CouponCourts::insert_many(schema.court_ids.iter().map(|e| {
+                coupon_courts::ActiveModel {
+                    template_id: Set(template.template_id),
+                    court_id: Set(*e),
+                }
+            }))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        let issued = Self::count(template.template_id, None, &txn).await?;
+        let used = Self::count(template.template_id, Some(CouponStatus::Used), &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(CouponTemplateSchema {
+            template,
+            court_ids: schema.court_ids,
+            issued,
+            used,
+        })
+    }
+
+    //删除未发放过的模板, 已发放的只能停用
+    pub async fn delete<T: From<String>>(
+        template_id: Uuid,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let template = Self::owned::<T, _>(template_id, admin_id, &state.db).await?;
+        if Self::count(template.template_id, None, &state.db).await? > 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "已发放的优惠券模板只能停用".to_string().into(),
+            ));
+        }
+        CouponTemplates::delete_by_id(template.template_id)
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //批量发放, 忽略不存在的用户, 返回批次号与发放张数
+    pub async fn issue<T: From<String>>(
+        admin_id: Uuid,
+        schema: CouponIssue,
+        state: &AppState,
+    ) -> Result<(Uuid, usize), HandleErr<T>> {
+        let total = schema.user_ids.len().checked_mul(schema.quantity);
+        if schema.quantity == 0 || total.is_none_or(|e| e > MAX_ISSUE) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("单次最多发放{}张", MAX_ISSUE).into(),
+            ));
+        }
+        let template = Self::owned::<T, _>(schema.template_id, admin_id, &state.db).await?;
+        if !template.enabled || template.valid_to <= chrono::Utc::now().naive_utc() {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "优惠券已停用或已过期".to_string().into(),
+            ));
+        }
+        let user_ids: Vec<Uuid> = Users::find()
+            .select_only()
+            .column(users::Column::UserId)
+            .filter(users::Column::UserId.is_in(schema.user_ids))
+            .into_tuple()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let batch_id = Uuid::new_v4();
+        let coupons: Vec<_> = user_ids
+            .iter()
+            .flat_map(|e| std::iter::repeat_n(*e, schema.quantity))
+            .collect();
+        //分批写入, 同一批次全部成功或全部失败
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        for chunk in coupons.chunks(INSERT_CHUNK) {
+            UserCoupons::insert_many(chunk.iter().map(|user_id| user_coupons::ActiveModel {
+                coupon_id: NotSet,
+                template_id: Set(template.template_id),
+                user_id: Set(*user_id),
+                status: NotSet,
+                batch_id: Set(batch_id),
+                order_id: NotSet,
+                discount: NotSet,
+                create_time: NotSet,
+                used_time: NotSet,
+            }))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok((batch_id, coupons.len()))
+    }
+
+    //系统发放一张优惠券, 如邀请奖励, 模板已停用或过期时不发放, 返回是否发放
+    pub async fn grant<T, C: ConnectionTrait>(
+        template_id: Uuid,
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let template = CouponTemplates::find_by_id(template_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let Some(template) = template.filter(|e| e.enabled && e.valid_to > now) else {
+            return Ok(false);
+        };
+        UserCoupons::insert(user_coupons::ActiveModel {
+            coupon_id: NotSet,
+            template_id: Set(template.template_id),
+            user_id: Set(user_id),
+            status: NotSet,
+            batch_id: Set(Uuid::new_v4()),
+            order_id: NotSet,
+            discount: NotSet,
+            create_time: NotSet,
+            used_time: NotSet,
+        })
+        .exec_without_returning(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(true)
+    }
+
+    //用户的优惠券, 未使用的在前
+    pub async fn mine<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<UserCouponSchema>, HandleErr<T>> {
+        Ok(UserCoupons::find()
+            .filter(user_coupons::Column::UserId.eq(user_id))
+            .find_also_related(CouponTemplates)
+            .order_by_desc(user_coupons::Column::Status.eq(CouponStatus::Unused))
+            .order_by_desc(user_coupons::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|(coupon, template)| UserCouponSchema { coupon, template })
+            .collect())
+    }
+
+    //检查用户的优惠券能否用于该球场的订单, 返回模板用于计算抵扣金额
+    pub async fn usable<T: From<String>, C: ConnectionTrait>(
+        coupon_id: Uuid,
+        user_id: Uuid,
+        court: &courts::Model,
+        cost: Decimal,
+        db: &C,
+    ) -> Result<coupon_templates::Model, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let (coupon, template) = UserCoupons::find_by_id(coupon_id)
+            .filter(user_coupons::Column::UserId.eq(user_id))
+            .find_also_related(CouponTemplates)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "优惠券不存在".to_string().into()))?;
+        let template =
+            template.ok_or(HandleErr::BadRequest(-1, "优惠券不存在".to_string().into()))?;
+        if coupon.status != CouponStatus::Unused {
+            return Err(HandleErr::BadRequest(-1, "优惠券已使用".to_string().into()));
+        }
+        if !template.enabled || now < template.valid_from || now >= template.valid_to {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "优惠券不在有效期内".to_string().into(),
+            ));
+        }
+        let court_ids = Self::courts_of(template.template_id, db).await?;
+        if court.admin_id != template.admin_id
+            || !(court_ids.is_empty() || court_ids.contains(&court.court_id))
+        {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "优惠券不适用于该球场".to_string().into(),
+            ));
+        }
+        if cost < template.min_amount {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("订单金额满{:.2}元可用", template.min_amount).into(),
+            ));
+        }
+        Ok(template)
+    }
+
+    //下单时核销, 以未使用为条件更新, 并发使用同一张券时只有一个成功
+    pub async fn redeem<T: From<&'static str>, C: ConnectionTrait>(
+        coupon_id: Uuid,
+        order_id: Uuid,
+        discount: Decimal,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let rows_affected = UserCoupons::update_many()
+            .col_expr(
+                user_coupons::Column::Status,
+                Expr::value(CouponStatus::Used),
+            )
+            .col_expr(user_coupons::Column::OrderId, Expr::value(order_id))
+            .col_expr(user_coupons::Column::Discount, Expr::value(discount))
+            .col_expr(
+                user_coupons::Column::UsedTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(
+                user_coupons::Column::CouponId
+                    .eq(coupon_id)
+                    .and(user_coupons::Column::Status.eq(CouponStatus::Unused)),
+            )
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "优惠券已使用".into()));
+        }
+        Ok(())
+    }
+
+    //未支付的订单取消后退还使用的优惠券
+    pub async fn restore<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        Ok(UserCoupons::update_many()
+            .col_expr(
+                user_coupons::Column::Status,
+                Expr::value(CouponStatus::Unused),
+            )
+            .col_expr(user_coupons::Column::OrderId, Expr::value(None::<Uuid>))
+            .col_expr(user_coupons::Column::Discount, Expr::value(None::<Decimal>))
+            .col_expr(
+                user_coupons::Column::UsedTime,
+                Expr::value(None::<DateTime>),
+            )
+            .filter(
+                user_coupons::Column::OrderId
+                    .eq(order_id)
+                    .and(user_coupons::Column::Status.eq(CouponStatus::Used)),
+            )
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+}
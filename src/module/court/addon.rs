@@ -0,0 +1,190 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self,
+        prelude::{CourtAddons, OrderAddons},
+    },
+    module::money,
+};
+use sea_orm::prelude::Decimal;
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, TryIntoModel,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::error;
+use uuid::Uuid;
+
+//单个附加项目的数量上限, 避免数量累加溢出或金额异常
+const MAX_QUANTITY: i32 = 99;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct AddonSave {
+    pub addon_id: Option<Uuid>,
+    pub court_id: Uuid,
+    pub addon_name: String,
+    pub price: Decimal,
+    #[serde(default)]
+    pub per_hour: bool,
+    #[serde(default = "default_active")]
+    pub is_active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AddonDel {
+    pub addon_id: Uuid,
+}
+
+//下单时选择的附加项目
+#[derive(Debug, Deserialize, Clone)]
+pub struct AddonItem {
+    pub addon_id: Uuid,
+    pub quantity: i32,
+}
+
+pub struct AddonOp;
+impl AddonOp {
+    pub async fn list<T>(
+        court_id: Uuid,
+        only_active: bool,
+        state: &AppState,
+    ) -> Result<Vec<db::court_addons::Model>, HandleErr<T>> {
+        let mut query = CourtAddons::find().filter(db::court_addons::Column::CourtId.eq(court_id));
+        if only_active {
+            query = query.filter(db::court_addons::Column::IsActive.eq(true));
+        }
+        query.all(&state.db).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    pub async fn save<T: From<&'static str>>(
+        schema: AddonSave,
+        state: &AppState,
+    ) -> Result<db::court_addons::Model, HandleErr<T>> {
+        if schema.price < Decimal::ZERO {
+            return Err(HandleErr::BadRequest(-1, "价格无效".into()));
+        }
+        let duplicated = Self::list(schema.court_id, false, state)
+            .await?
+            .into_iter()
+            .any(|e| Some(e.addon_id) != schema.addon_id && e.addon_name == schema.addon_name);
+        if duplicated {
+            return Err(HandleErr::BadRequest(-1, "附加项目名重复".into()));
+        }
+        db::court_addons::ActiveModel {
+            addon_id: schema.addon_id.map(Set).unwrap_or(NotSet),
+            court_id: Set(schema.court_id),
+            addon_name: Set(schema.addon_name),
+            price: Set(money::round(schema.price)),
+            per_hour: Set(schema.per_hour),
+            is_active: Set(schema.is_active),
+        }
+        .save(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //按球场校验下单选择的附加项目, 返回对应的项目与数量
+    pub async fn resolve<T: From<&'static str>>(
+        court_id: Uuid,
+        items: &[AddonItem],
+        state: &AppState,
+    ) -> Result<Vec<(db::court_addons::Model, i32)>, HandleErr<T>> {
+        if items.is_empty() {
+            return Ok(vec![]);
+        }
+        if items
+            .iter()
+            .any(|e| e.quantity <= 0 || e.quantity > MAX_QUANTITY)
+        {
+            return Err(HandleErr::BadRequest(-1, "附加项目数量须为1~99".into()));
+        }
+        let addons: HashMap<Uuid, db::court_addons::Model> = Self::list(court_id, true, state)
+            .await?
+            .into_iter()
+            .map(|e| (e.addon_id, e))
+            .collect();
+        let mut res: Vec<(db::court_addons::Model, i32)> = vec![];
+        for item in items {
+            let addon = addons
+                .get(&item.addon_id)
+                .ok_or(HandleErr::BadRequest(-1, "附加项目无效".into()))?;
+            match res.iter_mut().find(|(e, _)| e.addon_id == item.addon_id) {
+                Some((_, quantity)) => {
+                    //重复的项目合并后同样受数量上限约束
+                    *quantity += item.quantity;
+                    if *quantity > MAX_QUANTITY {
+                        return Err(HandleErr::BadRequest(-1, "附加项目数量须为1~99".into()));
+                    }
+                }
+                None => res.push((addon.clone(), item.quantity)),
+            }
+        }
+        Ok(res)
+    }
+
+    //订单已选的附加项目
+    pub async fn of_order<T>(
+        order_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<db::order_addons::Model>, HandleErr<T>> {
+        OrderAddons::find()
+            .filter(db::order_addons::Column::OrderId.eq(order_id))
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //记录订单的附加项目, 价格按当前价格快照
+    pub async fn attach<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        addons: &[(db::court_addons::Model, i32)],
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        if addons.is_empty() {
+            return Ok(());
+        }
+        OrderAddons::insert_many(addons.iter().map(|(addon, quantity)| {
+            db::order_addons::ActiveModel {
+                order_id: Set(order_id),
+                addon_id: Set(addon.addon_id),
+                quantity: Set(*quantity),
+                price: Set(addon.price),
+                per_hour: Set(addon.per_hour),
+            }
+        }))
+        .exec(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::{calendar::CalendarOp, open_hours::OpenHoursOp},
+        db::{self, prelude::Orders, sea_orm_active_enums::CourtStatus},
+        order::state,
+    },
+};
+use sea_orm::prelude::{Date, DateTime, Time};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AvailabilityQuery {
+    pub date: Date,
+    //时段长度, 分钟
+    #[serde(default = "default_step")]
+    pub step: i64,
+}
+
+fn default_step() -> i64 {
+    60
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Slot {
+    pub start: DateTime,
+    pub end: DateTime,
+    pub available: bool,
+}
+
+pub struct AvailabilityOp;
+impl AvailabilityOp {
+    //某天的可预约时段, 由营业时间/屏蔽时段/已有订单计算
+    pub async fn of<T>(
+        court: &db::courts::Model,
+        date: Date,
+        step: i64,
+        state: &AppState,
+    ) -> Result<Vec<Slot>, HandleErr<T>> {
+        if court.status != CourtStatus::Open {
+            return Ok(vec![]);
+        }
+        let Some(window) = OpenHoursOp::window(court, date, state).await? else {
+            return Ok(vec![]);
+        };
+        let (day_start, day_end) = (date.and_time(window.0), date.and_time(window.1));
+        let mut busy: Vec<_> = CalendarOp::blocks(court.court_id, day_start, day_end, state)
+            .await?
+            .into_iter()
+            .map(|e| (e.block_start, e.block_end))
+            .collect();
+        busy.extend(
+            Orders::find()
+                .filter(
+                    db::orders::Column::CourtId
+                        .eq(court.court_id)
+                        .and(db::orders::Column::AptStart.lt(day_end))
+                        .and(db::orders::Column::AptEnd.gt(day_start))
+                        .and(db::orders::Column::Status.is_not_in(state::RELEASED)),
+                )
+                .all(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+                .into_iter()
+                .map(|e| (e.apt_start, e.apt_end)),
+        );
+        let now = chrono::Utc::now().naive_utc();
+        Ok(slots(date, window, &busy, step, now))
+    }
+}
+
+//按step切分营业时间, 与占用时段重叠或已开始的时段不可预约
+//最后不足step的部分也作为一个时段
+pub fn slots(
+    date: Date,
+    window: (Time, Time),
+    busy: &[(DateTime, DateTime)],
+    step: i64,
+    now: DateTime,
+) -> Vec<Slot> {
+    let (mut start, end) = (date.and_time(window.0), date.and_time(window.1));
+    let step = chrono::Duration::minutes(step);
+    let mut res = vec![];
+    while start < end {
+        let slot_end = (start + step).min(end);
+        let available = start >= now && !busy.iter().any(|(s, e)| *s < slot_end && start < *e);
+        res.push(Slot {
+            start,
+            end: slot_end,
+            available,
+        });
+        start = slot_end;
+    }
+    res
+}
+
+#[test]
+fn test_slots() {
+    let date = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let t = |h, m| Time::from_hms_opt(h, m, 0).unwrap();
+    let at = |h, m| date.and_time(t(h, m));
+    let yesterday = at(0, 0) - chrono::Duration::days(1);
+    //09:00-12:30, 10:00-11:00已被预约
+    let res = slots(
+        date,
+        (t(9, 0), t(12, 30)),
+        &[(at(10, 0), at(11, 0))],
+        60,
+        yesterday,
+    );
+    assert_eq!(
+        res.iter()
+            .map(|e| (e.start, e.end, e.available))
+            .collect::<Vec<_>>(),
+        vec![
+            (at(9, 0), at(10, 0), true),
+            (at(10, 0), at(11, 0), false),
+            (at(11, 0), at(12, 0), true),
+            (at(12, 0), at(12, 30), true),
+        ]
+    );
+    //跨越多个时段的占用, 以及已经开始的时段
+    let res = slots(
+        date,
+        (t(9, 0), t(11, 0)),
+        &[(at(9, 30), at(10, 15))],
+        30,
+        at(9, 10),
+    );
+    assert_eq!(
+        res.iter().map(|e| e.available).collect::<Vec<_>>(),
+        vec![false, false, false, true]
+    );
+}
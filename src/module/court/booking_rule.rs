@@ -0,0 +1,195 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::blacklist::BlacklistOp,
+    module::db::{
+        self,
+        prelude::{CourtBookingRules, Orders, Users},
+    },
+    module::money,
+    module::order::state,
+    module::tier::TierOp,
+};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+//预约规则校验失败的错误码
+pub const ERR_TOO_SHORT: i32 = 1001;
+pub const ERR_TOO_LONG: i32 = 1002;
+pub const ERR_TOO_EARLY: i32 = 1003;
+pub const ERR_TOO_MANY: i32 = 1004;
+pub const ERR_BANNED: i32 = 1005;
+pub const ERR_BLACKLISTED: i32 = 1006;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BookingRuleSet {
+    pub court_id: Uuid,
+    pub min_minutes: Option<i32>,
+    pub max_minutes: Option<i32>,
+    pub max_advance_days: Option<i32>,
+    pub max_active_orders: Option<i32>,
+    //开始前多少分钟之前免费取消, 为空时随时免费
+    pub free_cancel_minutes: Option<i32>,
+    //超过免费取消时间后的取消费用比例, 0~1
+    #[serde(default)]
+    pub cancel_fee_rate: Decimal,
+}
+
+pub struct BookingRuleOp;
+impl BookingRuleOp {
+    pub async fn rule<T>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<Option<db::court_booking_rules::Model>, HandleErr<T>> {
+        CourtBookingRules::find_by_id(court_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //下单/改单时校验, order_id为修改中的订单
+    pub async fn check(
+        court_id: Uuid,
+        user_id: Uuid,
+        start: DateTime,
+        end: DateTime,
+        order_id: Option<Uuid>,
+        state: &AppState,
+    ) -> Result<(), HandleErr<String>> {
+        let now = chrono::Utc::now().naive_utc();
+        let user = Users::find_by_id(user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string()))?;
+        //多次未到场的用户在限制期内不能预约
+        if let Some(until) = user.banned_until.filter(|e| *e > now) {
+            return Err(HandleErr::BadRequest(
+                ERR_BANNED,
+                format!(
+                    "多次预约未到场, {}前不能预约",
+                    until.format("%Y-%m-%d %H:%M")
+                ),
+            ));
+        }
+        //场馆黑名单内的用户不能预约该场馆的球场
+        if let Some(entry) = BlacklistOp::of(court_id, user_id, state).await? {
+            return Err(HandleErr::BadRequest(
+                ERR_BLACKLISTED,
+                match entry.expire_time {
+                    Some(until) => format!(
+                        "您已被该场馆限制预约, {}前不能预约",
+                        until.format("%Y-%m-%d %H:%M")
+                    ),
+                    None => "您已被该场馆限制预约".to_string(),
+                },
+            ));
+        }
+        let Some(rule) = Self::rule(court_id, state).await? else {
+            return Ok(());
+        };
+        let minutes = (end - start).num_minutes();
+        if let Some(min) = rule.min_minutes.filter(|e| minutes < *e as i64) {
+            return Err(HandleErr::BadRequest(
+                ERR_TOO_SHORT,
+                format!("单次预约不能少于{}分钟", min),
+            ));
+        }
+        if let Some(max) = rule.max_minutes.filter(|e| minutes > *e as i64) {
+            return Err(HandleErr::BadRequest(
+                ERR_TOO_LONG,
+                format!("单次预约不能超过{}分钟", max),
+            ));
+        }
+        //会员可以提前更多天预约
+        let extra_days = TierOp::benefit(&user, &state.db)
+            .await?
+            .map_or(0, |e| e.extra_advance_days);
+        if let Some(days) = rule
+            .max_advance_days
+            .map(|e| e + extra_days)
+            .filter(|e| start.date() > now.date() + chrono::Duration::days(*e as i64))
+        {
+            return Err(HandleErr::BadRequest(
+                ERR_TOO_EARLY,
+                format!("最多提前{}天预约", days),
+            ));
+        }
+        if let Some(max) = rule.max_active_orders {
+            let count = Orders::find()
+                .filter(
+                    db::orders::Column::CourtId
+                        .eq(court_id)
+                        .and(db::orders::Column::UserId.eq(user_id))
+                        .and(db::orders::Column::AptEnd.gt(now))
+                        .and(db::orders::Column::Status.is_not_in(state::RELEASED))
+                        .and(db::orders::Column::OrderId.ne(order_id.unwrap_or(Uuid::nil()))),
+                )
+                .count(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            if count >= max as u64 {
+                return Err(HandleErr::BadRequest(
+                    ERR_TOO_MANY,
+                    format!("该球场最多同时预约{}个订单", max),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+//取消费用, 距开始时间不足免费取消时间时按比例收取
+pub fn cancel_fee(
+    rule: Option<&db::court_booking_rules::Model>,
+    cost: Decimal,
+    apt_start: DateTime,
+    now: DateTime,
+) -> Decimal {
+    match rule {
+        Some(rule) => match rule.free_cancel_minutes {
+            Some(minutes) if (apt_start - now).num_minutes() < minutes as i64 => {
+                money::round(cost * rule.cancel_fee_rate)
+            }
+            _ => Decimal::ZERO,
+        },
+        None => Decimal::ZERO,
+    }
+}
+
+#[test]
+fn test_cancel_fee() {
+    let rule = db::court_booking_rules::Model {
+        court_id: Uuid::nil(),
+        min_minutes: None,
+        max_minutes: None,
+        max_advance_days: None,
+        max_active_orders: None,
+        free_cancel_minutes: Some(120),
+        cancel_fee_rate: Decimal::new(5, 1),
+    };
+    let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+        .unwrap()
+        .and_hms_opt(18, 0, 0)
+        .unwrap();
+    let hours = |h| start - chrono::Duration::hours(h);
+    let d = |v: i64| Decimal::from(v);
+    assert_eq!(cancel_fee(Some(&rule), d(100), start, hours(3)), d(0));
+    assert_eq!(cancel_fee(Some(&rule), d(100), start, hours(1)), d(50));
+    assert_eq!(cancel_fee(None, d(100), start, hours(1)), d(0));
+}
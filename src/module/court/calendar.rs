@@ -0,0 +1,81 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{self, prelude::CourtBlocks},
+};
+use sea_orm::prelude::DateTime;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockAdd {
+    pub court_id: Uuid,
+    pub block_start: DateTime,
+    pub block_end: DateTime,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockDel {
+    pub block_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CalendarQuery {
+    pub from: DateTime,
+    pub to: DateTime,
+}
+
+pub struct CalendarOp;
+impl CalendarOp {
+    //时间段内的屏蔽记录
+    pub async fn blocks<T>(
+        court_id: Uuid,
+        start: DateTime,
+        end: DateTime,
+        state: &AppState,
+    ) -> Result<Vec<db::court_blocks::Model>, HandleErr<T>> {
+        CourtBlocks::find()
+            .filter(
+                db::court_blocks::Column::CourtId
+                    .eq(court_id)
+                    .and(db::court_blocks::Column::BlockStart.lt(end))
+                    .and(db::court_blocks::Column::BlockEnd.gt(start)),
+            )
+            .order_by_asc(db::court_blocks::Column::BlockStart)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //时间段是否与屏蔽记录重叠
+    pub async fn is_blocked<T>(
+        court_id: Uuid,
+        start: DateTime,
+        end: DateTime,
+        state: &AppState,
+    ) -> Result<bool, HandleErr<T>> {
+        let count = CourtBlocks::find()
+            .filter(
+                db::court_blocks::Column::CourtId
+                    .eq(court_id)
+                    .and(db::court_blocks::Column::BlockStart.lt(end))
+                    .and(db::court_blocks::Column::BlockEnd.gt(start)),
+            )
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(count > 0)
+    }
+}
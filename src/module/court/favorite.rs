@@ -0,0 +1,153 @@
+use super::{CourtOp, CourtUserSchema};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self,
+        prelude::{CourtFavorites, Courts},
+        sea_orm_active_enums::CourtStatus,
+    },
+};
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::NotSet, ColumnTrait, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tracing::error;
+use uuid::Uuid;
+
+//每个用户最多收藏的球场数
+const MAX_FAVORITES: u64 = 200;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FavoriteSave {
+    pub court_id: Uuid,
+}
+
+pub struct FavoriteOp;
+impl FavoriteOp {
+    //收藏球场, 重复收藏视为成功
+    pub async fn add<T: From<String>>(
+        user_id: Uuid,
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        Courts::find_by_id(court_id)
+            .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string().into()))?;
+        let count = CourtFavorites::find()
+            .filter(db::court_favorites::Column::UserId.eq(user_id))
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if count >= MAX_FAVORITES {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("最多收藏{}个球场", MAX_FAVORITES).into(),
+            ));
+        }
+        CourtFavorites::insert(db::court_favorites::ActiveModel {
+            user_id: Set(user_id),
+            court_id: Set(court_id),
+            create_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::columns([
+                db::court_favorites::Column::UserId,
+                db::court_favorites::Column::CourtId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //取消收藏, 未收藏时视为成功
+    pub async fn del<T>(
+        user_id: Uuid,
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        CourtFavorites::delete_by_id((user_id, court_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //收藏的球场, 最近收藏的在前, 关闭的球场不展示
+    pub async fn list<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<CourtUserSchema>, HandleErr<T>> {
+        let mut courts: Vec<_> = CourtFavorites::find()
+            .filter(db::court_favorites::Column::UserId.eq(user_id))
+            .find_also_related(Courts)
+            .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+            .order_by_desc(db::court_favorites::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .filter_map(|(_, court)| court)
+            .map(|e| CourtUserSchema {
+                admin_id: None,
+                favorite: Some(true),
+                ..e.into()
+            })
+            .collect();
+        CourtOp::fill(&mut courts, state).await?;
+        Ok(courts)
+    }
+
+    //用户收藏的全部球场id
+    pub async fn ids<T>(user_id: Uuid, state: &AppState) -> Result<HashSet<Uuid>, HandleErr<T>> {
+        let ids: Vec<Uuid> = CourtFavorites::find()
+            .select_only()
+            .column(db::court_favorites::Column::CourtId)
+            .filter(db::court_favorites::Column::UserId.eq(user_id))
+            .into_tuple()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(ids.into_iter().collect())
+    }
+
+    //标记列表中已收藏的球场
+    pub fn mark(courts: &mut [CourtUserSchema], favorites: &HashSet<Uuid>) {
+        courts.iter_mut().for_each(|e| {
+            e.favorite = e.court_id.map(|id| favorites.contains(&id));
+        });
+    }
+}
@@ -0,0 +1,576 @@
+use super::db::{
+    self,
+    prelude::{CourtAudit, CourtImages, Courts, Users},
+    sea_orm_active_enums::{CourtStatus, SportType},
+};
+use crate::{appstate::AppState, error::HandleErr, module::money};
+use sea_orm::prelude::{Decimal, Time};
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, ConnectionTrait, DbBackend, DbErr, EntityTrait, FromQueryResult, QueryFilter,
+    QueryOrder, QueryResult, Statement, TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::error;
+use uuid::Uuid;
+pub mod addon;
+pub mod availability;
+pub mod booking_rule;
+pub mod calendar;
+pub mod favorite;
+pub mod open_hours;
+pub mod poster;
+pub mod review;
+pub mod search;
+pub mod tag;
+use tag::TagOp;
+//update/insert
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct CourtSave {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub court_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_id: Option<Uuid>,
+    //所属场馆
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venue_id: Option<Uuid>,
+    pub court_name: String,
+    pub location: String,
+    pub label: String,
+    pub price_per_hour: Decimal,
+    pub open_time: Time,
+    pub close_time: Time,
+    #[serde(default)]
+    pub sport_type: SportType,
+    //可容纳人数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<i32>,
+    //押金, 随订单冻结, 签到后退还, 未签到扣除
+    #[serde(default)]
+    pub deposit: Decimal,
+    //经纬度, 需同时设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    //评价平均分与评价数, 仅用于返回
+    #[serde(default, skip_deserializing)]
+    pub rating: f64,
+    #[serde(default, skip_deserializing)]
+    pub rating_count: i32,
+    //球场状态, 仅用于返回, 通过 /court/status 修改
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub status: Option<CourtStatus>,
+    //球场标签, 仅用于返回, 通过 /court/tag/bind 修改
+    #[serde(default, skip_deserializing)]
+    pub tags: Vec<String>,
+    //球场图片, 仅用于返回
+    #[serde(default, skip_deserializing)]
+    pub images: Vec<String>,
+    //是否已收藏, 仅用于返回, 登录用户查询时填充
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub favorite: Option<bool>,
+}
+
+impl From<db::courts::Model> for CourtSave {
+    fn from(e: db::courts::Model) -> Self {
+        Self {
+            court_id: Some(e.court_id),
+            admin_id: Some(e.admin_id),
+            venue_id: e.venue_id,
+            court_name: e.court_name,
+            location: e.location,
+            label: e.label,
+            price_per_hour: e.price_per_hour,
+            open_time: e.open_time,
+            close_time: e.close_time,
+            sport_type: e.sport_type,
+            capacity: e.capacity,
+            deposit: e.deposit,
+            latitude: e.latitude,
+            longitude: e.longitude,
+            rating: e.rating,
+            rating_count: e.rating_count,
+            status: Some(e.status),
+            tags: vec![],
+            images: vec![],
+            favorite: None,
+        }
+    }
+}
+
+pub type CourtAdd = CourtSave;
+pub type CourtUpdate = CourtSave;
+pub type CourtAdminSchema = CourtSave;
+pub type CourtUserSchema = CourtSave;
+
+//列表版本, 用于生成ETag
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct CourtVersion {
+    pub count: i64,
+    pub update_time: Option<sea_orm::prelude::DateTime>,
+}
+
+//球场统计, 近30天
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct CourtStats {
+    //未开始的订单数
+    pub upcoming_orders: i64,
+    //近30天已预约小时数
+    pub booked_hours: f64,
+}
+
+//用户球场列表筛选
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtFilter {
+    pub tag: Option<String>,
+    pub sport_type: Option<SportType>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtNearby {
+    pub lat: f64,
+    pub lng: f64,
+    //搜索半径, 单位km, 默认10km, 最大50km
+    pub radius: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CourtNearbySchema {
+    #[serde(flatten)]
+    pub court: CourtUserSchema,
+    //距离, 单位km
+    pub distance: f64,
+}
+
+//附带距离的查询结果
+pub struct CourtDistance {
+    pub court: db::courts::Model,
+    pub distance: f64,
+}
+
+impl FromQueryResult for CourtDistance {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            court: db::courts::Model::from_query_result(res, pre)?,
+            distance: res.try_get(pre, "distance")?,
+        })
+    }
+}
+
+//批量修改中的一项, 未设置的字段不修改
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtPatch {
+    pub court_id: Uuid,
+    pub label: Option<String>,
+    pub price_per_hour: Option<Decimal>,
+    pub open_time: Option<Time>,
+    pub close_time: Option<Time>,
+    pub status: Option<CourtStatus>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtBatchUpdate {
+    pub items: Vec<CourtPatch>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtBatchDel {
+    pub court_ids: Vec<Uuid>,
+}
+
+//批量操作单项结果
+#[derive(Debug, Serialize, Clone)]
+pub struct CourtBatchResult {
+    pub court_id: Uuid,
+    pub success: bool,
+    pub msg: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtTransfer {
+    pub court_id: Uuid,
+    //新管理员
+    pub admin_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtClone {
+    pub court_id: Uuid,
+    //新球场名
+    pub court_name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtStatusSet {
+    pub court_id: Uuid,
+    pub status: CourtStatus,
+}
+
+//停用球场并取消未开始的订单
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtClose {
+    pub court_id: Uuid,
+    //取消原因, 同时用于通知内容
+    pub reason: Option<String>,
+}
+
+//停用球场时受影响的订单
+#[derive(Debug, Serialize, Clone)]
+pub struct CourtCloseAffected {
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub phone: String,
+    pub apt_start: sea_orm::prelude::DateTime,
+    //退款金额, 未支付的订单为0
+    pub refund: Decimal,
+    //通知是否发送成功
+    pub notified: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CourtDel {
+    pub court_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtSearch {
+    //球场名, 模糊匹配
+    pub name: Option<String>,
+    pub label: Option<String>,
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+    pub sport_type: Option<SportType>,
+}
+
+//CSV导入的一行, 表头与字段名一致
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtImportRow {
+    pub court_name: String,
+    pub location: String,
+    pub label: String,
+    pub price_per_hour: String,
+    pub open_time: String,
+    pub close_time: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CourtImportErr {
+    //数据行号, 不含表头, 从1开始
+    pub row: usize,
+    pub msg: String,
+}
+
+impl CourtImportRow {
+    pub fn validate(self) -> Result<CourtAdd, String> {
+        let court_name = self.court_name.trim().to_string();
+        if court_name.is_empty() {
+            return Err("球场名不能为空".to_string());
+        }
+        let price_per_hour = self
+            .price_per_hour
+            .trim()
+            .parse::<Decimal>()
+            .ok()
+            .filter(|e| *e > Decimal::ZERO)
+            .ok_or(format!("价格格式错误: {}", self.price_per_hour))?;
+        let open_time = self
+            .open_time
+            .trim()
+            .parse::<Time>()
+            .map_err(|_| format!("开放时间格式错误: {}", self.open_time))?;
+        let close_time = self
+            .close_time
+            .trim()
+            .parse::<Time>()
+            .map_err(|_| format!("关闭时间格式错误: {}", self.close_time))?;
+        if open_time >= close_time {
+            return Err("开放时间须早于关闭时间".to_string());
+        }
+        Ok(CourtAdd {
+            court_id: None,
+            admin_id: None,
+            court_name,
+            location: self.location.trim().to_string(),
+            label: self.label.trim().to_string(),
+            price_per_hour,
+            open_time,
+            close_time,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtImageDel {
+    pub image_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CourtImageSort {
+    pub court_id: Uuid,
+    //按展示顺序排列的图片id
+    pub image_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CourtAuditSchema {
+    #[serde(flatten)]
+    pub audit: db::court_audit::Model,
+    pub operator_name: String,
+}
+
+pub struct CourtOp;
+impl CourtOp {
+    //保存球场, 并将变更字段记录到court_audit
+    pub async fn save<T: From<&'static str>>(
+        schema: CourtSave,
+        operator: Uuid,
+        state: &AppState,
+    ) -> Result<db::courts::Model, HandleErr<T>> {
+        match (schema.latitude, schema.longitude) {
+            (None, None) => {}
+            (Some(lat), Some(lng))
+                if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng) => {}
+            _ => return Err(HandleErr::BadRequest(-1, "经纬度无效".into())),
+        }
+        if schema.capacity.is_some_and(|e| e <= 0) {
+            return Err(HandleErr::BadRequest(-1, "容纳人数须大于0".into()));
+        }
+        if schema.deposit < Decimal::ZERO {
+            return Err(HandleErr::BadRequest(-1, "押金无效".into()));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let old = match schema.court_id {
+            Some(court_id) => Courts::find_by_id(court_id)
+                .one(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?,
+            None => None,
+        };
+        let court = db::courts::ActiveModel {
+            court_id: schema.court_id.map(Set).unwrap_or(NotSet),
+            admin_id: schema.admin_id.map(Set).unwrap_or(NotSet),
+            venue_id: Set(schema.venue_id),
+            court_name: Set(schema.court_name),
+            location: Set(schema.location),
+            label: Set(schema.label),
+            price_per_hour: Set(money::round(schema.price_per_hour)),
+            open_time: Set(schema.open_time),
+            close_time: Set(schema.close_time),
+            sport_type: Set(schema.sport_type),
+            capacity: Set(schema.capacity),
+            deposit: Set(money::round(schema.deposit)),
+            latitude: Set(schema.latitude),
+            longitude: Set(schema.longitude),
+            rating: NotSet,
+            rating_count: NotSet,
+            status: NotSet,
+            update_time: NotSet,
+        }
+        .save(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+
+        let action = if old.is_some() { "update" } else { "create" };
+        let old = old.map_or(serde_json::Value::Null, |e| json!(e));
+        Self::audit(court.court_id, operator, action, &old, &json!(court), &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(court)
+    }
+
+    //记录球场变更的字段, 没有变化时不记录
+    //old为null时视为新建, new为null时视为删除
+    pub async fn audit<T, C: ConnectionTrait>(
+        court_id: Uuid,
+        operator: Uuid,
+        action: &str,
+        old: &serde_json::Value,
+        new: &serde_json::Value,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let diff = audit_diff(old, new);
+        if diff.as_object().is_none_or(|e| e.is_empty()) {
+            return Ok(());
+        }
+        db::court_audit::ActiveModel {
+            audit_id: NotSet,
+            court_id: Set(court_id),
+            operator_id: Set(operator),
+            action: Set(action.to_string()),
+            diff: Set(diff),
+            create_time: NotSet,
+        }
+        .insert(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //球场修改记录, 按时间倒序
+    pub async fn history<T>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<CourtAuditSchema>, HandleErr<T>> {
+        Ok(CourtAudit::find()
+            .filter(db::court_audit::Column::CourtId.eq(court_id))
+            .order_by_desc(db::court_audit::Column::CreateTime)
+            .find_also_related(Users)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|(audit, user)| CourtAuditSchema {
+                operator_name: user.map(|e| e.user_name).unwrap_or_default(),
+                audit,
+            })
+            .collect())
+    }
+
+    //查询管理员名下的球场, 不存在或不属于该管理员时报错
+    pub async fn owned<T: From<&'static str>>(
+        court_id: Uuid,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<db::courts::Model, HandleErr<T>> {
+        Courts::find()
+            .filter(
+                db::courts::Column::CourtId
+                    .eq(court_id)
+                    .and(db::courts::Column::AdminId.eq(admin_id)),
+            )
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "球场不存在".into()))
+    }
+
+    //近30天统计, 一次聚合查询完成
+    pub async fn stats<T>(court_id: Uuid, state: &AppState) -> Result<CourtStats, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let since = now - chrono::Duration::days(30);
+        CourtStats::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"select count(*) filter (where apt_start > $1) as upcoming_orders,
+                coalesce(sum(extract(epoch from least(apt_end, $1) - greatest(apt_start, $2)) / 3600)
+                    filter (where apt_end > $2 and apt_start < $1), 0)::float8 as booked_hours
+               from orders where court_id = $3 and status not in ('cancelled', 'refunded')"#,
+            [now.into(), since.into(), court_id.into()],
+        ))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or_else(|| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> 统计查询无结果", id);
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //近30天的营业小时数, 按每天的营业时间累加
+    pub async fn open_hours_30d<T>(
+        court: &db::courts::Model,
+        state: &AppState,
+    ) -> Result<f64, HandleErr<T>> {
+        let today = chrono::Utc::now().date_naive();
+        let week = open_hours::OpenHoursOp::week(court.court_id, state).await?;
+        Ok((1..=30)
+            .filter_map(|i| {
+                open_hours::OpenHoursOp::window_in(court, &week, today - chrono::Duration::days(i))
+            })
+            .map(|(open, close)| (close - open).num_minutes() as f64 / 60.0)
+            .sum())
+    }
+
+    //按球场分组查询图片url, 已按展示顺序排序
+    pub async fn images<T>(
+        court_ids: Vec<Uuid>,
+        state: &AppState,
+    ) -> Result<HashMap<Uuid, Vec<String>>, HandleErr<T>> {
+        let mut map: HashMap<Uuid, Vec<String>> = HashMap::new();
+        CourtImages::find()
+            .filter(db::court_images::Column::CourtId.is_in(court_ids))
+            .order_by_asc(db::court_images::Column::SortOrder)
+            .order_by_asc(db::court_images::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .for_each(|e| map.entry(e.court_id).or_default().push(e.url));
+        Ok(map)
+    }
+
+    //为球场列表填充图片与标签
+    pub async fn fill<T>(courts: &mut [CourtSave], state: &AppState) -> Result<(), HandleErr<T>> {
+        let court_ids: Vec<_> = courts.iter().filter_map(|e| e.court_id).collect();
+        let mut images = Self::images(court_ids.clone(), state).await?;
+        let mut tags = TagOp::names(court_ids, state).await?;
+        courts.iter_mut().for_each(|e| {
+            if let Some(id) = e.court_id {
+                e.images = images.remove(&id).unwrap_or_default();
+                e.tags = tags.remove(&id).unwrap_or_default();
+            }
+        });
+        Ok(())
+    }
+}
+
+//对比新旧两个json对象, 返回变化的字段; old或new为null时视为新建或删除, 记录全部字段
+fn audit_diff(old: &serde_json::Value, new: &serde_json::Value) -> serde_json::Value {
+    let mut diff = serde_json::Map::new();
+    let fields = new.as_object().or(old.as_object());
+    for key in fields.into_iter().flat_map(|e| e.keys()) {
+        let before = old.get(key).unwrap_or(&serde_json::Value::Null);
+        let after = new.get(key).unwrap_or(&serde_json::Value::Null);
+        if before != after || old.is_null() || new.is_null() {
+            diff.insert(key.clone(), json!({"old":before,"new":after}));
+        }
+    }
+    serde_json::Value::Object(diff)
+}
@@ -0,0 +1,79 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{self, prelude::CourtOpenHours},
+};
+use chrono::Datelike;
+use sea_orm::prelude::{Date, Time};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OpenHoursSet {
+    pub court_id: Uuid,
+    //1(周一)~7(周日)
+    pub weekday: i16,
+    pub open_time: Time,
+    pub close_time: Time,
+    #[serde(default)]
+    pub is_closed: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OpenHoursDel {
+    pub court_id: Uuid,
+    pub weekday: i16,
+}
+
+pub struct OpenHoursOp;
+impl OpenHoursOp {
+    //某天的营业时间, 未单独设置时使用球场默认时间, None表示当天闭馆
+    pub async fn window<T>(
+        court: &db::courts::Model,
+        date: Date,
+        state: &AppState,
+    ) -> Result<Option<(Time, Time)>, HandleErr<T>> {
+        let weekday = date.weekday().number_from_monday() as i16;
+        let hours = CourtOpenHours::find_by_id((court.court_id, weekday))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(Self::window_in(court, &Vec::from_iter(hours), date))
+    }
+
+    //球场一周的营业时间设置
+    pub async fn week<T>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<db::court_open_hours::Model>, HandleErr<T>> {
+        CourtOpenHours::find()
+            .filter(db::court_open_hours::Column::CourtId.eq(court_id))
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //根据已查询的一周设置计算某天的营业时间
+    pub fn window_in(
+        court: &db::courts::Model,
+        week: &[db::court_open_hours::Model],
+        date: Date,
+    ) -> Option<(Time, Time)> {
+        let weekday = date.weekday().number_from_monday() as i16;
+        match week.iter().find(|e| e.weekday == weekday) {
+            Some(e) if e.is_closed => None,
+            Some(e) => Some((e.open_time, e.close_time)),
+            None => Some((court.open_time, court.close_time)),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self,
+        prelude::{CourtImages, CourtPosters, Courts},
+        sea_orm_active_enums::CourtStatus,
+    },
+    utils::qrcode,
+};
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::NotSet, ColumnTrait, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use std::io::Cursor;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//小程序中的球场详情页, 扫码后由scene取得球场id
+const COURT_PAGE: &str = "pages/court/detail";
+const WIDTH: u32 = 750;
+const PHOTO_HEIGHT: u32 = 560;
+const FOOTER_HEIGHT: u32 = 260;
+const QR_SIZE: u32 = 220;
+//没有球场图片时的背景色
+const BACKGROUND: Rgb<u8> = Rgb([46, 125, 90]);
+
+pub struct PosterOp;
+impl PosterOp {
+    //球场分享海报的地址, 已生成且封面图未变化时直接返回
+    pub async fn court<T: From<String>>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<String, HandleErr<T>> {
+        Courts::find_by_id(court_id)
+            .filter(db::courts::Column::Status.ne(CourtStatus::Closed))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string().into()))?;
+        //与球场详情的图片顺序一致, 第一张为封面
+        let cover = CourtImages::find()
+            .filter(db::court_images::Column::CourtId.eq(court_id))
+            .order_by_asc(db::court_images::Column::SortOrder)
+            .order_by_asc(db::court_images::Column::CreateTime)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let cover_key = cover.map(|e| e.storage_key).unwrap_or_default();
+        let cached = CourtPosters::find_by_id(court_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if let Some(cached) = &cached {
+            if cached.cover_key == cover_key {
+                return Ok(cached.url.clone());
+            }
+        }
+        //同一球场同时只生成一次, 其余请求稍后重试时读取已生成的海报
+        let Some(_guard) = state.posters.enter(&court_id.to_string()) else {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "海报生成中, 请稍后再试".to_string().into(),
+            ));
+        };
+
+        let photo = if cover_key.is_empty() {
+            None
+        } else {
+            state
+                .storage
+                .get(&cover_key)
+                .await
+                .inspect_err(|err| warn!("读取球场({})封面图失败: {}", court_id, err))
+                .ok()
+        };
+        let qr = Self::qrcode(court_id, state).await?;
+        //图片解码与编码较耗时, 放到阻塞线程中执行
+        let poster = tokio::task::spawn_blocking(move || compose(photo.as_deref(), &qr))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        //同一封面图的海报使用固定的key
+        let cover_name = cover_key
+            .rsplit('/')
+            .next()
+            .and_then(|e| e.split('.').next())
+            .filter(|e| !e.is_empty())
+            .unwrap_or("default");
+        let key = format!("poster/{}/{}.jpg", court_id, cover_name);
+        let url = state
+            .storage
+            .put(&key, "image/jpeg", poster)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        CourtPosters::insert(db::court_posters::ActiveModel {
+            court_id: Set(court_id),
+            url: Set(url.clone()),
+            storage_key: Set(key.clone()),
+            cover_key: Set(cover_key),
+            create_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::column(db::court_posters::Column::CourtId)
+                .update_columns([
+                    db::court_posters::Column::Url,
+                    db::court_posters::Column::StorageKey,
+                    db::court_posters::Column::CoverKey,
+                    db::court_posters::Column::CreateTime,
+                ])
+                .to_owned(),
+        )
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        //封面图更换后删除旧海报
+        if let Some(cached) = cached.filter(|e| e.storage_key != key) {
+            if let Err(err) = state.storage.delete(&cached.storage_key).await {
+                warn!("删除旧海报({})失败: {}", cached.storage_key, err);
+            }
+        }
+        info!("球场({})分享海报已生成", court_id);
+        Ok(url)
+    }
+
+    //配置小程序时使用小程序码, 否则使用内容为页面路径的普通二维码
+    async fn qrcode<T>(court_id: Uuid, state: &AppState) -> Result<Vec<u8>, HandleErr<T>> {
+        let scene = court_id.simple().to_string();
+        let qr = match &state.wechat {
+            Some(wechat) => wechat.wxacode(&scene, COURT_PAGE).await,
+            None => qrcode::png(&format!("{}?scene={}", COURT_PAGE, scene)),
+        };
+        qr.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+}
+
+//上方为球场封面图, 下方居中放置二维码, 输出JPEG
+fn compose(photo: Option<&[u8]>, qr: &[u8]) -> crate::App::Result<Vec<u8>> {
+    let mut canvas = RgbImage::from_pixel(WIDTH, PHOTO_HEIGHT + FOOTER_HEIGHT, Rgb([255; 3]));
+    let photo = photo.and_then(|e| {
+        image::load_from_memory(e)
+            .inspect_err(|err| warn!("封面图解码失败: {}", err))
+            .ok()
+    });
+    match photo {
+        Some(photo) => {
+            let photo = photo.resize_to_fill(WIDTH, PHOTO_HEIGHT, imageops::FilterType::Triangle);
+            imageops::replace(&mut canvas, &photo.to_rgb8(), 0, 0);
+        }
+        None => {
+            let background = RgbImage::from_pixel(WIDTH, PHOTO_HEIGHT, BACKGROUND);
+            imageops::replace(&mut canvas, &background, 0, 0);
+        }
+    }
+    let qr = image::load_from_memory(qr)?.resize(QR_SIZE, QR_SIZE, imageops::FilterType::Nearest);
+    let (x, y) = (
+        (WIDTH - qr.width()) / 2,
+        PHOTO_HEIGHT + (FOOTER_HEIGHT - qr.height()) / 2,
+    );
+    imageops::replace(&mut canvas, &qr.to_rgb8(), x as i64, y as i64);
+    let mut buf = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(canvas).write_to(&mut buf, image::ImageFormat::Jpeg)?;
+    Ok(buf.into_inner())
+}
+
+#[test]
+fn test_compose() {
+    let qr = qrcode::png("pages/court/detail?scene=test").unwrap();
+    let poster = image::load_from_memory(&compose(None, &qr).unwrap()).unwrap();
+    assert_eq!(poster.width(), WIDTH);
+    assert_eq!(poster.height(), PHOTO_HEIGHT + FOOTER_HEIGHT);
+    //无图片时使用背景色, 下方为白底, JPEG有损压缩后颜色略有偏差
+    let poster = poster.to_rgb8();
+    let near = |a: &Rgb<u8>, b: &Rgb<u8>| a.0.iter().zip(b.0).all(|(x, y)| x.abs_diff(y) <= 8);
+    assert!(near(poster.get_pixel(10, 10), &BACKGROUND));
+    assert!(near(
+        poster.get_pixel(10, PHOTO_HEIGHT + 10),
+        &Rgb([255; 3])
+    ));
+}
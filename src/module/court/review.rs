@@ -0,0 +1,307 @@
+use crate::{
+    appstate::AppState,
+    error::{unique_err, HandleErr},
+    module::db::{
+        self,
+        prelude::{CourtReviews, Courts},
+        sea_orm_active_enums::OrderState,
+    },
+};
+use sea_orm::prelude::DateTime;
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, JoinType, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, Statement, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReviewCreate {
+    pub order_id: Uuid,
+    //1~5分
+    pub rating: i32,
+    #[serde(default)]
+    pub comment: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReviewHide {
+    pub review_id: Uuid,
+    pub hidden: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReviewReport {
+    pub review_id: Uuid,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, sea_orm::FromQueryResult)]
+struct ReviewRow {
+    review_id: Uuid,
+    user_name: String,
+    nickname: Option<String>,
+    rating: i32,
+    comment: String,
+    create_time: DateTime,
+}
+
+//公开展示的评价, 不暴露登录用户名
+#[derive(Debug, Serialize, Clone)]
+pub struct ReviewSchema {
+    pub review_id: Uuid,
+    pub nickname: String,
+    pub rating: i32,
+    pub comment: String,
+    pub create_time: DateTime,
+}
+
+impl From<ReviewRow> for ReviewSchema {
+    fn from(row: ReviewRow) -> Self {
+        let nickname = match row.nickname {
+            Some(nickname) if !nickname.trim().is_empty() => nickname,
+            _ => mask(&row.user_name),
+        };
+        ReviewSchema {
+            review_id: row.review_id,
+            nickname,
+            rating: row.rating,
+            comment: row.comment,
+            create_time: row.create_time,
+        }
+    }
+}
+
+//未设置昵称时只显示用户名首尾字符
+fn mask(user_name: &str) -> String {
+    let chars: Vec<char> = user_name.chars().collect();
+    match chars.len() {
+        0 => "匿名用户".to_string(),
+        1 | 2 => format!("{}*", chars[0]),
+        n => format!("{}***{}", chars[0], chars[n - 1]),
+    }
+}
+
+pub struct ReviewOp;
+impl ReviewOp {
+    //评价已完成的订单, 每个订单只能评价一次
+    pub async fn create<T: From<String> + From<&'static str>>(
+        order: &db::orders::Model,
+        rating: i32,
+        comment: String,
+        state: &AppState,
+    ) -> Result<db::court_reviews::Model, HandleErr<T>> {
+        if order.status != OrderState::Completed {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "订单完成后才能评价".to_string().into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let reviewed = CourtReviews::find()
+            .filter(db::court_reviews::Column::OrderId.eq(order.order_id))
+            .count(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if reviewed > 0 {
+            return Err(HandleErr::BadRequest(-1, "订单已评价".to_string().into()));
+        }
+        let review = db::court_reviews::ActiveModel {
+            review_id: NotSet,
+            order_id: Set(order.order_id),
+            court_id: Set(order.court_id),
+            user_id: Set(order.user_id),
+            rating: Set(rating),
+            comment: Set(comment),
+            hidden: NotSet,
+            reported: NotSet,
+            report_reason: NotSet,
+            create_time: NotSet,
+        }
+        .insert(&txn)
+        .await
+        //并发重复提交由order_id唯一约束拦截
+        .map_err(|err| unique_err(err, "订单已评价"))?;
+        Self::refresh(order.court_id, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(review)
+    }
+
+    //重新计算球场的平均分与评价数
+    async fn refresh<T, C: ConnectionTrait>(court_id: Uuid, db: &C) -> Result<(), HandleErr<T>> {
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"update courts set (rating, rating_count) = (
+                select coalesce(avg(rating), 0)::float8, count(*)::int4
+                from court_reviews where court_id = $1 and not hidden)
+               where court_id = $1"#,
+            [court_id.into()],
+        ))
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //球场公开的评价, 新的在前
+    pub async fn list<T>(
+        court_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(u64, Vec<ReviewSchema>), HandleErr<T>> {
+        let paginator = CourtReviews::find()
+            .select_only()
+            .column(db::court_reviews::Column::ReviewId)
+            .column(db::court_reviews::Column::Rating)
+            .column(db::court_reviews::Column::Comment)
+            .column(db::court_reviews::Column::CreateTime)
+            .column(db::users::Column::UserName)
+            .column(db::users::Column::Nickname)
+            .join(
+                JoinType::InnerJoin,
+                db::court_reviews::Relation::Users.def(),
+            )
+            .filter(
+                db::court_reviews::Column::CourtId
+                    .eq(court_id)
+                    .and(db::court_reviews::Column::Hidden.eq(false)),
+            )
+            .order_by_desc(db::court_reviews::Column::CreateTime)
+            .into_model::<ReviewRow>()
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let reviews = paginator.fetch_page(page - 1).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok((total, reviews.into_iter().map(Into::into).collect()))
+    }
+
+    //管理员名下球场的全部评价, 包括已隐藏的
+    pub async fn of_court<T>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<db::court_reviews::Model>, HandleErr<T>> {
+        CourtReviews::find()
+            .filter(db::court_reviews::Column::CourtId.eq(court_id))
+            .order_by_desc(db::court_reviews::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //管理员名下球场的评价
+    async fn owned<T: From<&'static str>, C: ConnectionTrait>(
+        review_id: Uuid,
+        admin_id: Uuid,
+        db: &C,
+    ) -> Result<db::court_reviews::Model, HandleErr<T>> {
+        let (review, court) = CourtReviews::find_by_id(review_id)
+            .find_also_related(Courts)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "评价不存在".into()))?;
+        if court.map(|e| e.admin_id) != Some(admin_id) {
+            return Err(HandleErr::BadRequest(-1, "评价不存在".into()));
+        }
+        Ok(review)
+    }
+
+    //隐藏或恢复评价, 同时更新球场评分
+    pub async fn hide<T: From<&'static str>>(
+        schema: ReviewHide,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<db::court_reviews::Model, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let review = Self::owned(schema.review_id, admin_id, &txn).await?;
+        let review = db::court_reviews::ActiveModel {
+            review_id: Set(review.review_id),
+            hidden: Set(schema.hidden),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::refresh(review.court_id, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(review)
+    }
+
+    //举报不当评价, 交由平台处理
+    pub async fn report<T: From<&'static str>>(
+        schema: ReviewReport,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<db::court_reviews::Model, HandleErr<T>> {
+        let review = Self::owned(schema.review_id, admin_id, &state.db).await?;
+        if review.reported {
+            return Err(HandleErr::BadRequest(-1, "评价已举报".into()));
+        }
+        db::court_reviews::ActiveModel {
+            review_id: Set(review.review_id),
+            reported: Set(true),
+            report_reason: Set(Some(schema.reason)),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+}
+
+#[test]
+fn mask_test() {
+    assert_eq!(mask("zhangsan"), "z***n");
+    assert_eq!(mask("ab"), "a*");
+    assert_eq!(mask(""), "匿名用户");
+}
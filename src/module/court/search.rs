@@ -0,0 +1,256 @@
+use super::{favorite::FavoriteOp, CourtOp, CourtUserSchema};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        courts,
+        prelude::{Courts, SearchHistory},
+        sea_orm_active_enums::CourtStatus,
+        search_history,
+    },
+};
+use sea_orm::sea_query::{extension::postgres::PgExpr, Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, EntityTrait, FromQueryResult, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+const KEYWORD_LEN: usize = 50;
+//搜索结果最多返回的球场数
+const MAX_RESULTS: u64 = 50;
+//最近搜索与联想词的条数
+const RECENT: u64 = 10;
+const SUGGESTIONS: usize = 10;
+//热门搜索的统计天数
+const POPULAR_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Court,
+    Keyword,
+}
+
+//联想词, 球场名称可直接跳转到球场详情
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub court_id: Option<Uuid>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CourtName {
+    court_id: Uuid,
+    court_name: String,
+}
+
+pub struct SearchOp;
+impl SearchOp {
+    //按名称搜索球场并记录搜索词, 关闭的球场不展示
+    pub async fn courts<T: From<&'static str>>(
+        user_id: Uuid,
+        query: SearchQuery,
+        state: &AppState,
+    ) -> Result<Vec<CourtUserSchema>, HandleErr<T>> {
+        let keyword = normalize(&query.q).ok_or(HandleErr::BadRequest(
+            -1,
+            "搜索词不能为空且不超过50字".into(),
+        ))?;
+        let mut courts: Vec<_> = Courts::find()
+            .filter(courts::Column::Status.ne(CourtStatus::Closed))
+            .filter(Expr::col(courts::Column::CourtName).ilike(format!("%{}%", escape(&keyword))))
+            .order_by_asc(courts::Column::CourtName)
+            .limit(MAX_RESULTS)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| CourtUserSchema {
+                admin_id: None,
+                ..e.into()
+            })
+            .collect();
+        CourtOp::fill(&mut courts, state).await?;
+        FavoriteOp::mark(&mut courts, &FavoriteOp::ids(user_id, state).await?);
+        //同一用户重复搜索时累计次数并更新时间
+        SearchHistory::insert(search_history::ActiveModel {
+            user_id: Set(user_id),
+            keyword: Set(keyword),
+            times: Set(1),
+            search_time: Set(chrono::Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                search_history::Column::UserId,
+                search_history::Column::Keyword,
+            ])
+            .update_column(search_history::Column::SearchTime)
+            .value(
+                search_history::Column::Times,
+                Expr::col((search_history::Entity, search_history::Column::Times)).add(1),
+            )
+            .to_owned(),
+        )
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(courts)
+    }
+
+    //最近搜索, 最新的在前
+    pub async fn recent<T>(user_id: Uuid, state: &AppState) -> Result<Vec<String>, HandleErr<T>> {
+        SearchHistory::find()
+            .select_only()
+            .column(search_history::Column::Keyword)
+            .filter(search_history::Column::UserId.eq(user_id))
+            .order_by_desc(search_history::Column::SearchTime)
+            .limit(RECENT)
+            .into_tuple()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //清空搜索记录, 热门搜索中不再计入
+    pub async fn clear<T>(user_id: Uuid, state: &AppState) -> Result<(), HandleErr<T>> {
+        SearchHistory::delete_many()
+            .filter(search_history::Column::UserId.eq(user_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //联想词: 名称以输入开头的球场在前, 其后为近期搜索人数最多的相同前缀搜索词
+    pub async fn suggest<T>(q: &str, state: &AppState) -> Result<Vec<Suggestion>, HandleErr<T>> {
+        let Some(keyword) = normalize(q) else {
+            return Ok(vec![]);
+        };
+        let prefix = format!("{}%", escape(&keyword));
+        let courts = Courts::find()
+            .select_only()
+            .column(courts::Column::CourtId)
+            .column(courts::Column::CourtName)
+            .filter(courts::Column::Status.ne(CourtStatus::Closed))
+            .filter(Expr::col(courts::Column::CourtName).ilike(prefix.clone()))
+            .order_by_asc(courts::Column::CourtName)
+            .limit(SUGGESTIONS as u64)
+            .into_model::<CourtName>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let keywords: Vec<String> = SearchHistory::find()
+            .select_only()
+            .column(search_history::Column::Keyword)
+            .filter(Expr::col(search_history::Column::Keyword).ilike(prefix))
+            .filter(
+                search_history::Column::SearchTime
+                    .gt(chrono::Utc::now().naive_utc() - chrono::Duration::days(POPULAR_DAYS)),
+            )
+            .group_by(search_history::Column::Keyword)
+            .order_by_desc(search_history::Column::UserId.count())
+            .order_by_asc(search_history::Column::Keyword)
+            .limit(SUGGESTIONS as u64)
+            .into_tuple()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(merge(
+            courts.into_iter().map(|e| (e.court_id, e.court_name)),
+            keywords,
+        ))
+    }
+}
+
+//去掉首尾空白并合并连续空白, 为空或过长时返回None
+fn normalize(q: &str) -> Option<String> {
+    let keyword = q.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!keyword.is_empty() && keyword.chars().count() <= KEYWORD_LEN).then_some(keyword)
+}
+
+//转义LIKE中的通配符
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+//球场在前, 与球场名称相同的搜索词不重复展示
+fn merge(courts: impl Iterator<Item = (Uuid, String)>, keywords: Vec<String>) -> Vec<Suggestion> {
+    let mut suggestions: Vec<_> = courts
+        .map(|(court_id, text)| Suggestion {
+            kind: SuggestionKind::Court,
+            text,
+            court_id: Some(court_id),
+        })
+        .collect();
+    for keyword in keywords {
+        if !suggestions
+            .iter()
+            .any(|e| e.text.to_lowercase() == keyword.to_lowercase())
+        {
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::Keyword,
+                text: keyword,
+                court_id: None,
+            });
+        }
+    }
+    suggestions.truncate(SUGGESTIONS);
+    suggestions
+}
+
+#[test]
+fn test_search_keyword() {
+    assert_eq!(normalize("  东区  羽毛球 ").as_deref(), Some("东区 羽毛球"));
+    assert!(normalize("   ").is_none());
+    assert!(normalize(&"球".repeat(51)).is_none());
+    assert_eq!(escape(r"50%_a\b"), r"50\%\_a\\b");
+    let id = Uuid::new_v4();
+    let suggestions = merge(
+        [(id, "东区羽毛球馆".to_string())].into_iter(),
+        vec!["东区羽毛球馆".into(), "东区".into()],
+    );
+    assert_eq!(suggestions.len(), 2);
+    assert_eq!(suggestions[0].court_id, Some(id));
+    assert_eq!(suggestions[1].kind, SuggestionKind::Keyword);
+    assert_eq!(suggestions[1].text, "东区");
+}
@@ -0,0 +1,131 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self,
+        prelude::{CourtTagLinks, CourtTags},
+    },
+};
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, DatabaseTransaction, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::error;
+use uuid::Uuid;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagSave {
+    pub tag_id: Option<Uuid>,
+    pub tag_name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagDel {
+    pub tag_id: Uuid,
+}
+
+//将from中的标签合并到into
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagMerge {
+    pub from: Vec<Uuid>,
+    pub into: Uuid,
+}
+
+//设置球场的全部标签
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagBind {
+    pub court_id: Uuid,
+    pub tag_ids: Vec<Uuid>,
+}
+
+pub struct TagOp;
+impl TagOp {
+    //按球场分组查询标签名
+    pub async fn names<T>(
+        court_ids: Vec<Uuid>,
+        state: &AppState,
+    ) -> Result<HashMap<Uuid, Vec<String>>, HandleErr<T>> {
+        let mut map: HashMap<Uuid, Vec<String>> = HashMap::new();
+        CourtTagLinks::find()
+            .filter(db::court_tag_links::Column::CourtId.is_in(court_ids))
+            .find_also_related(CourtTags)
+            .order_by_asc(db::court_tags::Column::TagName)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .for_each(|(link, tag)| {
+                if let Some(tag) = tag {
+                    map.entry(link.court_id).or_default().push(tag.tag_name);
+                }
+            });
+        Ok(map)
+    }
+
+    //管理员名下的标签, 数量与ids不一致时报错
+    pub async fn owned<T: From<&'static str>>(
+        tag_ids: Vec<Uuid>,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<db::court_tags::Model>, HandleErr<T>> {
+        let count = tag_ids.len();
+        let tags = CourtTags::find()
+            .filter(
+                db::court_tags::Column::TagId
+                    .is_in(tag_ids)
+                    .and(db::court_tags::Column::AdminId.eq(admin_id)),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if tags.len() != count {
+            return Err(HandleErr::BadRequest(-1, "标签不存在".into()));
+        }
+        Ok(tags)
+    }
+
+    //为球场添加标签关联, 已存在的关联忽略
+    pub async fn link<T>(
+        court_ids: Vec<Uuid>,
+        tag_id: Uuid,
+        txn: &DatabaseTransaction,
+    ) -> Result<(), HandleErr<T>> {
+        if court_ids.is_empty() {
+            return Ok(());
+        }
+        CourtTagLinks::insert_many(court_ids.into_iter().map(|court_id| {
+            db::court_tag_links::ActiveModel {
+                court_id: Set(court_id),
+                tag_id: Set(tag_id),
+            }
+        }))
+        .on_conflict(
+            OnConflict::columns([
+                db::court_tag_links::Column::CourtId,
+                db::court_tag_links::Column::TagId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .do_nothing()
+        .exec(txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+}
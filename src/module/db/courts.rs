@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
 
+use super::sea_orm_active_enums::{CourtStatus, SportType};
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, serde::Serialize)]
@@ -8,17 +9,61 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub court_id: Uuid,
     pub admin_id: Uuid,
+    pub venue_id: Option<Uuid>,
     pub court_name: String,
     pub label: String,
     pub location: String,
-    #[sea_orm(column_type = "Double")]
-    pub price_per_hour: f64,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub price_per_hour: Decimal,
     pub open_time: Time,
     pub close_time: Time,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub latitude: Option<f64>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub longitude: Option<f64>,
+    pub status: CourtStatus,
+    pub sport_type: SportType,
+    pub capacity: Option<i32>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub deposit: Decimal,
+    #[sea_orm(column_type = "Double")]
+    pub rating: f64,
+    pub rating_count: i32,
+    pub update_time: DateTime,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::feedback::Entity")]
+    Feedback,
+    #[sea_orm(has_many = "super::court_posters::Entity")]
+    CourtPosters,
+    #[sea_orm(has_many = "super::court_favorites::Entity")]
+    CourtFavorites,
+    #[sea_orm(has_many = "super::promotion_courts::Entity")]
+    PromotionCourts,
+    #[sea_orm(has_many = "super::settlement_ledger::Entity")]
+    SettlementLedger,
+    #[sea_orm(has_many = "super::court_reviews::Entity")]
+    CourtReviews,
+    #[sea_orm(has_many = "super::slot_holds::Entity")]
+    SlotHolds,
+    #[sea_orm(has_many = "super::court_addons::Entity")]
+    CourtAddons,
+    #[sea_orm(has_one = "super::court_booking_rules::Entity")]
+    CourtBookingRules,
+    #[sea_orm(has_many = "super::court_price_overrides::Entity")]
+    CourtPriceOverrides,
+    #[sea_orm(has_many = "super::court_blocks::Entity")]
+    CourtBlocks,
+    #[sea_orm(has_many = "super::court_images::Entity")]
+    CourtImages,
+    #[sea_orm(has_many = "super::court_open_hours::Entity")]
+    CourtOpenHours,
+    #[sea_orm(has_many = "super::court_price_rules::Entity")]
+    CourtPriceRules,
+    #[sea_orm(has_many = "super::court_tag_links::Entity")]
+    CourtTagLinks,
     #[sea_orm(has_many = "super::orders::Entity")]
     Orders,
     #[sea_orm(
@@ -29,6 +74,47 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Users,
+    #[sea_orm(
+        belongs_to = "super::venues::Entity",
+        from = "Column::VenueId",
+        to = "super::venues::Column::VenueId",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Venues,
+}
+
+impl Related<super::court_images::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtImages.def()
+    }
+}
+
+impl Related<super::court_open_hours::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtOpenHours.def()
+    }
+}
+
+impl Related<super::court_price_rules::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtPriceRules.def()
+    }
+}
+
+impl Related<super::court_tag_links::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtTagLinks.def()
+    }
+}
+
+impl Related<super::court_tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::court_tag_links::Relation::CourtTags.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::court_tag_links::Relation::Courts.def().rev())
+    }
 }
 
 impl Related<super::orders::Entity> for Entity {
@@ -43,4 +129,76 @@ impl Related<super::users::Entity> for Entity {
     }
 }
 
+impl Related<super::venues::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Venues.def()
+    }
+}
+
+impl Related<super::court_blocks::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtBlocks.def()
+    }
+}
+
+impl Related<super::court_price_overrides::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtPriceOverrides.def()
+    }
+}
+
+impl Related<super::court_booking_rules::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtBookingRules.def()
+    }
+}
+
+impl Related<super::court_addons::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtAddons.def()
+    }
+}
+
+impl Related<super::slot_holds::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SlotHolds.def()
+    }
+}
+
+impl Related<super::court_reviews::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtReviews.def()
+    }
+}
+
+impl Related<super::settlement_ledger::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SettlementLedger.def()
+    }
+}
+
+impl Related<super::promotion_courts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PromotionCourts.def()
+    }
+}
+
+impl Related<super::court_favorites::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtFavorites.def()
+    }
+}
+
+impl Related<super::court_posters::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtPosters.def()
+    }
+}
+
+impl Related<super::feedback::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Feedback.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
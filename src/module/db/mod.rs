@@ -2,6 +2,71 @@
 
 pub mod prelude;
 
+pub mod api_key_logs;
+pub mod api_keys;
+pub mod audit_log;
+pub mod coupon_courts;
+pub mod coupon_templates;
+pub mod court_addons;
+pub mod court_audit;
+pub mod court_blocks;
+pub mod court_booking_rules;
+pub mod court_demand_tiers;
+pub mod court_favorites;
+pub mod court_images;
+pub mod court_open_hours;
+pub mod court_posters;
+pub mod court_price_overrides;
+pub mod court_price_rules;
+pub mod court_reviews;
+pub mod court_tag_links;
+pub mod court_tags;
 pub mod courts;
+pub mod feedback;
+pub mod gift_cards;
+pub mod invoices;
+pub mod order_addons;
+pub mod order_items;
+pub mod order_participants;
+pub mod order_series;
+pub mod order_timeline;
 pub mod orders;
+pub mod package_purchases;
+pub mod package_usages;
+pub mod packages;
+pub mod partner_posts;
+pub mod partner_requests;
+pub mod password_resets;
+pub mod payment_notifications;
+pub mod points_config;
+pub mod points_transactions;
+pub mod promotion_courts;
+pub mod promotion_usages;
+pub mod promotions;
+pub mod reconcile_issues;
+pub mod reconcile_runs;
+pub mod referral_codes;
+pub mod referral_config;
+pub mod referrals;
+pub mod refresh_tokens;
+pub mod refund_items;
+pub mod refunds;
+pub mod sea_orm_active_enums;
+pub mod search_history;
+pub mod settlement_ledger;
+pub mod slot_holds;
+pub mod subscribe_consents;
+pub mod tier_benefits;
+pub mod user_contacts;
+pub mod user_coupons;
+pub mod user_notifications;
+pub mod user_packages;
+pub mod user_points;
+pub mod user_sessions;
 pub mod users;
+pub mod venue_blacklist;
+pub mod venue_follows;
+pub mod venues;
+pub mod wallet_recharges;
+pub mod wallet_transactions;
+pub mod wallets;
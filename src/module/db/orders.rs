@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
 
+use super::sea_orm_active_enums::{DepositStatus, OrderState, PayMethod};
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, serde::Serialize)]
@@ -12,12 +13,87 @@ pub struct Model {
     pub create_time: DateTime,
     pub apt_start: DateTime,
     pub apt_end: DateTime,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub cost: Decimal,
+    pub check_in_time: Option<DateTime>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub deposit: Decimal,
+    pub deposit_status: DepositStatus,
+    pub status: OrderState,
+    pub cancel_reason: Option<String>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub cancel_fee: Decimal,
+    pub series_id: Option<Uuid>,
+    pub pay_deadline: Option<DateTime>,
+    pub customer_name: Option<String>,
+    pub customer_phone: Option<String>,
+    pub contact_name: Option<String>,
+    pub contact_phone: Option<String>,
+    pub reminded: bool,
+    pub idempotency_key: Option<String>,
+    pub remark: Option<String>,
+    #[serde(skip_serializing)]
+    pub internal_note: Option<String>,
+    pub transaction_id: Option<String>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))", nullable)]
+    pub pay_amount: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))", nullable)]
+    pub prepay_amount: Option<Decimal>,
+    pub pay_method: Option<PayMethod>,
+    pub paid_time: Option<DateTime>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub discount: Decimal,
     #[sea_orm(column_type = "Double")]
-    pub cost: f64,
+    pub package_hours: f64,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub package_amount: Decimal,
+    pub points_used: i32,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub points_amount: Decimal,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub promotion_amount: Decimal,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub member_amount: Decimal,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::partner_posts::Entity")]
+    PartnerPosts,
+    #[sea_orm(has_many = "super::feedback::Entity")]
+    Feedback,
+    #[sea_orm(has_many = "super::referrals::Entity")]
+    Referrals,
+    #[sea_orm(has_many = "super::user_notifications::Entity")]
+    UserNotifications,
+    #[sea_orm(has_many = "super::promotion_usages::Entity")]
+    PromotionUsages,
+    #[sea_orm(has_many = "super::points_transactions::Entity")]
+    PointsTransactions,
+    #[sea_orm(has_many = "super::settlement_ledger::Entity")]
+    SettlementLedger,
+    #[sea_orm(has_one = "super::court_reviews::Entity")]
+    CourtReviews,
+    #[sea_orm(has_one = "super::invoices::Entity")]
+    Invoices,
+    #[sea_orm(has_many = "super::order_items::Entity")]
+    OrderItems,
+    #[sea_orm(has_many = "super::refunds::Entity")]
+    Refunds,
+    #[sea_orm(has_many = "super::order_participants::Entity")]
+    OrderParticipants,
+    #[sea_orm(has_many = "super::order_timeline::Entity")]
+    OrderTimeline,
+    #[sea_orm(
+        belongs_to = "super::order_series::Entity",
+        from = "Column::SeriesId",
+        to = "super::order_series::Column::SeriesId",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    OrderSeries,
+    #[sea_orm(has_many = "super::order_addons::Entity")]
+    OrderAddons,
     #[sea_orm(
         belongs_to = "super::courts::Entity",
         from = "Column::CourtId",
@@ -48,4 +124,94 @@ impl Related<super::users::Entity> for Entity {
     }
 }
 
+impl Related<super::order_addons::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderAddons.def()
+    }
+}
+
+impl Related<super::order_series::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderSeries.def()
+    }
+}
+
+impl Related<super::order_timeline::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderTimeline.def()
+    }
+}
+
+impl Related<super::order_participants::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderParticipants.def()
+    }
+}
+
+impl Related<super::refunds::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Refunds.def()
+    }
+}
+
+impl Related<super::order_items::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderItems.def()
+    }
+}
+
+impl Related<super::invoices::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Invoices.def()
+    }
+}
+
+impl Related<super::court_reviews::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtReviews.def()
+    }
+}
+
+impl Related<super::settlement_ledger::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SettlementLedger.def()
+    }
+}
+
+impl Related<super::points_transactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PointsTransactions.def()
+    }
+}
+
+impl Related<super::promotion_usages::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PromotionUsages.def()
+    }
+}
+
+impl Related<super::user_notifications::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserNotifications.def()
+    }
+}
+
+impl Related<super::referrals::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Referrals.def()
+    }
+}
+
+impl Related<super::feedback::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Feedback.def()
+    }
+}
+
+impl Related<super::partner_posts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PartnerPosts.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
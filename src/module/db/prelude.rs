@@ -1,5 +1,69 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
 
+pub use super::api_key_logs::Entity as ApiKeyLogs;
+pub use super::api_keys::Entity as ApiKeys;
+pub use super::audit_log::Entity as AuditLog;
+pub use super::coupon_courts::Entity as CouponCourts;
+pub use super::coupon_templates::Entity as CouponTemplates;
+pub use super::court_addons::Entity as CourtAddons;
+pub use super::court_audit::Entity as CourtAudit;
+pub use super::court_blocks::Entity as CourtBlocks;
+pub use super::court_booking_rules::Entity as CourtBookingRules;
+pub use super::court_demand_tiers::Entity as CourtDemandTiers;
+pub use super::court_favorites::Entity as CourtFavorites;
+pub use super::court_images::Entity as CourtImages;
+pub use super::court_open_hours::Entity as CourtOpenHours;
+pub use super::court_posters::Entity as CourtPosters;
+pub use super::court_price_overrides::Entity as CourtPriceOverrides;
+pub use super::court_price_rules::Entity as CourtPriceRules;
+pub use super::court_reviews::Entity as CourtReviews;
+pub use super::court_tag_links::Entity as CourtTagLinks;
+pub use super::court_tags::Entity as CourtTags;
 pub use super::courts::Entity as Courts;
+pub use super::feedback::Entity as Feedback;
+pub use super::gift_cards::Entity as GiftCards;
+pub use super::invoices::Entity as Invoices;
+pub use super::order_addons::Entity as OrderAddons;
+pub use super::order_items::Entity as OrderItems;
+pub use super::order_participants::Entity as OrderParticipants;
+pub use super::order_series::Entity as OrderSeries;
+pub use super::order_timeline::Entity as OrderTimeline;
 pub use super::orders::Entity as Orders;
+pub use super::package_purchases::Entity as PackagePurchases;
+pub use super::package_usages::Entity as PackageUsages;
+pub use super::packages::Entity as Packages;
+pub use super::partner_posts::Entity as PartnerPosts;
+pub use super::partner_requests::Entity as PartnerRequests;
+pub use super::password_resets::Entity as PasswordResets;
+pub use super::payment_notifications::Entity as PaymentNotifications;
+pub use super::points_config::Entity as PointsConfig;
+pub use super::points_transactions::Entity as PointsTransactions;
+pub use super::promotion_courts::Entity as PromotionCourts;
+pub use super::promotion_usages::Entity as PromotionUsages;
+pub use super::promotions::Entity as Promotions;
+pub use super::reconcile_issues::Entity as ReconcileIssues;
+pub use super::reconcile_runs::Entity as ReconcileRuns;
+pub use super::referral_codes::Entity as ReferralCodes;
+pub use super::referral_config::Entity as ReferralConfig;
+pub use super::referrals::Entity as Referrals;
+pub use super::refresh_tokens::Entity as RefreshTokens;
+pub use super::refund_items::Entity as RefundItems;
+pub use super::refunds::Entity as Refunds;
+pub use super::search_history::Entity as SearchHistory;
+pub use super::settlement_ledger::Entity as SettlementLedger;
+pub use super::slot_holds::Entity as SlotHolds;
+pub use super::subscribe_consents::Entity as SubscribeConsents;
+pub use super::tier_benefits::Entity as TierBenefits;
+pub use super::user_contacts::Entity as UserContacts;
+pub use super::user_coupons::Entity as UserCoupons;
+pub use super::user_notifications::Entity as UserNotifications;
+pub use super::user_packages::Entity as UserPackages;
+pub use super::user_points::Entity as UserPoints;
+pub use super::user_sessions::Entity as UserSessions;
 pub use super::users::Entity as Users;
+pub use super::venue_blacklist::Entity as VenueBlacklist;
+pub use super::venue_follows::Entity as VenueFollows;
+pub use super::venues::Entity as Venues;
+pub use super::wallet_recharges::Entity as WalletRecharges;
+pub use super::wallet_transactions::Entity as WalletTransactions;
+pub use super::wallets::Entity as Wallets;
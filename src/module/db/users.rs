@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
 
+use super::sea_orm_active_enums::{Gender, SportType, StaffScope, UserTier};
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -10,17 +11,92 @@ pub struct Model {
     #[sea_orm(unique)]
     pub user_name: String,
     pub user_pwd: String,
+    pub hash_version: i16,
     #[sea_orm(unique)]
-    pub phone: String,
+    pub phone: Option<String>,
     pub is_admin: bool,
+    pub is_super: bool,
+    pub staff_of: Option<Uuid>,
+    pub staff_scopes: Vec<StaffScope>,
+    pub no_show_count: i32,
+    pub banned_until: Option<DateTime>,
+    pub notify_reminder: bool,
+    #[sea_orm(unique)]
+    pub openid: Option<String>,
+    #[sea_orm(unique)]
+    pub unionid: Option<String>,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub gender: Gender,
+    pub preferred_sports: Vec<SportType>,
+    pub tier: UserTier,
+    pub tier_until: Option<DateTime>,
+    pub deleted_time: Option<DateTime>,
+    pub disabled_time: Option<DateTime>,
+    pub failed_logins: i32,
+    pub locked_until: Option<DateTime>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_last_step: i64,
+    pub totp_backup_codes: Vec<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::audit_log::Entity")]
+    AuditLog,
+    #[sea_orm(has_many = "super::api_keys::Entity")]
+    ApiKeys,
+    #[sea_orm(has_many = "super::password_resets::Entity")]
+    PasswordResets,
+    #[sea_orm(has_many = "super::user_contacts::Entity")]
+    UserContacts,
+    #[sea_orm(has_many = "super::search_history::Entity")]
+    SearchHistory,
+    #[sea_orm(has_many = "super::venue_follows::Entity")]
+    VenueFollows,
+    #[sea_orm(has_many = "super::subscribe_consents::Entity")]
+    SubscribeConsents,
+    #[sea_orm(has_many = "super::partner_requests::Entity")]
+    PartnerRequests,
+    #[sea_orm(has_many = "super::partner_posts::Entity")]
+    PartnerPosts,
+    #[sea_orm(has_many = "super::user_sessions::Entity")]
+    UserSessions,
+    #[sea_orm(has_many = "super::referral_codes::Entity")]
+    ReferralCodes,
+    #[sea_orm(has_many = "super::user_notifications::Entity")]
+    UserNotifications,
+    #[sea_orm(has_many = "super::court_favorites::Entity")]
+    CourtFavorites,
+    #[sea_orm(has_many = "super::promotions::Entity")]
+    Promotions,
+    #[sea_orm(has_many = "super::promotion_usages::Entity")]
+    PromotionUsages,
+    #[sea_orm(has_many = "super::points_transactions::Entity")]
+    PointsTransactions,
+    #[sea_orm(has_many = "super::user_points::Entity")]
+    UserPoints,
+    #[sea_orm(has_many = "super::settlement_ledger::Entity")]
+    SettlementLedger,
+    #[sea_orm(has_many = "super::court_reviews::Entity")]
+    CourtReviews,
+    #[sea_orm(has_many = "super::order_participants::Entity")]
+    OrderParticipants,
+    #[sea_orm(has_many = "super::court_tags::Entity")]
+    CourtTags,
     #[sea_orm(has_many = "super::courts::Entity")]
     Courts,
     #[sea_orm(has_many = "super::orders::Entity")]
     Orders,
+    #[sea_orm(has_many = "super::venues::Entity")]
+    Venues,
+}
+
+impl Related<super::court_tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtTags.def()
+    }
 }
 
 impl Related<super::courts::Entity> for Entity {
@@ -35,4 +111,130 @@ impl Related<super::orders::Entity> for Entity {
     }
 }
 
+impl Related<super::venues::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Venues.def()
+    }
+}
+
+impl Related<super::order_participants::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderParticipants.def()
+    }
+}
+
+impl Related<super::court_reviews::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtReviews.def()
+    }
+}
+
+impl Related<super::settlement_ledger::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SettlementLedger.def()
+    }
+}
+
+impl Related<super::user_points::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserPoints.def()
+    }
+}
+
+impl Related<super::points_transactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PointsTransactions.def()
+    }
+}
+
+impl Related<super::promotion_usages::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PromotionUsages.def()
+    }
+}
+
+impl Related<super::promotions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Promotions.def()
+    }
+}
+
+impl Related<super::court_favorites::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CourtFavorites.def()
+    }
+}
+
+impl Related<super::user_notifications::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserNotifications.def()
+    }
+}
+
+impl Related<super::referral_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReferralCodes.def()
+    }
+}
+
+impl Related<super::user_sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSessions.def()
+    }
+}
+
+impl Related<super::partner_posts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PartnerPosts.def()
+    }
+}
+
+impl Related<super::partner_requests::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PartnerRequests.def()
+    }
+}
+
+impl Related<super::subscribe_consents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SubscribeConsents.def()
+    }
+}
+
+impl Related<super::venue_follows::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VenueFollows.def()
+    }
+}
+
+impl Related<super::search_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SearchHistory.def()
+    }
+}
+
+impl Related<super::user_contacts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserContacts.def()
+    }
+}
+
+impl Related<super::password_resets::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResets.def()
+    }
+}
+
+impl Related<super::api_keys::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKeys.def()
+    }
+}
+
+impl Related<super::audit_log::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditLog.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
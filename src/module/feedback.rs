@@ -0,0 +1,344 @@
+use super::db::{
+    feedback,
+    prelude::{Courts, Feedback, Orders},
+    sea_orm_active_enums::{FeedbackCategory, FeedbackStatus, NotificationKind},
+    users,
+};
+use super::notify::inbox::InboxOp;
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::DateTime;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, FromQueryResult, JoinType, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//每条反馈最多的图片数
+pub const MAX_IMAGES: usize = 3;
+const CONTENT_LEN: usize = 1000;
+const REPLY_LEN: usize = 500;
+
+//提交反馈, 由multipart表单解析
+#[derive(Debug, Clone)]
+pub struct FeedbackSubmit {
+    pub category: FeedbackCategory,
+    pub content: String,
+    pub court_id: Option<Uuid>,
+    pub order_id: Option<Uuid>,
+}
+
+//已校验的图片: 内容, content_type, 扩展名, 见 UploadOp::validate
+pub type FeedbackImage = (Vec<u8>, &'static str, &'static str);
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedbackQuery {
+    pub status: Option<FeedbackStatus>,
+    pub category: Option<FeedbackCategory>,
+    #[serde(default = "super::order::default_page")]
+    pub page: u64,
+    #[serde(default = "super::order::default_page_size")]
+    pub page_size: u64,
+}
+
+//处理反馈, 状态只能依次推进
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedbackUpdate {
+    pub feedback_id: Uuid,
+    pub status: FeedbackStatus,
+    pub reply: Option<String>,
+}
+
+//管理员查看的反馈, 附提交用户的联系方式
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct FeedbackAdminSchema {
+    pub feedback_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub phone: Option<String>,
+    pub category: FeedbackCategory,
+    pub content: String,
+    pub court_id: Option<Uuid>,
+    pub order_id: Option<Uuid>,
+    pub images: Vec<String>,
+    pub status: FeedbackStatus,
+    pub reply: Option<String>,
+    pub create_time: DateTime,
+    pub update_time: DateTime,
+}
+
+pub struct FeedbackOp;
+impl FeedbackOp {
+    //提交反馈, 关联订单时球场取订单的球场, 由球场所属管理员处理
+    pub async fn submit<T: From<String>>(
+        user_id: Uuid,
+        schema: FeedbackSubmit,
+        images: Vec<FeedbackImage>,
+        state: &AppState,
+    ) -> Result<feedback::Model, HandleErr<T>> {
+        let content = schema.content.trim();
+        if content.is_empty() || content.chars().count() > CONTENT_LEN {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("反馈内容不能为空且不超过{}字", CONTENT_LEN).into(),
+            ));
+        }
+        if images.len() > MAX_IMAGES {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("最多上传{}张图片", MAX_IMAGES).into(),
+            ));
+        }
+        if schema.category == FeedbackCategory::Billing && schema.order_id.is_none() {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "费用争议须选择相关订单".to_string().into(),
+            ));
+        }
+        let mut court_id = schema.court_id;
+        if let Some(order_id) = schema.order_id {
+            let order = Orders::find_by_id(order_id)
+                .one(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+                .filter(|e| e.user_id == user_id)
+                .ok_or(HandleErr::BadRequest(-1, "订单不存在".to_string().into()))?;
+            court_id = Some(order.court_id);
+        }
+        let admin_id = match court_id {
+            Some(court_id) => Some(
+                Courts::find_by_id(court_id)
+                    .one(&state.db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?
+                    .ok_or(HandleErr::BadRequest(-1, "球场不存在".to_string().into()))?
+                    .admin_id,
+            ),
+            None => None,
+        };
+
+        let mut urls = vec![];
+        for (data, content_type, ext) in images {
+            let key = format!("feedback/{}/{}.{}", user_id, Uuid::new_v4(), ext);
+            let url = state
+                .storage
+                .put(&key, content_type, data)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            urls.push(url);
+        }
+        let feedback = feedback::ActiveModel {
+            feedback_id: NotSet,
+            user_id: Set(user_id),
+            category: Set(schema.category),
+            content: Set(content.to_string()),
+            court_id: Set(court_id),
+            order_id: Set(schema.order_id),
+            images: Set(urls),
+            admin_id: Set(admin_id),
+            status: NotSet,
+            reply: NotSet,
+            create_time: NotSet,
+            update_time: NotSet,
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})提交反馈({})", user_id, feedback.feedback_id);
+        Ok(feedback)
+    }
+
+    //我提交的反馈, 最新的在前, 返回反馈与总数
+    pub async fn mine<T>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<feedback::Model>, u64), HandleErr<T>> {
+        let paginator = Feedback::find()
+            .filter(feedback::Column::UserId.eq(user_id))
+            .order_by_desc(feedback::Column::CreateTime)
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let feedback = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((feedback, total))
+    }
+
+    //待处理队列, 超级管理员可查看全部反馈, 其他管理员只能查看名下球场的反馈
+    //未处理的在前, 同状态按提交时间先后
+    pub async fn queue<T>(
+        admin_id: Uuid,
+        is_super: bool,
+        query: &FeedbackQuery,
+        state: &AppState,
+    ) -> Result<(Vec<FeedbackAdminSchema>, u64), HandleErr<T>> {
+        let mut select = Feedback::find()
+            .column(users::Column::UserName)
+            .column(users::Column::Phone)
+            .join(JoinType::InnerJoin, feedback::Relation::Users1.def());
+        if !is_super {
+            select = select.filter(feedback::Column::AdminId.eq(admin_id));
+        }
+        if let Some(status) = &query.status {
+            select = select.filter(feedback::Column::Status.eq(status.clone()));
+        }
+        if let Some(category) = &query.category {
+            select = select.filter(feedback::Column::Category.eq(category.clone()));
+        }
+        let paginator = select
+            .order_by_asc(feedback::Column::Status)
+            .order_by_asc(feedback::Column::CreateTime)
+            .into_model::<FeedbackAdminSchema>()
+            .paginate(&state.db, query.page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let feedback = paginator
+            .fetch_page(query.page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((feedback, total))
+    }
+
+    //推进处理状态并通知用户
+    pub async fn update<T: From<String>>(
+        admin_id: Uuid,
+        is_super: bool,
+        schema: FeedbackUpdate,
+        state: &AppState,
+    ) -> Result<feedback::Model, HandleErr<T>> {
+        let reply = schema
+            .reply
+            .as_deref()
+            .map(str::trim)
+            .filter(|e| !e.is_empty());
+        if reply.is_some_and(|e| e.chars().count() > REPLY_LEN) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("回复不能超过{}字", REPLY_LEN).into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let feedback = Feedback::find_by_id(schema.feedback_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| is_super || e.admin_id == Some(admin_id))
+            .ok_or(HandleErr::BadRequest(-1, "反馈不存在".to_string().into()))?;
+        if !advance(&feedback.status, &schema.status) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!(
+                    "反馈{}, 不能变为{}",
+                    status_name(&feedback.status),
+                    status_name(&schema.status)
+                )
+                .into(),
+            ));
+        }
+        let mut model = feedback::ActiveModel {
+            feedback_id: Set(feedback.feedback_id),
+            status: Set(schema.status.clone()),
+            update_time: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        if let Some(reply) = reply {
+            model.reply = Set(Some(reply.to_string()));
+        }
+        let feedback = model.update(&txn).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        InboxOp::push(
+            feedback.user_id,
+            NotificationKind::Feedback,
+            &format!("反馈{}", status_name(&feedback.status)),
+            reply.unwrap_or(&format!(
+                "您{}提交的反馈{}",
+                feedback.create_time.format("%m-%d"),
+                status_name(&feedback.status)
+            )),
+            feedback.order_id,
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(feedback)
+    }
+}
+
+//新提交 → 处理中 → 已解决
+fn advance(from: &FeedbackStatus, to: &FeedbackStatus) -> bool {
+    matches!(
+        (from, to),
+        (FeedbackStatus::New, FeedbackStatus::InProgress)
+            | (FeedbackStatus::InProgress, FeedbackStatus::Resolved)
+    )
+}
+
+fn status_name(status: &FeedbackStatus) -> &'static str {
+    match status {
+        FeedbackStatus::New => "待处理",
+        FeedbackStatus::InProgress => "处理中",
+        FeedbackStatus::Resolved => "已解决",
+    }
+}
+
+#[test]
+fn test_advance() {
+    use FeedbackStatus::*;
+    assert!(advance(&New, &InProgress));
+    assert!(advance(&InProgress, &Resolved));
+    assert!(!advance(&New, &Resolved));
+    assert!(!advance(&Resolved, &InProgress));
+    assert!(!advance(&InProgress, &InProgress));
+}
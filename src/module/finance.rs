@@ -0,0 +1,263 @@
+use super::db::{
+    orders, package_purchases,
+    prelude::{Courts, SettlementLedger},
+    sea_orm_active_enums::{LedgerKind, PayMethod},
+    settlement_ledger,
+};
+use crate::{appstate::AppState, error::HandleErr, module::money};
+use chrono::{Datelike, Months, NaiveDate};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::{
+    ActiveValue::NotSet, ConnectionTrait, DbBackend, EntityTrait, FromQueryResult, Set, Statement,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+//账期按北京时间划分
+const PERIOD_OFFSET_HOURS: i64 = 8;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SettlementQuery {
+    //账期, 按月"2024-05"或按日"2024-05-01"
+    pub period: String,
+}
+
+//球场在账期内的结算数据, 次卡销售不属于球场, 合并为court_id为空的一条
+#[derive(Debug, Serialize, Clone, Default, PartialEq, FromQueryResult)]
+pub struct SettlementRow {
+    pub venue_id: Option<Uuid>,
+    pub venue_name: Option<String>,
+    pub court_id: Option<Uuid>,
+    pub court_name: Option<String>,
+    //支付总额
+    pub gross: Decimal,
+    //退款总额(正数)
+    pub refunds: Decimal,
+    //平台服务费, 已扣除退款退还的部分
+    pub fees: Decimal,
+    //应结算金额
+    pub net: Decimal,
+}
+
+//场馆小计, 未归属场馆的球场合并为一条
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct VenueSettlement {
+    pub venue_id: Option<Uuid>,
+    pub venue_name: Option<String>,
+    pub gross: Decimal,
+    pub refunds: Decimal,
+    pub fees: Decimal,
+    pub net: Decimal,
+}
+
+pub struct LedgerOp;
+impl LedgerOp {
+    //写入结算流水, amount为本次支付或退款的金额(正数), deposit为其中押金的部分, 不收取服务费
+    pub async fn record<T, C: ConnectionTrait>(
+        order: &orders::Model,
+        kind: LedgerKind,
+        amount: Decimal,
+        deposit: Decimal,
+        pay_method: Option<PayMethod>,
+        state: &AppState,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        if amount <= Decimal::ZERO {
+            return Ok(());
+        }
+        let court = Courts::find_by_id(order.court_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let Some(court) = court else {
+            return Ok(());
+        };
+        let sign = match kind {
+            LedgerKind::Payment => Decimal::ONE,
+            LedgerKind::Refund => -Decimal::ONE,
+        };
+        let charged = (amount - deposit.clamp(Decimal::ZERO, amount)) * sign;
+        SettlementLedger::insert(settlement_ledger::ActiveModel {
+            entry_id: NotSet,
+            admin_id: Set(court.admin_id),
+            court_id: Set(Some(court.court_id)),
+            order_id: Set(Some(order.order_id)),
+            purchase_id: NotSet,
+            kind: Set(kind),
+            pay_method: Set(pay_method),
+            amount: Set(amount * sign),
+            fee: Set(fee(charged, state.cfg.financecfg.fee_percent)),
+            create_time: NotSet,
+        })
+        .exec(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //写入次卡购买的结算流水, 归属套餐的管理员
+    pub async fn package<T, C: ConnectionTrait>(
+        purchase: &package_purchases::Model,
+        admin_id: Uuid,
+        state: &AppState,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        SettlementLedger::insert(settlement_ledger::ActiveModel {
+            entry_id: NotSet,
+            admin_id: Set(admin_id),
+            court_id: NotSet,
+            order_id: NotSet,
+            purchase_id: Set(Some(purchase.purchase_id)),
+            kind: Set(LedgerKind::Payment),
+            pay_method: Set(Some(PayMethod::Wechat)),
+            amount: Set(purchase.amount),
+            fee: Set(fee(purchase.amount, state.cfg.financecfg.fee_percent)),
+            create_time: NotSet,
+        })
+        .exec(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //管理员名下各球场在[from, to)内的结算数据
+    pub async fn settlement<T>(
+        admin_id: Uuid,
+        from: DateTime,
+        to: DateTime,
+        state: &AppState,
+    ) -> Result<Vec<SettlementRow>, HandleErr<T>> {
+        SettlementRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"select v.venue_id, v.venue_name, c.court_id, c.court_name,
+                coalesce(sum(l.amount) filter (where l.kind = 'payment'), 0)::numeric as gross,
+                coalesce(-sum(l.amount) filter (where l.kind = 'refund'), 0)::numeric as refunds,
+                coalesce(sum(l.fee), 0)::numeric as fees,
+                coalesce(sum(l.amount - l.fee), 0)::numeric as net
+               from settlement_ledger l
+               left join courts c on c.court_id = l.court_id
+               left join venues v on v.venue_id = c.venue_id
+               where l.admin_id = $1 and l.create_time >= $2 and l.create_time < $3
+               group by v.venue_id, v.venue_name, c.court_id, c.court_name
+               order by v.venue_name nulls last, c.court_name nulls last"#,
+            [admin_id.into(), from.into(), to.into()],
+        ))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+}
+
+//订单支付或退还金额中押金的部分, 先抵订单费用, 超出的部分为押金
+pub fn deposit_of(order: &orders::Model, amount: Decimal) -> Decimal {
+    (amount - order.cost).clamp(Decimal::ZERO, order.deposit)
+}
+
+//拼单分摊中押金的部分, 按分摊占订单应付金额的比例计算
+pub fn share_deposit(order: &orders::Model, share: Decimal) -> Decimal {
+    let total = order.cost + order.deposit;
+    if total <= Decimal::ZERO {
+        return Decimal::ZERO;
+    }
+    money::round(share * order.deposit / total)
+}
+
+//平台服务费, 与金额同号, 保留两位小数
+pub fn fee(amount: Decimal, fee_percent: Decimal) -> Decimal {
+    money::round(amount * fee_percent / Decimal::ONE_HUNDRED)
+}
+
+//账期对应的UTC时间范围
+pub fn period(s: &str) -> Option<(DateTime, DateTime)> {
+    let (start, end) = if let Ok(day) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        (day, day.succ_opt()?)
+    } else {
+        let month = NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d").ok()?;
+        (month, month.checked_add_months(Months::new(1))?)
+    };
+    if start.year() < 2000 {
+        return None;
+    }
+    let offset = chrono::Duration::hours(PERIOD_OFFSET_HOURS);
+    Some((
+        start.and_hms_opt(0, 0, 0)? - offset,
+        end.and_hms_opt(0, 0, 0)? - offset,
+    ))
+}
+
+//按场馆汇总, 保持球场列表中场馆的顺序
+pub fn by_venue(rows: &[SettlementRow]) -> Vec<VenueSettlement> {
+    let mut venues: Vec<VenueSettlement> = vec![];
+    for row in rows {
+        let i = match venues.iter().position(|e| e.venue_id == row.venue_id) {
+            Some(i) => i,
+            None => {
+                venues.push(VenueSettlement {
+                    venue_id: row.venue_id,
+                    venue_name: row.venue_name.clone(),
+                    ..Default::default()
+                });
+                venues.len() - 1
+            }
+        };
+        let venue = &mut venues[i];
+        venue.gross += row.gross;
+        venue.refunds += row.refunds;
+        venue.fees += row.fees;
+        venue.net += row.net;
+    }
+    venues
+}
+
+#[test]
+fn test_settlement() {
+    let d = |v: i64| Decimal::from(v);
+    let rate = Decimal::new(6, 1);
+    assert_eq!(fee(d(100), rate), Decimal::new(6, 1));
+    assert_eq!(fee(d(-50), rate), Decimal::new(-3, 1));
+    //0.6%的0.01元不足1分, 四舍五入为0
+    assert_eq!(fee(money::CENT, rate), d(0));
+    let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let (from, to) = period("2024-12").unwrap();
+    assert_eq!(from, day(2024, 11, 30).and_hms_opt(16, 0, 0).unwrap());
+    assert_eq!(to, day(2024, 12, 31).and_hms_opt(16, 0, 0).unwrap());
+    let (from, to) = period("2024-05-01").unwrap();
+    assert_eq!(from, day(2024, 4, 30).and_hms_opt(16, 0, 0).unwrap());
+    assert_eq!(to, day(2024, 5, 1).and_hms_opt(16, 0, 0).unwrap());
+    assert!(period("2024-13").is_none());
+    assert!(period("abc").is_none());
+    let venue = Some(Uuid::new_v4());
+    let row = |venue_id, gross, refunds| SettlementRow {
+        venue_id,
+        court_id: Some(Uuid::new_v4()),
+        gross,
+        refunds,
+        net: gross - refunds,
+        ..Default::default()
+    };
+    let venues = by_venue(&[
+        row(venue, d(100), d(0)),
+        row(venue, d(50), d(20)),
+        row(None, d(10), d(0)),
+    ]);
+    assert_eq!(venues.len(), 2);
+    assert_eq!((venues[0].gross, venues[0].net), (d(150), d(130)));
+    assert_eq!(venues[1].venue_id, None);
+}
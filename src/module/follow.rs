@@ -0,0 +1,175 @@
+use super::db::{
+    prelude::{VenueFollows, Venues},
+    users, venue_follows, venues,
+};
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::DateTime;
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, EntityTrait,
+    FromQueryResult, JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+    Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//每个用户最多关注的场馆数
+const MAX_FOLLOWS: u64 = 100;
+
+//关注场馆, 重复关注时更新是否推送
+#[derive(Debug, Deserialize, Clone)]
+pub struct VenueFollow {
+    pub venue_id: Uuid,
+    #[serde(default)]
+    pub push: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VenueUnfollow {
+    pub venue_id: Uuid,
+}
+
+//关注的场馆
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct FollowSchema {
+    pub venue_id: Uuid,
+    pub venue_name: String,
+    pub address: String,
+    pub announcement: String,
+    pub photos: Vec<String>,
+    pub push: bool,
+    pub create_time: DateTime,
+}
+
+//场馆的关注者, 公告推送时使用
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct Follower {
+    pub user_id: Uuid,
+    pub push: bool,
+    pub phone: Option<String>,
+}
+
+pub struct FollowOp;
+impl FollowOp {
+    pub async fn add<T: From<String>>(
+        user_id: Uuid,
+        schema: VenueFollow,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        Venues::find_by_id(schema.venue_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "场馆不存在".to_string().into()))?;
+        let count = VenueFollows::find()
+            .filter(venue_follows::Column::UserId.eq(user_id))
+            .filter(venue_follows::Column::VenueId.ne(schema.venue_id))
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if count >= MAX_FOLLOWS {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("最多关注{}个场馆", MAX_FOLLOWS).into(),
+            ));
+        }
+        VenueFollows::insert(venue_follows::ActiveModel {
+            user_id: Set(user_id),
+            venue_id: Set(schema.venue_id),
+            push: Set(schema.push),
+            create_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::columns([
+                venue_follows::Column::UserId,
+                venue_follows::Column::VenueId,
+            ])
+            .update_column(venue_follows::Column::Push)
+            .to_owned(),
+        )
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})关注场馆({})", user_id, schema.venue_id);
+        Ok(())
+    }
+
+    //取消关注, 未关注时视为成功
+    pub async fn del<T>(
+        user_id: Uuid,
+        venue_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        VenueFollows::delete_by_id((user_id, venue_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //关注的场馆, 最近关注的在前
+    pub async fn list<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<FollowSchema>, HandleErr<T>> {
+        VenueFollows::find()
+            .select_only()
+            .column(venue_follows::Column::VenueId)
+            .column(venues::Column::VenueName)
+            .column(venues::Column::Address)
+            .column(venues::Column::Announcement)
+            .column(venues::Column::Photos)
+            .column(venue_follows::Column::Push)
+            .column(venue_follows::Column::CreateTime)
+            .join(JoinType::InnerJoin, venue_follows::Relation::Venues.def())
+            .filter(venue_follows::Column::UserId.eq(user_id))
+            .order_by_desc(venue_follows::Column::CreateTime)
+            .into_model::<FollowSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //场馆的关注者, 已注销的用户除外
+    pub async fn followers<T, C: ConnectionTrait>(
+        venue_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<Follower>, HandleErr<T>> {
+        VenueFollows::find()
+            .select_only()
+            .column(venue_follows::Column::UserId)
+            .column(venue_follows::Column::Push)
+            .column(users::Column::Phone)
+            .join(JoinType::InnerJoin, venue_follows::Relation::Users.def())
+            .filter(venue_follows::Column::VenueId.eq(venue_id))
+            .filter(users::Column::DeletedTime.is_null())
+            .into_model::<Follower>()
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+}
@@ -0,0 +1,380 @@
+use super::db::{
+    gift_cards,
+    prelude::GiftCards,
+    sea_orm_active_enums::{GiftCardStatus, WalletTxnKind},
+    wallet_transactions,
+};
+use super::money;
+use super::payment::{
+    notification::NotificationOp,
+    wechat::{from_fen, to_fen, Transaction},
+};
+use super::wallet::{Change, WalletOp, MAX_RECHARGE};
+use crate::{appstate::AppState, error::HandleErr};
+use rand_core::RngCore;
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, FromQueryResult, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//兑换码字符集, 去掉易混淆的0/O/1/I
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 16;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GiftCardBuy {
+    pub amount: Decimal,
+    //给收卡人的赠言
+    #[serde(default)]
+    pub message: String,
+}
+
+//兑换到钱包, 指定订单时兑换后用余额支付该订单
+#[derive(Debug, Deserialize, Clone)]
+pub struct GiftCardRedeem {
+    pub code: String,
+    #[serde(default)]
+    pub order_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GiftCardQuery {
+    pub status: Option<GiftCardStatus>,
+    #[serde(default = "crate::module::order::default_page")]
+    pub page: u64,
+    #[serde(default = "crate::module::order::default_page_size")]
+    pub page_size: u64,
+}
+
+//各状态的张数与金额
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct GiftCardStats {
+    pub status: GiftCardStatus,
+    pub count: i64,
+    pub amount: Decimal,
+}
+
+pub struct GiftCardOp;
+impl GiftCardOp {
+    //创建待支付的礼品卡, 支付成功通知后兑换码生效
+    pub async fn purchase<T: From<String>>(
+        user_id: Uuid,
+        schema: GiftCardBuy,
+        state: &AppState,
+    ) -> Result<gift_cards::Model, HandleErr<T>> {
+        if !(money::CENT..=MAX_RECHARGE).contains(&schema.amount) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("礼品卡金额应在0.01~{:.2}之间", MAX_RECHARGE).into(),
+            ));
+        }
+        let message = schema.message.trim();
+        if message.chars().count() > 100 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "赠言不能超过100字".to_string().into(),
+            ));
+        }
+        gift_cards::ActiveModel {
+            card_id: NotSet,
+            code: Set(generate_code()),
+            amount: Set(money::round(schema.amount)),
+            buyer_id: Set(user_id),
+            message: Set(message.to_string()),
+            status: NotSet,
+            transaction_id: NotSet,
+            redeemed_by: NotSet,
+            order_id: NotSet,
+            create_time: NotSet,
+            paid_time: NotSet,
+            redeemed_time: NotSet,
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //购买支付成功通知, 锁定礼品卡后生效, 已生效时视为重复通知
+    pub async fn purchased<T: From<String>>(
+        transaction: &Transaction,
+        state: &AppState,
+    ) -> Result<gift_cards::Model, HandleErr<T>> {
+        let card_id = Uuid::parse_str(&transaction.out_trade_no).map_err(|_| {
+            HandleErr::BadRequest(
+                -1,
+                format!("out_trade_no无效: {}", transaction.out_trade_no).into(),
+            )
+        })?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let card = GiftCards::find_by_id(card_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "礼品卡不存在".to_string().into()))?;
+        if card.status != GiftCardStatus::Pending
+            || !NotificationOp::claim(transaction, &txn).await?
+        {
+            info!("礼品卡({})重复的支付通知", card.card_id);
+            return Ok(card);
+        }
+        //支付金额与面额不一致时不激活, 通知已记录, 由管理员对账处理
+        if transaction.amount.total != to_fen(card.amount) {
+            error!(
+                "礼品卡({})支付金额{:.2}元与面额{:.2}元不一致",
+                card.card_id,
+                from_fen(transaction.amount.total),
+                card.amount
+            );
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            return Ok(card);
+        }
+        let card = gift_cards::ActiveModel {
+            card_id: Set(card.card_id),
+            status: Set(GiftCardStatus::Active),
+            transaction_id: Set(Some(transaction.transaction_id.clone())),
+            paid_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})购买礼品卡{:.2}元", card.buyer_id, card.amount);
+        Ok(card)
+    }
+
+    //兑换为钱包余额, 以可兑换为条件更新, 同一兑换码并发兑换时只有一个成功
+    pub async fn redeem<T: From<String>>(
+        user_id: Uuid,
+        code: &str,
+        state: &AppState,
+    ) -> Result<(gift_cards::Model, wallet_transactions::Model), HandleErr<T>> {
+        let code = normalize(code);
+        if code.len() != CODE_LEN {
+            return Err(HandleErr::BadRequest(-1, "兑换码无效".to_string().into()));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let card = GiftCards::update_many()
+            .col_expr(
+                gift_cards::Column::Status,
+                Expr::value(GiftCardStatus::Redeemed),
+            )
+            .col_expr(gift_cards::Column::RedeemedBy, Expr::value(user_id))
+            .col_expr(
+                gift_cards::Column::RedeemedTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(
+                gift_cards::Column::Code
+                    .eq(code)
+                    .and(gift_cards::Column::Status.eq(GiftCardStatus::Active)),
+            )
+            .exec_with_returning(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .pop()
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "兑换码无效或已兑换".to_string().into(),
+            ))?;
+        let record = WalletOp::change(
+            user_id,
+            card.amount,
+            Change {
+                kind: WalletTxnKind::GiftCard,
+                order_id: None,
+                recharge_id: None,
+                admin_id: None,
+                remark: "礼品卡兑换",
+            },
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "用户({})兑换礼品卡({}) {:.2}元",
+            user_id, card.card_id, card.amount
+        );
+        Ok((card, record))
+    }
+
+    //记录兑换后支付的订单
+    pub async fn applied<T>(
+        card_id: Uuid,
+        order_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        gift_cards::ActiveModel {
+            card_id: Set(card_id),
+            order_id: Set(Some(order_id)),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //用户购买与兑换的礼品卡
+    pub async fn mine<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<gift_cards::Model>, HandleErr<T>> {
+        GiftCards::find()
+            .filter(
+                gift_cards::Column::BuyerId
+                    .eq(user_id)
+                    .or(gift_cards::Column::RedeemedBy.eq(user_id)),
+            )
+            .order_by_desc(gift_cards::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //全部礼品卡, 按状态筛选分页, 返回礼品卡与总数, 兑换码只展示首尾
+    pub async fn list<T>(
+        query: &GiftCardQuery,
+        state: &AppState,
+    ) -> Result<(Vec<gift_cards::Model>, u64), HandleErr<T>> {
+        let mut select = GiftCards::find();
+        if let Some(status) = &query.status {
+            select = select.filter(gift_cards::Column::Status.eq(status.clone()));
+        }
+        let paginator = select
+            .order_by_desc(gift_cards::Column::CreateTime)
+            .paginate(&state.db, query.page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let cards = paginator
+            .fetch_page(query.page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| gift_cards::Model {
+                code: mask(&e.code),
+                ..e
+            })
+            .collect();
+        Ok((cards, total))
+    }
+
+    pub async fn stats<T>(state: &AppState) -> Result<Vec<GiftCardStats>, HandleErr<T>> {
+        GiftCards::find()
+            .select_only()
+            .column(gift_cards::Column::Status)
+            .column_as(gift_cards::Column::CardId.count(), "count")
+            .column_as(gift_cards::Column::Amount.sum(), "amount")
+            .group_by(gift_cards::Column::Status)
+            .into_model::<GiftCardStats>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+}
+
+//随机生成兑换码
+pub fn generate_code() -> String {
+    let mut bytes = [0u8; CODE_LEN];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|e| CODE_CHARS[*e as usize % CODE_CHARS.len()] as char)
+        .collect()
+}
+
+//列表中展示的兑换码, 只保留首尾各4位
+pub fn mask(code: &str) -> String {
+    let chars: Vec<char> = code.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    let (head, tail) = (&chars[..4], &chars[chars.len() - 4..]);
+    format!(
+        "{}{}{}",
+        head.iter().collect::<String>(),
+        "*".repeat(chars.len() - 8),
+        tail.iter().collect::<String>()
+    )
+}
+
+//用户输入的兑换码, 忽略分隔符与大小写
+pub fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|e| e.is_ascii_alphanumeric())
+        .map(|e| e.to_ascii_uppercase())
+        .collect()
+}
+
+#[test]
+fn test_gift_card() {
+    let code = generate_code();
+    assert_eq!(code.len(), CODE_LEN);
+    assert!(code.bytes().all(|e| CODE_CHARS.contains(&e)));
+    assert_ne!(code, generate_code());
+    assert_eq!(normalize(" abcd-efgh jkmn-pqrs "), "ABCDEFGHJKMNPQRS");
+    assert_eq!(mask("ABCDEFGHJKMNPQRS"), "ABCD********PQRS");
+    assert_eq!(mask("ABC"), "***");
+}
@@ -0,0 +1,281 @@
+use super::db::{prelude::Users, users};
+use super::session::SessionOp;
+use crate::{appstate::AppState, error::HandleErr, utils::totp};
+use rand_core::RngCore;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//验证器App中显示的发行方
+const ISSUER: &str = "MiniProgram";
+//每次生成的备用验证码个数
+const BACKUP_CODES: usize = 10;
+
+//验证码可以是验证器App中的6位数字, 也可以是备用验证码
+#[derive(Debug, Deserialize)]
+pub struct MfaCode {
+    pub code: String,
+}
+
+//待确认的密钥, 在验证器App中添加后用验证码确认开启
+#[derive(Debug, Serialize)]
+pub struct MfaSetup {
+    pub secret: String,
+    pub uri: String,
+}
+
+pub struct MfaOp;
+impl MfaOp {
+    //生成新的密钥, 已开启时须先关闭
+    pub async fn setup<T: From<&'static str>>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<MfaSetup, HandleErr<T>> {
+        let user = Self::user(user_id, state).await?;
+        if user.totp_enabled {
+            return Err(HandleErr::BadRequest(-1, "已开启二次验证".into()));
+        }
+        let secret = totp::generate_secret();
+        users::ActiveModel {
+            user_id: Set(user_id),
+            totp_secret: Set(Some(secret.clone())),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})生成二次验证密钥", user_id);
+        Ok(MfaSetup {
+            uri: totp::uri(ISSUER, &user.user_name, &secret),
+            secret,
+        })
+    }
+
+    //用验证器App中的验证码确认密钥并开启, 返回备用验证码, 只展示这一次
+    //开启前签发的其他会话全部撤销, 当前会话视为已验证
+    pub async fn enable<T: From<&'static str>>(
+        user_id: Uuid,
+        session_id: Uuid,
+        code: &str,
+        state: &AppState,
+    ) -> Result<Vec<String>, HandleErr<T>> {
+        let user = Self::user(user_id, state).await?;
+        if user.totp_enabled {
+            return Err(HandleErr::BadRequest(-1, "已开启二次验证".into()));
+        }
+        let secret = user
+            .totp_secret
+            .as_deref()
+            .ok_or(HandleErr::BadRequest(-1, "请先生成二次验证密钥".into()))?;
+        let step = totp::verify(secret, code, chrono::Utc::now().timestamp(), 0)
+            .ok_or(HandleErr::BadRequest(-1, "验证码错误".into()))?;
+        let codes = backup_codes();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        users::ActiveModel {
+            user_id: Set(user_id),
+            totp_enabled: Set(true),
+            totp_last_step: Set(step),
+            totp_backup_codes: Set(codes.iter().map(|e| digest(user_id, e)).collect()),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let revoked = SessionOp::revoke_all(user_id, Some(session_id), &txn).await?;
+        SessionOp::mfa_verified(session_id, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})开启二次验证, 撤销其他{}个会话", user_id, revoked);
+        Ok(codes)
+    }
+
+    //关闭须验证当前的验证码或备用验证码
+    pub async fn disable<T: From<&'static str>>(
+        user_id: Uuid,
+        code: &str,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let user = Self::enabled(user_id, code, state).await?;
+        users::ActiveModel {
+            user_id: Set(user.user_id),
+            totp_secret: Set(None),
+            totp_enabled: Set(false),
+            totp_last_step: Set(0),
+            totp_backup_codes: Set(vec![]),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        warn!("用户({})关闭二次验证", user_id);
+        Ok(())
+    }
+
+    //重新生成备用验证码, 旧的全部作废
+    pub async fn backup<T: From<&'static str>>(
+        user_id: Uuid,
+        code: &str,
+        state: &AppState,
+    ) -> Result<Vec<String>, HandleErr<T>> {
+        Self::enabled(user_id, code, state).await?;
+        let codes = backup_codes();
+        users::ActiveModel {
+            user_id: Set(user_id),
+            totp_backup_codes: Set(codes.iter().map(|e| digest(user_id, e)).collect()),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})重新生成备用验证码", user_id);
+        Ok(codes)
+    }
+
+    //校验二次验证码, 时间步与备用验证码都只能使用一次, 以条件更新防止并发重放
+    pub async fn verify<T>(
+        user: &users::Model,
+        code: &str,
+        state: &AppState,
+    ) -> Result<bool, HandleErr<T>> {
+        let Some(secret) = user.totp_secret.as_deref().filter(|_| user.totp_enabled) else {
+            return Ok(false);
+        };
+        let now = chrono::Utc::now().timestamp();
+        let update = match totp::verify(secret, code, now, user.totp_last_step) {
+            Some(step) => Users::update_many()
+                .col_expr(users::Column::TotpLastStep, Expr::value(step))
+                .filter(users::Column::TotpLastStep.lt(step)),
+            None => {
+                let hash = digest(user.user_id, code);
+                Users::update_many()
+                    .col_expr(
+                        users::Column::TotpBackupCodes,
+                        Expr::cust_with_values("array_remove(totp_backup_codes, $1)", [&hash]),
+                    )
+                    .filter(Expr::cust_with_values(
+                        "$1 = any(totp_backup_codes)",
+                        [&hash],
+                    ))
+            }
+        };
+        let rows_affected = update
+            .filter(users::Column::UserId.eq(user.user_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            warn!("用户({})二次验证码错误", user.user_name);
+        }
+        Ok(rows_affected > 0)
+    }
+
+    async fn user<T: From<&'static str>>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<users::Model, HandleErr<T>> {
+        Users::find_by_id(user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".into()))
+    }
+
+    //已开启且验证码正确
+    async fn enabled<T: From<&'static str>>(
+        user_id: Uuid,
+        code: &str,
+        state: &AppState,
+    ) -> Result<users::Model, HandleErr<T>> {
+        let user = Self::user(user_id, state).await?;
+        if !user.totp_enabled {
+            return Err(HandleErr::BadRequest(-1, "未开启二次验证".into()));
+        }
+        if !Self::verify(&user, code, state).await? {
+            return Err(HandleErr::BadRequest(-1, "验证码错误".into()));
+        }
+        Ok(user)
+    }
+}
+
+//形如 1a2b3-c4d5e 的备用验证码
+fn backup_codes() -> Vec<String> {
+    (0..BACKUP_CODES)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rand_core::OsRng.fill_bytes(&mut bytes);
+            let hex: String = bytes.iter().map(|e| format!("{:02x}", e)).collect();
+            format!("{}-{}", &hex[..5], &hex[5..])
+        })
+        .collect()
+}
+
+//忽略大小写与连字符, 以用户id加盐的SHA-256, 十六进制
+fn digest(user_id: Uuid, code: &str) -> String {
+    let code: String = code
+        .trim()
+        .chars()
+        .filter(|e| *e != '-')
+        .map(|e| e.to_ascii_lowercase())
+        .collect();
+    let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+    ctx.update(user_id.as_bytes());
+    ctx.update(code.as_bytes());
+    ctx.finish()
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect()
+}
+
+#[test]
+fn test_backup_codes() {
+    let codes = backup_codes();
+    assert_eq!(codes.len(), BACKUP_CODES);
+    assert!(codes
+        .iter()
+        .all(|e| e.len() == 11 && e.chars().nth(5) == Some('-')));
+    let user_id = Uuid::new_v4();
+    assert_eq!(
+        digest(user_id, "1A2B3-C4D5E"),
+        digest(user_id, " 1a2b3c4d5e ")
+    );
+    assert_ne!(digest(user_id, &codes[0]), digest(user_id, &codes[1]));
+    assert_ne!(
+        digest(user_id, &codes[0]),
+        digest(Uuid::new_v4(), &codes[0])
+    );
+}
@@ -1,4 +1,33 @@
+pub mod account;
+pub mod api_key;
+pub mod audit;
+pub mod blacklist;
+pub mod contact;
+pub mod coupon;
 pub mod court;
 pub mod db;
+pub mod feedback;
+pub mod finance;
+pub mod follow;
+pub mod gift_card;
+pub mod mfa;
+pub mod money;
+pub mod notify;
 pub mod order;
+pub mod package;
+pub mod partner;
+pub mod password;
+pub mod payment;
+pub mod points;
+pub mod pricing;
+pub mod promotion;
+pub mod referral;
+pub mod session;
+pub mod staff;
+pub mod storage;
+pub mod tier;
+pub mod upload;
 pub mod user;
+pub mod venue;
+pub mod wallet;
+pub mod wechat;
@@ -0,0 +1,30 @@
+use rust_decimal::RoundingStrategy;
+use sea_orm::prelude::{DateTime, Decimal};
+
+//最小金额单位, 抵扣后至少支付0.01元
+pub const CENT: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+
+//金额保留两位小数, 四舍五入
+pub fn round(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+//预约时长, 小时
+pub fn hours(start: DateTime, end: DateTime) -> Decimal {
+    Decimal::from((end - start).num_minutes()) / Decimal::from(60)
+}
+
+//次卡时数等非金额数值参与计价时转换, 保留两位小数
+pub fn from_f64(value: f64) -> Decimal {
+    round(Decimal::try_from(value).unwrap_or_default())
+}
+
+#[test]
+fn test_money() {
+    assert_eq!(round(Decimal::new(1005, 3)), Decimal::new(101, 2));
+    assert_eq!(round(Decimal::new(-1005, 3)), Decimal::new(-101, 2));
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let t = |h, m| day.and_hms_opt(h, m, 0).unwrap();
+    assert_eq!(hours(t(18, 0), t(19, 30)), Decimal::new(15, 1));
+    assert_eq!(from_f64(0.1 + 0.2), Decimal::new(3, 1));
+}
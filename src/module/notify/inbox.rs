@@ -0,0 +1,281 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        courts, orders,
+        prelude::{Orders, UserNotifications},
+        sea_orm_active_enums::{NotificationKind, OrderState},
+        user_notifications, venues,
+    },
+    module::follow::FollowOp,
+};
+use sea_orm::{
+    sea_query::Expr, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, EntityTrait, JoinType,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const TITLE_LEN: usize = 50;
+const CONTENT_LEN: usize = 500;
+//每批写入的通知数, 避免超出数据库单条语句65535个参数的限制
+const INSERT_CHUNK: usize = 1000;
+
+//标记已读, 未指定通知时全部标记为已读
+#[derive(Debug, Deserialize, Clone)]
+pub struct InboxRead {
+    #[serde(default)]
+    pub notification_ids: Option<Vec<Uuid>>,
+}
+
+//公告通知的对象, 默认为有未开始预约的用户与关注者
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnounceTarget {
+    #[default]
+    All,
+    Booked,
+    Followers,
+}
+
+pub struct InboxOp;
+impl InboxOp {
+    //写入一条站内通知, 与触发通知的业务在同一事务中
+    pub async fn push<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        kind: NotificationKind,
+        title: &str,
+        content: &str,
+        order_id: Option<Uuid>,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        UserNotifications::insert(model(user_id, kind, title, content, order_id))
+            .exec_without_returning(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //场馆公告发给在该场馆有未开始预约的用户和/或关注者, 返回通知人数
+    //开启推送的关注者同时通过通知渠道推送
+    pub async fn announce<T>(
+        venue: &venues::Model,
+        target: AnnounceTarget,
+        state: &AppState,
+    ) -> Result<usize, HandleErr<T>> {
+        let booked: Vec<Uuid> = if target == AnnounceTarget::Followers {
+            vec![]
+        } else {
+            Self::booked(venue.venue_id, state).await?
+        };
+        let followers = if target == AnnounceTarget::Booked {
+            vec![]
+        } else {
+            FollowOp::followers(venue.venue_id, &state.db).await?
+        };
+        let user_ids = recipients(booked, followers.iter().map(|e| e.user_id));
+        if user_ids.is_empty() {
+            return Ok(0);
+        }
+        let title = format!("{}公告", venue.venue_name);
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        for chunk in user_ids.chunks(INSERT_CHUNK) {
+            UserNotifications::insert_many(chunk.iter().map(|e| {
+                model(
+                    *e,
+                    NotificationKind::Announcement,
+                    &title,
+                    &venue.announcement,
+                    None,
+                )
+            }))
+            .exec_without_returning(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        //推送耗时与关注人数成正比, 放到后台发送, 不阻塞请求
+        let pushes: Vec<(Uuid, String)> = followers
+            .into_iter()
+            .filter(|e| e.push)
+            .filter_map(|e| Some((e.user_id, e.phone?)))
+            .collect();
+        let notifier = state.notifier.clone();
+        let content = venue.announcement.clone();
+        tokio::spawn(async move {
+            for (user_id, phone) in pushes {
+                if let Err(err) = notifier.send(&phone, &title, &content).await {
+                    warn!("用户({})公告推送失败: {}", user_id, err);
+                }
+            }
+        });
+        info!("场馆({})公告已通知{}位用户", venue.venue_id, user_ids.len());
+        Ok(user_ids.len())
+    }
+
+    //在场馆有未开始预约的用户
+    async fn booked<T>(venue_id: Uuid, state: &AppState) -> Result<Vec<Uuid>, HandleErr<T>> {
+        Orders::find()
+            .select_only()
+            .column(orders::Column::UserId)
+            .distinct()
+            .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+            .filter(
+                courts::Column::VenueId
+                    .eq(venue_id)
+                    .and(orders::Column::Status.is_in([OrderState::Paid, OrderState::Confirmed]))
+                    .and(orders::Column::AptStart.gt(chrono::Utc::now().naive_utc())),
+            )
+            .into_tuple()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //通知列表, 最新的在前, 返回通知与总数
+    pub async fn list<T>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<user_notifications::Model>, u64), HandleErr<T>> {
+        let paginator = UserNotifications::find()
+            .filter(user_notifications::Column::UserId.eq(user_id))
+            .order_by_desc(user_notifications::Column::CreateTime)
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let notifications = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((notifications, total))
+    }
+
+    pub async fn unread<T>(user_id: Uuid, state: &AppState) -> Result<u64, HandleErr<T>> {
+        UserNotifications::find()
+            .filter(
+                user_notifications::Column::UserId
+                    .eq(user_id)
+                    .and(user_notifications::Column::IsRead.eq(false)),
+            )
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //标记已读, 只修改本人的通知, 返回修改条数
+    pub async fn read<T>(
+        user_id: Uuid,
+        notification_ids: Option<Vec<Uuid>>,
+        state: &AppState,
+    ) -> Result<u64, HandleErr<T>> {
+        let mut update = UserNotifications::update_many()
+            .col_expr(user_notifications::Column::IsRead, Expr::value(true))
+            .filter(
+                user_notifications::Column::UserId
+                    .eq(user_id)
+                    .and(user_notifications::Column::IsRead.eq(false)),
+            );
+        if let Some(ids) = notification_ids {
+            update = update.filter(user_notifications::Column::NotificationId.is_in(ids));
+        }
+        Ok(update
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+}
+
+fn model(
+    user_id: Uuid,
+    kind: NotificationKind,
+    title: &str,
+    content: &str,
+    order_id: Option<Uuid>,
+) -> user_notifications::ActiveModel {
+    user_notifications::ActiveModel {
+        notification_id: NotSet,
+        user_id: Set(user_id),
+        kind: Set(kind),
+        title: Set(truncate(title, TITLE_LEN)),
+        content: Set(truncate(content, CONTENT_LEN)),
+        order_id: Set(order_id),
+        is_read: NotSet,
+        create_time: NotSet,
+    }
+}
+
+//合并通知对象, 既有预约又关注的用户只通知一次
+fn recipients(booked: Vec<Uuid>, followers: impl Iterator<Item = Uuid>) -> Vec<Uuid> {
+    booked
+        .into_iter()
+        .chain(followers)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+//按字符截断, 超出时以省略号结尾
+fn truncate(s: &str, len: usize) -> String {
+    if s.chars().count() <= len {
+        return s.to_string();
+    }
+    let mut s: String = s.chars().take(len - 1).collect();
+    s.push('…');
+    s
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("球场公告", 4), "球场公告");
+    assert_eq!(truncate("球场临时维护公告", 4), "球场临…");
+    assert_eq!(truncate("球场临时维护公告", 4).chars().count(), 4);
+}
+
+#[test]
+fn test_recipients() {
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    let users = recipients(vec![a, b, a], [b].into_iter());
+    assert_eq!(users.len(), 2);
+    assert!(users.contains(&a) && users.contains(&b));
+    assert!(recipients(vec![], std::iter::empty()).is_empty());
+}
@@ -0,0 +1,50 @@
+use crate::cfg::NotifyCfg;
+use serde_json::json;
+use tracing::info;
+pub mod inbox;
+pub mod subscribe;
+
+//通知发送渠道, 按配置选择
+//webhook将消息转发给短信/订阅消息网关, 由网关负责实际下发
+#[derive(Debug, Clone)]
+pub enum Notifier {
+    Log,
+    Webhook {
+        client: reqwest::Client,
+        url: String,
+    },
+}
+
+impl Notifier {
+    pub fn new(cfg: &NotifyCfg) -> Self {
+        match cfg {
+            NotifyCfg::Log => Notifier::Log,
+            NotifyCfg::Webhook { url } => Notifier::Webhook {
+                client: reqwest::Client::new(),
+                url: url.clone(),
+            },
+        }
+    }
+
+    pub async fn send(&self, phone: &str, title: &str, content: &str) -> crate::App::Result<()> {
+        match self {
+            Notifier::Log => {
+                info!("通知({}) {}: {}", phone, title, content);
+                Ok(())
+            }
+            Notifier::Webhook { client, url } => {
+                client
+                    .post(url)
+                    .json(&json!({
+                        "phone":phone,
+                        "title":title,
+                        "content":content
+                    }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        prelude::SubscribeConsents, sea_orm_active_enums::SubscribeStatus, subscribe_consents,
+    },
+};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//wx.requestSubscribeMessage的授权结果, 模板id → accept/reject/ban/filter
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubscribeRecord {
+    pub results: HashMap<String, SubscribeStatus>,
+}
+
+pub struct SubscribeOp;
+impl SubscribeOp {
+    //记录授权结果, 每次允许增加一次下发次数, 拒绝后清空, 未配置的模板忽略
+    pub async fn record<T>(
+        user_id: Uuid,
+        schema: SubscribeRecord,
+        state: &AppState,
+    ) -> Result<Vec<subscribe_consents::Model>, HandleErr<T>> {
+        let results = known(&state.cfg.subscribecfg.templates(), schema.results);
+        if !results.is_empty() {
+            let now = chrono::Utc::now().naive_utc();
+            let txn = state.db.begin().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            for (template_id, status) in results {
+                let accepted = status == SubscribeStatus::Accept;
+                let mut on_conflict = OnConflict::columns([
+                    subscribe_consents::Column::UserId,
+                    subscribe_consents::Column::TemplateId,
+                ]);
+                if accepted {
+                    on_conflict
+                        .update_columns([
+                            subscribe_consents::Column::Status,
+                            subscribe_consents::Column::AcceptTime,
+                            subscribe_consents::Column::UpdateTime,
+                        ])
+                        .value(
+                            subscribe_consents::Column::Quota,
+                            Expr::col((
+                                subscribe_consents::Entity,
+                                subscribe_consents::Column::Quota,
+                            ))
+                            .add(1),
+                        );
+                } else {
+                    on_conflict.update_columns([
+                        subscribe_consents::Column::Status,
+                        subscribe_consents::Column::Quota,
+                        subscribe_consents::Column::UpdateTime,
+                    ]);
+                }
+                SubscribeConsents::insert(subscribe_consents::ActiveModel {
+                    user_id: Set(user_id),
+                    template_id: Set(template_id),
+                    status: Set(status),
+                    quota: Set(accepted as i32),
+                    accept_time: Set(accepted.then_some(now)),
+                    update_time: Set(now),
+                })
+                .on_conflict(on_conflict)
+                .exec_without_returning(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            }
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            info!("用户({})更新订阅消息授权", user_id);
+        }
+        Self::list(user_id, state).await
+    }
+
+    pub async fn list<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<subscribe_consents::Model>, HandleErr<T>> {
+        SubscribeConsents::find()
+            .filter(subscribe_consents::Column::UserId.eq(user_id))
+            .order_by_asc(subscribe_consents::Column::TemplateId)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //下发订阅消息, 先扣减一次授权次数, 没有次数时不请求微信接口
+    //返回是否已送达, 未送达时由调用方改用其他渠道
+    pub async fn send(
+        user_id: Uuid,
+        openid: &str,
+        template_id: &str,
+        page: &str,
+        data: &[(&str, String)],
+        state: &AppState,
+    ) -> bool {
+        let Some(wechat) = &state.wechat else {
+            return false;
+        };
+        match Self::adjust(user_id, template_id, -1, &state.db).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("用户({})没有模板({})的授权次数, 跳过", user_id, template_id);
+                return false;
+            }
+            Err(err) => {
+                warn!("扣减订阅消息次数失败: {}", err);
+                return false;
+            }
+        }
+        match wechat.subscribe_send(openid, template_id, page, data).await {
+            Ok(true) => true,
+            //微信侧已无次数, 与记录不一致时清空
+            Ok(false) => {
+                warn!(
+                    "用户({})在微信侧没有模板({})的授权次数",
+                    user_id, template_id
+                );
+                if let Err(err) = SubscribeConsents::update_many()
+                    .col_expr(subscribe_consents::Column::Quota, Expr::value(0))
+                    .filter(subscribe_consents::Column::UserId.eq(user_id))
+                    .filter(subscribe_consents::Column::TemplateId.eq(template_id))
+                    .exec(&state.db)
+                    .await
+                {
+                    warn!("清空订阅消息次数失败: {}", err);
+                }
+                false
+            }
+            //请求失败时次数未被消耗, 退回
+            Err(err) => {
+                warn!("用户({})订阅消息发送失败: {}", user_id, err);
+                if let Err(err) = Self::adjust(user_id, template_id, 1, &state.db).await {
+                    warn!("退回订阅消息次数失败: {}", err);
+                }
+                false
+            }
+        }
+    }
+
+    //增减授权次数, 扣减时次数不足返回false
+    async fn adjust<C: ConnectionTrait>(
+        user_id: Uuid,
+        template_id: &str,
+        delta: i32,
+        db: &C,
+    ) -> Result<bool, sea_orm::DbErr> {
+        let result = SubscribeConsents::update_many()
+            .col_expr(
+                subscribe_consents::Column::Quota,
+                Expr::col(subscribe_consents::Column::Quota).add(delta),
+            )
+            .filter(subscribe_consents::Column::UserId.eq(user_id))
+            .filter(subscribe_consents::Column::TemplateId.eq(template_id))
+            .filter(subscribe_consents::Column::Quota.gte(-delta))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+}
+
+//只保留已配置的模板
+fn known(
+    templates: &[&str],
+    results: HashMap<String, SubscribeStatus>,
+) -> Vec<(String, SubscribeStatus)> {
+    results
+        .into_iter()
+        .filter(|(k, _)| templates.contains(&k.as_str()))
+        .collect()
+}
+
+#[test]
+fn test_known() {
+    let results = serde_json::from_str::<SubscribeRecord>(
+        r#"{"results":{"tmpl_a":"accept","tmpl_b":"reject","tmpl_c":"ban"}}"#,
+    )
+    .unwrap()
+    .results;
+    let mut results = known(&["tmpl_a", "tmpl_b"], results);
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        results,
+        vec![
+            ("tmpl_a".to_string(), SubscribeStatus::Accept),
+            ("tmpl_b".to_string(), SubscribeStatus::Reject)
+        ]
+    );
+    assert!(known(&[], HashMap::new()).is_empty());
+}
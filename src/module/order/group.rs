@@ -0,0 +1,615 @@
+use super::{refund::RefundOp, OrderOp};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::{
+            order_participants, orders,
+            prelude::{OrderParticipants, Orders},
+            sea_orm_active_enums::{
+                LedgerKind, OrderState, PayMethod, RefundReason, ShareStatus, WalletTxnKind,
+            },
+        },
+        finance::{self, LedgerOp},
+        payment::{
+            notification::NotificationOp,
+            wechat::{from_fen, to_fen, RequestPayment, Transaction},
+            ATTACH_GROUP_SHARE,
+        },
+        wallet::{Change, WalletOp},
+    },
+};
+use rust_decimal::RoundingStrategy;
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//拼单付款时限, 分钟, 不晚于预约开始时间
+pub const GROUP_PAY_MINUTES: i64 = 30;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GroupCreate {
+    #[serde(flatten)]
+    pub order: super::SubmitOrder,
+    //参与人数, 包括发起人
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GroupSchema {
+    pub order: orders::Model,
+    //已支付金额
+    pub paid: Decimal,
+    pub participants: Vec<order_participants::Model>,
+}
+
+pub struct GroupOp;
+impl GroupOp {
+    //为新订单生成分摊, 发起人领取第一份
+    pub async fn open<T>(
+        order: &orders::Model,
+        size: usize,
+        state: &AppState,
+    ) -> Result<GroupSchema, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let deadline = order
+            .apt_start
+            .min(now + chrono::Duration::minutes(GROUP_PAY_MINUTES));
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let order = orders::ActiveModel {
+            order_id: Set(order.order_id),
+            pay_deadline: Set(Some(deadline)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let mut participants = vec![];
+        for (i, share) in split(order.cost + order.deposit, size)
+            .into_iter()
+            .enumerate()
+        {
+            let participant = order_participants::ActiveModel {
+                participant_id: NotSet,
+                order_id: Set(order.order_id),
+                user_id: Set((i == 0).then_some(order.user_id)),
+                share: Set(share),
+                status: NotSet,
+                pay_method: NotSet,
+                transaction_id: NotSet,
+                paid_time: NotSet,
+                create_time: NotSet,
+            }
+            .insert(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            participants.push(participant);
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(GroupSchema {
+            order,
+            paid: Decimal::ZERO,
+            participants,
+        })
+    }
+
+    //拼单详情, 通过分摊链接查看
+    pub async fn of_share<T: From<&'static str>>(
+        participant_id: Uuid,
+        state: &AppState,
+    ) -> Result<GroupSchema, HandleErr<T>> {
+        let (_, order) = OrderParticipants::find_by_id(participant_id)
+            .find_also_related(Orders)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "拼单不存在".into()))?;
+        let order = order.ok_or(HandleErr::BadRequest(-1, "拼单不存在".into()))?;
+        let participants = OrderParticipants::find()
+            .filter(order_participants::Column::OrderId.eq(order.order_id))
+            .order_by_asc(order_participants::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let paid = participants
+            .iter()
+            .filter(|e| e.status == ShareStatus::Paid)
+            .map(|e| e.share)
+            .sum();
+        Ok(GroupSchema {
+            order,
+            paid,
+            participants,
+        })
+    }
+
+    //支付一份分摊, 未领取的分摊由当前用户领取
+    //余额支付即时完成, 微信支付返回调起支付的参数, 支付通知后分摊才变为已支付
+    //全部分摊支付后订单变为已支付
+    pub async fn pay<T: From<String>>(
+        participant_id: Uuid,
+        user_id: Uuid,
+        method: PayMethod,
+        openid: Option<&str>,
+        state: &AppState,
+    ) -> Result<(order_participants::Model, Option<RequestPayment>), HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let (order, share) = Self::claim(participant_id, user_id, &txn).await?;
+        match method {
+            PayMethod::Balance => {
+                //先关闭微信支付单, 关闭失败(如已支付)时不扣款
+                state.payment.close(participant_id).await.map_err(|err| {
+                    warn!("拼单分摊({})关闭支付失败: {}", participant_id, err);
+                    HandleErr::BadRequest(-1, "分摊支付处理中, 请稍后刷新".to_string().into())
+                })?;
+                WalletOp::change(
+                    user_id,
+                    -share.share,
+                    Change {
+                        kind: WalletTxnKind::Payment,
+                        order_id: Some(order.order_id),
+                        recharge_id: None,
+                        admin_id: None,
+                        remark: "拼单分摊支付",
+                    },
+                    &txn,
+                )
+                .await?;
+                let share =
+                    Self::settle(&order, &share, PayMethod::Balance, None, state, &txn).await?;
+                txn.commit().await.map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+                Ok((share, None))
+            }
+            PayMethod::Wechat => {
+                //领取后即提交, 支付结果以支付通知为准
+                txn.commit().await.map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+                let description = format!("拼单 {}", order.apt_start.format("%m-%d %H:%M"));
+                let payment = state
+                    .payment
+                    .prepay(
+                        participant_id,
+                        ATTACH_GROUP_SHARE,
+                        &description,
+                        share.share,
+                        openid,
+                    )
+                    .await
+                    .map_err(|err| {
+                        warn!("拼单分摊({})发起支付失败: {}", participant_id, err);
+                        HandleErr::BadRequest(-1, "发起支付失败, 请稍后重试".to_string().into())
+                    })?
+                    .ok_or(HandleErr::BadRequest(
+                        -1,
+                        "未开通线上支付, 请使用余额支付".to_string().into(),
+                    ))?;
+                Ok((share, Some(payment)))
+            }
+            _ => Err(HandleErr::BadRequest(
+                -1,
+                "不支持的支付方式".to_string().into(),
+            )),
+        }
+    }
+
+    //分摊的微信支付通知, 以participant_id作为商户订单号
+    //金额不符时只记录到订单时间线, 不标记为已支付, 由财务核对处理
+    //拼单已结束或该份已由其他方式支付时, 记为已支付后原路退回
+    pub async fn paid<T: From<String>>(
+        transaction: &Transaction,
+        state: &AppState,
+    ) -> Result<order_participants::Model, HandleErr<T>> {
+        let participant_id = Uuid::parse_str(&transaction.out_trade_no).map_err(|_| {
+            HandleErr::BadRequest(
+                -1,
+                format!("out_trade_no无效: {}", transaction.out_trade_no).into(),
+            )
+        })?;
+        let now = chrono::Utc::now().naive_utc();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let (share, order) = Self::lock(participant_id, &txn).await?;
+        if share.transaction_id.is_some() || !NotificationOp::claim(transaction, &txn).await? {
+            info!("拼单分摊({})重复的支付通知", participant_id);
+            return Ok(share);
+        }
+        if transaction.amount.total != to_fen(share.share) {
+            error!(
+                "拼单分摊({})支付金额{:.2}元与应付金额{:.2}元不一致",
+                participant_id,
+                from_fen(transaction.amount.total),
+                share.share
+            );
+            OrderOp::record(
+                order.order_id,
+                "pay_mismatch",
+                json!({
+                    "participant_id":participant_id,
+                    "transaction_id":transaction.transaction_id,
+                    "amount":from_fen(transaction.amount.total)
+                }),
+                &txn,
+            )
+            .await?;
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            return Ok(share);
+        }
+        let open = share.status == ShareStatus::Pending
+            && order.status == OrderState::PendingPayment
+            && order.pay_deadline.is_some_and(|e| e > now);
+        let share = Self::settle(
+            &order,
+            &share,
+            PayMethod::Wechat,
+            Some(transaction.transaction_id.clone()),
+            state,
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if !open {
+            warn!("拼单分摊({})在拼单结束后支付, 原路退回", participant_id);
+            RefundOp::share::<T>(
+                &order,
+                participant_id,
+                "拼单已结束",
+                RefundReason::PaymentIssue,
+                state,
+            )
+            .await?;
+        }
+        Ok(share)
+    }
+
+    //锁定分摊与订单
+    async fn lock<T: From<String>, C: ConnectionTrait>(
+        participant_id: Uuid,
+        db: &C,
+    ) -> Result<(order_participants::Model, orders::Model), HandleErr<T>> {
+        let share = OrderParticipants::find_by_id(participant_id)
+            .lock_exclusive()
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "拼单不存在".to_string().into()))?;
+        let order = Orders::find_by_id(share.order_id)
+            .lock_exclusive()
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "拼单不存在".to_string().into()))?;
+        Ok((share, order))
+    }
+
+    //校验拼单仍在进行且该份待支付, 未领取的分摊由当前用户领取
+    async fn claim<T: From<String>, C: ConnectionTrait>(
+        participant_id: Uuid,
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<(orders::Model, order_participants::Model), HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let (share, order) = Self::lock(participant_id, db).await?;
+        if order.status != OrderState::PendingPayment || order.pay_deadline.is_none_or(|e| e <= now)
+        {
+            return Err(HandleErr::BadRequest(-1, "拼单已结束".to_string().into()));
+        }
+        if share.status != ShareStatus::Pending {
+            return Err(HandleErr::BadRequest(-1, "该份已支付".to_string().into()));
+        }
+        match share.user_id {
+            Some(e) if e != user_id => Err(HandleErr::BadRequest(
+                -1,
+                "该份已被他人领取".to_string().into(),
+            )),
+            Some(_) => Ok((order, share)),
+            None => {
+                let joined = OrderParticipants::find()
+                    .filter(
+                        order_participants::Column::OrderId
+                            .eq(order.order_id)
+                            .and(order_participants::Column::UserId.eq(user_id)),
+                    )
+                    .count(db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?;
+                if joined > 0 {
+                    return Err(HandleErr::BadRequest(-1, "已参与该拼单".to_string().into()));
+                }
+                let share = order_participants::ActiveModel {
+                    participant_id: Set(share.participant_id),
+                    user_id: Set(Some(user_id)),
+                    ..Default::default()
+                }
+                .update(db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+                Ok((order, share))
+            }
+        }
+    }
+
+    //分摊变为已支付, 全部分摊支付后订单变为已支付
+    async fn settle<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        share: &order_participants::Model,
+        method: PayMethod,
+        transaction_id: Option<String>,
+        state: &AppState,
+        db: &C,
+    ) -> Result<order_participants::Model, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let share = order_participants::ActiveModel {
+            participant_id: Set(share.participant_id),
+            status: Set(ShareStatus::Paid),
+            pay_method: Set(Some(method.clone())),
+            transaction_id: Set(transaction_id),
+            paid_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        OrderOp::record(
+            order.order_id,
+            "pay",
+            json!({"participant_id":share.participant_id, "method":method, "amount":share.share}),
+            db,
+        )
+        .await?;
+        LedgerOp::record(
+            order,
+            LedgerKind::Payment,
+            share.share,
+            finance::share_deposit(order, share.share),
+            Some(method),
+            state,
+            db,
+        )
+        .await?;
+        if order.status != OrderState::PendingPayment {
+            return Ok(share);
+        }
+        let shares = OrderParticipants::find()
+            .filter(order_participants::Column::OrderId.eq(order.order_id))
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if shares.iter().all(|e| e.status == ShareStatus::Paid) {
+            let paid = OrderOp::transit(order, OrderState::Paid, db).await?;
+            orders::ActiveModel {
+                order_id: Set(paid.order_id),
+                pay_amount: Set(Some(shares.iter().map(|e| e.share).sum())),
+                paid_time: Set(Some(now)),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            info!("拼单({})已全部支付", order.order_id);
+        }
+        Ok(share)
+    }
+
+    //已取消拼单中已支付的分摊逐份原路退回, 失败只记日志, 退款记录已提交的由退款重试任务补发
+    pub async fn refund_paid(
+        order: &orders::Model,
+        reason: &str,
+        reason_code: RefundReason,
+        state: &AppState,
+    ) -> u64 {
+        let shares = match OrderParticipants::find()
+            .filter(
+                order_participants::Column::OrderId
+                    .eq(order.order_id)
+                    .and(order_participants::Column::Status.eq(ShareStatus::Paid)),
+            )
+            .all(&state.db)
+            .await
+        {
+            Ok(e) => e,
+            Err(err) => {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                return 0;
+            }
+        };
+        let mut refunded = 0;
+        for share in shares {
+            match RefundOp::share::<String>(
+                order,
+                share.participant_id,
+                reason,
+                reason_code.clone(),
+                state,
+            )
+            .await
+            {
+                Ok(Some(_)) => refunded += 1,
+                Ok(None) => {}
+                Err(err) => warn!(
+                    "拼单({})分摊({})退款失败: {:?}",
+                    order.order_id, share.participant_id, err
+                ),
+            }
+        }
+        refunded
+    }
+
+    //超时未付清的拼单取消, 已支付的分摊退款
+    pub async fn expire<T: From<String>>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let orders = Orders::find()
+            .filter(
+                orders::Column::Status
+                    .eq(OrderState::PendingPayment)
+                    .and(orders::Column::PayDeadline.lte(now)),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut expired = 0;
+        for order in orders {
+            let txn = state.db.begin().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            //状态已被其他请求修改时跳过
+            if OrderOp::transit::<T, _>(&order, OrderState::Cancelled, &txn)
+                .await
+                .is_err()
+            {
+                continue;
+            }
+            orders::ActiveModel {
+                order_id: Set(order.order_id),
+                cancel_reason: Set(Some("拼单超时未付清".to_string())),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            let shares = OrderParticipants::update_many()
+                .col_expr(
+                    order_participants::Column::Status,
+                    Expr::value(ShareStatus::Expired),
+                )
+                .filter(
+                    order_participants::Column::OrderId
+                        .eq(order.order_id)
+                        .and(order_participants::Column::Status.eq(ShareStatus::Pending)),
+                )
+                .exec_with_returning(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            //关单后仍到达的支付通知按拼单已结束退回
+            for share in shares {
+                if let Err(err) = state.payment.close(share.participant_id).await {
+                    warn!("拼单分摊({})关闭支付失败: {}", share.participant_id, err);
+                }
+            }
+            Self::refund_paid(&order, "拼单超时未付清", RefundReason::Other, state).await;
+            expired += 1;
+        }
+        Ok(expired)
+    }
+}
+
+//按分均摊, 除不尽的部分由发起人承担
+fn split(total: Decimal, size: usize) -> Vec<Decimal> {
+    let base = (total / Decimal::from(size)).round_dp_with_strategy(2, RoundingStrategy::ToZero);
+    let rest = total - base * Decimal::from(size);
+    (0..size)
+        .map(|i| if i == 0 { base + rest } else { base })
+        .collect()
+}
+
+#[test]
+fn test_split() {
+    let d = |v: i64, scale: u32| Decimal::new(v, scale);
+    assert_eq!(split(d(100, 0), 4), vec![d(25, 0); 4]);
+    assert_eq!(
+        split(d(100, 0), 3),
+        vec![d(3334, 2), d(3333, 2), d(3333, 2)]
+    );
+    assert_eq!(split(d(5, 2), 2), vec![d(3, 2), d(2, 2)]);
+}
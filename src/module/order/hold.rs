@@ -0,0 +1,192 @@
+use super::OrderOp;
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self,
+        prelude::{Courts, SlotHolds},
+    },
+};
+use sea_orm::prelude::DateTime;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, EntityTrait,
+    PaginatorTrait, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+//锁定时长, 分钟
+pub const HOLD_MINUTES: i64 = 10;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HoldCreate {
+    pub court_id: Uuid,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HoldRelease {
+    pub hold_id: Uuid,
+}
+
+pub struct HoldOp;
+impl HoldOp {
+    //锁定时段, 与订单创建相同方式加锁检查冲突
+    //同一用户在同一球场重复锁定时替换之前的锁定
+    pub async fn create<T: From<&'static str>>(
+        user_id: Uuid,
+        schema: HoldCreate,
+        state: &AppState,
+    ) -> Result<db::slot_holds::Model, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Courts::find_by_id(schema.court_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "court_id无效".into()))?;
+        if OrderOp::overlaps(
+            schema.court_id,
+            schema.apt_start,
+            schema.apt_end,
+            None,
+            &txn,
+        )
+        .await?
+        {
+            return Err(HandleErr::BadRequest(-1, "时间冲突".into()));
+        }
+        if Self::is_held(
+            schema.court_id,
+            schema.apt_start,
+            schema.apt_end,
+            user_id,
+            &txn,
+        )
+        .await?
+        {
+            return Err(HandleErr::BadRequest(-1, "该时段正在被他人预订".into()));
+        }
+        //只替换该球场的锁定, 用户在其他球场的锁定不受影响
+        Self::release(user_id, schema.court_id, None, &txn).await?;
+        let now = chrono::Utc::now().naive_utc();
+        let hold = db::slot_holds::ActiveModel {
+            hold_id: NotSet,
+            user_id: Set(user_id),
+            court_id: Set(schema.court_id),
+            hold_start: Set(schema.apt_start),
+            hold_end: Set(schema.apt_end),
+            expire_time: Set(now + chrono::Duration::minutes(HOLD_MINUTES)),
+            create_time: NotSet,
+        }
+        .insert(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(hold)
+    }
+
+    //时段是否被其他用户锁定, 过期的锁定不计
+    pub async fn is_held<T, C: ConnectionTrait>(
+        court_id: Uuid,
+        start: DateTime,
+        end: DateTime,
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let count = SlotHolds::find()
+            .filter(
+                db::slot_holds::Column::CourtId
+                    .eq(court_id)
+                    .and(db::slot_holds::Column::UserId.ne(user_id))
+                    .and(db::slot_holds::Column::ExpireTime.gt(now))
+                    .and(db::slot_holds::Column::HoldStart.lt(end))
+                    .and(db::slot_holds::Column::HoldEnd.gt(start)),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(count > 0)
+    }
+
+    //下单成功或主动放弃时释放用户在该球场的锁定
+    pub async fn release<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        court_id: Uuid,
+        hold_id: Option<Uuid>,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        let mut query = SlotHolds::delete_many().filter(
+            db::slot_holds::Column::UserId
+                .eq(user_id)
+                .and(db::slot_holds::Column::CourtId.eq(court_id)),
+        );
+        if let Some(hold_id) = hold_id {
+            query = query.filter(db::slot_holds::Column::HoldId.eq(hold_id));
+        }
+        Ok(query
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+
+    //清理过期的锁定
+    pub async fn purge<T>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        Ok(SlotHolds::delete_many()
+            .filter(db::slot_holds::Column::ExpireTime.lte(now))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+
+    pub async fn owned<T: From<&'static str>>(
+        hold_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<db::slot_holds::Model, HandleErr<T>> {
+        SlotHolds::find_by_id(hold_id)
+            .filter(db::slot_holds::Column::UserId.eq(user_id))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "锁定不存在".into()))
+    }
+}
@@ -0,0 +1,226 @@
+use super::refund::{self, RefundOp};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        courts, invoices, orders,
+        prelude::{Courts, Invoices, Orders},
+        sea_orm_active_enums::{InvoiceStatus, OrderState},
+    },
+};
+use sea_orm::prelude::Decimal;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, JoinType, QueryFilter, QueryOrder,
+    QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct InvoiceRequest {
+    pub order_id: Uuid,
+    //发票抬头
+    pub title: String,
+    //企业抬头的税号, 个人抬头不填
+    pub tax_number: Option<String>,
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct InvoiceHandle {
+    pub invoice_id: Uuid,
+    //true: 已开具, false: 驳回
+    pub issue: bool,
+    //开具时必填, 发票文件地址
+    pub url: Option<String>,
+    pub reply: Option<String>,
+}
+
+pub struct InvoiceOp;
+impl InvoiceOp {
+    //申请开票, 金额为订单实付扣除已退款部分
+    //被驳回的申请修改信息后重新提交
+    pub async fn request<T: From<String>>(
+        order: &orders::Model,
+        schema: InvoiceRequest,
+        state: &AppState,
+    ) -> Result<invoices::Model, HandleErr<T>> {
+        if !matches!(
+            order.status,
+            OrderState::Paid | OrderState::Confirmed | OrderState::Completed
+        ) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "仅已支付的订单可以开具发票".to_string().into(),
+            ));
+        }
+        let records = RefundOp::of_order(order.order_id, &state.db).await?;
+        let amount = order.cost - refund::refunded(&records);
+        if amount <= Decimal::ZERO {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "订单已全额退款".to_string().into(),
+            ));
+        }
+        let invoice_id = match Self::of_order(order.order_id, &state.db).await? {
+            Some(e) if e.status == InvoiceStatus::Rejected => Set(e.invoice_id),
+            Some(_) => {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "该订单已申请发票".to_string().into(),
+                ));
+            }
+            None => NotSet,
+        };
+        let model = invoices::ActiveModel {
+            invoice_id,
+            order_id: Set(order.order_id),
+            user_id: Set(order.user_id),
+            title: Set(schema.title),
+            tax_number: Set(schema.tax_number),
+            email: Set(schema.email),
+            amount: Set(amount),
+            status: Set(InvoiceStatus::Requested),
+            url: Set(None),
+            admin_id: Set(None),
+            reply: Set(None),
+            create_time: NotSet,
+            handle_time: Set(None),
+        };
+        let res = if model.invoice_id.is_set() {
+            model.update(&state.db).await
+        } else {
+            model.insert(&state.db).await
+        };
+        res.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    pub async fn of_order<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<Option<invoices::Model>, HandleErr<T>> {
+        Invoices::find()
+            .filter(invoices::Column::OrderId.eq(order_id))
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //管理员名下球场待开具的发票
+    pub async fn pending<T>(
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<invoices::Model>, HandleErr<T>> {
+        Invoices::find()
+            .join(JoinType::InnerJoin, invoices::Relation::Orders.def())
+            .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+            .filter(
+                courts::Column::AdminId
+                    .eq(admin_id)
+                    .and(invoices::Column::Status.eq(InvoiceStatus::Requested)),
+            )
+            .order_by_asc(invoices::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //标记为已开具或驳回
+    pub async fn handle<T: From<String>>(
+        schema: InvoiceHandle,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<invoices::Model, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let url = schema
+            .url
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty());
+        if schema.issue && url.is_none() {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "请上传发票文件".to_string().into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let invoice = Invoices::find_by_id(schema.invoice_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "发票申请不存在".to_string().into(),
+            ))?;
+        let court = Orders::find_by_id(invoice.order_id)
+            .find_also_related(Courts)
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .and_then(|(_, court)| court);
+        if court.map(|e| e.admin_id) != Some(admin_id) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "发票申请不存在".to_string().into(),
+            ));
+        }
+        if invoice.status != InvoiceStatus::Requested {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "发票申请已处理".to_string().into(),
+            ));
+        }
+        let invoice = invoices::ActiveModel {
+            invoice_id: Set(invoice.invoice_id),
+            status: Set(if schema.issue {
+                InvoiceStatus::Issued
+            } else {
+                InvoiceStatus::Rejected
+            }),
+            url: Set(url.filter(|_| schema.issue)),
+            admin_id: Set(Some(admin_id)),
+            reply: Set(schema.reply),
+            handle_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(invoice)
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    error::HandleErr,
+    module::{
+        db::{
+            court_addons, courts, order_addons, order_items, orders,
+            prelude::{CourtAddons, OrderAddons, OrderItems},
+            sea_orm_active_enums::OrderItemKind,
+        },
+        money, pricing,
+    },
+};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+//订单附加项目: (名称, 单价, 是否按小时, 数量)
+pub type AddonLine = (String, Decimal, bool, i32);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Item {
+    pub kind: OrderItemKind,
+    pub name: String,
+    pub quantity: i32,
+    pub amount: Decimal,
+}
+
+//订单金额中的抵扣项, 次卡、会员折扣与促销活动抵扣场地费, 优惠券与积分抵扣剩余的应付金额
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Deductions {
+    pub package_hours: f64,
+    pub package_amount: Decimal,
+    pub discount: Decimal,
+    pub points_used: i32,
+    pub points_amount: Decimal,
+    pub promotion_amount: Decimal,
+    pub member_amount: Decimal,
+}
+
+impl From<&orders::Model> for Deductions {
+    fn from(order: &orders::Model) -> Self {
+        Self {
+            package_hours: order.package_hours,
+            package_amount: order.package_amount,
+            discount: order.discount,
+            points_used: order.points_used,
+            points_amount: order.points_amount,
+            promotion_amount: order.promotion_amount,
+            member_amount: order.member_amount,
+        }
+    }
+}
+
+//下单时选择的附加项目转为明细行
+pub fn lines(addons: &[(court_addons::Model, i32)]) -> Vec<AddonLine> {
+    addons
+        .iter()
+        .map(|(addon, quantity)| {
+            (
+                addon.addon_name.clone(),
+                addon.price,
+                addon.per_hour,
+                *quantity,
+            )
+        })
+        .collect()
+}
+
+pub struct ItemOp;
+impl ItemOp {
+    //按订单当前的时段与金额重新生成价格明细
+    pub async fn write<T, C: ConnectionTrait>(
+        order: &orders::Model,
+        court: &courts::Model,
+        addons: &[AddonLine],
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        OrderItems::delete_many()
+            .filter(order_items::Column::OrderId.eq(order.order_id))
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let items = breakdown(
+            court.price_per_hour,
+            order.cost,
+            addons,
+            order.into(),
+            order.deposit,
+            order.apt_start,
+            order.apt_end,
+        );
+        OrderItems::insert_many(items.into_iter().map(|e| order_items::ActiveModel {
+            item_id: NotSet,
+            order_id: Set(order.order_id),
+            kind: Set(e.kind),
+            name: Set(e.name),
+            quantity: Set(e.quantity),
+            amount: Set(e.amount),
+        }))
+        .exec(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    pub async fn of_order<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<order_items::Model>, HandleErr<T>> {
+        OrderItems::find()
+            .filter(order_items::Column::OrderId.eq(order_id))
+            .order_by_asc(order_items::Column::Kind)
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //订单已选附加项目及名称, 改期时重新生成明细用
+    pub async fn addons_of<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<AddonLine>, HandleErr<T>> {
+        Ok(OrderAddons::find()
+            .filter(order_addons::Column::OrderId.eq(order_id))
+            .find_also_related(CourtAddons)
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|(e, addon)| {
+                (
+                    addon.map(|a| a.addon_name).unwrap_or_default(),
+                    e.price,
+                    e.per_hour,
+                    e.quantity,
+                )
+            })
+            .collect())
+    }
+}
+
+//拆分订单金额, 场地费中超出基础价的部分记为时段加价(低于基础价时为负)
+//cost为订单应付金额(含附加项目, 已扣除次卡、会员折扣、活动、优惠券与积分抵扣, 不含押金)
+pub fn breakdown(
+    base_price: Decimal,
+    cost: Decimal,
+    addons: &[AddonLine],
+    deductions: Deductions,
+    deposit: Decimal,
+    start: DateTime,
+    end: DateTime,
+) -> Vec<Item> {
+    let hours = money::hours(start, end);
+    let base = money::round(base_price * hours);
+    let mut items = vec![Item {
+        kind: OrderItemKind::Base,
+        name: format!("场地费{:.1}小时", hours),
+        quantity: 1,
+        amount: base,
+    }];
+    let mut court_cost = cost
+        + deductions.package_amount
+        + deductions.member_amount
+        + deductions.promotion_amount
+        + deductions.discount
+        + deductions.points_amount;
+    for (name, price, per_hour, quantity) in addons {
+        let amount = pricing::addons_cost([(*price, *per_hour, *quantity)], start, end);
+        court_cost -= amount;
+        items.push(Item {
+            kind: OrderItemKind::Addon,
+            name: name.clone(),
+            quantity: *quantity,
+            amount,
+        });
+    }
+    let peak = court_cost - base;
+    if !peak.is_zero() {
+        items.insert(
+            1,
+            Item {
+                kind: OrderItemKind::Peak,
+                name: "时段加价".to_string(),
+                quantity: 1,
+                amount: peak,
+            },
+        );
+    }
+    if deductions.package_amount > Decimal::ZERO {
+        items.push(Item {
+            kind: OrderItemKind::Package,
+            name: format!("次卡抵扣{:.1}小时", deductions.package_hours),
+            quantity: 1,
+            amount: -deductions.package_amount,
+        });
+    }
+    if deductions.member_amount > Decimal::ZERO {
+        items.push(Item {
+            kind: OrderItemKind::Member,
+            name: "会员折扣".to_string(),
+            quantity: 1,
+            amount: -deductions.member_amount,
+        });
+    }
+    if deductions.promotion_amount > Decimal::ZERO {
+        items.push(Item {
+            kind: OrderItemKind::Promotion,
+            name: "活动优惠".to_string(),
+            quantity: 1,
+            amount: -deductions.promotion_amount,
+        });
+    }
+    if deductions.discount > Decimal::ZERO {
+        items.push(Item {
+            kind: OrderItemKind::Discount,
+            name: "优惠券抵扣".to_string(),
+            quantity: 1,
+            amount: -deductions.discount,
+        });
+    }
+    if deductions.points_amount > Decimal::ZERO {
+        items.push(Item {
+            kind: OrderItemKind::Points,
+            name: format!("积分抵扣{}分", deductions.points_used),
+            quantity: 1,
+            amount: -deductions.points_amount,
+        });
+    }
+    if deposit > Decimal::ZERO {
+        items.push(Item {
+            kind: OrderItemKind::Deposit,
+            name: "押金".to_string(),
+            quantity: 1,
+            amount: deposit,
+        });
+    }
+    items
+}
+
+#[test]
+fn test_breakdown() {
+    let d = |v: i64| Decimal::from(v);
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let start = day.and_hms_opt(18, 0, 0).unwrap();
+    let end = day.and_hms_opt(20, 0, 0).unwrap();
+    let addons = vec![("球拍".to_string(), d(10), false, 2)];
+    //基础价50/小时, 高峰场地费160, 附加项目20
+    let items = breakdown(
+        d(50),
+        d(180),
+        &addons,
+        Deductions::default(),
+        d(30),
+        start,
+        end,
+    );
+    let amounts: Vec<_> = items.iter().map(|e| (e.kind.clone(), e.amount)).collect();
+    assert_eq!(
+        amounts,
+        vec![
+            (OrderItemKind::Base, d(100)),
+            (OrderItemKind::Peak, d(60)),
+            (OrderItemKind::Addon, d(20)),
+            (OrderItemKind::Deposit, d(30)),
+        ]
+    );
+    //无加价无押金
+    let items = breakdown(d(50), d(100), &[], Deductions::default(), d(0), start, end);
+    assert_eq!(items.len(), 1);
+    //优惠券抵扣20, 应付80
+    let discount = Deductions {
+        discount: d(20),
+        ..Default::default()
+    };
+    let items = breakdown(d(50), d(80), &[], discount, d(0), start, end);
+    let amounts: Vec<_> = items.iter().map(|e| (e.kind.clone(), e.amount)).collect();
+    assert_eq!(
+        amounts,
+        vec![
+            (OrderItemKind::Base, d(100)),
+            (OrderItemKind::Discount, d(-20))
+        ]
+    );
+    //次卡抵扣1小时, 其余加附加项目由优惠券抵扣10, 应付70
+    let deductions = Deductions {
+        package_hours: 1.0,
+        package_amount: d(50),
+        discount: d(10),
+        ..Default::default()
+    };
+    let items = breakdown(d(50), d(60), &addons, deductions, d(0), start, end);
+    let amounts: Vec<_> = items.iter().map(|e| (e.kind.clone(), e.amount)).collect();
+    assert_eq!(
+        amounts,
+        vec![
+            (OrderItemKind::Base, d(100)),
+            (OrderItemKind::Addon, d(20)),
+            (OrderItemKind::Package, d(-50)),
+            (OrderItemKind::Discount, d(-10))
+        ]
+    );
+    //优惠券抵扣20后再用1000积分抵扣10, 应付70
+    let deductions = Deductions {
+        discount: d(20),
+        points_used: 1000,
+        points_amount: d(10),
+        ..Default::default()
+    };
+    let items = breakdown(d(50), d(70), &[], deductions, d(0), start, end);
+    let amounts: Vec<_> = items.iter().map(|e| (e.kind.clone(), e.amount)).collect();
+    assert_eq!(
+        amounts,
+        vec![
+            (OrderItemKind::Base, d(100)),
+            (OrderItemKind::Discount, d(-20)),
+            (OrderItemKind::Points, d(-10))
+        ]
+    );
+    //会员九折优惠10, 应付90
+    let deductions = Deductions {
+        member_amount: d(10),
+        ..Default::default()
+    };
+    let items = breakdown(d(50), d(90), &[], deductions, d(0), start, end);
+    let amounts: Vec<_> = items.iter().map(|e| (e.kind.clone(), e.amount)).collect();
+    assert_eq!(
+        amounts,
+        vec![
+            (OrderItemKind::Base, d(100)),
+            (OrderItemKind::Member, d(-10))
+        ]
+    );
+}
@@ -0,0 +1,1724 @@
+use super::db::prelude::*;
+use crate::error::HandleErr;
+use crate::utils::{cursor::Cursor, qrcode::Scanned};
+use crate::{
+    appstate::AppState,
+    module::{
+        contact,
+        coupon::CouponOp,
+        court::{
+            addon::{AddonItem, AddonOp},
+            calendar::CalendarOp,
+            open_hours::OpenHoursOp,
+            CourtOp,
+        },
+        db::{
+            court_addons, order_participants, order_timeline, orders, refunds,
+            sea_orm_active_enums::{
+                CourtStatus, DepositStatus, LedgerKind, NotificationKind, OrderState, PayMethod,
+                PointsTxnKind, RefundReason, RefundStatus, ShareStatus,
+            },
+        },
+        finance::LedgerOp,
+        notify::inbox::InboxOp,
+        package::PackageOp,
+        partner::PartnerOp,
+        points::PointsOp,
+        promotion::{self, Applied, PromotionOp},
+        referral::ReferralOp,
+    },
+};
+use hold::HoldOp;
+use item::ItemOp;
+use sea_orm::prelude::{Date, DateTime, Decimal};
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    error::{DbErr, RuntimeErr},
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseTransaction, DbBackend,
+    EntityTrait, FromQueryResult, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+    Statement, TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use state::TransitionErr;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+pub mod group;
+pub mod hold;
+pub mod invoice;
+pub mod item;
+pub mod no_show;
+pub mod pay;
+pub mod refund;
+pub mod remind;
+pub mod series;
+pub mod state;
+pub mod summary;
+
+//postgres排斥约束冲突的错误码
+const ERR_EXCLUSION_VIOLATION: &str = "23P01";
+
+//开始前多久可以签到, 分钟
+pub const CHECKIN_EARLY_MINUTES: i64 = 30;
+
+#[derive(Debug, Deserialize, Serialize, Clone, sea_orm::FromQueryResult)]
+pub struct OrderAdminSchema {
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub court_id: Uuid,
+    pub user_name: String,
+    pub user_phone: Option<String>,
+    pub court_name: String,
+    pub create_time: DateTime,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+    pub cost: Decimal,
+    pub deposit: Decimal,
+    pub deposit_status: DepositStatus,
+    pub status: OrderState,
+    pub check_in_time: Option<DateTime>,
+    //代客预约的顾客
+    pub customer_name: Option<String>,
+    pub customer_phone: Option<String>,
+    //现场联系人
+    pub contact_name: Option<String>,
+    pub contact_phone: Option<String>,
+    pub remark: Option<String>,
+    pub internal_note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, sea_orm::FromQueryResult)]
+pub struct OrderUserSchema {
+    pub order_id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub court_id: Uuid,
+    pub court_name: String,
+    pub court_location: String,
+    pub create_time: DateTime,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+    pub cost: Decimal,
+    pub deposit: Decimal,
+    pub deposit_status: DepositStatus,
+    pub status: OrderState,
+    pub remark: Option<String>,
+    pub contact_name: Option<String>,
+    pub contact_phone: Option<String>,
+}
+
+impl OrderUserSchema {
+    //作为球友加入的预约只展示场地与时间, 不含费用与联系人
+    pub fn view(&self, user_id: Uuid) -> serde_json::Value {
+        if self.user_id == user_id {
+            let mut data = json!(self);
+            data["is_owner"] = json!(true);
+            return data;
+        }
+        json!({
+            "order_id":self.order_id,
+            "court_id":self.court_id,
+            "court_name":self.court_name,
+            "court_location":self.court_location,
+            "apt_start":self.apt_start,
+            "apt_end":self.apt_end,
+            "status":self.status,
+            "is_owner":false
+        })
+    }
+}
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct SaveOrder {
+    pub order_id: Option<Uuid>,
+    pub court_id: Option<Uuid>,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+    pub cost: Decimal,
+    //仅新建时设置, 修改订单不改变押金
+    pub deposit: Option<Decimal>,
+    //仅新建时设置, 同一用户相同的幂等键只生成一个订单
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    //仅新建时设置
+    #[serde(default)]
+    pub remark: Option<String>,
+    //仅新建时设置, 使用的优惠券与抵扣金额, cost为抵扣后的金额
+    #[serde(default)]
+    pub coupon_id: Option<Uuid>,
+    #[serde(default)]
+    pub discount: Option<Decimal>,
+    //仅新建时设置, 次卡抵扣的时数与场地费
+    #[serde(default)]
+    pub package: Option<(f64, Decimal)>,
+    //仅新建时设置, 抵扣的积分与金额
+    #[serde(default)]
+    pub points: Option<(i32, Decimal)>,
+    //仅新建时设置, 参加的促销活动
+    #[serde(default)]
+    pub promotions: Vec<Applied>,
+    //仅新建时设置, 会员折扣金额
+    #[serde(default)]
+    pub member: Option<Decimal>,
+    //仅新建时设置, 现场联系人的姓名与手机号
+    #[serde(default)]
+    pub contact: Option<(String, String)>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubmitOrder {
+    pub court_id: Uuid,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+    //附加项目
+    #[serde(default)]
+    pub addons: Vec<AddonItem>,
+    //给球场的备注, 如"需要2支球拍"
+    #[serde(default)]
+    pub remark: Option<String>,
+    //使用的优惠券
+    #[serde(default)]
+    pub coupon_id: Option<Uuid>,
+    //使用的积分, 超出抵扣上限时按上限抵扣
+    #[serde(default)]
+    pub points: Option<i32>,
+    //支付方式, 默认微信支付, 选择余额时下单后直接扣款
+    #[serde(default)]
+    pub pay_method: Option<PayMethod>,
+    //现场联系人, 从常用联系人中选择
+    #[serde(default)]
+    pub contact_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OfflinePay {
+    pub order_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PayQuery {
+    //默认微信支付
+    #[serde(default)]
+    pub method: Option<PayMethod>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CancelOrder {
+    pub order_id: Uuid,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct UpdateOrder {
+    pub order_id: Uuid,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CheckIn {
+    //扫描球场二维码或订单电子票得到的token
+    pub token: String,
+}
+
+//前台为顾客签到
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeskCheckIn {
+    pub order_id: Uuid,
+}
+
+//某天的签到情况
+#[derive(Debug, Deserialize, Clone)]
+pub struct CheckinListQuery {
+    pub date: Date,
+    pub court_id: Option<Uuid>,
+}
+
+//管理员订单列表, 时间范围按预约开始时间筛选
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderListQuery {
+    //按场馆筛选, 超级管理员可查看任意场馆, 不传时为名下全部球场
+    pub venue_id: Option<Uuid>,
+    pub court_id: Option<Uuid>,
+    pub status: Option<OrderState>,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+    //从1开始
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+    //传入时按游标翻页, 首页传空值, 忽略page
+    pub cursor: Option<String>,
+}
+
+//游标翻页, 首页不传cursor
+#[derive(Debug, Deserialize, Clone)]
+pub struct CursorQuery {
+    pub cursor: Option<String>,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+}
+
+//按用户手机号或昵称搜索订单, 代客预约的订单匹配顾客信息
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderSearch {
+    pub q: String,
+    //按场馆筛选, 同订单列表
+    pub venue_id: Option<Uuid>,
+    //从1开始
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+}
+
+//订单导出, 时间范围按预约开始时间筛选
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExportQuery {
+    pub from: DateTime,
+    pub to: DateTime,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PageQuery {
+    //从1开始
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+}
+
+pub fn default_page() -> u64 {
+    1
+}
+
+pub fn default_page_size() -> u64 {
+    20
+}
+
+//管理员代客预约(电话/到店), 不走线上支付
+#[derive(Debug, Deserialize, Clone)]
+pub struct ManualOrder {
+    pub court_id: Uuid,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+    pub customer_name: String,
+    pub customer_phone: Option<String>,
+}
+
+impl ManualOrder {
+    //顾客姓名不能为空且不超过30字, 手机号可不填, 格式与常用联系人相同
+    pub fn customer(&self) -> Result<(String, Option<String>), &'static str> {
+        let name = self.customer_name.trim();
+        if name.is_empty()
+            || name.chars().count() > contact::NAME_LEN
+            || name.chars().any(char::is_control)
+        {
+            return Err("顾客姓名不能为空且不超过30字");
+        }
+        match self.customer_phone.as_deref().map(str::trim) {
+            Some(phone) if !phone.is_empty() => {
+                let (name, phone) = contact::validate(name, phone)?;
+                Ok((name, Some(phone)))
+            }
+            _ => Ok((name.to_string(), None)),
+        }
+    }
+}
+
+//统计粒度
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    //对应postgres date_trunc的单位
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+            Granularity::Month => "month",
+        }
+    }
+}
+
+//订单统计, 时间范围按预约开始时间筛选
+#[derive(Debug, Deserialize, Clone)]
+pub struct StatsQuery {
+    #[serde(default)]
+    pub granularity: Granularity,
+    pub court_id: Option<Uuid>,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+}
+
+//统计周期内的订单数据
+#[derive(Debug, Serialize, Clone, sea_orm::FromQueryResult)]
+pub struct OrderStats {
+    //周期开始时间
+    pub bucket: DateTime,
+    //有效预约数, 不含已取消/已退款
+    pub bookings: i64,
+    //已支付订单金额扣除已通过的退款
+    pub revenue: Decimal,
+    pub cancellations: i64,
+}
+
+//管理员内部备注, 为空时清除
+#[derive(Debug, Deserialize, Clone)]
+pub struct InternalNote {
+    pub order_id: Uuid,
+    pub internal_note: Option<String>,
+}
+
+//管理员手动处理押金
+#[derive(Debug, Deserialize, Clone)]
+pub struct DepositSettle {
+    pub order_id: Uuid,
+    //true: 退还, false: 扣除
+    pub release: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrdersOfCourt {
+    pub court_id: Uuid,
+}
+
+pub struct OrderOp;
+impl OrderOp {
+    fn active_model(user_id: Uuid, order: SaveOrder) -> orders::ActiveModel {
+        orders::ActiveModel {
+            order_id: order.order_id.map(Set).unwrap_or(NotSet),
+            user_id: Set(user_id),
+            court_id: order.court_id.map(Set).unwrap_or(NotSet),
+            apt_start: Set(order.apt_start),
+            apt_end: Set(order.apt_end),
+            cost: Set(order.cost),
+            deposit: order.deposit.map(Set).unwrap_or(NotSet),
+            deposit_status: order
+                .deposit
+                .map(|e| {
+                    Set(if e > Decimal::ZERO {
+                        DepositStatus::Held
+                    } else {
+                        DepositStatus::None
+                    })
+                })
+                .unwrap_or(NotSet),
+            idempotency_key: order
+                .idempotency_key
+                .map(|e| Set(Some(e)))
+                .unwrap_or(NotSet),
+            remark: order.remark.map(|e| Set(Some(e))).unwrap_or(NotSet),
+            discount: order.discount.map(Set).unwrap_or(NotSet),
+            package_hours: order.package.map(|e| Set(e.0)).unwrap_or(NotSet),
+            package_amount: order.package.map(|e| Set(e.1)).unwrap_or(NotSet),
+            points_used: order.points.map(|e| Set(e.0)).unwrap_or(NotSet),
+            points_amount: order.points.map(|e| Set(e.1)).unwrap_or(NotSet),
+            promotion_amount: if order.promotions.is_empty() {
+                NotSet
+            } else {
+                Set(promotion::total(&order.promotions))
+            },
+            member_amount: order.member.map(Set).unwrap_or(NotSet),
+            contact_name: order
+                .contact
+                .as_ref()
+                .map(|e| Set(Some(e.0.clone())))
+                .unwrap_or(NotSet),
+            contact_phone: order.contact.map(|e| Set(Some(e.1))).unwrap_or(NotSet),
+            ..Default::default()
+        }
+    }
+
+    //用户以该幂等键创建过的订单
+    pub async fn by_idempotency_key<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        key: &str,
+        db: &C,
+    ) -> Result<Option<orders::Model>, HandleErr<T>> {
+        Orders::find()
+            .filter(
+                orders::Column::UserId
+                    .eq(user_id)
+                    .and(orders::Column::IdempotencyKey.eq(key)),
+            )
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //修改订单, 指定次卡抵扣时按新的时数重新扣减
+    pub async fn save<T: From<&'static str>>(
+        user_id: Uuid,
+        admin_id: Uuid,
+        order: SaveOrder,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let order_id = order.order_id;
+        let package = order.package;
+        let promotions = order.promotions.clone();
+        let mut model = Self::active_model(user_id, order);
+        //修改时按新时段重新计算的活动优惠, 不再满足条件的为0
+        if let Some(order_id) = order_id {
+            model.promotion_amount = Set(promotion::total(&promotions));
+            PromotionOp::reapply(order_id, &promotions, &txn).await?;
+        }
+        let saved = model
+            .save(&txn)
+            .await
+            .map_err(write_err)?
+            .try_into_model()
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if let (Some(order_id), Some((hours, _))) = (order_id, package) {
+            PackageOp::reconsume(user_id, admin_id, order_id, hours, &txn).await?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(saved)
+    }
+
+    //新建订单, 在事务中锁定球场行后再检查冲突并写入
+    //同一球场的下单因此串行执行, 不会出现两人订到同一时段
+    pub async fn create<T: From<String> + From<&'static str>>(
+        user_id: Uuid,
+        order: SaveOrder,
+        addons: &[(court_addons::Model, i32)],
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let order = Self::insert(user_id, order, addons, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(order)
+    }
+
+    //代客预约: 在同一事务中下单并确认已线下收款, 记录顾客信息
+    pub async fn create_manual<T: From<String> + From<&'static str>>(
+        admin_id: Uuid,
+        order: SaveOrder,
+        customer_name: String,
+        customer_phone: Option<String>,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let order = Self::insert(admin_id, order, &[], &txn).await?;
+        let paid = Self::paid_offline(&order, state, &txn).await?;
+        Self::transit(&paid, OrderState::Confirmed, &txn).await?;
+        let order = orders::ActiveModel {
+            order_id: Set(order.order_id),
+            customer_name: Set(Some(customer_name)),
+            customer_phone: Set(customer_phone),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(order)
+    }
+
+    //锁定球场后检查冲突并写入订单及优惠、次卡、积分、附加项目等
+    async fn insert<T: From<String> + From<&'static str>>(
+        user_id: Uuid,
+        order: SaveOrder,
+        addons: &[(court_addons::Model, i32)],
+        txn: &DatabaseTransaction,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let court_id = order
+            .court_id
+            .ok_or(HandleErr::BadRequest(-1, "court_id无效".into()))?;
+        let court = Courts::find_by_id(court_id)
+            .lock_exclusive()
+            .one(txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "court_id无效".into()))?;
+        //并发的重复提交在获得锁后直接返回先提交的订单
+        if let Some(key) = &order.idempotency_key {
+            if let Some(order) = Self::by_idempotency_key(user_id, key, txn).await? {
+                return Ok(order);
+            }
+        }
+        if Self::overlaps(court_id, order.apt_start, order.apt_end, None, txn).await? {
+            return Err(HandleErr::BadRequest(-1, "时间冲突".into()));
+        }
+        if HoldOp::is_held(court_id, order.apt_start, order.apt_end, user_id, txn).await? {
+            return Err(HandleErr::BadRequest(-1, "该时段正在被他人预订".into()));
+        }
+        let coupon_id = order.coupon_id;
+        let promotions = order.promotions.clone();
+        let order = Self::active_model(user_id, order)
+            .insert(txn)
+            .await
+            .map_err(write_err)?;
+        if let Some(coupon_id) = coupon_id {
+            CouponOp::redeem(coupon_id, order.order_id, order.discount, txn).await?;
+        }
+        if order.package_hours > 0.0 {
+            PackageOp::consume(
+                user_id,
+                court.admin_id,
+                order.order_id,
+                order.package_hours,
+                txn,
+            )
+            .await?;
+        }
+        PromotionOp::record(user_id, order.order_id, &promotions, txn).await?;
+        if order.points_used > 0 {
+            PointsOp::change(
+                user_id,
+                -order.points_used,
+                PointsTxnKind::Redeem,
+                Some(order.order_id),
+                txn,
+            )
+            .await?;
+        }
+        Self::record(
+            order.order_id,
+            "created",
+            json!({
+                "cost":order.cost,
+                "deposit":order.deposit,
+                "discount":order.discount,
+                "coupon_id":coupon_id,
+                "package_hours":order.package_hours,
+                "points_used":order.points_used,
+                "promotions":promotions
+            }),
+            txn,
+        )
+        .await?;
+        //次卡全额抵扣且无押金时无需支付
+        let order = if order.cost + order.deposit <= Decimal::ZERO {
+            Self::paid_by_package(&order, txn).await?
+        } else {
+            order
+        };
+        AddonOp::attach(order.order_id, addons, txn).await?;
+        let lines = item::lines(addons);
+        ItemOp::write(&order, &court, &lines, txn).await?;
+        //下单成功后释放用户在该球场的锁定
+        HoldOp::release(user_id, court_id, None, txn).await?;
+        Ok(order)
+    }
+
+    async fn paid_by_package<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        db: &C,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let paid = Self::transit(order, OrderState::Paid, db).await?;
+        let paid = orders::ActiveModel {
+            order_id: Set(paid.order_id),
+            pay_amount: Set(Some(Decimal::ZERO)),
+            pay_method: Set(Some(PayMethod::Package)),
+            paid_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::record(
+            order.order_id,
+            "pay",
+            json!({"method":PayMethod::Package, "hours":order.package_hours}),
+            db,
+        )
+        .await?;
+        Ok(paid)
+    }
+
+    //同一球场是否有时间重叠的订单, order_id为修改中的订单
+    pub async fn overlaps<T, C: ConnectionTrait>(
+        court_id: Uuid,
+        start: DateTime,
+        end: DateTime,
+        order_id: Option<Uuid>,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        let count = Orders::find()
+            .filter(
+                orders::Column::CourtId
+                    .eq(court_id)
+                    .and(orders::Column::OrderId.ne(order_id.unwrap_or(Uuid::nil())))
+                    .and(orders::Column::AptStart.lt(end))
+                    .and(orders::Column::AptEnd.gt(start))
+                    .and(orders::Column::Status.is_not_in(state::RELEASED)),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(count > 0)
+    }
+
+    //结算已结束且已支付订单的押金, 签到过的退还, 未签到的扣除, 均逐单处理并记录
+    //返回(退还数, 扣除数)
+    pub async fn settle_deposits<T: From<String>>(
+        state: &AppState,
+    ) -> Result<(u64, u64), HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let finished = Orders::find()
+            .filter(
+                orders::Column::DepositStatus
+                    .eq(DepositStatus::Held)
+                    .and(orders::Column::Status.is_in([
+                        OrderState::Paid,
+                        OrderState::Confirmed,
+                        OrderState::Completed,
+                        OrderState::NoShow,
+                    ]))
+                    .and(orders::Column::AptEnd.lte(now)),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let (mut released, mut forfeited) = (0, 0);
+        for order in finished {
+            let release = order.check_in_time.is_some();
+            if !Self::deposit(&order, release, state).await? {
+                continue;
+            }
+            if release {
+                released += 1;
+            } else {
+                forfeited += 1;
+            }
+        }
+        Ok((released, forfeited))
+    }
+
+    //退还或扣除冻结中的押金, 押金已不在冻结中时返回false
+    //退还时在同一事务中生成押金退款记录, 提交后发起渠道退款
+    pub async fn deposit<T: From<String>>(
+        order: &orders::Model,
+        release: bool,
+        state: &AppState,
+    ) -> Result<bool, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let settled = if release {
+            Self::release_deposit(order, state, &txn).await?
+        } else {
+            let rows_affected = Orders::update_many()
+                .col_expr(
+                    orders::Column::DepositStatus,
+                    Expr::value(DepositStatus::Forfeited),
+                )
+                .filter(
+                    orders::Column::OrderId
+                        .eq(order.order_id)
+                        .and(orders::Column::DepositStatus.eq(DepositStatus::Held)),
+                )
+                .exec(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+                .rows_affected;
+            if rows_affected > 0 {
+                Self::record(
+                    order.order_id,
+                    "deposit",
+                    json!({"status":DepositStatus::Forfeited, "amount":order.deposit}),
+                    &txn,
+                )
+                .await?;
+            }
+            rows_affected > 0
+        };
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if settled && release {
+            refund::RefundOp::dispatch_order(order.order_id, state).await?;
+        }
+        Ok(settled)
+    }
+
+    //以冻结中为条件释放押金, 已支付的订单生成押金退款记录
+    //微信支付的押金退款在事务提交后由RefundOp::dispatch_order发起
+    pub async fn release_deposit<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        state: &AppState,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        let rows_affected = Orders::update_many()
+            .col_expr(
+                orders::Column::DepositStatus,
+                Expr::value(DepositStatus::Released),
+            )
+            .filter(
+                orders::Column::OrderId
+                    .eq(order.order_id)
+                    .and(orders::Column::DepositStatus.eq(DepositStatus::Held)),
+            )
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Ok(false);
+        }
+        Self::record(
+            order.order_id,
+            "deposit",
+            json!({"status":DepositStatus::Released, "amount":order.deposit}),
+            db,
+        )
+        .await?;
+        refund::RefundOp::deposit(order, state, db).await?;
+        Ok(true)
+    }
+
+    //签到, 预约开始前30分钟至结束前均可签到, 可扫描球场二维码或订单电子票
+    pub async fn check_in<T: From<&'static str>>(
+        user_id: Uuid,
+        scanned: Scanned,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        //扫球场码时用户可能有多个相邻的预约, 取最早未签到的一个
+        let cond = match scanned {
+            Scanned::Court(court_id) => orders::Column::CourtId
+                .eq(court_id)
+                .and(orders::Column::CheckInTime.is_null()),
+            Scanned::Order(order_id) => orders::Column::OrderId.eq(order_id),
+        };
+        let order = Orders::find()
+            .filter(
+                orders::Column::UserId
+                    .eq(user_id)
+                    .and(cond)
+                    .and(
+                        orders::Column::AptStart
+                            .lte(now + chrono::Duration::minutes(CHECKIN_EARLY_MINUTES)),
+                    )
+                    .and(orders::Column::AptEnd.gt(now))
+                    .and(orders::Column::Status.is_in([OrderState::Paid, OrderState::Confirmed])),
+            )
+            .order_by_asc(orders::Column::AptStart)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "不在预约时段内, 无法签到".into()))?;
+        if order.check_in_time.is_some() {
+            return Err(HandleErr::BadRequest(-1, "已签到".into()));
+        }
+        let order = orders::ActiveModel {
+            order_id: Set(order.order_id),
+            check_in_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::record(
+            order.order_id,
+            "check_in",
+            json!({"check_in_time":now}),
+            &state.db,
+        )
+        .await?;
+        Ok(order)
+    }
+
+    //前台为顾客签到, 订单须属于管理员名下的球场, 记录操作人
+    pub async fn desk_check_in<T: From<&'static str>>(
+        order_id: Uuid,
+        admin_id: Uuid,
+        operator: Uuid,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let order = Orders::find_by_id(order_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "订单不存在".into()))?;
+        CourtOp::owned::<T>(order.court_id, admin_id, state).await?;
+        if !matches!(order.status, OrderState::Paid | OrderState::Confirmed)
+            || order.apt_start > now + chrono::Duration::minutes(CHECKIN_EARLY_MINUTES)
+            || order.apt_end <= now
+        {
+            return Err(HandleErr::BadRequest(-1, "不在预约时段内, 无法签到".into()));
+        }
+        if order.check_in_time.is_some() {
+            return Err(HandleErr::BadRequest(-1, "已签到".into()));
+        }
+        let order = orders::ActiveModel {
+            order_id: Set(order.order_id),
+            check_in_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::record(
+            order.order_id,
+            "check_in",
+            json!({"check_in_time":now,"operator":operator}),
+            &state.db,
+        )
+        .await?;
+        Ok(order)
+    }
+
+    pub async fn hasClash(
+        start: DateTime,
+        end: DateTime,
+        order_id: Option<Uuid>,
+        court_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<bool, HandleErr<&'static str>> {
+        //提前拒绝, 避免违反数据库的时间约束变成服务器错误
+        if start >= end {
+            return Err(HandleErr::BadRequest(-1, "时间范围无效"));
+        }
+        if start <= chrono::Utc::now().naive_utc() {
+            return Err(HandleErr::BadRequest(-1, "预约开始时间已过"));
+        }
+        if (end.date() - start.date()).num_days() >= 1 {
+            return Ok(true);
+        }
+        let court = Courts::find_by_id(court_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "court_id无效"))?;
+        if court.status != CourtStatus::Open {
+            return Err(HandleErr::BadRequest(-1, "球场暂停开放"));
+        }
+        match OpenHoursOp::window(&court, start.date(), state).await? {
+            Some((open, close)) if start.time() >= open && end.time() <= close => {}
+            _ => return Err(HandleErr::BadRequest(-1, "不在营业时间内")),
+        }
+        if CalendarOp::is_blocked(court_id, start, end, state).await? {
+            return Err(HandleErr::BadRequest(-1, "该时段球场不开放预约"));
+        }
+        if HoldOp::is_held(court_id, start, end, user_id, &state.db).await? {
+            return Err(HandleErr::BadRequest(-1, "该时段正在被他人预订"));
+        }
+        Self::overlaps(court_id, start, end, order_id, &state.db).await
+    }
+
+    //查询用户自己的订单
+    pub async fn owned<T: From<&'static str>>(
+        order_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        Orders::find()
+            .filter(
+                orders::Column::OrderId
+                    .eq(order_id)
+                    .and(orders::Column::UserId.eq(user_id)),
+            )
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "订单信息不存在".into()))
+    }
+
+    //按状态机修改订单状态, 以原状态为条件更新, 并发修改时只有一个成功
+    //取消/退款时释放冻结的押金
+    pub async fn transit<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        to: OrderState,
+        db: &C,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let to = state::transition(&order.status, &to).map_err(|err: TransitionErr| {
+            HandleErr::BadRequest(err.code(), err.to_string().into())
+        })?;
+        let mut update = Orders::update_many()
+            .col_expr(orders::Column::Status, Expr::value(to.clone()))
+            .filter(
+                orders::Column::OrderId
+                    .eq(order.order_id)
+                    .and(orders::Column::Status.eq(order.status.clone())),
+            );
+        //未支付的订单没有收取押金, 直接释放; 已支付的由退款流程调用release_deposit退还
+        if state::RELEASED.contains(&to)
+            && order.status == OrderState::PendingPayment
+            && order.deposit_status == DepositStatus::Held
+        {
+            update = update.col_expr(
+                orders::Column::DepositStatus,
+                Expr::value(DepositStatus::Released),
+            );
+        }
+        let rows_affected = update
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(
+                state::ERR_INVALID_TRANSITION,
+                "订单状态已变化, 请刷新后重试".to_string().into(),
+            ));
+        }
+        if state::RELEASED.contains(&to) && order.package_hours > 0.0 {
+            PackageOp::restore(order.order_id, db).await?;
+        }
+        if state::RELEASED.contains(&to) && order.points_used > 0 {
+            PointsOp::restore(order.user_id, order.order_id, order.points_used, db).await?;
+        }
+        if state::RELEASED.contains(&to) && order.promotion_amount > Decimal::ZERO {
+            PromotionOp::release(order.order_id, db).await?;
+        }
+        //取消/退款后关闭找球友招募
+        if state::RELEASED.contains(&to) {
+            PartnerOp::release(order, db).await?;
+        }
+        //未支付即取消的订单退还优惠券
+        if order.status == OrderState::PendingPayment
+            && to == OrderState::Cancelled
+            && order.discount > Decimal::ZERO
+        {
+            CouponOp::restore(order.order_id, db).await?;
+        }
+        Self::record(
+            order.order_id,
+            "status",
+            json!({"from":order.status, "to":to}),
+            db,
+        )
+        .await?;
+        //代客预约的订单不通知下单的管理员
+        if order.customer_name.is_none() {
+            InboxOp::push(
+                order.user_id,
+                NotificationKind::Order,
+                &format!("订单{}", state::name(&to)),
+                &format!(
+                    "您{}开始的预约{}",
+                    order.apt_start.format("%m-%d %H:%M"),
+                    state::name(&to)
+                ),
+                Some(order.order_id),
+                db,
+            )
+            .await?;
+        }
+        Orders::find_by_id(order.order_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "订单信息不存在".to_string().into(),
+            ))
+    }
+
+    //取消订单并记录原因与取消费用, 已支付的订单随后进入退款流程
+    pub async fn cancel<T: From<String>>(
+        order: &orders::Model,
+        reason: String,
+        fee: Decimal,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let paid = matches!(order.status, OrderState::Paid | OrderState::Confirmed);
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let cancelled = Self::cancel_in(order, reason, fee, state, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if paid {
+            refund::RefundOp::dispatch_order(cancelled.order_id, state).await?;
+        }
+        Ok(cancelled)
+    }
+
+    //在调用方的事务中取消订单, 已支付的扣除手续费后生成退款记录
+    //事务提交后调用方应对已支付的订单调用RefundOp::dispatch_order发起渠道退款
+    async fn cancel_in<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        reason: String,
+        fee: Decimal,
+        state: &AppState,
+        db: &C,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let paid = matches!(order.status, OrderState::Paid | OrderState::Confirmed);
+        let cancelled = Self::transit(order, OrderState::Cancelled, db).await?;
+        let mut cancelled = orders::ActiveModel {
+            order_id: Set(cancelled.order_id),
+            cancel_reason: Set(Some(reason)),
+            cancel_fee: Set(fee),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if paid {
+            //扣除已通过申请退还的部分
+            let records = refund::RefundOp::of_order(order.order_id, db).await?;
+            let amount = (order.cost - fee - refund::refunded(&records)).max(Decimal::ZERO);
+            cancelled = Self::refund(
+                &cancelled,
+                amount,
+                "取消订单",
+                RefundReason::UserCancel,
+                state,
+                db,
+            )
+            .await?;
+        }
+        Ok(cancelled)
+    }
+
+    //球场停用时取消未开始的订单, 已支付的全额退还(扣除已通过申请退还的部分)
+    //事务提交后调用方应对取消的订单调用RefundOp::dispatch_order发起渠道退款
+    //未付清拼单中已支付的分摊由调用方调用GroupOp::refund_paid退还, 返回取消的订单及退款金额
+    pub async fn cancel_by_court<T: From<String>, C: ConnectionTrait>(
+        court_id: Uuid,
+        reason: String,
+        state: &AppState,
+        db: &C,
+    ) -> Result<Vec<(orders::Model, Decimal)>, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let affected = Orders::find()
+            .filter(
+                orders::Column::CourtId
+                    .eq(court_id)
+                    .and(orders::Column::AptStart.gt(now))
+                    .and(orders::Column::Status.is_in([
+                        OrderState::PendingPayment,
+                        OrderState::Paid,
+                        OrderState::Confirmed,
+                    ])),
+            )
+            .order_by_asc(orders::Column::AptStart)
+            .lock_exclusive()
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut cancelled = vec![];
+        for order in affected {
+            let paid = matches!(order.status, OrderState::Paid | OrderState::Confirmed);
+            let mut model = Self::transit(&order, OrderState::Cancelled, db).await?;
+            model = orders::ActiveModel {
+                order_id: Set(model.order_id),
+                cancel_reason: Set(Some(reason.clone())),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            let amount = if paid {
+                let records = refund::RefundOp::of_order(order.order_id, db).await?;
+                let amount = (order.cost - refund::refunded(&records)).max(Decimal::ZERO);
+                model = Self::refund(
+                    &model,
+                    amount,
+                    "球场停用",
+                    RefundReason::VenueIssue,
+                    state,
+                    db,
+                )
+                .await?;
+                amount
+            } else {
+                //未付清的拼单, 待支付的分摊关闭, 已支付的分摊由调用方在提交后调用GroupOp::refund_paid退还
+                OrderParticipants::update_many()
+                    .col_expr(
+                        order_participants::Column::Status,
+                        Expr::value(ShareStatus::Expired),
+                    )
+                    .filter(
+                        order_participants::Column::OrderId
+                            .eq(order.order_id)
+                            .and(order_participants::Column::Status.eq(ShareStatus::Pending)),
+                    )
+                    .exec(db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?;
+                OrderParticipants::find()
+                    .filter(
+                        order_participants::Column::OrderId
+                            .eq(order.order_id)
+                            .and(order_participants::Column::Status.eq(ShareStatus::Paid)),
+                    )
+                    .all(db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?
+                    .iter()
+                    .map(|e| e.share)
+                    .sum()
+            };
+            cancelled.push((model, amount));
+        }
+        Ok(cancelled)
+    }
+
+    //退款流程入口, 订单变为已退款后生成退款记录并通过支付渠道退款
+    pub async fn refund<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        amount: Decimal,
+        reason: &str,
+        code: RefundReason,
+        state: &AppState,
+        db: &C,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let order = Self::transit(order, OrderState::Refunded, db).await?;
+        Self::release_deposit(&order, state, db).await?;
+        if amount > Decimal::ZERO {
+            let record = refunds::ActiveModel {
+                refund_id: NotSet,
+                order_id: Set(order.order_id),
+                user_id: Set(order.user_id),
+                amount: Set(amount),
+                reason: Set(reason.to_string()),
+                reason_code: Set(code),
+                status: Set(RefundStatus::Approved),
+                handle_time: Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            refund::RefundOp::submit(record, &order, state, db).await?;
+        }
+        Ok(order)
+    }
+
+    //超时未支付的订单取消并释放时段, 拼单按付款截止时间由GroupOp::expire处理
+    //先关闭支付渠道的支付单, 关闭失败(如已支付)时跳过, 等待支付通知
+    pub async fn expire_unpaid<T: From<String>>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        let minutes = state.cfg.ordercfg.pay_timeout_minutes;
+        if minutes <= 0 {
+            return Ok(0);
+        }
+        let deadline = chrono::Utc::now().naive_utc() - chrono::Duration::minutes(minutes);
+        let unpaid = Orders::find()
+            .filter(
+                orders::Column::Status
+                    .eq(OrderState::PendingPayment)
+                    .and(orders::Column::PayDeadline.is_null())
+                    .and(orders::Column::CreateTime.lte(deadline)),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut expired = 0;
+        for order in unpaid {
+            if let Err(err) = state.payment.close(order.order_id).await {
+                warn!("订单({})关闭支付失败: {}", order.order_id, err);
+                continue;
+            }
+            let txn = state.db.begin().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            //状态已被其他请求修改时跳过
+            if Self::transit::<T, _>(&order, OrderState::Cancelled, &txn)
+                .await
+                .is_err()
+            {
+                continue;
+            }
+            orders::ActiveModel {
+                order_id: Set(order.order_id),
+                cancel_reason: Set(Some("超时未支付".to_string())),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            expired += 1;
+        }
+        Ok(expired)
+    }
+
+    //已确认且已结束的订单标记为完成
+    //完成的订单按实付金额获得积分
+    pub async fn complete_finished<T: From<String>>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let finished = Orders::find()
+            .filter(
+                orders::Column::Status
+                    .eq(OrderState::Confirmed)
+                    .and(orders::Column::AptEnd.lte(now)),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut completed = 0;
+        //逐单在各自的事务中完成, 单个订单失败不影响其他订单
+        for order in finished {
+            match Self::complete::<String>(&order, state).await {
+                Ok(true) => completed += 1,
+                Ok(false) => {}
+                Err(err) => warn!("订单({})完成失败: {:?}", order.order_id, err),
+            }
+        }
+        Ok(completed)
+    }
+
+    //以已确认为条件完成订单并发放积分与邀请奖励, 状态已被其他请求修改时返回false
+    async fn complete<T: From<String>>(
+        order: &orders::Model,
+        state: &AppState,
+    ) -> Result<bool, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let completed = Orders::update_many()
+            .col_expr(orders::Column::Status, Expr::value(OrderState::Completed))
+            .filter(
+                orders::Column::OrderId
+                    .eq(order.order_id)
+                    .and(orders::Column::Status.eq(OrderState::Confirmed)),
+            )
+            .exec_with_returning(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let Some(order) = completed.first() else {
+            return Ok(false);
+        };
+        let records = refund::RefundOp::of_order(order.order_id, &txn).await?;
+        let paid = order.cost - refund::refunded(&records);
+        PointsOp::earn(order.user_id, order.order_id, paid, &txn).await?;
+        //被邀请用户的首笔订单完成后发放邀请奖励, 取消或退款的订单不计, 代客预约的订单不计
+        if order.customer_name.is_none() {
+            ReferralOp::reward(order, &txn).await?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(true)
+    }
+
+    //管理员确认已线下收款, 订单变为已支付
+    pub async fn paid_offline<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        state: &AppState,
+        db: &C,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let amount = order.cost + order.deposit;
+        let paid = Self::transit(order, OrderState::Paid, db).await?;
+        let paid = orders::ActiveModel {
+            order_id: Set(paid.order_id),
+            pay_amount: Set(Some(amount)),
+            pay_method: Set(Some(PayMethod::Offline)),
+            paid_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::record(
+            order.order_id,
+            "pay",
+            json!({"method":PayMethod::Offline, "amount":amount}),
+            db,
+        )
+        .await?;
+        LedgerOp::record(
+            &paid,
+            LedgerKind::Payment,
+            amount,
+            order.deposit,
+            Some(PayMethod::Offline),
+            state,
+            db,
+        )
+        .await?;
+        info!("订单({})线下收款{:.2}元", order.order_id, amount);
+        Ok(paid)
+    }
+
+    //已支付的订单改期, 保留支付状态, 仅允许改到价格相同的时段
+    //差价无法补交或退还, 价格变化时需取消后重新预约
+    //与下单相同, 在事务中锁定球场后检查冲突
+    pub async fn reschedule<T: From<String> + From<&'static str>>(
+        order: &orders::Model,
+        apt_start: DateTime,
+        apt_end: DateTime,
+        cost: Decimal,
+        package: (f64, Decimal),
+        promotions: &[Applied],
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        if cost != order.cost {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!(
+                    "新时段价格为{:.2}元, 与原订单{:.2}元不同, 请取消后重新预约",
+                    cost, order.cost
+                )
+                .into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let court = Courts::find_by_id(order.court_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string().into()))?;
+        if Self::overlaps(
+            order.court_id,
+            apt_start,
+            apt_end,
+            Some(order.order_id),
+            &txn,
+        )
+        .await?
+            || HoldOp::is_held(order.court_id, apt_start, apt_end, order.user_id, &txn).await?
+        {
+            return Err(HandleErr::BadRequest(-1, "时间冲突".to_string().into()));
+        }
+        let rows_affected = Orders::update_many()
+            .col_expr(orders::Column::AptStart, Expr::value(apt_start))
+            .col_expr(orders::Column::AptEnd, Expr::value(apt_end))
+            .col_expr(orders::Column::Cost, Expr::value(cost))
+            .col_expr(orders::Column::PackageHours, Expr::value(package.0))
+            .col_expr(orders::Column::PackageAmount, Expr::value(package.1))
+            .col_expr(
+                orders::Column::PromotionAmount,
+                Expr::value(promotion::total(promotions)),
+            )
+            .filter(
+                orders::Column::OrderId
+                    .eq(order.order_id)
+                    .and(orders::Column::Status.eq(order.status.clone())),
+            )
+            .exec(&txn)
+            .await
+            .map_err(write_err)?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(
+                state::ERR_INVALID_TRANSITION,
+                "订单状态已变化, 请刷新后重试".to_string().into(),
+            ));
+        }
+        PromotionOp::reapply(order.order_id, promotions, &txn).await?;
+        if order.package_hours > 0.0 || package.0 > 0.0 {
+            PackageOp::reconsume(
+                order.user_id,
+                court.admin_id,
+                order.order_id,
+                package.0,
+                &txn,
+            )
+            .await?;
+        }
+        Self::record(
+            order.order_id,
+            "reschedule",
+            json!({
+                "old":{"apt_start":order.apt_start, "apt_end":order.apt_end, "cost":order.cost},
+                "new":{"apt_start":apt_start, "apt_end":apt_end, "cost":cost},
+                "package_hours":package.0,
+                "promotions":promotions
+            }),
+            &txn,
+        )
+        .await?;
+        let updated = Orders::find_by_id(order.order_id)
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "订单信息不存在".to_string().into(),
+            ))?;
+        let addons = ItemOp::addons_of(order.order_id, &txn).await?;
+        ItemOp::write(&updated, &court, &addons, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(updated)
+    }
+
+    //写入订单时间线
+    pub async fn record<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        action: &str,
+        detail: serde_json::Value,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        order_timeline::ActiveModel {
+            event_id: NotSet,
+            order_id: Set(order_id),
+            action: Set(action.to_string()),
+            detail: Set(detail),
+            create_time: NotSet,
+        }
+        .insert(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    pub async fn timeline<T>(
+        order_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<order_timeline::Model>, HandleErr<T>> {
+        OrderTimeline::find()
+            .filter(order_timeline::Column::OrderId.eq(order_id))
+            .order_by_asc(order_timeline::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //管理员名下球场按周期分组的订单统计
+    pub async fn stats<T>(
+        admin_id: Uuid,
+        query: StatsQuery,
+        state: &AppState,
+    ) -> Result<Vec<OrderStats>, HandleErr<T>> {
+        OrderStats::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"select date_trunc($1, o.apt_start) as bucket,
+                count(*) filter (where o.status not in ('cancelled', 'refunded')) as bookings,
+                coalesce(sum(o.cost - coalesce(r.amount, 0))
+                    filter (where o.status in ('paid', 'confirmed', 'completed', 'no_show')), 0)::numeric as revenue,
+                count(*) filter (where o.status = 'cancelled') as cancellations
+               from orders o
+               join courts c on c.court_id = o.court_id
+               left join (select order_id, sum(amount) as amount from refunds
+                          where status = 'approved' group by order_id) r on r.order_id = o.order_id
+               where c.admin_id = $2
+                 and ($3::uuid is null or o.court_id = $3)
+                 and ($4::timestamp is null or o.apt_start >= $4)
+                 and ($5::timestamp is null or o.apt_start < $5)
+               group by 1 order by 1"#,
+            [
+                query.granularity.as_str().into(),
+                admin_id.into(),
+                query.court_id.into(),
+                query.from.into(),
+                query.to.into(),
+            ],
+        ))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+}
+
+//游标之后的订单, 配合(apt_start, order_id)倒序使用
+//游标无效时返回错误
+pub fn after<T: From<&'static str>>(token: &str) -> Result<Condition, HandleErr<T>> {
+    let cursor = Cursor::decode(token).ok_or(HandleErr::BadRequest(-1, "游标无效".into()))?;
+    Ok(Condition::any()
+        .add(orders::Column::AptStart.lt(cursor.apt_start))
+        .add(
+            orders::Column::AptStart
+                .eq(cursor.apt_start)
+                .and(orders::Column::OrderId.lt(cursor.order_id)),
+        ))
+}
+
+//时段重叠的写入被数据库排斥约束拒绝时返回冲突提示, 其余按服务器错误处理
+pub(crate) fn write_err<T: From<&'static str>>(err: DbErr) -> HandleErr<T> {
+    let overlap = match &err {
+        DbErr::Exec(RuntimeErr::SqlxError(e)) | DbErr::Query(RuntimeErr::SqlxError(e)) => e
+            .as_database_error()
+            .and_then(|e| e.code())
+            .is_some_and(|code| code == ERR_EXCLUSION_VIOLATION),
+        _ => false,
+    };
+    if overlap {
+        return HandleErr::BadRequest(-1, "时间冲突".into());
+    }
+    let id = Uuid::new_v4();
+    error!("{} >>>> {}", id, err.to_string());
+    HandleErr::ServerInnerErr(id)
+}
@@ -0,0 +1,112 @@
+use super::OrderOp;
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self, orders,
+        prelude::{Orders, Users},
+        sea_orm_active_enums::OrderState,
+    },
+};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub struct NoShowOp;
+impl NoShowOp {
+    //已支付但到结束时间仍未签到的订单标记为未到场, 并累计用户的未到场次数
+    //返回标记的订单数
+    pub async fn mark<T>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let cfg = &state.cfg.noshowcfg;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let mut counts: HashMap<Uuid, i32> = HashMap::new();
+        let mut marked = 0;
+        for from in [OrderState::Paid, OrderState::Confirmed] {
+            let orders = Orders::update_many()
+                .col_expr(orders::Column::Status, Expr::value(OrderState::NoShow))
+                .filter(
+                    orders::Column::Status
+                        .eq(from.clone())
+                        .and(orders::Column::CheckInTime.is_null())
+                        //代客预约的顾客不使用小程序签到
+                        .and(orders::Column::CustomerName.is_null())
+                        .and(orders::Column::AptEnd.lte(now)),
+                )
+                .exec_with_returning(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            for order in orders {
+                OrderOp::record(
+                    order.order_id,
+                    "status",
+                    json!({"from":from, "to":OrderState::NoShow}),
+                    &txn,
+                )
+                .await?;
+                *counts.entry(order.user_id).or_default() += 1;
+                marked += 1;
+            }
+        }
+        for (user_id, n) in counts {
+            let Some(user) = Users::find_by_id(user_id).one(&txn).await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            else {
+                continue;
+            };
+            let (count, banned) = penalty(user.no_show_count + n, cfg.limit);
+            let mut model = db::users::ActiveModel {
+                user_id: Set(user_id),
+                no_show_count: Set(count),
+                ..Default::default()
+            };
+            if banned {
+                let until = now + chrono::Duration::days(cfg.ban_days);
+                model.banned_until = Set(Some(until));
+                info!("用户({})多次未到场, {}前限制预约", user.user_name, until);
+            }
+            model.update(&txn).await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(marked)
+    }
+}
+
+//累计次数达到上限时限制预约并清零, limit为0时不限制
+fn penalty(count: i32, limit: i32) -> (i32, bool) {
+    if limit > 0 && count >= limit {
+        (0, true)
+    } else {
+        (count, false)
+    }
+}
+
+#[test]
+fn test_penalty() {
+    assert_eq!(penalty(2, 3), (2, false));
+    assert_eq!(penalty(3, 3), (0, true));
+    assert_eq!(penalty(4, 3), (0, true));
+    assert_eq!(penalty(5, 0), (5, false));
+}
@@ -0,0 +1,239 @@
+use super::{refund::RefundOp, OrderOp};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::{
+            orders,
+            prelude::Orders,
+            sea_orm_active_enums::{LedgerKind, OrderState, PayMethod, RefundReason},
+        },
+        finance::{self, LedgerOp},
+        payment::{
+            notification::NotificationOp,
+            wechat::{from_fen, to_fen, Transaction},
+        },
+    },
+};
+use sea_orm::{ActiveModelTrait, ConnectionTrait, EntityTrait, QuerySelect, Set, TransactionTrait};
+use serde_json::json;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+pub struct PayOp;
+impl PayOp {
+    //锁定通知对应的订单并登记通知, 订单已写入微信支付订单号或通知已登记过时为重复通知
+    async fn claim<T: From<String>, C: ConnectionTrait>(
+        order_id: Uuid,
+        transaction: &Transaction,
+        txn: &C,
+    ) -> Result<(orders::Model, bool), HandleErr<T>> {
+        let order = Orders::find_by_id(order_id)
+            .lock_exclusive()
+            .one(txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "订单信息不存在".to_string().into(),
+            ))?;
+        let claimed =
+            order.transaction_id.is_none() && NotificationOp::claim(transaction, txn).await?;
+        Ok((order, claimed))
+    }
+
+    //支付成功通知, 锁定订单后写入微信支付订单号, 已写入时视为重复通知
+    //通知到达前订单已取消的, 原路退还支付金额
+    //支付金额与应付金额不一致时不变为已支付, 取消订单并原路退还
+    pub async fn paid<T: From<String>>(
+        transaction: &Transaction,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let order_id = Uuid::parse_str(&transaction.out_trade_no).map_err(|_| {
+            HandleErr::BadRequest(
+                -1,
+                format!("out_trade_no无效: {}", transaction.out_trade_no).into(),
+            )
+        })?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let (order, claimed) = Self::claim(order_id, transaction, &txn).await?;
+        if !claimed {
+            info!("订单({})重复的支付通知", order.order_id);
+            return Ok(order);
+        }
+        let amount = from_fen(transaction.amount.total);
+        let mismatched = transaction.amount.total != to_fen(order.cost + order.deposit);
+        if mismatched {
+            error!(
+                "订单({})支付金额{:.2}元与应付金额{:.2}元不一致",
+                order.order_id,
+                amount,
+                order.cost + order.deposit
+            );
+        }
+        let order = orders::ActiveModel {
+            order_id: Set(order.order_id),
+            transaction_id: Set(Some(transaction.transaction_id.clone())),
+            pay_amount: Set(Some(amount)),
+            pay_method: Set(Some(PayMethod::Wechat)),
+            paid_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        OrderOp::record(
+            order.order_id,
+            "pay",
+            json!({"transaction_id":transaction.transaction_id, "amount":amount}),
+            &txn,
+        )
+        .await?;
+        LedgerOp::record(
+            &order,
+            LedgerKind::Payment,
+            amount,
+            finance::deposit_of(&order, amount),
+            Some(PayMethod::Wechat),
+            state,
+            &txn,
+        )
+        .await?;
+        let order = match order.status {
+            OrderState::PendingPayment if mismatched => {
+                let cancelled = OrderOp::transit(&order, OrderState::Cancelled, &txn).await?;
+                let cancelled = orders::ActiveModel {
+                    order_id: Set(cancelled.order_id),
+                    cancel_reason: Set(Some("支付金额与订单金额不一致".to_string())),
+                    ..Default::default()
+                }
+                .update(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+                OrderOp::refund(
+                    &cancelled,
+                    amount,
+                    "支付金额与订单金额不一致",
+                    RefundReason::PaymentIssue,
+                    state,
+                    &txn,
+                )
+                .await?
+            }
+            OrderState::PendingPayment => OrderOp::transit(&order, OrderState::Paid, &txn).await?,
+            OrderState::Cancelled => {
+                OrderOp::refund(
+                    &order,
+                    amount,
+                    "支付前订单已取消",
+                    RefundReason::PaymentIssue,
+                    state,
+                    &txn,
+                )
+                .await?
+            }
+            _ => {
+                warn!(
+                    "订单({})状态为{:?}时收到支付通知",
+                    order.order_id, order.status
+                );
+                order
+            }
+        };
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("订单({})支付成功{:.2}元", order.order_id, amount);
+        if order.status == OrderState::Refunded {
+            RefundOp::dispatch_order(order.order_id, state).await?;
+        }
+        Ok(order)
+    }
+}
+
+#[tokio::test]
+async fn test_paid_duplicate() {
+    use crate::module::db::sea_orm_active_enums::DepositStatus;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    let now = chrono::Utc::now().naive_utc();
+    let order = orders::Model {
+        order_id: Uuid::new_v4(),
+        user_id: Uuid::new_v4(),
+        court_id: Uuid::new_v4(),
+        create_time: now,
+        apt_start: now + chrono::Duration::hours(1),
+        apt_end: now + chrono::Duration::hours(2),
+        cost: Decimal::ONE,
+        check_in_time: None,
+        deposit: Decimal::ZERO,
+        deposit_status: DepositStatus::None,
+        status: OrderState::PendingPayment,
+        cancel_reason: None,
+        cancel_fee: Decimal::ZERO,
+        series_id: None,
+        pay_deadline: None,
+        customer_name: None,
+        customer_phone: None,
+        contact_name: None,
+        contact_phone: None,
+        reminded: false,
+        idempotency_key: None,
+        remark: None,
+        internal_note: None,
+        transaction_id: None,
+        pay_amount: None,
+        prepay_amount: None,
+        pay_method: None,
+        paid_time: None,
+        discount: Decimal::ZERO,
+        package_hours: 0.0,
+        package_amount: Decimal::ZERO,
+        points_used: 0,
+        points_amount: Decimal::ZERO,
+        promotion_amount: Decimal::ZERO,
+        member_amount: Decimal::ZERO,
+    };
+    //订单尚未写入微信支付订单号, 但通知已被登记过
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[order.clone()]])
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 0,
+        }])
+        .into_connection();
+    let transaction: Transaction = serde_json::from_value(json!({
+        "out_trade_no": order.order_id.to_string(),
+        "transaction_id": "4200000001",
+        "trade_state": "SUCCESS",
+        "amount": { "total": 100 },
+    }))
+    .unwrap();
+    //重复通知原样返回订单, paid随即返回, 不更新订单
+    let (claimed, ok) = PayOp::claim::<String, _>(order.order_id, &transaction, &db)
+        .await
+        .unwrap();
+    assert!(!ok);
+    assert_eq!(claimed, order);
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("ON CONFLICT"));
+    assert!(!log.contains("UPDATE \\\"orders\\\""));
+}
@@ -0,0 +1,979 @@
+use super::{item::ItemOp, OrderOp};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::{
+            courts, order_items, order_participants, orders,
+            prelude::{Courts, OrderParticipants, Orders, RefundItems, Refunds},
+            refund_items, refunds,
+            sea_orm_active_enums::{
+                LedgerKind, NotificationKind, OrderItemKind, OrderState, PayMethod,
+                RefundChannelStatus, RefundReason, RefundStatus, ShareStatus, WalletTxnKind,
+            },
+        },
+        finance::{self, LedgerOp},
+        money,
+        notify::inbox::InboxOp,
+        payment::{
+            provider::{PaymentProvider, Provider, Refunded},
+            wechat::WechatRefund,
+            Payment,
+        },
+        wallet::{Change, WalletOp},
+    },
+};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Query;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, JoinType, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RefundRequest {
+    pub order_id: Uuid,
+    //为空时申请退还剩余全部金额, 选择了明细时为明细合计
+    pub amount: Option<Decimal>,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub reason_code: RefundReason,
+    //部分退款对应的订单明细
+    #[serde(default)]
+    pub items: Vec<RefundLine>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RefundLine {
+    pub item_id: Uuid,
+    //为空时退还该明细剩余全部金额
+    pub amount: Option<Decimal>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RefundApprove {
+    pub refund_id: Uuid,
+    //true: 通过, false: 拒绝
+    pub approve: bool,
+    pub reply: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RefundSchema {
+    #[serde(flatten)]
+    pub refund: refunds::Model,
+    pub items: Vec<refund_items::Model>,
+}
+
+pub struct RefundOp;
+impl RefundOp {
+    //用户申请退款, 同一订单同时只能有一个待处理的申请
+    pub async fn request<T: From<String>>(
+        order: &orders::Model,
+        schema: RefundRequest,
+        state: &AppState,
+    ) -> Result<RefundSchema, HandleErr<T>> {
+        if !matches!(order.status, OrderState::Paid | OrderState::Confirmed) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "仅已支付的订单可以申请退款".to_string().into(),
+            ));
+        }
+        if schema.reason_code == RefundReason::Deposit {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "押金在订单结束后由场馆退还".to_string().into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        //锁定订单, 避免并发申请
+        Orders::find_by_id(order.order_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let records = Self::of_order(order.order_id, &txn).await?;
+        if records.iter().any(|e| e.status == RefundStatus::Pending) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "已有待处理的退款申请".to_string().into(),
+            ));
+        }
+        let lines = if schema.items.is_empty() {
+            vec![]
+        } else {
+            let items = ItemOp::of_order(order.order_id, &txn).await?;
+            let approved: Vec<_> = records
+                .iter()
+                .filter(|e| e.status == RefundStatus::Approved)
+                .map(|e| e.refund_id)
+                .collect();
+            let refunded = Self::items_of(&approved, &txn).await?;
+            allocate(&items, &refunded, &schema.items)
+                .map_err(|msg| HandleErr::BadRequest(-1, msg.into()))?
+        };
+        let remaining = order.cost - refunded(&records);
+        let amount = if lines.is_empty() {
+            schema.amount.map_or(remaining, money::round)
+        } else {
+            let total = lines.iter().map(|(_, amount)| amount).sum::<Decimal>();
+            if schema.amount.is_some_and(|e| money::round(e) != total) {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    format!("退款金额应与明细合计{:.2}一致", total).into(),
+                ));
+            }
+            total
+        };
+        if amount <= Decimal::ZERO || amount > remaining {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("退款金额应在0~{:.2}之间", remaining).into(),
+            ));
+        }
+        let refund = refunds::ActiveModel {
+            refund_id: NotSet,
+            order_id: Set(order.order_id),
+            user_id: Set(order.user_id),
+            amount: Set(amount),
+            reason: Set(schema.reason),
+            reason_code: Set(schema.reason_code),
+            status: NotSet,
+            admin_id: NotSet,
+            reply: NotSet,
+            create_time: NotSet,
+            handle_time: NotSet,
+            channel_refund_id: NotSet,
+            channel_status: NotSet,
+            success_time: NotSet,
+        }
+        .insert(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if !lines.is_empty() {
+            RefundItems::insert_many(lines.into_iter().map(|(item, amount)| {
+                refund_items::ActiveModel {
+                    refund_item_id: NotSet,
+                    refund_id: Set(refund.refund_id),
+                    item_id: Set(Some(item.item_id)),
+                    kind: Set(item.kind),
+                    name: Set(item.name),
+                    amount: Set(amount),
+                }
+            }))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        let items = Self::items_of(&[refund.refund_id], &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let refund = Self::dispatch(refund, state).await?;
+        Ok(RefundSchema { refund, items })
+    }
+
+    //退款记录对应的订单明细
+    pub async fn items_of<T, C: ConnectionTrait>(
+        refund_ids: &[Uuid],
+        db: &C,
+    ) -> Result<Vec<refund_items::Model>, HandleErr<T>> {
+        if refund_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        RefundItems::find()
+            .filter(refund_items::Column::RefundId.is_in(refund_ids.iter().copied()))
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //附带退款明细
+    pub async fn detailed<T, C: ConnectionTrait>(
+        records: Vec<refunds::Model>,
+        db: &C,
+    ) -> Result<Vec<RefundSchema>, HandleErr<T>> {
+        let ids: Vec<_> = records.iter().map(|e| e.refund_id).collect();
+        let mut items = Self::items_of(&ids, db).await?;
+        Ok(records
+            .into_iter()
+            .map(|refund| {
+                let (mine, rest) = items
+                    .drain(..)
+                    .partition(|e: &refund_items::Model| e.refund_id == refund.refund_id);
+                items = rest;
+                RefundSchema {
+                    refund,
+                    items: mine,
+                }
+            })
+            .collect())
+    }
+
+    //用户的退款记录
+    pub async fn mine<T>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<RefundSchema>, u64), HandleErr<T>> {
+        let paginator = Refunds::find()
+            .filter(refunds::Column::UserId.eq(user_id))
+            .order_by_desc(refunds::Column::CreateTime)
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let records = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((Self::detailed(records, &state.db).await?, total))
+    }
+
+    pub async fn of_order<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<refunds::Model>, HandleErr<T>> {
+        Refunds::find()
+            .filter(refunds::Column::OrderId.eq(order_id))
+            .order_by_asc(refunds::Column::CreateTime)
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //管理员名下球场待处理的退款申请
+    pub async fn pending<T>(
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<RefundSchema>, HandleErr<T>> {
+        let records = Refunds::find()
+            .join(JoinType::InnerJoin, refunds::Relation::Orders.def())
+            .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+            .filter(
+                courts::Column::AdminId
+                    .eq(admin_id)
+                    .and(refunds::Column::Status.eq(RefundStatus::Pending)),
+            )
+            .order_by_asc(refunds::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Self::detailed(records, &state.db).await
+    }
+
+    //处理退款申请, 通过后经支付渠道退款
+    pub async fn handle<T: From<String>>(
+        schema: RefundApprove,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<RefundSchema, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let refund = Refunds::find_by_id(schema.refund_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "退款申请不存在".to_string().into(),
+            ))?;
+        let (order, court) = Orders::find_by_id(refund.order_id)
+            .find_also_related(Courts)
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "订单信息不存在".to_string().into(),
+            ))?;
+        if court.map(|e| e.admin_id) != Some(admin_id) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "退款申请不存在".to_string().into(),
+            ));
+        }
+        if refund.status != RefundStatus::Pending {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "退款申请已处理".to_string().into(),
+            ));
+        }
+        let status = if schema.approve {
+            RefundStatus::Approved
+        } else {
+            RefundStatus::Rejected
+        };
+        let refund = refunds::ActiveModel {
+            refund_id: Set(refund.refund_id),
+            status: Set(status),
+            admin_id: Set(Some(admin_id)),
+            reply: Set(schema.reply),
+            handle_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let refund = if schema.approve {
+            //累计退款达到订单金额时订单变为已退款
+            let records = Self::of_order(order.order_id, &txn).await?;
+            if refunded(&records) >= order.cost {
+                let order = OrderOp::transit(&order, OrderState::Refunded, &txn).await?;
+                OrderOp::release_deposit(&order, state, &txn).await?;
+            }
+            Self::submit(refund, &order, state, &txn).await?
+        } else {
+            InboxOp::push(
+                refund.user_id,
+                NotificationKind::Refund,
+                "退款申请未通过",
+                refund.reply.as_deref().unwrap_or("您的退款申请未通过"),
+                Some(refund.order_id),
+                &txn,
+            )
+            .await?;
+            refund
+        };
+        let items = Self::items_of(&[refund.refund_id], &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if schema.approve {
+            //同时退还的押金一并发起
+            Self::dispatch_order(order.order_id, state).await?;
+        }
+        let refund = Refunds::find_by_id(refund.refund_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .unwrap_or(refund);
+        Ok(RefundSchema { refund, items })
+    }
+
+    //已通过的退款记录按订单实际使用的支付方式退回并写入订单时间线
+    //微信支付的退款在事务提交后由dispatch发起, 未发起成功的由定时任务重试
+    pub async fn submit<T: From<String>, C: ConnectionTrait>(
+        refund: refunds::Model,
+        order: &orders::Model,
+        state: &AppState,
+        db: &C,
+    ) -> Result<refunds::Model, HandleErr<T>> {
+        let result = Provider::of_order(order)
+            .refund(&refund, order, state, db)
+            .await?;
+        OrderOp::record(
+            order.order_id,
+            "refund",
+            json!({"refund_id":refund.refund_id, "amount":refund.amount}),
+            db,
+        )
+        .await?;
+        LedgerOp::record(
+            order,
+            LedgerKind::Refund,
+            refund.amount,
+            match refund.reason_code {
+                RefundReason::Deposit => refund.amount,
+                _ => finance::deposit_of(order, refund.amount),
+            },
+            order.pay_method.clone(),
+            state,
+            db,
+        )
+        .await?;
+        info!("订单({})退款{:.2}元", order.order_id, refund.amount);
+        InboxOp::push(
+            refund.user_id,
+            NotificationKind::Refund,
+            "退款已发起",
+            &format!("订单退款{:.2}元已发起, 将按原支付方式退回", refund.amount),
+            Some(order.order_id),
+            db,
+        )
+        .await?;
+        match result {
+            Refunded::Instant => refunds::ActiveModel {
+                refund_id: Set(refund.refund_id),
+                channel_status: Set(Some(RefundChannelStatus::Success)),
+                success_time: Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            }),
+            Refunded::Deferred | Refunded::Manual => Ok(refund),
+        }
+    }
+
+    //已提交的微信退款记录发起渠道退款, 以refund_id作为商户退款单号, 重复发起不会重复退款
+    //拼单分摊的退款记录按该份的微信支付单退款, 其余按订单的支付单
+    //渠道调用失败时保留记录等待重试, 不返回错误
+    pub async fn dispatch<T>(
+        refund: refunds::Model,
+        state: &AppState,
+    ) -> Result<refunds::Model, HandleErr<T>> {
+        if refund.status != RefundStatus::Approved || refund.channel_status.is_some() {
+            return Ok(refund);
+        }
+        let share = OrderParticipants::find_by_id(refund.refund_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let result = match share {
+            Some(share) => match (share.pay_method, share.transaction_id) {
+                (Some(PayMethod::Wechat), Some(transaction_id)) => {
+                    state
+                        .payment
+                        .refund_transaction(&refund, &transaction_id, share.share)
+                        .await
+                }
+                _ => return Ok(refund),
+            },
+            None => {
+                let order = Orders::find_by_id(refund.order_id)
+                    .one(&state.db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?;
+                match order {
+                    Some(order) if order.pay_method == Some(PayMethod::Wechat) => {
+                        state.payment.refund(&refund, &order).await
+                    }
+                    _ => return Ok(refund),
+                }
+            }
+        };
+        match result {
+            Ok(Some(result)) => Self::sync(refund.refund_id, &result, &state.db).await,
+            Ok(None) => Ok(refund),
+            Err(err) => {
+                warn!(
+                    "退款({})提交支付渠道失败, 稍后重试: {}",
+                    refund.refund_id, err
+                );
+                Ok(refund)
+            }
+        }
+    }
+
+    //订单已提交但尚未发起渠道退款的记录, 在写入退款记录的事务提交后调用
+    pub async fn dispatch_order<T>(order_id: Uuid, state: &AppState) -> Result<(), HandleErr<T>> {
+        let records = Refunds::find()
+            .filter(
+                refunds::Column::OrderId
+                    .eq(order_id)
+                    .and(refunds::Column::Status.eq(RefundStatus::Approved))
+                    .and(refunds::Column::ChannelStatus.is_null()),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        for refund in records {
+            Self::dispatch(refund, state).await?;
+        }
+        Ok(())
+    }
+
+    //重新发起未提交成功的微信退款, 跳过刚通过的记录, 避免与提交后的发起并发
+    //返回已提交渠道的数量
+    pub async fn resubmit<T>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        if matches!(state.payment, Payment::Offline) {
+            return Ok(0);
+        }
+        let before = chrono::Utc::now().naive_utc() - chrono::Duration::minutes(1);
+        let wechat_orders = Query::select()
+            .column(orders::Column::OrderId)
+            .from(orders::Entity)
+            .and_where(orders::Column::PayMethod.eq(PayMethod::Wechat))
+            .to_owned();
+        let wechat_shares = Query::select()
+            .column(order_participants::Column::ParticipantId)
+            .from(order_participants::Entity)
+            .and_where(order_participants::Column::PayMethod.eq(PayMethod::Wechat))
+            .to_owned();
+        let records = Refunds::find()
+            .filter(
+                refunds::Column::Status
+                    .eq(RefundStatus::Approved)
+                    .and(refunds::Column::ChannelStatus.is_null())
+                    .and(refunds::Column::HandleTime.lte(before))
+                    .and(
+                        refunds::Column::RefundId
+                            .in_subquery(wechat_shares)
+                            .or(refunds::Column::OrderId.in_subquery(wechat_orders)),
+                    ),
+            )
+            .order_by_asc(refunds::Column::HandleTime)
+            .limit(100)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut submitted = 0;
+        for refund in records {
+            if Self::dispatch(refund, state)
+                .await?
+                .channel_status
+                .is_some()
+            {
+                submitted += 1;
+            }
+        }
+        Ok(submitted)
+    }
+
+    //退还已支付订单的押金, 生成押金退款记录后按订单的支付方式退回
+    //微信支付的在事务提交后由dispatch发起, 拼单订单未记录支付方式, 按线下退款处理
+    pub async fn deposit<T: From<String>, C: ConnectionTrait>(
+        order: &orders::Model,
+        state: &AppState,
+        db: &C,
+    ) -> Result<Option<refunds::Model>, HandleErr<T>> {
+        if order.deposit <= Decimal::ZERO || order.pay_amount.is_none() {
+            return Ok(None);
+        }
+        let record = refunds::ActiveModel {
+            refund_id: NotSet,
+            order_id: Set(order.order_id),
+            user_id: Set(order.user_id),
+            amount: Set(order.deposit),
+            reason: Set("押金退还".to_string()),
+            reason_code: Set(RefundReason::Deposit),
+            status: Set(RefundStatus::Approved),
+            handle_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::submit(record, order, state, db).await.map(Some)
+    }
+
+    //退还拼单中一份已支付的分摊, 以participant_id作为退款记录号, 重复调用不会重复退款
+    //退款记录与分摊状态先行提交, 微信退款在事务外发起, 余额支付的在事务中退回钱包
+    //分摊不是已支付状态时返回None
+    pub async fn share<T: From<String>>(
+        order: &orders::Model,
+        participant_id: Uuid,
+        reason: &str,
+        reason_code: RefundReason,
+        state: &AppState,
+    ) -> Result<Option<refunds::Model>, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let Some(share) = OrderParticipants::find_by_id(participant_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.status == ShareStatus::Paid)
+        else {
+            return Ok(None);
+        };
+        let user_id = share
+            .user_id
+            .ok_or(HandleErr::BadRequest(-1, "分摊未被领取".to_string().into()))?;
+        let method = share.pay_method.clone().unwrap_or(PayMethod::Offline);
+        let refund = refunds::ActiveModel {
+            refund_id: Set(share.participant_id),
+            order_id: Set(order.order_id),
+            user_id: Set(user_id),
+            amount: Set(share.share),
+            reason: Set(reason.chars().take(200).collect()),
+            reason_code: Set(reason_code),
+            status: Set(RefundStatus::Approved),
+            admin_id: NotSet,
+            reply: NotSet,
+            create_time: NotSet,
+            handle_time: Set(Some(now)),
+            channel_refund_id: NotSet,
+            channel_status: Set(
+                (method == PayMethod::Balance).then_some(RefundChannelStatus::Success)
+            ),
+            success_time: Set((method == PayMethod::Balance).then_some(now)),
+        }
+        .insert(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        order_participants::ActiveModel {
+            participant_id: Set(share.participant_id),
+            status: Set(ShareStatus::Refunded),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if method == PayMethod::Balance {
+            WalletOp::change(
+                user_id,
+                share.share,
+                Change {
+                    kind: WalletTxnKind::Refund,
+                    order_id: Some(order.order_id),
+                    recharge_id: None,
+                    admin_id: None,
+                    remark: reason,
+                },
+                &txn,
+            )
+            .await?;
+        }
+        OrderOp::record(
+            order.order_id,
+            "refund",
+            json!({"refund_id":refund.refund_id, "participant_id":share.participant_id, "amount":refund.amount}),
+            &txn,
+        )
+        .await?;
+        LedgerOp::record(
+            order,
+            LedgerKind::Refund,
+            refund.amount,
+            finance::share_deposit(order, refund.amount),
+            Some(method.clone()),
+            state,
+            &txn,
+        )
+        .await?;
+        InboxOp::push(
+            user_id,
+            NotificationKind::Refund,
+            "退款已发起",
+            &format!(
+                "拼单分摊{:.2}元已发起退款, 将按原支付方式退回",
+                refund.amount
+            ),
+            Some(order.order_id),
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "拼单({})分摊({})退款{:.2}元",
+            order.order_id, share.participant_id, share.share
+        );
+        Self::dispatch(refund, state).await.map(Some)
+    }
+
+    //写入微信退款单号与退款结果
+    async fn sync<T, C: ConnectionTrait>(
+        refund_id: Uuid,
+        result: &WechatRefund,
+        db: &C,
+    ) -> Result<refunds::Model, HandleErr<T>> {
+        refunds::ActiveModel {
+            refund_id: Set(refund_id),
+            channel_refund_id: Set(Some(result.refund_id.clone())),
+            channel_status: Set(channel_status(&result.status)),
+            success_time: Set(result
+                .success_time
+                .as_deref()
+                .and_then(|e| chrono::DateTime::parse_from_rfc3339(e).ok())
+                .map(|e| e.naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //微信退款结果通知, 重复通知时结果不变
+    //退款关闭或异常时记录到订单时间线, 由管理员线下处理
+    pub async fn notified<T: From<String>>(
+        result: &WechatRefund,
+        state: &AppState,
+    ) -> Result<refunds::Model, HandleErr<T>> {
+        let refund_id = Uuid::parse_str(&result.out_refund_no).map_err(|_| {
+            HandleErr::BadRequest(
+                -1,
+                format!("out_refund_no无效: {}", result.out_refund_no).into(),
+            )
+        })?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let refund = Refunds::find_by_id(refund_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "退款记录不存在".to_string().into(),
+            ))?;
+        let status = channel_status(&result.status);
+        if refund.channel_status == status {
+            info!("退款({})重复的退款通知", refund.refund_id);
+            return Ok(refund);
+        }
+        let refund = Self::sync(refund.refund_id, result, &txn).await?;
+        if status == Some(RefundChannelStatus::Success) {
+            InboxOp::push(
+                refund.user_id,
+                NotificationKind::Refund,
+                "退款已到账",
+                &format!("订单退款{:.2}元已退回微信支付账户", refund.amount),
+                Some(refund.order_id),
+                &txn,
+            )
+            .await?;
+        }
+        if matches!(
+            status,
+            Some(RefundChannelStatus::Closed | RefundChannelStatus::Abnormal)
+        ) {
+            error!(
+                "订单({})退款({})失败: {}",
+                refund.order_id, refund.refund_id, result.status
+            );
+            OrderOp::record(
+                refund.order_id,
+                "refund_failed",
+                json!({"refund_id":refund.refund_id, "status":status}),
+                &txn,
+            )
+            .await?;
+            InboxOp::push(
+                refund.user_id,
+                NotificationKind::Refund,
+                "退款失败",
+                &format!(
+                    "订单退款{:.2}元未能退回, 场馆将与您联系线下处理",
+                    refund.amount
+                ),
+                Some(refund.order_id),
+                &txn,
+            )
+            .await?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(refund)
+    }
+}
+
+//已通过的退款总额
+//押金单独退还, 不计入
+pub fn refunded(records: &[refunds::Model]) -> Decimal {
+    records
+        .iter()
+        .filter(|e| e.status == RefundStatus::Approved && e.reason_code != RefundReason::Deposit)
+        .map(|e| e.amount)
+        .sum()
+}
+
+//按明细拆分退款金额, 仅场地费、时段加价与附加项目可退
+//每项不超过该明细未退金额, 金额为空时退还该明细剩余全部
+pub fn allocate(
+    items: &[order_items::Model],
+    refunded: &[refund_items::Model],
+    lines: &[RefundLine],
+) -> Result<Vec<(order_items::Model, Decimal)>, String> {
+    let mut result: Vec<(order_items::Model, Decimal)> = vec![];
+    for line in lines {
+        if result.iter().any(|(e, _)| e.item_id == line.item_id) {
+            return Err("退款明细重复".to_string());
+        }
+        let item = items
+            .iter()
+            .find(|e| e.item_id == line.item_id)
+            .ok_or("订单明细不存在".to_string())?;
+        if !matches!(
+            item.kind,
+            OrderItemKind::Base | OrderItemKind::Peak | OrderItemKind::Addon
+        ) || item.amount <= Decimal::ZERO
+        {
+            return Err(format!("{}不可退款", item.name));
+        }
+        let remaining = item.amount
+            - refunded
+                .iter()
+                .filter(|e| e.item_id == Some(item.item_id))
+                .map(|e| e.amount)
+                .sum::<Decimal>();
+        let amount = line.amount.map_or(remaining, money::round);
+        if amount <= Decimal::ZERO || amount > remaining {
+            return Err(format!(
+                "{}退款金额应在0~{:.2}之间",
+                item.name,
+                remaining.max(Decimal::ZERO)
+            ));
+        }
+        result.push((item.clone(), amount));
+    }
+    Ok(result)
+}
+
+//微信退款状态
+fn channel_status(status: &str) -> Option<RefundChannelStatus> {
+    match status {
+        "PROCESSING" => Some(RefundChannelStatus::Processing),
+        "SUCCESS" => Some(RefundChannelStatus::Success),
+        "CLOSED" => Some(RefundChannelStatus::Closed),
+        "ABNORMAL" => Some(RefundChannelStatus::Abnormal),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_allocate() {
+    let d = |v: i64| Decimal::from(v);
+    let item = |kind, amount| order_items::Model {
+        item_id: Uuid::new_v4(),
+        order_id: Uuid::nil(),
+        kind,
+        name: "场地费3.0小时".to_string(),
+        quantity: 1,
+        amount,
+    };
+    let items = vec![
+        item(OrderItemKind::Base, d(150)),
+        item(OrderItemKind::Addon, d(20)),
+        item(OrderItemKind::Deposit, d(30)),
+    ];
+    let line = |i: usize, amount| RefundLine {
+        item_id: items[i].item_id,
+        amount,
+    };
+    //三小时中退一小时场地费, 附加项目全退
+    let result = allocate(&items, &[], &[line(0, Some(d(50))), line(1, None)]).unwrap();
+    let amounts: Vec<_> = result.iter().map(|(_, e)| *e).collect();
+    assert_eq!(amounts, vec![d(50), d(20)]);
+    //已退50后剩余100
+    let refunded = vec![refund_items::Model {
+        refund_item_id: Uuid::nil(),
+        refund_id: Uuid::nil(),
+        item_id: Some(items[0].item_id),
+        kind: OrderItemKind::Base,
+        name: String::new(),
+        amount: d(50),
+    }];
+    let result = allocate(&items, &refunded, &[line(0, None)]).unwrap();
+    assert_eq!(result[0].1, d(100));
+    assert!(allocate(&items, &refunded, &[line(0, Some(d(120)))]).is_err());
+    //押金不可退, 明细不可重复
+    assert!(allocate(&items, &[], &[line(2, None)]).is_err());
+    assert!(allocate(&items, &[], &[line(1, None), line(1, None)]).is_err());
+}
@@ -0,0 +1,140 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::{courts, orders, prelude::Orders, sea_orm_active_enums::OrderState, users},
+        notify::subscribe::SubscribeOp,
+    },
+};
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, Condition, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait,
+};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//开始前多久发送提醒, 分钟
+pub const REMIND_MINUTES: i64 = 60;
+//订阅消息点击后打开的订单详情页
+const ORDER_PAGE: &str = "pages/order/detail";
+
+#[derive(Debug, Clone, sea_orm::FromQueryResult)]
+struct RemindTarget {
+    order_id: Uuid,
+    user_id: Uuid,
+    apt_start: DateTime,
+    court_name: String,
+    phone: Option<String>,
+    openid: Option<String>,
+}
+
+pub struct RemindOp;
+impl RemindOp {
+    //给即将开始且未提醒过的已支付订单发送提醒, 关闭提醒或未绑定手机号与微信的用户跳过
+    //配置了提醒模板时优先使用订阅消息, 用户没有授权次数时改用通知渠道
+    //先标记为已提醒再发送, 多实例运行时不会重复发送
+    pub async fn run<T>(state: &AppState) -> Result<usize, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let targets = Orders::find()
+            .select_only()
+            .column(orders::Column::OrderId)
+            .column(orders::Column::UserId)
+            .column(orders::Column::AptStart)
+            .column_as(courts::Column::CourtName, "court_name")
+            .column_as(users::Column::Phone, "phone")
+            .column_as(users::Column::Openid, "openid")
+            .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+            .join(JoinType::InnerJoin, orders::Relation::Users.def())
+            .filter(
+                orders::Column::Status
+                    .is_in([OrderState::Paid, OrderState::Confirmed])
+                    .and(orders::Column::Reminded.eq(false))
+                    .and(orders::Column::CustomerName.is_null())
+                    .and(orders::Column::AptStart.gt(now))
+                    .and(
+                        orders::Column::AptStart
+                            .lte(now + chrono::Duration::minutes(REMIND_MINUTES)),
+                    )
+                    .and(users::Column::NotifyReminder.eq(true)),
+            )
+            .filter(
+                Condition::any()
+                    .add(users::Column::Phone.is_not_null())
+                    .add(users::Column::Openid.is_not_null()),
+            )
+            .into_model::<RemindTarget>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if targets.is_empty() {
+            return Ok(0);
+        }
+        let claimed = Orders::update_many()
+            .col_expr(orders::Column::Reminded, Expr::value(true))
+            .filter(
+                orders::Column::OrderId
+                    .is_in(targets.iter().map(|e| e.order_id))
+                    .and(orders::Column::Reminded.eq(false)),
+            )
+            .exec_with_returning(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut targets: HashMap<_, _> = targets.into_iter().map(|e| (e.order_id, e)).collect();
+        let mut sent = 0;
+        for order in claimed {
+            let Some(target) = targets.remove(&order.order_id) else {
+                continue;
+            };
+            let content = format!(
+                "您预约的{}将于{}开始, 请准时到场签到",
+                target.court_name,
+                target.apt_start.format("%H:%M")
+            );
+            if let (Some(template), Some(openid)) =
+                (&state.cfg.subscribecfg.reminder, &target.openid)
+            {
+                let page = format!("{}?order_id={}", ORDER_PAGE, target.order_id);
+                let data = [
+                    (template.court_field.as_str(), target.court_name.clone()),
+                    (
+                        template.time_field.as_str(),
+                        target.apt_start.format("%Y-%m-%d %H:%M").to_string(),
+                    ),
+                    (template.remark_field.as_str(), "请准时到场签到".to_string()),
+                ];
+                if SubscribeOp::send(
+                    target.user_id,
+                    openid,
+                    &template.template_id,
+                    &page,
+                    &data,
+                    state,
+                )
+                .await
+                {
+                    sent += 1;
+                    continue;
+                }
+            }
+            let Some(phone) = &target.phone else {
+                info!("订单({})无可用的提醒渠道, 跳过", target.order_id);
+                continue;
+            };
+            match state.notifier.send(phone, "预约提醒", &content).await {
+                Ok(()) => sent += 1,
+                Err(err) => warn!("订单({})提醒发送失败: {}", target.order_id, err),
+            }
+        }
+        Ok(sent)
+    }
+}
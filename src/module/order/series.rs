@@ -0,0 +1,196 @@
+use super::{hold::HoldOp, item::ItemOp, refund::RefundOp, write_err, OrderOp, SaveOrder};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        self, orders,
+        prelude::{Courts, OrderSeries, Orders},
+        sea_orm_active_enums::OrderState,
+    },
+};
+use sea_orm::prelude::{Date, DateTime, Decimal, Time};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::error;
+use uuid::Uuid;
+
+//每周重复预约, 如每周二19:00-21:00共8周
+#[derive(Debug, Deserialize, Clone)]
+pub struct SeriesCreate {
+    pub court_id: Uuid,
+    pub first_date: Date,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub weeks: i32,
+}
+
+impl SeriesCreate {
+    //每次预约的起止时间
+    pub fn occurrences(&self) -> Vec<(DateTime, DateTime)> {
+        (0..self.weeks as i64)
+            .map(|i| {
+                let date = self.first_date + chrono::Duration::weeks(i);
+                (date.and_time(self.start_time), date.and_time(self.end_time))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SeriesCancel {
+    pub series_id: Uuid,
+    #[serde(default)]
+    pub reason: String,
+}
+
+//无法预约的某一次
+#[derive(Debug, Serialize, Clone)]
+pub struct SeriesConflict {
+    pub date: Date,
+    pub msg: String,
+}
+
+pub struct SeriesOp;
+impl SeriesOp {
+    //在一个事务中创建重复预约及全部订单, 任意一次冲突则全部不创建
+    pub async fn create<T: From<String> + From<&'static str>>(
+        user_id: Uuid,
+        schema: &SeriesCreate,
+        orders: Vec<SaveOrder>,
+        state: &AppState,
+    ) -> Result<(db::order_series::Model, Vec<orders::Model>), HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let court = Courts::find_by_id(schema.court_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "court_id无效".to_string().into()))?;
+        for order in &orders {
+            if OrderOp::overlaps(schema.court_id, order.apt_start, order.apt_end, None, &txn)
+                .await?
+                || HoldOp::is_held(
+                    schema.court_id,
+                    order.apt_start,
+                    order.apt_end,
+                    user_id,
+                    &txn,
+                )
+                .await?
+            {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    format!("{} 时间冲突", order.apt_start.date()).into(),
+                ));
+            }
+        }
+        let series = db::order_series::ActiveModel {
+            series_id: NotSet,
+            user_id: Set(user_id),
+            court_id: Set(schema.court_id),
+            first_date: Set(schema.first_date),
+            start_time: Set(schema.start_time),
+            end_time: Set(schema.end_time),
+            weeks: Set(schema.weeks),
+            create_time: NotSet,
+        }
+        .insert(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let mut res = vec![];
+        for order in orders {
+            let mut model = OrderOp::active_model(user_id, order);
+            model.series_id = Set(Some(series.series_id));
+            let order = model.insert(&txn).await.map_err(write_err)?;
+            OrderOp::record(
+                order.order_id,
+                "created",
+                json!({"cost":order.cost, "deposit":order.deposit, "series_id":series.series_id}),
+                &txn,
+            )
+            .await?;
+            ItemOp::write(&order, &court, &[], &txn).await?;
+            res.push(order);
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok((series, res))
+    }
+
+    //用户自己的重复预约下的订单
+    pub async fn orders<T: From<&'static str>>(
+        series_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<orders::Model>, HandleErr<T>> {
+        OrderSeries::find_by_id(series_id)
+            .filter(db::order_series::Column::UserId.eq(user_id))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "重复预约不存在".into()))?;
+        Orders::find()
+            .filter(orders::Column::SeriesId.eq(series_id))
+            .order_by_asc(orders::Column::AptStart)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //在同一事务中取消重复预约的订单及对应手续费, 任一失败则全部不取消
+    //提交后再对已支付的订单发起渠道退款, 返回取消的订单数
+    pub async fn cancel<T: From<String>>(
+        orders: &[(orders::Model, Decimal)],
+        reason: String,
+        state: &AppState,
+    ) -> Result<usize, HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let mut paid = vec![];
+        for (order, fee) in orders {
+            let cancelled = OrderOp::cancel_in(order, reason.clone(), *fee, state, &txn).await?;
+            if matches!(order.status, OrderState::Paid | OrderState::Confirmed) {
+                paid.push(cancelled.order_id);
+            }
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        for order_id in paid {
+            RefundOp::dispatch_order(order_id, state).await?;
+        }
+        Ok(orders.len())
+    }
+}
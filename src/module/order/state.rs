@@ -0,0 +1,106 @@
+use crate::module::db::sea_orm_active_enums::OrderState;
+use std::fmt;
+
+//状态流转失败的错误码
+pub const ERR_INVALID_TRANSITION: i32 = 2001;
+pub const ERR_FINISHED: i32 = 2002;
+
+//已释放时段的状态, 冲突检测与统计时忽略
+pub const RELEASED: [OrderState; 2] = [OrderState::Cancelled, OrderState::Refunded];
+
+impl OrderState {
+    //订单未结束, 可以修改或取消
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            OrderState::PendingPayment | OrderState::Paid | OrderState::Confirmed
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransitionErr {
+    //订单已结束, 不能再变化
+    Finished(OrderState),
+    //不允许的状态流转
+    Invalid { from: OrderState, to: OrderState },
+}
+
+impl TransitionErr {
+    pub fn code(&self) -> i32 {
+        match self {
+            TransitionErr::Finished(_) => ERR_FINISHED,
+            TransitionErr::Invalid { .. } => ERR_INVALID_TRANSITION,
+        }
+    }
+}
+
+impl fmt::Display for TransitionErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransitionErr::Finished(state) => write!(f, "订单{}", name(state)),
+            TransitionErr::Invalid { from, to } => {
+                write!(f, "订单{}, 不能变为{}", name(from), name(to))
+            }
+        }
+    }
+}
+
+pub fn name(state: &OrderState) -> &'static str {
+    match state {
+        OrderState::PendingPayment => "待支付",
+        OrderState::Paid => "已支付",
+        OrderState::Confirmed => "已确认",
+        OrderState::Completed => "已完成",
+        OrderState::Cancelled => "已取消",
+        OrderState::Refunded => "已退款",
+        OrderState::NoShow => "未到场",
+    }
+}
+
+//pending_payment -> paid -> confirmed -> completed
+//未完成的订单可以取消, 已支付的订单取消后退款
+//已支付但结束前未签到的订单标记为未到场
+pub fn transition(from: &OrderState, to: &OrderState) -> Result<OrderState, TransitionErr> {
+    use OrderState::*;
+    let allowed = match from {
+        PendingPayment => matches!(to, Paid | Cancelled),
+        Paid => matches!(to, Confirmed | Cancelled | Refunded | NoShow),
+        Confirmed => matches!(to, Completed | Cancelled | Refunded | NoShow),
+        Cancelled => matches!(to, Refunded),
+        Completed | Refunded | NoShow => return Err(TransitionErr::Finished(from.clone())),
+    };
+    if allowed {
+        Ok(to.clone())
+    } else {
+        Err(TransitionErr::Invalid {
+            from: from.clone(),
+            to: to.clone(),
+        })
+    }
+}
+
+#[test]
+fn test_transition() {
+    use OrderState::*;
+    assert_eq!(transition(&PendingPayment, &Paid), Ok(Paid));
+    assert_eq!(transition(&Paid, &Confirmed), Ok(Confirmed));
+    assert_eq!(transition(&Confirmed, &Completed), Ok(Completed));
+    assert_eq!(transition(&Cancelled, &Refunded), Ok(Refunded));
+    assert_eq!(transition(&Confirmed, &NoShow), Ok(NoShow));
+    assert_eq!(
+        transition(&NoShow, &Refunded),
+        Err(TransitionErr::Finished(NoShow))
+    );
+    assert_eq!(
+        transition(&PendingPayment, &Completed),
+        Err(TransitionErr::Invalid {
+            from: PendingPayment,
+            to: Completed
+        })
+    );
+    assert_eq!(
+        transition(&Completed, &Cancelled),
+        Err(TransitionErr::Finished(Completed))
+    );
+}
@@ -0,0 +1,221 @@
+use crate::{appstate::AppState, error::HandleErr};
+use chrono::{Datelike, NaiveDate};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::{DbBackend, FromQueryResult, Statement};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::error;
+use uuid::Uuid;
+
+//计入统计的订单状态, 未到场的预约也算已预约
+const COUNTED: &str = "('paid', 'confirmed', 'completed', 'no_show')";
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SummaryQuery {
+    //默认为今年
+    pub year: Option<i32>,
+}
+
+//年度运动报告
+#[derive(Debug, Serialize, Clone)]
+pub struct UserSummary {
+    pub year: i32,
+    pub bookings: i64,
+    pub hours: f64,
+    //实付金额扣除已通过的退款
+    pub spend: Decimal,
+    pub favorite_court: Option<FavoriteCourt>,
+    //最常打球的星期, 1为周一, 7为周日
+    pub top_weekday: Option<i32>,
+    pub monthly: Vec<MonthlySpend>,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct FavoriteCourt {
+    pub court_id: Uuid,
+    pub court_name: String,
+    pub bookings: i64,
+    pub hours: f64,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct MonthlySpend {
+    pub month: i32,
+    pub bookings: i64,
+    pub spend: Decimal,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct Total {
+    bookings: i64,
+    hours: f64,
+    spend: Decimal,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct Weekday {
+    weekday: i32,
+}
+
+//(用户, 年份) → (统计日期, 报告)
+type Cached = HashMap<(Uuid, i32), (NaiveDate, UserSummary)>;
+
+//按天缓存的报告, 日期变化后重新统计
+#[derive(Debug, Clone, Default)]
+pub struct SummaryCache(Arc<RwLock<Cached>>);
+
+impl SummaryCache {
+    fn get(&self, key: (Uuid, i32), today: NaiveDate) -> Option<UserSummary> {
+        self.0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .filter(|(date, _)| *date == today)
+            .map(|(_, summary)| summary.clone())
+    }
+
+    //写入时清理前一天的缓存
+    fn put(&self, key: (Uuid, i32), today: NaiveDate, summary: UserSummary) {
+        let mut cache = self.0.write().unwrap_or_else(|e| e.into_inner());
+        cache.retain(|_, (date, _)| *date == today);
+        cache.insert(key, (today, summary));
+    }
+}
+
+pub struct SummaryOp;
+impl SummaryOp {
+    pub async fn get<T: From<&'static str>>(
+        user_id: Uuid,
+        query: SummaryQuery,
+        state: &AppState,
+    ) -> Result<UserSummary, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let year = query.year.unwrap_or(now.year());
+        let (from, to) = year_range(year).ok_or(HandleErr::BadRequest(-1, "年份无效".into()))?;
+        if let Some(summary) = state.summaries.get((user_id, year), now.date()) {
+            return Ok(summary);
+        }
+        let values = || [user_id.into(), from.into(), to.into()];
+        let total = Total::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                r#"select count(*) as bookings,
+                    coalesce(sum(extract(epoch from o.apt_end - o.apt_start) / 3600), 0)::float8 as hours,
+                    coalesce(sum(o.cost - coalesce(r.amount, 0)), 0)::numeric as spend
+                   from orders o
+                   left join (select order_id, sum(amount) as amount from refunds
+                              where status = 'approved' group by order_id) r on r.order_id = o.order_id
+                   where o.user_id = $1 and o.customer_name is null
+                     and o.apt_start >= $2 and o.apt_start < $3 and o.status in {}"#,
+                COUNTED
+            ),
+            values(),
+        ))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .ok_or(HandleErr::BadRequest(-1, "统计失败".into()))?;
+        //次数相同时按时长
+        let favorite_court = FavoriteCourt::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                r#"select o.court_id, c.court_name, count(*) as bookings,
+                    sum(extract(epoch from o.apt_end - o.apt_start) / 3600)::float8 as hours
+                   from orders o
+                   join courts c on c.court_id = o.court_id
+                   where o.user_id = $1 and o.customer_name is null
+                     and o.apt_start >= $2 and o.apt_start < $3 and o.status in {}
+                   group by o.court_id, c.court_name
+                   order by bookings desc, hours desc limit 1"#,
+                COUNTED
+            ),
+            values(),
+        ))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let top_weekday = Weekday::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                r#"select extract(isodow from o.apt_start)::int4 as weekday
+                   from orders o
+                   where o.user_id = $1 and o.customer_name is null
+                     and o.apt_start >= $2 and o.apt_start < $3 and o.status in {}
+                   group by 1 order by count(*) desc, 1 limit 1"#,
+                COUNTED
+            ),
+            values(),
+        ))
+        .one(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .map(|e| e.weekday);
+        let monthly = MonthlySpend::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                r#"select extract(month from o.apt_start)::int4 as month, count(*) as bookings,
+                    coalesce(sum(o.cost - coalesce(r.amount, 0)), 0)::numeric as spend
+                   from orders o
+                   left join (select order_id, sum(amount) as amount from refunds
+                              where status = 'approved' group by order_id) r on r.order_id = o.order_id
+                   where o.user_id = $1 and o.customer_name is null
+                     and o.apt_start >= $2 and o.apt_start < $3 and o.status in {}
+                   group by 1 order by 1"#,
+                COUNTED
+            ),
+            values(),
+        ))
+        .all(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let summary = UserSummary {
+            year,
+            bookings: total.bookings,
+            hours: total.hours,
+            spend: total.spend,
+            favorite_court,
+            top_weekday,
+            monthly,
+        };
+        state
+            .summaries
+            .put((user_id, year), now.date(), summary.clone());
+        Ok(summary)
+    }
+}
+
+//一年的起止时间, 左闭右开
+fn year_range(year: i32) -> Option<(DateTime, DateTime)> {
+    if !(2000..=9999).contains(&year) {
+        return None;
+    }
+    let from = NaiveDate::from_ymd_opt(year, 1, 1)?.and_hms_opt(0, 0, 0)?;
+    let to = NaiveDate::from_ymd_opt(year + 1, 1, 1)?.and_hms_opt(0, 0, 0)?;
+    Some((from, to))
+}
+
+#[test]
+fn test_year_range() {
+    let (from, to) = year_range(2024).unwrap();
+    assert_eq!(from.to_string(), "2024-01-01 00:00:00");
+    assert_eq!(to.to_string(), "2025-01-01 00:00:00");
+    assert!(year_range(1999).is_none());
+    assert!(year_range(10000).is_none());
+}
@@ -0,0 +1,552 @@
+use super::db::{
+    package_purchases, package_usages, packages,
+    prelude::{PackagePurchases, PackageUsages, Packages, UserPackages},
+    sea_orm_active_enums::RechargeStatus,
+    user_packages,
+};
+use super::finance::LedgerOp;
+use super::money;
+use super::payment::{
+    notification::NotificationOp,
+    wechat::{from_fen, to_fen, Transaction},
+};
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct PackageSave {
+    pub package_id: Option<Uuid>,
+    pub name: String,
+    pub hours: f64,
+    pub price: Decimal,
+    pub valid_days: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PackageDel {
+    pub package_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PackageBuy {
+    pub package_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct UserPackageSchema {
+    #[serde(flatten)]
+    pub card: user_packages::Model,
+    pub package: Option<packages::Model>,
+    //过期后剩余时数作废
+    pub expired: bool,
+}
+
+pub struct PackageOp;
+impl PackageOp {
+    //管理员的次卡套餐, only_enabled时只返回可购买的
+    pub async fn packages<T, C: ConnectionTrait>(
+        admin_id: Uuid,
+        only_enabled: bool,
+        db: &C,
+    ) -> Result<Vec<packages::Model>, HandleErr<T>> {
+        let mut query = Packages::find().filter(packages::Column::AdminId.eq(admin_id));
+        if only_enabled {
+            query = query.filter(packages::Column::Enabled.eq(true));
+        }
+        query
+            .order_by_asc(packages::Column::Price)
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    pub async fn save<T: From<String>>(
+        admin_id: Uuid,
+        schema: PackageSave,
+        state: &AppState,
+    ) -> Result<packages::Model, HandleErr<T>> {
+        if let Some(package_id) = schema.package_id {
+            Packages::find_by_id(package_id)
+                .filter(packages::Column::AdminId.eq(admin_id))
+                .one(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+                .ok_or(HandleErr::BadRequest(
+                    -1,
+                    "次卡套餐不存在".to_string().into(),
+                ))?;
+        }
+        packages::ActiveModel {
+            package_id: schema.package_id.map(Set).unwrap_or(NotSet),
+            admin_id: Set(admin_id),
+            name: Set(schema.name),
+            hours: Set(schema.hours),
+            price: Set(money::round(schema.price)),
+            valid_days: Set(schema.valid_days),
+            enabled: Set(schema.enabled),
+            create_time: NotSet,
+        }
+        .save(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //删除未售出过的套餐, 已售出的只能停用
+    pub async fn delete<T: From<String>>(
+        package_id: Uuid,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let package = Packages::find_by_id(package_id)
+            .filter(packages::Column::AdminId.eq(admin_id))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "次卡套餐不存在".to_string().into(),
+            ))?;
+        let sold = PackagePurchases::find()
+            .filter(package_purchases::Column::PackageId.eq(package.package_id))
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if sold > 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "已售出的次卡套餐只能停用".to_string().into(),
+            ));
+        }
+        Packages::delete_by_id(package.package_id)
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //创建待支付的购买单, 支付成功通知后发放次卡
+    pub async fn purchase<T: From<String>>(
+        user_id: Uuid,
+        package_id: Uuid,
+        state: &AppState,
+    ) -> Result<(package_purchases::Model, packages::Model), HandleErr<T>> {
+        let package = Packages::find_by_id(package_id)
+            .filter(packages::Column::Enabled.eq(true))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "次卡套餐不存在或已停售".to_string().into(),
+            ))?;
+        let purchase = package_purchases::ActiveModel {
+            purchase_id: NotSet,
+            package_id: Set(package.package_id),
+            user_id: Set(user_id),
+            amount: Set(package.price),
+            status: NotSet,
+            transaction_id: NotSet,
+            create_time: NotSet,
+            paid_time: NotSet,
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok((purchase, package))
+    }
+
+    //购买支付成功通知, 锁定购买单后发放次卡, 已发放时视为重复通知
+    pub async fn purchased<T: From<String>>(
+        transaction: &Transaction,
+        state: &AppState,
+    ) -> Result<package_purchases::Model, HandleErr<T>> {
+        let purchase_id = Uuid::parse_str(&transaction.out_trade_no).map_err(|_| {
+            HandleErr::BadRequest(
+                -1,
+                format!("out_trade_no无效: {}", transaction.out_trade_no).into(),
+            )
+        })?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let purchase = PackagePurchases::find_by_id(purchase_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "购买单不存在".to_string().into()))?;
+        if purchase.status == RechargeStatus::Paid
+            || !NotificationOp::claim(transaction, &txn).await?
+        {
+            info!("次卡购买单({})重复的支付通知", purchase.purchase_id);
+            return Ok(purchase);
+        }
+        //支付金额与购买单不一致时不发放, 通知已记录, 由管理员对账处理
+        if transaction.amount.total != to_fen(purchase.amount) {
+            error!(
+                "次卡购买单({})支付金额{:.2}元与应付金额{:.2}元不一致",
+                purchase.purchase_id,
+                from_fen(transaction.amount.total),
+                purchase.amount
+            );
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            return Ok(purchase);
+        }
+        let package = Packages::find_by_id(purchase.package_id)
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "次卡套餐不存在".to_string().into(),
+            ))?;
+        let now = chrono::Utc::now().naive_utc();
+        let purchase = package_purchases::ActiveModel {
+            purchase_id: Set(purchase.purchase_id),
+            status: Set(RechargeStatus::Paid),
+            transaction_id: Set(Some(transaction.transaction_id.clone())),
+            paid_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        LedgerOp::package(&purchase, package.admin_id, state, &txn).await?;
+        user_packages::ActiveModel {
+            user_package_id: NotSet,
+            user_id: Set(purchase.user_id),
+            package_id: Set(package.package_id),
+            admin_id: Set(package.admin_id),
+            purchase_id: Set(Some(purchase.purchase_id)),
+            hours: Set(package.hours),
+            hours_left: Set(package.hours),
+            expire_time: Set(now + chrono::Duration::days(package.valid_days as i64)),
+            create_time: NotSet,
+        }
+        .insert(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "用户({})购买次卡({}) {}小时",
+            purchase.user_id, package.name, package.hours
+        );
+        Ok(purchase)
+    }
+
+    //用户持有的次卡, 未过期的在前
+    pub async fn mine<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<UserPackageSchema>, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        Ok(UserPackages::find()
+            .filter(user_packages::Column::UserId.eq(user_id))
+            .find_also_related(Packages)
+            .order_by_desc(user_packages::Column::ExpireTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|(card, package)| UserPackageSchema {
+                expired: card.expire_time <= now,
+                card,
+                package,
+            })
+            .collect())
+    }
+
+    //可用于该管理员球场的剩余时数, 不含已过期的次卡
+    pub async fn available<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        admin_id: Uuid,
+        db: &C,
+    ) -> Result<f64, HandleErr<T>> {
+        Ok(Self::usable(user_id, admin_id, false, db)
+            .await?
+            .iter()
+            .map(|e| e.hours_left)
+            .sum())
+    }
+
+    async fn usable<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        admin_id: Uuid,
+        lock: bool,
+        db: &C,
+    ) -> Result<Vec<user_packages::Model>, HandleErr<T>> {
+        let mut query = UserPackages::find()
+            .filter(
+                user_packages::Column::UserId
+                    .eq(user_id)
+                    .and(user_packages::Column::AdminId.eq(admin_id))
+                    .and(user_packages::Column::HoursLeft.gt(0.0))
+                    .and(user_packages::Column::ExpireTime.gt(chrono::Utc::now().naive_utc())),
+            )
+            .order_by_asc(user_packages::Column::ExpireTime);
+        if lock {
+            query = query.lock_exclusive();
+        }
+        query.all(db).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //下单时扣减次卡时数, 先到期的先扣, 应在创建订单的事务中调用
+    //锁定次卡后剩余时数不足时(并发使用)下单失败
+    pub async fn consume<T: From<&'static str>, C: ConnectionTrait>(
+        user_id: Uuid,
+        admin_id: Uuid,
+        order_id: Uuid,
+        hours: f64,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let mut remain = hours;
+        for card in Self::usable(user_id, admin_id, true, db).await? {
+            if remain < 0.005 {
+                break;
+            }
+            let used = remain.min(card.hours_left);
+            user_packages::ActiveModel {
+                user_package_id: Set(card.user_package_id),
+                hours_left: Set(((card.hours_left - used) * 100.0).round() / 100.0),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            package_usages::ActiveModel {
+                usage_id: NotSet,
+                user_package_id: Set(card.user_package_id),
+                order_id: Set(order_id),
+                hours: Set(used),
+                restored: NotSet,
+                create_time: NotSet,
+            }
+            .insert(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            remain -= used;
+        }
+        if remain >= 0.005 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "次卡剩余时数不足, 请重新下单".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    //订单已扣减且所在次卡仍有效的时数, 修改时段时可重新用于该订单
+    pub async fn restorable<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<f64, HandleErr<T>> {
+        let usages = PackageUsages::find()
+            .filter(
+                package_usages::Column::OrderId
+                    .eq(order_id)
+                    .and(package_usages::Column::Restored.eq(false)),
+            )
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if usages.is_empty() {
+            return Ok(0.0);
+        }
+        let valid: Vec<Uuid> = UserPackages::find()
+            .filter(
+                user_packages::Column::UserPackageId
+                    .is_in(usages.iter().map(|e| e.user_package_id))
+                    .and(user_packages::Column::ExpireTime.gt(chrono::Utc::now().naive_utc())),
+            )
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| e.user_package_id)
+            .collect();
+        Ok(usages
+            .iter()
+            .filter(|e| valid.contains(&e.user_package_id))
+            .map(|e| e.hours)
+            .sum())
+    }
+
+    //修改时段后退回原扣减的时数再按新时数扣减, 应在修改订单的事务中调用
+    pub async fn reconsume<T: From<&'static str>, C: ConnectionTrait>(
+        user_id: Uuid,
+        admin_id: Uuid,
+        order_id: Uuid,
+        hours: f64,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        Self::restore(order_id, db).await?;
+        if hours > 0.0 {
+            Self::consume(user_id, admin_id, order_id, hours, db).await?;
+        }
+        Ok(())
+    }
+
+    //订单取消或退款后退回扣减的时数, 以未退回为条件更新, 重复调用不会多退
+    //已过期的次卡同样退回, 但不能再使用
+    pub async fn restore<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<f64, HandleErr<T>> {
+        let usages = PackageUsages::find()
+            .filter(
+                package_usages::Column::OrderId
+                    .eq(order_id)
+                    .and(package_usages::Column::Restored.eq(false)),
+            )
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut restored = 0.0;
+        for usage in usages {
+            let rows_affected = PackageUsages::update_many()
+                .col_expr(package_usages::Column::Restored, Expr::value(true))
+                .filter(
+                    package_usages::Column::UsageId
+                        .eq(usage.usage_id)
+                        .and(package_usages::Column::Restored.eq(false)),
+                )
+                .exec(db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+                .rows_affected;
+            if rows_affected == 0 {
+                continue;
+            }
+            UserPackages::update_many()
+                .col_expr(
+                    user_packages::Column::HoursLeft,
+                    Expr::col(user_packages::Column::HoursLeft).add(usage.hours),
+                )
+                .filter(user_packages::Column::UserPackageId.eq(usage.user_package_id))
+                .exec(db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            restored += usage.hours;
+        }
+        if restored > 0.0 {
+            info!("订单({})退回次卡{}小时", order_id, restored);
+        }
+        Ok(restored)
+    }
+}
@@ -0,0 +1,805 @@
+use super::db::{
+    courts, orders, partner_posts, partner_requests,
+    prelude::{Orders, PartnerPosts, PartnerRequests},
+    sea_orm_active_enums::{
+        NotificationKind, OrderState, PartnerPostStatus, PartnerRequestStatus, SportType,
+    },
+    users,
+};
+use super::notify::inbox::InboxOp;
+use super::order::state;
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::{Expr, Query, SelectStatement};
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult, JoinType,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const MAX_SLOTS: i16 = 20;
+const NOTE_LEN: usize = 200;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartnerOpen {
+    pub order_id: Uuid,
+    //招募人数, 不包括发起人
+    pub slots: i16,
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartnerBoardQuery {
+    pub sport_type: Option<SportType>,
+    #[serde(default = "super::order::default_page")]
+    pub page: u64,
+    #[serde(default = "super::order::default_page_size")]
+    pub page_size: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartnerJoin {
+    pub post_id: Uuid,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartnerHandle {
+    pub request_id: Uuid,
+    pub approve: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartnerRequestId {
+    pub request_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartnerPostId {
+    pub post_id: Uuid,
+}
+
+//招募信息, 附预约的球场与时段
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct PartnerPostSchema {
+    pub post_id: Uuid,
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub court_id: Uuid,
+    pub court_name: String,
+    pub court_location: String,
+    pub sport_type: SportType,
+    pub apt_start: DateTime,
+    pub apt_end: DateTime,
+    pub slots: i16,
+    pub joined: i16,
+    pub note: String,
+    pub status: PartnerPostStatus,
+    pub create_time: DateTime,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct PartnerRequestSchema {
+    pub request_id: Uuid,
+    pub post_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub message: String,
+    pub status: PartnerRequestStatus,
+    pub create_time: DateTime,
+    pub handle_time: Option<DateTime>,
+}
+
+//发起人查看的招募, 附收到的申请
+#[derive(Debug, Serialize, Clone)]
+pub struct PartnerMineSchema {
+    pub post: PartnerPostSchema,
+    pub requests: Vec<PartnerRequestSchema>,
+}
+
+//申请人查看的申请, 附所申请的招募
+#[derive(Debug, Serialize, Clone)]
+pub struct PartnerAppliedSchema {
+    pub request: PartnerRequestSchema,
+    pub post: PartnerPostSchema,
+}
+
+pub struct PartnerOp;
+impl PartnerOp {
+    //为已支付且未开始的预约发起招募
+    pub async fn open<T: From<String>>(
+        user_id: Uuid,
+        schema: PartnerOpen,
+        state: &AppState,
+    ) -> Result<partner_posts::Model, HandleErr<T>> {
+        if !(1..=MAX_SLOTS).contains(&schema.slots) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("招募人数须为1~{}人", MAX_SLOTS).into(),
+            ));
+        }
+        let note = schema.note.trim();
+        if note.chars().count() > NOTE_LEN {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("说明不能超过{}字", NOTE_LEN).into(),
+            ));
+        }
+        let order = Orders::find_by_id(schema.order_id)
+            .filter(orders::Column::UserId.eq(user_id))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "订单信息不存在".to_string().into(),
+            ))?;
+        if !joinable(&order, chrono::Utc::now().naive_utc()) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "仅已支付且未开始的预约可以招募球友".to_string().into(),
+            ));
+        }
+        let exists = PartnerPosts::find()
+            .filter(partner_posts::Column::OrderId.eq(order.order_id))
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if exists > 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "该预约已发起过招募".to_string().into(),
+            ));
+        }
+        let post = partner_posts::ActiveModel {
+            post_id: NotSet,
+            order_id: Set(order.order_id),
+            user_id: Set(user_id),
+            slots: Set(schema.slots),
+            joined: NotSet,
+            note: Set(note.to_string()),
+            status: NotSet,
+            create_time: NotSet,
+            update_time: NotSet,
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})为订单({})发起招募", user_id, order.order_id);
+        Ok(post)
+    }
+
+    //招募中的球友信息, 开始时间近的在前
+    pub async fn board<T>(
+        query: &PartnerBoardQuery,
+        state: &AppState,
+    ) -> Result<(Vec<PartnerPostSchema>, u64), HandleErr<T>> {
+        let mut select = Self::select()
+            .filter(partner_posts::Column::Status.eq(PartnerPostStatus::Open))
+            .filter(orders::Column::AptStart.gt(chrono::Utc::now().naive_utc()))
+            .filter(orders::Column::Status.is_not_in(state::RELEASED));
+        if let Some(sport_type) = &query.sport_type {
+            select = select.filter(courts::Column::SportType.eq(sport_type.clone()));
+        }
+        let paginator = select
+            .order_by_asc(orders::Column::AptStart)
+            .into_model::<PartnerPostSchema>()
+            .paginate(&state.db, query.page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let posts = paginator
+            .fetch_page(query.page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((posts, total))
+    }
+
+    //申请加入, 撤回后可重新申请, 被拒绝后不能再申请
+    pub async fn join<T: From<String>>(
+        user_id: Uuid,
+        user_name: &str,
+        schema: PartnerJoin,
+        state: &AppState,
+    ) -> Result<partner_requests::Model, HandleErr<T>> {
+        let message = schema.message.trim();
+        if message.chars().count() > NOTE_LEN {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("留言不能超过{}字", NOTE_LEN).into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let (post, order) = Self::locked(schema.post_id, &txn).await?;
+        if post.user_id == user_id {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "不能申请加入自己的预约".to_string().into(),
+            ));
+        }
+        if post.status != PartnerPostStatus::Open
+            || !joinable(&order, chrono::Utc::now().naive_utc())
+        {
+            return Err(HandleErr::BadRequest(-1, "招募已结束".to_string().into()));
+        }
+        let existing = PartnerRequests::find()
+            .filter(partner_requests::Column::PostId.eq(post.post_id))
+            .filter(partner_requests::Column::UserId.eq(user_id))
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let request = match existing {
+            Some(e) if e.status == PartnerRequestStatus::Withdrawn => {
+                partner_requests::ActiveModel {
+                    request_id: Set(e.request_id),
+                    message: Set(message.to_string()),
+                    status: Set(PartnerRequestStatus::Pending),
+                    create_time: Set(chrono::Utc::now().naive_utc()),
+                    handle_time: Set(None),
+                    ..Default::default()
+                }
+                .update(&txn)
+                .await
+            }
+            Some(e) if e.status == PartnerRequestStatus::Rejected => {
+                return Err(HandleErr::BadRequest(-1, "申请已被拒绝".to_string().into()));
+            }
+            Some(_) => {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "已申请过该预约".to_string().into(),
+                ));
+            }
+            None => {
+                partner_requests::ActiveModel {
+                    request_id: NotSet,
+                    post_id: Set(post.post_id),
+                    user_id: Set(user_id),
+                    message: Set(message.to_string()),
+                    status: NotSet,
+                    create_time: NotSet,
+                    handle_time: NotSet,
+                }
+                .insert(&txn)
+                .await
+            }
+        }
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        InboxOp::push(
+            post.user_id,
+            NotificationKind::Partner,
+            "新的球友申请",
+            &format!(
+                "{}申请加入您{}开始的预约",
+                user_name,
+                order.apt_start.format("%m-%d %H:%M")
+            ),
+            Some(order.order_id),
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(request)
+    }
+
+    //发起人审核申请, 通过后人数已满时招募变为已满员
+    pub async fn handle<T: From<String>>(
+        user_id: Uuid,
+        schema: PartnerHandle,
+        state: &AppState,
+    ) -> Result<partner_requests::Model, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let request = Self::request(schema.request_id, &txn).await?;
+        let (post, order) = Self::locked(request.post_id, &txn).await?;
+        if post.user_id != user_id {
+            return Err(HandleErr::BadRequest(-1, "申请不存在".to_string().into()));
+        }
+        if request.status != PartnerRequestStatus::Pending {
+            return Err(HandleErr::BadRequest(-1, "申请已处理".to_string().into()));
+        }
+        if schema.approve {
+            if post.status != PartnerPostStatus::Open || !joinable(&order, now) {
+                return Err(HandleErr::BadRequest(-1, "招募已结束".to_string().into()));
+            }
+            let joined = post.joined + 1;
+            partner_posts::ActiveModel {
+                post_id: Set(post.post_id),
+                joined: Set(joined),
+                status: Set(if joined >= post.slots {
+                    PartnerPostStatus::Full
+                } else {
+                    PartnerPostStatus::Open
+                }),
+                update_time: Set(now),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        let request = partner_requests::ActiveModel {
+            request_id: Set(request.request_id),
+            status: Set(if schema.approve {
+                PartnerRequestStatus::Approved
+            } else {
+                PartnerRequestStatus::Rejected
+            }),
+            handle_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let result = if schema.approve {
+            "已通过"
+        } else {
+            "未通过"
+        };
+        InboxOp::push(
+            request.user_id,
+            NotificationKind::Partner,
+            &format!("球友申请{}", result),
+            &format!(
+                "您申请加入的{}开始的预约{}",
+                order.apt_start.format("%m-%d %H:%M"),
+                result
+            ),
+            Some(order.order_id),
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "用户({})处理招募({})的申请({}): {}",
+            user_id, post.post_id, request.request_id, result
+        );
+        Ok(request)
+    }
+
+    //撤回申请, 已加入的球友在预约开始前可以退出
+    pub async fn withdraw<T: From<String>>(
+        user_id: Uuid,
+        request_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let request = Self::request(request_id, &txn).await?;
+        if request.user_id != user_id {
+            return Err(HandleErr::BadRequest(-1, "申请不存在".to_string().into()));
+        }
+        let (post, order) = Self::locked(request.post_id, &txn).await?;
+        match request.status {
+            PartnerRequestStatus::Pending => {}
+            PartnerRequestStatus::Approved if order.apt_start > now => {
+                let joined = post.joined - 1;
+                partner_posts::ActiveModel {
+                    post_id: Set(post.post_id),
+                    joined: Set(joined),
+                    status: Set(if post.status == PartnerPostStatus::Full {
+                        PartnerPostStatus::Open
+                    } else {
+                        post.status.clone()
+                    }),
+                    update_time: Set(now),
+                    ..Default::default()
+                }
+                .update(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+                InboxOp::push(
+                    post.user_id,
+                    NotificationKind::Partner,
+                    "球友退出",
+                    &format!(
+                        "有球友退出了您{}开始的预约",
+                        order.apt_start.format("%m-%d %H:%M")
+                    ),
+                    Some(order.order_id),
+                    &txn,
+                )
+                .await?;
+            }
+            PartnerRequestStatus::Approved => {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "预约已开始, 不能退出".to_string().into(),
+                ));
+            }
+            _ => {
+                return Err(HandleErr::BadRequest(-1, "申请已结束".to_string().into()));
+            }
+        }
+        partner_requests::ActiveModel {
+            request_id: Set(request.request_id),
+            status: Set(PartnerRequestStatus::Withdrawn),
+            handle_time: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})撤回申请({})", user_id, request_id);
+        Ok(())
+    }
+
+    //发起人关闭招募, 已加入的球友保留, 待审核的申请视为未通过
+    pub async fn close<T: From<String>>(
+        user_id: Uuid,
+        post_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let (post, order) = Self::locked(post_id, &txn).await?;
+        if post.user_id != user_id {
+            return Err(HandleErr::BadRequest(-1, "招募不存在".to_string().into()));
+        }
+        if post.status == PartnerPostStatus::Closed {
+            return Err(HandleErr::BadRequest(-1, "招募已关闭".to_string().into()));
+        }
+        Self::end(&post, &order, "发起人已关闭招募", false, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})关闭招募({})", user_id, post_id);
+        Ok(())
+    }
+
+    //订单取消或退款后关闭招募, 通知已加入与待审核的球友, 在订单状态变更的事务中执行
+    pub async fn release<T, C: ConnectionTrait>(
+        order: &orders::Model,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let post = PartnerPosts::find()
+            .filter(partner_posts::Column::OrderId.eq(order.order_id))
+            .filter(partner_posts::Column::Status.ne(PartnerPostStatus::Closed))
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if let Some(post) = post {
+            Self::end(&post, order, "发起人的预约已取消", true, db).await?;
+        }
+        Ok(())
+    }
+
+    //我发起的招募与收到的申请, 最新的在前
+    pub async fn mine<T>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<PartnerMineSchema>, u64), HandleErr<T>> {
+        let paginator = Self::select()
+            .filter(partner_posts::Column::UserId.eq(user_id))
+            .order_by_desc(partner_posts::Column::CreateTime)
+            .into_model::<PartnerPostSchema>()
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let posts = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut requests: HashMap<Uuid, Vec<PartnerRequestSchema>> = HashMap::new();
+        for request in PartnerRequests::find()
+            .filter(partner_requests::Column::PostId.is_in(posts.iter().map(|e| e.post_id)))
+            .filter(partner_requests::Column::Status.ne(PartnerRequestStatus::Withdrawn))
+            .column(users::Column::UserName)
+            .join(JoinType::InnerJoin, partner_requests::Relation::Users.def())
+            .order_by_asc(partner_requests::Column::CreateTime)
+            .into_model::<PartnerRequestSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+        {
+            requests.entry(request.post_id).or_default().push(request);
+        }
+        let posts = posts
+            .into_iter()
+            .map(|post| PartnerMineSchema {
+                requests: requests.remove(&post.post_id).unwrap_or_default(),
+                post,
+            })
+            .collect();
+        Ok((posts, total))
+    }
+
+    //我提交的申请, 最新的在前
+    pub async fn applied<T>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<PartnerAppliedSchema>, u64), HandleErr<T>> {
+        let paginator = PartnerRequests::find()
+            .filter(partner_requests::Column::UserId.eq(user_id))
+            .column(users::Column::UserName)
+            .join(JoinType::InnerJoin, partner_requests::Relation::Users.def())
+            .order_by_desc(partner_requests::Column::CreateTime)
+            .into_model::<PartnerRequestSchema>()
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let requests = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut posts: HashMap<Uuid, PartnerPostSchema> = Self::select()
+            .filter(partner_posts::Column::PostId.is_in(requests.iter().map(|e| e.post_id)))
+            .into_model::<PartnerPostSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| (e.post_id, e))
+            .collect();
+        let requests = requests
+            .into_iter()
+            .filter_map(|request| {
+                posts
+                    .remove(&request.post_id)
+                    .map(|post| PartnerAppliedSchema { request, post })
+            })
+            .collect();
+        Ok((requests, total))
+    }
+
+    //用户作为球友加入的订单, 用于订单历史
+    pub fn joined_orders(user_id: Uuid) -> SelectStatement {
+        Query::select()
+            .column((PartnerPosts, partner_posts::Column::OrderId))
+            .from(PartnerPosts)
+            .inner_join(
+                PartnerRequests,
+                Expr::col((PartnerRequests, partner_requests::Column::PostId))
+                    .equals((PartnerPosts, partner_posts::Column::PostId)),
+            )
+            .and_where(partner_requests::Column::UserId.eq(user_id))
+            .and_where(partner_requests::Column::Status.eq(PartnerRequestStatus::Approved))
+            .to_owned()
+    }
+
+    //招募及其预约的球场与时段, 附发起人昵称
+    fn select() -> sea_orm::Select<PartnerPosts> {
+        PartnerPosts::find()
+            .join(JoinType::InnerJoin, partner_posts::Relation::Orders.def())
+            .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+            .join(JoinType::InnerJoin, partner_posts::Relation::Users.def())
+            .column(users::Column::UserName)
+            .column(courts::Column::CourtId)
+            .column(courts::Column::CourtName)
+            .column_as(courts::Column::Location, "court_location")
+            .column(courts::Column::SportType)
+            .column(orders::Column::AptStart)
+            .column(orders::Column::AptEnd)
+    }
+
+    async fn request<T: From<String>, C: ConnectionTrait>(
+        request_id: Uuid,
+        db: &C,
+    ) -> Result<partner_requests::Model, HandleErr<T>> {
+        PartnerRequests::find_by_id(request_id)
+            .lock_exclusive()
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "申请不存在".to_string().into()))
+    }
+
+    //锁定招募, 同一招募的审核与退出串行执行
+    async fn locked<T: From<String>, C: ConnectionTrait>(
+        post_id: Uuid,
+        db: &C,
+    ) -> Result<(partner_posts::Model, orders::Model), HandleErr<T>> {
+        let post = PartnerPosts::find_by_id(post_id)
+            .lock_exclusive()
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "招募不存在".to_string().into()))?;
+        let order = Orders::find_by_id(post.order_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "招募不存在".to_string().into()))?;
+        Ok((post, order))
+    }
+
+    //关闭招募, 待审核的申请视为未通过并通知申请人, notify_joined时同时通知已加入的球友
+    async fn end<T, C: ConnectionTrait>(
+        post: &partner_posts::Model,
+        order: &orders::Model,
+        reason: &str,
+        notify_joined: bool,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        partner_posts::ActiveModel {
+            post_id: Set(post.post_id),
+            status: Set(PartnerPostStatus::Closed),
+            update_time: Set(now),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let mut users = PartnerRequests::update_many()
+            .col_expr(
+                partner_requests::Column::Status,
+                Expr::value(PartnerRequestStatus::Rejected),
+            )
+            .col_expr(partner_requests::Column::HandleTime, Expr::value(now))
+            .filter(partner_requests::Column::PostId.eq(post.post_id))
+            .filter(partner_requests::Column::Status.eq(PartnerRequestStatus::Pending))
+            .exec_with_returning(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| e.user_id)
+            .collect::<Vec<_>>();
+        if notify_joined {
+            users.extend(
+                PartnerRequests::find()
+                    .filter(partner_requests::Column::PostId.eq(post.post_id))
+                    .filter(partner_requests::Column::Status.eq(PartnerRequestStatus::Approved))
+                    .all(db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?
+                    .into_iter()
+                    .map(|e| e.user_id),
+            );
+        }
+        for user_id in users {
+            InboxOp::push(
+                user_id,
+                NotificationKind::Partner,
+                "球友招募已结束",
+                &format!(
+                    "{}开始的预约: {}",
+                    order.apt_start.format("%m-%d %H:%M"),
+                    reason
+                ),
+                Some(order.order_id),
+                db,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+//已支付或已确认且未开始的预约可以招募与加入
+fn joinable(order: &orders::Model, now: DateTime) -> bool {
+    matches!(order.status, OrderState::Paid | OrderState::Confirmed) && order.apt_start > now
+}
@@ -0,0 +1,314 @@
+use super::db::{password_resets, prelude::PasswordResets, prelude::Users, users};
+use super::session::SessionOp;
+use crate::{appstate::AppState, error::HandleErr, utils::passwd};
+use rand_core::RngCore;
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//验证码有效期(分钟)
+const CODE_TTL: i64 = 10;
+//验证失败达到次数后验证码作废
+const MAX_ATTEMPTS: i32 = 5;
+//同一账号重新发送的间隔(秒)
+const RESEND_INTERVAL: i64 = 60;
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotSchema {
+    pub phone: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifySchema {
+    pub phone: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetSchema {
+    pub phone: String,
+    pub code: String,
+    pub new_pwd: String,
+}
+
+pub struct PasswordResetOp;
+impl PasswordResetOp {
+    //向绑定的手机号发送验证码, 账号不存在时同样返回成功, 避免探测手机号是否注册
+    pub async fn forgot<T: From<&'static str>>(
+        schema: ForgotSchema,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let Some(user) = Self::user(&schema.phone, state).await? else {
+            warn!("找回密码: 手机号({})未绑定可用账号", schema.phone);
+            return Ok(());
+        };
+        let now = chrono::Utc::now().naive_utc();
+        let code = generate();
+        //重新发送时覆盖旧验证码并清零失败次数, 间隔不足的不覆盖, 并发请求只有一个能写入
+        let rows_affected = PasswordResets::insert(password_resets::ActiveModel {
+            user_id: Set(user.user_id),
+            code_hash: Set(digest(user.user_id, &code)),
+            attempts: Set(0),
+            send_time: Set(now),
+            expire_time: Set(now + chrono::Duration::minutes(CODE_TTL)),
+        })
+        .on_conflict(
+            OnConflict::column(password_resets::Column::UserId)
+                .update_columns([
+                    password_resets::Column::CodeHash,
+                    password_resets::Column::Attempts,
+                    password_resets::Column::SendTime,
+                    password_resets::Column::ExpireTime,
+                ])
+                .action_and_where(
+                    Expr::col((password_resets::Entity, password_resets::Column::SendTime))
+                        .lte(now - chrono::Duration::seconds(RESEND_INTERVAL)),
+                )
+                .to_owned(),
+        )
+        .exec_without_returning(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "验证码发送过于频繁".into()));
+        }
+        let content = format!(
+            "您正在找回密码, 验证码为{}, {}分钟内有效, 请勿泄露给他人",
+            code, CODE_TTL
+        );
+        state
+            .notifier
+            .send(&schema.phone, "找回密码", &content)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        info!("用户({})申请找回密码, 验证码已发送", user.user_id);
+        Ok(())
+    }
+
+    //仅校验验证码, 不作废, 供前端在填写新密码前确认
+    pub async fn verify<T: From<&'static str>>(
+        schema: VerifySchema,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let user = Self::user(&schema.phone, state)
+            .await?
+            .ok_or(HandleErr::BadRequest(-1, "验证码无效".into()))?;
+        Self::check(user.user_id, &schema.code, state).await
+    }
+
+    //校验验证码并设置新密码, 同时解除锁定并撤销全部会话, 返回撤销数
+    pub async fn reset<T: From<&'static str>>(
+        schema: ResetSchema,
+        state: &AppState,
+    ) -> Result<u64, HandleErr<T>> {
+        if !(6..=64).contains(&schema.new_pwd.chars().count()) {
+            return Err(HandleErr::BadRequest(-1, "新密码长度须为6-64位".into()));
+        }
+        let user = Self::user(&schema.phone, state)
+            .await?
+            .ok_or(HandleErr::BadRequest(-1, "验证码无效".into()))?;
+        Self::check(user.user_id, &schema.code, state).await?;
+        let password_hash = passwd::hash_password(&schema.new_pwd)?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        //验证码只能使用一次, 并发重置时只有一个请求能删除成功
+        let rows_affected = PasswordResets::delete_by_id(user.user_id)
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "验证码无效".into()));
+        }
+        users::ActiveModel {
+            user_id: Set(user.user_id),
+            user_pwd: Set(password_hash),
+            hash_version: Set(passwd::HASH_VERSION),
+            failed_logins: Set(0),
+            locked_until: Set(None),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let revoked = SessionOp::revoke_all(user.user_id, None, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "用户({})通过验证码重置密码, 撤销{}个会话",
+            user.user_id, revoked
+        );
+        Ok(revoked)
+    }
+
+    //已注销或已封禁的账号不能找回密码
+    async fn user<T>(phone: &str, state: &AppState) -> Result<Option<users::Model>, HandleErr<T>> {
+        Ok(Users::find()
+            .filter(users::Column::Phone.eq(phone.trim()))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.deleted_time.is_none() && e.disabled_time.is_none()))
+    }
+
+    //先占用一次校验次数再比对验证码, 并发请求不能超出次数上限, 校验通过时退还
+    async fn check<T: From<&'static str>>(
+        user_id: Uuid,
+        code: &str,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let reserved = PasswordResets::update_many()
+            .col_expr(
+                password_resets::Column::Attempts,
+                Expr::col(password_resets::Column::Attempts).add(1),
+            )
+            .filter(password_resets::Column::UserId.eq(user_id))
+            .filter(password_resets::Column::Attempts.lt(MAX_ATTEMPTS))
+            .exec_with_returning(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .pop();
+        let Some(reset) = reserved else {
+            //未申请过或次数已用尽
+            let exists = PasswordResets::find_by_id(user_id)
+                .one(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            return Err(match exists {
+                Some(_) => HandleErr::BadRequest(-1, "验证码错误次数过多, 请重新获取".into()),
+                None => HandleErr::BadRequest(-1, "验证码无效".into()),
+            });
+        };
+        let now = chrono::Utc::now().naive_utc();
+        match matches(&reset, &digest(user_id, code.trim()), now) {
+            Ok(()) => {
+                PasswordResets::update_many()
+                    .col_expr(
+                        password_resets::Column::Attempts,
+                        Expr::col(password_resets::Column::Attempts).sub(1),
+                    )
+                    .filter(password_resets::Column::UserId.eq(user_id))
+                    .filter(password_resets::Column::Attempts.gt(0))
+                    .exec(&state.db)
+                    .await
+                    .map_err(|err| {
+                        let id = Uuid::new_v4();
+                        error!("{} >>>> {}", id, err.to_string());
+                        HandleErr::ServerInnerErr(id)
+                    })?;
+                Ok(())
+            }
+            Err(Mismatch::Wrong) => {
+                warn!(
+                    "用户({})找回密码验证码错误, 第{}次",
+                    user_id, reset.attempts
+                );
+                Err(HandleErr::BadRequest(-1, "验证码错误".into()))
+            }
+            Err(Mismatch::Expired) => Err(HandleErr::BadRequest(-1, "验证码已过期".into())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Mismatch {
+    Expired,
+    Wrong,
+}
+
+//次数上限已在占用校验次数时检查
+fn matches(reset: &password_resets::Model, code_hash: &str, now: DateTime) -> Result<(), Mismatch> {
+    if reset.expire_time <= now {
+        Err(Mismatch::Expired)
+    } else if reset.code_hash != code_hash {
+        Err(Mismatch::Wrong)
+    } else {
+        Ok(())
+    }
+}
+
+//6位数字验证码
+fn generate() -> String {
+    format!("{:06}", rand_core::OsRng.next_u32() % 1_000_000)
+}
+
+//以用户id加盐的SHA-256, 十六进制
+fn digest(user_id: Uuid, code: &str) -> String {
+    let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+    ctx.update(user_id.as_bytes());
+    ctx.update(code.as_bytes());
+    ctx.finish()
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect()
+}
+
+#[test]
+fn test_reset_code() {
+    let code = generate();
+    assert_eq!(code.len(), 6);
+    assert!(code.chars().all(|e| e.is_ascii_digit()));
+    let user_id = Uuid::new_v4();
+    assert_eq!(digest(user_id, &code).len(), 64);
+    assert_ne!(digest(user_id, &code), digest(Uuid::new_v4(), &code));
+    let now = chrono::Utc::now().naive_utc();
+    let reset = password_resets::Model {
+        user_id,
+        code_hash: digest(user_id, "123456"),
+        attempts: 0,
+        send_time: now,
+        expire_time: now + chrono::Duration::minutes(CODE_TTL),
+    };
+    assert_eq!(matches(&reset, &digest(user_id, "123456"), now), Ok(()));
+    assert_eq!(
+        matches(&reset, &digest(user_id, "654321"), now),
+        Err(Mismatch::Wrong)
+    );
+    assert_eq!(
+        matches(&reset, &digest(user_id, "123456"), reset.expire_time),
+        Err(Mismatch::Expired)
+    );
+    //过期优先于验证码错误
+    assert_eq!(
+        matches(&reset, &digest(user_id, "654321"), reset.expire_time),
+        Err(Mismatch::Expired)
+    );
+}
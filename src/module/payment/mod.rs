@@ -0,0 +1,142 @@
+use crate::cfg::PaymentCfg;
+use crate::module::db::{orders, refunds};
+use axum::http::HeaderMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use tracing::info;
+use uuid::Uuid;
+pub mod notification;
+pub mod provider;
+pub mod reconcile;
+pub mod wechat;
+
+//支付单的业务类型
+pub const ATTACH_ORDER: &str = "order";
+pub const ATTACH_RECHARGE: &str = "recharge";
+pub const ATTACH_PACKAGE: &str = "package";
+pub const ATTACH_GIFT_CARD: &str = "gift_card";
+pub const ATTACH_GROUP_SHARE: &str = "group_share";
+
+//支付渠道, 线下收款时退款只记录日志, 由财务线下处理
+#[derive(Debug, Clone, Default)]
+pub enum Payment {
+    #[default]
+    Offline,
+    Wechat(Box<wechat::WechatPay>),
+}
+
+impl Payment {
+    pub fn new(cfg: &PaymentCfg) -> crate::App::Result<Self> {
+        Ok(match cfg {
+            PaymentCfg::Offline => Payment::Offline,
+            PaymentCfg::Wechat(cfg) => Payment::Wechat(Box::new(wechat::WechatPay::new(cfg)?)),
+        })
+    }
+
+    //发起线上支付, 返回小程序调起支付的参数
+    //线下收款或金额为0时不需要支付, 返回None
+    //out_trade_no为订单号或充值单号, attach区分业务类型, 支付通知时原样返回
+    pub async fn prepay(
+        &self,
+        out_trade_no: Uuid,
+        attach: &str,
+        description: &str,
+        amount: Decimal,
+        openid: Option<&str>,
+    ) -> crate::App::Result<Option<wechat::RequestPayment>> {
+        match self {
+            Payment::Offline => Ok(None),
+            Payment::Wechat(_) if wechat::to_fen(amount) <= 0 => Ok(None),
+            Payment::Wechat(pay) => {
+                let openid = openid.ok_or(anyhow::anyhow!("用户未绑定微信"))?;
+                let prepay_id = pay
+                    .prepay(
+                        out_trade_no,
+                        attach,
+                        description,
+                        wechat::to_fen(amount),
+                        openid,
+                    )
+                    .await?;
+                Ok(Some(pay.request_payment(&prepay_id)?))
+            }
+        }
+    }
+
+    //关闭超时未支付订单的支付单, 防止取消后用户仍能支付
+    pub async fn close(&self, order_id: Uuid) -> crate::App::Result<()> {
+        match self {
+            Payment::Offline => Ok(()),
+            Payment::Wechat(pay) => pay.close(order_id).await,
+        }
+    }
+
+    //下载某日的交易账单, 线下收款时没有账单
+    pub async fn trade_bill(
+        &self,
+        date: NaiveDate,
+    ) -> crate::App::Result<Option<Vec<wechat::BillRow>>> {
+        match self {
+            Payment::Offline => Ok(None),
+            Payment::Wechat(pay) => Ok(Some(pay.trade_bill(date).await?)),
+        }
+    }
+
+    //验签并解密支付渠道的回调通知, 返回事件类型与业务数据
+    pub fn notify<R: DeserializeOwned>(
+        &self,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> crate::App::Result<(String, R)> {
+        match self {
+            Payment::Offline => Err(anyhow::anyhow!("未启用微信支付")),
+            Payment::Wechat(pay) => pay.notify(headers, body),
+        }
+    }
+
+    //原路退款, 微信支付的订单调用退款接口, 返回微信的退款单
+    //线下收款或线下支付的订单只记录日志, 由财务线下处理
+    pub async fn refund(
+        &self,
+        refund: &refunds::Model,
+        order: &orders::Model,
+    ) -> crate::App::Result<Option<wechat::WechatRefund>> {
+        match &order.transaction_id {
+            Some(transaction_id) => {
+                let total = order.pay_amount.unwrap_or(order.cost + order.deposit);
+                self.refund_transaction(refund, transaction_id, total).await
+            }
+            None => {
+                info!("订单({})待线下退款{:.2}元", order.order_id, refund.amount);
+                Ok(None)
+            }
+        }
+    }
+
+    //按微信支付订单号退款, 用于订单以外单独支付的款项, 如拼单分摊
+    //total为该笔支付的金额
+    pub async fn refund_transaction(
+        &self,
+        refund: &refunds::Model,
+        transaction_id: &str,
+        total: Decimal,
+    ) -> crate::App::Result<Option<wechat::WechatRefund>> {
+        match self {
+            Payment::Wechat(pay) => Ok(Some(
+                pay.refund(
+                    refund.refund_id,
+                    transaction_id,
+                    wechat::to_fen(refund.amount),
+                    wechat::to_fen(total),
+                    &refund.reason,
+                )
+                .await?,
+            )),
+            Payment::Offline => {
+                info!("退款({})待线下退款{:.2}元", refund.refund_id, refund.amount);
+                Ok(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use super::wechat::{from_fen, Transaction};
+use crate::{
+    error::HandleErr,
+    module::db::{payment_notifications, prelude::PaymentNotifications},
+};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue::NotSet, ConnectionTrait, EntityTrait, Set};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub struct NotificationOp;
+impl NotificationOp {
+    //登记支付通知, 同一微信支付订单号只登记一次, 已登记时返回false
+    //应在处理通知的事务中调用, 处理失败回滚后重试的通知可重新登记
+    //并发的相同通知在唯一键上等待先到者提交, 随后视为重复
+    pub async fn claim<T, C: ConnectionTrait>(
+        transaction: &Transaction,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        let rows_affected = PaymentNotifications::insert(payment_notifications::ActiveModel {
+            transaction_id: Set(transaction.transaction_id.clone()),
+            out_trade_no: Set(transaction.out_trade_no.clone()),
+            attach: Set(transaction.attach.clone().unwrap_or_default()),
+            amount: Set(from_fen(transaction.amount.total)),
+            create_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::column(payment_notifications::Column::TransactionId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if rows_affected == 0 {
+            info!("微信支付订单({})的通知已处理过", transaction.transaction_id);
+        }
+        Ok(rows_affected > 0)
+    }
+}
+
+//本实例正在处理的支付通知, 同一交易的并发通知只处理一个, 其余返回失败等待重试
+#[derive(Debug, Clone, Default)]
+pub struct InFlight(Arc<Mutex<HashSet<String>>>);
+
+impl InFlight {
+    //开始处理, 已在处理中时返回None, 返回值释放时结束处理
+    pub fn enter(&self, key: &str) -> Option<InFlightGuard> {
+        let mut keys = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !keys.insert(key.to_string()) {
+            return None;
+        }
+        Some(InFlightGuard {
+            keys: self.0.clone(),
+            key: key.to_string(),
+        })
+    }
+}
+
+pub struct InFlightGuard {
+    keys: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.keys
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
+
+#[test]
+fn test_in_flight() {
+    use std::sync::Barrier;
+    let in_flight = InFlight::default();
+    let barrier = Arc::new(Barrier::new(8));
+    //8个并发的相同通知, 同时持有期间只有一个进入处理
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let in_flight = in_flight.clone();
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                let guard = in_flight.enter("4200000001");
+                barrier.wait();
+                guard.is_some()
+            })
+        })
+        .collect();
+    let entered = handles
+        .into_iter()
+        .map(|e| e.join().unwrap())
+        .filter(|e| *e)
+        .count();
+    assert_eq!(entered, 1);
+    //处理结束后重试的通知可再次进入, 不同交易互不影响
+    let guard = in_flight.enter("4200000001").unwrap();
+    assert!(in_flight.enter("4200000001").is_none());
+    assert!(in_flight.enter("4200000002").is_some());
+    drop(guard);
+    assert!(in_flight.enter("4200000001").is_some());
+}
+
+#[tokio::test]
+async fn test_claim() {
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    //唯一键冲突时不写入, 受影响行数为0
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 0,
+            },
+        ])
+        .into_connection();
+    let transaction: Transaction = serde_json::from_value(serde_json::json!({
+        "out_trade_no": Uuid::new_v4().to_string(),
+        "transaction_id": "4200000001",
+        "trade_state": "SUCCESS",
+        "amount": { "total": 100 },
+    }))
+    .unwrap();
+    //首次登记成功, 相同微信支付订单号再次登记视为已处理
+    assert!(NotificationOp::claim::<String, _>(&transaction, &db)
+        .await
+        .unwrap());
+    assert!(!NotificationOp::claim::<String, _>(&transaction, &db)
+        .await
+        .unwrap());
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 2);
+    assert!(log
+        .iter()
+        .all(|e| format!("{:?}", e).contains("ON CONFLICT")));
+}
@@ -0,0 +1,270 @@
+use super::{wechat, ATTACH_ORDER};
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        db::{
+            orders, refunds,
+            sea_orm_active_enums::{PayMethod, WalletTxnKind},
+        },
+        order::OrderOp,
+        wallet::{Change, WalletOp},
+    },
+};
+use sea_orm::{ActiveModelTrait, ConnectionTrait, Set, TransactionTrait};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//发起支付的用户信息
+pub struct Payer<'a> {
+    pub openid: Option<&'a str>,
+    //支付单描述, 如"球场名 01-01 18:00"
+    pub description: &'a str,
+}
+
+//发起支付的结果
+//线上渠道返回调起支付的参数(线下收款模式或金额为0时为空), 支付通知后完成
+//余额与线下收款即时完成, 返回已支付的订单
+pub enum Charge {
+    Prepay(Option<wechat::RequestPayment>),
+    Paid(Box<orders::Model>),
+}
+
+//退款的结果
+pub enum Refunded {
+    //已退回, 如退回钱包
+    Instant,
+    //事务提交后由RefundOp::dispatch提交支付渠道, 等待退款通知
+    Deferred,
+    //由财务线下处理
+    Manual,
+}
+
+//订单的支付方式, 下单或支付时选择, 订单的pay_method记录实际使用的方式
+pub trait PaymentProvider {
+    fn method(&self) -> PayMethod;
+
+    async fn charge<T: From<String>>(
+        &self,
+        order: &orders::Model,
+        payer: &Payer<'_>,
+        state: &AppState,
+    ) -> Result<Charge, HandleErr<T>>;
+
+    //按原支付方式退款
+    async fn refund<T: From<String>, C: ConnectionTrait>(
+        &self,
+        refund: &refunds::Model,
+        order: &orders::Model,
+        state: &AppState,
+        db: &C,
+    ) -> Result<Refunded, HandleErr<T>>;
+}
+
+pub struct WechatProvider;
+impl PaymentProvider for WechatProvider {
+    fn method(&self) -> PayMethod {
+        PayMethod::Wechat
+    }
+
+    async fn charge<T: From<String>>(
+        &self,
+        order: &orders::Model,
+        payer: &Payer<'_>,
+        state: &AppState,
+    ) -> Result<Charge, HandleErr<T>> {
+        let payment = state
+            .payment
+            .prepay(
+                order.order_id,
+                ATTACH_ORDER,
+                payer.description,
+                order.cost + order.deposit,
+                payer.openid,
+            )
+            .await
+            .map_err(|err| {
+                warn!("订单({})发起支付失败: {}", order.order_id, err);
+                HandleErr::BadRequest(-1, "发起支付失败, 请稍后重试".to_string().into())
+            })?;
+        //记录已发起支付的金额, 此后订单金额不能再修改
+        if payment.is_some() {
+            orders::ActiveModel {
+                order_id: Set(order.order_id),
+                prepay_amount: Set(Some(order.cost + order.deposit)),
+                ..Default::default()
+            }
+            .update(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        Ok(Charge::Prepay(payment))
+    }
+
+    //不在数据库事务中调用支付渠道, 退款记录提交后再发起
+    async fn refund<T: From<String>, C: ConnectionTrait>(
+        &self,
+        _refund: &refunds::Model,
+        _order: &orders::Model,
+        _state: &AppState,
+        _db: &C,
+    ) -> Result<Refunded, HandleErr<T>> {
+        Ok(Refunded::Deferred)
+    }
+}
+
+pub struct WalletProvider;
+impl PaymentProvider for WalletProvider {
+    fn method(&self) -> PayMethod {
+        PayMethod::Balance
+    }
+
+    //先关闭微信支付单, 关闭失败(如已支付)时不扣款
+    async fn charge<T: From<String>>(
+        &self,
+        order: &orders::Model,
+        _payer: &Payer<'_>,
+        state: &AppState,
+    ) -> Result<Charge, HandleErr<T>> {
+        let balance = WalletOp::balance(order.user_id, &state.db).await?;
+        if balance < order.cost + order.deposit {
+            return Err(HandleErr::BadRequest(-1, "余额不足".to_string().into()));
+        }
+        state.payment.close(order.order_id).await.map_err(|err| {
+            warn!("订单({})关闭支付失败: {}", order.order_id, err);
+            HandleErr::BadRequest(-1, "订单支付处理中, 请稍后刷新".to_string().into())
+        })?;
+        Ok(Charge::Paid(Box::new(
+            WalletOp::pay_order(order, state).await?,
+        )))
+    }
+
+    async fn refund<T: From<String>, C: ConnectionTrait>(
+        &self,
+        refund: &refunds::Model,
+        order: &orders::Model,
+        _state: &AppState,
+        db: &C,
+    ) -> Result<Refunded, HandleErr<T>> {
+        WalletOp::change(
+            order.user_id,
+            refund.amount,
+            Change {
+                kind: WalletTxnKind::Refund,
+                order_id: Some(order.order_id),
+                recharge_id: None,
+                admin_id: None,
+                remark: &refund.reason,
+            },
+            db,
+        )
+        .await?;
+        Ok(Refunded::Instant)
+    }
+}
+
+//线下收款, 由球场管理员确认收款
+pub struct OfflineProvider;
+impl PaymentProvider for OfflineProvider {
+    fn method(&self) -> PayMethod {
+        PayMethod::Offline
+    }
+
+    async fn charge<T: From<String>>(
+        &self,
+        order: &orders::Model,
+        _payer: &Payer<'_>,
+        state: &AppState,
+    ) -> Result<Charge, HandleErr<T>> {
+        state.payment.close(order.order_id).await.map_err(|err| {
+            warn!("订单({})关闭支付失败: {}", order.order_id, err);
+            HandleErr::BadRequest(-1, "订单支付处理中, 请稍后刷新".to_string().into())
+        })?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let paid = OrderOp::paid_offline(order, state, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(Charge::Paid(Box::new(paid)))
+    }
+
+    async fn refund<T: From<String>, C: ConnectionTrait>(
+        &self,
+        refund: &refunds::Model,
+        order: &orders::Model,
+        _state: &AppState,
+        _db: &C,
+    ) -> Result<Refunded, HandleErr<T>> {
+        info!("订单({})待线下退款{:.2}元", order.order_id, refund.amount);
+        Ok(Refunded::Manual)
+    }
+}
+
+pub enum Provider {
+    Wechat(WechatProvider),
+    Wallet(WalletProvider),
+    Offline(OfflineProvider),
+}
+
+impl Provider {
+    //次卡全额抵扣的订单没有实际收款, 退款按线下处理
+    pub fn of(method: &PayMethod) -> Self {
+        match method {
+            PayMethod::Wechat => Provider::Wechat(WechatProvider),
+            PayMethod::Balance => Provider::Wallet(WalletProvider),
+            PayMethod::Offline | PayMethod::Package => Provider::Offline(OfflineProvider),
+        }
+    }
+
+    //已支付订单实际使用的方式, 未记录时按线下处理
+    pub fn of_order(order: &orders::Model) -> Self {
+        Self::of(order.pay_method.as_ref().unwrap_or(&PayMethod::Offline))
+    }
+}
+
+impl PaymentProvider for Provider {
+    fn method(&self) -> PayMethod {
+        match self {
+            Provider::Wechat(p) => p.method(),
+            Provider::Wallet(p) => p.method(),
+            Provider::Offline(p) => p.method(),
+        }
+    }
+
+    async fn charge<T: From<String>>(
+        &self,
+        order: &orders::Model,
+        payer: &Payer<'_>,
+        state: &AppState,
+    ) -> Result<Charge, HandleErr<T>> {
+        match self {
+            Provider::Wechat(p) => p.charge(order, payer, state).await,
+            Provider::Wallet(p) => p.charge(order, payer, state).await,
+            Provider::Offline(p) => p.charge(order, payer, state).await,
+        }
+    }
+
+    async fn refund<T: From<String>, C: ConnectionTrait>(
+        &self,
+        refund: &refunds::Model,
+        order: &orders::Model,
+        state: &AppState,
+        db: &C,
+    ) -> Result<Refunded, HandleErr<T>> {
+        match self {
+            Provider::Wechat(p) => p.refund(refund, order, state, db).await,
+            Provider::Wallet(p) => p.refund(refund, order, state, db).await,
+            Provider::Offline(p) => p.refund(refund, order, state, db).await,
+        }
+    }
+}
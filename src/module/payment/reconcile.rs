@@ -0,0 +1,580 @@
+use super::wechat::BillRow;
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::db::{
+        gift_cards, orders, package_purchases,
+        prelude::{
+            Courts, GiftCards, Orders, PackagePurchases, Packages, ReconcileIssues, ReconcileRuns,
+            WalletRecharges,
+        },
+        reconcile_issues, reconcile_runs,
+        sea_orm_active_enums::{GiftCardStatus, RechargeStatus, ReconcileIssueKind},
+        wallet_recharges,
+    },
+};
+use sea_orm::prelude::{Date, DateTime, Decimal};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    Set, TransactionTrait,
+};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//微信支付账单按北京时间划分账单日, 本地时间为UTC
+const BILL_OFFSET_HOURS: i64 = 8;
+//前一日的账单在次日10点后才能下载
+const BILL_READY_HOUR: u32 = 10;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IssueQuery {
+    pub bill_date: Option<Date>,
+    pub resolved: Option<bool>,
+    #[serde(default = "crate::module::order::default_page")]
+    pub page: u64,
+    #[serde(default = "crate::module::order::default_page_size")]
+    pub page_size: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IssueResolve {
+    pub issue_id: Uuid,
+    pub remark: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReconcileRun {
+    pub bill_date: Date,
+}
+
+//本地的微信支付记录, 包括订单、钱包充值与次卡购买
+#[derive(Debug, Clone)]
+pub struct LocalPayment {
+    pub out_trade_no: String,
+    pub transaction_id: String,
+    pub amount: Decimal,
+    pub paid_time: DateTime,
+    //订单所属球场或次卡套餐的管理员
+    pub admin_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub kind: ReconcileIssueKind,
+    pub out_trade_no: String,
+    pub transaction_id: Option<String>,
+    pub local_amount: Option<Decimal>,
+    pub remote_amount: Option<Decimal>,
+    pub admin_id: Option<Uuid>,
+}
+
+//账单日对应的UTC时间范围
+pub fn window(date: Date) -> (DateTime, DateTime) {
+    let start = date.and_hms_opt(0, 0, 0).unwrap() - chrono::Duration::hours(BILL_OFFSET_HOURS);
+    (start, start + chrono::Duration::days(1))
+}
+
+//比对账单中支付成功的交易与本地记录
+//local应包含账单日内支付的记录, 以及账单中出现的交易对应的记录(支付通知可能跨日到达)
+pub fn diff(bill: &[BillRow], local: &[LocalPayment], date: Date) -> Vec<Issue> {
+    let (start, end) = window(date);
+    let by_transaction: HashMap<&str, &LocalPayment> = local
+        .iter()
+        .map(|e| (e.transaction_id.as_str(), e))
+        .collect();
+    let by_trade_no: HashMap<&str, &LocalPayment> =
+        local.iter().map(|e| (e.out_trade_no.as_str(), e)).collect();
+    let mut matched = HashSet::new();
+    let mut issues = vec![];
+    for row in bill.iter().filter(|e| e.trade_state == "SUCCESS") {
+        let issue = |kind, local: Option<&LocalPayment>| Issue {
+            kind,
+            out_trade_no: row.out_trade_no.clone(),
+            transaction_id: Some(row.transaction_id.clone()),
+            local_amount: local.map(|e| e.amount),
+            remote_amount: Some(row.amount),
+            admin_id: local.and_then(|e| e.admin_id),
+        };
+        if let Some(local) = by_transaction.get(row.transaction_id.as_str()) {
+            matched.insert(row.transaction_id.as_str());
+            if local.amount != row.amount {
+                issues.push(issue(ReconcileIssueKind::AmountMismatch, Some(local)));
+            }
+        } else if let Some(local) = by_trade_no.get(row.out_trade_no.as_str()) {
+            //同一笔业务对应了另一笔微信支付
+            issues.push(issue(ReconcileIssueKind::Duplicate, Some(local)));
+        } else {
+            issues.push(issue(ReconcileIssueKind::MissingLocal, None));
+        }
+    }
+    for local in local.iter().filter(|e| {
+        e.paid_time >= start && e.paid_time < end && !matched.contains(e.transaction_id.as_str())
+    }) {
+        issues.push(Issue {
+            kind: ReconcileIssueKind::MissingRemote,
+            out_trade_no: local.out_trade_no.clone(),
+            transaction_id: Some(local.transaction_id.clone()),
+            local_amount: Some(local.amount),
+            remote_amount: None,
+            admin_id: local.admin_id,
+        });
+    }
+    issues
+}
+
+pub struct ReconcileOp;
+impl ReconcileOp {
+    //本地的微信支付记录, 按支付时间或账单中的交易号、商户订单号查找
+    async fn local<T>(
+        bill: &[BillRow],
+        date: Date,
+        state: &AppState,
+    ) -> Result<Vec<LocalPayment>, HandleErr<T>> {
+        let (start, end) = window(date);
+        let transactions: Vec<String> = bill.iter().map(|e| e.transaction_id.clone()).collect();
+        let ids: Vec<Uuid> = bill
+            .iter()
+            .filter_map(|e| Uuid::parse_str(&e.out_trade_no).ok())
+            .collect();
+        let mut local = vec![];
+        let orders = Orders::find()
+            .filter(
+                Condition::all()
+                    .add(orders::Column::TransactionId.is_not_null())
+                    .add(
+                        Condition::any()
+                            .add(orders::Column::PaidTime.between(start, end))
+                            .add(orders::Column::TransactionId.is_in(transactions.clone()))
+                            .add(orders::Column::OrderId.is_in(ids.clone())),
+                    ),
+            )
+            .find_also_related(Courts)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        for (order, court) in orders {
+            local.push(LocalPayment {
+                out_trade_no: order.order_id.simple().to_string(),
+                transaction_id: order.transaction_id.unwrap_or_default(),
+                amount: order.pay_amount.unwrap_or(order.cost + order.deposit),
+                paid_time: order.paid_time.unwrap_or(order.create_time),
+                admin_id: court.map(|e| e.admin_id),
+            });
+        }
+        let recharges = WalletRecharges::find()
+            .filter(
+                Condition::all()
+                    .add(wallet_recharges::Column::Status.eq(RechargeStatus::Paid))
+                    .add(
+                        Condition::any()
+                            .add(wallet_recharges::Column::PaidTime.between(start, end))
+                            .add(
+                                wallet_recharges::Column::TransactionId.is_in(transactions.clone()),
+                            )
+                            .add(wallet_recharges::Column::RechargeId.is_in(ids.clone())),
+                    ),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        for recharge in recharges {
+            local.push(LocalPayment {
+                out_trade_no: recharge.recharge_id.simple().to_string(),
+                transaction_id: recharge.transaction_id.unwrap_or_default(),
+                amount: recharge.amount,
+                paid_time: recharge.paid_time.unwrap_or(recharge.create_time),
+                admin_id: None,
+            });
+        }
+        let purchases = PackagePurchases::find()
+            .filter(
+                Condition::all()
+                    .add(package_purchases::Column::Status.eq(RechargeStatus::Paid))
+                    .add(
+                        Condition::any()
+                            .add(package_purchases::Column::PaidTime.between(start, end))
+                            .add(
+                                package_purchases::Column::TransactionId
+                                    .is_in(transactions.clone()),
+                            )
+                            .add(package_purchases::Column::PurchaseId.is_in(ids.clone())),
+                    ),
+            )
+            .find_also_related(Packages)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        for (purchase, package) in purchases {
+            local.push(LocalPayment {
+                out_trade_no: purchase.purchase_id.simple().to_string(),
+                transaction_id: purchase.transaction_id.unwrap_or_default(),
+                amount: purchase.amount,
+                paid_time: purchase.paid_time.unwrap_or(purchase.create_time),
+                admin_id: package.map(|e| e.admin_id),
+            });
+        }
+        let cards = GiftCards::find()
+            .filter(
+                Condition::all()
+                    .add(gift_cards::Column::Status.ne(GiftCardStatus::Pending))
+                    .add(
+                        Condition::any()
+                            .add(gift_cards::Column::PaidTime.between(start, end))
+                            .add(gift_cards::Column::TransactionId.is_in(transactions))
+                            .add(gift_cards::Column::CardId.is_in(ids)),
+                    ),
+            )
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        for card in cards {
+            local.push(LocalPayment {
+                out_trade_no: card.card_id.simple().to_string(),
+                transaction_id: card.transaction_id.unwrap_or_default(),
+                amount: card.amount,
+                paid_time: card.paid_time.unwrap_or(card.create_time),
+                admin_id: None,
+            });
+        }
+        Ok(local)
+    }
+
+    //对账某一账单日, 重新对账时覆盖该日之前的结果
+    pub async fn run<T: From<String>>(
+        date: Date,
+        state: &AppState,
+    ) -> Result<reconcile_runs::Model, HandleErr<T>> {
+        let bill = state
+            .payment
+            .trade_bill(date)
+            .await
+            .map_err(|err| {
+                warn!("下载{}交易账单失败: {}", date, err);
+                HandleErr::BadRequest(-1, format!("下载交易账单失败: {}", err).into())
+            })?
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "未启用微信支付".to_string().into(),
+            ))?;
+        let local = Self::local(&bill, date, state).await?;
+        let issues = diff(&bill, &local, date);
+        let success: Vec<_> = bill.iter().filter(|e| e.trade_state == "SUCCESS").collect();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        ReconcileRuns::delete_by_id(date)
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let run = reconcile_runs::ActiveModel {
+            bill_date: Set(date),
+            trade_count: Set(success.len() as i32),
+            trade_amount: Set(success.iter().map(|e| e.amount).sum()),
+            issue_count: Set(issues.len() as i32),
+            error: Set(None),
+            run_time: NotSet,
+        }
+        .insert(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if !issues.is_empty() {
+            ReconcileIssues::insert_many(issues.iter().map(|e| reconcile_issues::ActiveModel {
+                issue_id: NotSet,
+                bill_date: Set(date),
+                kind: Set(e.kind.clone()),
+                admin_id: Set(e.admin_id),
+                out_trade_no: Set(e.out_trade_no.clone()),
+                transaction_id: Set(e.transaction_id.clone()),
+                local_amount: Set(e.local_amount),
+                remote_amount: Set(e.remote_amount),
+                resolved: NotSet,
+                resolved_by: NotSet,
+                remark: NotSet,
+                create_time: NotSet,
+            }))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        for issue in &issues {
+            warn!(
+                "对账异常({}): {:?} 商户订单号{} 微信订单号{} 本地{:?} 账单{:?}",
+                date,
+                issue.kind,
+                issue.out_trade_no,
+                issue.transaction_id.as_deref().unwrap_or_default(),
+                issue.local_amount,
+                issue.remote_amount
+            );
+        }
+        info!(
+            "{}对账完成: {}笔交易{:.2}元, {}条差异",
+            date, run.trade_count, run.trade_amount, run.issue_count
+        );
+        Ok(run)
+    }
+
+    //每日对账前一天的账单, 账单可下载后执行一次, 失败时记录原因并在下次继续重试
+    pub async fn daily<T: From<String>>(
+        state: &AppState,
+    ) -> Result<Option<reconcile_runs::Model>, HandleErr<T>> {
+        if matches!(state.payment, super::Payment::Offline) {
+            return Ok(None);
+        }
+        let now = chrono::Utc::now().naive_utc() + chrono::Duration::hours(BILL_OFFSET_HOURS);
+        if chrono::Timelike::hour(&now) < BILL_READY_HOUR {
+            return Ok(None);
+        }
+        let date = now.date() - chrono::Duration::days(1);
+        let done = ReconcileRuns::find_by_id(date)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if done.is_some_and(|e| e.error.is_none()) {
+            return Ok(None);
+        }
+        match Self::run::<String>(date, state).await {
+            Ok(run) => Ok(Some(run)),
+            Err(err) => {
+                let (reason, err) = match err {
+                    HandleErr::BadRequest(code, msg) => {
+                        (msg.clone(), HandleErr::BadRequest(code, msg.into()))
+                    }
+                    HandleErr::ServerInnerErr(id) => (
+                        format!("服务器内部错误, 错误代码{}", id),
+                        HandleErr::ServerInnerErr(id),
+                    ),
+                    HandleErr::UnAuthorized => ("未授权".to_string(), HandleErr::UnAuthorized),
+                };
+                Self::failed(date, reason, state).await?;
+                Err(err)
+            }
+        }
+    }
+
+    //记录对账失败的账单日, 覆盖之前的结果
+    async fn failed<T>(date: Date, reason: String, state: &AppState) -> Result<(), HandleErr<T>> {
+        let run = reconcile_runs::ActiveModel {
+            bill_date: Set(date),
+            trade_count: Set(0),
+            trade_amount: Set(Decimal::ZERO),
+            issue_count: Set(0),
+            error: Set(Some(reason.chars().take(500).collect())),
+            run_time: Set(chrono::Utc::now().naive_utc()),
+        };
+        ReconcileRuns::insert(run)
+            .on_conflict(
+                OnConflict::column(reconcile_runs::Column::BillDate)
+                    .update_columns([
+                        reconcile_runs::Column::TradeCount,
+                        reconcile_runs::Column::TradeAmount,
+                        reconcile_runs::Column::IssueCount,
+                        reconcile_runs::Column::Error,
+                        reconcile_runs::Column::RunTime,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //最近的对账结果
+    pub async fn runs<T>(
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<reconcile_runs::Model>, u64), HandleErr<T>> {
+        let paginator = ReconcileRuns::find()
+            .order_by_desc(reconcile_runs::Column::BillDate)
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let runs = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((runs, total))
+    }
+
+    //对账差异, admin_id为空时(超级管理员)返回全部
+    pub async fn issues<T>(
+        admin_id: Option<Uuid>,
+        query: IssueQuery,
+        state: &AppState,
+    ) -> Result<(Vec<reconcile_issues::Model>, u64), HandleErr<T>> {
+        let mut condition = Condition::all();
+        if let Some(admin_id) = admin_id {
+            condition = condition.add(reconcile_issues::Column::AdminId.eq(admin_id));
+        }
+        if let Some(bill_date) = query.bill_date {
+            condition = condition.add(reconcile_issues::Column::BillDate.eq(bill_date));
+        }
+        if let Some(resolved) = query.resolved {
+            condition = condition.add(reconcile_issues::Column::Resolved.eq(resolved));
+        }
+        let paginator = ReconcileIssues::find()
+            .filter(condition)
+            .order_by_desc(reconcile_issues::Column::BillDate)
+            .order_by_asc(reconcile_issues::Column::Kind)
+            .paginate(&state.db, query.page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let issues = paginator
+            .fetch_page(query.page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((issues, total))
+    }
+
+    //标记差异已处理, 管理员只能处理自己场馆的差异
+    pub async fn resolve<T: From<String>>(
+        schema: IssueResolve,
+        admin_id: Uuid,
+        is_super: bool,
+        state: &AppState,
+    ) -> Result<reconcile_issues::Model, HandleErr<T>> {
+        let remark = schema.remark.trim();
+        if remark.is_empty() || remark.chars().count() > 200 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "处理说明不能为空且不超过200字".to_string().into(),
+            ));
+        }
+        let issue = ReconcileIssues::find_by_id(schema.issue_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| is_super || e.admin_id == Some(admin_id))
+            .ok_or(HandleErr::BadRequest(
+                -1,
+                "对账差异不存在".to_string().into(),
+            ))?;
+        reconcile_issues::ActiveModel {
+            issue_id: Set(issue.issue_id),
+            resolved: Set(true),
+            resolved_by: Set(Some(admin_id)),
+            remark: Set(Some(remark.to_string())),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+}
+
+#[test]
+fn test_diff() {
+    let date = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let (start, _) = window(date);
+    let row = |transaction_id: &str, out_trade_no: &str, amount| BillRow {
+        transaction_id: transaction_id.to_string(),
+        out_trade_no: out_trade_no.to_string(),
+        trade_state: "SUCCESS".to_string(),
+        amount,
+        attach: String::new(),
+    };
+    let local = |transaction_id: &str, out_trade_no: &str, amount, hours| LocalPayment {
+        out_trade_no: out_trade_no.to_string(),
+        transaction_id: transaction_id.to_string(),
+        amount,
+        paid_time: start + chrono::Duration::hours(hours),
+        admin_id: None,
+    };
+    let bill = vec![
+        row("t1", "o1", Decimal::from(80)),
+        row("t2", "o2", Decimal::from(50)),
+        row("t3", "o3", Decimal::from(30)),
+        row("t4", "o1", Decimal::from(80)),
+    ];
+    let records = vec![
+        local("t1", "o1", Decimal::from(80), 1),
+        local("t2", "o2", Decimal::from(60), 2),
+        local("t5", "o5", Decimal::from(10), 3),
+        //前一天支付, 不在当日账单中
+        local("t6", "o6", Decimal::from(10), -1),
+    ];
+    let kinds: Vec<_> = diff(&bill, &records, date)
+        .into_iter()
+        .map(|e| (e.kind, e.out_trade_no))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (ReconcileIssueKind::AmountMismatch, "o2".to_string()),
+            (ReconcileIssueKind::MissingLocal, "o3".to_string()),
+            (ReconcileIssueKind::Duplicate, "o1".to_string()),
+            (ReconcileIssueKind::MissingRemote, "o5".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,474 @@
+use crate::{cfg::WechatPayCfg, module::money};
+use axum::http::HeaderMap;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::NaiveDate;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+use ring::rand::SystemRandom;
+use ring::signature::{
+    RsaKeyPair, UnparsedPublicKey, RSA_PKCS1_2048_8192_SHA256, RSA_PKCS1_SHA256,
+};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+use uuid::Uuid;
+
+const API_BASE: &str = "https://api.mch.weixin.qq.com";
+//回调通知时间戳与当前时间允许的偏差, 秒
+const NOTIFY_TOLERANCE_SECS: i64 = 300;
+
+//小程序调用 wx.requestPayment 所需的参数
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPayment {
+    pub app_id: String,
+    pub time_stamp: String,
+    pub nonce_str: String,
+    pub package: String,
+    pub sign_type: String,
+    pub pay_sign: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrepayResp {
+    prepay_id: String,
+}
+
+//回调通知, 业务数据在resource中加密
+#[derive(Debug, Deserialize)]
+struct Notify {
+    event_type: String,
+    resource: NotifyResource,
+}
+
+#[derive(Debug, Deserialize)]
+struct NotifyResource {
+    algorithm: String,
+    ciphertext: String,
+    #[serde(default)]
+    associated_data: String,
+    nonce: String,
+}
+
+//交易账单的下载地址与账单文件的摘要
+#[derive(Debug, Deserialize)]
+struct BillResp {
+    hash_type: String,
+    hash_value: String,
+    download_url: String,
+}
+
+//交易账单中的一笔交易, 金额单位为元
+#[derive(Debug, Clone, PartialEq)]
+pub struct BillRow {
+    pub transaction_id: String,
+    pub out_trade_no: String,
+    //SUCCESS/REFUND/REVOKED
+    pub trade_state: String,
+    //应结订单金额
+    pub amount: Decimal,
+    pub attach: String,
+}
+
+//支付通知解密后的交易信息
+#[derive(Debug, Deserialize)]
+pub struct Transaction {
+    pub out_trade_no: String,
+    pub transaction_id: String,
+    pub trade_state: String,
+    //下单时的业务类型, 为空时是订单
+    #[serde(default)]
+    pub attach: Option<String>,
+    pub amount: TransactionAmount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionAmount {
+    //订单总金额, 分
+    pub total: i64,
+}
+
+//退款申请的结果, 退款通知解密后的数据结构相同
+#[derive(Debug, Deserialize)]
+pub struct WechatRefund {
+    pub refund_id: String,
+    pub out_refund_no: String,
+    //SUCCESS/PROCESSING/CLOSED/ABNORMAL, 退款通知中字段名为refund_status
+    #[serde(alias = "refund_status")]
+    pub status: String,
+    //退款成功时间, RFC3339
+    pub success_time: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WechatPay {
+    client: reqwest::Client,
+    cfg: WechatPayCfg,
+    key: Arc<RsaKeyPair>,
+    //微信支付公钥, PKCS#1 DER
+    public_key: Arc<Vec<u8>>,
+}
+
+impl WechatPay {
+    pub fn new(cfg: &WechatPayCfg) -> crate::App::Result<Self> {
+        let der = pem::parse(cfg.private_key.trim())?;
+        let key = RsaKeyPair::from_pkcs8(der.contents())
+            .map_err(|err| anyhow::anyhow!("微信支付商户私钥无效: {}", err))?;
+        let der = pem::parse(cfg.public_key.trim())?;
+        let public_key = spki::SubjectPublicKeyInfoRef::try_from(der.contents())
+            .map_err(|err| anyhow::anyhow!("微信支付公钥无效: {}", err))?
+            .subject_public_key
+            .raw_bytes()
+            .to_vec();
+        if cfg.api_v3_key.len() != 32 {
+            return Err(anyhow::anyhow!("微信支付APIv3密钥应为32字节"));
+        }
+        Ok(Self {
+            client: reqwest::Client::new(),
+            cfg: cfg.clone(),
+            key: Arc::new(key),
+            public_key: Arc::new(public_key),
+        })
+    }
+
+    //SHA256 with RSA签名, 结果为base64
+    fn sign(&self, message: &str) -> crate::App::Result<String> {
+        let mut signature = vec![0; self.key.public().modulus_len()];
+        self.key
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|_| anyhow::anyhow!("微信支付签名失败"))?;
+        Ok(STANDARD.encode(signature))
+    }
+
+    //APIv3请求的Authorization头
+    fn authorization(&self, method: &str, path: &str, body: &str) -> crate::App::Result<String> {
+        let timestamp = chrono::Utc::now().timestamp();
+        let nonce = Uuid::new_v4().simple().to_string();
+        let signature = self.sign(&format!(
+            "{}\n{}\n{}\n{}\n{}\n",
+            method, path, timestamp, nonce, body
+        ))?;
+        Ok(format!(
+            r#"WECHATPAY2-SHA256-RSA2048 mchid="{}",nonce_str="{}",signature="{}",timestamp="{}",serial_no="{}""#,
+            self.cfg.mchid, nonce, signature, timestamp, self.cfg.serial_no
+        ))
+    }
+
+    //发送APIv3的POST请求
+    async fn request(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> crate::App::Result<reqwest::Response> {
+        let body = body.to_string();
+        Ok(self
+            .client
+            .post(format!("{}{}", API_BASE, path))
+            .header("Authorization", self.authorization("POST", path, &body)?)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await?)
+    }
+
+    //发送APIv3的GET请求, path包含查询参数
+    async fn get(&self, path: &str) -> crate::App::Result<reqwest::Response> {
+        Ok(self
+            .client
+            .get(format!("{}{}", API_BASE, path))
+            .header("Authorization", self.authorization("GET", path, "")?)
+            .header("Accept", "application/json")
+            .send()
+            .await?)
+    }
+
+    //POST请求并解析返回, 非2xx时返回微信的错误信息
+    async fn post<R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> crate::App::Result<R> {
+        let resp = self.request(path, body).await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "微信支付请求{}失败({}): {}",
+                path,
+                status,
+                text
+            ));
+        }
+        Ok(resp.json::<R>().await?)
+    }
+
+    //JSAPI下单, 返回prepay_id
+    //同一out_trade_no重复下单时微信返回相同的prepay_id
+    pub async fn prepay(
+        &self,
+        out_trade_no: Uuid,
+        attach: &str,
+        description: &str,
+        total: i64,
+        openid: &str,
+    ) -> crate::App::Result<String> {
+        let body = json!({
+            "appid":self.cfg.appid,
+            "mchid":self.cfg.mchid,
+            "description":description,
+            "out_trade_no":out_trade_no.simple().to_string(),
+            "attach":attach,
+            "notify_url":self.cfg.notify_url,
+            "amount":{"total":total, "currency":"CNY"},
+            "payer":{"openid":openid}
+        });
+        Ok(self
+            .post::<PrepayResp>("/v3/pay/transactions/jsapi", body)
+            .await?
+            .prepay_id)
+    }
+
+    //关闭未支付的订单, 关闭后用户无法再支付
+    //未调用过下单接口时微信返回404, 同样视为关闭成功; 已支付时返回错误
+    pub async fn close(&self, out_trade_no: Uuid) -> crate::App::Result<()> {
+        let path = format!(
+            "/v3/pay/transactions/out-trade-no/{}/close",
+            out_trade_no.simple()
+        );
+        let resp = self.request(&path, json!({"mchid":self.cfg.mchid})).await?;
+        let status = resp.status();
+        if status.is_success() || status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        let text = resp.text().await.unwrap_or_default();
+        Err(anyhow::anyhow!("微信支付关单失败({}): {}", status, text))
+    }
+
+    //申请退款, out_refund_no使用退款记录号, 重复申请时微信返回同一笔退款
+    //total为原支付金额, refund为本次退款金额, 单位为分
+    pub async fn refund(
+        &self,
+        out_refund_no: Uuid,
+        transaction_id: &str,
+        refund: i64,
+        total: i64,
+        reason: &str,
+    ) -> crate::App::Result<WechatRefund> {
+        let body = json!({
+            "transaction_id":transaction_id,
+            "out_refund_no":out_refund_no.simple().to_string(),
+            "reason":reason,
+            "notify_url":self.cfg.refund_notify_url,
+            "amount":{"refund":refund, "total":total, "currency":"CNY"}
+        });
+        self.post("/v3/refund/domestic/refunds", body).await
+    }
+
+    //下载某日的交易账单, 次日10点后可下载, 当日没有交易时返回空
+    pub async fn trade_bill(&self, date: NaiveDate) -> crate::App::Result<Vec<BillRow>> {
+        let path = format!(
+            "/v3/bill/tradebill?bill_date={}&bill_type=ALL",
+            date.format("%Y-%m-%d")
+        );
+        let resp = self.get(&path).await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            if text.contains("NO_STATEMENT_EXIST") {
+                return Ok(vec![]);
+            }
+            return Err(anyhow::anyhow!("申请交易账单失败({}): {}", status, text));
+        }
+        let bill = resp.json::<BillResp>().await?;
+        let path = bill
+            .download_url
+            .strip_prefix(API_BASE)
+            .ok_or_else(|| anyhow::anyhow!("账单下载地址无效: {}", bill.download_url))?;
+        let resp = self.get(path).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("下载交易账单失败({})", resp.status()));
+        }
+        let data = resp.bytes().await?;
+        let hash: String = Sha1::digest(&data)
+            .iter()
+            .map(|e| format!("{:02x}", e))
+            .collect();
+        if bill.hash_type != "SHA1" || !hash.eq_ignore_ascii_case(&bill.hash_value) {
+            return Err(anyhow::anyhow!("交易账单校验失败"));
+        }
+        parse_bill(&String::from_utf8_lossy(&data))
+    }
+
+    //由prepay_id生成小程序调起支付的参数
+    pub fn request_payment(&self, prepay_id: &str) -> crate::App::Result<RequestPayment> {
+        let time_stamp = chrono::Utc::now().timestamp().to_string();
+        let nonce_str = Uuid::new_v4().simple().to_string();
+        let package = format!("prepay_id={}", prepay_id);
+        let pay_sign = self.sign(&format!(
+            "{}\n{}\n{}\n{}\n",
+            self.cfg.appid, time_stamp, nonce_str, package
+        ))?;
+        Ok(RequestPayment {
+            app_id: self.cfg.appid.clone(),
+            time_stamp,
+            nonce_str,
+            package,
+            sign_type: "RSA".to_string(),
+            pay_sign,
+        })
+    }
+
+    //验证回调通知的签名, 签名串为 时间戳\n随机串\n报文主体\n
+    fn verify(&self, headers: &HeaderMap, body: &str) -> crate::App::Result<()> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|e| e.to_str().ok())
+                .ok_or_else(|| anyhow::anyhow!("缺少请求头{}", name))
+        };
+        let serial = header("wechatpay-serial")?;
+        if serial != self.cfg.public_key_id {
+            return Err(anyhow::anyhow!("未知的微信支付公钥: {}", serial));
+        }
+        let timestamp = header("wechatpay-timestamp")?.parse::<i64>()?;
+        if (chrono::Utc::now().timestamp() - timestamp).abs() > NOTIFY_TOLERANCE_SECS {
+            return Err(anyhow::anyhow!("回调通知已过期"));
+        }
+        let nonce = header("wechatpay-nonce")?;
+        let signature = STANDARD.decode(header("wechatpay-signature")?)?;
+        UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, self.public_key.as_slice())
+            .verify(
+                format!("{}\n{}\n{}\n", timestamp, nonce, body).as_bytes(),
+                &signature,
+            )
+            .map_err(|_| anyhow::anyhow!("回调通知签名验证失败"))
+    }
+
+    //验签并解密回调通知, 返回事件类型与业务数据
+    pub fn notify<R: DeserializeOwned>(
+        &self,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> crate::App::Result<(String, R)> {
+        self.verify(headers, body)?;
+        let notify = serde_json::from_str::<Notify>(body)?;
+        let data = decrypt(self.cfg.api_v3_key.as_bytes(), &notify.resource)?;
+        Ok((notify.event_type, serde_json::from_slice(&data)?))
+    }
+}
+
+//AEAD_AES_256_GCM解密回调通知的resource
+fn decrypt(key: &[u8], resource: &NotifyResource) -> crate::App::Result<Vec<u8>> {
+    if resource.algorithm != "AEAD_AES_256_GCM" {
+        return Err(anyhow::anyhow!("不支持的加密算法: {}", resource.algorithm));
+    }
+    let key = UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| anyhow::anyhow!("APIv3密钥无效"))?;
+    let nonce = Nonce::try_assume_unique_for_key(resource.nonce.as_bytes())
+        .map_err(|_| anyhow::anyhow!("回调通知nonce无效"))?;
+    let mut data = STANDARD.decode(&resource.ciphertext)?;
+    let plain = key
+        .open_in_place(
+            nonce,
+            Aad::from(resource.associated_data.as_bytes()),
+            &mut data,
+        )
+        .map_err(|_| anyhow::anyhow!("回调通知解密失败"))?;
+    Ok(plain.to_vec())
+}
+
+//解析交易账单, 首行为表头, 每个字段以`开头, 数据之后是汇总
+//字段依次为: 交易时间,公众账号ID,商户号,特约商户号,设备号,微信订单号,商户订单号,用户标识,
+//交易类型,交易状态,付款银行,货币种类,应结订单金额,代金券金额,...,商品名称,商户数据包,
+//手续费,费率,订单金额,申请退款金额,费率备注; 商品名称可能含逗号, 商户数据包从末尾定位
+pub fn parse_bill(text: &str) -> crate::App::Result<Vec<BillRow>> {
+    let mut rows = vec![];
+    for line in text.lines().skip(1) {
+        if !line.starts_with('`') {
+            break;
+        }
+        let fields: Vec<&str> = line.split(',').map(|e| e.trim_start_matches('`')).collect();
+        if fields.len() < 27 {
+            return Err(anyhow::anyhow!("交易账单格式错误: {}", line));
+        }
+        rows.push(BillRow {
+            transaction_id: fields[5].to_string(),
+            out_trade_no: fields[6].to_string(),
+            trade_state: fields[9].to_string(),
+            amount: fields[12].parse()?,
+            attach: fields[fields.len() - 6].to_string(),
+        });
+    }
+    Ok(rows)
+}
+
+//金额转为分
+pub fn to_fen(amount: Decimal) -> i64 {
+    i64::try_from(money::round(amount) * Decimal::ONE_HUNDRED).unwrap_or_default()
+}
+
+//分转为金额
+pub fn from_fen(fen: i64) -> Decimal {
+    Decimal::new(fen, 2)
+}
+
+#[test]
+fn test_to_fen() {
+    assert_eq!(to_fen(Decimal::new(3, 1)), 30);
+    assert_eq!(to_fen(Decimal::new(99999, 3)), 10000);
+    assert_eq!(to_fen(Decimal::ZERO), 0);
+    assert_eq!(from_fen(9999), Decimal::new(9999, 2));
+}
+
+#[test]
+fn test_decrypt() {
+    let key = b"0123456789abcdef0123456789abcdef";
+    let sealing = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).unwrap());
+    let mut data = br#"{"out_trade_no":"1"}"#.to_vec();
+    sealing
+        .seal_in_place_append_tag(
+            Nonce::try_assume_unique_for_key(b"0123456789ab").unwrap(),
+            Aad::from(b"transaction"),
+            &mut data,
+        )
+        .unwrap();
+    let mut resource = NotifyResource {
+        algorithm: "AEAD_AES_256_GCM".to_string(),
+        ciphertext: STANDARD.encode(data),
+        associated_data: "transaction".to_string(),
+        nonce: "0123456789ab".to_string(),
+    };
+    assert_eq!(
+        decrypt(key, &resource).unwrap(),
+        br#"{"out_trade_no":"1"}"#.to_vec()
+    );
+    resource.associated_data = "refund".to_string();
+    assert!(decrypt(key, &resource).is_err());
+}
+
+#[test]
+fn test_parse_bill() {
+    let text = "交易时间,公众账号ID,商户号,特约商户号,设备号,微信订单号,商户订单号,用户标识,交易类型,交易状态,付款银行,货币种类,应结订单金额,代金券金额,微信退款单号,商户退款单号,退款金额,充值券退款金额,退款类型,退款状态,商品名称,商户数据包,手续费,费率,订单金额,申请退款金额,费率备注
+`2024-01-01 10:00:00,`wx1,`1900000001,`0,`,`4200000001,`0f8fad5bd9cb469fa16570867728950e,`oUser,`JSAPI,`SUCCESS,`OTHERS,`CNY,`80.00,`0.00,`0,`0,`0.00,`0.00,`,`,`1号场 01-02 18:00,`order,`0.48000,`0.60%,`80.00,`0.00,`
+`2024-01-01 11:00:00,`wx1,`1900000001,`0,`,`4200000002,`7c9e6679742540de944be07fc1f90ae7,`oUser,`JSAPI,`REFUND,`OTHERS,`CNY,`0.00,`0.00,`50000001,`1,`100.00,`0.00,`ORIGINAL,`SUCCESS,`钱包充值,`recharge,`-0.60000,`0.60%,`0.00,`100.00,`
+总交易单数,应结订单总金额,退款总金额,充值券退款总金额,手续费总金额,订单总金额,申请退款总金额
+`2,`80.00,`100.00,`0.00,`-0.12000,`80.00,`100.00
+";
+    let rows = parse_bill(text).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].transaction_id, "4200000001");
+    assert_eq!(rows[0].trade_state, "SUCCESS");
+    assert_eq!(rows[0].amount, Decimal::from(80));
+    assert_eq!(rows[0].attach, "order");
+    assert_eq!(rows[1].trade_state, "REFUND");
+    assert!(parse_bill("").unwrap().is_empty());
+}
@@ -0,0 +1,309 @@
+use super::db::{
+    points_config, points_transactions,
+    prelude::{PointsConfig, PointsTransactions, UserPoints},
+    sea_orm_active_enums::PointsTxnKind,
+    user_points,
+};
+use super::money;
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//积分设置, 未设置时使用默认值
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PointsRule {
+    //每消费1元获得的积分
+    pub earn_rate: Decimal,
+    //每积分抵扣的金额, 元
+    pub redeem_rate: Decimal,
+    //单笔订单最多抵扣应付金额的百分比
+    pub max_redeem_percent: i16,
+}
+
+impl Default for PointsRule {
+    fn default() -> Self {
+        Self {
+            earn_rate: Decimal::ONE,
+            redeem_rate: money::CENT,
+            max_redeem_percent: 50,
+        }
+    }
+}
+
+impl From<points_config::Model> for PointsRule {
+    fn from(e: points_config::Model) -> Self {
+        Self {
+            earn_rate: e.earn_rate,
+            redeem_rate: e.redeem_rate,
+            max_redeem_percent: e.max_redeem_percent,
+        }
+    }
+}
+
+pub struct PointsOp;
+impl PointsOp {
+    pub async fn rule<T, C: ConnectionTrait>(db: &C) -> Result<PointsRule, HandleErr<T>> {
+        Ok(PointsConfig::find_by_id(1i16)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .map(PointsRule::from)
+            .unwrap_or_default())
+    }
+
+    pub async fn set_rule<T: From<&'static str>>(
+        rule: PointsRule,
+        state: &AppState,
+    ) -> Result<PointsRule, HandleErr<T>> {
+        if !(Decimal::ZERO..=Decimal::ONE_HUNDRED).contains(&rule.earn_rate)
+            || !(Decimal::ZERO..=Decimal::ONE).contains(&rule.redeem_rate)
+            || !(0..=100).contains(&rule.max_redeem_percent)
+        {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "获得比例应在0~100, 抵扣金额应在0~1元, 抵扣上限应在0~100%之间".into(),
+            ));
+        }
+        PointsConfig::insert(points_config::ActiveModel {
+            config_id: Set(1),
+            earn_rate: Set(rule.earn_rate),
+            redeem_rate: Set(rule.redeem_rate),
+            max_redeem_percent: Set(rule.max_redeem_percent),
+            update_time: Set(chrono::Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::column(points_config::Column::ConfigId)
+                .update_columns([
+                    points_config::Column::EarnRate,
+                    points_config::Column::RedeemRate,
+                    points_config::Column::MaxRedeemPercent,
+                    points_config::Column::UpdateTime,
+                ])
+                .to_owned(),
+        )
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(rule)
+    }
+
+    pub async fn balance<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<i32, HandleErr<T>> {
+        Ok(UserPoints::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .map_or(0, |e| e.points))
+    }
+
+    //变动积分并写入流水, 扣减时积分不足返回错误
+    pub async fn change<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        points: i32,
+        kind: PointsTxnKind,
+        order_id: Option<Uuid>,
+        db: &C,
+    ) -> Result<points_transactions::Model, HandleErr<T>> {
+        UserPoints::insert(user_points::ActiveModel {
+            user_id: Set(user_id),
+            points: NotSet,
+            update_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::column(user_points::Column::UserId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let account = UserPoints::update_many()
+            .col_expr(
+                user_points::Column::Points,
+                Expr::col(user_points::Column::Points).add(points),
+            )
+            .col_expr(
+                user_points::Column::UpdateTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(
+                user_points::Column::UserId
+                    .eq(user_id)
+                    .and(user_points::Column::Points.gte(-points)),
+            )
+            .exec_with_returning(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .pop()
+            .ok_or(HandleErr::BadRequest(-1, "积分不足".to_string().into()))?;
+        points_transactions::ActiveModel {
+            txn_id: NotSet,
+            user_id: Set(user_id),
+            kind: Set(kind),
+            points: Set(points),
+            balance: Set(account.points),
+            order_id: Set(order_id),
+            create_time: NotSet,
+        }
+        .insert(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //订单完成后按实付金额获得积分, 已获得的不重复发放
+    pub async fn earn<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        order_id: Uuid,
+        cost: Decimal,
+        db: &C,
+    ) -> Result<i32, HandleErr<T>> {
+        let earned_before = PointsTransactions::find()
+            .filter(
+                points_transactions::Column::OrderId
+                    .eq(order_id)
+                    .and(points_transactions::Column::Kind.eq(PointsTxnKind::Earn)),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if earned_before > 0 {
+            return Ok(0);
+        }
+        let rule = Self::rule(db).await?;
+        let points = earned(cost, rule.earn_rate);
+        if points > 0 {
+            Self::change(user_id, points, PointsTxnKind::Earn, Some(order_id), db).await?;
+            info!("订单({})完成, 获得{}积分", order_id, points);
+        }
+        Ok(points)
+    }
+
+    //取消或退款的订单退回抵扣的积分, 已退回的不重复退回
+    pub async fn restore<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        order_id: Uuid,
+        points: i32,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let restored = PointsTransactions::find()
+            .filter(
+                points_transactions::Column::OrderId
+                    .eq(order_id)
+                    .and(points_transactions::Column::Kind.eq(PointsTxnKind::Restore)),
+            )
+            .count(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if restored == 0 {
+            Self::change(user_id, points, PointsTxnKind::Restore, Some(order_id), db).await?;
+        }
+        Ok(())
+    }
+
+    //积分流水, 按时间倒序分页, 返回流水与总数
+    pub async fn transactions<T>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        state: &AppState,
+    ) -> Result<(Vec<points_transactions::Model>, u64), HandleErr<T>> {
+        let paginator = PointsTransactions::find()
+            .filter(points_transactions::Column::UserId.eq(user_id))
+            .order_by_desc(points_transactions::Column::CreateTime)
+            .paginate(&state.db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let records = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((records, total))
+    }
+}
+
+//订单获得的积分, 不足1分的部分舍去
+pub fn earned(cost: Decimal, earn_rate: Decimal) -> i32 {
+    i32::try_from((cost.max(Decimal::ZERO) * earn_rate).floor()).unwrap_or_default()
+}
+
+//下单时抵扣的积分与金额, 不超过余额与抵扣上限, 抵扣后至少支付0.01元
+pub fn redeem(cash: Decimal, requested: i32, balance: i32, rule: &PointsRule) -> (i32, Decimal) {
+    if requested <= 0 || rule.redeem_rate <= Decimal::ZERO {
+        return (0, Decimal::ZERO);
+    }
+    let cap = (cash * Decimal::from(rule.max_redeem_percent) / Decimal::ONE_HUNDRED)
+        .min(cash - money::CENT);
+    if cap <= Decimal::ZERO {
+        return (0, Decimal::ZERO);
+    }
+    let max = i32::try_from((cap / rule.redeem_rate).floor()).unwrap_or(i32::MAX);
+    let used = requested.min(balance).min(max).max(0);
+    (used, money::round(Decimal::from(used) * rule.redeem_rate))
+}
+
+#[test]
+fn test_points() {
+    let d = |v: i64| Decimal::from(v);
+    assert_eq!(earned(Decimal::new(9999, 2), Decimal::ONE), 99);
+    assert_eq!(earned(d(100), Decimal::new(5, 1)), 50);
+    let rule = PointsRule::default();
+    //最多抵扣100元的50%, 即5000分
+    assert_eq!(redeem(d(100), 8000, 10000, &rule), (5000, d(50)));
+    assert_eq!(redeem(d(100), 8000, 300, &rule), (300, d(3)));
+    assert_eq!(redeem(d(100), 0, 300, &rule), (0, d(0)));
+    //全额抵扣时保留0.01元
+    let all = PointsRule {
+        max_redeem_percent: 100,
+        ..rule
+    };
+    assert_eq!(redeem(d(1), 1000, 1000, &all), (99, Decimal::new(99, 2)));
+}
@@ -0,0 +1,186 @@
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        court::open_hours::OpenHoursOp,
+        db::{
+            self, court_demand_tiers, orders,
+            prelude::{CourtDemandTiers, Orders},
+        },
+        order::state,
+    },
+};
+use sea_orm::prelude::{Date, DateTime, Decimal};
+use sea_orm::{
+    ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DemandTier {
+    pub min_utilization: i16,
+    pub adjust_percent: Decimal,
+}
+
+//整体替换球场的调价档位, 为空时关闭按预订率调价
+#[derive(Debug, Deserialize, Clone)]
+pub struct DemandTiersSet {
+    pub court_id: Uuid,
+    pub tiers: Vec<DemandTier>,
+}
+
+pub struct DemandOp;
+impl DemandOp {
+    pub async fn tiers<T>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<court_demand_tiers::Model>, HandleErr<T>> {
+        CourtDemandTiers::find()
+            .filter(court_demand_tiers::Column::CourtId.eq(court_id))
+            .order_by_asc(court_demand_tiers::Column::MinUtilization)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    pub async fn set<T: From<&'static str>>(
+        schema: DemandTiersSet,
+        state: &AppState,
+    ) -> Result<Vec<court_demand_tiers::Model>, HandleErr<T>> {
+        let mut levels: Vec<_> = schema.tiers.iter().map(|e| e.min_utilization).collect();
+        levels.sort();
+        levels.dedup();
+        if levels.len() != schema.tiers.len() {
+            return Err(HandleErr::BadRequest(-1, "预订率档位重复".into()));
+        }
+        if schema.tiers.iter().any(|e| {
+            !(0..=100).contains(&e.min_utilization)
+                || !(Decimal::from(-90)..=Decimal::from(200)).contains(&e.adjust_percent)
+        }) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "预订率应在0~100之间, 调整比例应在-90~200之间".into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        CourtDemandTiers::delete_many()
+            .filter(court_demand_tiers::Column::CourtId.eq(schema.court_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if !schema.tiers.is_empty() {
+            CourtDemandTiers::insert_many(schema.tiers.iter().map(|e| {
+                court_demand_tiers::ActiveModel {
+                    court_id: Set(schema.court_id),
+                    min_utilization: Set(e.min_utilization),
+                    adjust_percent: Set(e.adjust_percent),
+                }
+            }))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::tiers(schema.court_id, state).await
+    }
+
+    //球场某天营业时间内已预订时长的百分比, 闭馆时为0
+    pub async fn utilization<T>(
+        court: &db::courts::Model,
+        date: Date,
+        state: &AppState,
+    ) -> Result<f64, HandleErr<T>> {
+        let Some((open, close)) = OpenHoursOp::window(court, date, state).await? else {
+            return Ok(0.0);
+        };
+        let (open, close) = (date.and_time(open), date.and_time(close));
+        let booked: Vec<(DateTime, DateTime)> = Orders::find()
+            .select_only()
+            .column(orders::Column::AptStart)
+            .column(orders::Column::AptEnd)
+            .filter(
+                orders::Column::CourtId
+                    .eq(court.court_id)
+                    .and(orders::Column::AptStart.lt(close))
+                    .and(orders::Column::AptEnd.gt(open))
+                    .and(orders::Column::Status.is_not_in(state::RELEASED)),
+            )
+            .into_tuple()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(utilization(open, close, &booked))
+    }
+}
+
+//已预订时长占营业时长的百分比, 订单时段按营业时间截取
+pub fn utilization(open: DateTime, close: DateTime, booked: &[(DateTime, DateTime)]) -> f64 {
+    let total = (close - open).num_minutes();
+    if total <= 0 {
+        return 0.0;
+    }
+    let minutes: i64 = booked
+        .iter()
+        .map(|(start, end)| {
+            ((*end).min(close) - (*start).max(open))
+                .num_minutes()
+                .max(0)
+        })
+        .sum();
+    (minutes as f64 * 100.0 / total as f64).min(100.0)
+}
+
+//预订率对应的调整百分比, 取不高于预订率的最高一档
+pub fn adjust(tiers: &[court_demand_tiers::Model], utilization: f64) -> Decimal {
+    tiers
+        .iter()
+        .filter(|e| e.min_utilization as f64 <= utilization)
+        .max_by_key(|e| e.min_utilization)
+        .map_or(Decimal::ZERO, |e| e.adjust_percent)
+}
+
+#[test]
+fn test_demand() {
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let t = |h| day.and_hms_opt(h, 0, 0).unwrap();
+    //营业10小时, 预订8~10点(截取为9~10点)与18~21点
+    let u = utilization(t(9), t(19), &[(t(8), t(10)), (t(18), t(21))]);
+    assert_eq!(u, 20.0);
+    let tier = |min_utilization, adjust_percent| court_demand_tiers::Model {
+        court_id: Uuid::nil(),
+        min_utilization,
+        adjust_percent,
+    };
+    let d = |v: i64| Decimal::from(v);
+    let tiers = vec![tier(0, d(-10)), tier(30, d(0)), tier(80, d(20))];
+    assert_eq!(adjust(&tiers, 20.0), d(-10));
+    assert_eq!(adjust(&tiers, 50.0), d(0));
+    assert_eq!(adjust(&tiers, 80.0), d(20));
+    assert_eq!(adjust(&[], 90.0), d(0));
+}
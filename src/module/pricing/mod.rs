@@ -0,0 +1,373 @@
+use super::db::{
+    self, coupon_templates, court_price_overrides, court_price_rules,
+    prelude::{CourtPriceOverrides, CourtPriceRules},
+    sea_orm_active_enums::CouponKind,
+};
+use crate::{appstate::AppState, error::HandleErr, module::money};
+use chrono::Datelike;
+use demand::DemandOp;
+use sea_orm::prelude::{Date, DateTime, Decimal, Time};
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, EntityTrait, QueryFilter, TryIntoModel,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+pub mod demand;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceRuleSave {
+    pub rule_id: Option<Uuid>,
+    pub court_id: Uuid,
+    //1(周一)~7(周日), 为空时每天生效
+    pub weekday: Option<i16>,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub price_per_hour: Decimal,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceRuleDel {
+    pub rule_id: Uuid,
+}
+
+//同一球场同一日期只有一条, 重复设置时覆盖
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceOverrideSet {
+    pub court_id: Uuid,
+    pub override_date: Date,
+    pub price_per_hour: Decimal,
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceOverrideDel {
+    pub override_id: Uuid,
+}
+
+pub struct PricingOp;
+impl PricingOp {
+    //场地费
+    //特殊日期价格优先, 其次为价格时段, 最后为基础价格
+    //设置了预订率档位时, 按当天预订率再做上下浮动
+    pub async fn cost<T>(
+        court: &db::courts::Model,
+        start: DateTime,
+        end: DateTime,
+        state: &AppState,
+    ) -> Result<Decimal, HandleErr<T>> {
+        let cost = match Self::override_of(court.court_id, start.date(), state).await? {
+            Some(e) => money::round(money::hours(start, end) * e.price_per_hour),
+            None => {
+                let rules = Self::rules(court.court_id, state).await?;
+                calc(court.price_per_hour, &rules, start, end)
+            }
+        };
+        let tiers = DemandOp::tiers(court.court_id, state).await?;
+        if tiers.is_empty() {
+            return Ok(cost);
+        }
+        let utilization = DemandOp::utilization(court, start.date(), state).await?;
+        let percent = demand::adjust(&tiers, utilization);
+        Ok(money::round(
+            cost * (Decimal::ONE + percent / Decimal::ONE_HUNDRED),
+        ))
+    }
+
+    //订单总价(场地费与附加项目, 不含押金), 下单与报价共用
+    pub async fn order_cost<T>(
+        court: &db::courts::Model,
+        start: DateTime,
+        end: DateTime,
+        addons: &[(db::court_addons::Model, i32)],
+        state: &AppState,
+    ) -> Result<Decimal, HandleErr<T>> {
+        Ok(Self::cost(court, start, end, state).await?
+            + addons_cost(
+                addons
+                    .iter()
+                    .map(|(addon, quantity)| (addon.price, addon.per_hour, *quantity)),
+                start,
+                end,
+            ))
+    }
+
+    pub async fn override_of<T>(
+        court_id: Uuid,
+        date: Date,
+        state: &AppState,
+    ) -> Result<Option<court_price_overrides::Model>, HandleErr<T>> {
+        CourtPriceOverrides::find()
+            .filter(
+                court_price_overrides::Column::CourtId
+                    .eq(court_id)
+                    .and(court_price_overrides::Column::OverrideDate.eq(date)),
+            )
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    pub async fn rules<T>(
+        court_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<court_price_rules::Model>, HandleErr<T>> {
+        CourtPriceRules::find()
+            .filter(court_price_rules::Column::CourtId.eq(court_id))
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    pub async fn save<T: From<&'static str>>(
+        schema: PriceRuleSave,
+        state: &AppState,
+    ) -> Result<court_price_rules::Model, HandleErr<T>> {
+        if schema.weekday.is_some_and(|e| !(1..=7).contains(&e)) {
+            return Err(HandleErr::BadRequest(-1, "weekday应在1~7之间".into()));
+        }
+        if schema.start_time >= schema.end_time {
+            return Err(HandleErr::BadRequest(-1, "开始时间须早于结束时间".into()));
+        }
+        if schema.price_per_hour <= Decimal::ZERO {
+            return Err(HandleErr::BadRequest(-1, "价格须大于0".into()));
+        }
+        //同一作用范围内的时段不能重叠
+        let overlap = Self::rules(schema.court_id, state)
+            .await?
+            .into_iter()
+            .filter(|e| Some(e.rule_id) != schema.rule_id && e.weekday == schema.weekday)
+            .any(|e| schema.start_time < e.end_time && e.start_time < schema.end_time);
+        if overlap {
+            return Err(HandleErr::BadRequest(-1, "与已有价格时段重叠".into()));
+        }
+
+        court_price_rules::ActiveModel {
+            rule_id: schema.rule_id.map(Set).unwrap_or(NotSet),
+            court_id: Set(schema.court_id),
+            weekday: Set(schema.weekday),
+            start_time: Set(schema.start_time),
+            end_time: Set(schema.end_time),
+            price_per_hour: Set(money::round(schema.price_per_hour)),
+        }
+        .save(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+}
+
+//按价格时段分段计费, 不在任何时段内的部分按基础价格计算
+//同一时段星期规则优先于每日规则
+pub fn calc(
+    base: Decimal,
+    rules: &[court_price_rules::Model],
+    start: DateTime,
+    end: DateTime,
+) -> Decimal {
+    let weekday = start.weekday().number_from_monday() as i16;
+    let rules: Vec<_> = rules
+        .iter()
+        .filter(|e| e.weekday.is_none_or(|w| w == weekday))
+        .collect();
+
+    let mut points = vec![start, end];
+    for rule in &rules {
+        for t in [rule.start_time, rule.end_time] {
+            let p = start.date().and_time(t);
+            if p > start && p < end {
+                points.push(p);
+            }
+        }
+    }
+    points.sort();
+    points.dedup();
+
+    money::round(
+        points
+            .windows(2)
+            .map(|w| {
+                let (a, b) = (w[0], w[1]);
+                let price = rules
+                    .iter()
+                    .filter(|e| e.start_time <= a.time() && b.time() <= e.end_time)
+                    .max_by_key(|e| e.weekday.is_some())
+                    .map_or(base, |e| e.price_per_hour);
+                money::hours(a, b) * price
+            })
+            .sum(),
+    )
+}
+
+//附加项目费用, 按小时计费的项目乘以预约时长
+//addons: (单价, 是否按小时, 数量)
+pub fn addons_cost(
+    addons: impl IntoIterator<Item = (Decimal, bool, i32)>,
+    start: DateTime,
+    end: DateTime,
+) -> Decimal {
+    let hours = money::hours(start, end);
+    money::round(
+        addons
+            .into_iter()
+            .map(|(price, per_hour, quantity)| {
+                let price = if per_hour { price * hours } else { price };
+                price * Decimal::from(quantity)
+            })
+            .sum(),
+    )
+}
+
+//优惠券抵扣金额, 百分比券按上限封顶, 抵扣后至少保留0.01元应付
+pub fn discount(coupon: &coupon_templates::Model, cost: Decimal) -> Decimal {
+    let amount = match coupon.kind {
+        CouponKind::Fixed => coupon.value,
+        CouponKind::Percent => {
+            let amount = cost * coupon.value / Decimal::ONE_HUNDRED;
+            coupon.max_discount.map_or(amount, |e| amount.min(e))
+        }
+    };
+    money::round(amount.min(cost - money::CENT).max(Decimal::ZERO))
+}
+
+//会员折扣金额, percent为折扣百分比
+pub fn member(court_cost: Decimal, percent: Decimal) -> Decimal {
+    money::round(court_cost.max(Decimal::ZERO) * percent / Decimal::ONE_HUNDRED)
+}
+
+//扣除抵扣金额后的应付金额, 修改时段时沿用下单时的抵扣金额
+//次卡全额抵扣时可为0, 使用优惠券时至少支付0.01元
+pub fn net(cost: Decimal, discount: Decimal) -> Decimal {
+    if discount <= Decimal::ZERO {
+        return money::round(cost.max(Decimal::ZERO));
+    }
+    money::round((cost - discount).max(money::CENT))
+}
+
+//次卡抵扣的时数与场地费, 剩余时数不足订单时长时按比例部分抵扣
+pub fn package(court_cost: Decimal, hours: f64, available: f64) -> (f64, Decimal) {
+    let used = (available.min(hours).max(0.0) * 100.0).round() / 100.0;
+    if used >= hours {
+        return (hours, court_cost);
+    }
+    (
+        used,
+        money::round(court_cost * money::from_f64(used) / money::from_f64(hours)),
+    )
+}
+
+#[test]
+fn test_calc() {
+    let d = |v: i64| Decimal::from(v);
+    let t = |h, m| Time::from_hms_opt(h, m, 0).unwrap();
+    let rule = |weekday, start, end, price| court_price_rules::Model {
+        rule_id: Uuid::new_v4(),
+        court_id: Uuid::nil(),
+        weekday,
+        start_time: start,
+        end_time: end,
+        price_per_hour: price,
+    };
+    //2024-01-01 周一
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let rules = vec![
+        rule(None, t(18, 0), t(22, 0), d(100)),
+        rule(Some(1), t(20, 0), t(22, 0), d(150)),
+        rule(Some(2), t(8, 0), t(10, 0), d(10)),
+    ];
+    //17:00-18:00 基础价, 18:00-20:00 每日规则, 20:00-21:00 周一规则
+    let cost = calc(
+        d(50),
+        &rules,
+        day.and_time(t(17, 0)),
+        day.and_time(t(21, 0)),
+    );
+    assert_eq!(cost, d(50 + 200 + 150));
+    //无规则覆盖
+    let cost = calc(d(50), &rules, day.and_time(t(8, 0)), day.and_time(t(9, 30)));
+    assert_eq!(cost, d(75));
+    //1小时20分, 按分钟计费后保留两位小数
+    let cost = calc(d(50), &[], day.and_time(t(8, 0)), day.and_time(t(9, 20)));
+    assert_eq!(cost, Decimal::new(6667, 2));
+}
+
+#[test]
+fn test_addons_cost() {
+    let d = |v: i64| Decimal::from(v);
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let start = day.and_time(Time::from_hms_opt(18, 0, 0).unwrap());
+    let end = day.and_time(Time::from_hms_opt(19, 30, 0).unwrap());
+    //2个球拍按件, 灯光费按小时
+    let cost = addons_cost([(d(10), false, 2), (d(20), true, 1)], start, end);
+    assert_eq!(cost, d(20 + 30));
+}
+
+#[test]
+fn test_discount() {
+    let d = |v: i64| Decimal::from(v);
+    let now = chrono::Utc::now().naive_utc();
+    let coupon = |kind, value, max_discount| coupon_templates::Model {
+        template_id: Uuid::nil(),
+        admin_id: Uuid::nil(),
+        name: String::new(),
+        kind,
+        value,
+        min_amount: Decimal::ZERO,
+        max_discount,
+        valid_from: now,
+        valid_to: now,
+        enabled: true,
+        create_time: now,
+    };
+    assert_eq!(
+        discount(&coupon(CouponKind::Fixed, d(20), None), d(100)),
+        d(20)
+    );
+    //固定金额超过订单金额时保留0.01元
+    assert_eq!(
+        discount(&coupon(CouponKind::Fixed, d(200), None), d(100)),
+        Decimal::new(9999, 2)
+    );
+    assert_eq!(
+        discount(&coupon(CouponKind::Percent, d(15), None), d(99)),
+        Decimal::new(1485, 2)
+    );
+    assert_eq!(
+        discount(&coupon(CouponKind::Percent, d(50), Some(d(30))), d(100)),
+        d(30)
+    );
+    assert_eq!(net(d(100), d(30)), d(70));
+    assert_eq!(net(d(20), d(30)), money::CENT);
+    assert_eq!(net(d(0), d(0)), d(0));
+    assert_eq!(member(d(160), d(10)), d(16));
+    assert_eq!(member(d(99), Decimal::new(125, 1)), Decimal::new(1238, 2));
+    assert_eq!(member(d(0), d(10)), d(0));
+    //2小时160元, 次卡剩0.5小时
+    assert_eq!(package(d(160), 2.0, 0.5), (0.5, d(40)));
+    assert_eq!(package(d(160), 2.0, 10.0), (2.0, d(160)));
+    assert_eq!(package(d(160), 2.0, 0.0), (0.0, d(0)));
+    //1小时, 次卡剩1/3小时
+    assert_eq!(package(d(100), 1.0, 0.33), (0.33, d(33)));
+}
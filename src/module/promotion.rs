@@ -0,0 +1,612 @@
+use super::db::{
+    courts,
+    prelude::{Courts, PromotionCourts, PromotionUsages, Promotions},
+    promotion_courts, promotion_usages, promotions,
+    sea_orm_active_enums::PromotionKind,
+};
+use super::money;
+use crate::{appstate::AppState, error::HandleErr};
+use chrono::Datelike;
+use sea_orm::prelude::{DateTime, Decimal, Time};
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromotionSave {
+    pub promotion_id: Option<Uuid>,
+    pub name: String,
+    pub kind: PromotionKind,
+    //赠送的时数, 或减免的百分比
+    pub value: Decimal,
+    #[serde(default)]
+    pub min_hours: Decimal,
+    //1(周一)~7(周日), 为空时每天
+    #[serde(default)]
+    pub weekdays: Vec<i16>,
+    #[serde(default)]
+    pub start_time: Option<Time>,
+    #[serde(default)]
+    pub end_time: Option<Time>,
+    #[serde(default)]
+    pub stackable: bool,
+    #[serde(default)]
+    pub usage_limit: Option<i32>,
+    #[serde(default)]
+    pub per_user_limit: Option<i32>,
+    pub valid_from: DateTime,
+    pub valid_to: DateTime,
+    //限定的球场, 为空时对名下全部球场生效
+    #[serde(default)]
+    pub court_ids: Vec<Uuid>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromotionDel {
+    pub promotion_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PromotionSchema {
+    #[serde(flatten)]
+    pub promotion: promotions::Model,
+    pub court_ids: Vec<Uuid>,
+    //已参加次数
+    pub used: u64,
+}
+
+//订单参加的活动与优惠金额
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Applied {
+    pub promotion_id: Uuid,
+    pub name: String,
+    pub amount: Decimal,
+}
+
+pub struct PromotionOp;
+impl PromotionOp {
+    //管理员创建的活动, 附带限定球场与参加次数
+    pub async fn all<T>(
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<PromotionSchema>, HandleErr<T>> {
+        let promotions = Promotions::find()
+            .filter(promotions::Column::AdminId.eq(admin_id))
+            .order_by_desc(promotions::Column::CreateTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut schemas = vec![];
+        for promotion in promotions {
+            let court_ids = Self::courts_of(promotion.promotion_id, &state.db).await?;
+            let used = Self::count(promotion.promotion_id, None, &state.db).await?;
+            schemas.push(PromotionSchema {
+                promotion,
+                court_ids,
+                used,
+            });
+        }
+        Ok(schemas)
+    }
+
+    async fn courts_of<T, C: ConnectionTrait>(
+        promotion_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<Uuid>, HandleErr<T>> {
+        Ok(PromotionCourts::find()
+            .filter(promotion_courts::Column::PromotionId.eq(promotion_id))
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| e.court_id)
+            .collect())
+    }
+
+    //参加次数, 指定用户时只统计该用户
+    async fn count<T, C: ConnectionTrait>(
+        promotion_id: Uuid,
+        user_id: Option<Uuid>,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        let mut query =
+            PromotionUsages::find().filter(promotion_usages::Column::PromotionId.eq(promotion_id));
+        if let Some(user_id) = user_id {
+            query = query.filter(promotion_usages::Column::UserId.eq(user_id));
+        }
+        query.count(db).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    pub async fn owned<T: From<String>, C: ConnectionTrait>(
+        promotion_id: Uuid,
+        admin_id: Uuid,
+        db: &C,
+    ) -> Result<promotions::Model, HandleErr<T>> {
+        Promotions::find_by_id(promotion_id)
+            .filter(promotions::Column::AdminId.eq(admin_id))
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "活动不存在".to_string().into()))
+    }
+
+    //新建或修改活动, 同时替换限定球场, 限定的球场须属于该管理员
+    pub async fn save<T: From<String>>(
+        admin_id: Uuid,
+        schema: PromotionSave,
+        state: &AppState,
+    ) -> Result<PromotionSchema, HandleErr<T>> {
+        if !schema.court_ids.is_empty() {
+            let owned = Courts::find()
+                .filter(
+                    courts::Column::CourtId
+                        .is_in(schema.court_ids.clone())
+                        .and(courts::Column::AdminId.eq(admin_id)),
+                )
+                .count(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            if owned as usize != schema.court_ids.len() {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "court_ids无效".to_string().into(),
+                ));
+            }
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if let Some(promotion_id) = schema.promotion_id {
+            Self::owned::<T, _>(promotion_id, admin_id, &txn).await?;
+        }
+        let mut weekdays = schema.weekdays;
+        weekdays.sort();
+        weekdays.dedup();
+        let promotion = promotions::ActiveModel {
+            promotion_id: schema.promotion_id.map(Set).unwrap_or(NotSet),
+            admin_id: Set(admin_id),
+            name: Set(schema.name),
+            kind: Set(schema.kind),
+            value: Set(money::round(schema.value)),
+            min_hours: Set(money::round(schema.min_hours)),
+            weekdays: Set(weekdays),
+            start_time: Set(schema.start_time),
+            end_time: Set(schema.end_time),
+            stackable: Set(schema.stackable),
+            usage_limit: Set(schema.usage_limit),
+            per_user_limit: Set(schema.per_user_limit),
+            valid_from: Set(schema.valid_from),
+            valid_to: Set(schema.valid_to),
+            enabled: Set(schema.enabled),
+            create_time: NotSet,
+        }
+        .save(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        PromotionCourts::delete_many()
+            .filter(promotion_courts::Column::PromotionId.eq(promotion.promotion_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if !schema.court_ids.is_empty() {
+            PromotionCourts::insert_many(schema.court_ids.iter().map(|e| {
+                promotion_courts::ActiveModel {
+                    promotion_id: Set(promotion.promotion_id),
+                    court_id: Set(*e),
+                }
+            }))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        let used = Self::count(promotion.promotion_id, None, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(PromotionSchema {
+            promotion,
+            court_ids: schema.court_ids,
+            used,
+        })
+    }
+
+    //删除未被参加过的活动, 已有订单参加的只能停用
+    pub async fn delete<T: From<String>>(
+        promotion_id: Uuid,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let promotion = Self::owned::<T, _>(promotion_id, admin_id, &state.db).await?;
+        if Self::count(promotion.promotion_id, None, &state.db).await? > 0 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "已有订单参加的活动只能停用".to_string().into(),
+            ));
+        }
+        Promotions::delete_by_id(promotion.promotion_id)
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //球场当前进行中且用户未达参加次数上限的活动
+    pub async fn available<T, C: ConnectionTrait>(
+        court: &courts::Model,
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<promotions::Model>, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let promotions = Promotions::find()
+            .filter(
+                promotions::Column::AdminId
+                    .eq(court.admin_id)
+                    .and(promotions::Column::Enabled.eq(true))
+                    .and(promotions::Column::ValidFrom.lte(now))
+                    .and(promotions::Column::ValidTo.gt(now)),
+            )
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let mut available = vec![];
+        for promotion in promotions {
+            let court_ids = Self::courts_of(promotion.promotion_id, db).await?;
+            if !(court_ids.is_empty() || court_ids.contains(&court.court_id)) {
+                continue;
+            }
+            if Self::exhausted(&promotion, user_id, db).await? {
+                continue;
+            }
+            available.push(promotion);
+        }
+        Ok(available)
+    }
+
+    async fn exhausted<T, C: ConnectionTrait>(
+        promotion: &promotions::Model,
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        if let Some(limit) = promotion.usage_limit {
+            if Self::count(promotion.promotion_id, None, db).await? >= limit as u64 {
+                return Ok(true);
+            }
+        }
+        if let Some(limit) = promotion.per_user_limit {
+            if Self::count(promotion.promotion_id, Some(user_id), db).await? >= limit as u64 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    //下单时记录参加的活动, 锁定活动后再检查次数, 并发下单时不超过上限
+    pub async fn record<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        order_id: Uuid,
+        applied: &[Applied],
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        for e in applied {
+            let promotion = Promotions::find_by_id(e.promotion_id)
+                .lock_exclusive()
+                .one(db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+                .ok_or(HandleErr::BadRequest(-1, "活动不存在".to_string().into()))?;
+            if Self::exhausted(&promotion, user_id, db).await? {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    format!("活动({})已达参加次数上限", promotion.name).into(),
+                ));
+            }
+            PromotionUsages::insert(promotion_usages::ActiveModel {
+                promotion_id: Set(e.promotion_id),
+                order_id: Set(order_id),
+                user_id: Set(user_id),
+                amount: Set(e.amount),
+                create_time: NotSet,
+            })
+            .exec_without_returning(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        Ok(())
+    }
+
+    //订单参加的活动, 修改时段时只在这些活动中重新计算, 不再占用新的参加次数
+    pub async fn of_order<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<Vec<promotions::Model>, HandleErr<T>> {
+        let promotion_ids: Vec<Uuid> = PromotionUsages::find()
+            .filter(promotion_usages::Column::OrderId.eq(order_id))
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| e.promotion_id)
+            .collect();
+        if promotion_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        Promotions::find()
+            .filter(promotions::Column::PromotionId.is_in(promotion_ids))
+            .all(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //按重新计算的优惠更新参加记录, 新时段不再满足条件的活动删除记录
+    pub async fn reapply<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        applied: &[Applied],
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        PromotionUsages::delete_many()
+            .filter(
+                promotion_usages::Column::OrderId.eq(order_id).and(
+                    promotion_usages::Column::PromotionId
+                        .is_not_in(applied.iter().map(|e| e.promotion_id)),
+                ),
+            )
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        for e in applied {
+            PromotionUsages::update_many()
+                .col_expr(promotion_usages::Column::Amount, Expr::value(e.amount))
+                .filter(
+                    promotion_usages::Column::OrderId
+                        .eq(order_id)
+                        .and(promotion_usages::Column::PromotionId.eq(e.promotion_id)),
+                )
+                .exec(db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+        }
+        Ok(())
+    }
+
+    //取消或退款的订单不再计入参加次数
+    pub async fn release<T, C: ConnectionTrait>(
+        order_id: Uuid,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        Ok(PromotionUsages::delete_many()
+            .filter(promotion_usages::Column::OrderId.eq(order_id))
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+}
+
+//订单是否满足活动的星期、时段与时长条件
+pub fn applies(promotion: &promotions::Model, start: DateTime, end: DateTime) -> bool {
+    let weekday = start.weekday().number_from_monday() as i16;
+    if !(promotion.weekdays.is_empty() || promotion.weekdays.contains(&weekday)) {
+        return false;
+    }
+    if let (Some(from), Some(to)) = (promotion.start_time, promotion.end_time) {
+        if start.time() < from || end > start.date().and_time(to) {
+            return false;
+        }
+    }
+    money::hours(start, end) >= promotion.min_hours
+}
+
+//单个活动的优惠金额
+//赠送时长按订单场地费的平均单价折算, 折扣按扣除次卡后的场地费计算
+pub fn amount(
+    promotion: &promotions::Model,
+    court_cost: Decimal,
+    payable: Decimal,
+    hours: Decimal,
+) -> Decimal {
+    let amount = match promotion.kind {
+        PromotionKind::FreeHours if hours > Decimal::ZERO => court_cost / hours * promotion.value,
+        PromotionKind::FreeHours => Decimal::ZERO,
+        PromotionKind::PercentOff => payable * promotion.value / Decimal::ONE_HUNDRED,
+    };
+    money::round(amount.min(payable).max(Decimal::ZERO))
+}
+
+//选择参加的活动: 可叠加的活动合计与单个不可叠加的活动取优惠较大者
+//使用优惠券时只能参加可叠加的活动, 优惠后至少保留0.01元场地费
+pub fn best(
+    promotions: &[promotions::Model],
+    court_cost: Decimal,
+    payable: Decimal,
+    start: DateTime,
+    end: DateTime,
+    with_coupon: bool,
+) -> Vec<Applied> {
+    let hours = money::hours(start, end);
+    let cap = (payable - money::CENT).max(Decimal::ZERO);
+    let applied = |e: &promotions::Model, amount| Applied {
+        promotion_id: e.promotion_id,
+        name: e.name.clone(),
+        amount,
+    };
+    let candidates: Vec<_> = promotions
+        .iter()
+        .filter(|e| (e.stackable || !with_coupon) && applies(e, start, end))
+        .map(|e| (e, amount(e, court_cost, payable, hours)))
+        .filter(|(_, amount)| *amount > Decimal::ZERO)
+        .collect();
+    let mut stacked = vec![];
+    let mut total = Decimal::ZERO;
+    for (e, amount) in candidates.iter().filter(|(e, _)| e.stackable) {
+        let amount = (*amount).min(cap - total);
+        if amount <= Decimal::ZERO {
+            break;
+        }
+        total += amount;
+        stacked.push(applied(e, amount));
+    }
+    let exclusive = candidates
+        .iter()
+        .filter(|(e, _)| !e.stackable)
+        .max_by_key(|(_, amount)| *amount)
+        .map(|(e, amount)| applied(e, (*amount).min(cap)));
+    match exclusive {
+        Some(e) if e.amount > total && e.amount > Decimal::ZERO => vec![e],
+        _ => stacked,
+    }
+}
+
+//参加活动的优惠合计
+pub fn total(applied: &[Applied]) -> Decimal {
+    applied.iter().map(|e| e.amount).sum()
+}
+
+#[test]
+fn test_promotion() {
+    let d = |v: i64| Decimal::from(v);
+    let t = |h| Time::from_hms_opt(h, 0, 0).unwrap();
+    let promotion = |kind, value, min_hours, stackable| promotions::Model {
+        promotion_id: Uuid::new_v4(),
+        admin_id: Uuid::nil(),
+        name: String::new(),
+        kind,
+        value,
+        min_hours,
+        weekdays: vec![],
+        start_time: None,
+        end_time: None,
+        stackable,
+        usage_limit: None,
+        per_user_limit: None,
+        valid_from: DateTime::default(),
+        valid_to: DateTime::default(),
+        enabled: true,
+        create_time: DateTime::default(),
+    };
+    //2024-01-01 周一
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let (start, end) = (day.and_time(t(9)), day.and_time(t(12)));
+    //订3小时送1小时
+    let free = promotion(PromotionKind::FreeHours, d(1), d(3), false);
+    assert!(applies(&free, start, end));
+    assert!(!applies(&free, start, day.and_time(t(11))));
+    assert_eq!(amount(&free, d(150), d(150), d(3)), d(50));
+    //工作日上午8折
+    let morning = promotions::Model {
+        weekdays: vec![1, 2, 3, 4, 5],
+        start_time: Some(t(8)),
+        end_time: Some(t(12)),
+        ..promotion(PromotionKind::PercentOff, d(20), d(0), true)
+    };
+    assert!(applies(&morning, start, end));
+    assert!(!applies(&morning, start, day.and_time(t(13))));
+    assert!(!applies(
+        &morning,
+        start + chrono::Duration::days(5),
+        end + chrono::Duration::days(5)
+    ));
+    assert_eq!(amount(&morning, d(150), d(150), d(3)), d(30));
+    //不可叠加的赠送时长优惠更大
+    let all = vec![free.clone(), morning.clone()];
+    let applied = best(&all, d(150), d(150), start, end, false);
+    assert_eq!((applied.len(), total(&applied)), (1, d(50)));
+    //使用优惠券时只参加可叠加的活动
+    let applied = best(&all, d(150), d(150), start, end, true);
+    assert_eq!((applied.len(), total(&applied)), (1, d(30)));
+    //可叠加的活动合计, 至少保留0.01元
+    let most = promotion(PromotionKind::PercentOff, d(90), d(0), true);
+    let applied = best(&[morning, most], d(150), d(150), start, end, false);
+    assert_eq!(total(&applied), d(150) - money::CENT);
+    assert!(best(&[], d(150), d(150), start, end, false).is_empty());
+}
@@ -0,0 +1,437 @@
+use super::coupon::CouponOp;
+use super::db::{
+    coupon_templates, orders,
+    prelude::{CouponTemplates, ReferralCodes, ReferralConfig, Referrals},
+    referral_codes, referral_config, referrals, users,
+};
+use crate::{appstate::AppState, error::HandleErr};
+use rand_core::RngCore;
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult, JoinType, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 8;
+//邀请链向上追溯的最大层数
+const CHAIN_DEPTH: usize = 20;
+//统计中展示的邀请人数
+const TOP_INVITERS: u64 = 20;
+
+//邀请奖励设置, 模板为空时不发放
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct ReferralRule {
+    pub inviter_template_id: Option<Uuid>,
+    pub invitee_template_id: Option<Uuid>,
+}
+
+impl From<referral_config::Model> for ReferralRule {
+    fn from(e: referral_config::Model) -> Self {
+        Self {
+            inviter_template_id: e.inviter_template_id,
+            invitee_template_id: e.invitee_template_id,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReferralChainQuery {
+    pub user_id: Uuid,
+}
+
+//邀请的用户, 只展示昵称
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct InviteeSchema {
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub rewarded_time: Option<DateTime>,
+    pub create_time: DateTime,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ReferralSummary {
+    pub code: String,
+    pub invited: usize,
+    pub rewarded: usize,
+    pub invitees: Vec<InviteeSchema>,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct InviterStat {
+    pub inviter_id: Uuid,
+    pub user_name: String,
+    pub invited: i64,
+    pub rewarded: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ReferralStats {
+    pub invited: u64,
+    pub rewarded: u64,
+    pub top_inviters: Vec<InviterStat>,
+}
+
+#[derive(Debug, Serialize, Clone, FromQueryResult)]
+pub struct ReferralNode {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub create_time: DateTime,
+}
+
+//邀请链: 由近及远的上级邀请人与直接邀请的用户
+#[derive(Debug, Serialize, Clone)]
+pub struct ReferralChain {
+    pub inviters: Vec<ReferralNode>,
+    pub invitees: Vec<ReferralNode>,
+}
+
+pub struct ReferralOp;
+impl ReferralOp {
+    //用户的邀请码, 没有时生成, 邀请码冲突时重新生成
+    pub async fn code<T>(user_id: Uuid, state: &AppState) -> Result<String, HandleErr<T>> {
+        for _ in 0..3 {
+            if let Some(code) = ReferralCodes::find_by_id(user_id)
+                .one(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?
+            {
+                return Ok(code.code);
+            }
+            ReferralCodes::insert(referral_codes::ActiveModel {
+                user_id: Set(user_id),
+                code: Set(generate_code()),
+                create_time: NotSet,
+            })
+            .on_conflict(OnConflict::new().do_nothing().to_owned())
+            .exec_without_returning(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        let id = Uuid::new_v4();
+        error!("{} >>>> 用户({})的邀请码生成失败", id, user_id);
+        Err(HandleErr::ServerInnerErr(id))
+    }
+
+    //邀请码所属的用户, 已注销的用户不能邀请
+    pub async fn inviter<T: From<&'static str>, C: ConnectionTrait>(
+        code: &str,
+        db: &C,
+    ) -> Result<Uuid, HandleErr<T>> {
+        ReferralCodes::find()
+            .join(JoinType::InnerJoin, referral_codes::Relation::Users.def())
+            .filter(referral_codes::Column::Code.eq(normalize(code)))
+            .filter(users::Column::DeletedTime.is_null())
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .map(|e| e.user_id)
+            .ok_or(HandleErr::BadRequest(-1, "邀请码无效".into()))
+    }
+
+    //新用户注册时绑定邀请人, 每个用户只能被邀请一次
+    pub async fn bind<T, C: ConnectionTrait>(
+        invitee_id: Uuid,
+        inviter_id: Uuid,
+        db: &C,
+    ) -> Result<bool, HandleErr<T>> {
+        if invitee_id == inviter_id {
+            return Ok(false);
+        }
+        let rows_affected = Referrals::insert(referrals::ActiveModel {
+            invitee_id: Set(invitee_id),
+            inviter_id: Set(inviter_id),
+            order_id: NotSet,
+            rewarded_time: NotSet,
+            create_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::column(referrals::Column::InviteeId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if rows_affected > 0 {
+            info!("用户({})由({})邀请注册", invitee_id, inviter_id);
+        }
+        Ok(rows_affected > 0)
+    }
+
+    //被邀请人首笔订单完成后双方获得优惠券, 与订单状态变更在同一事务中
+    //以未奖励为条件更新, 每个邀请关系只奖励一次
+    pub async fn reward<T, C: ConnectionTrait>(
+        order: &orders::Model,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        let referral = Referrals::update_many()
+            .col_expr(referrals::Column::OrderId, Expr::value(order.order_id))
+            .col_expr(
+                referrals::Column::RewardedTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(referrals::Column::InviteeId.eq(order.user_id))
+            .filter(referrals::Column::RewardedTime.is_null())
+            .exec_with_returning(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let Some(referral) = referral.into_iter().next() else {
+            return Ok(());
+        };
+        let rule = Self::rule::<T, _>(db).await?;
+        for (template_id, user_id) in [
+            (rule.inviter_template_id, referral.inviter_id),
+            (rule.invitee_template_id, referral.invitee_id),
+        ] {
+            let Some(template_id) = template_id else {
+                continue;
+            };
+            if !CouponOp::grant::<T, _>(template_id, user_id, db).await? {
+                warn!("邀请奖励优惠券({})已停用或过期, 未发放", template_id);
+            }
+        }
+        info!(
+            "用户({})首笔订单({})已完成, 发放邀请奖励",
+            referral.invitee_id, order.order_id
+        );
+        Ok(())
+    }
+
+    //我的邀请码与邀请记录
+    pub async fn summary<T>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<ReferralSummary, HandleErr<T>> {
+        let code = Self::code::<T>(user_id, state).await?;
+        let invitees = Referrals::find()
+            .select_only()
+            .columns([
+                referrals::Column::RewardedTime,
+                referrals::Column::CreateTime,
+            ])
+            .columns([users::Column::Nickname, users::Column::AvatarUrl])
+            .join(JoinType::InnerJoin, referrals::Relation::Users2.def())
+            .filter(referrals::Column::InviterId.eq(user_id))
+            .order_by_desc(referrals::Column::CreateTime)
+            .into_model::<InviteeSchema>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(ReferralSummary {
+            code,
+            invited: invitees.len(),
+            rewarded: invitees
+                .iter()
+                .filter(|e| e.rewarded_time.is_some())
+                .count(),
+            invitees,
+        })
+    }
+
+    pub async fn rule<T, C: ConnectionTrait>(db: &C) -> Result<ReferralRule, HandleErr<T>> {
+        Ok(ReferralConfig::find_by_id(1i16)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .map(ReferralRule::from)
+            .unwrap_or_default())
+    }
+
+    //设置邀请奖励, 模板须存在且已启用
+    pub async fn set_rule<T: From<&'static str>>(
+        rule: ReferralRule,
+        state: &AppState,
+    ) -> Result<ReferralRule, HandleErr<T>> {
+        let template_ids: HashSet<Uuid> = [rule.inviter_template_id, rule.invitee_template_id]
+            .into_iter()
+            .flatten()
+            .collect();
+        if !template_ids.is_empty() {
+            let enabled = CouponTemplates::find()
+                .filter(coupon_templates::Column::TemplateId.is_in(template_ids.clone()))
+                .filter(coupon_templates::Column::Enabled.eq(true))
+                .count(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            if enabled as usize != template_ids.len() {
+                return Err(HandleErr::BadRequest(-1, "优惠券模板不存在或已停用".into()));
+            }
+        }
+        ReferralConfig::insert(referral_config::ActiveModel {
+            config_id: Set(1),
+            inviter_template_id: Set(rule.inviter_template_id),
+            invitee_template_id: Set(rule.invitee_template_id),
+            update_time: Set(chrono::Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::column(referral_config::Column::ConfigId)
+                .update_columns([
+                    referral_config::Column::InviterTemplateId,
+                    referral_config::Column::InviteeTemplateId,
+                    referral_config::Column::UpdateTime,
+                ])
+                .to_owned(),
+        )
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(rule)
+    }
+
+    //邀请总数/已奖励数与邀请最多的用户
+    pub async fn stats<T>(state: &AppState) -> Result<ReferralStats, HandleErr<T>> {
+        let invited = Referrals::find().count(&state.db).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let rewarded = Referrals::find()
+            .filter(referrals::Column::RewardedTime.is_not_null())
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let top_inviters = Referrals::find()
+            .select_only()
+            .column(referrals::Column::InviterId)
+            .column(users::Column::UserName)
+            .column_as(referrals::Column::InviteeId.count(), "invited")
+            .column_as(referrals::Column::RewardedTime.count(), "rewarded")
+            .join(JoinType::InnerJoin, referrals::Relation::Users1.def())
+            .group_by(referrals::Column::InviterId)
+            .group_by(users::Column::UserName)
+            .order_by_desc(Expr::cust("invited"))
+            .limit(TOP_INVITERS)
+            .into_model::<InviterStat>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(ReferralStats {
+            invited,
+            rewarded,
+            top_inviters,
+        })
+    }
+
+    //用户的邀请链
+    pub async fn chain<T>(user_id: Uuid, state: &AppState) -> Result<ReferralChain, HandleErr<T>> {
+        let mut inviters = vec![];
+        let mut visited = HashSet::from([user_id]);
+        let mut current = user_id;
+        while inviters.len() < CHAIN_DEPTH {
+            let inviter = Referrals::find_by_id(current)
+                .select_only()
+                .column_as(referrals::Column::InviterId, "user_id")
+                .column(users::Column::UserName)
+                .column(referrals::Column::CreateTime)
+                .join(JoinType::InnerJoin, referrals::Relation::Users1.def())
+                .into_model::<ReferralNode>()
+                .one(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            match inviter {
+                Some(inviter) if visited.insert(inviter.user_id) => {
+                    current = inviter.user_id;
+                    inviters.push(inviter);
+                }
+                _ => break,
+            }
+        }
+        let invitees = Referrals::find()
+            .select_only()
+            .column_as(referrals::Column::InviteeId, "user_id")
+            .column(users::Column::UserName)
+            .column(referrals::Column::CreateTime)
+            .join(JoinType::InnerJoin, referrals::Relation::Users2.def())
+            .filter(referrals::Column::InviterId.eq(user_id))
+            .order_by_desc(referrals::Column::CreateTime)
+            .into_model::<ReferralNode>()
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(ReferralChain { inviters, invitees })
+    }
+}
+
+fn generate_code() -> String {
+    let mut bytes = [0u8; CODE_LEN];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|e| CODE_CHARS[*e as usize % CODE_CHARS.len()] as char)
+        .collect()
+}
+
+//用户输入的邀请码, 忽略空白与大小写
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|e| e.is_ascii_alphanumeric())
+        .map(|e| e.to_ascii_uppercase())
+        .collect()
+}
+
+#[test]
+fn test_referral_code() {
+    let code = generate_code();
+    assert_eq!(code.len(), CODE_LEN);
+    assert!(code.bytes().all(|e| CODE_CHARS.contains(&e)));
+    assert_eq!(normalize(" ab3d efg9 "), "AB3DEFG9");
+}
@@ -0,0 +1,497 @@
+use super::db::{
+    prelude::{RefreshTokens, UserSessions, Users},
+    refresh_tokens, user_sessions, users,
+};
+use crate::{appstate::AppState, error::HandleErr, utils::token};
+use axum::http::{header, HeaderMap};
+use rand_core::RngCore;
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//最近活跃时间的更新间隔, 避免每次请求都写库
+const ACTIVE_INTERVAL_MINUTES: i64 = 5;
+const DEVICE_LEN: usize = 255;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SessionRevoke {
+    pub session_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RefreshSchema {
+    pub refresh_token: String,
+}
+
+//登录或刷新后签发的一对令牌
+#[derive(Debug, Serialize, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SessionSchema {
+    pub session_id: Uuid,
+    pub device: String,
+    pub ip: String,
+    pub create_time: DateTime,
+    pub last_active_time: DateTime,
+    pub expire_time: DateTime,
+    //是否为当前请求使用的会话
+    pub current: bool,
+}
+
+pub struct SessionOp;
+impl SessionOp {
+    //登录成功后记录会话并签发token, 会话有效期与刷新令牌一致
+    //mfa_verified: 本次登录已校验二次验证码
+    pub async fn issue<T>(
+        user_id: Uuid,
+        mfa_verified: bool,
+        headers: &HeaderMap,
+        addr: SocketAddr,
+        state: &AppState,
+    ) -> Result<TokenPair, HandleErr<T>> {
+        let session_id = Uuid::new_v4();
+        let now = chrono::Utc::now().naive_utc();
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        UserSessions::insert(user_sessions::ActiveModel {
+            session_id: Set(session_id),
+            user_id: Set(user_id),
+            device: Set(device(headers)),
+            ip: Set(client_ip(headers, addr, &state.cfg.servercfg.trusted_proxies).to_string()),
+            create_time: Set(now),
+            last_active_time: Set(now),
+            expire_time: Set(now + chrono::Duration::days(state.cfg.tokencfg.refresh_token_ttl)),
+            revoked_time: Set(None),
+            mfa_verified: Set(mfa_verified),
+        })
+        .exec_without_returning(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let tokens = Self::tokens(user_id, session_id, &txn, state).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(tokens)
+    }
+
+    //用刷新令牌换取新的一对令牌, 旧的刷新令牌随即失效
+    //已使用过的刷新令牌再次出现时, 说明令牌可能泄露, 撤销整个会话
+    pub async fn refresh<T>(
+        refresh_token: &str,
+        state: &AppState,
+    ) -> Result<TokenPair, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let token_hash = digest(refresh_token.trim());
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        //只有未使用的令牌能被标记, 并发刷新时仅一个请求成功
+        let rows_affected = RefreshTokens::update_many()
+            .col_expr(refresh_tokens::Column::UsedTime, Expr::value(now))
+            .filter(refresh_tokens::Column::TokenHash.eq(&token_hash))
+            .filter(refresh_tokens::Column::UsedTime.is_null())
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        let token = RefreshTokens::find_by_id(token_hash)
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or_else(|| {
+                warn!("refresh_token不存在");
+                HandleErr::UnAuthorized
+            })?;
+        if rows_affected == 0 {
+            UserSessions::update_many()
+                .col_expr(user_sessions::Column::RevokedTime, Expr::value(now))
+                .filter(user_sessions::Column::SessionId.eq(token.session_id))
+                .filter(user_sessions::Column::RevokedTime.is_null())
+                .exec(&txn)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+            txn.commit().await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            warn!(
+                "会话({})的refresh_token被重复使用, 已撤销该会话",
+                token.session_id
+            );
+            return Err(HandleErr::UnAuthorized);
+        }
+        let session = UserSessions::find_by_id(token.session_id)
+            .filter(user_sessions::Column::RevokedTime.is_null())
+            .filter(user_sessions::Column::ExpireTime.gt(now))
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or_else(|| {
+                warn!("refresh_token所属会话({})已失效", token.session_id);
+                HandleErr::UnAuthorized
+            })?;
+        Users::find_by_id(session.user_id)
+            .filter(users::Column::DeletedTime.is_null())
+            .filter(users::Column::DisabledTime.is_null())
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or_else(|| {
+                warn!(
+                    "refresh_token所属用户({})不存在, 已注销或已封禁",
+                    session.user_id
+                );
+                HandleErr::UnAuthorized
+            })?;
+        let tokens = Self::tokens(session.user_id, session.session_id, &txn, state).await?;
+        UserSessions::update_many()
+            .col_expr(
+                user_sessions::Column::ExpireTime,
+                Expr::value(now + chrono::Duration::days(state.cfg.tokencfg.refresh_token_ttl)),
+            )
+            .col_expr(user_sessions::Column::LastActiveTime, Expr::value(now))
+            .filter(user_sessions::Column::SessionId.eq(session.session_id))
+            .exec(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("会话({})刷新token", session.session_id);
+        Ok(tokens)
+    }
+
+    //为会话签发access_token与新的刷新令牌, 刷新令牌只保存哈希
+    async fn tokens<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        session_id: Uuid,
+        db: &C,
+        state: &AppState,
+    ) -> Result<TokenPair, HandleErr<T>> {
+        let access_token = token::create(
+            user_id,
+            session_id,
+            state.cfg.tokencfg.access_token_ttl,
+            &state.cfg.tokencfg.access_prikey,
+        )
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let refresh_token = generate();
+        RefreshTokens::insert(refresh_tokens::ActiveModel {
+            token_hash: Set(digest(&refresh_token)),
+            session_id: Set(session_id),
+            create_time: Set(chrono::Utc::now().naive_utc()),
+            used_time: Set(None),
+        })
+        .exec_without_returning(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(TokenPair {
+            access_token,
+            refresh_token,
+        })
+    }
+
+    //未撤销且未过期的会话, 有效时更新最近活跃时间
+    pub async fn check<T>(
+        session_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<Option<user_sessions::Model>, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        let session = UserSessions::find_by_id(session_id)
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedTime.is_null())
+            .filter(user_sessions::Column::ExpireTime.gt(now))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let Some(session) = session else {
+            return Ok(None);
+        };
+        if now - session.last_active_time > chrono::Duration::minutes(ACTIVE_INTERVAL_MINUTES) {
+            UserSessions::update_many()
+                .col_expr(user_sessions::Column::LastActiveTime, Expr::value(now))
+                .filter(user_sessions::Column::SessionId.eq(session_id))
+                .exec(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+        }
+        Ok(Some(session))
+    }
+
+    //用户的有效会话, 最近活跃的在前
+    pub async fn list<T>(
+        user_id: Uuid,
+        current: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<SessionSchema>, HandleErr<T>> {
+        Ok(UserSessions::find()
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedTime.is_null())
+            .filter(user_sessions::Column::ExpireTime.gt(chrono::Utc::now().naive_utc()))
+            .order_by_desc(user_sessions::Column::LastActiveTime)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(|e| SessionSchema {
+                current: e.session_id == current,
+                session_id: e.session_id,
+                device: e.device,
+                ip: e.ip,
+                create_time: e.create_time,
+                last_active_time: e.last_active_time,
+                expire_time: e.expire_time,
+            })
+            .collect())
+    }
+
+    //撤销本人的一个会话, 该会话的token立即失效
+    pub async fn revoke<T: From<&'static str>>(
+        user_id: Uuid,
+        session_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let rows_affected = UserSessions::update_many()
+            .col_expr(
+                user_sessions::Column::RevokedTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(user_sessions::Column::SessionId.eq(session_id))
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedTime.is_null())
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "会话不存在或已失效".into()));
+        }
+        info!("用户({})撤销会话({})", user_id, session_id);
+        Ok(())
+    }
+
+    //撤销除当前会话外的全部会话, 返回撤销数
+    pub async fn revoke_others<T>(
+        user_id: Uuid,
+        current: Uuid,
+        state: &AppState,
+    ) -> Result<u64, HandleErr<T>> {
+        let rows_affected = Self::revoke_all(user_id, Some(current), &state.db).await?;
+        info!("用户({})撤销其他{}个会话", user_id, rows_affected);
+        Ok(rows_affected)
+    }
+
+    //开启二次验证后当前会话视为已验证
+    pub async fn mfa_verified<T, C: ConnectionTrait>(
+        session_id: Uuid,
+        db: &C,
+    ) -> Result<(), HandleErr<T>> {
+        UserSessions::update_many()
+            .col_expr(user_sessions::Column::MfaVerified, Expr::value(true))
+            .filter(user_sessions::Column::SessionId.eq(session_id))
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok(())
+    }
+
+    //撤销用户的全部会话, except为保留的会话, 返回撤销数
+    //修改密码/封禁/注销时调用, 已签发的token与刷新令牌随即失效
+    pub async fn revoke_all<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        except: Option<Uuid>,
+        db: &C,
+    ) -> Result<u64, HandleErr<T>> {
+        let mut update = UserSessions::update_many()
+            .col_expr(
+                user_sessions::Column::RevokedTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedTime.is_null());
+        if let Some(except) = except {
+            update = update.filter(user_sessions::Column::SessionId.ne(except));
+        }
+        Ok(update
+            .exec(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+
+    //删除已过期的会话及其刷新令牌, 过期的token已无法通过签名校验
+    pub async fn purge<T>(state: &AppState) -> Result<u64, HandleErr<T>> {
+        Ok(UserSessions::delete_many()
+            .filter(user_sessions::Column::ExpireTime.lte(chrono::Utc::now().naive_utc()))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected)
+    }
+}
+
+//随机的刷新令牌, 256位, 十六进制
+fn generate() -> String {
+    let mut bytes = [0u8; 32];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|e| format!("{:02x}", e)).collect()
+}
+
+//刷新令牌的SHA-256哈希, 十六进制
+fn digest(refresh_token: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, refresh_token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect()
+}
+
+//登录设备, 取User-Agent
+fn device(headers: &HeaderMap) -> String {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|e| e.to_str().ok())
+        .map(|e| e.trim().chars().take(DEVICE_LEN).collect())
+        .unwrap_or_default()
+}
+
+//客户端ip, 仅当连接来自受信任的反向代理时采用X-Forwarded-For
+//从右向左跳过受信任的代理, 取第一个不受信任的地址, 客户端自行添加的部分不被采用
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr, trusted: &[IpAddr]) -> IpAddr {
+    if !trusted.contains(&addr.ip()) {
+        return addr.ip();
+    }
+    let Some(forwarded) = headers.get("x-forwarded-for").and_then(|e| e.to_str().ok()) else {
+        return addr.ip();
+    };
+    for ip in forwarded.rsplit(',') {
+        match ip.trim().parse::<IpAddr>() {
+            Ok(ip) if trusted.contains(&ip) => continue,
+            Ok(ip) => return ip,
+            Err(_) => break,
+        }
+    }
+    addr.ip()
+}
+
+#[test]
+fn test_client() {
+    let addr: SocketAddr = "10.0.0.2:443".parse().unwrap();
+    let mut headers = HeaderMap::new();
+    assert_eq!(device(&headers), "");
+    let trusted: Vec<IpAddr> = vec!["10.0.0.2".parse().unwrap(), "10.0.0.1".parse().unwrap()];
+    let ip =
+        |headers: &HeaderMap, trusted: &[IpAddr]| client_ip(headers, addr, trusted).to_string();
+    assert_eq!(ip(&headers, &trusted), "10.0.0.2");
+    headers.insert(
+        "x-forwarded-for",
+        "198.51.100.9, 203.0.113.7, 10.0.0.1".parse().unwrap(),
+    );
+    headers.insert(header::USER_AGENT, " MicroMessenger/8.0 ".parse().unwrap());
+    //客户端伪造的198.51.100.9不被采用
+    assert_eq!(ip(&headers, &trusted), "203.0.113.7");
+    //不是来自受信任代理的连接忽略X-Forwarded-For
+    assert_eq!(ip(&headers, &[]), "10.0.0.2");
+    assert_eq!(device(&headers), "MicroMessenger/8.0");
+    headers.insert("x-forwarded-for", "unknown".parse().unwrap());
+    assert_eq!(ip(&headers, &trusted), "10.0.0.2");
+}
+
+#[test]
+fn test_refresh_token() {
+    let token = generate();
+    assert_eq!(token.len(), 64);
+    assert!(token.chars().all(|e| e.is_ascii_hexdigit()));
+    assert_ne!(token, generate());
+    assert_eq!(digest(&token), digest(&token));
+    assert_ne!(digest(&token), token);
+    assert_eq!(
+        digest("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
@@ -0,0 +1,284 @@
+use super::db::{prelude::Users, sea_orm_active_enums::StaffScope, users};
+use super::session::SessionOp;
+use crate::{appstate::AppState, error::HandleErr, utils::passwd};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//每个管理员最多创建的员工账号数
+const MAX_STAFF: u64 = 50;
+const NAME_LEN: usize = 30;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StaffAdd {
+    pub user_name: String,
+    pub pwd: String,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<StaffScope>,
+}
+
+//只更新提供的字段, 修改密码后该员工需重新登录
+#[derive(Debug, Deserialize, Clone)]
+pub struct StaffUpdate {
+    pub user_id: Uuid,
+    pub scopes: Option<Vec<StaffScope>>,
+    pub pwd: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StaffDel {
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StaffSchema {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub phone: Option<String>,
+    pub scopes: Vec<StaffScope>,
+}
+
+impl From<users::Model> for StaffSchema {
+    fn from(value: users::Model) -> Self {
+        Self {
+            user_id: value.user_id,
+            user_name: value.user_name,
+            phone: value.phone,
+            scopes: value.staff_scopes,
+        }
+    }
+}
+
+pub struct StaffOp;
+impl StaffOp {
+    //管理员创建员工账号, 员工用账号密码登录
+    pub async fn add(
+        admin_id: Uuid,
+        schema: StaffAdd,
+        state: &AppState,
+    ) -> Result<StaffSchema, HandleErr<String>> {
+        let user_name = validate(&schema.user_name, &schema.pwd)
+            .map_err(|e| HandleErr::BadRequest(-1, e.to_string()))?;
+        let phone = schema
+            .phone
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty());
+        let count = Users::find()
+            .filter(users::Column::StaffOf.eq(admin_id))
+            .filter(users::Column::DisabledTime.is_null())
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if count >= MAX_STAFF {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("最多创建{}个员工账号", MAX_STAFF),
+            ));
+        }
+        let mut cond = users::Column::UserName.eq(&user_name);
+        if let Some(phone) = &phone {
+            cond = cond.or(users::Column::Phone.eq(phone));
+        }
+        let exists = Users::find()
+            .filter(cond)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if exists.is_some() {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "用户名已存在, 或者号码已存在".to_string(),
+            ));
+        }
+        let password_hash = passwd::hash_password(&schema.pwd)?;
+        let user = users::ActiveModel {
+            user_name: Set(user_name),
+            user_pwd: Set(password_hash),
+            hash_version: Set(passwd::HASH_VERSION),
+            phone: Set(phone),
+            is_admin: Set(false),
+            staff_of: Set(Some(admin_id)),
+            staff_scopes: Set(dedup(schema.scopes)),
+            notify_reminder: Set(false),
+            ..Default::default()
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("管理员({})创建员工账号({})", admin_id, user.user_id);
+        Ok(user.into())
+    }
+
+    //调整授权范围即时生效, 修改密码时撤销该员工的全部会话
+    pub async fn update(
+        admin_id: Uuid,
+        schema: StaffUpdate,
+        state: &AppState,
+    ) -> Result<StaffSchema, HandleErr<String>> {
+        Self::owned(admin_id, schema.user_id, state).await?;
+        let mut model = users::ActiveModel {
+            user_id: Set(schema.user_id),
+            ..Default::default()
+        };
+        if let Some(scopes) = schema.scopes {
+            model.staff_scopes = Set(dedup(scopes));
+        }
+        if let Some(pwd) = &schema.pwd {
+            if !(6..=64).contains(&pwd.chars().count()) {
+                return Err(HandleErr::BadRequest(-1, "密码长度须为6-64位".to_string()));
+            }
+            model.user_pwd = Set(passwd::hash_password(pwd)?);
+            model.hash_version = Set(passwd::HASH_VERSION);
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let user = model.update(&txn).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if schema.pwd.is_some() {
+            SessionOp::revoke_all(user.user_id, None, &txn).await?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(user.into())
+    }
+
+    //停用员工账号, 已登录的设备立即下线
+    pub async fn del(
+        admin_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<String>> {
+        Self::owned(admin_id, user_id, state).await?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        users::ActiveModel {
+            user_id: Set(user_id),
+            staff_scopes: Set(vec![]),
+            disabled_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        SessionOp::revoke_all(user_id, None, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("管理员({})停用员工账号({})", admin_id, user_id);
+        Ok(())
+    }
+
+    //管理员名下未停用的员工账号
+    pub async fn list<T>(
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<Vec<StaffSchema>, HandleErr<T>> {
+        Ok(Users::find()
+            .filter(users::Column::StaffOf.eq(admin_id))
+            .filter(users::Column::DisabledTime.is_null())
+            .order_by_asc(users::Column::UserName)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .into_iter()
+            .map(StaffSchema::from)
+            .collect())
+    }
+
+    async fn owned(
+        admin_id: Uuid,
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<users::Model, HandleErr<String>> {
+        Users::find_by_id(user_id)
+            .filter(users::Column::StaffOf.eq(admin_id))
+            .filter(users::Column::DisabledTime.is_null())
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "员工账号不存在".to_string()))
+    }
+}
+
+//用户名去掉首尾空白, 密码6-64位
+fn validate(user_name: &str, pwd: &str) -> Result<String, &'static str> {
+    let user_name = user_name.trim();
+    if user_name.is_empty() || user_name.chars().count() > NAME_LEN {
+        return Err("用户名不能为空且不超过30字");
+    }
+    if !(6..=64).contains(&pwd.chars().count()) {
+        return Err("密码长度须为6-64位");
+    }
+    Ok(user_name.to_string())
+}
+
+//去掉重复的授权范围, 保持原有顺序
+fn dedup(scopes: Vec<StaffScope>) -> Vec<StaffScope> {
+    let mut distinct = vec![];
+    for scope in scopes {
+        if !distinct.contains(&scope) {
+            distinct.push(scope);
+        }
+    }
+    distinct
+}
+
+#[test]
+fn test_staff_validate() {
+    assert_eq!(validate(" 前台01 ", "123456"), Ok("前台01".to_string()));
+    assert!(validate("  ", "123456").is_err());
+    assert!(validate("前台", "12345").is_err());
+    assert!(validate(&"员".repeat(31), "123456").is_err());
+    assert_eq!(
+        dedup(vec![
+            StaffScope::Checkin,
+            StaffScope::Orders,
+            StaffScope::Checkin
+        ]),
+        vec![StaffScope::Checkin, StaffScope::Orders]
+    );
+}
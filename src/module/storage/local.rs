@@ -0,0 +1,64 @@
+use anyhow::anyhow;
+use std::path::{Component, Path, PathBuf};
+
+//本地目录存储, 由 /static 路由对外提供访问
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: &str, base_url: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub async fn put(&self, key: &str, data: Vec<u8>) -> crate::App::Result<String> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&path, data).await?;
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+
+    pub async fn get(&self, key: &str) -> crate::App::Result<Vec<u8>> {
+        Ok(tokio::fs::read(self.path(key)?).await?)
+    }
+
+    pub async fn delete(&self, key: &str) -> crate::App::Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    //只允许根目录下的相对路径, 拒绝 .. 与绝对路径, 防止越过存储根目录
+    fn path(&self, key: &str) -> crate::App::Result<PathBuf> {
+        if !safe_key(key) {
+            return Err(anyhow!("非法的存储路径: {}", key));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+fn safe_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with(['/', '\\'])
+        && Path::new(key)
+            .components()
+            .all(|e| matches!(e, Component::Normal(_)))
+}
+
+#[test]
+fn test_safe_key() {
+    assert!(safe_key("court/1/a.png"));
+    assert!(!safe_key(""));
+    assert!(!safe_key("/etc/passwd"));
+    assert!(!safe_key("feedback/../../x"));
+    assert!(!safe_key("image/../x"));
+    assert!(!safe_key("./a.png"));
+}
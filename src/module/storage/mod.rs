@@ -0,0 +1,61 @@
+use crate::cfg::StorageCfg;
+mod local;
+mod oss;
+
+//文件存储后端, 按配置选择
+#[derive(Debug, Clone)]
+pub enum Storage {
+    Local(local::LocalStorage),
+    Oss(oss::OssStorage),
+}
+
+impl Storage {
+    pub fn new(cfg: &StorageCfg) -> Self {
+        match cfg {
+            StorageCfg::Local { root, base_url } => {
+                Storage::Local(local::LocalStorage::new(root, base_url))
+            }
+            StorageCfg::Oss {
+                endpoint,
+                bucket,
+                access_key_id,
+                access_key_secret,
+                base_url,
+            } => Storage::Oss(oss::OssStorage::new(
+                endpoint,
+                bucket,
+                access_key_id,
+                access_key_secret,
+                base_url.as_deref(),
+            )),
+        }
+    }
+
+    //保存文件, 返回可访问的url
+    pub async fn put(
+        &self,
+        key: &str,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> crate::App::Result<String> {
+        match self {
+            Storage::Local(e) => e.put(key, data).await,
+            Storage::Oss(e) => e.put(key, content_type, data).await,
+        }
+    }
+
+    //读取文件内容
+    pub async fn get(&self, key: &str) -> crate::App::Result<Vec<u8>> {
+        match self {
+            Storage::Local(e) => e.get(key).await,
+            Storage::Oss(e) => e.get(key).await,
+        }
+    }
+
+    pub async fn delete(&self, key: &str) -> crate::App::Result<()> {
+        match self {
+            Storage::Local(e) => e.delete(key).await,
+            Storage::Oss(e) => e.delete(key).await,
+        }
+    }
+}
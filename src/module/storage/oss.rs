@@ -0,0 +1,108 @@
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use reqwest::header;
+
+//阿里云OSS存储, 使用V1签名
+#[derive(Debug, Clone)]
+pub struct OssStorage {
+    client: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    access_key_id: String,
+    access_key_secret: String,
+    base_url: String,
+}
+
+impl OssStorage {
+    pub fn new(
+        endpoint: &str,
+        bucket: &str,
+        access_key_id: &str,
+        access_key_secret: &str,
+        base_url: Option<&str>,
+    ) -> Self {
+        let base_url = base_url
+            .map(|e| e.trim_end_matches('/').to_string())
+            .unwrap_or(format!("https://{}.{}", bucket, endpoint));
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.to_string(),
+            bucket: bucket.to_string(),
+            access_key_id: access_key_id.to_string(),
+            access_key_secret: access_key_secret.to_string(),
+            base_url,
+        }
+    }
+
+    pub async fn put(
+        &self,
+        key: &str,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> crate::App::Result<String> {
+        let date = httpdate();
+        self.client
+            .put(self.object_url(key))
+            .header(header::DATE, &date)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(
+                header::AUTHORIZATION,
+                self.sign("PUT", content_type, &date, key),
+            )
+            .body(data)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+
+    pub async fn get(&self, key: &str) -> crate::App::Result<Vec<u8>> {
+        let date = httpdate();
+        let data = self
+            .client
+            .get(self.object_url(key))
+            .header(header::DATE, &date)
+            .header(header::AUTHORIZATION, self.sign("GET", "", &date, key))
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(data.to_vec())
+    }
+
+    pub async fn delete(&self, key: &str) -> crate::App::Result<()> {
+        let date = httpdate();
+        self.client
+            .delete(self.object_url(key))
+            .header(header::DATE, &date)
+            .header(header::AUTHORIZATION, self.sign("DELETE", "", &date, key))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        format!("https://{}.{}/{}", self.bucket, self.endpoint, key)
+    }
+
+    fn sign(&self, verb: &str, content_type: &str, date: &str, key: &str) -> String {
+        let plain = format!(
+            "{}\n\n{}\n{}\n/{}/{}",
+            verb, content_type, date, self.bucket, key
+        );
+        let mut mac = Hmac::<sha1::Sha1>::new_from_slice(self.access_key_secret.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(plain.as_bytes());
+        let signature =
+            base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+        format!("OSS {}:{}", self.access_key_id, signature)
+    }
+}
+
+fn httpdate() -> String {
+    chrono::Utc::now()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
@@ -0,0 +1,222 @@
+use super::db::{
+    prelude::{TierBenefits, Users},
+    sea_orm_active_enums::UserTier,
+    tier_benefits, users,
+};
+use super::money;
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::{DateTime, Decimal};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveModelTrait, ConnectionTrait, EntityTrait, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+//设置用户的会员等级, until为空表示长期有效
+#[derive(Debug, Deserialize, Clone)]
+pub struct TierAssign {
+    pub user_id: Uuid,
+    pub tier: UserTier,
+    pub until: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TierBenefitSet {
+    pub tier: UserTier,
+    pub extra_advance_days: i32,
+    pub discount_percent: Decimal,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TierSchema {
+    pub user_id: Uuid,
+    pub tier: UserTier,
+    pub tier_until: Option<DateTime>,
+}
+
+pub struct TierOp;
+impl TierOp {
+    //用户当前等级的权益, 未设置时为None
+    pub async fn benefit<T, C: ConnectionTrait>(
+        user: &users::Model,
+        db: &C,
+    ) -> Result<Option<tier_benefits::Model>, HandleErr<T>> {
+        TierBenefits::find_by_id(effective(user, chrono::Utc::now().naive_utc()))
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    //按用户id查询权益
+    pub async fn benefit_of<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<Option<tier_benefits::Model>, HandleErr<T>> {
+        let user = Users::find_by_id(user_id).one(db).await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        match user {
+            Some(user) => Self::benefit(&user, db).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn assign<T: From<&'static str>>(
+        schema: TierAssign,
+        state: &AppState,
+    ) -> Result<TierSchema, HandleErr<T>> {
+        let now = chrono::Utc::now().naive_utc();
+        if schema.until.is_some_and(|e| e <= now) {
+            return Err(HandleErr::BadRequest(-1, "到期时间须晚于当前时间".into()));
+        }
+        let user = Users::find_by_id(schema.user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.deleted_time.is_none())
+            .ok_or(HandleErr::BadRequest(-1, "用户不存在".into()))?;
+        //普通用户没有到期时间
+        let until = match schema.tier {
+            UserTier::Normal => None,
+            _ => schema.until,
+        };
+        let user = users::ActiveModel {
+            user_id: Set(user.user_id),
+            tier: Set(schema.tier),
+            tier_until: Set(until),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "用户({})会员等级设为{:?}, 到期时间: {:?}",
+            user.user_id, user.tier, user.tier_until
+        );
+        Ok(TierSchema {
+            user_id: user.user_id,
+            tier: user.tier,
+            tier_until: user.tier_until,
+        })
+    }
+
+    pub async fn benefits<T>(state: &AppState) -> Result<Vec<tier_benefits::Model>, HandleErr<T>> {
+        TierBenefits::find()
+            .order_by_asc(tier_benefits::Column::Tier)
+            .all(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })
+    }
+
+    pub async fn set_benefit<T: From<&'static str>>(
+        schema: TierBenefitSet,
+        state: &AppState,
+    ) -> Result<TierBenefitSet, HandleErr<T>> {
+        if !(0..=365).contains(&schema.extra_advance_days)
+            || !(Decimal::ZERO..=Decimal::from(50)).contains(&schema.discount_percent)
+        {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "增加的提前天数应在0~365, 折扣应在0~50%之间".into(),
+            ));
+        }
+        TierBenefits::insert(tier_benefits::ActiveModel {
+            tier: Set(schema.tier.clone()),
+            extra_advance_days: Set(schema.extra_advance_days),
+            discount_percent: Set(money::round(schema.discount_percent)),
+            update_time: Set(chrono::Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::column(tier_benefits::Column::Tier)
+                .update_columns([
+                    tier_benefits::Column::ExtraAdvanceDays,
+                    tier_benefits::Column::DiscountPercent,
+                    tier_benefits::Column::UpdateTime,
+                ])
+                .to_owned(),
+        )
+        .exec(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(schema)
+    }
+}
+
+//当前生效的等级, 已到期的按普通用户处理
+pub fn effective(user: &users::Model, now: DateTime) -> UserTier {
+    match user.tier_until {
+        Some(until) if until <= now => UserTier::Normal,
+        _ => user.tier.clone(),
+    }
+}
+
+#[test]
+fn test_effective() {
+    let now = chrono::Utc::now().naive_utc();
+    let user = |tier, tier_until| users::Model {
+        user_id: Uuid::nil(),
+        user_name: String::new(),
+        user_pwd: String::new(),
+        hash_version: 2,
+        phone: None,
+        is_admin: false,
+        is_super: false,
+        staff_of: None,
+        staff_scopes: vec![],
+        no_show_count: 0,
+        banned_until: None,
+        notify_reminder: true,
+        openid: None,
+        unionid: None,
+        nickname: None,
+        avatar_url: None,
+        gender: Default::default(),
+        preferred_sports: vec![],
+        tier,
+        tier_until,
+        deleted_time: None,
+        disabled_time: None,
+        failed_logins: 0,
+        locked_until: None,
+        totp_secret: None,
+        totp_enabled: false,
+        totp_last_step: 0,
+        totp_backup_codes: vec![],
+    };
+    let hour = chrono::Duration::hours(1);
+    assert_eq!(effective(&user(UserTier::Vip, None), now), UserTier::Vip);
+    assert_eq!(
+        effective(&user(UserTier::Vip, Some(now + hour)), now),
+        UserTier::Vip
+    );
+    assert_eq!(
+        effective(&user(UserTier::Vip, Some(now - hour)), now),
+        UserTier::Normal
+    );
+    assert_eq!(
+        effective(&user(UserTier::Normal, None), now),
+        UserTier::Normal
+    );
+}
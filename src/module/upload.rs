@@ -0,0 +1,223 @@
+use crate::{appstate::AppState, error::HandleErr};
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use tracing::{error, info};
+use uuid::Uuid;
+
+//图片的最大边长, 防止解码时占用过多内存
+const MAX_DIMENSION: u32 = 4096;
+
+//图片用途, 决定存放目录, 大小上限与缩略图尺寸
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadPurpose {
+    Avatar,
+    Court,
+    Feedback,
+}
+
+impl UploadPurpose {
+    fn dir(&self) -> &'static str {
+        match self {
+            UploadPurpose::Avatar => "avatar",
+            UploadPurpose::Court => "court",
+            UploadPurpose::Feedback => "feedback",
+        }
+    }
+
+    //文件大小上限, 字节
+    fn max_size(&self) -> usize {
+        match self {
+            UploadPurpose::Avatar => 2 * 1024 * 1024,
+            UploadPurpose::Court => 10 * 1024 * 1024,
+            UploadPurpose::Feedback => 5 * 1024 * 1024,
+        }
+    }
+
+    //缩略图最大边长
+    fn thumbnail_size(&self) -> u32 {
+        match self {
+            UploadPurpose::Avatar => 132,
+            UploadPurpose::Court | UploadPurpose::Feedback => 400,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct UploadSchema {
+    pub url: String,
+    pub thumbnail_url: String,
+    pub width: u32,
+    pub height: u32,
+    pub size: usize,
+}
+
+pub struct UploadOp;
+impl UploadOp {
+    //校验并保存图片, 同时生成缩略图, 返回访问地址
+    pub async fn image<T: From<String>>(
+        user_id: Uuid,
+        purpose: UploadPurpose,
+        data: Vec<u8>,
+        state: &AppState,
+    ) -> Result<UploadSchema, HandleErr<T>> {
+        let size = data.len();
+        let (data, (format, width, height, thumbnail)) =
+            blocking(move || process(&data, purpose).map(|e| (data, e))).await?;
+        let (content_type, ext) = mime(format);
+        let name = Uuid::new_v4();
+        let key = format!("upload/{}/{}/{}.{}", purpose.dir(), user_id, name, ext);
+        let thumbnail_key = format!(
+            "upload/{}/{}/{}_thumb.{}",
+            purpose.dir(),
+            user_id,
+            name,
+            ext
+        );
+        let url = state
+            .storage
+            .put(&key, content_type, data)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        let thumbnail_url = state
+            .storage
+            .put(&thumbnail_key, content_type, thumbnail)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        info!("用户({})上传图片: {}", user_id, key);
+        Ok(UploadSchema {
+            url,
+            thumbnail_url,
+            width,
+            height,
+            size,
+        })
+    }
+
+    //只校验不保存, 返回按文件内容确定的Content-Type与扩展名, 供自行决定存放位置的上传使用
+    pub async fn validate<T: From<String>>(
+        data: Vec<u8>,
+        purpose: UploadPurpose,
+    ) -> Result<(Vec<u8>, &'static str, &'static str), HandleErr<T>> {
+        let (data, format) =
+            blocking(move || decode(&data, purpose).map(|(e, _)| (data, e))).await?;
+        let (content_type, ext) = mime(format);
+        Ok((data, content_type, ext))
+    }
+}
+
+//解码较耗CPU与内存, 放到阻塞线程池中执行, 不占用异步运行时的工作线程
+async fn blocking<R, T>(
+    f: impl FnOnce() -> Result<R, String> + Send + 'static,
+) -> Result<R, HandleErr<T>>
+where
+    R: Send + 'static,
+    T: From<String>,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .map_err(|e| HandleErr::BadRequest(-1, e.into()))
+}
+
+fn mime(format: ImageFormat) -> (&'static str, &'static str) {
+    match format {
+        ImageFormat::Png => ("image/png", "png"),
+        _ => ("image/jpeg", "jpg"),
+    }
+}
+
+//按文件内容识别格式, 只接受JPEG与PNG
+fn decode(data: &[u8], purpose: UploadPurpose) -> Result<(ImageFormat, DynamicImage), String> {
+    if data.is_empty() {
+        return Err("文件为空".into());
+    }
+    if data.len() > purpose.max_size() {
+        return Err(format!(
+            "图片不能超过{}MB",
+            purpose.max_size() / 1024 / 1024
+        ));
+    }
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| "无法识别的图片".to_string())?;
+    let format = reader
+        .format()
+        .filter(|e| matches!(e, ImageFormat::Jpeg | ImageFormat::Png))
+        .ok_or("仅支持JPEG/PNG图片")?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+    let image = reader
+        .decode()
+        .map_err(|_| "图片已损坏或尺寸过大".to_string())?;
+    Ok((format, image))
+}
+
+//校验后生成同格式的缩略图, 返回格式, 宽高与缩略图
+fn process(
+    data: &[u8],
+    purpose: UploadPurpose,
+) -> Result<(ImageFormat, u32, u32, Vec<u8>), String> {
+    let (format, image) = decode(data, purpose)?;
+    //小图不放大
+    let max = purpose.thumbnail_size();
+    let thumbnail = if image.width() <= max && image.height() <= max {
+        image.clone()
+    } else {
+        image.thumbnail(max, max)
+    };
+    let mut buf = Cursor::new(vec![]);
+    match format {
+        ImageFormat::Png => thumbnail.write_to(&mut buf, ImageFormat::Png),
+        //JPEG不支持透明通道
+        _ => DynamicImage::ImageRgb8(thumbnail.to_rgb8()).write_to(&mut buf, ImageFormat::Jpeg),
+    }
+    .map_err(|err| err.to_string())?;
+    Ok((format, image.width(), image.height(), buf.into_inner()))
+}
+
+#[test]
+fn test_process() {
+    let mut png = Cursor::new(vec![]);
+    image::DynamicImage::new_rgba8(800, 600)
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+    let (format, width, height, thumbnail) = process(&png, UploadPurpose::Court).unwrap();
+    assert_eq!(format, ImageFormat::Png);
+    assert_eq!((width, height), (800, 600));
+    let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (400, 300));
+    let (_, _, _, thumbnail) = process(&png, UploadPurpose::Avatar).unwrap();
+    assert_eq!(image::load_from_memory(&thumbnail).unwrap().width(), 132);
+    let mut jpeg = Cursor::new(vec![]);
+    image::DynamicImage::new_rgb8(100, 80)
+        .write_to(&mut jpeg, ImageFormat::Jpeg)
+        .unwrap();
+    let (format, _, _, thumbnail) = process(&jpeg.into_inner(), UploadPurpose::Court).unwrap();
+    assert_eq!(format, ImageFormat::Jpeg);
+    assert_eq!(image::load_from_memory(&thumbnail).unwrap().width(), 100);
+    assert!(process(b"GIF89a", UploadPurpose::Court).is_err());
+    assert!(process(&[], UploadPurpose::Court).is_err());
+    assert!(process(&vec![0; 3 * 1024 * 1024], UploadPurpose::Avatar).is_err());
+    let mut large = Cursor::new(vec![]);
+    DynamicImage::new_luma8(MAX_DIMENSION + 1, 1)
+        .write_to(&mut large, ImageFormat::Png)
+        .unwrap();
+    assert!(decode(&large.into_inner(), UploadPurpose::Court).is_err());
+}
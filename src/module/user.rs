@@ -1,16 +1,125 @@
-use super::db::{self, prelude::Users};
-use crate::{appstate::AppState, error::HandleErr, utils::passwd};
-use sea_orm::{EntityTrait, Set};
+use super::db::{
+    self,
+    prelude::Users,
+    sea_orm_active_enums::{Gender, SportType, StaffScope, UserTier},
+};
+use super::{referral::ReferralOp, session::SessionOp, wechat::Session};
+use crate::{
+    appstate::AppState,
+    cfg::LoginCfg,
+    error::{unique_err, HandleErr},
+    utils::passwd,
+};
+use sea_orm::prelude::DateTime;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 #[derive(Debug, Deserialize, Serialize, Clone, sea_orm::FromQueryResult)]
 pub struct UserSchema {
     pub user_id: Uuid,
     pub user_name: String,
     // pub pwd: String,
-    pub phone: String,
+    pub phone: Option<String>,
     pub is_admin: bool,
+    pub is_super: bool,
+    pub staff_of: Option<Uuid>,
+    pub staff_scopes: Vec<StaffScope>,
+    pub no_show_count: i32,
+    pub banned_until: Option<sea_orm::prelude::DateTime>,
+    pub notify_reminder: bool,
+    #[serde(skip_serializing)]
+    pub openid: Option<String>,
+    pub tier: UserTier,
+    pub tier_until: Option<sea_orm::prelude::DateTime>,
+    pub totp_enabled: bool,
+}
+
+impl UserSchema {
+    pub fn role(&self) -> Role {
+        role(self.is_super, self.is_admin, self.staff_of.is_some())
+    }
+
+    //管理员拥有全部范围, 员工只拥有被授予的范围
+    pub fn has_scope(&self, scope: &StaffScope) -> bool {
+        match self.role() {
+            Role::Admin | Role::Super => true,
+            Role::Staff => self.staff_scopes.contains(scope),
+            Role::User => false,
+        }
+    }
+}
+
+//角色, 由低到高, 高级角色拥有低级角色的全部权限
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    User,
+    //场馆员工, 隶属于某个场馆管理员
+    Staff,
+    //场馆管理员, 只能管理自己名下的场馆
+    Admin,
+    //超级管理员, 可查看全部场馆
+    Super,
+}
+
+impl Role {
+    pub fn of(user: &db::users::Model) -> Role {
+        role(user.is_super, user.is_admin, user.staff_of.is_some())
+    }
+}
+
+//wx.login得到的code
+#[derive(Debug, Deserialize)]
+pub struct WechatBind {
+    pub code: String,
+}
+
+//getPhoneNumber得到的code
+#[derive(Debug, Deserialize)]
+pub struct PhoneBind {
+    pub code: String,
+}
+
+//通知偏好
+#[derive(Debug, Deserialize)]
+pub struct NotifyPreference {
+    pub reminder: bool,
+}
+
+//个人资料
+#[derive(Debug, Serialize, Clone, sea_orm::FromQueryResult)]
+pub struct UserProfile {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub gender: Gender,
+    pub preferred_sports: Vec<SportType>,
+}
+
+//修改个人资料, 未提供的字段保持不变, 昵称与头像传空字符串时清除
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProfileUpdate {
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub gender: Option<Gender>,
+    pub preferred_sports: Option<Vec<SportType>>,
+}
+
+//小程序登录, wx.login得到的code
+#[derive(Debug, Deserialize)]
+pub struct WechatLogin {
+    pub code: String,
+    //邀请码, 仅首次登录时生效
+    #[serde(default)]
+    pub referral_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,31 +127,64 @@ pub struct UserRegisterSchema {
     pub name: String,
     pub pwd: String,
     pub phone: String,
-    pub is_admin: bool,
+    //邀请码, 选填
+    #[serde(default)]
+    pub referral_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UserLoginSchema {
     pub name: String,
     pub pwd: String,
+    //已开启二次验证的账号须填写
+    #[serde(default)]
+    pub otp: Option<String>,
+}
+
+//封禁/解封账号
+#[derive(Debug, Deserialize)]
+pub struct UserDisable {
+    pub user_id: Uuid,
+}
+
+//授予/撤销管理员身份
+#[derive(Debug, Deserialize)]
+pub struct UserGrant {
+    pub user_id: Uuid,
+    pub is_admin: bool,
+}
+
+//解除登录失败锁定
+#[derive(Debug, Deserialize)]
+pub struct UserUnlock {
+    pub user_id: Uuid,
 }
 
 pub struct UserOP;
 
 impl UserOP {
+    //注册与绑定邀请人在同一事务中完成
     pub async fn register_new_user<T>(
         schema: UserRegisterSchema,
+        inviter: Option<Uuid>,
         state: &AppState,
     ) -> Result<Uuid, HandleErr<T>> {
         let password_hash = passwd::hash_password(&schema.pwd)?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
         let id = Users::insert(db::users::ActiveModel {
             user_name: Set(schema.name),
             user_pwd: Set(password_hash),
-            phone: Set(schema.phone),
-            is_admin: Set(schema.is_admin),
+            hash_version: Set(passwd::HASH_VERSION),
+            phone: Set(Some(schema.phone)),
+            //注册的账号均为普通用户, 管理员由超级管理员授予
+            is_admin: Set(false),
             ..Default::default()
         })
-        .exec(&state.db)
+        .exec(&txn)
         .await
         .map_err(|err| {
             let id = Uuid::new_v4();
@@ -50,6 +192,580 @@ impl UserOP {
             HandleErr::ServerInnerErr(id)
         })?
         .last_insert_id;
+        if let Some(inviter) = inviter {
+            ReferralOp::bind(id, inviter, &txn).await?;
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
         Ok(id)
     }
+
+    //按openid查找微信用户, 首次登录时创建并绑定邀请人, 返回用户、是否新建与是否绑定了邀请人
+    //并发的首次登录以openid唯一约束去重
+    pub async fn wechat_login<T>(
+        session: &Session,
+        inviter: Option<Uuid>,
+        state: &AppState,
+    ) -> Result<(db::users::Model, bool, bool), HandleErr<T>> {
+        let user_id = Uuid::new_v4();
+        //随机密码, 微信用户不能用密码登录
+        let password_hash = passwd::hash_password(&Uuid::new_v4().to_string())?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let rows_affected = Users::insert(db::users::ActiveModel {
+            user_id: Set(user_id),
+            user_name: Set(wechat_user_name(user_id)),
+            user_pwd: Set(password_hash),
+            hash_version: Set(passwd::HASH_VERSION),
+            phone: Set(None),
+            is_admin: Set(false),
+            openid: Set(Some(session.openid.clone())),
+            unionid: Set(session.unionid.clone()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(db::users::Column::Openid)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let created = rows_affected > 0;
+        let referred = match inviter {
+            Some(inviter) if created => ReferralOp::bind(user_id, inviter, &txn).await?,
+            _ => false,
+        };
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let user = Users::find()
+            .filter(db::users::Column::Openid.eq(&session.openid))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or_else(|| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> 微信用户({})创建后未找到", id, session.openid);
+                HandleErr::ServerInnerErr(id)
+            })?;
+        //小程序后来绑定开放平台时补全unionid
+        if user.unionid.is_none() && session.unionid.is_some() {
+            let user = db::users::ActiveModel {
+                user_id: Set(user.user_id),
+                unionid: Set(session.unionid.clone()),
+                ..Default::default()
+            }
+            .update(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+            return Ok((user, created, referred));
+        }
+        Ok((user, created, referred))
+    }
+
+    //封禁或解封账号, 超级管理员不能被封禁
+    //封禁时撤销全部会话, 已签发的token立即失效
+    //身份变更后撤销该用户的全部会话, 重新登录后生效
+    pub async fn grant<T: From<String>>(
+        schema: UserGrant,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let user = Users::find_by_id(schema.user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.deleted_time.is_none())
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string().into()))?;
+        if user.is_super || user.staff_of.is_some() {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "不能变更超级管理员或员工的身份".to_string().into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        db::users::ActiveModel {
+            user_id: Set(user.user_id),
+            is_admin: Set(schema.is_admin),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        SessionOp::revoke_all(user.user_id, None, &txn).await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    pub async fn disable<T>(
+        user_id: Uuid,
+        disabled: bool,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>>
+    where
+        T: From<String>,
+    {
+        let user = Users::find_by_id(user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .filter(|e| e.deleted_time.is_none())
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string().into()))?;
+        if disabled && user.is_super {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "不能封禁超级管理员".to_string().into(),
+            ));
+        }
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        db::users::ActiveModel {
+            user_id: Set(user_id),
+            disabled_time: Set(disabled.then(|| chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        if disabled {
+            let revoked = SessionOp::revoke_all(user_id, None, &txn).await?;
+            info!("用户({})被封禁, 撤销{}个会话", user_id, revoked);
+        }
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Ok(())
+    }
+
+    //密码登录失败, 连续失败达到上限时锁定账号, 返回锁定截止时间
+    //失败次数在数据库中原子递增, 并发的错误尝试不会相互覆盖
+    pub async fn login_failed<T>(
+        user: &db::users::Model,
+        ip: &str,
+        state: &AppState,
+    ) -> Result<Option<DateTime>, HandleErr<T>> {
+        let cfg = &state.cfg.logincfg;
+        state.login_failures.record(ip, cfg, Instant::now());
+        let failed = Users::update_many()
+            .col_expr(
+                db::users::Column::FailedLogins,
+                Expr::col(db::users::Column::FailedLogins).add(1),
+            )
+            .filter(db::users::Column::UserId.eq(user.user_id))
+            .exec_with_returning(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .first()
+            .map_or(0, |e| e.failed_logins);
+        let locked_until = lockout(failed, cfg, chrono::Utc::now().naive_utc());
+        if let Some(until) = locked_until {
+            //只有计数仍达到上限时才锁定, 并发请求已锁定并清零的不再重复
+            Users::update_many()
+                .col_expr(db::users::Column::FailedLogins, Expr::value(0))
+                .col_expr(db::users::Column::LockedUntil, Expr::value(until))
+                .filter(db::users::Column::UserId.eq(user.user_id))
+                .filter(db::users::Column::FailedLogins.gte(cfg.max_failures))
+                .exec(&state.db)
+                .await
+                .map_err(|err| {
+                    let id = Uuid::new_v4();
+                    error!("{} >>>> {}", id, err.to_string());
+                    HandleErr::ServerInnerErr(id)
+                })?;
+        }
+        match locked_until {
+            Some(until) => warn!(
+                "用户({})密码连续错误, 来自{}, 锁定至{}",
+                user.user_name, ip, until
+            ),
+            None => warn!(
+                "用户({})密码错误, 来自{}, 连续{}次",
+                user.user_name, ip, failed
+            ),
+        }
+        Ok(locked_until)
+    }
+
+    //密码登录成功后清零失败次数
+    pub async fn login_succeeded<T: From<&'static str>>(
+        user: &db::users::Model,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        if user.failed_logins == 0 && user.locked_until.is_none() {
+            return Ok(());
+        }
+        Self::unlock(user.user_id, state).await
+    }
+
+    //登录成功后用明文密码按当前算法重新散列, 逐步淘汰旧版本的散列
+    pub async fn rehash<T>(user_id: Uuid, pwd: &str, state: &AppState) -> Result<(), HandleErr<T>> {
+        db::users::ActiveModel {
+            user_id: Set(user_id),
+            user_pwd: Set(passwd::hash_password(pwd)?),
+            hash_version: Set(passwd::HASH_VERSION),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})的密码散列已升级", user_id);
+        Ok(())
+    }
+
+    //解除锁定并清零失败次数
+    pub async fn unlock<T: From<&'static str>>(
+        user_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        let rows_affected = Users::update_many()
+            .col_expr(db::users::Column::FailedLogins, Expr::value(0))
+            .col_expr(
+                db::users::Column::LockedUntil,
+                Expr::value(Option::<DateTime>::None),
+            )
+            .filter(db::users::Column::UserId.eq(user_id))
+            .exec(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .rows_affected;
+        if rows_affected == 0 {
+            return Err(HandleErr::BadRequest(-1, "未发现用户".into()));
+        }
+        Ok(())
+    }
+
+    //绑定手机号, 已被其他账号使用时拒绝
+    pub async fn bind_phone<T>(
+        user_id: Uuid,
+        phone: String,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>>
+    where
+        T: From<String> + From<&'static str>,
+    {
+        let owner = Users::find()
+            .filter(db::users::Column::Phone.eq(&phone))
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        match owner {
+            Some(owner) if owner.user_id == user_id => return Ok(()),
+            Some(_) => {
+                return Err(HandleErr::BadRequest(
+                    -1,
+                    "该手机号已绑定其他账号".to_string().into(),
+                ))
+            }
+            None => {}
+        }
+        db::users::ActiveModel {
+            user_id: Set(user_id),
+            phone: Set(Some(phone)),
+            ..Default::default()
+        }
+        .update(&state.db)
+        .await
+        //并发绑定同一手机号时由唯一约束拦截
+        .map_err(|err| unique_err(err, "该手机号已绑定其他账号"))?;
+        Ok(())
+    }
+
+    pub async fn profile<T>(user_id: Uuid, state: &AppState) -> Result<UserProfile, HandleErr<T>>
+    where
+        T: From<String>,
+    {
+        Users::find_by_id(user_id)
+            .into_model::<UserProfile>()
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "未发现用户".to_string().into()))
+    }
+
+    //只更新提供的字段, 返回更新后的资料
+    pub async fn update_profile<T>(
+        user_id: Uuid,
+        schema: ProfileUpdate,
+        state: &AppState,
+    ) -> Result<UserProfile, HandleErr<T>>
+    where
+        T: From<String>,
+    {
+        let schema = normalize_profile(schema)
+            .map_err(|err| HandleErr::BadRequest(-1, err.to_string().into()))?;
+        let mut changed = false;
+        let mut model = db::users::ActiveModel {
+            user_id: Set(user_id),
+            ..Default::default()
+        };
+        if let Some(nickname) = schema.nickname {
+            model.nickname = Set((!nickname.is_empty()).then_some(nickname));
+            changed = true;
+        }
+        if let Some(avatar_url) = schema.avatar_url {
+            model.avatar_url = Set((!avatar_url.is_empty()).then_some(avatar_url));
+            changed = true;
+        }
+        if let Some(gender) = schema.gender {
+            model.gender = Set(gender);
+            changed = true;
+        }
+        if let Some(sports) = schema.preferred_sports {
+            model.preferred_sports = Set(sports);
+            changed = true;
+        }
+        if changed {
+            model.update(&state.db).await.map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        }
+        Self::profile(user_id, state).await
+    }
+}
+
+//微信用户的默认用户名
+fn wechat_user_name(user_id: Uuid) -> String {
+    format!("wx_{}", &user_id.simple().to_string()[..12])
+}
+
+//校验并整理个人资料: 去掉首尾空白, 运动类型去重
+fn normalize_profile(mut schema: ProfileUpdate) -> Result<ProfileUpdate, &'static str> {
+    if let Some(nickname) = &mut schema.nickname {
+        *nickname = nickname.trim().to_string();
+        if nickname.chars().count() > 20 {
+            return Err("昵称不能超过20字");
+        }
+        if nickname.chars().any(char::is_control) {
+            return Err("昵称包含非法字符");
+        }
+    }
+    if let Some(avatar_url) = &mut schema.avatar_url {
+        *avatar_url = avatar_url.trim().to_string();
+        if avatar_url.len() > 255 {
+            return Err("头像地址过长");
+        }
+        if !avatar_url.is_empty()
+            && !avatar_url.starts_with("https://")
+            && !avatar_url.starts_with("http://")
+        {
+            return Err("头像地址应为http(s)链接");
+        }
+    }
+    if let Some(sports) = &mut schema.preferred_sports {
+        let mut distinct = vec![];
+        for sport in sports.drain(..) {
+            if !distinct.contains(&sport) {
+                distinct.push(sport);
+            }
+        }
+        *sports = distinct;
+    }
+    Ok(schema)
+}
+
+//登录失败后的连续失败次数与锁定截止时间, 达到上限时锁定并清零次数
+//递增后的失败次数达到上限时返回锁定截止时间
+fn lockout(failed: i32, cfg: &LoginCfg, now: DateTime) -> Option<DateTime> {
+    (cfg.max_failures > 0 && failed >= cfg.max_failures)
+        .then(|| now + chrono::Duration::minutes(cfg.lock_minutes))
+}
+
+//按ip统计的密码登录失败, ip → (窗口内失败次数, 窗口开始时间)
+//不区分账号, 防止同一来源轮换用户名撞库
+#[derive(Debug, Clone, Default)]
+pub struct LoginFailures(Arc<Mutex<HashMap<String, (i32, Instant)>>>);
+
+impl LoginFailures {
+    fn window(cfg: &LoginCfg) -> Duration {
+        Duration::from_secs(cfg.lock_minutes.max(1) as u64 * 60)
+    }
+
+    //窗口内失败次数已达上限
+    pub fn blocked(&self, ip: &str, cfg: &LoginCfg, now: Instant) -> bool {
+        if cfg.ip_max_failures <= 0 {
+            return false;
+        }
+        let failures = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        failures.get(ip).is_some_and(|(count, start)| {
+            now.duration_since(*start) < Self::window(cfg) && *count >= cfg.ip_max_failures
+        })
+    }
+
+    //记录一次失败, 返回窗口内的失败次数
+    pub fn record(&self, ip: &str, cfg: &LoginCfg, now: Instant) -> i32 {
+        let window = Self::window(cfg);
+        let mut failures = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        //新来源加入时清理过期窗口, 防止表无限增长
+        if !failures.contains_key(ip) && failures.len() > 10000 {
+            failures.retain(|_, (_, start)| now.duration_since(*start) < window);
+        }
+        let (count, start) = failures.entry(ip.to_string()).or_insert((0, now));
+        if now.duration_since(*start) >= window {
+            *count = 0;
+            *start = now;
+        }
+        *count += 1;
+        *count
+    }
+}
+
+fn role(is_super: bool, is_admin: bool, is_staff: bool) -> Role {
+    if is_super {
+        Role::Super
+    } else if is_admin {
+        Role::Admin
+    } else if is_staff {
+        Role::Staff
+    } else {
+        Role::User
+    }
+}
+
+#[test]
+fn test_normalize_profile() {
+    let schema = normalize_profile(ProfileUpdate {
+        nickname: Some("  小明 ".to_string()),
+        avatar_url: Some("".to_string()),
+        preferred_sports: Some(vec![
+            SportType::Tennis,
+            SportType::Badminton,
+            SportType::Tennis,
+        ]),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(schema.nickname.as_deref(), Some("小明"));
+    assert_eq!(schema.avatar_url.as_deref(), Some(""));
+    assert!(schema.gender.is_none());
+    assert_eq!(
+        schema.preferred_sports,
+        Some(vec![SportType::Tennis, SportType::Badminton])
+    );
+    let long = ProfileUpdate {
+        nickname: Some("一".repeat(21)),
+        ..Default::default()
+    };
+    assert!(normalize_profile(long).is_err());
+    let ftp = ProfileUpdate {
+        avatar_url: Some("ftp://example.com/a.png".to_string()),
+        ..Default::default()
+    };
+    assert!(normalize_profile(ftp).is_err());
+}
+
+#[test]
+fn test_role() {
+    assert_eq!(role(true, true, false), Role::Super);
+    assert_eq!(role(false, true, true), Role::Admin);
+    assert_eq!(role(false, false, true), Role::Staff);
+    assert_eq!(role(false, false, false), Role::User);
+    assert!(Role::Super > Role::Admin && Role::Admin > Role::Staff && Role::Staff > Role::User);
+}
+
+#[test]
+fn test_lockout() {
+    let now = chrono::Utc::now().naive_utc();
+    let cfg = LoginCfg {
+        max_failures: 3,
+        lock_minutes: 15,
+        ip_max_failures: 2,
+    };
+    assert_eq!(lockout(1, &cfg, now), None);
+    assert_eq!(lockout(2, &cfg, now), None);
+    assert_eq!(
+        lockout(3, &cfg, now),
+        Some(now + chrono::Duration::minutes(15))
+    );
+    //并发请求递增超过上限时同样锁定
+    assert!(lockout(4, &cfg, now).is_some());
+    let off = LoginCfg {
+        max_failures: 0,
+        lock_minutes: 15,
+        ip_max_failures: 0,
+    };
+    assert_eq!(lockout(100, &off, now), None);
+
+    let failures = LoginFailures::default();
+    let start = Instant::now();
+    assert!(!failures.blocked("203.0.113.7", &cfg, start));
+    assert_eq!(failures.record("203.0.113.7", &cfg, start), 1);
+    assert_eq!(failures.record("203.0.113.7", &cfg, start), 2);
+    assert!(failures.blocked("203.0.113.7", &cfg, start));
+    assert!(!failures.blocked("203.0.113.8", &cfg, start));
+    assert!(!failures.blocked("203.0.113.7", &off, start));
+    //窗口过后重新计数
+    let later = start + Duration::from_secs(15 * 60);
+    assert!(!failures.blocked("203.0.113.7", &cfg, later));
+    assert_eq!(failures.record("203.0.113.7", &cfg, later), 1);
 }
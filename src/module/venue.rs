@@ -0,0 +1,111 @@
+use super::db::{self, prelude::Venues};
+use super::notify::inbox::AnnounceTarget;
+use super::user::{Role, UserSchema};
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, EntityTrait, QueryFilter, TryIntoModel,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+//update/insert
+#[derive(Debug, Deserialize, Clone)]
+pub struct VenueSave {
+    pub venue_id: Option<Uuid>,
+    pub venue_name: String,
+    pub address: String,
+    #[serde(default)]
+    pub announcement: String,
+    //为空时不修改图片
+    pub photos: Option<Vec<String>>,
+}
+
+//发布场馆公告并按对象通知
+#[derive(Debug, Deserialize, Clone)]
+pub struct VenueAnnounce {
+    pub venue_id: Uuid,
+    pub announcement: String,
+    #[serde(default)]
+    pub target: AnnounceTarget,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VenueDel {
+    pub venue_id: Uuid,
+}
+
+pub struct VenueOp;
+impl VenueOp {
+    pub async fn save<T>(
+        admin_id: Uuid,
+        schema: VenueSave,
+        state: &AppState,
+    ) -> Result<db::venues::Model, HandleErr<T>> {
+        db::venues::ActiveModel {
+            venue_id: schema.venue_id.map(Set).unwrap_or(NotSet),
+            admin_id: Set(admin_id),
+            venue_name: Set(schema.venue_name),
+            address: Set(schema.address),
+            announcement: Set(schema.announcement),
+            photos: schema.photos.map(Set).unwrap_or(NotSet),
+        }
+        .save(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .try_into_model()
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //查询管理员名下的场馆
+    pub async fn owned<T: From<&'static str>>(
+        venue_id: Uuid,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<db::venues::Model, HandleErr<T>> {
+        Venues::find()
+            .filter(
+                db::venues::Column::VenueId
+                    .eq(venue_id)
+                    .and(db::venues::Column::AdminId.eq(admin_id)),
+            )
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "场馆不存在".into()))
+    }
+
+    //可查看的场馆, 超级管理员可查看全部场馆, 员工可查看所属管理员的场馆
+    pub async fn viewable<T: From<&'static str>>(
+        venue_id: Uuid,
+        user: &UserSchema,
+        state: &AppState,
+    ) -> Result<db::venues::Model, HandleErr<T>> {
+        if user.role() != Role::Super {
+            return Self::owned(venue_id, user.staff_of.unwrap_or(user.user_id), state).await;
+        }
+        Venues::find_by_id(venue_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "场馆不存在".into()))
+    }
+}
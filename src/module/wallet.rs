@@ -0,0 +1,424 @@
+use super::db::{
+    courts, orders,
+    prelude::{Orders, Users, WalletRecharges, WalletTransactions, Wallets},
+    sea_orm_active_enums::{LedgerKind, OrderState, PayMethod, RechargeStatus, WalletTxnKind},
+    wallet_recharges, wallet_transactions, wallets,
+};
+use super::finance::LedgerOp;
+use super::money;
+use super::order::OrderOp;
+use super::payment::{
+    notification::NotificationOp,
+    wechat::{self, Transaction},
+};
+use super::user::{Role, UserSchema};
+use crate::{appstate::AppState, error::HandleErr};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, JoinType, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{error, info};
+use uuid::Uuid;
+
+//单次充值上限, 元
+pub const MAX_RECHARGE: Decimal = Decimal::from_parts(5000, 0, 0, false, 0);
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RechargeCreate {
+    pub amount: Decimal,
+}
+
+//管理员调整余额, amount为负时扣减, remark必填作为审计记录
+#[derive(Debug, Deserialize, Clone)]
+pub struct WalletAdjust {
+    pub user_id: Uuid,
+    pub amount: Decimal,
+    pub remark: String,
+}
+
+//余额变动的来源
+pub struct Change<'a> {
+    pub kind: WalletTxnKind,
+    pub order_id: Option<Uuid>,
+    pub recharge_id: Option<Uuid>,
+    pub admin_id: Option<Uuid>,
+    pub remark: &'a str,
+}
+
+pub struct WalletOp;
+impl WalletOp {
+    //当前余额, 未开通钱包时为0
+    pub async fn balance<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        db: &C,
+    ) -> Result<Decimal, HandleErr<T>> {
+        Ok(Wallets::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .map(|e| e.balance)
+            .unwrap_or_default())
+    }
+
+    //变动余额并写入流水, 以余额充足为条件更新, 并发扣减时不会透支
+    //应在事务中调用, 与订单等业务修改一起提交
+    pub async fn change<T: From<String>, C: ConnectionTrait>(
+        user_id: Uuid,
+        amount: Decimal,
+        change: Change<'_>,
+        db: &C,
+    ) -> Result<wallet_transactions::Model, HandleErr<T>> {
+        Wallets::insert(wallets::ActiveModel {
+            user_id: Set(user_id),
+            balance: NotSet,
+            update_time: NotSet,
+        })
+        .on_conflict(
+            OnConflict::column(wallets::Column::UserId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let wallet = Wallets::update_many()
+            .col_expr(
+                wallets::Column::Balance,
+                Expr::col(wallets::Column::Balance).add(amount),
+            )
+            .col_expr(
+                wallets::Column::UpdateTime,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(
+                wallets::Column::UserId
+                    .eq(user_id)
+                    .and(wallets::Column::Balance.gte(-amount)),
+            )
+            .exec_with_returning(db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .pop()
+            .ok_or(HandleErr::BadRequest(-1, "余额不足".to_string().into()))?;
+        wallet_transactions::ActiveModel {
+            txn_id: NotSet,
+            user_id: Set(user_id),
+            kind: Set(change.kind),
+            amount: Set(amount),
+            balance: Set(wallet.balance),
+            order_id: Set(change.order_id),
+            recharge_id: Set(change.recharge_id),
+            admin_id: Set(change.admin_id),
+            remark: Set(change.remark.to_string()),
+            create_time: NotSet,
+        }
+        .insert(db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //钱包流水, 按时间倒序分页, 返回流水与总数
+    pub async fn transactions<T, C: ConnectionTrait>(
+        user_id: Uuid,
+        page: u64,
+        page_size: u64,
+        db: &C,
+    ) -> Result<(Vec<wallet_transactions::Model>, u64), HandleErr<T>> {
+        let paginator = WalletTransactions::find()
+            .filter(wallet_transactions::Column::UserId.eq(user_id))
+            .order_by_desc(wallet_transactions::Column::CreateTime)
+            .paginate(db, page_size);
+        let total = paginator.num_items().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let records = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        Ok((records, total))
+    }
+
+    //创建待支付的充值单, 支付成功通知后入账
+    pub async fn recharge<T: From<String>>(
+        user_id: Uuid,
+        amount: Decimal,
+        state: &AppState,
+    ) -> Result<wallet_recharges::Model, HandleErr<T>> {
+        if !(money::CENT..=MAX_RECHARGE).contains(&amount) {
+            return Err(HandleErr::BadRequest(
+                -1,
+                format!("充值金额应在0.01~{:.2}之间", MAX_RECHARGE).into(),
+            ));
+        }
+        wallet_recharges::ActiveModel {
+            recharge_id: NotSet,
+            user_id: Set(user_id),
+            amount: Set(money::round(amount)),
+            status: NotSet,
+            transaction_id: NotSet,
+            create_time: NotSet,
+            paid_time: NotSet,
+        }
+        .insert(&state.db)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })
+    }
+
+    //充值支付成功通知, 锁定充值单后入账, 已入账时视为重复通知
+    pub async fn recharged<T: From<String>>(
+        transaction: &Transaction,
+        state: &AppState,
+    ) -> Result<wallet_recharges::Model, HandleErr<T>> {
+        let recharge_id = Uuid::parse_str(&transaction.out_trade_no).map_err(|_| {
+            HandleErr::BadRequest(
+                -1,
+                format!("out_trade_no无效: {}", transaction.out_trade_no).into(),
+            )
+        })?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let recharge = WalletRecharges::find_by_id(recharge_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "充值单不存在".to_string().into()))?;
+        if recharge.status == RechargeStatus::Paid
+            || !NotificationOp::claim(transaction, &txn).await?
+        {
+            info!("充值单({})重复的支付通知", recharge.recharge_id);
+            return Ok(recharge);
+        }
+        //以实际支付金额入账
+        let amount = wechat::from_fen(transaction.amount.total);
+        let recharge = wallet_recharges::ActiveModel {
+            recharge_id: Set(recharge.recharge_id),
+            status: Set(RechargeStatus::Paid),
+            transaction_id: Set(Some(transaction.transaction_id.clone())),
+            paid_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        Self::change(
+            recharge.user_id,
+            amount,
+            Change {
+                kind: WalletTxnKind::Recharge,
+                order_id: None,
+                recharge_id: Some(recharge.recharge_id),
+                admin_id: None,
+                remark: "微信充值",
+            },
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("用户({})充值{:.2}元", recharge.user_id, amount);
+        Ok(recharge)
+    }
+
+    //余额支付订单, 扣款与订单变为已支付在同一事务中完成
+    //调用前应关闭微信支付单, 避免重复支付
+    pub async fn pay_order<T: From<String>>(
+        order: &orders::Model,
+        state: &AppState,
+    ) -> Result<orders::Model, HandleErr<T>> {
+        let amount = order.cost + order.deposit;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let paid = OrderOp::transit(order, OrderState::Paid, &txn).await?;
+        Self::change(
+            order.user_id,
+            -amount,
+            Change {
+                kind: WalletTxnKind::Payment,
+                order_id: Some(order.order_id),
+                recharge_id: None,
+                admin_id: None,
+                remark: "订单支付",
+            },
+            &txn,
+        )
+        .await?;
+        let paid = orders::ActiveModel {
+            order_id: Set(paid.order_id),
+            pay_amount: Set(Some(amount)),
+            pay_method: Set(Some(PayMethod::Balance)),
+            paid_time: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        OrderOp::record(
+            order.order_id,
+            "pay",
+            json!({"method":PayMethod::Balance, "amount":amount}),
+            &txn,
+        )
+        .await?;
+        LedgerOp::record(
+            &paid,
+            LedgerKind::Payment,
+            amount,
+            order.deposit,
+            Some(PayMethod::Balance),
+            state,
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!("订单({})余额支付{:.2}元", order.order_id, amount);
+        Ok(paid)
+    }
+
+    //管理员调整余额, 流水记录操作的管理员与原因
+    pub async fn adjust<T: From<String>>(
+        schema: WalletAdjust,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<wallet_transactions::Model, HandleErr<T>> {
+        let remark = schema.remark.trim();
+        if remark.is_empty() || remark.chars().count() > 200 {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "调整原因不能为空且不超过200字".to_string().into(),
+            ));
+        }
+        let amount = money::round(schema.amount);
+        if amount.is_zero() {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "调整金额不能为0".to_string().into(),
+            ));
+        }
+        if schema.user_id == admin_id {
+            return Err(HandleErr::BadRequest(
+                -1,
+                "不能调整自己的余额".to_string().into(),
+            ));
+        }
+        Users::find_by_id(schema.user_id)
+            .one(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?
+            .ok_or(HandleErr::BadRequest(-1, "用户不存在".to_string().into()))?;
+        let txn = state.db.begin().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        let record = Self::change(
+            schema.user_id,
+            amount,
+            Change {
+                kind: WalletTxnKind::Adjust,
+                order_id: None,
+                recharge_id: None,
+                admin_id: Some(admin_id),
+                remark,
+            },
+            &txn,
+        )
+        .await?;
+        txn.commit().await.map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?;
+        info!(
+            "管理员({})调整用户({})余额{:.2}元: {}",
+            admin_id, schema.user_id, amount, remark
+        );
+        Ok(record)
+    }
+    //管理员只能查看在其球场下过单的用户, 超级管理员不限
+    pub async fn visible<T: From<String>>(
+        user_id: Uuid,
+        viewer: &UserSchema,
+        admin_id: Uuid,
+        state: &AppState,
+    ) -> Result<(), HandleErr<T>> {
+        if viewer.role() == Role::Super {
+            return Ok(());
+        }
+        let count = Orders::find()
+            .join(JoinType::InnerJoin, orders::Relation::Courts.def())
+            .filter(orders::Column::UserId.eq(user_id))
+            .filter(courts::Column::AdminId.eq(admin_id))
+            .count(&state.db)
+            .await
+            .map_err(|err| {
+                let id = Uuid::new_v4();
+                error!("{} >>>> {}", id, err.to_string());
+                HandleErr::ServerInnerErr(id)
+            })?;
+        if count == 0 {
+            return Err(HandleErr::BadRequest(-1, "用户不存在".to_string().into()));
+        }
+        Ok(())
+    }
+}
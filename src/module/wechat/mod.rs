@@ -0,0 +1,290 @@
+use crate::cfg::{Cfg, PaymentCfg};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+const JSCODE2SESSION: &str = "https://api.weixin.qq.com/sns/jscode2session";
+const STABLE_TOKEN: &str = "https://api.weixin.qq.com/cgi-bin/stable_token";
+const GET_PHONE_NUMBER: &str = "https://api.weixin.qq.com/wxa/business/getuserphonenumber";
+const GET_WXACODE: &str = "https://api.weixin.qq.com/wxa/getwxacodeunlimit";
+const SUBSCRIBE_SEND: &str = "https://api.weixin.qq.com/cgi-bin/message/subscribe/send";
+//用户未授权或授权次数已用完
+const ERR_NO_QUOTA: i64 = 43101;
+//接口调用凭证提前刷新的秒数
+const TOKEN_MARGIN_SECS: u64 = 300;
+
+//wx.login得到的code换取的登录态
+#[derive(Debug, Clone, Deserialize)]
+pub struct Session {
+    pub openid: String,
+    //小程序绑定微信开放平台后才会返回
+    pub unionid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionResp {
+    #[serde(flatten)]
+    session: Option<Session>,
+    errcode: Option<i64>,
+    errmsg: Option<String>,
+}
+
+//接口出错时返回的内容
+#[derive(Debug, Deserialize)]
+struct ErrResp {
+    errcode: Option<i64>,
+    errmsg: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResp {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+    errcode: Option<i64>,
+    errmsg: Option<String>,
+}
+
+//getPhoneNumber得到的手机号
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PhoneInfo {
+    //不带区号的手机号
+    pure_phone_number: String,
+    country_code: String,
+}
+
+impl PhoneInfo {
+    fn full(self) -> String {
+        if self.country_code == "86" {
+            self.pure_phone_number
+        } else {
+            format!("+{}{}", self.country_code, self.pure_phone_number)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PhoneResp {
+    phone_info: Option<PhoneInfo>,
+    errcode: Option<i64>,
+    errmsg: Option<String>,
+}
+
+//小程序服务端接口
+#[derive(Debug, Clone)]
+pub struct Wechat {
+    client: reqwest::Client,
+    appid: String,
+    secret: String,
+    //接口调用凭证与过期时间
+    token: Arc<RwLock<Option<(String, Instant)>>>,
+}
+
+impl Wechat {
+    //优先使用小程序登录配置, 其次使用微信支付配置, 都未配置时不可用
+    pub fn new(cfg: &Cfg) -> Option<Self> {
+        let (appid, secret) = match (&cfg.wechatcfg, &cfg.paymentcfg) {
+            (Some(e), _) => (e.appid.clone(), e.secret.clone()),
+            (None, PaymentCfg::Wechat(e)) => (e.appid.clone(), e.secret.clone()),
+            (None, PaymentCfg::Offline) => return None,
+        };
+        Some(Self {
+            client: reqwest::Client::new(),
+            appid,
+            secret,
+            token: Default::default(),
+        })
+    }
+
+    //wx.login得到的code换取openid/unionid
+    pub async fn code2session(&self, code: &str) -> crate::App::Result<Session> {
+        let resp = self
+            .client
+            .get(JSCODE2SESSION)
+            .query(&[
+                ("appid", self.appid.as_str()),
+                ("secret", self.secret.as_str()),
+                ("js_code", code),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await?
+            .json::<SessionResp>()
+            .await?;
+        resp.session.ok_or_else(|| {
+            anyhow::anyhow!(
+                "换取openid失败({}): {}",
+                resp.errcode.unwrap_or_default(),
+                resp.errmsg.unwrap_or_default()
+            )
+        })
+    }
+
+    //接口调用凭证, 过期前复用, 使用稳定版接口避免多实例刷新时互相失效
+    async fn access_token(&self) -> crate::App::Result<String> {
+        if let Some((token, expire)) = self
+            .token
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            if Instant::now() < *expire {
+                return Ok(token.clone());
+            }
+        }
+        let resp = self
+            .client
+            .post(STABLE_TOKEN)
+            .json(&json!({
+                "grant_type":"client_credential",
+                "appid":self.appid,
+                "secret":self.secret
+            }))
+            .send()
+            .await?
+            .json::<TokenResp>()
+            .await?;
+        let Some(token) = resp.access_token else {
+            return Err(anyhow::anyhow!(
+                "获取access_token失败({}): {}",
+                resp.errcode.unwrap_or_default(),
+                resp.errmsg.unwrap_or_default()
+            ));
+        };
+        let ttl = resp
+            .expires_in
+            .unwrap_or_default()
+            .saturating_sub(TOKEN_MARGIN_SECS);
+        *self.token.write().unwrap_or_else(|e| e.into_inner()) =
+            Some((token.clone(), Instant::now() + Duration::from_secs(ttl)));
+        Ok(token)
+    }
+
+    //getPhoneNumber得到的code换取用户手机号, 境外号码带+区号
+    pub async fn phone_number(&self, code: &str) -> crate::App::Result<String> {
+        let access_token = self.access_token().await?;
+        let resp = self
+            .client
+            .post(GET_PHONE_NUMBER)
+            .query(&[("access_token", access_token.as_str())])
+            .json(&json!({ "code": code }))
+            .send()
+            .await?
+            .json::<PhoneResp>()
+            .await?;
+        //凭证失效时清除缓存, 下次重新获取
+        if matches!(resp.errcode, Some(40001 | 42001)) {
+            *self.token.write().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+        let phone = resp.phone_info.ok_or_else(|| {
+            anyhow::anyhow!(
+                "获取手机号失败({}): {}",
+                resp.errcode.unwrap_or_default(),
+                resp.errmsg.unwrap_or_default()
+            )
+        })?;
+        Ok(phone.full())
+    }
+
+    //小程序码, 扫码后打开page并带上scene(最长32个字符), 返回图片内容
+    pub async fn wxacode(&self, scene: &str, page: &str) -> crate::App::Result<Vec<u8>> {
+        let access_token = self.access_token().await?;
+        let resp = self
+            .client
+            .post(GET_WXACODE)
+            .query(&[("access_token", access_token.as_str())])
+            .json(&json!({
+                "scene":scene,
+                "page":page,
+                //未发布的页面也能生成
+                "check_path":false,
+                "width":280
+            }))
+            .send()
+            .await?;
+        let is_json = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|e| e.to_str().ok())
+            .is_some_and(|e| e.contains("json"));
+        let data = resp.bytes().await?;
+        //成功时返回图片, 失败时返回json
+        if is_json {
+            let resp = serde_json::from_slice::<ErrResp>(&data)?;
+            if matches!(resp.errcode, Some(40001 | 42001)) {
+                *self.token.write().unwrap_or_else(|e| e.into_inner()) = None;
+            }
+            return Err(anyhow::anyhow!(
+                "获取小程序码失败({}): {}",
+                resp.errcode.unwrap_or_default(),
+                resp.errmsg.unwrap_or_default()
+            ));
+        }
+        Ok(data.to_vec())
+    }
+
+    //下发订阅消息, data为模板字段名到内容的映射
+    //用户没有可用的授权次数时返回false, 由调用方改用其他渠道
+    pub async fn subscribe_send(
+        &self,
+        openid: &str,
+        template_id: &str,
+        page: &str,
+        data: &[(&str, String)],
+    ) -> crate::App::Result<bool> {
+        let access_token = self.access_token().await?;
+        let data: serde_json::Map<_, _> = data
+            .iter()
+            .map(|(k, v)| (k.to_string(), json!({ "value": v })))
+            .collect();
+        let resp = self
+            .client
+            .post(SUBSCRIBE_SEND)
+            .query(&[("access_token", access_token.as_str())])
+            .json(&json!({
+                "touser":openid,
+                "template_id":template_id,
+                "page":page,
+                "data":data
+            }))
+            .send()
+            .await?
+            .json::<ErrResp>()
+            .await?;
+        match resp.errcode {
+            None | Some(0) => Ok(true),
+            Some(ERR_NO_QUOTA) => Ok(false),
+            Some(errcode) => {
+                if matches!(errcode, 40001 | 42001) {
+                    *self.token.write().unwrap_or_else(|e| e.into_inner()) = None;
+                }
+                Err(anyhow::anyhow!(
+                    "发送订阅消息失败({}): {}",
+                    errcode,
+                    resp.errmsg.unwrap_or_default()
+                ))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resp() {
+    let resp = serde_json::from_str::<SessionResp>(
+        r#"{"openid":"o6_bmjrPTlm6_2sgVt7hMZOPfL2M","session_key":"tiihtNczf5v6AKRyjwEUhQ=="}"#,
+    )
+    .unwrap();
+    let session = resp.session.unwrap();
+    assert_eq!(session.openid, "o6_bmjrPTlm6_2sgVt7hMZOPfL2M");
+    assert!(session.unionid.is_none());
+    let resp = serde_json::from_str::<SessionResp>(r#"{"errcode":40029,"errmsg":"invalid code"}"#)
+        .unwrap();
+    assert!(resp.session.is_none());
+    assert_eq!(resp.errcode, Some(40029));
+    let resp = serde_json::from_str::<PhoneResp>(
+        r#"{"errcode":0,"errmsg":"ok","phone_info":{"phoneNumber":"+8613800138000","purePhoneNumber":"13800138000","countryCode":"86","watermark":{"timestamp":1637744274,"appid":"xxxx"}}}"#,
+    )
+    .unwrap();
+    assert_eq!(resp.phone_info.unwrap().full(), "13800138000");
+}
@@ -0,0 +1,77 @@
+use crate::appstate::AppState;
+use std::{future::Future, sync::Arc, time::Duration};
+use tracing::{debug, info};
+mod order;
+mod payment;
+mod session;
+
+//启动全部定时任务
+pub fn spawn(state: Arc<AppState>) {
+    every(
+        "订单完成",
+        Duration::from_secs(600),
+        state.clone(),
+        order::finish,
+    );
+    every(
+        "押金结算",
+        Duration::from_secs(600),
+        state.clone(),
+        order::settle,
+    );
+    every(
+        "拼单超时",
+        Duration::from_secs(60),
+        state.clone(),
+        order::expire_groups,
+    );
+    every(
+        "支付超时",
+        Duration::from_secs(60),
+        state.clone(),
+        order::expire_unpaid,
+    );
+    every(
+        "锁定清理",
+        Duration::from_secs(60),
+        state.clone(),
+        order::purge_holds,
+    );
+    every(
+        "退款重试",
+        Duration::from_secs(300),
+        state.clone(),
+        payment::resubmit_refunds,
+    );
+    every(
+        "支付对账",
+        Duration::from_secs(3600),
+        state.clone(),
+        payment::reconcile,
+    );
+    every(
+        "会话清理",
+        Duration::from_secs(3600),
+        state.clone(),
+        session::purge,
+    );
+    every("预约提醒", Duration::from_secs(60), state, order::remind);
+}
+
+//按固定间隔执行任务, 上一次未完成时跳过错过的周期
+fn every<F, Fut>(name: &'static str, period: Duration, state: Arc<AppState>, job: F)
+where
+    F: Fn(Arc<AppState>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    info!("定时任务({})已启动, 间隔{}秒", name, period.as_secs());
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            debug!("执行定时任务({})", name);
+            job(state.clone()).await;
+        }
+    });
+}
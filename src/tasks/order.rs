@@ -0,0 +1,60 @@
+use crate::{
+    appstate::AppState,
+    module::order::{group::GroupOp, hold::HoldOp, no_show::NoShowOp, remind::RemindOp, OrderOp},
+};
+use std::sync::Arc;
+use tracing::info;
+
+//已结束的订单, 未签到的先标记为未到场, 其余标记为完成
+pub async fn finish(state: Arc<AppState>) {
+    if let Ok(marked) = NoShowOp::mark::<String>(&state).await {
+        if marked > 0 {
+            info!("{}个订单未到场", marked);
+        }
+    }
+    if let Ok(completed) = OrderOp::complete_finished::<String>(&state).await {
+        if completed > 0 {
+            info!("{}个订单已完成", completed);
+        }
+    }
+}
+
+pub async fn settle(state: Arc<AppState>) {
+    if let Ok((released, forfeited)) = OrderOp::settle_deposits::<String>(&state).await {
+        if released + forfeited > 0 {
+            info!("押金结算: 退还{}单, 扣除{}单", released, forfeited);
+        }
+    }
+}
+
+pub async fn expire_groups(state: Arc<AppState>) {
+    if let Ok(expired) = GroupOp::expire::<String>(&state).await {
+        if expired > 0 {
+            info!("{}个拼单超时取消", expired);
+        }
+    }
+}
+
+pub async fn expire_unpaid(state: Arc<AppState>) {
+    if let Ok(expired) = OrderOp::expire_unpaid::<String>(&state).await {
+        if expired > 0 {
+            info!("{}个订单超时未支付取消", expired);
+        }
+    }
+}
+
+pub async fn purge_holds(state: Arc<AppState>) {
+    if let Ok(purged) = HoldOp::purge::<String>(&state).await {
+        if purged > 0 {
+            info!("清理{}个过期的时段锁定", purged);
+        }
+    }
+}
+
+pub async fn remind(state: Arc<AppState>) {
+    if let Ok(sent) = RemindOp::run::<String>(&state).await {
+        if sent > 0 {
+            info!("发送{}条预约提醒", sent);
+        }
+    }
+}
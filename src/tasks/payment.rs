@@ -0,0 +1,33 @@
+use crate::{
+    appstate::AppState,
+    module::{order::refund::RefundOp, payment::reconcile::ReconcileOp},
+};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+//对账失败的账单日已记录在对账记录中, 下次继续重试
+pub async fn reconcile(state: Arc<AppState>) {
+    match ReconcileOp::daily::<String>(&state).await {
+        Ok(Some(run)) if run.issue_count > 0 => {
+            warn!("{}对账发现{}条差异", run.bill_date, run.issue_count);
+        }
+        Ok(_) => {}
+        Err(err) => {
+            let id = Uuid::new_v4();
+            error!("{} >>>> 对账失败: {:?}", id, err);
+        }
+    }
+}
+
+//未能提交支付渠道的退款按原退款单号重新发起
+pub async fn resubmit_refunds(state: Arc<AppState>) {
+    match RefundOp::resubmit::<String>(&state).await {
+        Ok(submitted) if submitted > 0 => info!("重新发起{}笔退款", submitted),
+        Ok(_) => {}
+        Err(err) => {
+            let id = Uuid::new_v4();
+            error!("{} >>>> 重新发起退款失败: {:?}", id, err);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use crate::{appstate::AppState, module::session::SessionOp};
+use std::sync::Arc;
+use tracing::info;
+
+pub async fn purge(state: Arc<AppState>) {
+    if let Ok(purged) = SessionOp::purge::<String>(&state).await {
+        if purged > 0 {
+            info!("清理{}个过期的登录会话", purged);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::auth::JWTAuthMiddleware;
+use crate::{
+    appstate::AppState,
+    error::HandleErr,
+    module::{
+        audit::{AuditEntry, AuditOp},
+        session::client_ip,
+    },
+};
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, FromRequest, OriginalUri, Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::IntoResponse,
+};
+use std::{net::SocketAddr, sync::Arc};
+
+//记录管理端的写操作, 挂载在身份验证之后:
+//middleware::from_fn_with_state(state, audit)
+pub async fn audit(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let Some(actor_id) = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .map(|e| e.user.user_id)
+        .filter(|_| mutating(req.method()))
+    else {
+        return Ok(next.run(req).await);
+    };
+    let ip = client_ip(req.headers(), addr, &state.cfg.servercfg.trusted_proxies).to_string();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|e| e.path().to_string())
+        .unwrap_or(req.uri().path().to_string());
+    //上传文件不缓存请求体
+    let multipart = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|e| e.to_str().ok())
+        .is_some_and(|e| e.starts_with("multipart/"));
+    let (req, payload_digest) = if multipart {
+        (req, None)
+    } else {
+        //带上原请求的扩展, 以沿用路由设置的请求体大小限制
+        let (parts, body) = req.into_parts();
+        let body = Bytes::from_request(Request::from_parts(parts.clone(), body), &())
+            .await
+            .map_err(|_| HandleErr::BadRequest(-1, "请求体过大"))?;
+        let digest = digest(&body);
+        (Request::from_parts(parts, body.into()), Some(digest))
+    };
+    let response = next.run(req).await;
+    AuditOp::record(
+        AuditEntry {
+            actor_id,
+            ip,
+            method,
+            route,
+            status: response.status().as_u16(),
+            payload_digest,
+        },
+        &state,
+    )
+    .await;
+    Ok(response)
+}
+
+fn mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+//请求体的SHA-256, 十六进制
+fn digest(body: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, body)
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect()
+}
+
+#[test]
+fn test_audit() {
+    assert!(mutating(&Method::POST));
+    assert!(mutating(&Method::DELETE));
+    assert!(mutating(&Method::PUT));
+    assert!(!mutating(&Method::GET));
+    assert!(!mutating(&Method::HEAD));
+    assert_eq!(
+        digest(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+}
@@ -2,23 +2,46 @@ use super::token;
 use crate::{
     appstate::AppState,
     error::HandleErr,
-    module::{db::prelude::Users, user::UserSchema},
+    module::{
+        api_key::ApiKeyOp,
+        db::{prelude::Users, sea_orm_active_enums::StaffScope, users},
+        session::{client_ip, SessionOp},
+        user::{Role, UserSchema},
+    },
 };
 use axum::{
-    extract::{Request, State},
+    body::Bytes,
+    extract::{ConnectInfo, FromRequest, OriginalUri, Request, State},
     http::header,
     middleware::Next,
     response::IntoResponse,
     Extension,
 };
+use ring::hmac;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JWTAuthMiddleware {
     pub user: UserSchema,
+    //当前token对应的会话
+    pub session_id: Uuid,
+    //该会话登录时已通过二次验证
+    pub mfa_verified: bool,
+}
+
+impl JWTAuthMiddleware {
+    //管理的场馆所属的管理员, 员工代其所属的管理员操作
+    pub fn admin_id(&self) -> Uuid {
+        self.user.staff_of.unwrap_or(self.user.user_id)
+    }
 }
 
 pub async fn auth(
@@ -28,8 +51,33 @@ pub async fn auth(
     next: Next,
 ) -> Result<impl IntoResponse, HandleErr<&'static str>> {
     debug!("提取token");
-    //从cookie或者头部取得token
-    let access_token = cookie_jar
+    let access_token = access_token(&cookie_jar, &req).ok_or_else(|| {
+        warn!("未发现token");
+        HandleErr::UnAuthorized
+    })?;
+    let auth = authenticate(&access_token, &state).await?;
+    req.extensions_mut().insert(auth);
+    Ok(next.run(req).await)
+}
+
+//无需登录的接口, 带有效token时同样注入用户信息, 否则按未登录处理
+pub async fn optional_auth(
+    cookie_jar: axum_extra::extract::CookieJar,
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> impl IntoResponse {
+    if let Some(access_token) = access_token(&cookie_jar, &req) {
+        if let Ok(auth) = authenticate(&access_token, &state).await {
+            req.extensions_mut().insert(auth);
+        }
+    }
+    next.run(req).await
+}
+
+//从cookie或者头部取得token
+fn access_token(cookie_jar: &axum_extra::extract::CookieJar, req: &Request) -> Option<String> {
+    cookie_jar
         .get("access_token")
         .map(|e| e.value().to_string())
         .or(req
@@ -37,15 +85,16 @@ pub async fn auth(
             .get(header::AUTHORIZATION)
             .and_then(|e| e.to_str().ok())
             .and_then(|auth_value| auth_value.strip_prefix("Bearer ").map(|e| e.to_string())))
-        .ok_or_else(|| {
-            warn!("未发现token");
-            HandleErr::UnAuthorized
-        })?;
+}
 
+async fn authenticate(
+    access_token: &str,
+    state: &AppState,
+) -> Result<JWTAuthMiddleware, HandleErr<&'static str>> {
     debug!("正在验证token");
     //校验token
-    let user_id =
-        token::verify(&access_token, &state.cfg.tokencfg.access_pubkey).map_err(|err| {
+    let (user_id, session_id) = token::verify(access_token, &state.cfg.tokencfg.access_pubkey)
+        .map_err(|err| {
             warn!("token 验证错误: {}", err.to_string());
             HandleErr::UnAuthorized
         })?;
@@ -65,29 +114,349 @@ pub async fn auth(
             warn!("token所属用户不存在");
             HandleErr::UnAuthorized
         })?;
+    if user.deleted_time.is_some() {
+        warn!("token所属用户({})已注销", user.user_id);
+        return Err(HandleErr::UnAuthorized);
+    }
+    if user.disabled_time.is_some() {
+        warn!("token所属用户({})已被封禁", user.user_id);
+        return Err(HandleErr::UnAuthorized);
+    }
+    //会话已撤销或不存在时token失效
+    let session = SessionOp::check(session_id, user_id, state)
+        .await?
+        .ok_or_else(|| {
+            warn!("token所属会话({})已失效", session_id);
+            HandleErr::UnAuthorized
+        })?;
+
+    let user = user_schema(user);
+    //token签名校验/数据库用户校验, 全部完成用过认证, 为合法用户
+    info!("用户({})身份验证通过", user.user_name);
+    Ok(JWTAuthMiddleware {
+        user,
+        session_id,
+        mfa_verified: session.mfa_verified,
+    })
+}
 
-    let user = UserSchema {
+fn user_schema(user: users::Model) -> UserSchema {
+    UserSchema {
         user_id: user.user_id,
         user_name: user.user_name,
         // pwd: user.user_pwd,
         phone: user.phone,
         is_admin: user.is_admin,
-    };
-    //token签名校验/数据库用户校验, 全部完成用过认证, 为合法用户
-    info!("用户({})身份验证通过", user.user_name);
-    req.extensions_mut().insert(JWTAuthMiddleware { user });
-    Ok(next.run(req).await)
+        is_super: user.is_super,
+        staff_of: user.staff_of,
+        staff_scopes: user.staff_scopes,
+        no_show_count: user.no_show_count,
+        banned_until: user.banned_until,
+        notify_reminder: user.notify_reminder,
+        openid: user.openid,
+        tier: user.tier,
+        tier_until: user.tier_until,
+        totp_enabled: user.totp_enabled,
+    }
+}
+
+//通过API密钥调用的请求, 限流与调用记录按密钥区分
+#[derive(Debug, Clone, Copy)]
+pub struct ApiKeyAuth {
+    pub key_id: Uuid,
 }
 
+//第三方对接用API密钥代替token, 以密钥所属的管理员身份访问只读接口,
+//session_id为密钥id, 这些接口不涉及会话
+pub async fn api_key_auth(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let key = req
+        .headers()
+        .get("x-api-key")
+        .and_then(|e| e.to_str().ok())
+        .map(|e| e.trim().to_string())
+        .ok_or_else(|| {
+            warn!("未发现API密钥");
+            HandleErr::UnAuthorized
+        })?;
+    let (key, admin) = ApiKeyOp::authenticate(&key, &state).await?.ok_or_else(|| {
+        warn!("API密钥无效");
+        HandleErr::UnAuthorized
+    })?;
+    let ip = client_ip(req.headers(), addr, &state.cfg.servercfg.trusted_proxies).to_string();
+    let method = req.method().to_string();
+    //嵌套路由中的uri已去掉前缀, 记录完整路径
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|e| e.path().to_string())
+        .unwrap_or(req.uri().path().to_string());
+    req.extensions_mut()
+        .insert(ApiKeyAuth { key_id: key.key_id });
+    req.extensions_mut().insert(JWTAuthMiddleware {
+        user: user_schema(admin),
+        session_id: key.key_id,
+        mfa_verified: false,
+    });
+    let response = next.run(req).await;
+    ApiKeyOp::record(
+        key.key_id,
+        &method,
+        &path,
+        response.status().as_u16(),
+        &ip,
+        &state,
+    )
+    .await;
+    info!(
+        "API密钥({}) {} {} {}",
+        key.key_prefix,
+        method,
+        path,
+        response.status()
+    );
+    Ok(response)
+}
+
+//员工也可进入 /admin/*, 具体接口再按角色与授权范围校验
 pub async fn admin_auth(
     Extension(auth): Extension<JWTAuthMiddleware>,
     req: Request,
     next: Next,
 ) -> Result<impl IntoResponse, HandleErr<&'static str>> {
-    if auth.user.is_admin {
+    if auth.user.role() >= Role::Staff {
         Ok(next.run(req).await)
     } else {
         warn!("用户({})被拒绝访问 /admin/*", auth.user.user_name);
         Err(HandleErr::BadRequest(401, "权限不足"))
     }
 }
+
+pub async fn super_auth(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    if auth.user.role() == Role::Super {
+        Ok(next.run(req).await)
+    } else {
+        warn!("用户({})被拒绝访问超级管理员接口", auth.user.user_name);
+        Err(HandleErr::BadRequest(401, "权限不足"))
+    }
+}
+
+//要求不低于指定的角色, 挂载在具体路由上:
+//middleware::from_fn_with_state(Role::Admin, role_auth)
+pub async fn role_auth(
+    State(role): State<Role>,
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    if auth.user.role() >= role {
+        Ok(next.run(req).await)
+    } else {
+        warn!(
+            "用户({})的角色({:?})不足, 需要{:?}",
+            auth.user.user_name,
+            auth.user.role(),
+            role
+        );
+        Err(HandleErr::BadRequest(401, "权限不足"))
+    }
+}
+
+//员工须被授予指定的范围, 管理员不受限制:
+//middleware::from_fn_with_state(StaffScope::Orders, scope_auth)
+pub async fn scope_auth(
+    State(scope): State<StaffScope>,
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    if auth.user.has_scope(&scope) {
+        Ok(next.run(req).await)
+    } else {
+        warn!("用户({})未被授予{:?}范围", auth.user.user_name, scope);
+        Err(HandleErr::BadRequest(401, "权限不足"))
+    }
+}
+
+//敏感操作要求已开启二次验证, 且当前会话登录时已校验过验证码
+pub async fn mfa_auth(
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    if !auth.user.totp_enabled {
+        warn!("用户({})未开启二次验证, 拒绝敏感操作", auth.user.user_name);
+        Err(HandleErr::BadRequest(403, "请先开启二次验证"))
+    } else if !auth.mfa_verified {
+        warn!(
+            "用户({})的会话({})未经二次验证, 拒绝敏感操作",
+            auth.user.user_name, auth.session_id
+        );
+        Err(HandleErr::BadRequest(403, "请重新登录并完成二次验证"))
+    } else {
+        Ok(next.run(req).await)
+    }
+}
+
+//签名请求的请求体上限
+const SIGNED_BODY_LIMIT: usize = 1024 * 1024;
+
+//已使用的随机串, (对接方, 随机串) → 首次出现的时间
+#[derive(Debug, Clone, Default)]
+pub struct NonceCache(Arc<Mutex<HashMap<(String, String), Instant>>>);
+
+impl NonceCache {
+    //首次出现时记录并返回true, 保留期内重复出现返回false
+    fn insert(&self, key: (String, String), now: Instant, ttl: Duration) -> bool {
+        let mut nonces = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        nonces.retain(|_, time| now.duration_since(*time) < ttl);
+        if nonces.contains_key(&key) {
+            return false;
+        }
+        nonces.insert(key, now);
+        true
+    }
+}
+
+//服务端对接的请求签名, 挂载在需要的路由上:
+//middleware::from_fn_with_state(state, sign_auth)
+//签名为 HMAC-SHA256(secret, 待签名串) 的十六进制, 待签名串见 canonical
+pub async fn sign_auth(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HandleErr<&'static str>> {
+    let headers = req.headers().clone();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|e| e.to_str().ok())
+            .map(|e| e.trim().to_string())
+            .ok_or(HandleErr::BadRequest(401, "缺少签名参数"))
+    };
+    let app_id = header("x-app-id")?;
+    let timestamp = header("x-timestamp")?;
+    let nonce = header("x-nonce")?;
+    let signature = header("x-signature")?;
+    let secret = state.cfg.signcfg.secret(&app_id).ok_or_else(|| {
+        warn!("未知的对接方({})", app_id);
+        HandleErr::BadRequest(401, "签名校验失败")
+    })?;
+    let max_skew = state.cfg.signcfg.max_skew;
+    let skew = timestamp
+        .parse::<i64>()
+        .map(|e| (chrono::Utc::now().timestamp() - e).abs())
+        .unwrap_or(i64::MAX);
+    if skew > max_skew {
+        warn!("对接方({})的请求时间戳({})超出允许范围", app_id, timestamp);
+        return Err(HandleErr::BadRequest(401, "请求已过期"));
+    }
+    if nonce.is_empty() || nonce.len() > 64 {
+        return Err(HandleErr::BadRequest(401, "随机串无效"));
+    }
+    //嵌套路由中的uri已去掉前缀, 按完整路径签名
+    let uri = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|e| e.0.clone())
+        .unwrap_or(req.uri().clone());
+    let path = uri.path_and_query().map(|e| e.as_str()).unwrap_or("/");
+    let (parts, body) = req.into_parts();
+    let body = Bytes::from_request(Request::new(body), &())
+        .await
+        .ok()
+        .filter(|e| e.len() <= SIGNED_BODY_LIMIT)
+        .ok_or(HandleErr::BadRequest(-1, "请求体过大"))?;
+    let canonical = canonical(parts.method.as_str(), path, &timestamp, &nonce, &body);
+    if !verify_signature(secret, &canonical, &signature) {
+        warn!("对接方({})的请求签名错误", app_id);
+        return Err(HandleErr::BadRequest(401, "签名校验失败"));
+    }
+    //签名通过后再记录随机串, 避免伪造请求占用
+    if !state.nonces.insert(
+        (app_id.clone(), nonce),
+        Instant::now(),
+        Duration::from_secs(max_skew.max(0) as u64 * 2),
+    ) {
+        warn!("对接方({})的请求重放", app_id);
+        return Err(HandleErr::BadRequest(401, "请求重复"));
+    }
+    debug!("对接方({})签名校验通过", app_id);
+    Ok(next.run(Request::from_parts(parts, body.into())).await)
+}
+
+//待签名串: 方法\n路径与查询串\n时间戳\n随机串\n请求体SHA-256的十六进制
+fn canonical(method: &str, path: &str, timestamp: &str, nonce: &str, body: &[u8]) -> String {
+    let body_hash: String = ring::digest::digest(&ring::digest::SHA256, body)
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect();
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path,
+        timestamp,
+        nonce,
+        body_hash
+    )
+}
+
+//常数时间比较
+fn verify_signature(secret: &str, canonical: &str, signature: &str) -> bool {
+    let Some(tag) = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()
+    else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, canonical.as_bytes(), &tag).is_ok()
+}
+
+#[test]
+fn test_sign() {
+    let canonical = canonical(
+        "get",
+        "/api/integration/orders?page=1",
+        "1700000000",
+        "abc",
+        b"",
+    );
+    assert_eq!(
+        canonical,
+        "GET\n/api/integration/orders?page=1\n1700000000\nabc\n\
+         e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+    let signature: String = hmac::sign(&key, canonical.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect();
+    assert!(verify_signature("secret", &canonical, &signature));
+    assert!(verify_signature(
+        "secret",
+        &canonical,
+        &signature.to_uppercase()
+    ));
+    assert!(!verify_signature("other", &canonical, &signature));
+    assert!(!verify_signature("secret", &canonical, &signature[1..]));
+    assert!(!verify_signature("secret", &canonical, "zz"));
+    let nonces = NonceCache::default();
+    let (now, ttl) = (Instant::now(), Duration::from_secs(600));
+    let key = ("app".to_string(), "n1".to_string());
+    assert!(nonces.insert(key.clone(), now, ttl));
+    assert!(!nonces.insert(key.clone(), now + Duration::from_secs(599), ttl));
+    assert!(nonces.insert(("app2".to_string(), "n1".to_string()), now, ttl));
+    assert!(nonces.insert(key, now + Duration::from_secs(1200), ttl));
+}
@@ -0,0 +1,59 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sea_orm::prelude::DateTime;
+use uuid::Uuid;
+
+//订单列表游标, 按(apt_start, order_id)倒序翻页时指向上一页的最后一条
+//对客户端不透明, 只原样传回
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub apt_start: DateTime,
+    pub order_id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}|{}",
+            self.apt_start.timestamp_micros(),
+            self.order_id
+        ))
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let (micros, order_id) = raw.split_once('|')?;
+        Some(Self {
+            apt_start: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            order_id: order_id.parse().ok()?,
+        })
+    }
+}
+
+//按size+1条查询的结果截取一页, 有下一页时返回下一页的游标
+pub fn page<M>(
+    mut rows: Vec<M>,
+    size: u64,
+    key: impl Fn(&M) -> Cursor,
+) -> (Vec<M>, Option<String>) {
+    if rows.len() as u64 <= size {
+        return (rows, None);
+    }
+    rows.truncate(size as usize);
+    let next = rows.last().map(|e| key(e).encode());
+    (rows, next)
+}
+
+#[test]
+fn test_cursor() {
+    let cursor = Cursor {
+        apt_start: chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_micro_opt(18, 30, 0, 123)
+            .unwrap(),
+        order_id: Uuid::new_v4(),
+    };
+    assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    assert_eq!(Cursor::decode("not a cursor"), None);
+    assert_eq!(Cursor::decode(""), None);
+}
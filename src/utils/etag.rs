@@ -0,0 +1,28 @@
+use axum::http::{header, HeaderMap};
+use sha1::{Digest, Sha1};
+
+//由若干版本信息生成强ETag
+pub fn etag(parts: &[&str]) -> String {
+    let mut hasher = Sha1::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    let hex: String = hasher
+        .finalize()
+        .iter()
+        .map(|e| format!("{:02x}", e))
+        .collect();
+    format!("\"{}\"", hex)
+}
+
+//If-None-Match 与当前ETag一致时客户端缓存仍有效
+pub fn fresh(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|e| e.to_str().ok())
+        .flat_map(|e| e.split(','))
+        .map(|e| e.trim())
+        .any(|e| e == "*" || e.trim_start_matches("W/") == etag)
+}
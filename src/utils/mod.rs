@@ -1,5 +1,9 @@
+pub mod audit;
 pub mod auth;
+pub mod cursor;
+pub mod etag;
 pub mod passwd;
+pub mod qrcode;
+pub mod ratelimit;
 pub mod token;
-pub mod validate;
-pub mod ws;
+pub mod totp;
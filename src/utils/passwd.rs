@@ -1,33 +1,61 @@
 use crate::error::HandleErr;
-use pbkdf2::password_hash::{PasswordVerifier, SaltString};
+use argon2::Argon2;
+use pbkdf2::password_hash::{PasswordHash, PasswordHasher, SaltString};
 use tracing::{error, info};
 use uuid::Uuid;
-//密码散列
+
+//当前的散列算法版本, 1为PBKDF2, 2为Argon2id
+pub const HASH_VERSION: i16 = 2;
+
+//密码散列, 使用Argon2id
 pub fn hash_password<T>(pwd: &str) -> Result<String, HandleErr<T>> {
     let salt = SaltString::generate(&mut rand_core::OsRng);
     let password = pwd.as_bytes();
-    let password_hash =
-        pbkdf2::password_hash::PasswordHasher::hash_password(&pbkdf2::Pbkdf2, password, &salt)
-            .map_err(|err| {
-                let id = Uuid::new_v4();
-                error!("{} >>>> {}", id, err.to_string());
-                HandleErr::ServerInnerErr(id)
-            })?
-            .to_string();
+    let password_hash = Argon2::default()
+        .hash_password(password, &salt)
+        .map_err(|err| {
+            let id = Uuid::new_v4();
+            error!("{} >>>> {}", id, err.to_string());
+            HandleErr::ServerInnerErr(id)
+        })?
+        .to_string();
     info!("密码散列成功");
     Ok(password_hash)
 }
 
-//密码校验
+//密码校验, 按散列字符串中的算法标识选择Argon2id或PBKDF2
 pub fn verify_password(pwd: &str, password_hash: &str) -> Result<(), HandleErr<&'static str>> {
-    let parsed_hash = pbkdf2::password_hash::PasswordHash::new(password_hash).map_err(|err| {
+    let parsed_hash = PasswordHash::new(password_hash).map_err(|err| {
         let id = Uuid::new_v4();
         error!("{} >>>> {}", id, err.to_string());
         HandleErr::ServerInnerErr(id)
     })?;
-    pbkdf2::Pbkdf2
-        .verify_password(pwd.as_bytes(), &parsed_hash)
+    parsed_hash
+        .verify_password(&[&Argon2::default(), &pbkdf2::Pbkdf2], pwd.as_bytes())
         .or(Err(HandleErr::BadRequest(-1, "密码校验错误")))?;
     info!("密码检验通过");
     Ok(())
 }
+
+//旧版本的散列在登录成功后重新散列
+pub fn needs_rehash(hash_version: i16) -> bool {
+    hash_version < HASH_VERSION
+}
+
+#[test]
+fn test_password() {
+    let hash = hash_password::<&str>("secret").unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(verify_password("secret", &hash).is_ok());
+    assert!(verify_password("Secret", &hash).is_err());
+    //PBKDF2的旧散列仍可校验
+    let salt = SaltString::generate(&mut rand_core::OsRng);
+    let legacy = pbkdf2::Pbkdf2
+        .hash_password(b"secret", &salt)
+        .unwrap()
+        .to_string();
+    assert!(verify_password("secret", &legacy).is_ok());
+    assert!(verify_password("other", &legacy).is_err());
+    assert!(needs_rehash(1));
+    assert!(!needs_rehash(HASH_VERSION));
+}
@@ -0,0 +1,137 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+//签到码中的有效载荷
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckinClaims {
+    //球场或订单标识
+    sub: Uuid,
+    //用途, 防止与登录token混用
+    kind: String,
+    //过期时间戳(秒)
+    exp: i64,
+}
+
+const KIND: &str = "checkin";
+const TICKET_KIND: &str = "ticket";
+//球场张贴的二维码有效天数, 到期前须重新打印
+pub const COURT_VALID_DAYS: i64 = 365;
+//缓存的球场二维码数量上限
+const MAX_CACHED: usize = 1000;
+
+//球场签到码PNG缓存, 按天重新生成, 保证取得的二维码剩余有效期不少于COURT_VALID_DAYS-1天
+//球场 → (生成日期, PNG)
+type Cached = HashMap<Uuid, (NaiveDate, Vec<u8>)>;
+
+#[derive(Debug, Clone, Default)]
+pub struct QrCache(Arc<RwLock<Cached>>);
+
+impl QrCache {
+    pub fn get(&self, court_id: Uuid, today: NaiveDate) -> Option<Vec<u8>> {
+        self.0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&court_id)
+            .filter(|(date, _)| *date == today)
+            .map(|(_, png)| png.clone())
+    }
+
+    //写入时清理前一天的缓存, 超过上限时全部清空
+    pub fn put(&self, court_id: Uuid, today: NaiveDate, png: Vec<u8>) {
+        let mut cache = self.0.write().unwrap_or_else(|e| e.into_inner());
+        cache.retain(|_, (date, _)| *date == today);
+        if cache.len() >= MAX_CACHED {
+            cache.clear();
+        }
+        cache.insert(court_id, (today, png));
+    }
+}
+
+//签到码扫描结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scanned {
+    //球场张贴的二维码
+    Court(Uuid),
+    //订单电子票
+    Order(Uuid),
+}
+
+fn encode(
+    sub: Uuid,
+    kind: &str,
+    exp: i64,
+    private_key: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = CheckinClaims {
+        sub,
+        kind: kind.to_string(),
+        exp,
+    };
+    let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    jsonwebtoken::encode(
+        &header,
+        &claims,
+        &jsonwebtoken::EncodingKey::from_rsa_pem(private_key.as_bytes())?,
+    )
+    .inspect_err(|err| {
+        warn!("签到码生成错误: {}", err.to_string());
+    })
+}
+
+//球场张贴的二维码, 有效期COURT_VALID_DAYS天
+pub fn sign(court_id: Uuid, private_key: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    let exp = chrono::Utc::now() + chrono::Duration::days(COURT_VALID_DAYS);
+    encode(court_id, KIND, exp.timestamp(), private_key)
+}
+
+//订单电子票, 仅在订单时段内可用于签到, 订单结束后过期
+pub fn sign_ticket(
+    order_id: Uuid,
+    apt_end: chrono::NaiveDateTime,
+    private_key: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(
+        order_id,
+        TICKET_KIND,
+        apt_end.and_utc().timestamp(),
+        private_key,
+    )
+}
+
+//校验签到码, 返回球场或订单id
+pub fn verify(token: &str, public_key: &str) -> Result<Scanned, jsonwebtoken::errors::Error> {
+    let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+    validation.leeway = 0;
+    let decoded: jsonwebtoken::TokenData<CheckinClaims> = jsonwebtoken::decode(
+        token,
+        &jsonwebtoken::DecodingKey::from_rsa_pem(public_key.as_bytes())?,
+        &validation,
+    )?;
+    let scanned = match decoded.claims.kind.as_str() {
+        KIND => Scanned::Court(decoded.claims.sub),
+        TICKET_KIND => Scanned::Order(decoded.claims.sub),
+        _ => return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into()),
+    };
+    info!("签到码检验通过");
+    Ok(scanned)
+}
+
+//将内容编码为二维码PNG图片
+pub fn png(content: &str) -> crate::App::Result<Vec<u8>> {
+    let code =
+        qrcode::QrCode::new(content.as_bytes()).map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(300, 300)
+        .build();
+    let mut buf = Cursor::new(vec![]);
+    image
+        .write_to(&mut buf, image::ImageFormat::Png)
+        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    Ok(buf.into_inner())
+}
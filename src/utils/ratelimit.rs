@@ -0,0 +1,157 @@
+use super::auth::{ApiKeyAuth, JWTAuthMiddleware};
+use crate::{appstate::AppState, module::session::client_ip};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+use uuid::Uuid;
+
+//限流对象, API密钥调用按密钥, 已登录的按用户, 否则按客户端ip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RateKey {
+    Ip(IpAddr),
+    User(Uuid),
+    ApiKey(Uuid),
+}
+
+//超过该数量的限流对象时清理已补满的桶
+const MAX_KEYS: usize = 10000;
+
+//按受信任代理解析出的客户端ip, 由client_addr写入请求扩展
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+//令牌桶限流, 桶容量为max, 每个窗口补满一次, 允许短时突发
+#[derive(Debug)]
+pub struct RateLimiter {
+    max: u32,
+    window: Duration,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    //剩余令牌数与上次补充时间
+    tokens: HashMap<RateKey, (f64, Instant)>,
+    //上次清理时间, 每个窗口最多清理一次
+    purged: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(max: u32, window: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            window,
+            buckets: Mutex::new(Buckets {
+                tokens: HashMap::new(),
+                purged: Instant::now(),
+            }),
+        })
+    }
+
+    //取一个令牌, 不足时返回需要等待的时间
+    fn take(&self, key: RateKey, now: Instant) -> Result<(), Duration> {
+        let rate = self.max as f64 / self.window.as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        //清理已补满的桶, 防止表无限增长
+        if buckets.tokens.len() > MAX_KEYS && now.duration_since(buckets.purged) >= self.window {
+            let window = self.window;
+            buckets
+                .tokens
+                .retain(|_, (_, last)| now.duration_since(*last) < window);
+            buckets.purged = now;
+        }
+        let (tokens, last) = buckets.tokens.entry(key).or_insert((self.max as f64, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(self.max as f64);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - *tokens) / rate))
+        }
+    }
+}
+
+//解析客户端ip写入请求扩展, 限流与日志使用同一个ip
+pub async fn client_addr(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(req.headers(), addr, &state.cfg.servercfg.trusted_proxies);
+    req.extensions_mut().insert(ClientIp(ip));
+    next.run(req).await
+}
+
+pub async fn ratelimit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let extensions = req.extensions();
+    let ip = extensions.get::<ClientIp>().map_or(addr.ip(), |e| e.0);
+    let key = extensions
+        .get::<ApiKeyAuth>()
+        .map(|e| RateKey::ApiKey(e.key_id))
+        .or(extensions
+            .get::<JWTAuthMiddleware>()
+            .map(|e| RateKey::User(e.user.user_id)))
+        .unwrap_or(RateKey::Ip(ip));
+    match limiter.take(key, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            //向上取整, 至少1秒
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            warn!("{:?} 请求过于频繁, {}秒后重试", key, retry_after);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.max(1).to_string())],
+                Json(json!({"code":-1,"msg":"请求过于频繁"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[test]
+fn test_take() {
+    let limiter = RateLimiter::new(2, Duration::from_secs(60));
+    let now = Instant::now();
+    let ip = RateKey::Ip("10.0.0.1".parse().unwrap());
+    assert!(limiter.take(ip, now).is_ok());
+    assert!(limiter.take(ip, now).is_ok());
+    //每30秒补充一个令牌
+    let wait = limiter.take(ip, now).unwrap_err();
+    assert_eq!(wait.as_secs(), 30);
+    assert!(limiter.take(RateKey::User(Uuid::nil()), now).is_ok());
+    assert!(limiter.take(RateKey::ApiKey(Uuid::nil()), now).is_ok());
+    assert!(limiter.take(ip, now + Duration::from_secs(15)).is_err());
+    assert!(limiter.take(ip, now + Duration::from_secs(30)).is_ok());
+    assert!(limiter.take(ip, now + Duration::from_secs(3600)).is_ok());
+    assert!(limiter.take(ip, now + Duration::from_secs(3600)).is_ok());
+    assert!(limiter.take(ip, now + Duration::from_secs(3600)).is_err());
+    //超过数量上限后每个窗口只清理一次
+    let limiter = RateLimiter::new(2, Duration::from_secs(60));
+    let now = Instant::now();
+    for i in 0..=MAX_KEYS as u128 {
+        assert!(limiter.take(RateKey::User(Uuid::from_u128(i)), now).is_ok());
+    }
+    let later = now + Duration::from_secs(120);
+    assert!(limiter.take(ip, later).is_ok());
+    let buckets = limiter.buckets.lock().unwrap();
+    assert_eq!((buckets.tokens.len(), buckets.purged), (1, later));
+}
@@ -4,7 +4,7 @@ use tracing::{info, warn};
 pub struct TokenClaims {
     //用户标识
     pub sub: String,
-    //
+    //会话标识, 撤销会话后token失效
     pub token_uuid: uuid::Uuid,
     //过期时间
     pub exp: i64,
@@ -16,13 +16,14 @@ pub struct TokenClaims {
 
 pub fn create(
     user_id: uuid::Uuid,
+    token_uuid: uuid::Uuid,
     ttl: i64,
     private_key: &str,
 ) -> Result<String, jsonwebtoken::errors::Error> {
     let now = chrono::Utc::now();
     let claims = TokenClaims {
         sub: user_id.to_string(),
-        token_uuid: uuid::Uuid::new_v4(),
+        token_uuid,
         exp: (now + chrono::Duration::minutes(ttl)).timestamp(),
         iat: now.timestamp(),
         nbf: now.timestamp(),
@@ -34,15 +35,18 @@ pub fn create(
         &claims,                                                           //有效载荷
         &jsonwebtoken::EncodingKey::from_rsa_pem(private_key.as_bytes())?, //签名
     )
-    .map_err(|err| {
+    .inspect_err(|err| {
         warn!("token生成错误: {}", err.to_string());
-        err
     })?;
     info!("token生成成功");
     Ok(token)
 }
 
-pub fn verify(token: &str, public_key: &str) -> Result<uuid::Uuid, jsonwebtoken::errors::Error> {
+//返回用户标识与会话标识
+pub fn verify(
+    token: &str,
+    public_key: &str,
+) -> Result<(uuid::Uuid, uuid::Uuid), jsonwebtoken::errors::Error> {
     let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
     let decoded: jsonwebtoken::TokenData<TokenClaims> = jsonwebtoken::decode(
         token,
@@ -51,5 +55,5 @@ pub fn verify(token: &str, public_key: &str) -> Result<uuid::Uuid, jsonwebtoken:
     )?;
     let user_id = uuid::Uuid::parse_str(decoded.claims.sub.as_str()).unwrap();
     info!("token检验通过");
-    Ok(user_id)
+    Ok((user_id, decoded.claims.token_uuid))
 }
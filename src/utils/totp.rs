@@ -0,0 +1,129 @@
+use rand_core::RngCore;
+use ring::hmac;
+
+//RFC 6238, 30秒一个时间步, 6位数字, HMAC-SHA1, 与常见验证器App兼容
+const PERIOD: i64 = 30;
+const DIGITS: u32 = 6;
+//前后各容忍一个时间步的时钟偏差
+const SKEW: i64 = 1;
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+//20字节随机密钥, base32编码后为32个字符
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    encode(&bytes)
+}
+
+//验证器App扫码添加用的otpauth链接
+pub fn uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        escape(issuer),
+        escape(account),
+        secret,
+        escape(issuer),
+        DIGITS,
+        PERIOD
+    )
+}
+
+//校验成功时返回匹配的时间步, 不大于last_step的时间步视为重放
+pub fn verify(secret: &str, code: &str, unix_time: i64, last_step: i64) -> Option<i64> {
+    let key = decode(secret)?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|e| e.is_ascii_digit()) {
+        return None;
+    }
+    let current = unix_time / PERIOD;
+    (current - SKEW..=current + SKEW)
+        .filter(|step| *step > last_step)
+        .find(|step| format!("{:06}", hotp(&key, *step as u64)) == code)
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let tag = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key),
+        &counter.to_be_bytes(),
+    );
+    let digest = tag.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS)
+}
+
+//百分号编码, 保留RFC 3986中的非保留字符
+fn escape(s: &str) -> String {
+    s.bytes()
+        .map(|e| match e {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (e as char).to_string()
+            }
+            _ => format!("%{:02X}", e),
+        })
+        .collect()
+}
+
+//base32, 不带填充
+fn encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+//忽略大小写、空格与填充
+fn decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in s.chars().filter(|e| !e.is_whitespace() && *e != '=') {
+        let value = ALPHABET
+            .iter()
+            .position(|e| *e as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+#[test]
+fn test_totp() {
+    //RFC 6238附录B的SHA1测试向量, 取后6位
+    let secret = encode(b"12345678901234567890");
+    assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    assert_eq!(
+        decode(&secret.to_lowercase()).unwrap(),
+        b"12345678901234567890"
+    );
+    assert_eq!(verify(&secret, "287082", 59, 0), Some(1));
+    assert_eq!(verify(&secret, "081804", 1111111109, 0), Some(37037036));
+    //相邻时间步可通过, 已使用的时间步不能重放
+    assert_eq!(verify(&secret, "287082", 89, 0), Some(1));
+    assert_eq!(verify(&secret, "287082", 59, 1), None);
+    assert_eq!(verify(&secret, "287083", 59, 0), None);
+    assert_eq!(verify(&secret, "28708", 59, 0), None);
+    assert_eq!(generate_secret().len(), 32);
+    assert_eq!(
+        uri("球场", "a b", "ABC"),
+        "otpauth://totp/%E7%90%83%E5%9C%BA:a%20b?secret=ABC&issuer=%E7%90%83%E5%9C%BA&algorithm=SHA1&digits=6&period=30"
+    );
+}
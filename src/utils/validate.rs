@@ -1,3 +0,0 @@
-pub fn isPhoneNum() -> bool {
-    todo!()
-}